@@ -0,0 +1,151 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Benchmarks of control file parsing.
+//!
+//! `line_parser` feeds lines to a `ControlFileParser`, which copies every line. The
+//! paragraph readers buffer a paragraph and parse it in place. `slice_reader` borrows
+//! everything from the input.
+//! `slice_reader_parallel_N` parses with N threads, one per available core.
+//!
+//! `cache_load` decodes the same packages from a `package_list_cache` cache.
+
+use {
+    criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput},
+    debian_packaging::{
+        binary_package_control::BinaryPackageControlFile,
+        binary_package_list::BinaryPackageList,
+        control::{
+            ControlFileParser, ControlParagraphAsyncReader, ControlParagraphReader,
+            ControlParagraphSliceReader,
+        },
+        io::ContentDigest,
+        package_list_cache::{write_binary_package_list, PackageListCache},
+    },
+};
+
+/// Generate a `Packages` file resembling those in Debian repositories.
+fn packages_file(count: usize) -> String {
+    let mut res = String::new();
+
+    for i in 0..count {
+        res.push_str(&format!(
+            "Package: package-{i}\n\
+             Source: source-{src}\n\
+             Version: 1.{i}-1\n\
+             Installed-Size: {size}\n\
+             Maintainer: Jane Doe <jane@example.com>\n\
+             Architecture: amd64\n\
+             Depends: libc6 (>= 2.34), libgcc-s1 (>= 3.0), package-{dep} (= 1.{dep}-1)\n\
+             Description: package number {i}\n \
+             This package exists for benchmarking. Its description is long enough to\n \
+             span multiple lines, like most real descriptions.\n \
+             .\n \
+             It has multiple paragraphs.\n\
+             Homepage: https://example.com/package-{i}\n\
+             Description-md5: 0123456789abcdef0123456789abcdef\n\
+             Section: libs\n\
+             Priority: optional\n\
+             Filename: pool/main/p/source-{src}/package-{i}_1.{i}-1_amd64.deb\n\
+             Size: {size}\n\
+             MD5sum: 0123456789abcdef0123456789abcdef\n\
+             SHA256: 0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef\n\
+             \n",
+            src = i / 4,
+            size = i * 17 % 100_000,
+            dep = i.saturating_sub(1),
+        ));
+    }
+
+    res
+}
+
+fn parse(c: &mut Criterion) {
+    let data = packages_file(10_000);
+
+    let mut group = c.benchmark_group("packages");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+
+    group.bench_function("line_parser", |b| {
+        b.iter(|| {
+            let mut parser = ControlFileParser::default();
+            let mut count = 0;
+            for line in data.split_inclusive('\n') {
+                count += parser.write_line(line).unwrap().is_some() as usize;
+            }
+            count += parser.finish().unwrap().is_some() as usize;
+
+            black_box(count)
+        })
+    });
+
+    group.bench_function("paragraph_reader", |b| {
+        b.iter(|| black_box(ControlParagraphReader::new(data.as_bytes()).count()))
+    });
+
+    group.bench_function("async_paragraph_reader", |b| {
+        b.iter(|| {
+            futures::executor::block_on(async {
+                let mut reader =
+                    ControlParagraphAsyncReader::new(futures::io::Cursor::new(data.as_bytes()));
+                let mut count = 0;
+                while reader.read_paragraph().await.unwrap().is_some() {
+                    count += 1;
+                }
+
+                black_box(count)
+            })
+        })
+    });
+
+    group.bench_function("slice_reader", |b| {
+        b.iter(|| black_box(ControlParagraphSliceReader::new(&data).count()))
+    });
+
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    group.bench_function(format!("slice_reader_parallel_{}", threads), |b| {
+        b.iter(|| {
+            black_box(
+                ControlParagraphSliceReader::new(&data)
+                    .read_all_parallel(threads, |p| p.into_interned())
+                    .unwrap(),
+            )
+        })
+    });
+
+    group.bench_function("slice_reader_into_owned", |b| {
+        b.iter(|| {
+            black_box(
+                ControlParagraphSliceReader::new(&data)
+                    .map(|p| p.unwrap().into_owned())
+                    .collect::<Vec<_>>(),
+            )
+        })
+    });
+
+    let mut packages = BinaryPackageList::default();
+    packages.extend(
+        ControlParagraphSliceReader::new(&data)
+            .map(|p| BinaryPackageControlFile::from(p.unwrap().into_owned())),
+    );
+    let mut cache = vec![];
+    write_binary_package_list(
+        &mut cache,
+        &ContentDigest::sha256_hex(&"0".repeat(64)).unwrap(),
+        &packages,
+    )
+    .unwrap();
+
+    group.bench_function("cache_load", |b| {
+        b.iter(|| {
+            let cache = PackageListCache::from_bytes(cache.clone()).unwrap();
+            black_box(cache.binary_packages().unwrap().len())
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,619 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! apt preferences and pin priorities.
+
+apt assigns every available version of a package a *pin priority* and installs the version
+with the highest priority. Priorities are influenced by `/etc/apt/preferences` and files in
+`/etc/apt/preferences.d`, which consist of stanzas like:
+
+```text
+Package: *
+Pin: release a=bookworm-backports
+Pin-Priority: 200
+```
+
+[AptPreferences] parses these files. [AptPreferences::pin_priority()] computes the priority
+apt assigns a [CandidateVersion] and [AptPreferences::select_candidate()] predicts which
+version apt would select. See `apt_preferences(5)` for the semantics being modeled.
+*/
+
+use {
+    crate::{
+        control::{ControlFile, ControlParagraph},
+        error::{DebianError, Result},
+        package_version::PackageVersion,
+        repository::release::ReleaseFile,
+    },
+    regex::Regex,
+    std::path::Path,
+};
+
+/// Priority of versions in the target release.
+pub const DEFAULT_RELEASE_PRIORITY: i32 = 990;
+
+/// Priority of versions not otherwise pinned.
+pub const DEFAULT_PRIORITY: i32 = 500;
+
+/// Priority of installed versions and of `NotAutomatic` releases with `ButAutomaticUpgrades`.
+pub const INSTALLED_PRIORITY: i32 = 100;
+
+/// Priority of versions in `NotAutomatic` releases.
+pub const NOT_AUTOMATIC_PRIORITY: i32 = 1;
+
+/// Minimum priority for a version to be selected over a newer installed version.
+pub const DOWNGRADE_PRIORITY: i32 = 1000;
+
+/// A pattern matching a string value.
+///
+/// Values enclosed in `/` are unanchored regular expressions. Other values are glob patterns.
+#[derive(Clone, Debug)]
+pub enum ValuePattern {
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl ValuePattern {
+    /// Parse a pattern from its string form.
+    pub fn parse(s: &str) -> Result<Self> {
+        if s.len() > 1 && s.starts_with('/') && s.ends_with('/') {
+            Ok(Self::Regex(Regex::new(&s[1..s.len() - 1])?))
+        } else {
+            Ok(Self::Glob(glob::Pattern::new(s)?))
+        }
+    }
+
+    /// Whether a value matches this pattern.
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Self::Glob(pattern) => pattern.matches(value),
+            Self::Regex(re) => re.is_match(value),
+        }
+    }
+}
+
+/// A term of the `Package` field of a preferences stanza.
+#[derive(Clone, Debug)]
+pub enum PackageMatcher {
+    /// Matches binary package names.
+    Binary(ValuePattern),
+    /// Matches binary packages built from matching source packages (`src:name`).
+    Source(ValuePattern),
+}
+
+impl PackageMatcher {
+    /// Parse a single term.
+    pub fn parse(s: &str) -> Result<Self> {
+        if let Some(source) = s.strip_prefix("src:") {
+            Ok(Self::Source(ValuePattern::parse(source)?))
+        } else {
+            Ok(Self::Binary(ValuePattern::parse(s)?))
+        }
+    }
+
+    /// Whether a candidate is matched.
+    pub fn matches(&self, candidate: &CandidateVersion) -> bool {
+        match self {
+            Self::Binary(pattern) => pattern.matches(&candidate.package),
+            Self::Source(pattern) => pattern.matches(
+                candidate
+                    .source_package
+                    .as_deref()
+                    .unwrap_or(&candidate.package),
+            ),
+        }
+    }
+}
+
+/// A property of a release matched by `Pin: release`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReleaseProperty {
+    /// `a=`, the archive or suite.
+    Archive,
+    /// `n=`, the codename.
+    Codename,
+    /// `v=`, the release version.
+    Version,
+    /// `c=`, the component.
+    Component,
+    /// `o=`, the `Origin` field.
+    Origin,
+    /// `l=`, the `Label` field.
+    Label,
+    /// `b=`, the architecture.
+    Architecture,
+}
+
+impl ReleaseProperty {
+    /// Resolve the property from its `Pin: release` key.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "a" => Some(Self::Archive),
+            "n" => Some(Self::Codename),
+            "v" => Some(Self::Version),
+            "c" => Some(Self::Component),
+            "o" => Some(Self::Origin),
+            "l" => Some(Self::Label),
+            "b" => Some(Self::Architecture),
+            _ => None,
+        }
+    }
+
+    fn value<'a>(&self, origin: &'a PackageOrigin) -> Option<&'a str> {
+        match self {
+            Self::Archive => origin.archive.as_deref(),
+            Self::Codename => origin.codename.as_deref(),
+            Self::Version => origin.version.as_deref(),
+            Self::Component => origin.component.as_deref(),
+            Self::Origin => origin.origin.as_deref(),
+            Self::Label => origin.label.as_deref(),
+            Self::Architecture => origin.architecture.as_deref(),
+        }
+    }
+}
+
+/// What a preferences stanza pins.
+#[derive(Clone, Debug)]
+pub enum PinTarget {
+    /// `Pin: version`, matching package versions.
+    Version(ValuePattern),
+    /// `Pin: release`, matching release properties. All properties must match.
+    Release(Vec<(ReleaseProperty, ValuePattern)>),
+    /// `Pin: origin`, matching the host a release was obtained from.
+    Origin(ValuePattern),
+}
+
+impl PinTarget {
+    /// Parse the value of a `Pin` field.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (kind, value) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let value = value.trim();
+
+        match kind {
+            "version" => Ok(Self::Version(ValuePattern::parse(value)?)),
+            "origin" => Ok(Self::Origin(ValuePattern::parse(value.trim_matches('"'))?)),
+            "release" => {
+                let mut properties = vec![];
+
+                for term in value.split(',').map(|t| t.trim()).filter(|t| !t.is_empty()) {
+                    let (property, value) = match term.split_once('=') {
+                        Some((key, value)) => (
+                            ReleaseProperty::from_key(key.trim()).ok_or_else(|| {
+                                DebianError::AptPreferencesInvalid(format!(
+                                    "unknown release property: {}",
+                                    key
+                                ))
+                            })?,
+                            value.trim(),
+                        ),
+                        // A bare value is a version if it looks like one, else an archive.
+                        None if term.starts_with(|c: char| c.is_ascii_digit()) => {
+                            (ReleaseProperty::Version, term)
+                        }
+                        None => (ReleaseProperty::Archive, term),
+                    };
+
+                    properties.push((property, ValuePattern::parse(value.trim_matches('"'))?));
+                }
+
+                Ok(Self::Release(properties))
+            }
+            _ => Err(DebianError::AptPreferencesInvalid(format!(
+                "unknown pin type: {}",
+                s
+            ))),
+        }
+    }
+
+    /// Whether a source of a package version is matched.
+    ///
+    /// [Self::Version] pins don't match origins.
+    pub fn matches_origin(&self, origin: &PackageOrigin) -> bool {
+        match self {
+            Self::Version(_) => false,
+            Self::Release(properties) => properties.iter().all(|(property, pattern)| {
+                property
+                    .value(origin)
+                    .is_some_and(|value| pattern.matches(value))
+            }),
+            Self::Origin(pattern) => pattern.matches(origin.site.as_deref().unwrap_or_default()),
+        }
+    }
+
+    /// Whether a package version, or any of its sources, is matched.
+    pub fn matches(&self, candidate: &CandidateVersion) -> bool {
+        match self {
+            Self::Version(pattern) => pattern.matches(&candidate.version.to_string()),
+            _ => candidate.origins.iter().any(|o| self.matches_origin(o)),
+        }
+    }
+}
+
+/// A stanza in an apt preferences file.
+#[derive(Clone, Debug)]
+pub struct PinStanza {
+    /// Terms of the `Package` field.
+    pub packages: Vec<PackageMatcher>,
+    /// The `Pin` field.
+    pub pin: PinTarget,
+    /// The `Pin-Priority` field.
+    pub priority: i32,
+}
+
+impl PinStanza {
+    /// Construct an instance from a control paragraph.
+    pub fn from_paragraph(para: &ControlParagraph) -> Result<Self> {
+        let field = |name: &str| {
+            para.field_str(name).ok_or_else(|| {
+                DebianError::AptPreferencesInvalid(format!("stanza lacks {} field", name))
+            })
+        };
+
+        let packages = field("Package")?
+            .split_ascii_whitespace()
+            .map(PackageMatcher::parse)
+            .collect::<Result<Vec<_>>>()?;
+        let pin = PinTarget::parse(field("Pin")?)?;
+        let priority = field("Pin-Priority")?.trim().parse::<i32>()?;
+
+        Ok(Self {
+            packages,
+            pin,
+            priority,
+        })
+    }
+
+    /// Whether this is a general stanza applying to all packages (`Package: *`).
+    ///
+    /// Other stanzas are specific and take precedence over general stanzas.
+    pub fn is_general(&self) -> bool {
+        matches!(&self.packages[..], [PackageMatcher::Binary(ValuePattern::Glob(p))] if p.as_str() == "*")
+    }
+
+    /// Whether the `Package` field matches a candidate.
+    pub fn matches_package(&self, candidate: &CandidateVersion) -> bool {
+        self.packages.iter().any(|m| m.matches(candidate))
+    }
+}
+
+/// A source a package version is available from.
+#[derive(Clone, Debug, Default)]
+pub struct PackageOrigin {
+    /// The archive or suite, e.g. `stable`.
+    pub archive: Option<String>,
+    /// The release codename, e.g. `bookworm`.
+    pub codename: Option<String>,
+    /// The release version, e.g. `12.5`.
+    pub version: Option<String>,
+    /// The repository component, e.g. `main`.
+    pub component: Option<String>,
+    /// The `Origin` field of the release.
+    pub origin: Option<String>,
+    /// The `Label` field of the release.
+    pub label: Option<String>,
+    /// The architecture of the packages index.
+    pub architecture: Option<String>,
+    /// The host the release is obtained from, e.g. `deb.debian.org`.
+    pub site: Option<String>,
+    /// Whether the release has `NotAutomatic: yes`.
+    pub not_automatic: bool,
+    /// Whether the release has `ButAutomaticUpgrades: yes`.
+    pub but_automatic_upgrades: bool,
+}
+
+impl PackageOrigin {
+    /// Describe a packages index of a release.
+    pub fn from_release_file(
+        release: &ReleaseFile,
+        component: &str,
+        architecture: &str,
+        site: &str,
+    ) -> Self {
+        Self {
+            archive: release.suite().map(|s| s.to_string()),
+            codename: release.codename().map(|s| s.to_string()),
+            version: release.version().map(|s| s.to_string()),
+            component: Some(component.to_string()),
+            origin: release.origin().map(|s| s.to_string()),
+            label: release.label().map(|s| s.to_string()),
+            architecture: Some(architecture.to_string()),
+            site: Some(site.to_string()),
+            not_automatic: release.not_automatic().unwrap_or_default(),
+            but_automatic_upgrades: release.but_automatic_upgrades().unwrap_or_default(),
+        }
+    }
+}
+
+/// An available version of a package.
+#[derive(Clone, Debug)]
+pub struct CandidateVersion {
+    /// Binary package name.
+    pub package: String,
+    /// Source package name, if different from the binary package name.
+    pub source_package: Option<String>,
+    /// The package version.
+    pub version: PackageVersion,
+    /// Sources the version is available from.
+    pub origins: Vec<PackageOrigin>,
+    /// Whether this version is installed.
+    pub installed: bool,
+}
+
+/// Parsed apt preferences.
+#[derive(Clone, Debug, Default)]
+pub struct AptPreferences {
+    stanzas: Vec<PinStanza>,
+    default_release: Option<String>,
+}
+
+impl AptPreferences {
+    /// Parse preferences from a string.
+    pub fn parse_str(s: &str) -> Result<Self> {
+        let mut res = Self::default();
+        res.load_str(s)?;
+
+        Ok(res)
+    }
+
+    /// Load the preferences of an apt configuration directory, usually `/etc/apt`.
+    ///
+    /// `preferences` is loaded followed by files in `preferences.d` in lexical order.
+    /// Like apt, files in `preferences.d` are only loaded if they have no extension or a
+    /// `.pref` extension and their names consist of alphanumerics, `_`, `-` and `.`.
+    pub fn load_directory(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut res = Self::default();
+
+        let preferences = path.join("preferences");
+        if preferences.exists() {
+            res.load_str(&std::fs::read_to_string(preferences)?)?;
+        }
+
+        let fragments = path.join("preferences.d");
+        if fragments.is_dir() {
+            let mut paths = std::fs::read_dir(fragments)?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<Result<Vec<_>>>()?;
+            paths.sort();
+
+            for path in paths {
+                let name = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or_default();
+
+                if name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+                    && (!name.contains('.') || name.ends_with(".pref"))
+                    && path.is_file()
+                {
+                    res.load_str(&std::fs::read_to_string(&path)?)?;
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Parse stanzas from a string and append them.
+    ///
+    /// Comment lines and `Explanation` fields are ignored.
+    pub fn load_str(&mut self, s: &str) -> Result<()> {
+        let s = s
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        for para in ControlFile::parse_str(&s)?.paragraphs() {
+            self.stanzas.push(PinStanza::from_paragraph(para)?);
+        }
+
+        Ok(())
+    }
+
+    /// Set the target release (`APT::Default-Release`).
+    ///
+    /// Versions from releases whose archive or codename matches receive priority 990.
+    pub fn set_default_release(&mut self, release: Option<String>) {
+        self.default_release = release;
+    }
+
+    /// Loaded stanzas, in evaluation order.
+    pub fn stanzas(&self) -> &[PinStanza] {
+        &self.stanzas
+    }
+
+    /// The priority of a package version from a single source, ignoring specific pins.
+    pub fn origin_priority(&self, origin: &PackageOrigin) -> i32 {
+        if let Some(stanza) = self
+            .stanzas
+            .iter()
+            .find(|s| s.is_general() && s.pin.matches_origin(origin))
+        {
+            return stanza.priority;
+        }
+
+        if let Some(release) = &self.default_release {
+            if origin.archive.as_ref() == Some(release) || origin.codename.as_ref() == Some(release)
+            {
+                return DEFAULT_RELEASE_PRIORITY;
+            }
+        }
+
+        match (origin.not_automatic, origin.but_automatic_upgrades) {
+            (true, true) => INSTALLED_PRIORITY,
+            (true, false) => NOT_AUTOMATIC_PRIORITY,
+            _ => DEFAULT_PRIORITY,
+        }
+    }
+
+    /// The priority apt assigns a package version.
+    ///
+    /// The first specific stanza matching the package and version or one of its sources
+    /// determines the priority. Otherwise the priority is the highest of its sources, as
+    /// determined by [Self::origin_priority()], and [INSTALLED_PRIORITY] if installed.
+    pub fn pin_priority(&self, candidate: &CandidateVersion) -> i32 {
+        if let Some(stanza) = self
+            .stanzas
+            .iter()
+            .find(|s| !s.is_general() && s.matches_package(candidate) && s.pin.matches(candidate))
+        {
+            return stanza.priority;
+        }
+
+        let general_version = self.stanzas.iter().find(|s| {
+            s.is_general() && matches!(s.pin, PinTarget::Version(_)) && s.pin.matches(candidate)
+        });
+        if let Some(stanza) = general_version {
+            return stanza.priority;
+        }
+
+        candidate
+            .origins
+            .iter()
+            .map(|o| self.origin_priority(o))
+            .chain(candidate.installed.then_some(INSTALLED_PRIORITY))
+            .max()
+            .unwrap_or(i32::MIN)
+    }
+
+    /// Predict which version of a package apt would select.
+    ///
+    /// `candidates` are the available versions of a single package. The version with the
+    /// highest priority is selected, preferring higher versions on ties. Versions with
+    /// negative priorities are never selected and versions older than the installed
+    /// version require a priority of at least [DOWNGRADE_PRIORITY].
+    pub fn select_candidate<'a>(
+        &self,
+        candidates: &'a [CandidateVersion],
+    ) -> Option<&'a CandidateVersion> {
+        let installed = candidates.iter().find(|c| c.installed).map(|c| &c.version);
+
+        let mut best: Option<(i32, &CandidateVersion)> = None;
+
+        for candidate in candidates {
+            let priority = self.pin_priority(candidate);
+
+            if priority < 0
+                || installed
+                    .is_some_and(|v| &candidate.version < v && priority < DOWNGRADE_PRIORITY)
+            {
+                continue;
+            }
+
+            if best.map_or(true, |(p, c)| {
+                priority > p || (priority == p && candidate.version > c.version)
+            }) {
+                best = Some((priority, candidate));
+            }
+        }
+
+        best.map(|(_, c)| c)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn origin(archive: &str, not_automatic: bool) -> PackageOrigin {
+        PackageOrigin {
+            archive: Some(archive.to_string()),
+            origin: Some("Debian".to_string()),
+            component: Some("main".to_string()),
+            site: Some("deb.debian.org".to_string()),
+            not_automatic,
+            ..Default::default()
+        }
+    }
+
+    fn candidate(package: &str, version: &str, origins: Vec<PackageOrigin>) -> CandidateVersion {
+        CandidateVersion {
+            package: package.to_string(),
+            source_package: None,
+            version: PackageVersion::parse(version).unwrap(),
+            origins,
+            installed: false,
+        }
+    }
+
+    #[test]
+    fn pin_priorities() -> Result<()> {
+        let mut prefs = AptPreferences::parse_str(
+            "# Prefer backports for the kernel.\n\
+             Explanation: kernel\n\
+             Package: linux-image-* src:linux\n\
+             Pin: release a=bookworm-backports\n\
+             Pin-Priority: 600\n\
+             \n\
+             Package: *\n\
+             Pin: release o=Debian, a=testing\n\
+             Pin-Priority: -10\n\
+             \n\
+             Package: /^curl/\n\
+             Pin: version 7.*\n\
+             Pin-Priority: 1001\n",
+        )?;
+        assert_eq!(prefs.stanzas().len(), 3);
+        assert!(prefs.stanzas()[1].is_general());
+
+        let stable = origin("stable", false);
+        let backports = origin("bookworm-backports", true);
+        let testing = origin("testing", false);
+
+        let kernel = vec![
+            candidate("linux-image-amd64", "6.1", vec![stable.clone()]),
+            candidate("linux-image-amd64", "6.6", vec![backports.clone()]),
+            candidate("linux-image-amd64", "6.7", vec![testing.clone()]),
+        ];
+        assert_eq!(prefs.pin_priority(&kernel[0]), 500);
+        assert_eq!(prefs.pin_priority(&kernel[1]), 600);
+        assert_eq!(prefs.pin_priority(&kernel[2]), -10);
+        assert_eq!(
+            prefs.select_candidate(&kernel).unwrap().version.to_string(),
+            "6.6"
+        );
+
+        let mut vim = vec![
+            candidate("vim", "9.0", vec![stable.clone()]),
+            candidate("vim", "9.1", vec![backports.clone()]),
+        ];
+        assert_eq!(prefs.pin_priority(&vim[1]), NOT_AUTOMATIC_PRIORITY);
+        assert_eq!(
+            prefs.select_candidate(&vim).unwrap().version.to_string(),
+            "9.0"
+        );
+        vim[1].installed = true;
+        assert_eq!(prefs.pin_priority(&vim[1]), INSTALLED_PRIORITY);
+        assert_eq!(
+            prefs.select_candidate(&vim).unwrap().version.to_string(),
+            "9.1"
+        );
+
+        let curl = vec![
+            CandidateVersion {
+                installed: true,
+                ..candidate("curl", "8.0", vec![stable.clone()])
+            },
+            candidate("curl", "7.88", vec![stable.clone()]),
+        ];
+        assert_eq!(
+            prefs.select_candidate(&curl).unwrap().version.to_string(),
+            "7.88"
+        );
+
+        prefs.set_default_release(Some("testing".to_string()));
+        assert_eq!(prefs.pin_priority(&kernel[2]), -10);
+        assert_eq!(prefs.origin_priority(&testing), -10);
+        prefs.set_default_release(Some("stable".to_string()));
+        assert_eq!(prefs.pin_priority(&vim[0]), DEFAULT_RELEASE_PRIORITY);
+
+        assert!(AptPreferences::parse_str("Package: *\nPin: bogus\nPin-Priority: 1\n").is_err());
+
+        Ok(())
+    }
+}
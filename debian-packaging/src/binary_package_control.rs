@@ -7,13 +7,22 @@
 use {
     crate::{
         control::ControlParagraph,
-        dependency::{DependencyList, PackageDependencyFields},
+        deb::PackageType,
+        dependency::{BinaryDependency, DependencyList, PackageDependencyFields, PackageRelations},
         error::{DebianError, Result},
         io::ContentDigest,
+        maintainer::Contact,
         package_version::PackageVersion,
-        repository::{builder::DebPackageReference, release::ChecksumType},
+        repository::{
+            builder::{DebPackageReference, PoolLayout},
+            release::ChecksumType,
+        },
+    },
+    std::{
+        fmt::{Display, Formatter},
+        ops::{Deref, DerefMut},
+        str::FromStr,
     },
-    std::ops::{Deref, DerefMut},
 };
 
 /// A Debian binary package control file/paragraph.
@@ -33,7 +42,14 @@ use {
 /// return [Result] and will error if a field is not present. Non-mandatory fields
 /// return [Option]. This enforcement can be bypassed by calling
 /// [ControlParagraph::field()].
+///
+/// With the `serde` feature, instances serialize like their [ControlParagraph].
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(transparent)
+)]
 pub struct BinaryPackageControlFile<'a> {
     paragraph: ControlParagraph<'a>,
 }
@@ -90,16 +106,81 @@ impl<'a> BinaryPackageControlFile<'a> {
         self.required_field_str("Maintainer")
     }
 
+    /// The parsed `Maintainer` field.
+    pub fn typed_maintainer(&self) -> Result<Contact> {
+        Contact::parse(self.maintainer()?)
+    }
+
     /// The `Description` field.
     pub fn description(&self) -> Result<&str> {
         self.required_field_str("Description")
     }
 
+    /// The `Description` field, parsed to a [PackageDescription].
+    pub fn typed_description(&self) -> Result<PackageDescription> {
+        Ok(PackageDescription::parse(self.description()?))
+    }
+
+    /// The synopsis of the `Description` field, its first line.
+    pub fn synopsis(&self) -> Result<&str> {
+        Ok(self.description()?.lines().next().unwrap_or_default())
+    }
+
     /// The `Source` field.
     pub fn source(&self) -> Option<&str> {
         self.field_str("Source")
     }
 
+    /// The name of the source package this binary package was built from.
+    ///
+    /// This is the `Source` field without any version in parentheses (e.g. `foo (1.2-1)`
+    /// yields `foo`), or the `Package` field if there is no `Source` field.
+    pub fn source_package_name(&self) -> Result<&str> {
+        match self.source() {
+            Some(source) => Ok(source
+                .split_once('(')
+                .map_or(source, |(name, _)| name)
+                .trim()),
+            None => self.package(),
+        }
+    }
+
+    /// The conventional filename of this package.
+    ///
+    /// This is `<package>_<version>_<architecture>` (with any version epoch removed)
+    /// followed by `.udeb` for `Package-Type: udeb` packages and `.deb` otherwise.
+    pub fn conventional_filename(&self) -> Result<String> {
+        let version = self.version_str()?;
+        let version = version.split_once(':').map_or(version, |(_, v)| v);
+
+        let package_type = if self.package_type() == Some(PackageType::Udeb.as_str()) {
+            PackageType::Udeb
+        } else {
+            PackageType::Deb
+        };
+
+        Ok(format!(
+            "{}_{}_{}{}",
+            self.package()?,
+            version,
+            self.architecture()?,
+            package_type.extension()
+        ))
+    }
+
+    /// The path of this package in a repository pool, as used by the `Filename` field.
+    ///
+    /// The path is derived from [Self::source_package_name()] and
+    /// [Self::conventional_filename()] according to `layout`. e.g.
+    /// `pool/main/libz/libzstd/libzstd1_1.4.8+dfsg-2.1_amd64.deb`.
+    pub fn pool_path(&self, layout: PoolLayout, component: &str) -> Result<String> {
+        Ok(layout.path(
+            component,
+            self.source_package_name()?,
+            &self.conventional_filename()?,
+        ))
+    }
+
     /// The `Section` field.
     pub fn section(&self) -> Option<&str> {
         self.field_str("Section")
@@ -110,11 +191,78 @@ impl<'a> BinaryPackageControlFile<'a> {
         self.field_str("Priority")
     }
 
+    /// The `Priority` field, parsed to a [Priority].
+    pub fn typed_priority(&self) -> Option<Priority> {
+        self.priority().map(Priority::from)
+    }
+
+    /// The section of the `Section` field, parsed to a [Section].
+    ///
+    /// Any archive area prefix is removed. See [Self::archive_area()].
+    pub fn typed_section(&self) -> Option<Section> {
+        self.section().map(|section| {
+            Section::from(
+                section
+                    .split_once('/')
+                    .map_or(section, |(_, section)| section),
+            )
+        })
+    }
+
+    /// The archive area prefix of the `Section` field, e.g. `contrib` for `contrib/net`.
+    ///
+    /// `None` if there is no `Section` field or it lacks an archive area.
+    pub fn archive_area(&self) -> Option<&str> {
+        self.section()
+            .and_then(|section| section.split_once('/'))
+            .map(|(area, _)| area)
+    }
+
     /// The `Essential` field.
     pub fn essential(&self) -> Option<&str> {
         self.field_str("Essential")
     }
 
+    /// The `Essential` field, parsed to a [bool].
+    ///
+    /// Essential packages must never be removed. Values other than `yes` and `no` yield
+    /// `None`. Use [ControlParagraph::field_yes_no()] to detect them.
+    ///
+    /// See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#essential>.
+    pub fn typed_essential(&self) -> Option<bool> {
+        self.field_yes_no("Essential").and_then(|v| v.ok())
+    }
+
+    /// The `Protected` field.
+    pub fn protected(&self) -> Option<&str> {
+        self.field_str("Protected")
+    }
+
+    /// The `Protected` field, parsed to a [bool].
+    ///
+    /// Protected packages are needed to boot the system and must not be removed without
+    /// explicit confirmation. Unlike essential packages, they are not implicitly installed.
+    pub fn typed_protected(&self) -> Option<bool> {
+        self.field_yes_no("Protected").and_then(|v| v.ok())
+    }
+
+    /// The `Build-Essential` field.
+    pub fn build_essential(&self) -> Option<&str> {
+        self.field_str("Build-Essential")
+    }
+
+    /// The `Build-Essential` field, parsed to a [bool].
+    ///
+    /// Build-essential packages are implicitly part of every package's build dependencies.
+    pub fn typed_build_essential(&self) -> Option<bool> {
+        self.field_yes_no("Build-Essential").and_then(|v| v.ok())
+    }
+
+    /// Whether the package is `Essential` or `Protected` and so must not be removed.
+    pub fn is_removal_protected(&self) -> bool {
+        self.typed_essential() == Some(true) || self.typed_protected() == Some(true)
+    }
+
     /// The `Homepage` field.
     pub fn homepage(&self) -> Option<&str> {
         self.field_str("Homepage")
@@ -160,10 +308,862 @@ impl<'a> BinaryPackageControlFile<'a> {
         self.field_dependency_list("Pre-Depends")
     }
 
+    /// All relationship fields, parsed to a [PackageRelations].
+    pub fn relations(&self) -> Result<PackageRelations> {
+        PackageRelations::from_paragraph(self)
+    }
+
     /// Obtain parsed values of all fields defining dependencies.
     pub fn package_dependency_fields(&self) -> Result<PackageDependencyFields> {
         PackageDependencyFields::from_paragraph(self)
     }
+
+    /// The `Multi-Arch` field, parsed to a [MultiArch].
+    pub fn multi_arch(&self) -> Option<Result<MultiArch>> {
+        self.field_str("Multi-Arch").map(MultiArch::from_str)
+    }
+
+    /// The `Package-Type` field. e.g. `udeb`.
+    pub fn package_type(&self) -> Option<&str> {
+        self.field_str("Package-Type")
+    }
+
+    /// Validate the `Multi-Arch` field against `Architecture` and `Package-Type`.
+    ///
+    /// Errors if the value is unknown, if `Multi-Arch: same` is combined with
+    /// `Architecture: all` (which dpkg refuses to install) or if a `udeb` package
+    /// declares anything other than `Multi-Arch: no`, since the Debian installer
+    /// doesn't support multiarch.
+    pub fn validate_multi_arch(&self) -> Result<()> {
+        let multi_arch = match self.multi_arch() {
+            Some(value) => value?,
+            None => return Ok(()),
+        };
+
+        if multi_arch == MultiArch::Same && self.architecture()? == "all" {
+            Err(DebianError::MultiArchIncompatible(
+                multi_arch.to_string(),
+                "is not allowed for Architecture: all packages",
+            ))
+        } else if multi_arch != MultiArch::No && self.package_type() == Some("udeb") {
+            Err(DebianError::MultiArchIncompatible(
+                multi_arch.to_string(),
+                "is not allowed for udeb packages",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A parsed `Description` field.
+///
+/// The first line of the field is the synopsis. Continuation lines form the extended
+/// description. In the field value, every extended line is indented by a space and blank
+/// lines are written as ` .`. [Self::extended] holds the text without this encoding.
+/// [Display] produces the field value again.
+///
+/// See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#description>.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PackageDescription {
+    /// The single line summary.
+    pub synopsis: String,
+    /// The extended description, if any.
+    ///
+    /// Lines starting with further spaces are meant to be displayed verbatim.
+    pub extended: Option<String>,
+}
+
+impl PackageDescription {
+    /// Parse a `Description` field value.
+    pub fn parse(value: &str) -> Self {
+        let mut lines = value.lines();
+        let synopsis = lines.next().unwrap_or_default().trim().to_string();
+
+        let extended = lines
+            .map(|line| {
+                let line = line
+                    .strip_prefix(' ')
+                    .or_else(|| line.strip_prefix('\t'))
+                    .unwrap_or(line);
+                if line.trim_end() == "." {
+                    ""
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            synopsis,
+            extended: if extended.is_empty() {
+                None
+            } else {
+                Some(extended.join("\n"))
+            },
+        }
+    }
+
+    /// MD5 digest of the field value as used by `Description-md5` fields, in hex.
+    ///
+    /// This is the digest of the field value followed by a newline and is how `Packages` and
+    /// `Translation-*` files refer to a description.
+    pub fn md5_hex(&self) -> String {
+        let mut h = ChecksumType::Md5.new_hasher();
+        h.update(self.to_string().as_bytes());
+        h.update(b"\n");
+
+        hex::encode(h.finish())
+    }
+}
+
+impl Display for PackageDescription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.synopsis)?;
+
+        for line in self.extended.iter().flat_map(|s| s.lines()) {
+            write!(f, "\n {}", if line.trim().is_empty() { "." } else { line })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Values of the `Multi-Arch` field.
+///
+/// See <https://wiki.debian.org/Multiarch/Implementation>.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum MultiArch {
+    /// `same`, the package is co-installable with itself for other architectures.
+    Same,
+    /// `foreign`, the package satisfies dependencies of packages of other architectures.
+    Foreign,
+    /// `allowed`, dependents may opt in to cross-architecture resolution via `:any`.
+    Allowed,
+    /// `no`, the default.
+    No,
+}
+
+impl Display for MultiArch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Same => "same",
+            Self::Foreign => "foreign",
+            Self::Allowed => "allowed",
+            Self::No => "no",
+        })
+    }
+}
+
+impl FromStr for MultiArch {
+    type Err = DebianError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "same" => Ok(Self::Same),
+            "foreign" => Ok(Self::Foreign),
+            "allowed" => Ok(Self::Allowed),
+            "no" => Ok(Self::No),
+            _ => Err(DebianError::MultiArchInvalid(s.to_string())),
+        }
+    }
+}
+
+/// A Debian machine architecture.
+///
+/// Well-known architectures have dedicated variants. Others are represented by
+/// [Architecture::Other].
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Architecture {
+    /// `all`, for architecture-independent packages.
+    All,
+    /// `amd64`.
+    Amd64,
+    /// `arm64`.
+    Arm64,
+    /// `armel`.
+    Armel,
+    /// `armhf`.
+    Armhf,
+    /// `i386`.
+    I386,
+    /// `mips64el`.
+    Mips64el,
+    /// `ppc64el`.
+    Ppc64el,
+    /// `riscv64`.
+    Riscv64,
+    /// `s390x`.
+    S390x,
+    /// Any other architecture.
+    Other(String),
+}
+
+impl Display for Architecture {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::All => "all",
+            Self::Amd64 => "amd64",
+            Self::Arm64 => "arm64",
+            Self::Armel => "armel",
+            Self::Armhf => "armhf",
+            Self::I386 => "i386",
+            Self::Mips64el => "mips64el",
+            Self::Ppc64el => "ppc64el",
+            Self::Riscv64 => "riscv64",
+            Self::S390x => "s390x",
+            Self::Other(s) => s,
+        })
+    }
+}
+
+impl FromStr for Architecture {
+    type Err = DebianError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "all" => Self::All,
+            "amd64" => Self::Amd64,
+            "arm64" => Self::Arm64,
+            "armel" => Self::Armel,
+            "armhf" => Self::Armhf,
+            "i386" => Self::I386,
+            "mips64el" => Self::Mips64el,
+            "ppc64el" => Self::Ppc64el,
+            "riscv64" => Self::Riscv64,
+            "s390x" => Self::S390x,
+            _ => {
+                if s.is_empty()
+                    || !s
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+                {
+                    return Err(DebianError::ArchitectureInvalid(s.to_string()));
+                }
+
+                Self::Other(s.to_string())
+            }
+        })
+    }
+}
+
+/// Values of the `Priority` field.
+///
+/// See <https://www.debian.org/doc/debian-policy/ch-archive.html#priorities>. Variants are
+/// ordered from most to least important, with unknown values last. `extra` is deprecated
+/// in favor of `optional` but still seen in the wild.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Priority {
+    /// `required`, packages necessary for the proper functioning of the system.
+    Required,
+    /// `important`, programs expected on any Unix-like system.
+    Important,
+    /// `standard`, a reasonably small but not too limited character-mode system.
+    Standard,
+    /// `optional`, the default for most packages.
+    Optional,
+    /// `extra`, the deprecated priority.
+    Extra,
+    /// Any other priority.
+    Other(String),
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Required => "required",
+            Self::Important => "important",
+            Self::Standard => "standard",
+            Self::Optional => "optional",
+            Self::Extra => "extra",
+            Self::Other(s) => s,
+        })
+    }
+}
+
+impl From<&str> for Priority {
+    fn from(s: &str) -> Self {
+        match s {
+            "required" => Self::Required,
+            "important" => Self::Important,
+            "standard" => Self::Standard,
+            "optional" => Self::Optional,
+            "extra" => Self::Extra,
+            _ => Self::Other(s.to_string()),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self::from(s))
+    }
+}
+
+/// Sections defined by Debian Policy.
+///
+/// See <https://www.debian.org/doc/debian-policy/ch-archive.html#sections>. Values don't
+/// include archive area prefixes like `contrib/`. Unknown sections are represented by
+/// [Section::Other].
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Section {
+    /// `admin`.
+    Admin,
+    /// `cli-mono`.
+    CliMono,
+    /// `comm`.
+    Comm,
+    /// `database`.
+    Database,
+    /// `debian-installer`.
+    DebianInstaller,
+    /// `debug`.
+    Debug,
+    /// `devel`.
+    Devel,
+    /// `doc`.
+    Doc,
+    /// `editors`.
+    Editors,
+    /// `education`.
+    Education,
+    /// `electronics`.
+    Electronics,
+    /// `embedded`.
+    Embedded,
+    /// `fonts`.
+    Fonts,
+    /// `games`.
+    Games,
+    /// `gnome`.
+    Gnome,
+    /// `gnu-r`.
+    GnuR,
+    /// `gnustep`.
+    Gnustep,
+    /// `graphics`.
+    Graphics,
+    /// `hamradio`.
+    Hamradio,
+    /// `haskell`.
+    Haskell,
+    /// `httpd`.
+    Httpd,
+    /// `interpreters`.
+    Interpreters,
+    /// `introspection`.
+    Introspection,
+    /// `java`.
+    Java,
+    /// `javascript`.
+    Javascript,
+    /// `kde`.
+    Kde,
+    /// `kernel`.
+    Kernel,
+    /// `libdevel`.
+    Libdevel,
+    /// `libs`.
+    Libs,
+    /// `lisp`.
+    Lisp,
+    /// `localization`.
+    Localization,
+    /// `mail`.
+    Mail,
+    /// `math`.
+    Math,
+    /// `metapackages`.
+    Metapackages,
+    /// `misc`.
+    Misc,
+    /// `net`.
+    Net,
+    /// `news`.
+    News,
+    /// `ocaml`.
+    Ocaml,
+    /// `oldlibs`.
+    Oldlibs,
+    /// `otherosfs`.
+    Otherosfs,
+    /// `perl`.
+    Perl,
+    /// `php`.
+    Php,
+    /// `python`.
+    Python,
+    /// `ruby`.
+    Ruby,
+    /// `rust`.
+    Rust,
+    /// `science`.
+    Science,
+    /// `shells`.
+    Shells,
+    /// `sound`.
+    Sound,
+    /// `tasks`.
+    Tasks,
+    /// `tex`.
+    Tex,
+    /// `text`.
+    Text,
+    /// `utils`.
+    Utils,
+    /// `vcs`.
+    Vcs,
+    /// `video`.
+    Video,
+    /// `web`.
+    Web,
+    /// `x11`.
+    X11,
+    /// `xfce`.
+    Xfce,
+    /// `zope`.
+    Zope,
+    /// Any other section.
+    Other(String),
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Admin => "admin",
+            Self::CliMono => "cli-mono",
+            Self::Comm => "comm",
+            Self::Database => "database",
+            Self::DebianInstaller => "debian-installer",
+            Self::Debug => "debug",
+            Self::Devel => "devel",
+            Self::Doc => "doc",
+            Self::Editors => "editors",
+            Self::Education => "education",
+            Self::Electronics => "electronics",
+            Self::Embedded => "embedded",
+            Self::Fonts => "fonts",
+            Self::Games => "games",
+            Self::Gnome => "gnome",
+            Self::GnuR => "gnu-r",
+            Self::Gnustep => "gnustep",
+            Self::Graphics => "graphics",
+            Self::Hamradio => "hamradio",
+            Self::Haskell => "haskell",
+            Self::Httpd => "httpd",
+            Self::Interpreters => "interpreters",
+            Self::Introspection => "introspection",
+            Self::Java => "java",
+            Self::Javascript => "javascript",
+            Self::Kde => "kde",
+            Self::Kernel => "kernel",
+            Self::Libdevel => "libdevel",
+            Self::Libs => "libs",
+            Self::Lisp => "lisp",
+            Self::Localization => "localization",
+            Self::Mail => "mail",
+            Self::Math => "math",
+            Self::Metapackages => "metapackages",
+            Self::Misc => "misc",
+            Self::Net => "net",
+            Self::News => "news",
+            Self::Ocaml => "ocaml",
+            Self::Oldlibs => "oldlibs",
+            Self::Otherosfs => "otherosfs",
+            Self::Perl => "perl",
+            Self::Php => "php",
+            Self::Python => "python",
+            Self::Ruby => "ruby",
+            Self::Rust => "rust",
+            Self::Science => "science",
+            Self::Shells => "shells",
+            Self::Sound => "sound",
+            Self::Tasks => "tasks",
+            Self::Tex => "tex",
+            Self::Text => "text",
+            Self::Utils => "utils",
+            Self::Vcs => "vcs",
+            Self::Video => "video",
+            Self::Web => "web",
+            Self::X11 => "x11",
+            Self::Xfce => "xfce",
+            Self::Zope => "zope",
+            Self::Other(s) => s,
+        })
+    }
+}
+
+impl From<&str> for Section {
+    fn from(s: &str) -> Self {
+        match s {
+            "admin" => Self::Admin,
+            "cli-mono" => Self::CliMono,
+            "comm" => Self::Comm,
+            "database" => Self::Database,
+            "debian-installer" => Self::DebianInstaller,
+            "debug" => Self::Debug,
+            "devel" => Self::Devel,
+            "doc" => Self::Doc,
+            "editors" => Self::Editors,
+            "education" => Self::Education,
+            "electronics" => Self::Electronics,
+            "embedded" => Self::Embedded,
+            "fonts" => Self::Fonts,
+            "games" => Self::Games,
+            "gnome" => Self::Gnome,
+            "gnu-r" => Self::GnuR,
+            "gnustep" => Self::Gnustep,
+            "graphics" => Self::Graphics,
+            "hamradio" => Self::Hamradio,
+            "haskell" => Self::Haskell,
+            "httpd" => Self::Httpd,
+            "interpreters" => Self::Interpreters,
+            "introspection" => Self::Introspection,
+            "java" => Self::Java,
+            "javascript" => Self::Javascript,
+            "kde" => Self::Kde,
+            "kernel" => Self::Kernel,
+            "libdevel" => Self::Libdevel,
+            "libs" => Self::Libs,
+            "lisp" => Self::Lisp,
+            "localization" => Self::Localization,
+            "mail" => Self::Mail,
+            "math" => Self::Math,
+            "metapackages" => Self::Metapackages,
+            "misc" => Self::Misc,
+            "net" => Self::Net,
+            "news" => Self::News,
+            "ocaml" => Self::Ocaml,
+            "oldlibs" => Self::Oldlibs,
+            "otherosfs" => Self::Otherosfs,
+            "perl" => Self::Perl,
+            "php" => Self::Php,
+            "python" => Self::Python,
+            "ruby" => Self::Ruby,
+            "rust" => Self::Rust,
+            "science" => Self::Science,
+            "shells" => Self::Shells,
+            "sound" => Self::Sound,
+            "tasks" => Self::Tasks,
+            "tex" => Self::Tex,
+            "text" => Self::Text,
+            "utils" => Self::Utils,
+            "vcs" => Self::Vcs,
+            "video" => Self::Video,
+            "web" => Self::Web,
+            "x11" => Self::X11,
+            "xfce" => Self::Xfce,
+            "zope" => Self::Zope,
+            _ => Self::Other(s.to_string()),
+        }
+    }
+}
+
+impl FromStr for Section {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self::from(s))
+    }
+}
+
+impl Section {
+    /// Whether this section is defined by Debian Policy.
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Self::Other(_))
+    }
+}
+
+/// A builder for [BinaryPackageControlFile] instances describing newly built packages.
+///
+/// Fields are set via typed setters and validated. [Self::build()] emits fields in the
+/// conventional order, with `Description` last.
+#[derive(Clone, Debug, Default)]
+pub struct BinaryPackageControlBuilder {
+    package: Option<String>,
+    source: Option<String>,
+    version: Option<PackageVersion>,
+    architecture: Option<Architecture>,
+    maintainer: Option<String>,
+    installed_size: Option<u64>,
+    relations: PackageRelations,
+    section: Option<String>,
+    priority: Option<String>,
+    multi_arch: Option<MultiArch>,
+    essential: bool,
+    protected: bool,
+    homepage: Option<String>,
+    extra_fields: Vec<(String, String)>,
+    description: Option<PackageDescription>,
+}
+
+impl BinaryPackageControlBuilder {
+    /// Set the `Package` field.
+    ///
+    /// Package names must consist only of lowercase letters, digits, `+`, `-` and `.`,
+    /// be at least 2 characters long and start with an alphanumeric character.
+    pub fn set_package(mut self, name: &str) -> Result<Self> {
+        let mut chars = name.chars();
+        let valid_start = chars
+            .next()
+            .map(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+            .unwrap_or(false);
+
+        if !valid_start
+            || name.len() < 2
+            || !chars.all(|c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '+' | '-' | '.')
+            })
+        {
+            return Err(DebianError::BinaryPackageNameInvalid(name.to_string()));
+        }
+
+        self.package = Some(name.to_string());
+        Ok(self)
+    }
+
+    /// Set the `Source` field.
+    #[must_use]
+    pub fn set_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Set the `Version` field.
+    #[must_use]
+    pub fn set_version(mut self, version: PackageVersion) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Set the `Version` field from a string, which is parsed and validated.
+    pub fn set_version_str(self, version: &str) -> Result<Self> {
+        Ok(self.set_version(PackageVersion::parse(version)?))
+    }
+
+    /// Set the `Architecture` field.
+    #[must_use]
+    pub fn set_architecture(mut self, architecture: Architecture) -> Self {
+        self.architecture = Some(architecture);
+        self
+    }
+
+    /// Set the `Maintainer` field.
+    ///
+    /// This is typically of the form `Full Name <email@example.com>`.
+    #[must_use]
+    pub fn set_maintainer(mut self, maintainer: impl Into<String>) -> Self {
+        self.maintainer = Some(maintainer.into());
+        self
+    }
+
+    /// Set the `Installed-Size` field, in KiB.
+    #[must_use]
+    pub fn set_installed_size(mut self, kib: u64) -> Self {
+        self.installed_size = Some(kib);
+        self
+    }
+
+    /// Set a dependency field such as `Depends`.
+    #[must_use]
+    pub fn set_dependency(mut self, field: BinaryDependency, value: DependencyList) -> Self {
+        *self.relations.get_mut(field.into()) = Some(value);
+        self
+    }
+
+    /// Set all relationship fields, replacing any previously set.
+    #[must_use]
+    pub fn set_relations(mut self, relations: PackageRelations) -> Self {
+        self.relations = relations;
+        self
+    }
+
+    /// Set the `Breaks` field.
+    #[must_use]
+    pub fn set_breaks(mut self, value: DependencyList) -> Self {
+        self.relations.breaks = Some(value);
+        self
+    }
+
+    /// Set the `Conflicts` field.
+    #[must_use]
+    pub fn set_conflicts(mut self, value: DependencyList) -> Self {
+        self.relations.conflicts = Some(value);
+        self
+    }
+
+    /// Set the `Provides` field.
+    #[must_use]
+    pub fn set_provides(mut self, value: DependencyList) -> Self {
+        self.relations.provides = Some(value);
+        self
+    }
+
+    /// Set the `Replaces` field.
+    #[must_use]
+    pub fn set_replaces(mut self, value: DependencyList) -> Self {
+        self.relations.replaces = Some(value);
+        self
+    }
+
+    /// Set the `Section` field.
+    #[must_use]
+    pub fn set_section(mut self, section: impl ToString) -> Self {
+        self.section = Some(section.to_string());
+        self
+    }
+
+    /// Set the `Priority` field.
+    #[must_use]
+    pub fn set_priority(mut self, priority: impl ToString) -> Self {
+        self.priority = Some(priority.to_string());
+        self
+    }
+
+    /// Set the `Multi-Arch` field.
+    ///
+    /// The value is validated against the architecture by [Self::build()].
+    #[must_use]
+    pub fn set_multi_arch(mut self, multi_arch: MultiArch) -> Self {
+        self.multi_arch = Some(multi_arch);
+        self
+    }
+
+    /// Set whether the package is `Essential`.
+    #[must_use]
+    pub fn set_essential(mut self, essential: bool) -> Self {
+        self.essential = essential;
+        self
+    }
+
+    /// Set whether the package is `Protected`.
+    #[must_use]
+    pub fn set_protected(mut self, protected: bool) -> Self {
+        self.protected = protected;
+        self
+    }
+
+    /// Set the `Homepage` field.
+    #[must_use]
+    pub fn set_homepage(mut self, homepage: impl Into<String>) -> Self {
+        self.homepage = Some(homepage.into());
+        self
+    }
+
+    /// Set the `Description` field.
+    ///
+    /// `synopsis` is the single line summary. `extended` is the optional long
+    /// description. Its lines are indented and blank lines are encoded as ` .` when
+    /// the field is written.
+    #[must_use]
+    pub fn set_description(
+        mut self,
+        synopsis: impl Into<String>,
+        extended: impl Into<String>,
+    ) -> Self {
+        let extended = extended.into();
+        self.description = Some(PackageDescription {
+            synopsis: synopsis.into(),
+            extended: (!extended.is_empty()).then_some(extended),
+        });
+        self
+    }
+
+    /// Set an arbitrary single line field not covered by other setters.
+    ///
+    /// Fields are emitted after the well-known fields, before `Description`.
+    #[must_use]
+    pub fn set_field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        self.extra_fields
+            .retain(|(existing, _)| !existing.eq_ignore_ascii_case(&name));
+        self.extra_fields.push((name, value.into()));
+        self
+    }
+
+    /// Build the control file.
+    ///
+    /// Errors if a mandatory field (`Package`, `Version`, `Architecture`, `Maintainer`
+    /// and `Description`) is missing or if a single line field contains a line break.
+    pub fn build(&self) -> Result<BinaryPackageControlFile<'static>> {
+        let required = |name: &str, value: Option<String>| {
+            value.ok_or_else(|| DebianError::ControlRequiredFieldMissing(name.to_string()))
+        };
+
+        let mut fields = vec![(
+            "Package".to_string(),
+            required("Package", self.package.clone())?,
+        )];
+        if let Some(source) = &self.source {
+            fields.push(("Source".into(), source.clone()));
+        }
+        fields.push((
+            "Version".into(),
+            required("Version", self.version.as_ref().map(|v| v.to_string()))?,
+        ));
+        fields.push((
+            "Architecture".into(),
+            required(
+                "Architecture",
+                self.architecture.as_ref().map(|v| v.to_string()),
+            )?,
+        ));
+        if self.essential {
+            fields.push(("Essential".into(), "yes".into()));
+        }
+        if self.protected {
+            fields.push(("Protected".into(), "yes".into()));
+        }
+        fields.push((
+            "Maintainer".into(),
+            required("Maintainer", self.maintainer.clone())?,
+        ));
+        if let Some(size) = self.installed_size {
+            fields.push(("Installed-Size".into(), size.to_string()));
+        }
+        for (field, value) in self.relations.iter() {
+            fields.push((field.field_name().into(), value.to_string()));
+        }
+        for (name, value) in [
+            ("Section", &self.section),
+            ("Priority", &self.priority),
+            ("Multi-Arch", &self.multi_arch.map(|v| v.to_string())),
+            ("Homepage", &self.homepage),
+        ] {
+            if let Some(value) = value {
+                fields.push((name.into(), value.clone()));
+            }
+        }
+        fields.extend(self.extra_fields.iter().cloned());
+
+        if fields.iter().any(|(_, value)| value.contains('\n'))
+            || self
+                .description
+                .as_ref()
+                .map(|description| description.synopsis.contains('\n'))
+                .unwrap_or(false)
+        {
+            return Err(DebianError::ControlSimpleValueNoMultiline);
+        }
+
+        let description = self
+            .description
+            .as_ref()
+            .ok_or_else(|| DebianError::ControlRequiredFieldMissing("Description".to_string()))?;
+        fields.push(("Description".into(), description.to_string()));
+
+        let mut para = ControlParagraph::default();
+        for (name, value) in fields {
+            para.set_field_from_string(name.into(), value.into());
+        }
+
+        let cf = BinaryPackageControlFile::from(para);
+        cf.validate_multi_arch()?;
+
+        Ok(cf)
+    }
 }
 
 impl<'cf, 'a: 'cf> DebPackageReference<'cf> for BinaryPackageControlFile<'a> {
@@ -199,3 +1199,153 @@ impl<'cf, 'a: 'cf> DebPackageReference<'cf> for BinaryPackageControlFile<'a> {
         Ok(self.clone())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn control_builder() -> Result<()> {
+        let cf = BinaryPackageControlBuilder::default()
+            .set_package("libfoo1")?
+            .set_version_str("1:1.2-3")?
+            .set_architecture("amd64".parse()?)
+            .set_maintainer("Jane Doe <jane@example.com>")
+            .set_installed_size(42)
+            .set_dependency(
+                BinaryDependency::Depends,
+                DependencyList::parse("libc6 (>= 2.4)")?,
+            )
+            .set_breaks(DependencyList::parse("libfoo0")?)
+            .set_section(Section::Libs)
+            .set_multi_arch(MultiArch::Same)
+            .set_description("Foo library", "Does foo.\n\nAnd bar.")
+            .build()?;
+
+        assert_eq!(cf.architecture()?, "amd64");
+        assert_eq!(cf.version()?, PackageVersion::parse("1:1.2-3")?);
+        assert_eq!(cf.installed_size().unwrap()?, 42);
+        assert_eq!(
+            cf.to_string(),
+            "Package: libfoo1\n\
+             Version: 1:1.2-3\n\
+             Architecture: amd64\n\
+             Maintainer: Jane Doe <jane@example.com>\n\
+             Installed-Size: 42\n\
+             Depends: libc6 (>= 2.4)\n\
+             Breaks: libfoo0\n\
+             Section: libs\n\
+             Multi-Arch: same\n\
+             Description: Foo library\n \
+             Does foo.\n \
+             .\n \
+             And bar.\n"
+        );
+
+        assert!(matches!(
+            BinaryPackageControlBuilder::default().set_package("Foo"),
+            Err(DebianError::BinaryPackageNameInvalid(_))
+        ));
+        assert!(matches!(
+            BinaryPackageControlBuilder::default()
+                .set_package("foo")?
+                .set_version_str("1.0")?
+                .set_architecture(Architecture::All)
+                .set_maintainer("Foo <foo@example.com>")
+                .set_multi_arch(MultiArch::Same)
+                .set_description("Foo", "")
+                .build(),
+            Err(DebianError::MultiArchIncompatible(_, _))
+        ));
+        assert_eq!(cf.typed_section(), Some(Section::Libs));
+        assert_eq!(cf.synopsis()?, "Foo library");
+        assert_eq!(
+            cf.typed_description()?,
+            PackageDescription {
+                synopsis: "Foo library".into(),
+                extended: Some("Does foo.\n\nAnd bar.".into()),
+            }
+        );
+        assert_eq!(
+            cf.typed_description()?.to_string(),
+            cf.description()?.to_string()
+        );
+        assert_eq!(cf.typed_priority(), None);
+        assert!(matches!(
+            "x86_64".parse::<Architecture>(),
+            Err(DebianError::ArchitectureInvalid(_))
+        ));
+        assert!(matches!(
+            BinaryPackageControlBuilder::default()
+                .set_package("foo")?
+                .build(),
+            Err(DebianError::ControlRequiredFieldMissing(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn package_description() {
+        let value = "greeter \n Says hello.\n .\n   $ hello\n";
+        let description = PackageDescription::parse(value);
+        assert_eq!(description.synopsis, "greeter");
+        assert_eq!(
+            description.extended.as_deref(),
+            Some("Says hello.\n\n  $ hello")
+        );
+        assert_eq!(
+            description.to_string(),
+            "greeter\n Says hello.\n .\n   $ hello"
+        );
+        assert_eq!(
+            PackageDescription::parse(&description.to_string()),
+            description
+        );
+        assert_eq!(PackageDescription::parse("short").extended, None);
+        assert_eq!(description.md5_hex(), "6f957103a06ce8e99e0171daf818f42a");
+    }
+
+    #[test]
+    fn priority_and_section() {
+        assert!(Priority::Required < Priority::Optional);
+        assert!(Priority::Extra < Priority::from("bogus"));
+        assert_eq!(Priority::from("standard").to_string(), "standard");
+
+        for name in crate::checks::builtin::POLICY_SECTIONS {
+            let section = Section::from(*name);
+            assert!(section.is_known(), "{}", name);
+            assert_eq!(section.to_string(), *name);
+        }
+        assert_eq!(Section::from("bogus"), Section::Other("bogus".to_string()));
+
+        let mut cf = BinaryPackageControlFile::from(ControlParagraph::default());
+        cf.set_field_from_string("Section".into(), "non-free/net".into());
+        assert_eq!(cf.typed_section(), Some(Section::Net));
+        assert_eq!(cf.archive_area(), Some("non-free"));
+    }
+
+    #[test]
+    fn pool_path() -> Result<()> {
+        let mut cf = BinaryPackageControlFile::from(ControlParagraph::default());
+        cf.set_field_from_string("Package".into(), "libzstd1".into());
+        cf.set_field_from_string("Version".into(), "1:1.4.8+dfsg-2.1".into());
+        cf.set_field_from_string("Architecture".into(), "amd64".into());
+
+        assert_eq!(cf.source_package_name()?, "libzstd1");
+        assert_eq!(
+            cf.pool_path(PoolLayout::default(), "main")?,
+            "pool/main/libz/libzstd1/libzstd1_1.4.8+dfsg-2.1_amd64.deb"
+        );
+
+        cf.set_field_from_string("Source".into(), "libzstd (1.4.8+dfsg-2)".into());
+        cf.set_field_from_string("Package-Type".into(), "udeb".into());
+        assert_eq!(cf.source_package_name()?, "libzstd");
+        assert_eq!(
+            cf.pool_path(PoolLayout::default(), "main")?,
+            "pool/main/libz/libzstd/libzstd1_1.4.8+dfsg-2.1_amd64.udeb"
+        );
+
+        Ok(())
+    }
+}
@@ -5,10 +5,30 @@
 /*! Interface with a collection of binary package control definitions. */
 
 use {
-    crate::binary_package_control::BinaryPackageControlFile,
-    std::ops::{Deref, DerefMut},
+    crate::{
+        binary_package_control::{BinaryPackageControlFile, MultiArch},
+        control::ControlParagraph,
+        error::Result,
+        package_version::PackageVersion,
+        provides::ProvidesIndex,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        ops::{Deref, DerefMut},
+    },
 };
 
+/// A problem with `Multi-Arch` metadata found by [BinaryPackageList::multi_arch_issues()].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct MultiArchIssue {
+    /// Name of the package with the problem.
+    pub package: String,
+    /// Architecture of the offending package, if the problem is specific to one.
+    pub architecture: Option<String>,
+    /// Description of the problem.
+    pub problem: String,
+}
+
 /// Represents a collection of binary package control files.
 ///
 /// Various operations in Debian packaging operate against a collection of
@@ -53,6 +73,128 @@ impl<'a> BinaryPackageList<'a> {
             .iter()
             .filter(move |cf| matches!(cf.package(), Ok(name) if name == package))
     }
+
+    /// Find the instance of a package with the highest version.
+    ///
+    /// Returns `Ok(None)` if no package has the name.
+    pub fn max_by_version(&self, package: &str) -> Result<Option<&BinaryPackageControlFile<'a>>> {
+        let mut res: Option<(PackageVersion, &BinaryPackageControlFile<'a>)> = None;
+
+        for cf in self.find_packages_with_name(package.to_string()) {
+            let version = cf.version()?;
+
+            if res.as_ref().map_or(true, |(max, _)| &version > max) {
+                res = Some((version, cf));
+            }
+        }
+
+        Ok(res.map(|(_, cf)| cf))
+    }
+
+    /// Obtain an instance not borrowing from any input, minimizing memory use.
+    ///
+    /// Well-known field names and values use static storage and excess capacity is
+    /// released. See [ControlParagraph::into_interned()].
+    pub fn into_interned(self) -> BinaryPackageList<'static> {
+        let mut packages = self
+            .packages
+            .into_iter()
+            .map(|cf| BinaryPackageControlFile::from(ControlParagraph::from(cf).into_interned()))
+            .collect::<Vec<_>>();
+        packages.shrink_to_fit();
+
+        BinaryPackageList { packages }
+    }
+
+    /// Sort packages by name and then version, in ascending order.
+    pub fn sort_by_version(&mut self) -> Result<()> {
+        let keys = self
+            .packages
+            .iter()
+            .map(|cf| Ok((cf.package()?.to_string(), cf.version()?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut entries = keys
+            .into_iter()
+            .zip(std::mem::take(&mut self.packages))
+            .collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.packages = entries.into_iter().map(|(_, cf)| cf).collect();
+
+        Ok(())
+    }
+
+    /// Index the virtual packages provided by packages in this collection.
+    pub fn provides_index(&self) -> Result<ProvidesIndex<'_, 'a>> {
+        let mut index = ProvidesIndex::default();
+        index.load_binary_packages(self.packages.iter())?;
+
+        Ok(index)
+    }
+
+    /// Find problems with `Multi-Arch` metadata in this collection.
+    ///
+    /// Each package is checked with [BinaryPackageControlFile::validate_multi_arch()].
+    /// In addition, a `Multi-Arch: same` package must use that value and have the same
+    /// version on every architecture, otherwise its instances can't be co-installed.
+    ///
+    /// Issues are sorted and deduplicated.
+    pub fn multi_arch_issues(&self) -> Vec<MultiArchIssue> {
+        let mut issues = BTreeSet::new();
+        let mut by_name = BTreeMap::<&str, Vec<&BinaryPackageControlFile<'a>>>::new();
+
+        for cf in &self.packages {
+            let package = cf.package().unwrap_or_default();
+
+            if let Err(e) = cf.validate_multi_arch() {
+                issues.insert(MultiArchIssue {
+                    package: package.to_string(),
+                    architecture: cf.architecture().ok().map(|s| s.to_string()),
+                    problem: e.to_string(),
+                });
+            }
+
+            if cf.architecture().map(|a| a != "all").unwrap_or(false) {
+                by_name.entry(package).or_default().push(cf);
+            }
+        }
+
+        for (package, instances) in by_name {
+            if !instances
+                .iter()
+                .any(|cf| matches!(cf.multi_arch(), Some(Ok(MultiArch::Same))))
+            {
+                continue;
+            }
+
+            for cf in &instances {
+                if !matches!(cf.multi_arch(), Some(Ok(MultiArch::Same))) {
+                    issues.insert(MultiArchIssue {
+                        package: package.to_string(),
+                        architecture: cf.architecture().ok().map(|s| s.to_string()),
+                        problem: "is not Multi-Arch: same but other architectures are".to_string(),
+                    });
+                }
+            }
+
+            let versions = instances
+                .iter()
+                .filter_map(|cf| cf.version_str().ok())
+                .collect::<BTreeSet<_>>();
+            if versions.len() > 1 {
+                issues.insert(MultiArchIssue {
+                    package: package.to_string(),
+                    architecture: None,
+                    problem: format!(
+                        "Multi-Arch: same package has differing versions across architectures: {}",
+                        versions.into_iter().collect::<Vec<_>>().join(", ")
+                    ),
+                });
+            }
+        }
+
+        issues.into_iter().collect()
+    }
 }
 
 #[cfg(test)]
@@ -115,4 +257,69 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn max_by_version() -> Result<()> {
+        let mut l = BinaryPackageList::default();
+        for data in [
+            "Package: foo\nVersion: 1.0-2\n",
+            "Package: foo\nVersion: 1:0.9-1\n",
+            "Package: bar\nVersion: 2.0\n",
+            "Package: foo\nVersion: 1.0-10\n",
+        ] {
+            let para = ControlParagraphReader::new(Cursor::new(data.as_bytes()))
+                .next()
+                .unwrap()?;
+            l.push(BinaryPackageControlFile::from(para));
+        }
+
+        assert_eq!(l.max_by_version("foo")?.unwrap().version_str()?, "1:0.9-1");
+        assert!(l.max_by_version("other")?.is_none());
+
+        l.sort_by_version()?;
+        assert_eq!(
+            l.iter()
+                .map(|cf| cf.version_str())
+                .collect::<Result<Vec<_>>>()?,
+            vec!["2.0", "1.0-2", "1.0-10", "1:0.9-1"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn multi_arch_issues() -> Result<()> {
+        let mut l = BinaryPackageList::default();
+        for data in [
+            "Package: libfoo1\nVersion: 1.0\nArchitecture: amd64\nMulti-Arch: same\n",
+            "Package: libfoo1\nVersion: 1.1\nArchitecture: i386\nMulti-Arch: same\n",
+            "Package: libbar1\nVersion: 1.0\nArchitecture: amd64\nMulti-Arch: same\n",
+            "Package: libbar1\nVersion: 1.0\nArchitecture: i386\n",
+            "Package: data\nVersion: 1.0\nArchitecture: all\nMulti-Arch: same\n",
+            "Package: tool\nVersion: 1.0\nArchitecture: amd64\nMulti-Arch: foreign\n",
+            "Package: tool\nVersion: 1.1\nArchitecture: i386\nMulti-Arch: foreign\n",
+        ] {
+            let para = ControlParagraphReader::new(Cursor::new(data.as_bytes()))
+                .next()
+                .unwrap()?;
+            l.push(BinaryPackageControlFile::from(para));
+        }
+
+        let issues = l
+            .multi_arch_issues()
+            .into_iter()
+            .map(|issue| (issue.package, issue.architecture))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            issues,
+            vec![
+                ("data".to_string(), Some("all".to_string())),
+                ("libbar1".to_string(), Some("i386".to_string())),
+                ("libfoo1".to_string(), None),
+            ]
+        );
+
+        Ok(())
+    }
 }
@@ -9,30 +9,70 @@ for the specification.
 */
 
 use {
-    chrono::{DateTime, Local},
-    std::{borrow::Cow, io::Write},
+    crate::{
+        error::{DebianError, Result},
+        package_version::PackageVersion,
+    },
+    chrono::{DateTime, FixedOffset},
+    once_cell::sync::Lazy,
+    regex::Regex,
+    std::{
+        borrow::Cow,
+        io::{Read, Write},
+    },
 };
 
+/// Regular expression matching the first line of a changelog entry.
+static RE_HEADER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?P<package>[a-z0-9][a-z0-9+.-]*) \((?P<version>[^ )]+)\)(?P<distributions>(?: +[^ ;]+)+) *;(?P<keyvalues>.*)$")
+        .unwrap()
+});
+
+/// Regular expression matching the trailer line of a changelog entry.
+static RE_TRAILER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^ -- (?P<name>.*?) <(?P<email>[^>]*)>  (?P<date>.+?)\s*$").unwrap());
+
+/// An entry in a `debian/changelog` file.
 #[derive(Clone, Debug)]
 pub struct ChangelogEntry<'a> {
+    /// The source package name.
     pub package: Cow<'a, str>,
+    /// The version of the package.
     pub version: Cow<'a, str>,
+    /// Distributions the version is uploaded to.
     pub distributions: Vec<Cow<'a, str>>,
+    /// The urgency of the upload.
     pub urgency: Cow<'a, str>,
+    /// Header key-value pairs other than the urgency, in order. e.g. `binary-only=yes`.
+    pub keyvalues: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+    /// Change details, verbatim. Lines are conventionally indented with 2 spaces.
     pub details: Cow<'a, str>,
+    /// Name of the person who made the changes.
     pub maintainer_name: Cow<'a, str>,
+    /// Email address of the person who made the changes.
     pub maintainer_email: Cow<'a, str>,
-    pub date: DateTime<Local>,
+    /// When the changes were made.
+    pub date: DateTime<FixedOffset>,
 }
 
 impl<'a> ChangelogEntry<'a> {
+    /// The parsed [PackageVersion] of this entry.
+    pub fn package_version(&self) -> Result<PackageVersion> {
+        PackageVersion::parse(&self.version)
+    }
+
+    /// The maintainer in `name <email>` form, as used by `Maintainer` and `Changed-By` fields.
+    pub fn maintainer(&self) -> String {
+        format!("{} <{}>", self.maintainer_name, self.maintainer_email)
+    }
+
     /// Serialize the changelog entry to a writer.
     ///
     /// This incurs multiple `.write()` calls. So a buffered writer is
     /// recommended if performance matters.
     pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
         /*
-        package (version) distribution(s); urgency=urgency
+        package (version) distribution(s); urgency=urgency[, key=value]
           [optional blank line(s), stripped]
           * change details
           more change details
@@ -48,10 +88,16 @@ impl<'a> ChangelogEntry<'a> {
         writer.write_all(self.distributions.join(" ").as_bytes())?;
         writer.write_all(b"; urgency=")?;
         writer.write_all(self.urgency.as_bytes())?;
+        for (key, value) in &self.keyvalues {
+            writer.write_all(b", ")?;
+            writer.write_all(key.as_bytes())?;
+            writer.write_all(b"=")?;
+            writer.write_all(value.as_bytes())?;
+        }
         writer.write_all(b"\n\n")?;
         writer.write_all(self.details.as_bytes())?;
-        writer.write_all(b"\n")?;
-        writer.write_all(b"-- ")?;
+        writer.write_all(b"\n\n")?;
+        writer.write_all(b" -- ")?;
         writer.write_all(self.maintainer_name.as_bytes())?;
         writer.write_all(b" <")?;
         writer.write_all(self.maintainer_email.as_bytes())?;
@@ -65,18 +111,135 @@ impl<'a> ChangelogEntry<'a> {
 
 /// Represents a complete `debian/changelog` file.
 ///
-/// Changelogs are an ordered series of `ChangelogEntry` items.
-#[derive(Default)]
+/// Changelogs are an ordered series of `ChangelogEntry` items, newest first.
+#[derive(Clone, Debug, Default)]
 pub struct Changelog<'a> {
     entries: Vec<ChangelogEntry<'a>>,
 }
 
+impl Changelog<'static> {
+    /// Parse a changelog from a string.
+    ///
+    /// Like `dpkg-parsechangelog`, parsing stops at an `Old Changelog:` or Emacs
+    /// `Local variables:` line. `vim:` modelines and `# ` comments between entries are
+    /// ignored.
+    pub fn parse_str(s: &str) -> Result<Self> {
+        let error =
+            |line: usize, message: &str| DebianError::ChangelogParse(line + 1, message.into());
+
+        let mut entries = vec![];
+        let mut current: Option<(ChangelogEntry<'static>, Vec<&str>)> = None;
+
+        for (number, line) in s.lines().enumerate() {
+            if let Some((mut entry, mut details)) = current.take() {
+                if let Some(caps) = RE_TRAILER.captures(line) {
+                    while details.last().is_some_and(|l| l.trim().is_empty()) {
+                        details.pop();
+                    }
+                    let start = details
+                        .iter()
+                        .position(|l| !l.trim().is_empty())
+                        .unwrap_or(details.len());
+
+                    entry.details = details[start..].join("\n").into();
+                    entry.maintainer_name = caps["name"].to_string().into();
+                    entry.maintainer_email = caps["email"].to_string().into();
+                    entry.date = DateTime::parse_from_rfc2822(&caps["date"])
+                        .map_err(|_| error(number, "invalid date in trailer"))?;
+                    entries.push(entry);
+                } else if RE_HEADER.is_match(line) || line.starts_with(" --") {
+                    return Err(error(number, "expected trailer line"));
+                } else {
+                    details.push(line);
+                    current = Some((entry, details));
+                }
+            } else if let Some(caps) = RE_HEADER.captures(line) {
+                let mut urgency = None;
+                let mut keyvalues = vec![];
+                for (key, value) in caps["keyvalues"]
+                    .split(',')
+                    .filter_map(|kv| kv.trim().split_once('='))
+                {
+                    if key.eq_ignore_ascii_case("urgency") {
+                        urgency = Some(value.to_string());
+                    } else {
+                        keyvalues
+                            .push((Cow::Owned(key.to_string()), Cow::Owned(value.to_string())));
+                    }
+                }
+                let urgency = urgency.ok_or_else(|| error(number, "missing urgency"))?;
+
+                current = Some((
+                    ChangelogEntry {
+                        package: caps["package"].to_string().into(),
+                        version: caps["version"].to_string().into(),
+                        distributions: caps["distributions"]
+                            .split_whitespace()
+                            .map(|d| Cow::Owned(d.to_string()))
+                            .collect(),
+                        urgency: urgency.into(),
+                        keyvalues,
+                        details: "".into(),
+                        maintainer_name: "".into(),
+                        maintainer_email: "".into(),
+                        date: DateTime::default(),
+                    },
+                    vec![],
+                ));
+            } else if line.starts_with("Old Changelog:")
+                || line
+                    .trim_start_matches(";;")
+                    .trim_start()
+                    .to_ascii_lowercase()
+                    .starts_with("local variables:")
+            {
+                break;
+            } else if line.trim().is_empty() || line.starts_with("vim:") || line.starts_with("# ") {
+                continue;
+            } else {
+                return Err(error(number, "expected entry header line"));
+            }
+        }
+
+        if current.is_some() {
+            return Err(error(s.lines().count(), "missing trailer line"));
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Parse a changelog from a reader.
+    pub fn parse_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut s = String::new();
+        reader.read_to_string(&mut s)?;
+
+        Self::parse_str(&s)
+    }
+}
+
 impl<'a> Changelog<'a> {
-    /// Add an entry to this changelog.
+    /// Add an entry to the end of this changelog, as its oldest entry.
     pub fn add_entry<'b: 'a>(&mut self, entry: ChangelogEntry<'b>) {
         self.entries.push(entry)
     }
 
+    /// Add an entry to the beginning of this changelog, as its newest entry.
+    ///
+    /// This is how a new version is recorded.
+    pub fn prepend_entry<'b: 'a>(&mut self, entry: ChangelogEntry<'b>) {
+        self.entries.insert(0, entry)
+    }
+
+    /// Obtain entries in this changelog, newest first.
+    pub fn entries(&self) -> impl Iterator<Item = &ChangelogEntry<'a>> {
+        self.entries.iter()
+    }
+
+    /// The newest entry in this changelog.
+    pub fn latest(&self) -> Option<&ChangelogEntry<'a>> {
+        self.entries.first()
+    }
+
     /// Serialize the changelog to a writer.
     ///
     /// Use of a buffered writer is encouraged if performance is a concern.
@@ -101,6 +264,7 @@ mod tests {
             version: "0.1".into(),
             distributions: vec!["mydist".into()],
             urgency: "low".into(),
+            keyvalues: vec![],
             details: "details".into(),
             maintainer_name: "maintainer".into(),
             maintainer_email: "me@example.com".into(),
@@ -116,7 +280,83 @@ mod tests {
         changelog.write(&mut buf)?;
 
         let s = String::from_utf8(buf).unwrap();
-        assert_eq!(s, "mypackage (0.1) mydist; urgency=low\n\ndetails\n-- maintainer <me@example.com>  Tue, 30 Dec 2014 21:26:40 -0700\n\n");
+        assert_eq!(s, "mypackage (0.1) mydist; urgency=low\n\ndetails\n\n -- maintainer <me@example.com>  Tue, 30 Dec 2014 21:26:40 -0700\n\n");
+
+        Ok(())
+    }
+
+    const CHANGELOG: &str = "hello (2.10-3) unstable experimental; urgency=medium, binary-only=yes
+
+  * New upload.
+
+  * Second change.
+
+ -- Jane Doe <jane@example.com>  Sat, 02 Nov 2024 12:00:00 +0100
+
+hello (2.10-2) unstable; urgency=low
+
+  * Initial release.
+
+ -- John Doe <john@example.com>  Fri, 01 Nov 2024 12:00:00 +0000
+
+Local variables:
+mode: debian-changelog
+End:
+";
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let mut changelog = Changelog::parse_str(CHANGELOG)?;
+
+        let entries = changelog.entries().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].package, "hello");
+        assert_eq!(entries[0].version, "2.10-3");
+        assert_eq!(entries[0].distributions, vec!["unstable", "experimental"]);
+        assert_eq!(entries[0].urgency, "medium");
+        assert_eq!(entries[0].details, "  * New upload.\n\n  * Second change.");
+        assert_eq!(entries[0].maintainer(), "Jane Doe <jane@example.com>");
+        assert_eq!(
+            entries[0].date.to_rfc2822(),
+            "Sat, 2 Nov 2024 12:00:00 +0100"
+        );
+        assert_eq!(
+            entries[0].keyvalues,
+            vec![(Cow::from("binary-only"), Cow::from("yes"))]
+        );
+        assert_eq!(entries[1].urgency, "low");
+        assert!(entries[1].keyvalues.is_empty());
+
+        let latest = changelog.latest().unwrap();
+        let mut next = latest.clone();
+        next.version = "2.10-4".into();
+        next.details = "  * Bump.".into();
+        assert!(next.package_version()? > latest.package_version()?);
+        changelog.prepend_entry(next);
+
+        let mut buf = vec![];
+        changelog.write(&mut buf)?;
+        let written = String::from_utf8(buf).unwrap();
+        assert!(written.starts_with(
+            "hello (2.10-4) unstable experimental; urgency=medium, binary-only=yes\n"
+        ));
+        let reparsed = Changelog::parse_str(&written)?;
+        assert_eq!(
+            reparsed
+                .entries()
+                .map(|e| e.version.as_ref())
+                .collect::<Vec<_>>(),
+            vec!["2.10-4", "2.10-3", "2.10-2"]
+        );
+        assert_eq!(
+            reparsed.entries().nth(1).unwrap().details,
+            "  * New upload.\n\n  * Second change."
+        );
+
+        assert!(matches!(
+            Changelog::parse_str("hello (1.0) unstable; urgency=low\n\n  * x\n"),
+            Err(DebianError::ChangelogParse(_, _))
+        ));
 
         Ok(())
     }
@@ -0,0 +1,671 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Debian `.changes` files.
+
+A `.changes` file describes an upload to a Debian archive: the source package and version
+being uploaded, the target distribution, and the set of files (`.deb`, `.dsc`, tarballs, etc)
+constituting the upload along with their digests.
+
+The format is defined at
+<https://www.debian.org/doc/debian-policy/ch-controlfields.html#debian-changes-files-changes>.
+
+[DebianChangesFile] parses existing (possibly PGP signed) files. [DebianChangesFileBuilder]
+generates new ones from a set of built artifacts.
+*/
+
+use {
+    crate::{
+        control::{ControlField, ControlParagraph, ControlParagraphReader},
+        error::{DebianError, Result},
+        file_list::{file_list_field, parse_file_list, validate_file_lists, FileListEntry},
+        io::{MultiContentDigest, MultiDigester},
+        keyring::{Keyring, SignerIdentity, TrustPolicy},
+        maintainer::Contact,
+        package_version::PackageVersion,
+        repository::release::{ChecksumType, DATE_FORMAT},
+    },
+    chrono::{DateTime, Utc},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        io::BufRead,
+        ops::{Deref, DerefMut},
+    },
+};
+
+/// A single file as described by a `Files` or `Checksums-*` field in a [DebianChangesFile].
+///
+/// Entries from the `Files` field have a section and priority.
+pub type DebianChangesFileEntry<'a> = FileListEntry<'a>;
+
+/// A Debian `.changes` file.
+///
+/// This control file consists of a single paragraph describing an upload.
+#[derive(Default)]
+pub struct DebianChangesFile<'a> {
+    paragraph: ControlParagraph<'a>,
+    /// Parsed PGP signatures for this file.
+    signatures: Option<pgp_cleartext::CleartextSignatures>,
+}
+
+impl<'a> Deref for DebianChangesFile<'a> {
+    type Target = ControlParagraph<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.paragraph
+    }
+}
+
+impl<'a> DerefMut for DebianChangesFile<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.paragraph
+    }
+}
+
+impl<'a> From<ControlParagraph<'a>> for DebianChangesFile<'a> {
+    fn from(paragraph: ControlParagraph<'a>) -> Self {
+        Self {
+            paragraph,
+            signatures: None,
+        }
+    }
+}
+
+impl<'a> From<DebianChangesFile<'a>> for ControlParagraph<'a> {
+    fn from(cf: DebianChangesFile<'a>) -> Self {
+        cf.paragraph
+    }
+}
+
+impl<'a> DebianChangesFile<'a> {
+    /// Construct an instance by reading data from a reader.
+    ///
+    /// The source must be a `.changes` file with exactly 1 paragraph and must not be PGP
+    /// armored. For PGP armored data, use [Self::from_armored_reader()].
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let paragraphs = ControlParagraphReader::new(reader).collect::<Result<Vec<_>>>()?;
+
+        if paragraphs.len() != 1 {
+            return Err(DebianError::DebianChangesFileParagraphMismatch(
+                paragraphs.len(),
+            ));
+        }
+
+        let paragraph = paragraphs
+            .into_iter()
+            .next()
+            .expect("validated paragraph count above");
+
+        Ok(Self {
+            paragraph,
+            signatures: None,
+        })
+    }
+
+    /// Construct an instance by reading data from a reader containing a PGP cleartext signature.
+    ///
+    /// The PGP signature is NOT validated. Use [Self::verify_signatures()] to verify it.
+    pub fn from_armored_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let reader = pgp_cleartext::CleartextSignatureReader::new(reader);
+        let mut reader = std::io::BufReader::new(reader);
+
+        let mut slf = Self::from_reader(&mut reader)?;
+        slf.signatures = Some(reader.into_inner().finalize());
+
+        Ok(slf)
+    }
+
+    /// Obtain PGP signatures from this possibly signed file.
+    pub fn signatures(&self) -> Option<&pgp_cleartext::CleartextSignatures> {
+        self.signatures.as_ref()
+    }
+
+    /// Verify the PGP signatures of this file against a keyring, subject to a policy.
+    ///
+    /// Returns the identities of the keys that made acceptable signatures. Errors with
+    /// [DebianError::ReleaseNoSignatures] if this file isn't signed. Otherwise errors are
+    /// as for [Keyring::verify_trusted()].
+    pub fn verify_signatures(
+        &self,
+        keyring: &Keyring,
+        policy: &TrustPolicy,
+    ) -> Result<Vec<SignerIdentity>> {
+        let signatures = self
+            .signatures
+            .as_ref()
+            .ok_or(DebianError::ReleaseNoSignatures)?;
+
+        keyring.verify_trusted(signatures, policy)
+    }
+
+    /// The format of the `.changes` file.
+    pub fn format(&self) -> Result<&str> {
+        self.required_field_str("Format")
+    }
+
+    /// The date the upload was prepared.
+    pub fn date(&self) -> Option<Result<DateTime<Utc>>> {
+        self.field_datetime_rfc5322("Date")
+    }
+
+    /// The name of the source package.
+    pub fn source(&self) -> Result<&str> {
+        self.required_field_str("Source")
+    }
+
+    /// Names of binary packages in this upload.
+    pub fn binary(&self) -> Option<Box<dyn Iterator<Item = &str> + '_>> {
+        self.iter_field_words("Binary")
+    }
+
+    /// Architectures in this upload. May include `source`.
+    pub fn architecture(&self) -> Option<Box<dyn Iterator<Item = &str> + '_>> {
+        self.iter_field_words("Architecture")
+    }
+
+    /// The version string of the upload.
+    pub fn version_str(&self) -> Result<&str> {
+        self.required_field_str("Version")
+    }
+
+    /// The parsed version of the upload.
+    pub fn version(&self) -> Result<PackageVersion> {
+        PackageVersion::parse(self.version_str()?)
+    }
+
+    /// Distributions this upload targets.
+    pub fn distribution(&self) -> Option<Box<dyn Iterator<Item = &str> + '_>> {
+        self.iter_field_words("Distribution")
+    }
+
+    /// The urgency of the upload.
+    pub fn urgency(&self) -> Option<&str> {
+        self.field_str("Urgency")
+    }
+
+    /// The maintainer of the package.
+    pub fn maintainer(&self) -> Result<&str> {
+        self.required_field_str("Maintainer")
+    }
+
+    /// The person who prepared the upload.
+    pub fn changed_by(&self) -> Option<&str> {
+        self.field_str("Changed-By")
+    }
+
+    /// The parsed `Maintainer` field.
+    pub fn typed_maintainer(&self) -> Result<Contact> {
+        Contact::parse(self.maintainer()?)
+    }
+
+    /// The parsed `Changed-By` field.
+    pub fn typed_changed_by(&self) -> Option<Result<Contact>> {
+        self.changed_by().map(Contact::parse)
+    }
+
+    /// The changelog entries for this upload.
+    pub fn changes(&self) -> Option<&str> {
+        self.field_str("Changes")
+    }
+
+    /// List of files in the upload with SHA-1 checksums.
+    pub fn checksums_sha1(
+        &self,
+    ) -> Option<Box<dyn Iterator<Item = Result<DebianChangesFileEntry<'_>>> + '_>> {
+        self.iter_files(ChecksumType::Sha1)
+    }
+
+    /// List of files in the upload with SHA-256 checksums.
+    pub fn checksums_sha256(
+        &self,
+    ) -> Option<Box<dyn Iterator<Item = Result<DebianChangesFileEntry<'_>>> + '_>> {
+        self.iter_files(ChecksumType::Sha256)
+    }
+
+    /// List of files in the upload with MD5 checksums, sections, and priorities.
+    pub fn files(
+        &self,
+    ) -> Result<Box<dyn Iterator<Item = Result<DebianChangesFileEntry<'_>>> + '_>> {
+        self.iter_files(ChecksumType::Md5)
+            .ok_or_else(|| DebianError::ControlRequiredFieldMissing("Files".to_string()))
+    }
+
+    /// List of files in the upload having digests of the given flavor.
+    ///
+    /// Returns `None` if the field for the checksum flavor isn't present.
+    pub fn iter_files(
+        &self,
+        checksum: ChecksumType,
+    ) -> Option<Box<dyn Iterator<Item = Result<DebianChangesFileEntry<'_>>> + '_>> {
+        let lines = self.iter_field_lines(checksum.source_field_name())?;
+
+        Some(Box::new(parse_file_list(
+            checksum,
+            checksum == ChecksumType::Md5,
+            lines,
+        )))
+    }
+
+    /// Validate that the `Files` and `Checksums-*` fields describe the same files.
+    ///
+    /// `Files` is required. Every filename must occur once per field and every present
+    /// `Checksums-*` field must list the same filenames with the same sizes as `Files`.
+    ///
+    /// Mismatches are reported as [DebianError::DebianChangesFileInconsistent].
+    pub fn validate_checksums(&self) -> Result<()> {
+        validate_file_lists(self, true).map_err(|e| match e {
+            DebianError::FileListInconsistent(msg) => {
+                DebianError::DebianChangesFileInconsistent(msg)
+            }
+            e => e,
+        })
+    }
+}
+
+/// A file to be recorded in a generated `.changes` file.
+#[derive(Clone, Debug)]
+struct ChangesArtifact {
+    filename: String,
+    size: u64,
+    digests: MultiContentDigest,
+    section: String,
+    priority: String,
+}
+
+/// Generate a `.changes` file from a set of built artifacts.
+///
+/// Binary packages added via [Self::add_deb()] contribute to the `Binary`, `Architecture`,
+/// and `Description` fields. A source package added via [Self::add_dsc()] adds the `source`
+/// architecture. Other files (e.g. source tarballs or `.buildinfo` files) can be added with
+/// [Self::add_file()].
+///
+/// The emitted file uses format `1.8` and records MD5, SHA-1, and SHA-256 digests of every
+/// file.
+#[derive(Clone, Debug)]
+pub struct DebianChangesFileBuilder {
+    source: String,
+    version: String,
+    maintainer: String,
+    changed_by: Option<String>,
+    distribution: String,
+    urgency: String,
+    date: Option<DateTime<Utc>>,
+    changes: Option<String>,
+    closes: Vec<String>,
+    has_source: bool,
+    binaries: BTreeMap<String, String>,
+    architectures: BTreeSet<String>,
+    files: Vec<ChangesArtifact>,
+}
+
+impl DebianChangesFileBuilder {
+    /// Create a new builder for an upload of a given source package and version.
+    ///
+    /// The distribution defaults to `unstable` and the urgency to `medium`.
+    pub fn new(source: impl ToString, version: impl ToString, maintainer: impl ToString) -> Self {
+        Self {
+            source: source.to_string(),
+            version: version.to_string(),
+            maintainer: maintainer.to_string(),
+            changed_by: None,
+            distribution: "unstable".to_string(),
+            urgency: "medium".to_string(),
+            date: None,
+            changes: None,
+            closes: vec![],
+            has_source: false,
+            binaries: BTreeMap::new(),
+            architectures: BTreeSet::new(),
+            files: vec![],
+        }
+    }
+
+    /// Set the person who prepared the upload (`Changed-By`).
+    #[must_use]
+    pub fn set_changed_by(mut self, value: impl ToString) -> Self {
+        self.changed_by = Some(value.to_string());
+        self
+    }
+
+    /// Set the distribution the upload targets.
+    #[must_use]
+    pub fn set_distribution(mut self, value: impl ToString) -> Self {
+        self.distribution = value.to_string();
+        self
+    }
+
+    /// Set the urgency of the upload.
+    #[must_use]
+    pub fn set_urgency(mut self, value: impl ToString) -> Self {
+        self.urgency = value.to_string();
+        self
+    }
+
+    /// Set the date of the upload.
+    ///
+    /// If not set, the current time is used when [Self::build()] is called.
+    #[must_use]
+    pub fn set_date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = Some(date);
+        self
+    }
+
+    /// Set the changelog entries for this upload.
+    ///
+    /// This is typically the `debian/changelog` entry being uploaded. It is required.
+    #[must_use]
+    pub fn set_changes(mut self, value: impl ToString) -> Self {
+        self.changes = Some(value.to_string());
+        self
+    }
+
+    /// Register a bug number closed by this upload.
+    #[must_use]
+    pub fn add_closes(mut self, bug: impl ToString) -> Self {
+        self.closes.push(bug.to_string());
+        self
+    }
+
+    /// Add an arbitrary file to the upload.
+    ///
+    /// `filename` must be a non-empty base name without whitespace.
+    pub fn add_file(
+        mut self,
+        filename: impl ToString,
+        data: &[u8],
+        section: impl ToString,
+        priority: impl ToString,
+    ) -> Result<Self> {
+        let filename = filename.to_string();
+
+        if filename.is_empty() || filename.contains(|c: char| c.is_whitespace() || c == '/') {
+            return Err(DebianError::DebianChangesFileInvalidFilename(filename));
+        }
+        if self.files.iter().any(|f| f.filename == filename) {
+            return Err(DebianError::DebianChangesFileInconsistent(format!(
+                "{} added multiple times",
+                filename
+            )));
+        }
+
+        let mut digester = MultiDigester::default();
+        digester.update(data);
+
+        self.files.push(ChangesArtifact {
+            filename,
+            size: data.len() as u64,
+            digests: digester.finish(),
+            section: section.to_string(),
+            priority: priority.to_string(),
+        });
+
+        Ok(self)
+    }
+
+    /// Add a source package's `.dsc` file to the upload.
+    ///
+    /// This adds the `source` architecture. Files referenced by the `.dsc` must be added
+    /// separately via [Self::add_file()].
+    pub fn add_dsc(
+        mut self,
+        filename: impl ToString,
+        data: &[u8],
+        section: impl ToString,
+        priority: impl ToString,
+    ) -> Result<Self> {
+        self.has_source = true;
+        self.add_file(filename, data, section, priority)
+    }
+
+    /// Add a binary package (`.deb` or `.udeb`) to the upload.
+    ///
+    /// The package's control file is read to obtain its name, architecture, section,
+    /// priority, and description synopsis. Missing sections and priorities are recorded
+    /// as `-`, like `dpkg-genchanges` does.
+    pub fn add_deb(mut self, filename: impl ToString, data: &[u8]) -> Result<Self> {
+        let cf = crate::deb::reader::resolve_control_file(std::io::Cursor::new(data))?;
+
+        let package = cf.package()?.to_string();
+        let synopsis = cf.synopsis()?.to_string();
+        self.architectures.insert(cf.architecture()?.to_string());
+        let section = cf.section().unwrap_or("-").to_string();
+        let priority = cf.priority().unwrap_or("-").to_string();
+        self.binaries.insert(package, synopsis);
+
+        self.add_file(filename, data, section, priority)
+    }
+
+    /// Build a [DebianChangesFile] from this builder.
+    pub fn build(&self) -> Result<DebianChangesFile<'static>> {
+        let changes = self
+            .changes
+            .as_ref()
+            .ok_or_else(|| DebianError::ControlRequiredFieldMissing("Changes".to_string()))?;
+
+        if self.files.is_empty() {
+            return Err(DebianError::DebianChangesFileInconsistent(
+                "upload contains no files".to_string(),
+            ));
+        }
+
+        let mut para = ControlParagraph::default();
+
+        let mut set = |name: &'static str, value: String| {
+            para.set_field_from_string(name.into(), value.into());
+        };
+
+        set("Format", "1.8".to_string());
+        set(
+            "Date",
+            format!("{}", self.date.unwrap_or_else(Utc::now).format(DATE_FORMAT)),
+        );
+        set("Source", self.source.clone());
+        if !self.binaries.is_empty() {
+            set(
+                "Binary",
+                self.binaries.keys().cloned().collect::<Vec<_>>().join(" "),
+            );
+        }
+        set(
+            "Architecture",
+            self.has_source
+                .then(|| "source".to_string())
+                .into_iter()
+                .chain(self.architectures.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        set("Version", self.version.clone());
+        set("Distribution", self.distribution.clone());
+        set("Urgency", self.urgency.clone());
+        set("Maintainer", self.maintainer.clone());
+        if let Some(changed_by) = &self.changed_by {
+            set("Changed-By", changed_by.clone());
+        }
+
+        if !self.binaries.is_empty() {
+            para.set_field(ControlField::from_lines(
+                "Description".into(),
+                std::iter::once("".to_string()).chain(
+                    self.binaries
+                        .iter()
+                        .map(|(package, synopsis)| format!("{} - {}", package, synopsis)),
+                ),
+            ));
+        }
+        if !self.closes.is_empty() {
+            para.set_field_from_string("Closes".into(), self.closes.join(" ").into());
+        }
+
+        para.set_field(ControlField::from_lines(
+            "Changes".into(),
+            std::iter::once("".to_string()).chain(changes.lines().map(|line| {
+                if line.trim().is_empty() {
+                    ".".to_string()
+                } else {
+                    line.to_string()
+                }
+            })),
+        ));
+
+        for checksum in [ChecksumType::Sha1, ChecksumType::Sha256, ChecksumType::Md5] {
+            para.set_field(file_list_field(
+                checksum,
+                self.files.iter().map(|f| FileListEntry {
+                    filename: &f.filename,
+                    digest: f.digests.digest_from_checksum(checksum).clone(),
+                    size: f.size,
+                    section: (checksum == ChecksumType::Md5).then_some(f.section.as_str()),
+                    priority: (checksum == ChecksumType::Md5).then_some(f.priority.as_str()),
+                }),
+            ));
+        }
+
+        Ok(para.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::io::ContentDigest};
+
+    const CHANGES: &str = "Format: 1.8
+Date: Sat, 02 Nov 2024 12:00:00 +0000
+Source: hello
+Binary: hello
+Architecture: source amd64
+Version: 2.10-3
+Distribution: unstable
+Urgency: medium
+Maintainer: Jane Doe <jane@example.com>
+Changes:
+ hello (2.10-3) unstable; urgency=medium
+ .
+   * New upload.
+Checksums-Sha256:
+ 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824 5 hello_2.10-3.dsc
+ 486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7 5 hello_2.10-3_amd64.deb
+Files:
+ 5d41402abc4b2a76b9719d911017c592 5 devel optional hello_2.10-3.dsc
+ 7d793037a0760186574b0282f2f435e7 5 devel optional hello_2.10-3_amd64.deb
+";
+
+    #[test]
+    fn parse() -> Result<()> {
+        let cf = DebianChangesFile::from_reader(std::io::Cursor::new(CHANGES.as_bytes()))?;
+
+        assert_eq!(cf.format()?, "1.8");
+        assert_eq!(cf.source()?, "hello");
+        assert_eq!(cf.version_str()?, "2.10-3");
+        assert_eq!(
+            cf.architecture().unwrap().collect::<Vec<_>>(),
+            vec!["source", "amd64"]
+        );
+        assert_eq!(
+            cf.distribution().unwrap().collect::<Vec<_>>(),
+            vec!["unstable"]
+        );
+        assert!(cf.date().unwrap().is_ok());
+        assert!(cf.checksums_sha1().is_none());
+
+        let files = cf.files()?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            files[1],
+            DebianChangesFileEntry {
+                filename: "hello_2.10-3_amd64.deb",
+                digest: ContentDigest::md5_hex("7d793037a0760186574b0282f2f435e7")?,
+                size: 5,
+                section: Some("devel"),
+                priority: Some("optional"),
+            }
+        );
+
+        let sha256 = cf.checksums_sha256().unwrap().collect::<Result<Vec<_>>>()?;
+        assert_eq!(sha256.len(), 2);
+        assert_eq!(sha256[0].filename, "hello_2.10-3.dsc");
+        assert_eq!(sha256[0].section, None);
+
+        cf.validate_checksums()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn validate_inconsistent() -> Result<()> {
+        let data = CHANGES.replace(
+            " 486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7 5 hello_2.10-3_amd64.deb\n",
+            "",
+        );
+        let cf = DebianChangesFile::from_reader(std::io::Cursor::new(data.as_bytes()))?;
+        assert!(matches!(
+            cf.validate_checksums(),
+            Err(DebianError::DebianChangesFileInconsistent(_))
+        ));
+
+        let data = CHANGES.replace("7 5 devel optional", "7 6 devel optional");
+        let cf = DebianChangesFile::from_reader(std::io::Cursor::new(data.as_bytes()))?;
+        assert!(matches!(
+            cf.validate_checksums(),
+            Err(DebianError::DebianChangesFileInconsistent(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn build() -> Result<()> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "hello".into());
+        para.set_field_from_string("Version".into(), "2.10-3".into());
+        para.set_field_from_string("Architecture".into(), "amd64".into());
+        para.set_field_from_string("Section".into(), "devel".into());
+        para.set_field_from_string("Description".into(), "example package\n more".into());
+        let mut control = crate::control::ControlFile::default();
+        control.add_paragraph(para);
+
+        let mut deb = vec![];
+        crate::deb::builder::DebBuilder::new(control).write(&mut deb)?;
+
+        let cf = DebianChangesFileBuilder::new("hello", "2.10-3", "Jane Doe <jane@example.com>")
+            .set_changes("hello (2.10-3) unstable; urgency=medium\n\n  * New upload.")
+            .add_dsc("hello_2.10-3.dsc", b"dsc", "devel", "optional")?
+            .add_file("hello_2.10.orig.tar.gz", b"tarball", "devel", "optional")?
+            .add_deb("hello_2.10-3_amd64.deb", &deb)?
+            .build()?;
+
+        let mut buffer = vec![];
+        cf.write(&mut buffer)?;
+        let cf = DebianChangesFile::from_reader(std::io::Cursor::new(buffer))?;
+
+        cf.validate_checksums()?;
+        assert_eq!(cf.format()?, "1.8");
+        assert!(cf.date().unwrap().is_ok());
+        assert_eq!(cf.binary().unwrap().collect::<Vec<_>>(), vec!["hello"]);
+        assert_eq!(
+            cf.architecture().unwrap().collect::<Vec<_>>(),
+            vec!["source", "amd64"]
+        );
+        assert_eq!(cf.field_str("Description"), Some("hello - example package"));
+
+        let files = cf.files()?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[2].section, Some("devel"));
+        assert_eq!(files[2].priority, Some("-"));
+        assert_eq!(files[2].size, deb.len() as u64);
+        assert_eq!(cf.checksums_sha1().unwrap().count(), 3);
+
+        assert!(matches!(
+            DebianChangesFileBuilder::new("hello", "1", "me").build(),
+            Err(DebianError::ControlRequiredFieldMissing(_))
+        ));
+        for filename in ["", "has space.deb", "dir/hello.deb"] {
+            assert!(matches!(
+                DebianChangesFileBuilder::new("hello", "1", "me").add_file(filename, b"", "-", "-"),
+                Err(DebianError::DebianChangesFileInvalidFilename(_))
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Built-in package checks. */
+
+use crate::checks::{Finding, PackageCheck, PackageContents, Severity};
+
+/// Fields every binary package control file must define.
+///
+/// See Debian Policy section 5.3.
+pub const MANDATORY_FIELDS: &[&str] = &[
+    "Package",
+    "Version",
+    "Architecture",
+    "Maintainer",
+    "Description",
+];
+
+/// Fields whose value must be `yes` or `no`.
+pub const YES_NO_FIELDS: &[&str] = &["Essential", "Protected", "Build-Essential"];
+
+/// Archive areas that may prefix a section, e.g. `contrib/net`.
+pub const ARCHIVE_AREAS: &[&str] = &["main", "contrib", "non-free", "non-free-firmware"];
+
+/// Sections defined by Debian Policy section 2.4.
+pub const POLICY_SECTIONS: &[&str] = &[
+    "admin",
+    "cli-mono",
+    "comm",
+    "database",
+    "debian-installer",
+    "debug",
+    "devel",
+    "doc",
+    "editors",
+    "education",
+    "electronics",
+    "embedded",
+    "fonts",
+    "games",
+    "gnome",
+    "gnu-r",
+    "gnustep",
+    "graphics",
+    "hamradio",
+    "haskell",
+    "httpd",
+    "interpreters",
+    "introspection",
+    "java",
+    "javascript",
+    "kde",
+    "kernel",
+    "libdevel",
+    "libs",
+    "lisp",
+    "localization",
+    "mail",
+    "math",
+    "metapackages",
+    "misc",
+    "net",
+    "news",
+    "ocaml",
+    "oldlibs",
+    "otherosfs",
+    "perl",
+    "php",
+    "python",
+    "ruby",
+    "rust",
+    "science",
+    "shells",
+    "sound",
+    "tasks",
+    "tex",
+    "text",
+    "utils",
+    "vcs",
+    "video",
+    "web",
+    "x11",
+    "xfce",
+    "zope",
+];
+
+/// Directories whose executables are expected to be ELF binaries or `#!` scripts.
+const BIN_DIRECTORIES: &[&str] = &["bin", "sbin", "usr/bin", "usr/sbin", "usr/games"];
+
+/// Obtain instances of all built-in checks.
+pub fn builtin_checks() -> Vec<Box<dyn PackageCheck>> {
+    vec![
+        Box::new(MandatoryFieldsCheck),
+        Box::new(SectionCheck),
+        Box::new(YesNoFieldCheck),
+        Box::new(ScriptShebangCheck),
+        Box::new(UsrLocalCheck),
+        Box::new(WorldWritableCheck),
+    ]
+}
+
+/// Reports [MANDATORY_FIELDS] missing from the control file.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MandatoryFieldsCheck;
+
+impl PackageCheck for MandatoryFieldsCheck {
+    fn name(&self) -> &'static str {
+        "missing-mandatory-field"
+    }
+
+    fn check(&self, package: &PackageContents) -> Vec<Finding> {
+        MANDATORY_FIELDS
+            .iter()
+            .filter(|field| {
+                package
+                    .control
+                    .field_str(field)
+                    .map_or(true, |v| v.trim().is_empty())
+            })
+            .map(|field| {
+                Finding::new(
+                    self.name(),
+                    Severity::Error,
+                    format!("control file lacks the {} field", field),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Reports a `Section` not in [POLICY_SECTIONS].
+///
+/// Sections may be prefixed by one of [ARCHIVE_AREAS]. A missing `Section` is reported
+/// as informational.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SectionCheck;
+
+impl PackageCheck for SectionCheck {
+    fn name(&self) -> &'static str {
+        "unknown-section"
+    }
+
+    fn check(&self, package: &PackageContents) -> Vec<Finding> {
+        let (section, name) = match (package.control.section(), package.control.typed_section()) {
+            (Some(section), Some(name)) => (section, name),
+            _ => {
+                return vec![Finding::new(
+                    self.name(),
+                    Severity::Info,
+                    "control file lacks a Section field",
+                )]
+            }
+        };
+
+        let mut res = vec![];
+        if let Some(area) = package.control.archive_area() {
+            if !ARCHIVE_AREAS.contains(&area) {
+                res.push(Finding::new(
+                    self.name(),
+                    Severity::Warning,
+                    format!("section {} has unknown archive area {}", section, area),
+                ));
+            }
+        }
+        if !name.is_known() {
+            res.push(Finding::new(
+                self.name(),
+                Severity::Warning,
+                format!("section {} is not defined by Debian Policy", section),
+            ));
+        }
+
+        res
+    }
+}
+
+/// Reports [YES_NO_FIELDS] whose value is not `yes` or `no`.
+///
+/// A `no` value is the default and is reported as informational.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct YesNoFieldCheck;
+
+impl PackageCheck for YesNoFieldCheck {
+    fn name(&self) -> &'static str {
+        "invalid-yes-no-field"
+    }
+
+    fn check(&self, package: &PackageContents) -> Vec<Finding> {
+        YES_NO_FIELDS
+            .iter()
+            .filter_map(|field| match package.control.field_yes_no(field)? {
+                Ok(true) => None,
+                Ok(false) => Some(Finding::new(
+                    self.name(),
+                    Severity::Info,
+                    format!("{}: no is the default and can be omitted", field),
+                )),
+                Err(e) => Some(Finding::new(self.name(), Severity::Error, e)),
+            })
+            .collect()
+    }
+}
+
+/// Reports scripts that lack a `#!` interpreter line.
+///
+/// Maintainer scripts must start with `#!`. Executables in `bin` and `sbin` directories
+/// which are neither ELF binaries nor `#!` scripts are reported as warnings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScriptShebangCheck;
+
+impl PackageCheck for ScriptShebangCheck {
+    fn name(&self) -> &'static str {
+        "script-without-shebang"
+    }
+
+    fn check(&self, package: &PackageContents) -> Vec<Finding> {
+        let mut res = package
+            .maintainer_scripts
+            .iter()
+            .filter(|(_, data)| !data.starts_with(b"#!"))
+            .map(|(script, _)| {
+                Finding::new(
+                    self.name(),
+                    Severity::Error,
+                    "maintainer script does not begin with #!",
+                )
+                .set_path(script.filename())
+            })
+            .collect::<Vec<_>>();
+
+        res.extend(
+            package
+                .files
+                .iter()
+                .filter(|file| {
+                    file.info.entry_type.is_file()
+                        && file.info.mode & 0o111 != 0
+                        && file
+                            .info
+                            .path
+                            .rsplit_once('/')
+                            .is_some_and(|(dir, _)| BIN_DIRECTORIES.contains(&dir))
+                        && !file.head.starts_with(b"#!")
+                        && !file.head.starts_with(b"\x7fELF")
+                })
+                .map(|file| {
+                    Finding::new(
+                        self.name(),
+                        Severity::Warning,
+                        "executable is neither an ELF binary nor begins with #!",
+                    )
+                    .set_path(&file.info.path)
+                }),
+        );
+
+        res
+    }
+}
+
+/// Reports content installed to `/usr/local`, which is reserved for the local administrator.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UsrLocalCheck;
+
+impl PackageCheck for UsrLocalCheck {
+    fn name(&self) -> &'static str {
+        "file-in-usr-local"
+    }
+
+    fn check(&self, package: &PackageContents) -> Vec<Finding> {
+        package
+            .files
+            .iter()
+            .filter(|file| {
+                file.info.path == "usr/local" || file.info.path.starts_with("usr/local/")
+            })
+            .map(|file| {
+                Finding::new(
+                    self.name(),
+                    Severity::Error,
+                    "packages must not install to /usr/local",
+                )
+                .set_path(&file.info.path)
+            })
+            .collect()
+    }
+}
+
+/// Reports world-writable files and directories.
+///
+/// Symlinks and directories with the sticky bit (like `/tmp`) are exempt.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WorldWritableCheck;
+
+impl PackageCheck for WorldWritableCheck {
+    fn name(&self) -> &'static str {
+        "world-writable-file"
+    }
+
+    fn check(&self, package: &PackageContents) -> Vec<Finding> {
+        package
+            .files
+            .iter()
+            .filter(|file| {
+                let entry_type = file.info.entry_type;
+
+                file.info.mode & 0o002 != 0
+                    && !entry_type.is_symlink()
+                    && !(entry_type.is_dir() && file.info.mode & 0o1000 != 0)
+            })
+            .map(|file| {
+                Finding::new(
+                    self.name(),
+                    Severity::Error,
+                    format!("mode {:04o} is world-writable", file.info.mode),
+                )
+                .set_path(&file.info.path)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            checks::PackageChecker,
+            control::{ControlFile, ControlParagraph},
+            deb::{builder::DebBuilder, MaintainerScript},
+            error::Result,
+        },
+        simple_file_manifest::FileEntry,
+    };
+
+    #[test]
+    fn builtin() -> Result<()> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "hello".into());
+        para.set_field_from_string("Version".into(), "1.0".into());
+        para.set_field_from_string("Architecture".into(), "all".into());
+        para.set_field_from_string("Section".into(), "contrib/bogus".into());
+        para.set_field_from_string("Essential".into(), "maybe".into());
+        para.set_field_from_string("Protected".into(), "no".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(para);
+
+        let mut deb = vec![];
+        DebBuilder::new(control)
+            .set_check_maintainer_scripts(false)
+            .add_maintainer_script(MaintainerScript::Postinst, b"set -e\n".to_vec())?
+            .install_file(
+                "usr/bin/hello",
+                FileEntry::new_from_data(b"#!/bin/sh\n".to_vec(), true),
+            )?
+            .install_file(
+                "usr/bin/data",
+                FileEntry::new_from_data(b"data".to_vec(), true),
+            )?
+            .install_file(
+                "usr/local/share/hello",
+                FileEntry::new_from_data(vec![], false),
+            )?
+            .install_file("var/log/hello", FileEntry::new_from_data(vec![], false))?
+            .set_path_mode("var/log/hello", 0o666)
+            .write(&mut deb)?;
+
+        let findings = PackageChecker::default().check_deb(std::io::Cursor::new(&deb))?;
+        let summary = findings
+            .iter()
+            .map(|f| (f.check, f.severity, f.path.as_deref()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("missing-mandatory-field", Severity::Error, None),
+                ("missing-mandatory-field", Severity::Error, None),
+                ("unknown-section", Severity::Warning, None),
+                ("invalid-yes-no-field", Severity::Error, None),
+                ("invalid-yes-no-field", Severity::Info, None),
+                ("script-without-shebang", Severity::Error, Some("postinst")),
+                (
+                    "script-without-shebang",
+                    Severity::Warning,
+                    Some("usr/bin/data")
+                ),
+                ("file-in-usr-local", Severity::Error, Some("usr/local")),
+                (
+                    "file-in-usr-local",
+                    Severity::Error,
+                    Some("usr/local/share")
+                ),
+                (
+                    "file-in-usr-local",
+                    Severity::Error,
+                    Some("usr/local/share/hello")
+                ),
+                (
+                    "world-writable-file",
+                    Severity::Error,
+                    Some("var/log/hello")
+                ),
+            ]
+        );
+
+        let findings = PackageChecker::default()
+            .remove_check("file-in-usr-local")
+            .check_deb(std::io::Cursor::new(&deb))?;
+        assert!(findings.iter().all(|f| f.check != "file-in-usr-local"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Quality checks for binary packages.
+
+This module implements a small, [lintian](https://lintian.debian.org/)-like framework for
+detecting common packaging mistakes.
+
+A `.deb` is read once into a [PackageContents], which is handed to each registered
+[PackageCheck]. Checks emit [Finding]s tagged with a [Severity]. [PackageChecker] holds a
+set of checks (the [builtin] checks by default) and runs them against individual `.deb`
+files or every binary package in a repository.
+*/
+
+pub mod builtin;
+
+use {
+    crate::{
+        binary_package_control::BinaryPackageControlFile,
+        deb::{
+            reader::{BinaryPackageReader, BinaryPackageStreamingEntry, ControlTarFile},
+            search::{normalize_path, DataFileMatch},
+            MaintainerScript,
+        },
+        error::{DebianError, Result},
+        repository::{ReleaseReader, RepositoryRootReader},
+    },
+    futures::{AsyncReadExt, StreamExt, TryStreamExt},
+    std::io::{Cursor, Read},
+};
+
+/// Number of leading content bytes of regular files retained in [PackageFile::head].
+pub const FILE_HEAD_SIZE: u64 = 128;
+
+/// The severity of a [Finding].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// Informational. Not necessarily a problem.
+    Info,
+    /// Likely a problem, but the package is usable.
+    Warning,
+    /// A violation of policy that should be fixed.
+    Error,
+}
+
+impl Severity {
+    /// The lowercase name of this severity.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A problem reported by a [PackageCheck].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Finding {
+    /// Name of the check that emitted this finding.
+    pub check: &'static str,
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// Human readable description of the problem.
+    pub message: String,
+    /// The installed path or control member the finding relates to, if any.
+    pub path: Option<String>,
+}
+
+impl Finding {
+    /// Construct a finding not related to a specific path.
+    pub fn new(check: &'static str, severity: Severity, message: impl ToString) -> Self {
+        Self {
+            check,
+            severity,
+            message: message.to_string(),
+            path: None,
+        }
+    }
+
+    /// Set the path this finding relates to.
+    #[must_use]
+    pub fn set_path(mut self, path: impl ToString) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.severity, self.check)?;
+        if let Some(path) = &self.path {
+            write!(f, " {}", path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// A file in the `data.tar` archive of a package.
+#[derive(Clone, Debug)]
+pub struct PackageFile {
+    /// Metadata of the archive entry.
+    pub info: DataFileMatch,
+    /// The first [FILE_HEAD_SIZE] bytes of content. Empty for non-regular files.
+    pub head: Vec<u8>,
+}
+
+/// The content of a binary package relevant to checks.
+#[derive(Clone, Debug)]
+pub struct PackageContents {
+    /// The parsed `control` file.
+    pub control: BinaryPackageControlFile<'static>,
+    /// Maintainer scripts and their content, in archive order.
+    pub maintainer_scripts: Vec<(MaintainerScript, Vec<u8>)>,
+    /// Entries in the `data.tar` archive, in archive order.
+    pub files: Vec<PackageFile>,
+}
+
+impl PackageContents {
+    /// Read a `.deb` into an instance.
+    ///
+    /// Only the leading bytes of installed files are retained, so memory use is
+    /// proportional to the number of files rather than their size.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        let mut reader = BinaryPackageReader::new(reader)?;
+
+        let mut control = None;
+        let mut maintainer_scripts = vec![];
+        let mut files = vec![];
+
+        while let Some(entry) = reader.next_entry_streaming() {
+            match entry? {
+                BinaryPackageStreamingEntry::DebianBinary(_) => {}
+                BinaryPackageStreamingEntry::Control(mut tar) => {
+                    for entry in tar.entries()? {
+                        let mut entry = entry?;
+                        let path = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+                        let script = MaintainerScript::ALL
+                            .into_iter()
+                            .find(|s| s.filename() == path.trim_start_matches("./"));
+
+                        match (entry.to_control_file()?.1, script) {
+                            (ControlTarFile::Control(cf), _) => {
+                                control = Some(cf);
+                            }
+                            (
+                                ControlTarFile::Preinst(data)
+                                | ControlTarFile::Postinst(data)
+                                | ControlTarFile::Prerm(data)
+                                | ControlTarFile::Postrm(data)
+                                | ControlTarFile::Other(_, data),
+                                Some(script),
+                            ) => {
+                                maintainer_scripts.push((script, data));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                BinaryPackageStreamingEntry::Data(mut tar) => {
+                    for entry in tar.entries()? {
+                        let mut entry = entry?;
+
+                        let path = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+                        let path = normalize_path(&path).trim_end_matches('/');
+                        if path.is_empty() {
+                            continue;
+                        }
+
+                        let info = DataFileMatch::from_entry(path.into(), &entry)?;
+                        let mut head = vec![];
+                        if info.entry_type.is_file() {
+                            (&mut entry).take(FILE_HEAD_SIZE).read_to_end(&mut head)?;
+                        }
+
+                        files.push(PackageFile { info, head });
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            control: control.ok_or(DebianError::ControlFileNotFound)?,
+            maintainer_scripts,
+            files,
+        })
+    }
+}
+
+/// A check run against binary packages.
+pub trait PackageCheck: Send + Sync {
+    /// A short, unique, kebab-case name for this check.
+    fn name(&self) -> &'static str;
+
+    /// Examine a package, returning problems found.
+    fn check(&self, package: &PackageContents) -> Vec<Finding>;
+}
+
+/// Findings for a binary package in a repository.
+#[derive(Clone, Debug)]
+pub struct PackageFindings {
+    /// The package name.
+    pub package: String,
+    /// The package version.
+    pub version: String,
+    /// The package architecture.
+    pub architecture: String,
+    /// Path of the `.deb` relative to the repository root.
+    pub path: String,
+    /// Problems found in the package.
+    pub findings: Vec<Finding>,
+}
+
+/// Runs a set of [PackageCheck] against packages.
+///
+/// The default instance runs all [builtin] checks.
+pub struct PackageChecker {
+    checks: Vec<Box<dyn PackageCheck>>,
+}
+
+impl Default for PackageChecker {
+    fn default() -> Self {
+        Self {
+            checks: builtin::builtin_checks(),
+        }
+    }
+}
+
+impl PackageChecker {
+    /// Construct an instance without any checks.
+    pub fn empty() -> Self {
+        Self { checks: vec![] }
+    }
+
+    /// Register an additional check.
+    #[must_use]
+    pub fn add_check(mut self, check: impl PackageCheck + 'static) -> Self {
+        self.checks.push(Box::new(check));
+        self
+    }
+
+    /// Remove the check having the given name.
+    #[must_use]
+    pub fn remove_check(mut self, name: &str) -> Self {
+        self.checks.retain(|check| check.name() != name);
+        self
+    }
+
+    /// Names of registered checks, in the order they run.
+    pub fn check_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.checks.iter().map(|check| check.name())
+    }
+
+    /// Run all checks against package contents.
+    pub fn check_package(&self, package: &PackageContents) -> Vec<Finding> {
+        self.checks
+            .iter()
+            .flat_map(|check| check.check(package))
+            .collect()
+    }
+
+    /// Run all checks against a `.deb`.
+    pub fn check_deb(&self, reader: impl Read) -> Result<Vec<Finding>> {
+        Ok(self.check_package(&PackageContents::from_reader(reader)?))
+    }
+
+    /// Run all checks against every binary package in a repository release.
+    ///
+    /// Packages are fetched from `root` with up to `threads` concurrent fetches. Results
+    /// are sorted by package path.
+    pub async fn check_repository(
+        &self,
+        root: &dyn RepositoryRootReader,
+        release: &dyn ReleaseReader,
+        threads: usize,
+    ) -> Result<Vec<PackageFindings>> {
+        let fetches = release
+            .resolve_package_fetches(Box::new(|_| true), Box::new(|_| true), threads)
+            .await?;
+
+        let fs = fetches
+            .into_iter()
+            .map(|fetch| async move {
+                let package = fetch.control_file.package()?.to_string();
+                let version = fetch.control_file.version_str()?.to_string();
+                let architecture = fetch.control_file.architecture()?.to_string();
+                let path = fetch.path.clone();
+
+                let mut reader = root.fetch_binary_package_generic(fetch).await?;
+                let mut data = vec![];
+                reader.read_to_end(&mut data).await?;
+
+                Ok::<_, DebianError>(PackageFindings {
+                    package,
+                    version,
+                    architecture,
+                    path,
+                    findings: self.check_deb(Cursor::new(data))?,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut res = futures::stream::iter(fs)
+            .buffer_unordered(threads)
+            .try_collect::<Vec<_>>()
+            .await?;
+        res.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(res)
+    }
+}
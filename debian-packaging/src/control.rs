@@ -12,19 +12,150 @@ use {
     crate::{
         dependency::DependencyList,
         error::{DebianError, Result},
+        intern,
     },
     chrono::{DateTime, TimeZone, Utc},
-    futures::{AsyncBufRead, AsyncBufReadExt},
+    futures::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     pin_project::pin_project,
     std::{
         borrow::Cow,
         collections::HashMap,
         fmt::Display,
-        io::{BufRead, Write},
+        io::{BufRead, Read, Write},
         str::FromStr,
     },
 };
 
+/// Limits applied when parsing potentially untrusted input.
+///
+/// Control files, `Release` files and `Contents` files are fetched from remote repositories,
+/// which may be malicious. Without limits, a single unterminated line or endless paragraph
+/// can exhaust memory before any content is validated.
+///
+/// The default limits are far larger than legitimate input but bound the memory used by a
+/// single line, field or paragraph, as well as the total size of input. The number of entries
+/// is unlimited by default, as it is bounded by the total size.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParseLimits {
+    /// Maximum size in bytes of a field, including continuation lines and line terminators.
+    ///
+    /// This also bounds the length of individual lines.
+    pub max_field_length: usize,
+    /// Maximum size in bytes of a paragraph.
+    pub max_paragraph_size: usize,
+    /// Maximum number of entries, such as paragraphs in a control file or lines in a
+    /// `Contents` file.
+    pub max_entries: usize,
+    /// Maximum total size in bytes of input.
+    pub max_total_size: u64,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_field_length: 16 * 1024 * 1024,
+            max_paragraph_size: 64 * 1024 * 1024,
+            max_entries: usize::MAX,
+            max_total_size: 4 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+impl ParseLimits {
+    /// Limits that never trigger.
+    ///
+    /// Only use this for trusted input.
+    pub fn unlimited() -> Self {
+        Self {
+            max_field_length: usize::MAX,
+            max_paragraph_size: usize::MAX,
+            max_entries: usize::MAX,
+            max_total_size: u64::MAX,
+        }
+    }
+
+    /// Error if an entry count exceeds [Self::max_entries].
+    pub(crate) fn check_entries(&self, count: usize) -> Result<()> {
+        if count > self.max_entries {
+            Err(DebianError::ParseTooManyEntries(self.max_entries))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Error if a total input size exceeds [Self::max_total_size].
+    pub(crate) fn check_total_size(&self, size: u64) -> Result<()> {
+        if size > self.max_total_size {
+            Err(DebianError::ParseInputTooLarge(self.max_total_size))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Read all remaining data from an async reader, up to [Self::max_total_size] bytes.
+    pub(crate) async fn read_to_end_async(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        buf: &mut Vec<u8>,
+    ) -> Result<usize> {
+        let size = reader
+            .take(self.max_total_size.saturating_add(1))
+            .read_to_end(buf)
+            .await?;
+        self.check_total_size(size as u64)?;
+
+        Ok(size)
+    }
+
+    /// Read all remaining UTF-8 data from an async reader, up to [Self::max_total_size] bytes.
+    pub(crate) async fn read_to_string_async(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        buf: &mut String,
+    ) -> Result<usize> {
+        let size = reader
+            .take(self.max_total_size.saturating_add(1))
+            .read_to_string(buf)
+            .await?;
+        self.check_total_size(size as u64)?;
+
+        Ok(size)
+    }
+
+    /// Read a line of at most [Self::max_field_length] bytes.
+    pub(crate) fn read_line(&self, reader: &mut impl BufRead, line: &mut String) -> Result<usize> {
+        let size = reader
+            .take(self.max_field_length.saturating_add(1) as u64)
+            .read_line(line)?;
+        self.check_line(line)?;
+
+        Ok(size)
+    }
+
+    /// Read a line of at most [Self::max_field_length] bytes from an async reader.
+    pub(crate) async fn read_line_async(
+        &self,
+        reader: &mut (impl AsyncBufRead + Unpin),
+        line: &mut String,
+    ) -> Result<usize> {
+        let size = reader
+            .take(self.max_field_length.saturating_add(1) as u64)
+            .read_line(line)
+            .await?;
+        self.check_line(line)?;
+
+        Ok(size)
+    }
+
+    fn check_line(&self, line: &str) -> Result<()> {
+        if line.len() > self.max_field_length {
+            Err(DebianError::ParseFieldTooLong(self.max_field_length))
+        } else {
+            Ok(())
+        }
+    }
+}
+
 /// A field value in a control file.
 ///
 /// This represents the value after the colon (`:`) in field definitions.
@@ -112,6 +243,31 @@ impl<'a> ControlField<'a> {
         Self { name, value }
     }
 
+    /// Obtain an instance not borrowing from any input, copying borrowed data.
+    pub fn into_owned(self) -> ControlField<'static> {
+        ControlField {
+            name: Cow::Owned(self.name.into_owned()),
+            value: Cow::Owned(self.value.into_owned()),
+        }
+    }
+
+    /// Obtain an instance not borrowing from any input, using static copies of well-known
+    /// names and values instead of copying them.
+    ///
+    /// See [crate::intern].
+    pub fn into_interned(self) -> ControlField<'static> {
+        let name = match intern::field_name(&self.name) {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(self.name.into_owned()),
+        };
+        let value = match intern::field_value(&name, &self.value) {
+            Some(value) => Cow::Borrowed(value),
+            None => Cow::Owned(self.value.into_owned()),
+        };
+
+        ControlField { name, value }
+    }
+
     /// Construct an instance from an iterable of lines.
     ///
     /// Each line should not have leading whitespace.
@@ -196,12 +352,28 @@ impl<'a> Display for ControlField<'a> {
 
 /// A paragraph in a control file.
 ///
+/// Whether field names are equal, ignoring case.
+///
+/// Names are almost always ASCII, which is compared without allocating.
+fn field_names_equal(a: &str, b: &str) -> bool {
+    if a.eq_ignore_ascii_case(b) {
+        true
+    } else if a.is_ascii() && b.is_ascii() {
+        false
+    } else {
+        a.to_lowercase() == b.to_lowercase()
+    }
+}
+
 /// A paragraph is an ordered series of control fields.
 ///
 /// Field names are case insensitive on read and case preserving on set.
 ///
 /// Paragraphs can only contain a single occurrence of a field and this is enforced through
 /// the mutation APIs.
+///
+/// With the `serde` feature, instances serialize as a map of field names to values,
+/// preserving field order.
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct ControlParagraph<'a> {
     fields: Vec<ControlField<'a>>,
@@ -221,7 +393,7 @@ impl<'a> ControlParagraph<'a> {
     /// will be replaced by the incoming value.
     pub fn set_field(&mut self, field: ControlField<'a>) {
         self.fields
-            .retain(|cf| cf.name.to_lowercase() != field.name.to_lowercase());
+            .retain(|cf| !field_names_equal(&cf.name, &field.name));
         self.fields.push(field);
     }
 
@@ -233,6 +405,58 @@ impl<'a> ControlParagraph<'a> {
         self.set_field(ControlField::new(name, value));
     }
 
+    /// Obtain an instance not borrowing from any input, copying borrowed data.
+    pub fn into_owned(self) -> ControlParagraph<'static> {
+        ControlParagraph {
+            fields: self.fields.into_iter().map(|f| f.into_owned()).collect(),
+        }
+    }
+
+    /// Obtain an instance not borrowing from any input, using static copies of well-known
+    /// names and values instead of copying them.
+    ///
+    /// Excess capacity is also released. This minimizes the memory used by paragraphs
+    /// retained in bulk, such as those from `Packages` files. See [crate::intern].
+    pub fn into_interned(self) -> ControlParagraph<'static> {
+        let mut fields = self
+            .fields
+            .into_iter()
+            .map(|f| f.into_interned())
+            .collect::<Vec<_>>();
+        fields.shrink_to_fit();
+
+        ControlParagraph { fields }
+    }
+
+    /// Construct an empty instance with room for `capacity` fields.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            fields: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Add a field while building a paragraph, replacing any field of the same name.
+    ///
+    /// Duplicate fields are rare. `seen` is a bitmask of hashes of the ASCII field names
+    /// added so far, starting at 0, which rules out most duplicates without searching
+    /// the paragraph.
+    pub(crate) fn add_field(&mut self, seen: &mut u64, field: ControlField<'a>) {
+        let name = field.name();
+        let first = name.bytes().next().unwrap_or_default().to_ascii_lowercase();
+        let bit = 1 << ((name.len() * 31 + first as usize) % 64);
+
+        if name.is_ascii() && *seen & bit == 0 {
+            *seen |= bit;
+            self.fields.push(field);
+        } else {
+            // Non-ASCII names may equal names of other lengths when lowercased.
+            if !name.is_ascii() {
+                *seen = u64::MAX;
+            }
+            self.set_field(field);
+        }
+    }
+
     /// Whether a named field is present in this paragraph.
     pub fn has_field(&self, name: &str) -> bool {
         self.field(name).is_some()
@@ -249,14 +473,14 @@ impl<'a> ControlParagraph<'a> {
     pub fn field(&self, name: &str) -> Option<&'_ ControlField<'a>> {
         self.fields
             .iter()
-            .find(|f| f.name.as_ref().to_lowercase() == name.to_lowercase())
+            .find(|f| field_names_equal(&f.name, name))
     }
 
     /// Obtain a mutable reference to the field with a given name.
     pub fn field_mut(&mut self, name: &str) -> Option<&'a mut ControlField> {
         self.fields
             .iter_mut()
-            .find(|f| f.name.as_ref().to_lowercase() == name.to_lowercase())
+            .find(|f| field_names_equal(&f.name, name))
     }
 
     /// Obtain the named field and error if it isn't defined.
@@ -282,6 +506,21 @@ impl<'a> ControlParagraph<'a> {
         self.field_str(name).map(|v| matches!(v, "yes"))
     }
 
+    /// Obtain the value of a field, strictly parsed as a `yes` or `no` boolean.
+    ///
+    /// Fields like `Essential` and `Protected` must have one of these values. Other values
+    /// are an error.
+    pub fn field_yes_no(&self, name: &str) -> Option<Result<bool>> {
+        self.field_str(name).map(|v| match v.trim() {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            v => Err(DebianError::ControlFieldBoolParse(
+                name.to_string(),
+                v.to_string(),
+            )),
+        })
+    }
+
     /// Obtain the value of a field, evaluated as a [u64].
     pub fn field_u64(&self, name: &str) -> Option<Result<u64>> {
         self.field_str(name).map(|x| {
@@ -383,55 +622,332 @@ impl<'a> Display for ControlParagraph<'a> {
     }
 }
 
+/// Where in its input a control file parse error occurred.
+///
+/// Parse errors from [ControlFileParser] and the paragraph readers are reported as
+/// [DebianError::ControlParseAt], which holds an instance of this type.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ParseLocation {
+    /// Description of the input, such as a path or URL.
+    pub source: Option<String>,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number, if known.
+    pub column: Option<usize>,
+    /// Name of the field being parsed, if known.
+    pub field: Option<String>,
+}
+
+impl Display for ParseLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            self.source.as_deref().unwrap_or("<input>"),
+            self.line
+        )?;
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, " (field {})", field)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parser state spanning paragraphs.
+#[derive(Clone, Debug, Default)]
+struct ParseState {
+    limits: ParseLimits,
+    source: Option<String>,
+    /// Number of lines consumed.
+    line_number: usize,
+    /// Number of paragraphs emitted.
+    paragraph_count: usize,
+    /// Number of bytes consumed.
+    bytes: u64,
+}
+
+impl ParseState {
+    /// Wrap an error with the location it occurred at.
+    fn locate(
+        &self,
+        error: DebianError,
+        line: usize,
+        column: Option<usize>,
+        field: Option<&str>,
+    ) -> DebianError {
+        DebianError::ControlParseAt(
+            Box::new(ParseLocation {
+                source: self.source.clone(),
+                line,
+                column,
+                field: field.map(|s| s.to_string()),
+            }),
+            Box::new(error),
+        )
+    }
+
+    /// Count a consumed line, enforcing [ParseLimits::max_total_size].
+    fn count_line(&mut self, line: &str) -> Result<()> {
+        self.line_number += 1;
+        self.bytes = self.bytes.saturating_add(line.len() as u64);
+        self.limits
+            .check_total_size(self.bytes)
+            .map_err(|e| self.locate(e, self.line_number, None, None))
+    }
+
+    /// Count an emitted paragraph, enforcing [ParseLimits::max_entries].
+    fn count_paragraph(&mut self) -> Result<()> {
+        self.paragraph_count += 1;
+        self.limits
+            .check_entries(self.paragraph_count)
+            .map_err(|e| self.locate(e, self.line_number, None, None))
+    }
+
+    /// Parse a field spanning `v`, which started on line `start`.
+    fn parse_field<'a>(&self, v: &'a str, start: usize) -> Result<ControlField<'a>> {
+        let first_line = v.lines().next().unwrap_or_default().trim_end();
+
+        let Some(colon) = memchr::memchr(b':', v.as_bytes()) else {
+            return Err(self.locate(
+                DebianError::ControlParseError(format!(
+                    "error parsing line '{}'; missing colon",
+                    first_line
+                )),
+                start,
+                Some(first_line.chars().count() + 1),
+                None,
+            ));
+        };
+
+        let name = &v[..colon];
+        if name.trim().is_empty() {
+            return Err(self.locate(
+                DebianError::ControlParseError(format!(
+                    "error parsing line '{}'; missing field name",
+                    first_line
+                )),
+                start,
+                Some(1),
+                None,
+            ));
+        }
+
+        Ok(ControlField::new(
+            Cow::Borrowed(name),
+            Cow::Borrowed(v[colon + 1..].trim()),
+        ))
+    }
+
+    /// Parse the next paragraph from `data`, starting at byte offset `position`.
+    ///
+    /// `position` is advanced past the consumed lines, including the empty line terminating
+    /// the paragraph. Fields borrow from `data`.
+    fn next_paragraph<'a>(
+        &mut self,
+        data: &'a str,
+        position: &mut usize,
+    ) -> Result<Option<ControlParagraph<'a>>> {
+        let mut paragraph = ControlParagraph::default();
+        let mut paragraph_size = 0;
+        let mut seen = 0;
+        // Byte range and starting line of the in-progress field.
+        let mut field: Option<(usize, usize, usize)> = None;
+
+        while *position < data.len() {
+            let start = *position;
+            let end = memchr::memchr(b'\n', &data.as_bytes()[start..])
+                .map_or(data.len(), |i| start + i + 1);
+            let line = &data[start..end];
+            *position = end;
+            self.count_line(line)?;
+
+            let is_empty_line = line.trim().is_empty();
+            let is_indented = line.starts_with(' ') && line.len() > 1;
+
+            if let Err(e) = self.limits.check_line(line) {
+                let name = match field {
+                    Some((s, e, _)) if is_indented => field_name(&data[s..e]),
+                    _ => field_name(line),
+                };
+                return Err(self.locate(e, self.line_number, None, name));
+            }
+
+            // Empty lines signify the end of a paragraph.
+            if is_empty_line {
+                if let Some((s, e, line)) = field.take() {
+                    paragraph.add_field(&mut seen, self.parse_field(&data[s..e], line)?);
+                }
+
+                if paragraph.is_empty() {
+                    paragraph_size = 0;
+                    continue;
+                }
+
+                self.count_paragraph()?;
+                return Ok(Some(paragraph));
+            }
+
+            paragraph_size += line.len();
+            if paragraph_size > self.limits.max_paragraph_size {
+                return Err(self.locate(
+                    DebianError::ParseParagraphTooLarge(self.limits.max_paragraph_size),
+                    self.line_number,
+                    None,
+                    None,
+                ));
+            }
+
+            field = match field {
+                // An indented line continues the current field. Fields are contiguous in the
+                // input, so extending the range is sufficient.
+                Some((s, e, line)) if is_indented => {
+                    if e - s + (end - start) > self.limits.max_field_length {
+                        return Err(self.locate(
+                            DebianError::ParseFieldTooLong(self.limits.max_field_length),
+                            self.line_number,
+                            None,
+                            field_name(&data[s..e]),
+                        ));
+                    }
+
+                    Some((s, end, line))
+                }
+                // Otherwise this line begins a new field.
+                Some((s, e, line)) => {
+                    paragraph.add_field(&mut seen, self.parse_field(&data[s..e], line)?);
+                    Some((start, end, self.line_number))
+                }
+                None => Some((start, end, self.line_number)),
+            };
+        }
+
+        if let Some((s, e, line)) = field {
+            paragraph.add_field(&mut seen, self.parse_field(&data[s..e], line)?);
+        }
+
+        Ok(if paragraph.is_empty() {
+            None
+        } else {
+            self.count_paragraph()?;
+            Some(paragraph)
+        })
+    }
+}
+
 /// Holds parsing state for Debian control files.
 ///
 /// Instances of this type are essentially fed lines of text and periodically emit
 /// [ControlParagraph] instances as they are completed.
+///
+/// Each line is copied into the parser. When the input is already in memory,
+/// [ControlParagraphSliceReader] is faster, as it borrows from the input instead.
+///
+/// Errors are reported as [DebianError::ControlParseAt], giving the [ParseLocation] of
+/// the problem.
 #[derive(Clone, Debug, Default)]
 pub struct ControlFileParser {
+    state: ParseState,
     paragraph: ControlParagraph<'static>,
-    field: Option<String>,
+    /// The in-progress field and the line it started on.
+    field: Option<(String, usize)>,
+    /// Bytes of lines in the current paragraph.
+    paragraph_size: usize,
+}
+
+/// The name of the field defined by a line, if it has a colon.
+fn field_name(line: &str) -> Option<&str> {
+    line.split_once(':').map(|(name, _)| name)
 }
 
 impl ControlFileParser {
+    /// Set the limits enforced on input.
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        self.state.limits = limits;
+    }
+
+    /// The limits enforced on input.
+    pub fn limits(&self) -> &ParseLimits {
+        &self.state.limits
+    }
+
+    /// Set a description of the input, such as a path or URL, to include in errors.
+    pub fn set_source(&mut self, source: impl ToString) {
+        self.state.source = Some(source.to_string());
+    }
+
+    /// Wrap an error with the location it occurred at.
+    fn locate(
+        &self,
+        error: DebianError,
+        line: usize,
+        column: Option<usize>,
+        field: Option<&str>,
+    ) -> DebianError {
+        self.state.locate(error, line, column, field)
+    }
+
     /// Write a line to the parser.
     ///
     /// If the line terminates an in-progress paragraph, that paragraph will be returned.
     /// Otherwise `Ok(None)` is returned.
     ///
-    /// `Err` is returned if the control file in invalid.
+    /// `Err` is returned if the control file in invalid or exceeds the configured
+    /// [ParseLimits].
     pub fn write_line(&mut self, line: &str) -> Result<Option<ControlParagraph<'static>>> {
+        self.state.count_line(line)?;
+        let line_number = self.state.line_number;
+
         let is_empty_line = line.trim().is_empty();
         let is_indented = line.starts_with(' ') && line.len() > 1;
 
+        if let Err(e) = self.state.limits.check_line(line) {
+            let field = match (&self.field, is_indented) {
+                (Some((v, _)), true) => field_name(v),
+                _ => field_name(line),
+            };
+            return Err(self.locate(e, line_number, None, field));
+        }
+
         let current_field = self.field.take();
 
         // Empty lines signify the end of a paragraph. Flush any state.
         if is_empty_line {
-            if let Some(field) = current_field {
-                self.flush_field(field)?;
+            if let Some((field, start)) = current_field {
+                self.flush_field(field, start)?;
             }
 
+            self.paragraph_size = 0;
+
             return Ok(if self.paragraph.is_empty() {
                 None
             } else {
-                let para = self.paragraph.clone();
-                self.paragraph = ControlParagraph::default();
-                Some(para)
+                self.state.count_paragraph()?;
+
+                Some(std::mem::take(&mut self.paragraph))
             });
         }
 
+        self.paragraph_size += line.len();
+        if self.paragraph_size > self.state.limits.max_paragraph_size {
+            return Err(self.locate(
+                DebianError::ParseParagraphTooLarge(self.state.limits.max_paragraph_size),
+                line_number,
+                None,
+                None,
+            ));
+        }
+
         match (current_field, is_indented) {
             // We have a field on the stack and got an unindented line. This
             // must be the beginning of a new field. Flush the current field.
-            (Some(v), false) => {
-                self.flush_field(v)?;
+            (Some((v, start)), false) => {
+                self.flush_field(v, start)?;
 
-                self.field = if is_empty_line {
-                    None
-                } else {
-                    Some(line.to_string())
-                };
+                self.field = Some((line.to_string(), line_number));
 
                 Ok(None)
             }
@@ -439,15 +955,24 @@ impl ControlFileParser {
             // We got a non-empty line and no field is currently being
             // processed. This must be the start of a new field.
             (None, _) => {
-                self.field = Some(line.to_string());
+                self.field = Some((line.to_string(), line_number));
 
                 Ok(None)
             }
             // We have a field on the stack and got an indented line. This
             // must be a field value continuation. Add it to the current
             // field.
-            (Some(v), true) => {
-                self.field = Some(v + line);
+            (Some((v, start)), true) => {
+                if v.len() + line.len() > self.state.limits.max_field_length {
+                    return Err(self.locate(
+                        DebianError::ParseFieldTooLong(self.state.limits.max_field_length),
+                        line_number,
+                        None,
+                        field_name(&v),
+                    ));
+                }
+
+                self.field = Some((v + line, start));
 
                 Ok(None)
             }
@@ -459,49 +984,311 @@ impl ControlFileParser {
     /// If a non-empty paragraph is present in the instance, it will be returned. Else if there
     /// is no unflushed state, None is returned.
     pub fn finish(mut self) -> Result<Option<ControlParagraph<'static>>> {
-        if let Some(field) = self.field.take() {
-            self.flush_field(field)?;
+        if let Some((field, start)) = self.field.take() {
+            self.flush_field(field, start)?;
         }
 
         Ok(if self.paragraph.is_empty() {
             None
         } else {
+            self.state.count_paragraph()?;
+
             Some(self.paragraph)
         })
     }
 
-    fn flush_field(&mut self, v: String) -> Result<()> {
-        let mut parts = v.splitn(2, ':');
+    /// Add a field to the current paragraph. `start` is the line the field started on.
+    fn flush_field(&mut self, v: String, start: usize) -> Result<()> {
+        let field = self.state.parse_field(&v, start)?.into_owned();
+        self.paragraph.set_field(field);
 
-        let name = parts.next().ok_or_else(|| {
-            DebianError::ControlParseError(format!("error parsing line '{}'; missing colon", v))
-        })?;
-        let value = parts
-            .next()
-            .ok_or_else(|| {
-                DebianError::ControlParseError(format!(
-                    "error parsing field '{}'; could not detect value",
-                    v
-                ))
-            })?
-            .trim();
+        Ok(())
+    }
+}
 
-        self.paragraph
-            .set_field_from_string(Cow::Owned(name.to_string()), Cow::Owned(value.to_string()));
+/// A reader of [ControlParagraph] borrowing from a string.
+///
+/// Field names and values are slices of the input rather than copies, so parsing only
+/// allocates storage for each paragraph's list of fields. This is the fastest way to parse
+/// control data already in memory. Use [ControlParagraph::into_owned()] to detach a
+/// paragraph from the input.
+///
+/// Instances can be consumed as an iterator. Iteration ends after the first error.
+#[derive(Clone, Debug)]
+pub struct ControlParagraphSliceReader<'a> {
+    data: &'a str,
+    position: usize,
+    state: ParseState,
+    failed: bool,
+}
 
-        Ok(())
+impl<'a> ControlParagraphSliceReader<'a> {
+    /// Create a new instance parsing a string.
+    pub fn new(data: &'a str) -> Self {
+        Self {
+            data,
+            position: 0,
+            state: ParseState::default(),
+            failed: false,
+        }
+    }
+
+    /// Set the limits enforced on input.
+    ///
+    /// See [ControlFileParser::set_limits()].
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        self.state.limits = limits;
+    }
+
+    /// Set a description of the input, such as a path or URL, to include in errors.
+    ///
+    /// See [ControlFileParser::set_source()].
+    pub fn set_source(&mut self, source: impl ToString) {
+        self.state.source = Some(source.to_string());
+    }
+
+    /// The unparsed remainder of the input.
+    pub fn remaining(&self) -> &'a str {
+        &self.data[self.position..]
+    }
+
+    /// Parse all remaining paragraphs using up to `threads` threads, converting each with `f`.
+    ///
+    /// The input is split into chunks ending on empty lines, which always end paragraphs, and
+    /// chunks are parsed concurrently. Results are in input order.
+    ///
+    /// If parsing fails, the input is parsed again sequentially, so errors are the same as
+    /// when iterating.
+    pub fn read_all_parallel<T, F>(self, threads: usize, f: F) -> Result<Vec<T>>
+    where
+        T: Send,
+        F: Fn(ControlParagraph<'a>) -> T + Sync,
+    {
+        let chunks = split_paragraph_chunks(self.remaining(), threads);
+
+        let total_size = self.state.bytes + self.remaining().len() as u64;
+
+        if chunks.len() > 1 && self.state.limits.check_total_size(total_size).is_ok() {
+            // Entries are counted across chunks below.
+            let limits = ParseLimits {
+                max_entries: usize::MAX,
+                max_total_size: u64::MAX,
+                ..self.state.limits
+            };
+            let f = &f;
+
+            let results = std::thread::scope(|scope| {
+                let handles = chunks
+                    .iter()
+                    .map(|chunk| {
+                        let mut reader = ControlParagraphSliceReader::new(chunk);
+                        reader.set_limits(limits);
+
+                        scope.spawn(move || reader.map(|p| p.map(f)).collect::<Result<Vec<_>>>())
+                    })
+                    .collect::<Vec<_>>();
+
+                handles
+                    .into_iter()
+                    .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                    .collect::<Result<Vec<_>>>()
+            });
+
+            if let Ok(results) = results {
+                let count = results.iter().map(|r| r.len()).sum::<usize>();
+
+                if self
+                    .state
+                    .limits
+                    .check_entries(self.state.paragraph_count + count)
+                    .is_ok()
+                {
+                    return Ok(results.into_iter().flatten().collect());
+                }
+            }
+        }
+
+        self.map(|p| p.map(&f)).collect()
+    }
+}
+
+/// Split control data into at most `count` chunks, each ending with an empty line.
+fn split_paragraph_chunks(data: &str, count: usize) -> Vec<&str> {
+    let target = data.len() / count.max(1) + 1;
+    let mut chunks = vec![];
+    let mut rest = data;
+
+    while chunks.len() + 1 < count && rest.len() > target {
+        let Some(i) = memchr::memmem::find(&rest.as_bytes()[target..], b"\n\n") else {
+            break;
+        };
+
+        let (chunk, remaining) = rest.split_at(target + i + 2);
+        chunks.push(chunk);
+        rest = remaining;
+    }
+
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+
+    chunks
+}
+
+impl<'a> Iterator for ControlParagraphSliceReader<'a> {
+    type Item = Result<ControlParagraph<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        match self.state.next_paragraph(self.data, &mut self.position) {
+            Ok(paragraph) => paragraph.map(Ok),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Buffers the lines of a paragraph so they can be parsed without copying each line.
+///
+/// Used by readers of streams, which emit owned paragraphs.
+#[derive(Clone, Debug, Default)]
+struct ParagraphBuffer {
+    state: ParseState,
+    data: String,
+    /// Whether to emit paragraphs with [ControlParagraph::into_interned()].
+    intern: bool,
+}
+
+impl ParagraphBuffer {
+    /// Process a line appended to the buffer at `start`, returning whether to stop reading.
+    ///
+    /// Reading stops at the end of the paragraph or once limits are exceeded, which is
+    /// reported when the buffer is parsed. Leading empty lines are discarded.
+    fn push_line(&mut self, start: usize) -> bool {
+        let line = &self.data[start..];
+
+        if line.len() > self.state.limits.max_field_length {
+            true
+        } else if !line.trim().is_empty() {
+            self.data.len() > self.state.limits.max_paragraph_size
+        } else if start == 0 {
+            if self
+                .state
+                .limits
+                .check_total_size(self.state.bytes + line.len() as u64)
+                .is_err()
+            {
+                // Parsing the line reports the error.
+                return true;
+            }
+
+            self.state.line_number += 1;
+            self.state.bytes += line.len() as u64;
+            self.data.clear();
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Wrap an error reading the next line with its location.
+    fn locate_read_error(&self, error: DebianError) -> DebianError {
+        let mut line = self.state.line_number + 1;
+        let mut field = None;
+
+        for l in self.data.split_inclusive('\n') {
+            line += 1;
+            if !(l.starts_with(' ') && l.len() > 1) {
+                field = field_name(l);
+            }
+        }
+
+        self.state.locate(error, line, None, field)
+    }
+
+    /// The take limit when reading a line, allowing lines exceeding limits to be detected.
+    fn read_limit(&self) -> u64 {
+        self.state.limits.max_field_length.saturating_add(1) as u64
+    }
+
+    /// Read the next paragraph from a reader and parse it.
+    ///
+    /// Returns `None` at end of input.
+    fn read_paragraph(
+        &mut self,
+        reader: &mut impl BufRead,
+    ) -> Result<Option<ControlParagraph<'static>>> {
+        self.data.clear();
+
+        loop {
+            let start = self.data.len();
+            let size = reader
+                .take(self.read_limit())
+                .read_line(&mut self.data)
+                .map_err(|e| self.locate_read_error(e.into()))?;
+
+            if size == 0 || self.push_line(start) {
+                break;
+            }
+        }
+
+        self.parse()
+    }
+
+    /// Read the next paragraph from an async reader and parse it.
+    ///
+    /// Returns `None` at end of input.
+    async fn read_paragraph_async(
+        &mut self,
+        reader: &mut (impl AsyncBufRead + Unpin),
+    ) -> Result<Option<ControlParagraph<'static>>> {
+        self.data.clear();
+
+        loop {
+            let start = self.data.len();
+            let size = reader
+                .take(self.read_limit())
+                .read_line(&mut self.data)
+                .await
+                .map_err(|e| self.locate_read_error(e.into()))?;
+
+            if size == 0 || self.push_line(start) {
+                break;
+            }
+        }
+
+        self.parse()
+    }
+
+    fn parse(&mut self) -> Result<Option<ControlParagraph<'static>>> {
+        let mut position = 0;
+        let paragraph = self.state.next_paragraph(&self.data, &mut position)?;
+
+        Ok(paragraph.map(|p| {
+            if self.intern {
+                p.into_interned()
+            } else {
+                p.into_owned()
+            }
+        }))
     }
 }
 
 /// A reader for [ControlParagraph].
 ///
-/// Instances are bound to a reader, which is capable of feeding lines into a parser.
+/// Instances are bound to a reader. Lines are buffered until a paragraph is complete,
+/// which is then parsed.
 ///
 /// Instances can be consumed as an iterator. Each call into the iterator will attempt to
 /// read a full paragraph from the underlying reader.
 pub struct ControlParagraphReader<R: BufRead> {
     reader: R,
-    parser: Option<ControlFileParser>,
+    parser: Option<ParagraphBuffer>,
 }
 
 impl<R: BufRead> ControlParagraphReader<R> {
@@ -509,7 +1296,7 @@ impl<R: BufRead> ControlParagraphReader<R> {
     pub fn new(reader: R) -> Self {
         Self {
             reader,
-            parser: Some(ControlFileParser::default()),
+            parser: Some(ParagraphBuffer::default()),
         }
     }
 
@@ -518,28 +1305,43 @@ impl<R: BufRead> ControlParagraphReader<R> {
         self.reader
     }
 
-    fn get_next(&mut self) -> Result<Option<ControlParagraph<'static>>> {
-        let mut parser = self.parser.take().unwrap();
+    /// Set the limits enforced on input.
+    ///
+    /// See [ControlFileParser::set_limits()].
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        if let Some(parser) = &mut self.parser {
+            parser.state.limits = limits;
+        }
+    }
 
-        loop {
-            let mut line = String::new();
+    /// Set a description of the input, such as a path or URL, to include in errors.
+    ///
+    /// See [ControlFileParser::set_source()].
+    pub fn set_source(&mut self, source: impl ToString) {
+        if let Some(parser) = &mut self.parser {
+            parser.state.source = Some(source.to_string());
+        }
+    }
 
-            let bytes_read = self.reader.read_line(&mut line)?;
+    /// Set whether to emit paragraphs using static copies of well-known strings.
+    ///
+    /// This reduces the memory used by retained paragraphs. See
+    /// [ControlParagraph::into_interned()].
+    pub fn set_intern(&mut self, intern: bool) {
+        if let Some(parser) = &mut self.parser {
+            parser.intern = intern;
+        }
+    }
 
-            if bytes_read != 0 {
-                if let Some(paragraph) = parser.write_line(&line)? {
-                    self.parser.replace(parser);
-                    return Ok(Some(paragraph));
-                }
-                // Continue reading.
-            } else {
-                return if let Some(paragraph) = parser.finish()? {
-                    Ok(Some(paragraph))
-                } else {
-                    Ok(None)
-                };
-            }
+    fn get_next(&mut self) -> Result<Option<ControlParagraph<'static>>> {
+        let mut parser = self.parser.take().unwrap();
+
+        let paragraph = parser.read_paragraph(&mut self.reader)?;
+        if paragraph.is_some() {
+            self.parser.replace(parser);
         }
+
+        Ok(paragraph)
     }
 }
 
@@ -566,7 +1368,7 @@ impl<R: BufRead> Iterator for ControlParagraphReader<R> {
 pub struct ControlParagraphAsyncReader<R> {
     #[pin]
     reader: R,
-    parser: Option<ControlFileParser>,
+    parser: Option<ParagraphBuffer>,
 }
 
 impl<R> ControlParagraphAsyncReader<R>
@@ -577,7 +1379,7 @@ where
     pub fn new(reader: R) -> Self {
         Self {
             reader,
-            parser: Some(ControlFileParser::default()),
+            parser: Some(ParagraphBuffer::default()),
         }
     }
 
@@ -586,6 +1388,34 @@ where
         self.reader
     }
 
+    /// Set the limits enforced on input.
+    ///
+    /// See [ControlFileParser::set_limits()].
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        if let Some(parser) = &mut self.parser {
+            parser.state.limits = limits;
+        }
+    }
+
+    /// Set a description of the input, such as a path or URL, to include in errors.
+    ///
+    /// See [ControlFileParser::set_source()].
+    pub fn set_source(&mut self, source: impl ToString) {
+        if let Some(parser) = &mut self.parser {
+            parser.state.source = Some(source.to_string());
+        }
+    }
+
+    /// Set whether to emit paragraphs using static copies of well-known strings.
+    ///
+    /// This reduces the memory used by retained paragraphs. See
+    /// [ControlParagraph::into_interned()].
+    pub fn set_intern(&mut self, intern: bool) {
+        if let Some(parser) = &mut self.parser {
+            parser.intern = intern;
+        }
+    }
+
     /// Read the next available paragraph from this reader.
     ///
     /// Resolves to [None] on end of input.
@@ -596,57 +1426,157 @@ where
             return Ok(None);
         };
 
-        loop {
-            let mut line = String::new();
+        let paragraph = parser.read_paragraph_async(&mut self.reader).await?;
+        if paragraph.is_some() {
+            self.parser.replace(parser);
+        }
 
-            let bytes_read = self.reader.read_line(&mut line).await?;
+        Ok(paragraph)
+    }
+}
 
-            if bytes_read != 0 {
-                if let Some(paragraph) = parser.write_line(&line)? {
-                    self.parser.replace(parser);
-                    return Ok(Some(paragraph));
-                }
-                // Continue reading.
+/// Encode a field in its serialized form, including the trailing newline.
+///
+/// Continuation lines lacking leading whitespace are indented by a space and empty
+/// continuation lines are written as ` .`, so the output always parses back to the
+/// same paragraph structure.
+fn encode_field(field: &ControlField) -> String {
+    let mut res = format!("{}:", field.name());
+
+    for (i, line) in field
+        .value_str()
+        .trim_end_matches('\n')
+        .split('\n')
+        .enumerate()
+    {
+        if i == 0 {
+            if !line.is_empty() {
+                res.push(' ');
+                res.push_str(line);
+            }
+        } else {
+            res.push('\n');
+            if line.trim().is_empty() {
+                res.push_str(" .");
             } else {
-                return if let Some(paragraph) = parser.finish()? {
-                    Ok(Some(paragraph))
-                } else {
-                    Ok(None)
-                };
+                if !line.starts_with([' ', '\t']) {
+                    res.push(' ');
+                }
+                res.push_str(line);
             }
         }
     }
+
+    res.push('\n');
+    res
+}
+
+/// An asynchronous writer of [ControlParagraph].
+///
+/// Each paragraph is followed by a blank line, as in `Packages` and `Sources` files.
+/// Multiline values are folded so they parse back to the same fields.
+///
+/// Wrap the destination in a compressing [AsyncWrite] to produce compressed indices.
+/// Call [Self::close()] when done so such writers can finalize their output.
+pub struct ControlParagraphAsyncWriter<W> {
+    writer: W,
+    paragraphs: usize,
+}
+
+impl<W> ControlParagraphAsyncWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Create a new instance bound to a writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            paragraphs: 0,
+        }
+    }
+
+    /// Consumes self, returning the inner writer.
+    ///
+    /// Buffered data in the inner writer isn't flushed.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// The number of paragraphs written.
+    pub fn paragraph_count(&self) -> usize {
+        self.paragraphs
+    }
+
+    /// Write a paragraph and its terminating blank line.
+    pub async fn write_paragraph(&mut self, paragraph: &ControlParagraph<'_>) -> Result<()> {
+        let mut data = String::new();
+        for field in paragraph.iter_fields() {
+            data.push_str(&encode_field(field));
+        }
+        data.push('\n');
+
+        self.writer.write_all(data.as_bytes()).await?;
+        self.paragraphs += 1;
+
+        Ok(())
+    }
+
+    /// Write all paragraphs from an iterable.
+    pub async fn write_paragraphs<'p, 'a: 'p>(
+        &mut self,
+        paragraphs: impl IntoIterator<Item = &'p ControlParagraph<'a>>,
+    ) -> Result<()> {
+        for paragraph in paragraphs {
+            self.write_paragraph(paragraph).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Flush the inner writer.
+    pub async fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush().await?)
+    }
+
+    /// Close the inner writer, finalizing any compressed stream.
+    pub async fn close(&mut self) -> Result<()> {
+        Ok(self.writer.close().await?)
+    }
 }
 
 /// A debian control file.
 ///
 /// A control file is an ordered series of paragraphs.
+///
+/// With the `serde` feature, instances serialize as a sequence of paragraphs.
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(transparent)
+)]
 pub struct ControlFile<'a> {
     paragraphs: Vec<ControlParagraph<'a>>,
 }
 
 impl<'a> ControlFile<'a> {
     /// Construct a new instance by parsing data from a reader.
+    ///
+    /// The default [ParseLimits] are enforced.
     pub fn parse_reader<R: BufRead>(reader: &mut R) -> Result<Self> {
-        let mut paragraphs = Vec::new();
-        let mut parser = ControlFileParser::default();
-
-        loop {
-            let mut line = String::new();
-            let bytes_read = reader.read_line(&mut line)?;
-
-            // .read_line() indicates EOF by Ok(0).
-            if bytes_read == 0 {
-                break;
-            }
+        Self::parse_reader_with_limits(reader, ParseLimits::default())
+    }
 
-            if let Some(paragraph) = parser.write_line(&line)? {
-                paragraphs.push(paragraph);
-            }
-        }
+    /// Construct a new instance by parsing data from a reader, enforcing [ParseLimits].
+    pub fn parse_reader_with_limits<R: BufRead>(
+        reader: &mut R,
+        limits: ParseLimits,
+    ) -> Result<Self> {
+        let mut paragraphs = Vec::new();
+        let mut parser = ParagraphBuffer::default();
+        parser.state.limits = limits;
 
-        if let Some(paragraph) = parser.finish()? {
+        while let Some(paragraph) = parser.read_paragraph(reader)? {
             paragraphs.push(paragraph);
         }
 
@@ -655,8 +1585,27 @@ impl<'a> ControlFile<'a> {
 
     /// Parse a control file from a string.
     pub fn parse_str(s: &str) -> Result<Self> {
-        let mut reader = std::io::BufReader::new(s.as_bytes());
-        Self::parse_reader(&mut reader)
+        Ok(ControlFile::parse_borrowed(s)?.into_owned())
+    }
+
+    /// Parse a control file from a string, borrowing fields from it.
+    ///
+    /// This avoids copying field names and values. See [ControlParagraphSliceReader].
+    pub fn parse_borrowed(s: &'a str) -> Result<Self> {
+        Ok(Self {
+            paragraphs: ControlParagraphSliceReader::new(s).collect::<Result<Vec<_>>>()?,
+        })
+    }
+
+    /// Obtain an instance not borrowing from any input, copying borrowed data.
+    pub fn into_owned(self) -> ControlFile<'static> {
+        ControlFile {
+            paragraphs: self
+                .paragraphs
+                .into_iter()
+                .map(|p| p.into_owned())
+                .collect(),
+        }
     }
 
     /// Add a paragraph to this control file.
@@ -685,6 +1634,53 @@ impl<'a> ControlFile<'a> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'a> serde::Serialize for ControlParagraph<'a> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for field in &self.fields {
+            map.serialize_entry(field.name(), field.value_str())?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, 'a> serde::Deserialize<'de> for ControlParagraph<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct ParagraphVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ParagraphVisitor {
+            type Value = ControlParagraph<'static>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a map of control field names to values")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                let mut para = ControlParagraph::default();
+                while let Some((name, value)) = map.next_entry::<String, String>()? {
+                    para.set_field_from_string(name.into(), value.into());
+                }
+
+                Ok(para)
+            }
+        }
+
+        deserializer.deserialize_map(ParagraphVisitor)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -704,6 +1700,275 @@ mod tests {
         assert_eq!(p.field("FOO").unwrap().value, "bar");
     }
 
+    #[tokio::test]
+    async fn parse_limits() -> Result<()> {
+        let data = "Package: foo\nDescription: a\n b\n c\n\nPackage: bar\n";
+
+        let limits = ParseLimits {
+            max_field_length: 21,
+            ..Default::default()
+        };
+        assert_eq!(
+            ControlFile::parse_reader_with_limits(&mut data.as_bytes(), limits)?
+                .paragraphs()
+                .count(),
+            2
+        );
+
+        let limits = ParseLimits {
+            max_field_length: 16,
+            ..Default::default()
+        };
+        let err = ControlFile::parse_reader_with_limits(&mut data.as_bytes(), limits).unwrap_err();
+        assert!(matches!(
+            err.unlocated(),
+            DebianError::ParseFieldTooLong(16)
+        ));
+        assert_eq!(
+            err.parse_location().unwrap().to_string(),
+            "<input>:3 (field Description)"
+        );
+
+        let mut reader = ControlParagraphAsyncReader::new(futures::io::Cursor::new(data));
+        reader.set_limits(ParseLimits {
+            max_paragraph_size: 30,
+            ..Default::default()
+        });
+        assert!(matches!(
+            reader.read_paragraph().await.unwrap_err().unlocated(),
+            DebianError::ParseParagraphTooLarge(30)
+        ));
+
+        let mut reader = ControlParagraphReader::new(data.as_bytes());
+        reader.set_limits(ParseLimits {
+            max_entries: 1,
+            ..Default::default()
+        });
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next().unwrap().unwrap_err().unlocated(),
+            DebianError::ParseTooManyEntries(1)
+        ));
+
+        // An unterminated line is rejected without buffering all of it.
+        let mut reader = ControlParagraphReader::new(std::io::BufReader::new(
+            std::io::repeat(b'a').take(1024 * 1024),
+        ));
+        reader.set_limits(ParseLimits {
+            max_field_length: 1024,
+            ..Default::default()
+        });
+        assert!(matches!(
+            reader.next().unwrap().unwrap_err().unlocated(),
+            DebianError::ParseFieldTooLong(1024)
+        ));
+
+        let limits = ParseLimits {
+            max_total_size: 40,
+            ..Default::default()
+        };
+        let mut reader = ControlParagraphReader::new(data.as_bytes());
+        reader.set_limits(limits);
+        assert!(reader.next().unwrap().is_ok());
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(matches!(
+            err.unlocated(),
+            DebianError::ParseInputTooLarge(40)
+        ));
+        assert_eq!(err.parse_location().unwrap().line, 6);
+
+        let mut reader = ControlParagraphSliceReader::new(data);
+        reader.set_limits(limits);
+        assert!(matches!(
+            reader.read_all_parallel(2, |p| p).unwrap_err().unlocated(),
+            DebianError::ParseInputTooLarge(40)
+        ));
+
+        // Endless empty lines are also bounded.
+        let mut reader = ControlParagraphReader::new(std::io::BufReader::new(
+            std::io::repeat(b'\n').take(1024 * 1024),
+        ));
+        reader.set_limits(ParseLimits {
+            max_total_size: 1024,
+            ..Default::default()
+        });
+        assert!(matches!(
+            reader.next().unwrap().unwrap_err().unlocated(),
+            DebianError::ParseInputTooLarge(1024)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn async_writer() -> Result<()> {
+        let mut first = ControlParagraph::default();
+        first.set_field_from_string("Package".into(), "foo".into());
+        first.set_field_from_string("Description".into(), "synopsis\n line\n\nmore".into());
+        let mut second = ControlParagraph::default();
+        second.set_field_from_string("Package".into(), "bar".into());
+
+        let mut writer = ControlParagraphAsyncWriter::new(futures::io::Cursor::new(vec![]));
+        writer.write_paragraphs([&first, &second]).await?;
+        writer.close().await?;
+        assert_eq!(writer.paragraph_count(), 2);
+
+        let data = String::from_utf8(writer.into_inner().into_inner()).unwrap();
+        assert_eq!(
+            data,
+            "Package: foo\nDescription: synopsis\n line\n .\n more\n\nPackage: bar\n\n"
+        );
+
+        let paragraphs = ControlFile::parse_str(&data)?
+            .into_paragraphs()
+            .collect::<Vec<_>>();
+        assert_eq!(paragraphs.len(), 2);
+        assert_eq!(
+            paragraphs[0].field_str("Description"),
+            Some("synopsis\n line\n .\n more")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn slice_reader() -> Result<()> {
+        let data = "\n\nPackage: foo\nDescription: a\n b\n .\n c\npackage: bar\n\n \n\
+                    Source: baz\r\nArchitecture: any";
+
+        let paragraphs = ControlParagraphSliceReader::new(data).collect::<Result<Vec<_>>>()?;
+        assert_eq!(paragraphs.len(), 2);
+        assert!(paragraphs
+            .iter()
+            .flat_map(|p| p.iter_fields())
+            .all(|f| matches!((&f.name, &f.value), (Cow::Borrowed(_), Cow::Borrowed(_)))));
+        assert_eq!(paragraphs[0].field_str("Package"), Some("bar"));
+        assert_eq!(
+            paragraphs[0].field_str("Description"),
+            Some("a\n b\n .\n c")
+        );
+        assert_eq!(paragraphs[1].field_str("Source"), Some("baz"));
+
+        // All parsers agree.
+        let mut parser = ControlFileParser::default();
+        let mut expected = vec![];
+        for line in data.split_inclusive('\n') {
+            expected.extend(parser.write_line(line)?);
+        }
+        expected.extend(parser.finish()?);
+        assert_eq!(paragraphs, expected);
+        assert_eq!(
+            ControlParagraphReader::new(data.as_bytes()).collect::<Result<Vec<_>>>()?,
+            expected
+        );
+
+        let err = ControlParagraphSliceReader::new("Package: foo\n\nPackage: bar\nbogus\n")
+            .nth(1)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.parse_location().unwrap().to_string(), "<input>:4:6");
+
+        Ok(())
+    }
+
+    #[test]
+    fn parallel_reader() -> Result<()> {
+        let data = (0..100)
+            .map(|i| format!("Package: p{}\nDescription: d\n more\n\n\n", i))
+            .collect::<String>();
+
+        let expected = ControlParagraphSliceReader::new(&data).collect::<Result<Vec<_>>>()?;
+        assert_eq!(expected.len(), 100);
+        assert_eq!(split_paragraph_chunks(&data, 4).len(), 4);
+        assert_eq!(
+            ControlParagraphSliceReader::new(&data).read_all_parallel(4, |p| p)?,
+            expected
+        );
+
+        // Errors match sequential parsing.
+        let mut reader = ControlParagraphSliceReader::new(&data);
+        reader.set_limits(ParseLimits {
+            max_entries: 99,
+            ..Default::default()
+        });
+        let err = reader.read_all_parallel(4, |p| p).unwrap_err();
+        assert_eq!(err.parse_location().unwrap().line, 499);
+
+        let data = data.replace("Package: p60\n", "bogus\n");
+        let err = ControlParagraphSliceReader::new(&data)
+            .read_all_parallel(4, |p| p)
+            .unwrap_err();
+        assert_eq!(err.parse_location().unwrap().line, 301);
+
+        Ok(())
+    }
+
+    #[test]
+    fn interned_paragraph() -> Result<()> {
+        let data = "Package: foo\nX-Custom: value\nArchitecture: amd64\nSection: non-free/libs\n";
+
+        let mut reader = ControlParagraphReader::new(data.as_bytes());
+        reader.set_intern(true);
+        let paragraph = reader.next().unwrap()?;
+
+        let borrowed = paragraph
+            .iter_fields()
+            .map(|f| {
+                (
+                    matches!(f.name, Cow::Borrowed(_)),
+                    matches!(f.value, Cow::Borrowed(_)),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            borrowed,
+            vec![(true, false), (false, false), (true, true), (true, true)]
+        );
+        assert_eq!(paragraph.to_string(), data);
+
+        // Case differences aren't normalized.
+        let paragraph = ControlFile::parse_str("package: foo\nPriority: Optional\n")?
+            .into_paragraphs()
+            .next()
+            .unwrap()
+            .into_interned();
+        let fields = paragraph.fields.iter().collect::<Vec<_>>();
+        assert!(matches!(fields[0].name, Cow::Owned(_)));
+        assert!(matches!(fields[1].name, Cow::Borrowed(_)));
+        assert!(matches!(fields[1].value, Cow::Owned(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_error_location() {
+        let data = "Package: foo\nVersion: 1.0\n\nPackage: bar\nbogus\n";
+
+        let mut reader = ControlParagraphReader::new(data.as_bytes());
+        reader.set_source("Packages");
+        assert!(reader.next().unwrap().is_ok());
+
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(
+            err.parse_location(),
+            Some(&ParseLocation {
+                source: Some("Packages".into()),
+                line: 5,
+                column: Some(6),
+                field: None,
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "Packages:5:6: control file parse error: error parsing line 'bogus'; missing colon"
+        );
+
+        let err = ControlFile::parse_str(": value\n")
+            .unwrap_err()
+            .with_parse_source("control");
+        assert_eq!(err.parse_location().unwrap().to_string(), "control:1:1");
+    }
+
     #[test]
     fn parse_paragraph_release() -> Result<()> {
         let paragraphs = ControlParagraphReader::new(std::io::Cursor::new(include_bytes!(
@@ -802,4 +2067,35 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_json() -> Result<()> {
+        use crate::{
+            binary_package_control::BinaryPackageControlFile, package_version::PackageVersion,
+        };
+
+        let cf =
+            ControlFile::parse_str("Package: foo\nVersion: 1:1.0-1\nDescription: short\n long\n")?;
+
+        let json = serde_json::to_string(&cf)?;
+        assert_eq!(
+            json,
+            r#"[{"Package":"foo","Version":"1:1.0-1","Description":"short\n long"}]"#
+        );
+        let roundtrip: ControlFile = serde_json::from_str(&json)?;
+        assert_eq!(
+            roundtrip.paragraphs().collect::<Vec<_>>(),
+            cf.paragraphs().collect::<Vec<_>>()
+        );
+
+        let binary: BinaryPackageControlFile =
+            serde_json::from_str(r#"{"Package":"foo","Version":"1:1.0-1"}"#)?;
+        assert_eq!(binary.package()?, "foo");
+        assert_eq!(binary.version()?, PackageVersion::parse("1:1.0-1")?);
+        assert_eq!(serde_json::to_string(&binary.version()?)?, r#""1:1.0-1""#);
+        assert!(serde_json::from_str::<PackageVersion>(r#""1:a:b""#).is_err());
+
+        Ok(())
+    }
 }
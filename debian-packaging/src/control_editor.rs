@@ -0,0 +1,475 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Format-preserving editing of control files.
+
+[crate::control::ControlFile] normalizes content as it is parsed: comments are rejected
+and field formatting is not retained. This makes it unsuitable for tools wanting to patch
+files like `debian/control` or `/var/lib/dpkg/status` while leaving the rest of the file
+alone.
+
+[ControlFileEditor] retains the original text of a control file. Fields can be set and
+removed and paragraphs appended or removed. Only modified fields are reformatted:
+untouched fields keep their order, folding, and whitespace and comments are retained.
+Serializing an unmodified instance (via [std::fmt::Display]) reproduces the original
+content byte for byte.
+*/
+
+use {
+    crate::{
+        control::{ControlFile, ControlParagraph},
+        error::{DebianError, Result},
+    },
+    std::fmt::{Display, Formatter},
+};
+
+/// An element of a paragraph.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Entry {
+    /// A field and the raw lines defining it.
+    ///
+    /// Comment lines between continuation lines are retained in `lines`.
+    Field { name: String, lines: Vec<String> },
+    /// A comment line.
+    Comment(String),
+}
+
+/// Format a field as lines of text.
+///
+/// Lines of `value` after the first are indented by a space unless already indented.
+/// Empty lines are written as ` .`.
+fn format_field(name: &str, value: &str) -> Vec<String> {
+    value
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 && line.trim().is_empty() {
+                format!("{}:\n", name)
+            } else if i == 0 {
+                format!("{}: {}\n", name, line.trim())
+            } else if line.trim().is_empty() {
+                " .\n".to_string()
+            } else if line.starts_with(' ') || line.starts_with('\t') {
+                format!("{}\n", line.trim_end())
+            } else {
+                format!(" {}\n", line.trim_end())
+            }
+        })
+        .collect()
+}
+
+/// Whether a line is a comment.
+fn is_comment(line: &str) -> bool {
+    line.starts_with('#')
+}
+
+/// A paragraph of a [ControlFileEditor].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ParagraphEditor {
+    entries: Vec<Entry>,
+}
+
+impl ParagraphEditor {
+    fn field_entry(&self, name: &str) -> Option<(&String, &Vec<String>)> {
+        self.entries.iter().find_map(|entry| match entry {
+            Entry::Field { name: n, lines } if n.eq_ignore_ascii_case(name) => Some((n, lines)),
+            _ => None,
+        })
+    }
+
+    /// Names of fields in this paragraph, in order.
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().filter_map(|entry| match entry {
+            Entry::Field { name, .. } => Some(name.as_str()),
+            Entry::Comment(_) => None,
+        })
+    }
+
+    /// Whether a named field is present. Names are compared case insensitively.
+    pub fn has_field(&self, name: &str) -> bool {
+        self.field_entry(name).is_some()
+    }
+
+    /// Obtain the value of a field.
+    ///
+    /// The value is the text after the colon with surrounding whitespace removed and
+    /// comment lines omitted. Continuation lines retain their leading whitespace, as
+    /// with [crate::control::ControlField::value_str()].
+    pub fn field_value(&self, name: &str) -> Option<String> {
+        let (_, lines) = self.field_entry(name)?;
+
+        let text = lines
+            .iter()
+            .filter(|line| !is_comment(line))
+            .map(|line| line.as_str())
+            .collect::<String>();
+        let value = text.split_once(':').map(|(_, v)| v).unwrap_or_default();
+
+        Some(value.trim().to_string())
+    }
+
+    /// Set the value of a field.
+    ///
+    /// An existing field is replaced in place, retaining the case of its name.
+    /// Otherwise the field is appended to the paragraph. Lines of `value` after the
+    /// first are indented by a space unless already indented and empty lines are
+    /// written as ` .`.
+    pub fn set_field(&mut self, name: &str, value: &str) {
+        for entry in self.entries.iter_mut() {
+            if let Entry::Field { name: n, lines } = entry {
+                if n.eq_ignore_ascii_case(name) {
+                    *lines = format_field(n, value);
+                    return;
+                }
+            }
+        }
+
+        self.ensure_trailing_newline();
+        self.entries.push(Entry::Field {
+            name: name.to_string(),
+            lines: format_field(name, value),
+        });
+    }
+
+    /// Remove a field, returning whether it was present.
+    ///
+    /// Comment lines within the field's continuation lines are removed with it.
+    pub fn remove_field(&mut self, name: &str) -> bool {
+        let count = self.entries.len();
+        self.entries.retain(
+            |entry| !matches!(entry, Entry::Field { name: n, .. } if n.eq_ignore_ascii_case(name)),
+        );
+
+        self.entries.len() != count
+    }
+
+    /// Convert to a [ControlParagraph], discarding comments and formatting.
+    pub fn to_paragraph(&self) -> ControlParagraph<'static> {
+        let mut para = ControlParagraph::default();
+        for name in self.field_names() {
+            if let Some(value) = self.field_value(name) {
+                para.set_field_from_string(name.to_string().into(), value.into());
+            }
+        }
+
+        para
+    }
+
+    /// Ensure the last line of the paragraph is newline terminated.
+    fn ensure_trailing_newline(&mut self) {
+        let line = match self.entries.last_mut() {
+            Some(Entry::Field { lines, .. }) => lines.last_mut(),
+            Some(Entry::Comment(line)) => Some(line),
+            None => None,
+        };
+
+        if let Some(line) = line {
+            if !line.ends_with('\n') {
+                line.push('\n');
+            }
+        }
+    }
+
+    fn has_fields(&self) -> bool {
+        self.field_names().next().is_some()
+    }
+}
+
+impl Display for ParagraphEditor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            match entry {
+                Entry::Field { lines, .. } => {
+                    for line in lines {
+                        f.write_str(line)?;
+                    }
+                }
+                Entry::Comment(line) => f.write_str(line)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> From<&ControlParagraph<'a>> for ParagraphEditor {
+    fn from(para: &ControlParagraph<'a>) -> Self {
+        let mut res = Self::default();
+        for field in para.iter_fields() {
+            res.set_field(field.name(), field.value_str());
+        }
+
+        res
+    }
+}
+
+/// A control file editor preserving the formatting of untouched content.
+///
+/// Lines starting with `#` are treated as comments, as in `debian/control` files.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ControlFileEditor {
+    /// Paragraphs and the raw text preceding each.
+    ///
+    /// The preceding text consists of blank lines and comment blocks not attached to
+    /// a paragraph.
+    paragraphs: Vec<(String, ParagraphEditor)>,
+    /// Raw text after the last paragraph.
+    trailing: String,
+}
+
+impl ControlFileEditor {
+    /// Parse a control file from a string.
+    pub fn parse_str(s: &str) -> Result<Self> {
+        let mut res = Self::default();
+        let mut pending = String::new();
+        let mut block: Vec<&str> = vec![];
+
+        for line in s.split_inclusive('\n').chain(std::iter::once("")) {
+            if !line.trim().is_empty() {
+                block.push(line);
+                continue;
+            }
+
+            if !block.is_empty() {
+                let paragraph = Self::parse_paragraph(&block)?;
+
+                if paragraph.has_fields() {
+                    res.paragraphs
+                        .push((std::mem::take(&mut pending), paragraph));
+                } else {
+                    pending.extend(block.iter().copied());
+                }
+
+                block.clear();
+            }
+
+            pending.push_str(line);
+        }
+
+        res.trailing = pending;
+
+        Ok(res)
+    }
+
+    fn parse_paragraph(lines: &[&str]) -> Result<ParagraphEditor> {
+        let mut entries = vec![];
+        let mut comments = vec![];
+
+        for line in lines {
+            if is_comment(line) {
+                comments.push(line.to_string());
+            } else if line.starts_with(' ') || line.starts_with('\t') {
+                match entries.last_mut() {
+                    Some(Entry::Field { lines, .. }) => {
+                        lines.append(&mut comments);
+                        lines.push(line.to_string());
+                    }
+                    _ => {
+                        return Err(DebianError::ControlParseError(format!(
+                            "continuation line without field: '{}'",
+                            line.trim_end()
+                        )))
+                    }
+                }
+            } else {
+                entries.extend(comments.drain(..).map(Entry::Comment));
+
+                let (name, _) = line.split_once(':').ok_or_else(|| {
+                    DebianError::ControlParseError(format!(
+                        "error parsing line '{}'; missing colon",
+                        line.trim_end()
+                    ))
+                })?;
+
+                entries.push(Entry::Field {
+                    name: name.trim().to_string(),
+                    lines: vec![line.to_string()],
+                });
+            }
+        }
+
+        entries.extend(comments.into_iter().map(Entry::Comment));
+
+        Ok(ParagraphEditor { entries })
+    }
+
+    /// Obtain the paragraphs in this file.
+    pub fn paragraphs(&self) -> impl Iterator<Item = &ParagraphEditor> {
+        self.paragraphs.iter().map(|(_, para)| para)
+    }
+
+    /// Obtain a mutable reference to the paragraph at an index.
+    pub fn paragraph_mut(&mut self, index: usize) -> Option<&mut ParagraphEditor> {
+        self.paragraphs.get_mut(index).map(|(_, para)| para)
+    }
+
+    /// Find the first paragraph having a field with the given value.
+    ///
+    /// e.g. `find_paragraph_mut("Package", "foo")` finds the paragraph for package `foo`.
+    pub fn find_paragraph_mut(&mut self, field: &str, value: &str) -> Option<&mut ParagraphEditor> {
+        self.paragraphs
+            .iter_mut()
+            .map(|(_, para)| para)
+            .find(|para| para.field_value(field).as_deref() == Some(value))
+    }
+
+    /// Append a paragraph to the end of the file.
+    ///
+    /// The paragraph is separated from existing content by a blank line.
+    pub fn append_paragraph(&mut self, paragraph: ParagraphEditor) {
+        let mut leading = std::mem::take(&mut self.trailing);
+
+        if let Some((_, last)) = self.paragraphs.last_mut() {
+            last.ensure_trailing_newline();
+            if leading.is_empty() {
+                leading.push('\n');
+            }
+        }
+        if !leading.is_empty() && !leading.ends_with('\n') {
+            leading.push('\n');
+        }
+
+        self.paragraphs.push((leading, paragraph));
+    }
+
+    /// Remove the paragraph at an index.
+    ///
+    /// The blank lines and comments preceding the paragraph are removed with it, except
+    /// for the first paragraph, whose preceding text (such as a file header comment) is
+    /// retained.
+    pub fn remove_paragraph(&mut self, index: usize) -> Option<ParagraphEditor> {
+        if index >= self.paragraphs.len() {
+            return None;
+        }
+
+        let (leading, para) = self.paragraphs.remove(index);
+        if index == 0 {
+            match self.paragraphs.first_mut() {
+                Some((next, _)) => *next = leading,
+                None => self.trailing = leading,
+            }
+        }
+
+        Some(para)
+    }
+
+    /// Convert to a [ControlFile], discarding comments and formatting.
+    pub fn to_control_file(&self) -> ControlFile<'static> {
+        let mut cf = ControlFile::default();
+        for para in self.paragraphs() {
+            cf.add_paragraph(para.to_paragraph());
+        }
+
+        cf
+    }
+}
+
+impl Display for ControlFileEditor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (leading, para) in &self.paragraphs {
+            f.write_str(leading)?;
+            para.fmt(f)?;
+        }
+
+        f.write_str(&self.trailing)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, indoc::indoc};
+
+    const CONTROL: &str = indoc! {"
+        # Maintained by hand.
+        Source: hello
+        Build-Depends: debhelper-compat (= 13),
+        # needed for tests
+                       python3
+        Standards-Version: 4.6.0
+
+        Package: hello
+        Architecture: any
+        Depends: ${shlibs:Depends},
+         ${misc:Depends}
+        Description: greeter
+         A friendly program.
+         .
+         It says hello.
+    "};
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let editor = ControlFileEditor::parse_str(CONTROL)?;
+        assert_eq!(editor.to_string(), CONTROL);
+        assert_eq!(editor.paragraphs().count(), 2);
+
+        let source = editor.paragraphs().next().unwrap();
+        assert_eq!(
+            source.field_value("build-depends").as_deref(),
+            Some("debhelper-compat (= 13),\n               python3")
+        );
+
+        let cf = editor.to_control_file();
+        let binary = cf.paragraphs().nth(1).unwrap();
+        assert_eq!(binary.field_str("Package"), Some("hello"));
+
+        let unterminated = "Package: foo\nVersion: 1.0";
+        assert_eq!(
+            ControlFileEditor::parse_str(unterminated)?.to_string(),
+            unterminated
+        );
+
+        assert!(ControlFileEditor::parse_str(" continuation\n").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn edit() -> Result<()> {
+        let mut editor = ControlFileEditor::parse_str(CONTROL)?;
+
+        let source = editor.paragraph_mut(0).unwrap();
+        source.set_field("standards-version", "4.7.0");
+        source.set_field("Rules-Requires-Root", "no");
+
+        let binary = editor.find_paragraph_mut("Package", "hello").unwrap();
+        assert!(binary.remove_field("Depends"));
+        assert!(!binary.remove_field("Recommends"));
+
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "hello-doc".into());
+        para.set_field_from_string("Description".into(), "docs\n More docs.".into());
+        editor.append_paragraph(ParagraphEditor::from(&para));
+
+        assert_eq!(
+            editor.to_string(),
+            indoc! {"
+                # Maintained by hand.
+                Source: hello
+                Build-Depends: debhelper-compat (= 13),
+                # needed for tests
+                               python3
+                Standards-Version: 4.7.0
+                Rules-Requires-Root: no
+
+                Package: hello
+                Architecture: any
+                Description: greeter
+                 A friendly program.
+                 .
+                 It says hello.
+
+                Package: hello-doc
+                Description: docs
+                 More docs.
+            "}
+        );
+
+        // Comments directly preceding a field belong to its paragraph.
+        editor.remove_paragraph(0);
+        assert!(editor.to_string().starts_with("Package: hello\n"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Canonical formatting of control paragraphs.
+
+This implements the conventions of the `wrap-and-sort` tool from `devscripts`. Entries of
+relationship fields like `Depends` and `Build-Depends` are normalized, deduplicated and
+sorted. Fields too long for one line, or all of them with
+[WrapAndSortOptions::wrap_always], are wrapped to one entry per line. `Architecture` values
+are sorted.
+
+Alternatives within an entry (e.g. `foo | bar`) keep their order, as it is significant.
+Entries starting with a lowercase letter or digit sort before others, so substitution
+variables like `${misc:Depends}` come last.
+
+[format_paragraph()] produces a formatted copy of a paragraph, suitable for deterministic
+output. [unformatted_fields()] reports which fields differ from their formatted form, for
+linting.
+*/
+
+use crate::control::{ControlFile, ControlParagraph};
+
+/// Fields holding comma delimited lists that are sorted and wrapped.
+pub const LIST_FIELDS: &[&str] = &[
+    "Breaks",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Built-Using",
+    "Conflicts",
+    "Depends",
+    "Enhances",
+    "Pre-Depends",
+    "Provides",
+    "Recommends",
+    "Replaces",
+    "Static-Built-Using",
+    "Suggests",
+];
+
+/// Fields holding whitespace delimited lists that are sorted.
+pub const WORD_FIELDS: &[&str] = &["Architecture"];
+
+/// Options controlling formatting, mirroring `wrap-and-sort` flags.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WrapAndSortOptions {
+    /// Wrap list fields to one entry per line even if they fit on one line.
+    ///
+    /// Corresponds to `--wrap-always`.
+    pub wrap_always: bool,
+    /// Indent wrapped entries by a single space and start them on the line after the
+    /// field name, instead of aligning them after the field name.
+    ///
+    /// Corresponds to `--short-indent`.
+    pub short_indent: bool,
+    /// Add a comma after the last entry of wrapped fields.
+    ///
+    /// Corresponds to `--trailing-comma`.
+    pub trailing_comma: bool,
+    /// Sort list entries. If false, entries are only normalized and wrapped.
+    pub sort: bool,
+    /// Lines longer than this cause a field to be wrapped.
+    pub max_line_length: usize,
+}
+
+impl Default for WrapAndSortOptions {
+    fn default() -> Self {
+        Self {
+            wrap_always: false,
+            short_indent: false,
+            trailing_comma: false,
+            sort: true,
+            max_line_length: 79,
+        }
+    }
+}
+
+/// Split a list field value into normalized entries.
+///
+/// Whitespace within entries is collapsed, empty entries are removed and duplicates are
+/// removed. Entries are sorted if [WrapAndSortOptions::sort] is set.
+pub fn list_entries(value: &str, options: &WrapAndSortOptions) -> Vec<String> {
+    let mut entries: Vec<String> = vec![];
+
+    for entry in value.split(',') {
+        let entry = entry.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !entry.is_empty() && !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+
+    if options.sort {
+        entries.sort_by_cached_key(|entry| {
+            let special =
+                !entry.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit());
+            (special, entry.clone())
+        });
+    }
+
+    entries
+}
+
+/// Format the value of a field.
+///
+/// Returns `None` if the field isn't one of [LIST_FIELDS] or [WORD_FIELDS].
+///
+/// Wrapped values have continuation lines indented by whitespace. With
+/// [WrapAndSortOptions::short_indent], the value starts with a newline so the first entry
+/// is on its own line.
+pub fn format_field_value(name: &str, value: &str, options: &WrapAndSortOptions) -> Option<String> {
+    let is_field = |fields: &[&str]| fields.iter().any(|f| f.eq_ignore_ascii_case(name));
+
+    if is_field(WORD_FIELDS) {
+        let mut words = value.split_whitespace().collect::<Vec<_>>();
+        if options.sort {
+            words.sort_unstable();
+        }
+        words.dedup();
+
+        return Some(words.join(" "));
+    }
+
+    if !is_field(LIST_FIELDS) {
+        return None;
+    }
+
+    let entries = list_entries(value, options);
+    let single = entries.join(", ");
+
+    if !options.wrap_always && name.len() + 2 + single.len() <= options.max_line_length {
+        return Some(single);
+    }
+
+    let mut res = entries.join(",\n");
+    if options.trailing_comma {
+        res.push(',');
+    }
+
+    let indent = if options.short_indent {
+        " ".to_string()
+    } else {
+        " ".repeat(name.len() + 2)
+    };
+    let res = res.replace('\n', &format!("\n{}", indent));
+
+    Some(if options.short_indent {
+        format!("\n{}{}", indent, res)
+    } else {
+        res
+    })
+}
+
+/// Obtain a formatted copy of a paragraph.
+///
+/// Field order is preserved. Only [LIST_FIELDS] and [WORD_FIELDS] are changed.
+pub fn format_paragraph(
+    paragraph: &ControlParagraph,
+    options: &WrapAndSortOptions,
+) -> ControlParagraph<'static> {
+    let mut res = ControlParagraph::default();
+
+    for field in paragraph.iter_fields() {
+        let value = format_field_value(field.name(), field.value_str(), options)
+            .unwrap_or_else(|| field.value_str().to_string());
+
+        res.set_field_from_string(field.name().to_string().into(), value.into());
+    }
+
+    res
+}
+
+/// Obtain a formatted copy of every paragraph in a control file.
+pub fn format_control_file(
+    control: &ControlFile,
+    options: &WrapAndSortOptions,
+) -> ControlFile<'static> {
+    let mut res = ControlFile::default();
+    for paragraph in control.paragraphs() {
+        res.add_paragraph(format_paragraph(paragraph, options));
+    }
+
+    res
+}
+
+/// Names of fields whose value differs from its formatted form.
+pub fn unformatted_fields<'p>(
+    paragraph: &'p ControlParagraph,
+    options: &WrapAndSortOptions,
+) -> Vec<&'p str> {
+    paragraph
+        .iter_fields()
+        .filter(|field| {
+            format_field_value(field.name(), field.value_str(), options)
+                .is_some_and(|value| value != field.value_str())
+        })
+        .map(|field| field.name())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::error::Result};
+
+    #[test]
+    fn wrap_and_sort() -> Result<()> {
+        let control = ControlFile::parse_str(
+            "Source: foo\n\
+             Build-Depends: zlib1g-dev,  debhelper-compat (= 13),\n \
+             ${misc:Depends}, libc6-dev | libc-dev, zlib1g-dev\n\
+             Architecture: i386 amd64\n",
+        )?;
+        let paragraph = control.paragraphs().next().unwrap();
+
+        let options = WrapAndSortOptions::default();
+        assert_eq!(
+            unformatted_fields(paragraph, &options),
+            vec!["Build-Depends", "Architecture"]
+        );
+
+        let formatted = format_paragraph(paragraph, &options);
+        assert_eq!(
+            formatted.to_string(),
+            "Source: foo\n\
+             Build-Depends: debhelper-compat (= 13),\n               \
+             libc6-dev | libc-dev,\n               \
+             zlib1g-dev,\n               \
+             ${misc:Depends}\n\
+             Architecture: amd64 i386\n"
+        );
+        assert!(unformatted_fields(&formatted, &options).is_empty());
+
+        let options = WrapAndSortOptions {
+            wrap_always: true,
+            trailing_comma: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_field_value("Depends", "b, a", &options).as_deref(),
+            Some("a,\n         b,")
+        );
+
+        let options = WrapAndSortOptions {
+            wrap_always: true,
+            short_indent: true,
+            ..Default::default()
+        };
+        let formatted = format_paragraph(paragraph, &options);
+        assert_eq!(
+            formatted.to_string(),
+            "Source: foo\n\
+             Build-Depends: \n \
+             debhelper-compat (= 13),\n \
+             libc6-dev | libc-dev,\n \
+             zlib1g-dev,\n \
+             ${misc:Depends}\n\
+             Architecture: amd64 i386\n"
+        );
+
+        Ok(())
+    }
+}
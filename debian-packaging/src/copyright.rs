@@ -0,0 +1,506 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Machine-readable `debian/copyright` files.
+
+The format, also known as DEP-5, is defined at
+<https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/>.
+*/
+
+use {
+    crate::{
+        control::{ControlFile, ControlParagraph},
+        error::{DebianError, Result},
+    },
+    regex::Regex,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        io::BufRead,
+    },
+};
+
+/// Value of the `Format` field of version 1.0 of the format.
+pub const FORMAT_URL: &str = "https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/";
+
+/// Split a formatted text field into its first line and the remaining text.
+///
+/// Continuation lines have their leading whitespace removed and lines consisting of
+/// `.` become empty lines.
+fn split_text(value: &str) -> (String, Option<String>) {
+    let mut lines = value.lines();
+    let first = lines.next().unwrap_or_default().trim().to_string();
+
+    let rest = lines
+        .map(|line| match line.trim() {
+            "." => "",
+            _ => line.trim_start(),
+        })
+        .collect::<Vec<_>>();
+
+    if rest.iter().all(|line| line.is_empty()) {
+        (first, None)
+    } else {
+        (first, Some(rest.join("\n")))
+    }
+}
+
+/// Obtain a formatted text field's value in full, with continuation lines unindented.
+fn field_text(paragraph: &ControlParagraph, name: &str) -> Option<String> {
+    paragraph.field_str(name).map(|value| {
+        let (first, rest) = split_text(value);
+
+        match (first.is_empty(), rest) {
+            (_, None) => first,
+            (true, Some(rest)) => rest,
+            (false, Some(rest)) => format!("{}\n{}", first, rest),
+        }
+    })
+}
+
+/// Obtain the non-empty lines of a field, with surrounding whitespace removed.
+fn field_lines(paragraph: &ControlParagraph, name: &str) -> Vec<String> {
+    paragraph
+        .iter_field_lines(name)
+        .map(|lines| {
+            lines
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .map(|line| line.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Convert a `Files` pattern into a regular expression.
+///
+/// `*` matches any sequence of characters, including `/`. `?` matches any single character.
+/// `\*`, `\?` and `\\` match the literal characters.
+fn pattern_regex(pattern: &str) -> Result<Regex> {
+    let mut expr = "^".to_string();
+    let mut chars = pattern.trim_start_matches("./").chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => expr.push_str(".*"),
+            '?' => expr.push('.'),
+            '\\' => match chars.next() {
+                Some(c @ ('*' | '?' | '\\')) => expr.push_str(&regex::escape(&c.to_string())),
+                _ => {
+                    return Err(DebianError::CopyrightParse(format!(
+                        "invalid escape in Files pattern: {}",
+                        pattern
+                    )))
+                }
+            },
+            c => expr.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    expr.push('$');
+
+    Regex::new(&expr).map_err(|e| DebianError::CopyrightParse(e.to_string()))
+}
+
+/// A `License` field value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct License {
+    /// The license expression on the first line, e.g. `GPL-2+ or Artistic`.
+    pub expression: String,
+
+    /// The license text or remarks following the first line, if present.
+    pub text: Option<String>,
+}
+
+impl License {
+    fn from_paragraph(paragraph: &ControlParagraph) -> Result<Self> {
+        let (expression, text) = split_text(paragraph.required_field_str("License")?);
+
+        Ok(Self { expression, text })
+    }
+
+    /// Short license names referenced by the expression.
+    ///
+    /// The expression is split on `or`, `and` and commas. Exception clauses such as
+    /// `with OpenSSL exception` are retained as part of the name they modify.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = vec![];
+        let mut current = vec![];
+
+        let expression = self.expression.replace(',', " , ");
+
+        for word in expression.split_whitespace() {
+            if word == "," || word.eq_ignore_ascii_case("or") || word.eq_ignore_ascii_case("and") {
+                if !current.is_empty() {
+                    names.push(current.join(" "));
+                    current.clear();
+                }
+            } else {
+                current.push(word);
+            }
+        }
+        if !current.is_empty() {
+            names.push(current.join(" "));
+        }
+
+        names
+    }
+}
+
+/// The header stanza of a copyright file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CopyrightHeader {
+    /// URI of the format specification.
+    pub format: String,
+    /// The name upstream uses for the software.
+    pub upstream_name: Option<String>,
+    /// Preferred addresses to reach upstream.
+    pub upstream_contact: Vec<String>,
+    /// Where the upstream source was obtained.
+    pub source: Option<String>,
+    /// Disclaimer, e.g. for packages not part of Debian proper.
+    pub disclaimer: Option<String>,
+    /// Free-form comment.
+    pub comment: Option<String>,
+    /// License of the work as a whole.
+    pub license: Option<License>,
+    /// Copyright statements for the work as a whole.
+    pub copyright: Vec<String>,
+}
+
+/// A stanza describing the copyright and license of files matching patterns.
+#[derive(Clone, Debug)]
+pub struct FilesStanza {
+    /// Whitespace separated patterns from the `Files` field.
+    pub files: Vec<String>,
+    /// Copyright statements, one per line.
+    pub copyright: Vec<String>,
+    /// The license of the matched files.
+    pub license: License,
+    /// Free-form comment.
+    pub comment: Option<String>,
+    patterns: Vec<Regex>,
+}
+
+impl FilesStanza {
+    /// Whether a path relative to the root of the source tree matches this stanza.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_start_matches("./");
+
+        self.patterns.iter().any(|re| re.is_match(path))
+    }
+}
+
+/// A stand-alone license stanza, providing the text for a license name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LicenseStanza {
+    /// The license.
+    pub license: License,
+    /// Free-form comment.
+    pub comment: Option<String>,
+}
+
+/// A summary of the licenses in a copyright file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LicenseSummary {
+    /// License names and the patterns or paths covered by each license.
+    pub licenses: BTreeMap<String, Vec<String>>,
+    /// Paths not matched by any `Files` stanza.
+    pub unmatched: Vec<String>,
+    /// License names whose text isn't present in the file.
+    pub missing_texts: BTreeSet<String>,
+}
+
+/// A parsed DEP-5 `debian/copyright` file.
+#[derive(Clone, Debug)]
+pub struct CopyrightFile {
+    /// The header stanza.
+    pub header: CopyrightHeader,
+    /// `Files` stanzas, in file order.
+    pub files: Vec<FilesStanza>,
+    /// Stand-alone license stanzas, in file order.
+    pub licenses: Vec<LicenseStanza>,
+}
+
+impl CopyrightFile {
+    /// Construct an instance from control paragraphs.
+    ///
+    /// The first paragraph is the header and must have a `Format` field. Remaining
+    /// paragraphs are `Files` stanzas if they have a `Files` field and stand-alone license
+    /// stanzas otherwise.
+    pub fn from_paragraphs<'a>(
+        mut paragraphs: impl Iterator<Item = &'a ControlParagraph<'a>>,
+    ) -> Result<Self> {
+        let header = paragraphs
+            .next()
+            .ok_or_else(|| DebianError::CopyrightParse("no header stanza".into()))?;
+
+        let header = CopyrightHeader {
+            format: header.required_field_str("Format")?.trim().to_string(),
+            upstream_name: header.field_str("Upstream-Name").map(|v| v.to_string()),
+            upstream_contact: field_lines(header, "Upstream-Contact"),
+            source: field_text(header, "Source"),
+            disclaimer: field_text(header, "Disclaimer"),
+            comment: field_text(header, "Comment"),
+            license: if header.has_field("License") {
+                Some(License::from_paragraph(header)?)
+            } else {
+                None
+            },
+            copyright: field_lines(header, "Copyright"),
+        };
+
+        let mut files = vec![];
+        let mut licenses = vec![];
+
+        for paragraph in paragraphs {
+            let license = License::from_paragraph(paragraph)?;
+            let comment = field_text(paragraph, "Comment");
+
+            if let Some(patterns) = paragraph.iter_field_words("Files") {
+                let patterns = patterns.map(|p| p.to_string()).collect::<Vec<_>>();
+                if patterns.is_empty() {
+                    return Err(DebianError::CopyrightParse("empty Files field".into()));
+                }
+                paragraph.required_field_str("Copyright")?;
+
+                files.push(FilesStanza {
+                    patterns: patterns
+                        .iter()
+                        .map(|p| pattern_regex(p))
+                        .collect::<Result<Vec<_>>>()?,
+                    files: patterns,
+                    copyright: field_lines(paragraph, "Copyright"),
+                    license,
+                    comment,
+                });
+            } else {
+                licenses.push(LicenseStanza { license, comment });
+            }
+        }
+
+        Ok(Self {
+            header,
+            files,
+            licenses,
+        })
+    }
+
+    /// Parse a copyright file from a reader.
+    pub fn parse_reader<R: BufRead>(reader: &mut R) -> Result<Self> {
+        let control = ControlFile::parse_reader(reader)?;
+
+        Self::from_paragraphs(control.paragraphs())
+    }
+
+    /// Parse a copyright file from a string.
+    pub fn parse_str(s: &str) -> Result<Self> {
+        let mut reader = std::io::BufReader::new(s.as_bytes());
+        Self::parse_reader(&mut reader)
+    }
+
+    /// Find the `Files` stanza governing a path relative to the root of the source tree.
+    ///
+    /// When multiple stanzas match, the last one wins.
+    pub fn files_stanza_for_path(&self, path: &str) -> Option<&FilesStanza> {
+        self.files.iter().rev().find(|stanza| stanza.matches(path))
+    }
+
+    /// Obtain the text of a license by its short name.
+    ///
+    /// Stand-alone license stanzas take precedence over text in `Files` stanzas and the
+    /// header. Exception clauses (`with ... exception`) are ignored when looking up a name.
+    pub fn license_text(&self, name: &str) -> Option<&str> {
+        let base = |n: &str| -> String {
+            let lower = n.to_ascii_lowercase();
+            match lower.find(" with ") {
+                Some(pos) => lower[..pos].trim().to_string(),
+                None => lower.trim().to_string(),
+            }
+        };
+        let wanted = base(name);
+
+        self.licenses
+            .iter()
+            .map(|l| &l.license)
+            .chain(self.files.iter().map(|f| &f.license))
+            .chain(self.header.license.iter())
+            .filter(|l| l.names().len() == 1 && base(&l.expression) == wanted)
+            .find_map(|l| l.text.as_deref())
+    }
+
+    fn summary<'a>(
+        &'a self,
+        assignments: impl Iterator<Item = (&'a License, String)>,
+    ) -> LicenseSummary {
+        let mut summary = LicenseSummary::default();
+
+        for (license, value) in assignments {
+            for name in license.names() {
+                if self.license_text(&name).is_none() {
+                    summary.missing_texts.insert(name.clone());
+                }
+                summary
+                    .licenses
+                    .entry(name)
+                    .or_default()
+                    .push(value.clone());
+            }
+        }
+
+        summary
+    }
+
+    /// Summarize the licenses of this file by the `Files` patterns they cover.
+    pub fn license_summary(&self) -> LicenseSummary {
+        self.summary(self.files.iter().flat_map(|stanza| {
+            stanza
+                .files
+                .iter()
+                .map(move |pattern| (&stanza.license, pattern.clone()))
+        }))
+    }
+
+    /// Summarize the licenses governing a set of paths relative to the root of the source tree.
+    ///
+    /// Paths not matched by any `Files` stanza are recorded in [LicenseSummary::unmatched].
+    pub fn summarize_paths<'p>(&self, paths: impl IntoIterator<Item = &'p str>) -> LicenseSummary {
+        let mut unmatched = vec![];
+        let mut assignments = vec![];
+
+        for path in paths {
+            match self.files_stanza_for_path(path) {
+                Some(stanza) => assignments.push((&stanza.license, path.to_string())),
+                None => unmatched.push(path.to_string()),
+            }
+        }
+
+        let mut summary = self.summary(assignments.into_iter());
+        summary.unmatched = unmatched;
+
+        summary
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const COPYRIGHT: &str =
+        "Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/
+Upstream-Name: hello
+Upstream-Contact: Jane Doe <jane@example.com>
+Source: https://example.com/hello
+
+Files: *
+Copyright: 2020 Jane Doe
+ 2021 John Doe
+License: GPL-2+ or Artistic
+
+Files: debian/*
+Copyright: 2024 Packager
+License: MIT
+ Permission is hereby granted.
+ .
+ The software is provided as is.
+
+Files: src/vendor/* lib/*.\\?
+Copyright: 2019 Vendor
+License: GPL-2+ with OpenSSL exception
+
+License: GPL-2+
+ This program is free software.
+";
+
+    #[test]
+    fn parse() -> Result<()> {
+        let cf = CopyrightFile::parse_str(COPYRIGHT)?;
+
+        assert_eq!(cf.header.format, FORMAT_URL);
+        assert_eq!(cf.header.upstream_name.as_deref(), Some("hello"));
+        assert_eq!(cf.files.len(), 3);
+        assert_eq!(cf.licenses.len(), 1);
+        assert_eq!(
+            cf.files[0].copyright,
+            vec!["2020 Jane Doe", "2021 John Doe"]
+        );
+        assert_eq!(cf.files[0].license.names(), vec!["GPL-2+", "Artistic"]);
+        assert_eq!(
+            cf.files[1].license.text.as_deref(),
+            Some("Permission is hereby granted.\n\nThe software is provided as is.")
+        );
+        assert_eq!(
+            cf.files[2].license.names(),
+            vec!["GPL-2+ with OpenSSL exception"]
+        );
+
+        assert_eq!(
+            cf.files_stanza_for_path("debian/rules")
+                .unwrap()
+                .license
+                .expression,
+            "MIT"
+        );
+        assert_eq!(
+            cf.files_stanza_for_path("src/vendor/a/b.c")
+                .unwrap()
+                .license
+                .expression,
+            "GPL-2+ with OpenSSL exception"
+        );
+        assert_eq!(
+            cf.files_stanza_for_path("lib/x.?")
+                .unwrap()
+                .license
+                .expression,
+            "GPL-2+ with OpenSSL exception"
+        );
+        assert_eq!(
+            cf.files_stanza_for_path("lib/x.c")
+                .unwrap()
+                .license
+                .expression,
+            "GPL-2+ or Artistic"
+        );
+        assert_eq!(
+            cf.license_text("GPL-2+ with OpenSSL exception"),
+            Some("This program is free software.")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn summary() -> Result<()> {
+        let cf = CopyrightFile::parse_str(COPYRIGHT)?;
+
+        let summary = cf.license_summary();
+        assert_eq!(
+            summary.licenses.keys().collect::<Vec<_>>(),
+            vec!["Artistic", "GPL-2+", "GPL-2+ with OpenSSL exception", "MIT"]
+        );
+        assert_eq!(summary.licenses["GPL-2+"], vec!["*"]);
+        assert_eq!(
+            summary.missing_texts.into_iter().collect::<Vec<_>>(),
+            vec!["Artistic"]
+        );
+
+        let summary = cf.summarize_paths(["README", "debian/control", "src/vendor/x.c"]);
+        assert_eq!(summary.licenses["MIT"], vec!["debian/control"]);
+        assert_eq!(summary.licenses["Artistic"], vec!["README"]);
+        assert!(summary.unmatched.is_empty());
+
+        let cf =
+            CopyrightFile::parse_str("Format: x\n\nFiles: src/*\nCopyright: me\nLicense: MIT\n")?;
+        assert_eq!(
+            cf.summarize_paths(["README"]).unmatched,
+            vec!["README".to_string()]
+        );
+
+        assert!(matches!(
+            CopyrightFile::parse_str("Format: x\n\nFiles: *\nLicense: MIT\n"),
+            Err(DebianError::ControlRequiredFieldMissing(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Deterministic ar archive writing.
+
+`.deb` files are ar archives. [ArArchiveBuilder] writes them with fixed member
+metadata so output only depends on member names and content.
+
+The common ar format limits member names to 16 characters. Archives having longer
+names are written in the GNU format, which stores long names in a `//` name table
+ahead of all other members. dpkg requires `debian-binary` to be the first member of a
+`.deb` file and rejects GNU format archives, so builders for `.deb` files set
+[ArArchiveBuilder::set_deb] to refuse long names instead.
+*/
+
+use {
+    crate::error::{DebianError, Result},
+    std::io::Write,
+};
+
+/// Maximum length of member names in the common ar format.
+const MAX_SHORT_NAME_LENGTH: usize = 16;
+
+/// A builder for ar archives with deterministic member metadata.
+///
+/// All members are owned by uid and gid 0 and have the same modification time.
+#[derive(Clone, Debug, Default)]
+pub struct ArArchiveBuilder {
+    members: Vec<(Vec<u8>, u32, Vec<u8>)>,
+    mtime: u64,
+    deb: bool,
+}
+
+impl ArArchiveBuilder {
+    /// Set the modification time of all members, in seconds since the UNIX epoch.
+    ///
+    /// Defaults to 0.
+    #[must_use]
+    pub fn set_mtime(mut self, mtime: u64) -> Self {
+        self.mtime = mtime;
+        self
+    }
+
+    /// Set whether the archive is a `.deb` file.
+    ///
+    /// `.deb` files are always written in the common ar format. [Self::write] fails if
+    /// a member name is longer than 16 characters.
+    #[must_use]
+    pub fn set_deb(mut self, deb: bool) -> Self {
+        self.deb = deb;
+        self
+    }
+
+    /// Add a member with mode `0644`.
+    pub fn add_member(self, name: impl Into<Vec<u8>>, data: impl Into<Vec<u8>>) -> Result<Self> {
+        self.add_member_with_mode(name, 0o644, data)
+    }
+
+    /// Add a member with the given permission bits.
+    ///
+    /// Names must be non-empty and can't contain `/` or whitespace, as neither can be
+    /// represented in every ar format.
+    pub fn add_member_with_mode(
+        mut self,
+        name: impl Into<Vec<u8>>,
+        mode: u32,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<Self> {
+        let name = name.into();
+
+        if name.is_empty() || name.iter().any(|c| *c == b'/' || c.is_ascii_whitespace()) {
+            return Err(DebianError::ArMemberNameInvalid(
+                String::from_utf8_lossy(&name).to_string(),
+            ));
+        }
+
+        self.members.push((name, mode, data.into()));
+
+        Ok(self)
+    }
+
+    /// Write the archive to a writer, returning the writer.
+    pub fn write<W: Write>(&self, writer: W) -> Result<W> {
+        if self.deb {
+            if let Some((name, _, _)) = self
+                .members
+                .iter()
+                .find(|(name, _, _)| name.len() > MAX_SHORT_NAME_LENGTH)
+            {
+                return Err(DebianError::ArMemberNameTooLong(
+                    String::from_utf8_lossy(name).to_string(),
+                ));
+            }
+        }
+
+        let headers = self
+            .members
+            .iter()
+            .map(|(name, mode, data)| {
+                let mut header = ar::Header::new(name.clone(), data.len() as _);
+                header.set_mode(*mode);
+                header.set_mtime(self.mtime);
+                header.set_uid(0);
+                header.set_gid(0);
+                header
+            })
+            .collect::<Vec<_>>();
+
+        if self
+            .members
+            .iter()
+            .any(|(name, _, _)| name.len() > MAX_SHORT_NAME_LENGTH)
+        {
+            let mut builder = ar::GnuBuilder::new(
+                writer,
+                self.members
+                    .iter()
+                    .map(|(name, _, _)| name.clone())
+                    .collect(),
+            );
+            for (header, (_, _, data)) in headers.iter().zip(&self.members) {
+                builder.append(header, data.as_slice())?;
+            }
+
+            Ok(builder.into_inner()?)
+        } else {
+            let mut builder = ar::Builder::new(writer);
+            for (header, (_, _, data)) in headers.iter().zip(&self.members) {
+                builder.append(header, data.as_slice())?;
+            }
+
+            Ok(builder.into_inner()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, std::io::Read};
+
+    fn read_members(data: &[u8]) -> Result<Vec<(String, u64, Vec<u8>)>> {
+        let mut archive = ar::Archive::new(data);
+        let mut res = vec![];
+
+        while let Some(entry) = archive.next_entry() {
+            let mut entry = entry?;
+            let mut data = vec![];
+            entry.read_to_end(&mut data)?;
+            res.push((
+                String::from_utf8_lossy(entry.header().identifier()).to_string(),
+                entry.header().mtime(),
+                data,
+            ));
+        }
+
+        Ok(res)
+    }
+
+    #[test]
+    fn long_names() -> Result<()> {
+        let builder = ArArchiveBuilder::default()
+            .set_mtime(42)
+            .add_member("debian-binary", b"2.0\n".to_vec())?
+            .add_member("a-very-long-member-name.tar.xz", b"odd".to_vec())?
+            .add_member("another-long-member-name", b"even".to_vec())?;
+
+        let data = builder.write(vec![])?;
+        assert_eq!(builder.write(vec![])?, data);
+        assert!(data.windows(3).any(|w| w == b"\n//"));
+
+        assert_eq!(
+            read_members(&data)?,
+            vec![
+                ("debian-binary".to_string(), 42, b"2.0\n".to_vec()),
+                (
+                    "a-very-long-member-name.tar.xz".to_string(),
+                    42,
+                    b"odd".to_vec()
+                ),
+                ("another-long-member-name".to_string(), 42, b"even".to_vec()),
+            ]
+        );
+
+        let data = ArArchiveBuilder::default()
+            .add_member("debian-binary", b"2.0\n".to_vec())?
+            .write(vec![])?;
+        assert!(data.starts_with(b"!<arch>\ndebian-binary   0 "));
+
+        // `.deb` files never use the GNU format.
+        let mut control = crate::control::ControlFile::default();
+        let mut para = crate::control::ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "mypackage".into());
+        control.add_paragraph(para);
+        let mut deb = vec![];
+        crate::deb::builder::DebBuilder::new(control)
+            .install_file("usr/bin/myapp", vec![42])?
+            .write(&mut deb)?;
+
+        assert!(deb.starts_with(b"!<arch>\ndebian-binary   "));
+
+        let mut builder = ArArchiveBuilder::default().set_deb(true);
+        for (name, _, data) in read_members(&deb)? {
+            builder = builder.add_member(name, data)?;
+        }
+        assert!(builder
+            .write(vec![])?
+            .starts_with(b"!<arch>\ndebian-binary   "));
+        assert!(matches!(
+            builder
+                .add_member("_an-extension-with-a-long-name", vec![])?
+                .write(vec![]),
+            Err(DebianError::ArMemberNameTooLong(name)) if name == "_an-extension-with-a-long-name"
+        ));
+
+        assert!(matches!(
+            ArArchiveBuilder::default().add_member("a/b", vec![]),
+            Err(DebianError::ArMemberNameInvalid(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -5,27 +5,205 @@
 /*! Create .deb package files and their components. */
 
 use {
-    crate::{control::ControlFile, deb::DebCompression, error::Result},
+    crate::{
+        binary_package_control::BinaryPackageControlFile,
+        control::{ControlFile, ControlParagraph},
+        deb::{
+            archive::ArArchiveBuilder, CompressionOptions, DebCompression, MaintainerScript,
+            PackageType,
+        },
+        dependency::DependencyList,
+        error::{DebianError, Result},
+    },
     md5::Digest,
     os_str_bytes::OsStrBytes,
     simple_file_manifest::{FileEntry, FileManifest},
     std::{
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet},
         io::{BufWriter, Cursor, Read, Write},
-        path::Path,
+        path::{Path, PathBuf},
         time::SystemTime,
     },
 };
 
+/// Ownership recorded on entries in `data.tar` archives.
+///
+/// Defaults to `root:root` (uid and gid 0), which is what packages conventionally use.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TarOwnership {
+    /// Numeric user id.
+    pub uid: u64,
+    /// Numeric group id.
+    pub gid: u64,
+    /// User name.
+    pub username: String,
+    /// Group name.
+    pub groupname: String,
+}
+
+impl Default for TarOwnership {
+    fn default() -> Self {
+        Self {
+            uid: 0,
+            gid: 0,
+            username: "root".into(),
+            groupname: "root".into(),
+        }
+    }
+}
+
+/// Rules controlling how [DebBuilder::install_directory()] ingests a directory tree.
+///
+/// Glob patterns are matched against installed paths relative to the filesystem root,
+/// e.g. `usr/bin/*`. When multiple rules of a kind match a path, the last one added wins.
+#[derive(Clone, Debug)]
+pub struct DirectoryInstallRules {
+    prefix: PathBuf,
+    exclude: Vec<glob::Pattern>,
+    executable_from_filesystem: bool,
+    executable: Vec<glob::Pattern>,
+    modes: Vec<(glob::Pattern, u32)>,
+    ownership: Vec<(glob::Pattern, TarOwnership)>,
+    follow_symlinks: bool,
+}
+
+impl Default for DirectoryInstallRules {
+    fn default() -> Self {
+        Self {
+            prefix: PathBuf::new(),
+            exclude: vec![],
+            executable_from_filesystem: true,
+            executable: vec![],
+            modes: vec![],
+            ownership: vec![],
+            follow_symlinks: false,
+        }
+    }
+}
+
+impl DirectoryInstallRules {
+    /// Set the path under which the directory's content is installed.
+    ///
+    /// e.g. `opt/myapp`. Defaults to the filesystem root.
+    pub fn set_install_prefix(&mut self, prefix: impl AsRef<Path>) {
+        self.prefix = PathBuf::from(normalize_installed_path(prefix.as_ref()));
+    }
+
+    /// Exclude paths matching a glob pattern.
+    ///
+    /// Excluding a directory excludes everything beneath it.
+    pub fn add_exclude_pattern(&mut self, pattern: &str) -> Result<()> {
+        self.exclude.push(glob::Pattern::new(pattern)?);
+        Ok(())
+    }
+
+    /// Set whether executable bits are taken from the filesystem.
+    ///
+    /// Defaults to true. When false, files are only executable if they match a pattern
+    /// from [Self::add_executable_pattern()] or a mode rule.
+    pub fn set_executable_from_filesystem(&mut self, value: bool) {
+        self.executable_from_filesystem = value;
+    }
+
+    /// Mark files matching a glob pattern as executable (mode 0755).
+    pub fn add_executable_pattern(&mut self, pattern: &str) -> Result<()> {
+        self.executable.push(glob::Pattern::new(pattern)?);
+        Ok(())
+    }
+
+    /// Use an explicit mode for files and directories matching a glob pattern.
+    ///
+    /// This takes precedence over executable bit rules. e.g. `0o4755` for a setuid
+    /// binary or `0o600` for a file holding secrets.
+    pub fn add_mode_rule(&mut self, pattern: &str, mode: u32) -> Result<()> {
+        self.modes.push((glob::Pattern::new(pattern)?, mode));
+        Ok(())
+    }
+
+    /// Use explicit ownership for files and directories matching a glob pattern.
+    ///
+    /// Paths not matching a rule use the builder's [DebBuilder::set_data_ownership()].
+    pub fn add_ownership_rule(&mut self, pattern: &str, ownership: TarOwnership) -> Result<()> {
+        self.ownership
+            .push((glob::Pattern::new(pattern)?, ownership));
+        Ok(())
+    }
+
+    /// Set whether symlinks are followed.
+    ///
+    /// Defaults to false, which records symlinks as symlinks. When true, symlinks to
+    /// files are replaced by the content of their target. Symlinks to directories are
+    /// always recorded as symlinks.
+    pub fn set_follow_symlinks(&mut self, value: bool) {
+        self.follow_symlinks = value;
+    }
+
+    fn matches<'a, T>(rules: &'a [(glob::Pattern, T)], path: &str) -> Option<&'a T> {
+        rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map(|(_, value)| value)
+    }
+}
+
+/// Per-path metadata applied when writing `data.tar` archives.
+#[derive(Clone, Debug, Default)]
+struct DataTarMetadata {
+    /// Directories to emit even if they contain no files.
+    directories: BTreeSet<PathBuf>,
+    /// Explicit modes of files and directories.
+    modes: BTreeMap<PathBuf, u32>,
+    /// Explicit ownership of files and directories.
+    ownership: BTreeMap<PathBuf, TarOwnership>,
+}
+
+/// Resolve the modified time of archive members, clamped to a `SOURCE_DATE_EPOCH`.
+pub(crate) fn resolve_mtime(mtime: Option<SystemTime>, source_date_epoch: Option<u64>) -> u64 {
+    let mtime = mtime
+        .unwrap_or_else(std::time::SystemTime::now)
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("times before UNIX epoch not accepted")
+        .as_secs();
+
+    match source_date_epoch {
+        Some(epoch) => mtime.min(epoch),
+        None => mtime,
+    }
+}
+
+/// Resolve the `SOURCE_DATE_EPOCH` environment variable.
+///
+/// See <https://reproducible-builds.org/specs/source-date-epoch/>.
+pub(crate) fn source_date_epoch_from_env() -> Result<Option<u64>> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => Ok(Some(value.trim().parse()?)),
+        Err(_) => Ok(None),
+    }
+}
+
 /// A builder for a `.deb` package file.
 pub struct DebBuilder<'control> {
     control_builder: ControlTarBuilder<'control>,
 
     compression: DebCompression,
 
+    compression_options: CompressionOptions,
+
     /// Files to install as part of the package.
     install_files: FileManifest,
 
     mtime: Option<SystemTime>,
+
+    /// Upper bound for modified times of archive members.
+    source_date_epoch: Option<u64>,
+
+    /// Ownership of entries in `data.tar`.
+    data_ownership: TarOwnership,
+
+    /// Per-path metadata of entries in `data.tar`.
+    data_metadata: DataTarMetadata,
 }
 
 impl<'control> DebBuilder<'control> {
@@ -34,8 +212,12 @@ impl<'control> DebBuilder<'control> {
         Self {
             control_builder: ControlTarBuilder::new(control_file),
             compression: DebCompression::Gzip,
-            install_files: FileManifest::default(),
+            compression_options: CompressionOptions::default(),
+            install_files: FileManifest::new_with_links(),
             mtime: None,
+            source_date_epoch: None,
+            data_ownership: TarOwnership::default(),
+            data_metadata: DataTarMetadata::default(),
         }
     }
 
@@ -48,12 +230,36 @@ impl<'control> DebBuilder<'control> {
         self
     }
 
+    /// Set the type of package to produce.
+    ///
+    /// This also resets the compression format to the conventional one for the
+    /// package type. Call [Self::set_compression()] afterwards to override it.
+    ///
+    /// See [ControlTarBuilder::set_package_type()] for how `.udeb` packages differ.
+    #[must_use]
+    pub fn set_package_type(mut self, package_type: PackageType) -> Self {
+        self.control_builder = self.control_builder.set_package_type(package_type);
+        self.compression = package_type.default_compression();
+        self
+    }
+
+    /// Obtain the conventional filename of the package being built.
+    ///
+    /// This is `<package>_<version>_<architecture>` (with any version epoch removed)
+    /// followed by the extension of the package type.
+    pub fn package_filename(&self) -> Result<String> {
+        self.control_builder.package_filename()
+    }
+
+    /// Set options for the compression encoder, such as the number of threads.
+    #[must_use]
+    pub fn set_compression_options(mut self, options: CompressionOptions) -> Self {
+        self.compression_options = options;
+        self
+    }
+
     fn mtime(&self) -> u64 {
-        self.mtime
-            .unwrap_or_else(std::time::SystemTime::now)
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("times before UNIX epoch not accepted")
-            .as_secs()
+        resolve_mtime(self.mtime, self.source_date_epoch)
     }
 
     /// Set the modified time to use on archive members.
@@ -69,6 +275,35 @@ impl<'control> DebBuilder<'control> {
         self
     }
 
+    /// Clamp modified times of archive members to a `SOURCE_DATE_EPOCH` value.
+    ///
+    /// Archive members use the earlier of the time from [Self::set_mtime()] (or the
+    /// current time) and this value, in seconds since the UNIX epoch. Together with
+    /// the builder's fixed ownership, entry ordering and compression settings, this
+    /// makes identical inputs produce bit-identical `.deb` files.
+    #[must_use]
+    pub fn set_source_date_epoch(mut self, epoch: Option<u64>) -> Self {
+        self.source_date_epoch = epoch;
+        self.control_builder = self.control_builder.set_source_date_epoch(epoch);
+        self
+    }
+
+    /// Clamp modified times to the `SOURCE_DATE_EPOCH` environment variable, if set.
+    ///
+    /// See [Self::set_source_date_epoch()].
+    pub fn set_source_date_epoch_from_env(self) -> Result<Self> {
+        Ok(self.set_source_date_epoch(source_date_epoch_from_env()?))
+    }
+
+    /// Set the ownership recorded on entries in `data.tar`.
+    ///
+    /// Defaults to `root:root`.
+    #[must_use]
+    pub fn set_data_ownership(mut self, ownership: TarOwnership) -> Self {
+        self.data_ownership = ownership;
+        self
+    }
+
     /// Add an extra file to the `control.tar` archive.
     pub fn extra_control_tar_file(
         mut self,
@@ -79,6 +314,61 @@ impl<'control> DebBuilder<'control> {
         Ok(self)
     }
 
+    /// Add a maintainer script to the `control.tar` archive.
+    ///
+    /// See [ControlTarBuilder::add_maintainer_script()].
+    pub fn add_maintainer_script(
+        mut self,
+        script: MaintainerScript,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<Self> {
+        self.control_builder = self.control_builder.add_maintainer_script(script, data)?;
+        Ok(self)
+    }
+
+    /// Set whether to sanity check maintainer scripts when writing.
+    ///
+    /// See [ControlTarBuilder::set_check_maintainer_scripts()].
+    #[must_use]
+    pub fn set_check_maintainer_scripts(mut self, value: bool) -> Self {
+        self.control_builder = self.control_builder.set_check_maintainer_scripts(value);
+        self
+    }
+
+    /// Set the content of the `shlibs` file in the `control.tar` archive.
+    ///
+    /// See [ControlTarBuilder::set_shlibs()].
+    pub fn set_shlibs(mut self, data: impl Into<Vec<u8>>) -> Result<Self> {
+        self.control_builder = self.control_builder.set_shlibs(data)?;
+        Ok(self)
+    }
+
+    /// Set the content of the `symbols` file in the `control.tar` archive.
+    ///
+    /// See [ControlTarBuilder::set_symbols()].
+    pub fn set_symbols(mut self, data: impl Into<Vec<u8>>) -> Result<Self> {
+        self.control_builder = self.control_builder.set_symbols(data)?;
+        Ok(self)
+    }
+
+    /// Declare an installed file as a conffile.
+    ///
+    /// See [ControlTarBuilder::add_conffile()].
+    #[must_use]
+    pub fn add_conffile(mut self, path: impl AsRef<Path>) -> Self {
+        self.control_builder = self.control_builder.add_conffile(path);
+        self
+    }
+
+    /// Set whether conffiles may live outside `/etc`.
+    ///
+    /// See [ControlTarBuilder::set_allow_conffiles_outside_etc()].
+    #[must_use]
+    pub fn set_allow_conffiles_outside_etc(mut self, value: bool) -> Self {
+        self.control_builder = self.control_builder.set_allow_conffiles_outside_etc(value);
+        self
+    }
+
     /// Register a file as to be installed by this package.
     ///
     /// Filenames should be relative to the filesystem root. e.g.
@@ -93,78 +383,281 @@ impl<'control> DebBuilder<'control> {
     ) -> Result<Self> {
         let entry = entry.into();
 
-        let data = entry.resolve_content()?;
-        let mut cursor = Cursor::new(&data);
-        self.control_builder = self
-            .control_builder
-            .add_data_file(path.clone(), &mut cursor)?;
+        // Symlinks don't have content to checksum.
+        if entry.link_target().is_none() {
+            let data = entry.resolve_content()?;
+            let mut cursor = Cursor::new(&data);
+            self.control_builder = self
+                .control_builder
+                .add_data_file(path.clone(), &mut cursor)?;
+        }
 
         self.install_files.add_file_entry(path, entry)?;
 
         Ok(self)
     }
 
+    /// Register a symlink as to be installed by this package.
+    ///
+    /// `path` is relative to the filesystem root. `target` is recorded verbatim.
+    pub fn install_symlink(
+        mut self,
+        path: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+    ) -> Result<Self> {
+        self.install_files.add_symlink(path, target)?;
+        Ok(self)
+    }
+
+    /// Set an explicit mode for an installed file or directory.
+    ///
+    /// By default, files are 0755 if executable and 0644 otherwise and directories
+    /// are 0755.
+    #[must_use]
+    pub fn set_path_mode(mut self, path: impl AsRef<Path>, mode: u32) -> Self {
+        self.data_metadata
+            .modes
+            .insert(normalize_installed_path(path.as_ref()).into(), mode);
+        self
+    }
+
+    /// Set explicit ownership for an installed file or directory.
+    ///
+    /// Overrides [Self::set_data_ownership()] for this path.
+    #[must_use]
+    pub fn set_path_ownership(mut self, path: impl AsRef<Path>, ownership: TarOwnership) -> Self {
+        self.data_metadata
+            .ownership
+            .insert(normalize_installed_path(path.as_ref()).into(), ownership);
+        self
+    }
+
+    /// Install the content of a directory tree on the filesystem.
+    ///
+    /// Every file, symlink and directory (including empty ones) under `source_dir` is
+    /// installed at the same relative path, subject to `rules`. File content is read
+    /// from the filesystem to compute checksums and again when the package is written,
+    /// so the files must not change in between. Files other than regular files,
+    /// directories and symlinks are rejected.
+    pub fn install_directory(
+        mut self,
+        source_dir: impl AsRef<Path>,
+        rules: &DirectoryInstallRules,
+    ) -> Result<Self> {
+        let source_dir = source_dir.as_ref();
+
+        let mut pending = vec![source_dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let mut children = std::fs::read_dir(&dir)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            children.sort();
+
+            for source_path in children {
+                let rel_path = rules.prefix.join(
+                    source_path
+                        .strip_prefix(source_dir)
+                        .expect("children are under source directory"),
+                );
+                let rel_str = rel_path.display().to_string();
+
+                if rules.exclude.iter().any(|p| p.matches(&rel_str)) {
+                    continue;
+                }
+
+                let mode = DirectoryInstallRules::matches(&rules.modes, &rel_str).copied();
+                if let Some(mode) = mode {
+                    self.data_metadata.modes.insert(rel_path.clone(), mode);
+                }
+                if let Some(ownership) = DirectoryInstallRules::matches(&rules.ownership, &rel_str)
+                {
+                    self.data_metadata
+                        .ownership
+                        .insert(rel_path.clone(), ownership.clone());
+                }
+
+                let mut metadata = std::fs::symlink_metadata(&source_path)?;
+                if metadata.file_type().is_symlink() && rules.follow_symlinks {
+                    let target_metadata = std::fs::metadata(&source_path)?;
+                    if target_metadata.is_file() {
+                        metadata = target_metadata;
+                    }
+                }
+
+                if metadata.is_dir() {
+                    self.data_metadata.directories.insert(rel_path);
+                    pending.push(source_path);
+                } else if metadata.file_type().is_symlink() {
+                    let target = std::fs::read_link(&source_path)?;
+                    self = self.install_symlink(rel_path, target)?;
+                } else if metadata.is_file() {
+                    let executable = if let Some(mode) = mode {
+                        mode & 0o111 != 0
+                    } else {
+                        rules.executable.iter().any(|p| p.matches(&rel_str))
+                            || (rules.executable_from_filesystem
+                                && simple_file_manifest::is_executable(&metadata))
+                    };
+
+                    self = self.install_file(
+                        rel_path,
+                        FileEntry::new_from_path(&source_path, executable),
+                    )?;
+                } else {
+                    return Err(DebianError::DebStagingUnsupportedFile(
+                        source_path.display().to_string(),
+                    ));
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Compute the `Installed-Size` of the package, in KiB.
+    ///
+    /// This follows `dpkg-gencontrol`: regular files and symlinks count their size
+    /// rounded up to whole KiB and every other entry, such as a directory, counts 1.
+    ///
+    /// [Self::write()] adds this value to the control paragraph unless it already has
+    /// an `Installed-Size` field.
+    pub fn installed_size(&self) -> Result<u64> {
+        // The root directory.
+        let mut size = 1;
+
+        size += data_tar_directories(&self.install_files, &self.data_metadata).len() as u64;
+
+        for (_, content) in self.install_files.iter_entries() {
+            let bytes = if let Some(target) = content.link_target() {
+                target.as_os_str().len() as u64
+            } else {
+                content.resolve_content()?.len() as u64
+            };
+
+            size += bytes.div_ceil(1024);
+        }
+
+        Ok(size)
+    }
+
+    /// Parse the ELF objects among installed files.
+    ///
+    /// Requires the `shlibdeps` crate feature.
+    #[cfg(feature = "shlibdeps")]
+    pub fn elf_objects(&self) -> Result<Vec<crate::shlibdeps::ElfObject>> {
+        let mut res = vec![];
+
+        for (_, content) in self.install_files.iter_entries() {
+            if content.link_target().is_none() {
+                if let Some(object) =
+                    crate::shlibdeps::ElfObject::parse(&content.resolve_content()?)?
+                {
+                    res.push(object);
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Add shared library dependencies of installed ELF objects to `Depends`.
+    ///
+    /// Dependencies are resolved from [Self::elf_objects()] by `resolver` and merged
+    /// into any existing `Depends` field of the first control paragraph. This is akin
+    /// to running `dpkg-shlibdeps` and substituting `${shlibs:Depends}`.
+    ///
+    /// Requires the `shlibdeps` crate feature.
+    #[cfg(feature = "shlibdeps")]
+    pub fn set_shlib_depends(
+        mut self,
+        resolver: &crate::shlibdeps::ShlibDependencyResolver,
+    ) -> Result<Self> {
+        let resolved = resolver.resolve(&self.elf_objects()?)?;
+        if resolved.requirements().next().is_none() {
+            return Ok(self);
+        }
+
+        let mut control = ControlFile::default();
+        for (i, para) in self.control_builder.control.paragraphs().enumerate() {
+            let mut para = para.clone();
+
+            if i == 0 {
+                let depends = crate::shlibdeps::merge_dependencies(
+                    para.field_str("Depends")
+                        .map(|v| v.to_string())
+                        .into_iter()
+                        .chain(std::iter::once(resolved.to_string())),
+                )?;
+                para.set_field_from_string("Depends".into(), depends.to_string().into());
+            }
+
+            control.add_paragraph(para);
+        }
+        self.control_builder.control = control;
+
+        Ok(self)
+    }
+
     /// Write `.deb` file content to a writer.
     ///
     /// This effectively materialized the `.deb` package somewhere.
     pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
-        let mut ar_builder = ar::Builder::new(writer);
-
-        // First entry is a debian-binary file with static content.
-        let data: &[u8] = b"2.0\n";
-        let mut header = ar::Header::new(b"debian-binary".to_vec(), data.len() as _);
-        header.set_mode(0o644);
-        header.set_mtime(self.mtime());
-        header.set_uid(0);
-        header.set_gid(0);
-        ar_builder.append(&header, data)?;
+        // Resolve once so all members agree even if the clock ticks while writing.
+        let mtime = self.mtime();
 
         // Second entry is a control.tar with metadata.
         let mut control_writer = BufWriter::new(Vec::new());
-        self.control_builder.write(&mut control_writer)?;
+        self.control_builder.write_with_mtime(
+            &mut control_writer,
+            mtime,
+            Some(self.installed_size()?),
+        )?;
         let control_tar = control_writer.into_inner().map_err(|e| e.into_error())?;
-        let control_tar = self
-            .compression
-            .compress(&mut std::io::Cursor::new(control_tar))?;
-
-        let mut header = ar::Header::new(
-            format!("control.tar{}", self.compression.extension()).into_bytes(),
-            control_tar.len() as _,
-        );
-        header.set_mode(0o644);
-        header.set_mtime(self.mtime());
-        header.set_uid(0);
-        header.set_gid(0);
-        ar_builder.append(&header, &*control_tar)?;
+        let control_tar = self.compression.compress_with_options(
+            &mut std::io::Cursor::new(control_tar),
+            &self.compression_options,
+        )?;
 
         // Third entry is a data.tar with file content.
         let mut data_writer = BufWriter::new(Vec::new());
-        write_deb_tar(&mut data_writer, &self.install_files, self.mtime())?;
+        write_data_tar(
+            &mut data_writer,
+            &self.install_files,
+            mtime,
+            &self.data_ownership,
+            &self.data_metadata,
+        )?;
         let data_tar = data_writer.into_inner().map_err(|e| e.into_error())?;
-        let data_tar = self
-            .compression
-            .compress(&mut std::io::Cursor::new(data_tar))?;
+        let data_tar = self.compression.compress_with_options(
+            &mut std::io::Cursor::new(data_tar),
+            &self.compression_options,
+        )?;
 
-        let mut header = ar::Header::new(
-            format!("data.tar{}", self.compression.extension()).into_bytes(),
-            data_tar.len() as _,
-        );
-        header.set_mode(0o644);
-        header.set_mtime(self.mtime());
-        header.set_uid(0);
-        header.set_gid(0);
-        ar_builder.append(&header, &*data_tar)?;
+        // First entry is a debian-binary file with static content.
+        ArArchiveBuilder::default()
+            .set_deb(true)
+            .set_mtime(mtime)
+            .add_member("debian-binary", b"2.0\n".to_vec())?
+            .add_member(
+                format!("control.tar{}", self.compression.extension()),
+                control_tar,
+            )?
+            .add_member(
+                format!("data.tar{}", self.compression.extension()),
+                data_tar,
+            )?
+            .write(writer)?;
 
         Ok(())
     }
 }
 
-fn new_tar_header(mtime: u64) -> Result<tar::Header> {
+pub(crate) fn new_tar_header(mtime: u64, ownership: &TarOwnership) -> Result<tar::Header> {
     let mut header = tar::Header::new_gnu();
-    header.set_uid(0);
-    header.set_gid(0);
-    header.set_username("root")?;
-    header.set_groupname("root")?;
+    header.set_uid(ownership.uid);
+    header.set_gid(ownership.gid);
+    header.set_username(&ownership.username)?;
+    header.set_groupname(&ownership.groupname)?;
     header.set_mtime(mtime);
 
     Ok(header)
@@ -228,8 +721,20 @@ pub struct ControlTarBuilder<'a> {
     extra_files: FileManifest,
     /// Hashes of files that will be installed.
     md5sums: Vec<Vec<u8>>,
+    /// Paths of files that will be installed, relative to the filesystem root.
+    data_paths: BTreeSet<String>,
+    /// Installed paths to register as conffiles, relative to the filesystem root.
+    conffiles: Vec<String>,
+    /// Whether conffiles may live outside `/etc`.
+    allow_conffiles_outside_etc: bool,
     /// Modified time for tar archive entries.
     mtime: Option<SystemTime>,
+    /// Upper bound for modified times of tar archive entries.
+    source_date_epoch: Option<u64>,
+    /// Whether to sanity check maintainer scripts when writing.
+    check_maintainer_scripts: bool,
+    /// The type of package the archive belongs to.
+    package_type: PackageType,
 }
 
 impl<'a> ControlTarBuilder<'a> {
@@ -239,7 +744,13 @@ impl<'a> ControlTarBuilder<'a> {
             control: control_file,
             extra_files: FileManifest::default(),
             md5sums: vec![],
+            data_paths: BTreeSet::new(),
+            conffiles: vec![],
+            allow_conffiles_outside_etc: false,
             mtime: None,
+            source_date_epoch: None,
+            check_maintainer_scripts: false,
+            package_type: PackageType::Deb,
         }
     }
 
@@ -258,6 +769,126 @@ impl<'a> ControlTarBuilder<'a> {
         Ok(self)
     }
 
+    /// Add a maintainer script to the control archive.
+    ///
+    /// The script is installed under its well-known filename with mode 0755,
+    /// replacing any existing script of the same flavor.
+    pub fn add_maintainer_script(
+        mut self,
+        script: MaintainerScript,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<Self> {
+        self.extra_files.add_file_entry(
+            script.filename(),
+            FileEntry::new_from_data(data.into(), true),
+        )?;
+
+        Ok(self)
+    }
+
+    /// Set whether to sanity check maintainer scripts when writing.
+    ///
+    /// When enabled, [Self::write()] fails if a maintainer script lacks a `#!`
+    /// interpreter line or has CRLF line endings. This applies to scripts added
+    /// via [Self::add_extra_file()] as well.
+    #[must_use]
+    pub fn set_check_maintainer_scripts(mut self, value: bool) -> Self {
+        self.check_maintainer_scripts = value;
+        self
+    }
+
+    /// Set the type of package the archive belongs to.
+    ///
+    /// For [PackageType::Udeb], the control paragraph gains `Package-Type: udeb` and
+    /// `Section: debian-installer` fields unless already present, no `md5sums` file is
+    /// written, and registering conffiles is an error.
+    #[must_use]
+    pub fn set_package_type(mut self, package_type: PackageType) -> Self {
+        self.package_type = package_type;
+        self
+    }
+
+    /// Obtain the conventional filename of the package this archive belongs to.
+    pub fn package_filename(&self) -> Result<String> {
+        let para = self
+            .control
+            .paragraphs()
+            .next()
+            .ok_or(DebianError::ControlFileNoParagraph)?;
+
+        let version = para.required_field_str("Version")?;
+        let version = version.split_once(':').map(|(_, v)| v).unwrap_or(version);
+
+        Ok(format!(
+            "{}_{}_{}{}",
+            para.required_field_str("Package")?,
+            version,
+            para.required_field_str("Architecture")?,
+            self.package_type.extension()
+        ))
+    }
+
+    /// Set the content of the `shlibs` file in the control archive.
+    ///
+    /// The `shlibs` file maps shared libraries provided by the package to dependencies
+    /// that `dpkg-shlibdeps` adds to packages linking against them. Each line has the
+    /// form `[<type>:] <library-name> <soname-version> <dependencies>`.
+    ///
+    /// The content is validated and an error is returned if it is malformed.
+    pub fn set_shlibs(mut self, data: impl Into<Vec<u8>>) -> Result<Self> {
+        let data = data.into();
+        validate_shlibs(&data)?;
+        self.extra_files
+            .add_file_entry("shlibs", FileEntry::new_from_data(data, false))?;
+
+        Ok(self)
+    }
+
+    /// Set the content of the `symbols` file in the control archive.
+    ///
+    /// The `symbols` file records the versions in which each symbol of the package's
+    /// shared libraries was introduced, allowing `dpkg-shlibdeps` to generate minimal
+    /// dependencies. See `deb-symbols(5)` for the format.
+    ///
+    /// The content is validated and an error is returned if it is malformed.
+    pub fn set_symbols(mut self, data: impl Into<Vec<u8>>) -> Result<Self> {
+        let data = data.into();
+        validate_symbols(&data)?;
+        self.extra_files
+            .add_file_entry("symbols", FileEntry::new_from_data(data, false))?;
+
+        Ok(self)
+    }
+
+    /// Declare an installed file as a conffile.
+    ///
+    /// Conffiles are configuration files whose local modifications dpkg preserves
+    /// across upgrades. `path` is the installed path, with or without a leading `/`.
+    /// It is emitted in the `conffiles` member of the control archive.
+    ///
+    /// [Self::write()] fails if the path wasn't registered via [Self::add_data_file()]
+    /// or, unless [Self::set_allow_conffiles_outside_etc()] is enabled, if it isn't
+    /// under `/etc`.
+    #[must_use]
+    pub fn add_conffile(mut self, path: impl AsRef<Path>) -> Self {
+        let path = normalize_installed_path(path.as_ref());
+
+        if !self.conffiles.contains(&path) {
+            self.conffiles.push(path);
+        }
+
+        self
+    }
+
+    /// Set whether conffiles may live outside `/etc`.
+    ///
+    /// Debian policy requires conffiles to be in `/etc`, so this defaults to false.
+    #[must_use]
+    pub fn set_allow_conffiles_outside_etc(mut self, value: bool) -> Self {
+        self.allow_conffiles_outside_etc = value;
+        self
+    }
+
     /// Add a data file to be indexed.
     ///
     /// This should be called for every file in the corresponding `data.tar`
@@ -289,22 +920,20 @@ impl<'a> ControlTarBuilder<'a> {
         let digest = context.finalize();
 
         let mut entry = Vec::new();
-        entry.write_all(&digest.to_ascii_lowercase())?;
+        entry.write_all(hex::encode(digest).as_bytes())?;
         entry.write_all(b"  ")?;
         entry.write_all(path.as_ref().to_raw_bytes().as_ref())?;
         entry.write_all(b"\n")?;
 
         self.md5sums.push(entry);
+        self.data_paths
+            .insert(normalize_installed_path(path.as_ref()));
 
         Ok(self)
     }
 
     fn mtime(&self) -> u64 {
-        self.mtime
-            .unwrap_or_else(std::time::SystemTime::now)
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("times before UNIX epoch not accepted")
-            .as_secs()
+        resolve_mtime(self.mtime, self.source_date_epoch)
     }
 
     #[must_use]
@@ -313,26 +942,266 @@ impl<'a> ControlTarBuilder<'a> {
         self
     }
 
+    /// Clamp modified times of archive entries to a `SOURCE_DATE_EPOCH` value.
+    ///
+    /// See [DebBuilder::set_source_date_epoch()].
+    #[must_use]
+    pub fn set_source_date_epoch(mut self, epoch: Option<u64>) -> Self {
+        self.source_date_epoch = epoch;
+        self
+    }
+
     /// Write the `control.tar` file to a writer.
     pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.write_with_mtime(writer, self.mtime(), None)
+    }
+
+    fn write_with_mtime<W: Write>(
+        &self,
+        writer: &mut W,
+        mtime: u64,
+        installed_size: Option<u64>,
+    ) -> Result<()> {
+        if self.check_maintainer_scripts {
+            for script in MaintainerScript::ALL {
+                if let Some(entry) = self.extra_files.get(script.filename()) {
+                    script.check_content(&entry.resolve_content()?)?;
+                }
+            }
+        }
+
+        let mut default_fields = vec![];
+        if self.package_type == PackageType::Udeb {
+            default_fields.push(("Package-Type", "udeb".to_string()));
+            default_fields.push(("Section", "debian-installer".to_string()));
+        }
+        if let Some(size) = installed_size {
+            default_fields.push(("Installed-Size", size.to_string()));
+        }
+
+        let control = if default_fields.is_empty() {
+            Cow::Borrowed(&self.control)
+        } else {
+            Cow::Owned(control_file_with_defaults(&self.control, &default_fields))
+        };
+
+        if let Some(para) = control.paragraphs().next() {
+            if para.has_field("Multi-Arch") {
+                BinaryPackageControlFile::from(para.clone()).validate_multi_arch()?;
+            }
+        }
+
         let mut control_buffer = BufWriter::new(Vec::new());
-        self.control.write(&mut control_buffer)?;
+        control.write(&mut control_buffer)?;
         let control_data = control_buffer.into_inner().map_err(|e| e.into_error())?;
 
         let mut manifest = self.extra_files.clone();
         manifest.add_file_entry("control", control_data)?;
-        manifest.add_file_entry("md5sums", self.md5sums.concat::<u8>())?;
 
-        write_deb_tar(writer, &manifest, self.mtime())
+        if self.package_type == PackageType::Udeb {
+            if let Some(path) = self.conffiles.first() {
+                return Err(DebianError::DebConffileInvalid(
+                    format!("/{}", path),
+                    "is not supported in udeb packages",
+                ));
+            }
+        } else {
+            manifest.add_file_entry("md5sums", self.md5sums.concat::<u8>())?;
+        }
+
+        if !self.conffiles.is_empty() {
+            let mut conffiles = vec![];
+            for path in &self.conffiles {
+                if !self.data_paths.contains(path) {
+                    return Err(DebianError::DebConffileInvalid(
+                        format!("/{}", path),
+                        "is not installed by the package",
+                    ));
+                }
+                if !self.allow_conffiles_outside_etc && !path.starts_with("etc/") {
+                    return Err(DebianError::DebConffileInvalid(
+                        format!("/{}", path),
+                        "is not under /etc",
+                    ));
+                }
+
+                conffiles.push(format!("/{}\n", path));
+            }
+
+            manifest.add_file_entry("conffiles", conffiles.concat().into_bytes())?;
+        }
+
+        write_deb_tar(writer, &manifest, mtime)
+    }
+}
+
+/// Add fields to the first paragraph of a control file unless already present.
+fn control_file_with_defaults<'a>(
+    control: &ControlFile<'a>,
+    fields: &[(&str, String)],
+) -> ControlFile<'a> {
+    let mut result = ControlFile::default();
+
+    for (i, para) in control.paragraphs().enumerate() {
+        let mut para: ControlParagraph<'a> = para.clone();
+
+        if i == 0 {
+            for (name, value) in fields {
+                if !para.has_field(name) {
+                    para.set_field_from_string(name.to_string().into(), value.clone().into());
+                }
+            }
+        }
+
+        result.add_paragraph(para);
     }
+
+    result
+}
+
+/// Obtain the lines of a control member that aren't blank or comments.
+pub(crate) fn control_member_lines<'a>(
+    member: &'static str,
+    data: &'a [u8],
+) -> Result<impl Iterator<Item = (usize, &'a str)>> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| DebianError::DebControlMemberInvalid(member, 0, "not valid UTF-8"))?;
+
+    Ok(text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#')))
+}
+
+/// Validate the content of a `shlibs` control member.
+fn validate_shlibs(data: &[u8]) -> Result<()> {
+    for (number, line) in control_member_lines("shlibs", data)? {
+        let mut words = line.split_whitespace().peekable();
+
+        // Skip the optional package type, e.g. `udeb:`.
+        if words.peek().map(|w| w.ends_with(':')).unwrap_or(false) {
+            words.next();
+        }
+
+        if words.next().is_none() || words.next().is_none() {
+            return Err(DebianError::DebControlMemberInvalid(
+                "shlibs",
+                number,
+                "expected library name and soname version",
+            ));
+        }
+
+        let dependencies = words.collect::<Vec<_>>().join(" ");
+        if dependencies.is_empty() || DependencyList::parse(&dependencies).is_err() {
+            return Err(DebianError::DebControlMemberInvalid(
+                "shlibs",
+                number,
+                "invalid dependencies",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate the content of a `symbols` control member.
+fn validate_symbols(data: &[u8]) -> Result<()> {
+    let mut have_library = false;
+
+    for (number, line) in control_member_lines("symbols", data)? {
+        let invalid = |reason| {
+            Err(DebianError::DebControlMemberInvalid(
+                "symbols", number, reason,
+            ))
+        };
+
+        if (line.starts_with(' ') || line.starts_with('|') || line.starts_with('*'))
+            && !have_library
+        {
+            return invalid("entry precedes library line");
+        }
+
+        if line.starts_with(' ') {
+            if line.split_whitespace().count() < 2 {
+                return invalid("expected symbol and version");
+            }
+        } else if let Some(alternative) = line.strip_prefix('|') {
+            if alternative.trim().is_empty() {
+                return invalid("empty alternative dependency");
+            }
+        } else if let Some(meta) = line.strip_prefix('*') {
+            if !meta.contains(':') {
+                return invalid("expected `* <field>: <value>`");
+            }
+        } else {
+            if line.split_whitespace().count() < 2 {
+                return invalid("expected library soname and dependency template");
+            }
+            have_library = true;
+        }
+    }
+
+    Ok(())
+}
+
+/// Normalize an installed path to be relative to the filesystem root.
+fn normalize_installed_path(path: &Path) -> String {
+    let path = path.display().to_string();
+
+    path.trim_start_matches("./")
+        .trim_start_matches('/')
+        .to_string()
 }
 
 /// Write a tar archive suitable for inclusion in a `.deb` archive.
+///
+/// Entries are owned by `root:root`.
 pub fn write_deb_tar<W: Write>(writer: W, files: &FileManifest, mtime: u64) -> Result<()> {
+    write_deb_tar_with_ownership(writer, files, mtime, &TarOwnership::default())
+}
+
+/// Write a tar archive suitable for inclusion in a `.deb` archive with custom ownership.
+///
+/// Entries are emitted in sorted path order, with directories preceding their content.
+pub fn write_deb_tar_with_ownership<W: Write>(
+    writer: W,
+    files: &FileManifest,
+    mtime: u64,
+    ownership: &TarOwnership,
+) -> Result<()> {
+    write_data_tar(writer, files, mtime, ownership, &DataTarMetadata::default())
+}
+
+/// Resolve the directories to write to `data.tar`, excluding the root directory.
+fn data_tar_directories(files: &FileManifest, metadata: &DataTarMetadata) -> BTreeSet<PathBuf> {
+    let mut directories = files
+        .relative_directories()
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    for dir in &metadata.directories {
+        directories.extend(
+            dir.ancestors()
+                .filter(|p| !p.as_os_str().is_empty())
+                .map(|p| p.to_path_buf()),
+        );
+    }
+
+    directories
+}
+
+fn write_data_tar<W: Write>(
+    writer: W,
+    files: &FileManifest,
+    mtime: u64,
+    default_ownership: &TarOwnership,
+    metadata: &DataTarMetadata,
+) -> Result<()> {
     let mut builder = tar::Builder::new(writer);
+    let ownership = |path: &Path| metadata.ownership.get(path).unwrap_or(default_ownership);
 
     // Add root directory entry.
-    let mut header = new_tar_header(mtime)?;
+    let mut header = new_tar_header(mtime, default_ownership)?;
     header.set_path(Path::new("./"))?;
     header.set_mode(0o755);
     header.set_size(0);
@@ -340,28 +1209,52 @@ pub fn write_deb_tar<W: Write>(writer: W, files: &FileManifest, mtime: u64) -> R
     header.set_cksum();
     builder.append(&header, &*vec![])?;
 
-    // And entries for each directory in the tree.
-    for directory in files.relative_directories() {
-        let mut header = new_tar_header(mtime)?;
-        set_header_path(&mut builder, &mut header, &directory, true)?;
-        header.set_mode(0o755);
-        header.set_size(0);
-        header.set_entry_type(tar::EntryType::Directory);
-        header.set_cksum();
-        builder.append(&header, &*vec![])?;
-    }
+    // Then directories and files interleaved in sorted order, like `find | sort`.
+    // Path ordering is by component, so directories sort before their content.
+    let mut entries = data_tar_directories(files, metadata)
+        .into_iter()
+        .map(|path| (path, None))
+        .chain(
+            files
+                .iter_entries()
+                .map(|(path, content)| (path.to_path_buf(), Some(content))),
+        )
+        .collect::<Vec<(PathBuf, _)>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (rel_path, content) in entries {
+        let mut header = new_tar_header(mtime, ownership(&rel_path))?;
+        let mode = metadata.modes.get(&rel_path).copied();
+
+        let Some(content) = content else {
+            set_header_path(&mut builder, &mut header, &rel_path, true)?;
+            header.set_mode(mode.unwrap_or(0o755));
+            header.set_size(0);
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_cksum();
+            builder.append(&header, &*vec![])?;
+            continue;
+        };
+
+        if let Some(target) = content.link_target() {
+            set_header_path(&mut builder, &mut header, &rel_path, false)?;
+            header.set_mode(0o777);
+            header.set_size(0);
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_link_name(target)?;
+            header.set_cksum();
+            builder.append(&header, &*vec![])?;
+            continue;
+        }
 
-    // FileManifest is backed by a BTreeMap, so iteration is deterministic.
-    for (rel_path, content) in files.iter_entries() {
         let data = content.resolve_content()?;
 
-        let mut header = new_tar_header(mtime)?;
-        set_header_path(&mut builder, &mut header, rel_path, false)?;
-        header.set_mode(if content.is_executable() {
+        set_header_path(&mut builder, &mut header, &rel_path, false)?;
+        header.set_mode(mode.unwrap_or(if content.is_executable() {
             0o755
         } else {
             0o644
-        });
+        }));
         header.set_size(data.len() as _);
         header.set_cksum();
         builder.append(&header, &*data)?;
@@ -412,6 +1305,130 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_write_control_tar_maintainer_scripts() -> Result<()> {
+        let mut control_para = ControlParagraph::default();
+        control_para.set_field_from_string("Package".into(), "mypackage".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(control_para);
+
+        let builder = ControlTarBuilder::new(control)
+            .set_mtime(Some(SystemTime::UNIX_EPOCH))
+            .set_check_maintainer_scripts(true)
+            .add_maintainer_script(MaintainerScript::Postinst, "#!/bin/sh\nset -e\n")?
+            .add_maintainer_script(MaintainerScript::Config, "#!/bin/sh\n")?;
+
+        let mut buffer = vec![];
+        builder.write(&mut buffer)?;
+
+        let mut archive = tar::Archive::new(std::io::Cursor::new(buffer));
+        let mut modes = vec![];
+        for entry in archive.entries()? {
+            let entry = entry?;
+            modes.push((entry.path()?.display().to_string(), entry.header().mode()?));
+        }
+        assert!(modes.contains(&("./postinst".to_string(), 0o755)));
+        assert!(modes.contains(&("./config".to_string(), 0o755)));
+
+        let builder =
+            builder.add_maintainer_script(MaintainerScript::Prerm, "#!/bin/sh\r\nexit 0\r\n")?;
+        assert!(matches!(
+            builder.write(&mut vec![]),
+            Err(DebianError::DebMaintainerScriptInvalid(_, _))
+        ));
+
+        let builder = builder
+            .add_maintainer_script(MaintainerScript::Prerm, "exit 0\n")?
+            .set_check_maintainer_scripts(false);
+        builder.write(&mut vec![])?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_conffiles() -> Result<()> {
+        let mut control_para = ControlParagraph::default();
+        control_para.set_field_from_string("Package".into(), "mypackage".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(control_para);
+
+        let builder = ControlTarBuilder::new(control)
+            .add_data_file("etc/mypackage.conf", &mut std::io::Cursor::new("a=b"))?
+            .add_data_file(
+                "usr/share/mypackage/defaults",
+                &mut std::io::Cursor::new(""),
+            )?
+            .add_conffile("/etc/mypackage.conf");
+
+        let mut buffer = vec![];
+        builder.write(&mut buffer)?;
+
+        let mut archive = tar::Archive::new(std::io::Cursor::new(buffer));
+        let mut conffiles = String::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.path()? == Path::new("./conffiles") {
+                entry.read_to_string(&mut conffiles)?;
+            }
+        }
+        assert_eq!(conffiles, "/etc/mypackage.conf\n");
+
+        let builder = builder.add_conffile("usr/share/mypackage/defaults");
+        assert!(matches!(
+            builder.write(&mut vec![]),
+            Err(DebianError::DebConffileInvalid(_, "is not under /etc"))
+        ));
+        let builder = builder.set_allow_conffiles_outside_etc(true);
+        builder.write(&mut vec![])?;
+
+        let builder = builder.add_conffile("etc/missing.conf");
+        assert!(matches!(
+            builder.write(&mut vec![]),
+            Err(DebianError::DebConffileInvalid(
+                _,
+                "is not installed by the package"
+            ))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shlibs_symbols() -> Result<()> {
+        let builder = ControlTarBuilder::new(ControlFile::default())
+            .set_shlibs("# comment\nlibfoo 1 libfoo1 (>= 1.0)\nudeb: libfoo 1 libfoo1-udeb\n")?
+            .set_symbols(indoc::indoc! {"
+                libfoo.so.1 libfoo1 #MINVER#
+                | libfoo1-alt
+                * Build-Depends-Package: libfoo-dev
+                 foo@Base 1.0
+                 bar@Base 1.1 1
+            "})?;
+        assert!(builder.extra_files.get("shlibs").is_some());
+        assert!(builder.extra_files.get("symbols").is_some());
+
+        assert!(matches!(
+            ControlTarBuilder::new(ControlFile::default()).set_shlibs("libfoo 1\n"),
+            Err(DebianError::DebControlMemberInvalid("shlibs", 1, _))
+        ));
+        assert!(matches!(
+            ControlTarBuilder::new(ControlFile::default())
+                .set_shlibs("\nlibfoo 1 libfoo1 (>= 1_0)\n"),
+            Err(DebianError::DebControlMemberInvalid("shlibs", 2, _))
+        ));
+        assert!(matches!(
+            ControlTarBuilder::new(ControlFile::default()).set_symbols(" foo@Base 1.0\n"),
+            Err(DebianError::DebControlMemberInvalid("symbols", 1, _))
+        ));
+        assert!(matches!(
+            ControlTarBuilder::new(ControlFile::default())
+                .set_symbols("libfoo.so.1 libfoo1\n foo@Base\n"),
+            Err(DebianError::DebControlMemberInvalid("symbols", 2, _))
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_write_data_tar_one_file() -> Result<()> {
         let mut manifest = FileManifest::default();
@@ -438,6 +1455,143 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_reproducible_deb() -> Result<()> {
+        let build = |ownership: TarOwnership| -> Result<Vec<u8>> {
+            let mut control_para = ControlParagraph::default();
+            control_para.set_field_from_string("Package".into(), "mypackage".into());
+            let mut control = ControlFile::default();
+            control.add_paragraph(control_para);
+
+            let builder = DebBuilder::new(control)
+                .set_compression(DebCompression::Xz(6))
+                .set_source_date_epoch(Some(1_000_000))
+                .set_data_ownership(ownership)
+                .install_file("usr/share/b.txt", vec![1])?
+                .install_file("usr/bin/a", FileEntry::new_from_data(vec![2], true))?
+                .install_file("etc/c.conf", vec![3])?;
+
+            let mut buffer = vec![];
+            builder.write(&mut buffer)?;
+            Ok(buffer)
+        };
+
+        let deb = build(TarOwnership::default())?;
+        assert_eq!(deb, build(TarOwnership::default())?);
+
+        let ownership = TarOwnership {
+            uid: 1000,
+            gid: 100,
+            username: "build".into(),
+            groupname: "users".into(),
+        };
+        let deb = build(ownership.clone())?;
+
+        let mut reader = crate::deb::reader::BinaryPackageReader::new(std::io::Cursor::new(deb))?;
+        let mut paths = vec![];
+        while let Some(entry) = reader.next_entry_streaming() {
+            if let crate::deb::reader::BinaryPackageStreamingEntry::Data(mut data) = entry? {
+                for entry in data.entries()? {
+                    let entry = entry?;
+                    assert_eq!(entry.header().mtime()?, 1_000_000);
+                    assert_eq!(entry.header().uid()?, ownership.uid);
+                    assert_eq!(entry.header().groupname().unwrap(), Some("users"));
+                    paths.push(entry.path()?.display().to_string());
+                }
+            }
+        }
+        assert_eq!(
+            paths,
+            vec![
+                "./",
+                "./etc/",
+                "./etc/c.conf",
+                "./usr/",
+                "./usr/bin/",
+                "./usr/bin/a",
+                "./usr/share/",
+                "./usr/share/b.txt"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_install_directory() -> Result<()> {
+        let td = tempfile::TempDir::new()?;
+        let staging = td.path();
+        std::fs::create_dir_all(staging.join("bin"))?;
+        std::fs::create_dir_all(staging.join("etc/empty"))?;
+        std::fs::create_dir_all(staging.join("build"))?;
+        std::fs::write(staging.join("bin/tool"), "#!/bin/sh\n")?;
+        std::fs::write(staging.join("bin/helper"), "#!/bin/sh\n")?;
+        std::fs::write(staging.join("etc/secret"), "x")?;
+        std::fs::write(staging.join("build/junk"), "x")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("tool", staging.join("bin/alias"))?;
+
+        let mut rules = DirectoryInstallRules::default();
+        rules.set_install_prefix("/opt/app");
+        rules.set_executable_from_filesystem(false);
+        rules.add_executable_pattern("opt/app/bin/*")?;
+        rules.add_mode_rule("opt/app/bin/helper", 0o4755)?;
+        rules.add_mode_rule("opt/app/etc/secret", 0o600)?;
+        rules.add_exclude_pattern("opt/app/build")?;
+        rules.add_ownership_rule(
+            "opt/app/etc/secret",
+            TarOwnership {
+                uid: 0,
+                gid: 42,
+                username: "root".into(),
+                groupname: "shadow".into(),
+            },
+        )?;
+
+        let mut control_para = ControlParagraph::default();
+        control_para.set_field_from_string("Package".into(), "mypackage".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(control_para);
+
+        let builder = DebBuilder::new(control)
+            .set_mtime(Some(SystemTime::UNIX_EPOCH))
+            .install_directory(staging, &rules)?;
+
+        let mut deb = vec![];
+        builder.write(&mut deb)?;
+
+        let mut reader = crate::deb::reader::BinaryPackageReader::new(std::io::Cursor::new(deb))?;
+        let mut entries = BTreeMap::new();
+        while let Some(entry) = reader.next_entry_streaming() {
+            if let crate::deb::reader::BinaryPackageStreamingEntry::Data(mut data) = entry? {
+                for entry in data.entries()? {
+                    let entry = entry?;
+                    entries.insert(
+                        entry.path()?.display().to_string(),
+                        (
+                            entry.header().mode()?,
+                            entry.header().gid()?,
+                            entry.link_name()?.map(|p| p.display().to_string()),
+                        ),
+                    );
+                }
+            }
+        }
+
+        assert_eq!(entries["./opt/app/bin/tool"], (0o755, 0, None));
+        assert_eq!(entries["./opt/app/bin/helper"], (0o4755, 0, None));
+        assert_eq!(entries["./opt/app/etc/secret"], (0o600, 42, None));
+        assert_eq!(entries["./opt/app/etc/empty/"].0, 0o755);
+        assert!(!entries.keys().any(|p| p.contains("build")));
+        #[cfg(unix)]
+        assert_eq!(
+            entries["./opt/app/bin/alias"],
+            (0o777, 0, Some("tool".to_string()))
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_write_data_tar_long_path() -> Result<()> {
         let long_path = PathBuf::from(format!("f{}.txt", "u".repeat(200)));
@@ -501,4 +1655,104 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_udeb() -> Result<()> {
+        let mut control_para = ControlParagraph::default();
+        control_para.set_field_from_string("Package".into(), "mypackage-udeb".into());
+        control_para.set_field_from_string("Version".into(), "1:1.0-1".into());
+        control_para.set_field_from_string("Architecture".into(), "amd64".into());
+
+        let mut control = ControlFile::default();
+        control.add_paragraph(control_para);
+
+        let builder = DebBuilder::new(control)
+            .set_package_type(PackageType::Udeb)
+            .install_file("usr/bin/myapp", FileEntry::new_from_data(vec![42], true))?;
+        assert_eq!(
+            builder.package_filename()?,
+            "mypackage-udeb_1.0-1_amd64.udeb"
+        );
+
+        let mut buffer = vec![];
+        builder.write(&mut buffer)?;
+
+        let mut archive = ar::Archive::new(std::io::Cursor::new(buffer));
+        archive.next_entry().unwrap()?;
+        let entry = archive.next_entry().unwrap()?;
+        assert_eq!(entry.header().identifier(), b"control.tar.xz");
+
+        let mut control_tar = tar::Archive::new(xz2::read::XzDecoder::new(entry));
+        let mut paths = vec![];
+        let mut control_data = String::new();
+        for entry in control_tar.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.display().to_string();
+            if path == "./control" {
+                entry.read_to_string(&mut control_data)?;
+            }
+            paths.push(path);
+        }
+
+        assert_eq!(paths, vec!["./", "./control"]);
+        assert!(control_data.contains("Package-Type: udeb\n"));
+        assert!(control_data.contains("Section: debian-installer\n"));
+
+        let builder = DebBuilder::new(ControlFile::default())
+            .set_package_type(PackageType::Udeb)
+            .install_file("etc/myapp.conf", FileEntry::new_from_data(vec![42], false))?
+            .add_conffile("etc/myapp.conf");
+        assert!(matches!(
+            builder.write(&mut vec![]),
+            Err(DebianError::DebConffileInvalid(_, _))
+        ));
+
+        let mut control_para = ControlParagraph::default();
+        control_para.set_field_from_string("Architecture".into(), "amd64".into());
+        control_para.set_field_from_string("Multi-Arch".into(), "same".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(control_para);
+
+        let builder = DebBuilder::new(control).set_package_type(PackageType::Udeb);
+        assert!(matches!(
+            builder.write(&mut vec![]),
+            Err(DebianError::MultiArchIncompatible(_, _))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_installed_size() -> Result<()> {
+        let mut control_para = ControlParagraph::default();
+        control_para.set_field_from_string("Package".into(), "mypackage".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(control_para.clone());
+
+        let builder = DebBuilder::new(control)
+            .install_file("usr/bin/empty", FileEntry::new_from_data(vec![], false))?
+            .install_file(
+                "usr/bin/myapp",
+                FileEntry::new_from_data(vec![0; 1024], true),
+            )?
+            .install_symlink("usr/bin/link", "myapp")?;
+        // Root, usr and usr/bin, then 0 for the empty file, 1 for 1 KiB and 1 for the symlink.
+        assert_eq!(builder.installed_size()?, 5);
+
+        let mut buffer = vec![];
+        builder.write(&mut buffer)?;
+        let cf = crate::deb::reader::resolve_control_file(std::io::Cursor::new(buffer))?;
+        assert_eq!(cf.installed_size().unwrap()?, 5);
+
+        control_para.set_field_from_string("Installed-Size".into(), "99".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(control_para);
+
+        let mut buffer = vec![];
+        DebBuilder::new(control).write(&mut buffer)?;
+        let cf = crate::deb::reader::resolve_control_file(std::io::Cursor::new(buffer))?;
+        assert_eq!(cf.installed_size().unwrap()?, 99);
+
+        Ok(())
+    }
 }
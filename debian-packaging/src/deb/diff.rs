@@ -0,0 +1,374 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Compare the content of .deb package files.
+
+[diff_packages()] reads two `.deb` files and produces a [PackageDiff] describing
+how `control` fields and `data.tar` entries differ between them, similar to
+what `debdiff` reports for binary packages.
+*/
+
+use {
+    crate::{
+        deb::reader::{
+            sanitize_data_path, BinaryPackageReader, BinaryPackageStreamingEntry, ControlTarFile,
+        },
+        error::{DebianError, Result},
+    },
+    sha2::Digest,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        io::Read,
+    },
+};
+
+/// Metadata of an entry in a `data.tar` archive.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DataEntryInfo {
+    /// The tar entry type.
+    pub entry_type: tar::EntryType,
+    /// Size in bytes of the entry content.
+    pub size: u64,
+    /// Hex SHA-256 digest of the content, for regular files.
+    pub sha256: Option<String>,
+    /// Permission bits.
+    pub mode: u32,
+    /// Numeric owner.
+    pub uid: u64,
+    /// Numeric group.
+    pub gid: u64,
+    /// Owner name.
+    pub username: Option<String>,
+    /// Group name.
+    pub groupname: Option<String>,
+    /// Target of symlinks and hard links.
+    pub link_target: Option<String>,
+}
+
+impl DataEntryInfo {
+    fn from_entry(entry: &mut tar::Entry<'_, impl Read>) -> Result<Self> {
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        let size = header.size()?;
+        let mode = header.mode()?;
+        let uid = header.uid()?;
+        let gid = header.gid()?;
+        let username = header
+            .username_bytes()
+            .map(|v| String::from_utf8_lossy(v).to_string());
+        let groupname = header
+            .groupname_bytes()
+            .map(|v| String::from_utf8_lossy(v).to_string());
+        let link_target = entry
+            .link_name_bytes()
+            .map(|v| String::from_utf8_lossy(&v).to_string());
+
+        let sha256 = if entry_type.is_file() {
+            let mut hasher = sha2::Sha256::new();
+            std::io::copy(entry, &mut hasher)?;
+            Some(hex::encode(hasher.finalize()))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            entry_type,
+            size,
+            sha256,
+            mode,
+            uid,
+            gid,
+            username,
+            groupname,
+            link_target,
+        })
+    }
+}
+
+/// A change to a `control` field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ControlFieldChange {
+    /// Name of the field.
+    pub name: String,
+    /// Value in the old package, if present.
+    pub old: Option<String>,
+    /// Value in the new package, if present.
+    pub new: Option<String>,
+}
+
+/// How an entry in `data.tar` differs between packages.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DataEntryChange {
+    /// The entry only exists in the new package.
+    Added(DataEntryInfo),
+    /// The entry only exists in the old package.
+    Removed(DataEntryInfo),
+    /// The entry exists in both packages with different metadata or content.
+    Changed {
+        /// Entry in the old package.
+        old: DataEntryInfo,
+        /// Entry in the new package.
+        new: DataEntryInfo,
+    },
+}
+
+impl DataEntryChange {
+    /// Change in content size in bytes.
+    pub fn size_delta(&self) -> i64 {
+        match self {
+            Self::Added(new) => new.size as i64,
+            Self::Removed(old) => -(old.size as i64),
+            Self::Changed { old, new } => new.size as i64 - old.size as i64,
+        }
+    }
+
+    /// Whether the type, content or link target of an existing entry changed.
+    pub fn content_changed(&self) -> bool {
+        match self {
+            Self::Changed { old, new } => {
+                old.entry_type != new.entry_type
+                    || old.sha256 != new.sha256
+                    || old.link_target != new.link_target
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the permissions of an existing entry changed.
+    pub fn mode_changed(&self) -> bool {
+        matches!(self, Self::Changed { old, new } if old.mode != new.mode)
+    }
+
+    /// Whether the ownership of an existing entry changed.
+    pub fn ownership_changed(&self) -> bool {
+        matches!(self, Self::Changed { old, new } if old.uid != new.uid
+            || old.gid != new.gid
+            || old.username != new.username
+            || old.groupname != new.groupname)
+    }
+}
+
+/// A change to a single `data.tar` entry.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DataEntryDiff {
+    /// Installed path, relative to the filesystem root.
+    pub path: String,
+    /// What changed.
+    pub change: DataEntryChange,
+}
+
+/// Describes the differences between two binary packages.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PackageDiff {
+    /// Changed `control` fields, sorted by field name.
+    pub control: Vec<ControlFieldChange>,
+    /// Changed `data.tar` entries, sorted by path.
+    pub data: Vec<DataEntryDiff>,
+}
+
+impl PackageDiff {
+    /// Whether the packages have no differences.
+    pub fn is_empty(&self) -> bool {
+        self.control.is_empty() && self.data.is_empty()
+    }
+
+    /// Change in total content size of `data.tar` entries, in bytes.
+    pub fn size_delta(&self) -> i64 {
+        self.data.iter().map(|d| d.change.size_delta()).sum()
+    }
+}
+
+/// The parts of a package that are compared.
+struct PackageSnapshot {
+    control: BTreeMap<String, String>,
+    data: BTreeMap<String, DataEntryInfo>,
+}
+
+impl PackageSnapshot {
+    fn read(reader: impl Read) -> Result<Self> {
+        let mut reader = BinaryPackageReader::new(reader)?;
+        let mut control = None;
+        let mut data = BTreeMap::new();
+
+        while let Some(entry) = reader.next_entry_streaming() {
+            match entry? {
+                BinaryPackageStreamingEntry::DebianBinary(_) => {}
+                BinaryPackageStreamingEntry::Control(mut tar) => {
+                    for entry in tar.entries()? {
+                        if let ControlTarFile::Control(cf) = entry?.to_control_file()?.1 {
+                            control = Some(
+                                cf.iter_fields()
+                                    .map(|f| (f.name().to_string(), f.value_str().to_string()))
+                                    .collect::<BTreeMap<_, _>>(),
+                            );
+                        }
+                    }
+                }
+                BinaryPackageStreamingEntry::Data(mut tar) => {
+                    for entry in tar.entries()? {
+                        let mut entry = entry?;
+                        let path = sanitize_data_path(&entry.path_bytes())?;
+                        if path.as_os_str().is_empty() {
+                            continue;
+                        }
+
+                        data.insert(
+                            path.display().to_string(),
+                            DataEntryInfo::from_entry(&mut entry)?,
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            control: control.ok_or(DebianError::ControlFileNotFound)?,
+            data,
+        })
+    }
+}
+
+/// Compare two `.deb` packages.
+///
+/// `old` and `new` are readers of `.deb` files. Both are read in full.
+pub fn diff_packages(old: impl Read, new: impl Read) -> Result<PackageDiff> {
+    let old = PackageSnapshot::read(old)?;
+    let new = PackageSnapshot::read(new)?;
+
+    let mut control = vec![];
+    for name in old
+        .control
+        .keys()
+        .chain(new.control.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        let old_value = old.control.get(name);
+        let new_value = new.control.get(name);
+
+        if old_value != new_value {
+            control.push(ControlFieldChange {
+                name: name.clone(),
+                old: old_value.cloned(),
+                new: new_value.cloned(),
+            });
+        }
+    }
+
+    let mut data = vec![];
+    for path in old
+        .data
+        .keys()
+        .chain(new.data.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        let change = match (old.data.get(path), new.data.get(path)) {
+            (Some(old), Some(new)) if old == new => continue,
+            (Some(old), Some(new)) => DataEntryChange::Changed {
+                old: old.clone(),
+                new: new.clone(),
+            },
+            (Some(old), None) => DataEntryChange::Removed(old.clone()),
+            (None, Some(new)) => DataEntryChange::Added(new.clone()),
+            (None, None) => unreachable!(),
+        };
+
+        data.push(DataEntryDiff {
+            path: path.clone(),
+            change,
+        });
+    }
+
+    Ok(PackageDiff { control, data })
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            control::{ControlFile, ControlParagraph},
+            deb::builder::DebBuilder,
+        },
+        simple_file_manifest::FileEntry,
+        std::time::SystemTime,
+    };
+
+    fn build(version: &str, files: &[(&str, &[u8], bool)]) -> Result<Vec<u8>> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "mypackage".into());
+        para.set_field_from_string("Version".into(), version.to_string().into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(para);
+
+        let mut builder = DebBuilder::new(control).set_mtime(Some(SystemTime::UNIX_EPOCH));
+        for (path, data, executable) in files {
+            builder = builder
+                .install_file(*path, FileEntry::new_from_data(data.to_vec(), *executable))?;
+        }
+
+        let mut deb = vec![];
+        builder.write(&mut deb)?;
+
+        Ok(deb)
+    }
+
+    #[test]
+    fn diff() -> Result<()> {
+        let old = build(
+            "1.0",
+            &[
+                ("usr/bin/myapp", b"old", true),
+                ("usr/share/doc/README", b"readme", false),
+                ("usr/share/doc/removed", b"gone", false),
+            ],
+        )?;
+        let new = build(
+            "1.1",
+            &[
+                ("usr/bin/myapp", b"newer", true),
+                ("usr/share/doc/README", b"readme", true),
+                ("usr/share/doc/added", b"new", false),
+            ],
+        )?;
+
+        assert!(diff_packages(old.as_slice(), old.as_slice())?.is_empty());
+
+        let diff = diff_packages(old.as_slice(), new.as_slice())?;
+
+        assert_eq!(
+            diff.control,
+            vec![ControlFieldChange {
+                name: "Version".into(),
+                old: Some("1.0".into()),
+                new: Some("1.1".into()),
+            }]
+        );
+
+        let paths = diff
+            .data
+            .iter()
+            .map(|d| d.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "usr/bin/myapp",
+                "usr/share/doc/README",
+                "usr/share/doc/added",
+                "usr/share/doc/removed",
+            ]
+        );
+
+        assert!(diff.data[0].change.content_changed());
+        assert!(!diff.data[0].change.mode_changed());
+        assert_eq!(diff.data[0].change.size_delta(), 2);
+        assert!(!diff.data[1].change.content_changed());
+        assert!(diff.data[1].change.mode_changed());
+        assert!(matches!(diff.data[2].change, DataEntryChange::Added(_)));
+        assert!(matches!(diff.data[3].change, DataEntryChange::Removed(_)));
+        assert_eq!(diff.size_delta(), 2 + 3 - 4);
+
+        Ok(())
+    }
+}
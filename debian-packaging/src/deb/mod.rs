@@ -7,10 +7,86 @@
 The .deb file specification lives at <https://manpages.debian.org/unstable/dpkg-dev/deb.5.en.html>.
 */
 
-use {crate::error::Result, std::io::Read};
+use {
+    crate::error::{DebianError, Result},
+    std::io::{Read, Write},
+};
 
+pub mod archive;
 pub mod builder;
+pub mod diff;
 pub mod reader;
+pub mod search;
+pub mod signing;
+
+/// Tuning for the encoders used by [DebCompression].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CompressionOptions {
+    /// Number of worker threads for xz and zstd compression.
+    ///
+    /// 0 and 1 use the single-threaded encoders. Multi-threaded encoders produce
+    /// different (but still deterministic) output than single-threaded ones. The
+    /// output doesn't depend on the number of threads.
+    pub threads: u32,
+
+    /// Approximate upper bound on encoder memory use, in bytes.
+    ///
+    /// Multi-threaded xz compression uses fewer threads if needed to stay within
+    /// this limit. zstd compression shrinks its window so each thread's window fits
+    /// within this limit, which reduces the compression ratio. Windows are never smaller
+    /// than 1 KiB.
+    pub memory_limit: Option<u64>,
+}
+
+/// The flavor of binary package being produced.
+///
+/// `.udeb` packages are stripped down packages consumed by the Debian installer.
+/// They don't carry `md5sums` or conffiles and are conventionally compressed with xz.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PackageType {
+    /// A regular `.deb` package.
+    #[default]
+    Deb,
+    /// A `.udeb` micro package for the Debian installer.
+    Udeb,
+}
+
+impl PackageType {
+    /// The value of this type in `Package-Type` control fields.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deb => "deb",
+            Self::Udeb => "udeb",
+        }
+    }
+
+    /// Obtain the filename extension for this package type.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Deb => ".deb",
+            Self::Udeb => ".udeb",
+        }
+    }
+
+    /// Resolve the package type from a filename's extension.
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        if filename.ends_with(".udeb") {
+            Some(Self::Udeb)
+        } else if filename.ends_with(".deb") {
+            Some(Self::Deb)
+        } else {
+            None
+        }
+    }
+
+    /// The compression conventionally used for archive members of this package type.
+    pub fn default_compression(&self) -> DebCompression {
+        match self {
+            Self::Deb => DebCompression::Gzip,
+            Self::Udeb => DebCompression::Xz(6),
+        }
+    }
+}
 
 /// Compression format to apply to `.deb` files.
 pub enum DebCompression {
@@ -37,34 +113,266 @@ impl DebCompression {
 
     /// Compress input data from a reader.
     pub fn compress(&self, reader: &mut impl Read) -> Result<Vec<u8>> {
-        let mut buffer = vec![];
+        self.compress_with_options(reader, &CompressionOptions::default())
+    }
+
+    /// Compress input data from a reader using custom encoder options.
+    pub fn compress_with_options(
+        &self,
+        reader: &mut impl Read,
+        options: &CompressionOptions,
+    ) -> Result<Vec<u8>> {
+        self.compress_to(reader, vec![], options)
+    }
 
+    /// Compress input data from a reader, streaming output to a writer.
+    ///
+    /// Compressed data is written as it is produced instead of being buffered.
+    /// The writer is returned once the compressed stream is finished.
+    pub fn compress_to<W: Write>(
+        &self,
+        reader: &mut impl Read,
+        mut writer: W,
+        options: &CompressionOptions,
+    ) -> Result<W> {
         match self {
             Self::Uncompressed => {
-                std::io::copy(reader, &mut buffer)?;
+                std::io::copy(reader, &mut writer)?;
+                Ok(writer)
             }
             Self::Gzip => {
-                let header = libflate::gzip::HeaderBuilder::new().finish();
+                // Don't record the current time in the gzip header so output is deterministic.
+                let header = libflate::gzip::HeaderBuilder::new()
+                    .modification_time(0)
+                    .finish();
 
                 let mut encoder = libflate::gzip::Encoder::with_options(
-                    &mut buffer,
+                    writer,
                     libflate::gzip::EncodeOptions::new().header(header),
                 )?;
                 std::io::copy(reader, &mut encoder)?;
-                encoder.finish().into_result()?;
+                Ok(encoder.finish().into_result()?)
             }
             Self::Xz(level) => {
-                let mut encoder = xz2::write::XzEncoder::new(buffer, *level);
+                let mut encoder = if options.threads > 1 {
+                    xz2::write::XzEncoder::new_stream(writer, xz_mt_stream(*level, options)?)
+                } else {
+                    xz2::write::XzEncoder::new(writer, *level)
+                };
                 std::io::copy(reader, &mut encoder)?;
-                buffer = encoder.finish()?;
+                Ok(encoder.finish()?)
             }
             Self::Zstandard(level) => {
-                let mut encoder = zstd::Encoder::new(buffer, *level)?;
+                let mut encoder = zstd::Encoder::new(writer, *level)?;
+                if options.threads > 1 {
+                    encoder.multithread(options.threads)?;
+                }
+                if let Some(window_log) = zstd_window_log(*level, options) {
+                    encoder.window_log(window_log)?;
+                }
                 std::io::copy(reader, &mut encoder)?;
-                buffer = encoder.finish()?;
+                Ok(encoder.finish()?)
             }
         }
+    }
+}
+
+/// Construct a multi-threaded xz encoder honoring the memory limit.
+fn xz_mt_stream(level: u32, options: &CompressionOptions) -> Result<xz2::stream::Stream> {
+    let mut builder = xz2::stream::MtStreamBuilder::new();
+    builder
+        .preset(level)
+        .check(xz2::stream::Check::Crc64)
+        .threads(options.threads);
+
+    if let Some(limit) = options.memory_limit {
+        let mut threads = options.threads;
+        while threads > 1 && builder.memusage() > limit {
+            threads -= 1;
+            builder.threads(threads);
+        }
+    }
+
+    builder
+        .encoder()
+        .map_err(|e| std::io::Error::other(e).into())
+}
+
+/// The window log of the zstd window honoring the memory limit.
+///
+/// Returns `None` if the level's default window already fits.
+fn zstd_window_log(level: i32, options: &CompressionOptions) -> Option<u32> {
+    // Window logs zstd uses by default for inputs of unknown size.
+    let default = match level {
+        i32::MIN..=1 => 19,
+        2 => 20,
+        3..=8 => 21,
+        9..=16 => 22,
+        17..=19 => 23,
+        20 => 25,
+        21 => 26,
+        _ => 27,
+    };
+
+    let per_thread = options.memory_limit? / u64::from(options.threads.max(1));
+    // zstd rejects windows smaller than 1 KiB.
+    let window_log = per_thread.max(1).ilog2().max(10);
+
+    (window_log < default).then_some(window_log)
+}
+
+/// A maintainer script stored in the `control.tar` archive of a `.deb` file.
+///
+/// See <https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html>.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MaintainerScript {
+    /// `preinst`, run before the package is unpacked.
+    Preinst,
+    /// `postinst`, run after the package is unpacked.
+    Postinst,
+    /// `prerm`, run before the package is removed.
+    Prerm,
+    /// `postrm`, run after the package is removed.
+    Postrm,
+    /// `config`, run by debconf to ask configuration questions.
+    Config,
+}
+
+impl MaintainerScript {
+    /// All maintainer script flavors.
+    pub const ALL: [Self; 5] = [
+        Self::Preinst,
+        Self::Postinst,
+        Self::Prerm,
+        Self::Postrm,
+        Self::Config,
+    ];
+
+    /// The filename of this script in the `control.tar` archive.
+    pub fn filename(&self) -> &'static str {
+        match self {
+            Self::Preinst => "preinst",
+            Self::Postinst => "postinst",
+            Self::Prerm => "prerm",
+            Self::Postrm => "postrm",
+            Self::Config => "config",
+        }
+    }
+
+    /// Perform basic sanity checks on the content of a maintainer script.
+    ///
+    /// Scripts must begin with a `#!` interpreter line and must not have Windows
+    /// (CRLF) line endings, which would break the interpreter invocation.
+    pub fn check_content(&self, data: &[u8]) -> Result<()> {
+        if !data.starts_with(b"#!") {
+            Err(DebianError::DebMaintainerScriptInvalid(
+                self.filename().to_string(),
+                "does not begin with #!",
+            ))
+        } else if data.windows(2).any(|w| w == b"\r\n") {
+            Err(DebianError::DebMaintainerScriptInvalid(
+                self.filename().to_string(),
+                "has CRLF line endings",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn package_type() {
+        assert_eq!(
+            PackageType::from_filename("foo_1.0_amd64.udeb"),
+            Some(PackageType::Udeb)
+        );
+        assert_eq!(
+            PackageType::from_filename("foo_1.0_amd64.deb"),
+            Some(PackageType::Deb)
+        );
+        assert_eq!(PackageType::from_filename("foo.dsc"), None);
+        assert_eq!(PackageType::Udeb.default_compression().extension(), ".xz");
+    }
+
+    #[test]
+    fn compress_threaded() -> Result<()> {
+        let data = (0..4_000_000u32)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        let options = CompressionOptions {
+            threads: 4,
+            memory_limit: None,
+        };
+
+        let xz = DebCompression::Xz(1).compress_with_options(&mut data.as_slice(), &options)?;
+        let mut decoded = vec![];
+        xz2::read::XzDecoder::new(xz.as_slice()).read_to_end(&mut decoded)?;
+        assert_eq!(decoded, data);
+
+        // Output doesn't depend on the number of threads actually used.
+        let limited = DebCompression::Xz(1).compress_with_options(
+            &mut data.as_slice(),
+            &CompressionOptions {
+                threads: 4,
+                memory_limit: Some(1),
+            },
+        )?;
+        assert_eq!(limited, xz);
+
+        let zst =
+            DebCompression::Zstandard(3).compress_with_options(&mut data.as_slice(), &options)?;
+        assert_eq!(zstd::decode_all(zst.as_slice())?, data);
+
+        let mut streamed = vec![];
+        DebCompression::Zstandard(3).compress_to(&mut data.as_slice(), &mut streamed, &options)?;
+        assert_eq!(streamed, zst);
+
+        // A memory limit shrinks the zstd window.
+        let limited = DebCompression::Zstandard(3).compress_with_options(
+            &mut data.as_slice(),
+            &CompressionOptions {
+                threads: 4,
+                memory_limit: Some(4 << 16),
+            },
+        )?;
+        assert_ne!(limited, zst);
+        assert_eq!(zstd::decode_all(limited.as_slice())?, data);
+        assert_eq!(
+            zstd_window_log(
+                3,
+                &CompressionOptions {
+                    threads: 4,
+                    memory_limit: Some(4 << 16),
+                }
+            ),
+            Some(16)
+        );
+        assert_eq!(
+            zstd_window_log(
+                3,
+                &CompressionOptions {
+                    threads: 0,
+                    memory_limit: Some(1),
+                }
+            ),
+            Some(10)
+        );
+        assert_eq!(
+            zstd_window_log(
+                3,
+                &CompressionOptions {
+                    threads: 1,
+                    memory_limit: Some(1 << 30),
+                }
+            ),
+            None
+        );
 
-        Ok(buffer)
+        Ok(())
     }
 }
@@ -8,20 +8,33 @@ use {
     crate::{
         binary_package_control::BinaryPackageControlFile,
         control::ControlParagraphReader,
+        deb::MaintainerScript,
         error::{DebianError, Result},
     },
+    md5::Digest,
     std::{
+        collections::{BTreeMap, BTreeSet},
         io::{Cursor, Read},
         ops::{Deref, DerefMut},
+        path::{Component, Path, PathBuf},
+        time::{Duration, SystemTime},
     },
 };
 
-fn reader_from_filename(extension: &str, data: std::io::Cursor<Vec<u8>>) -> Result<Box<dyn Read>> {
+pub(crate) fn reader_from_filename<'a>(
+    extension: &str,
+    data: impl Read + 'a,
+) -> Result<Box<dyn Read + 'a>> {
     match extension {
         "" => Ok(Box::new(data)),
         ".gz" => Ok(Box::new(libflate::gzip::Decoder::new(data)?)),
         ".xz" => Ok(Box::new(xz2::read::XzDecoder::new(data))),
         ".zst" => Ok(Box::new(zstd::Decoder::new(data)?)),
+        ".bz2" => Ok(Box::new(bzip2::read::BzDecoder::new(data))),
+        ".lzma" => Ok(Box::new(xz2::read::XzDecoder::new_stream(
+            data,
+            xz2::stream::Stream::new_lzma_decoder(u64::MAX).map_err(std::io::Error::other)?,
+        ))),
         _ => Err(DebianError::DebUnknownCompression(extension.to_string())),
     }
 }
@@ -41,10 +54,143 @@ fn reader_from_filename_async(
         ".zst" => Ok(Box::new(
             async_compression::futures::bufread::ZstdDecoder::new(data),
         )),
+        ".bz2" => Ok(Box::new(
+            async_compression::futures::bufread::BzDecoder::new(data),
+        )),
+        ".lzma" => Ok(Box::new(
+            async_compression::futures::bufread::LzmaDecoder::new(data),
+        )),
         _ => Err(DebianError::DebUnknownCompression(extension.to_string())),
     }
 }
 
+/// Magic bytes at the start of ar archives.
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+
+/// Filename extensions of compression formats supported for archive members.
+const MEMBER_COMPRESSION_EXTENSIONS: [&str; 6] = ["", ".gz", ".xz", ".zst", ".bz2", ".lzma"];
+
+/// Filename extensions denoting encrypted archive members.
+const ENCRYPTED_MEMBER_EXTENSIONS: [&str; 4] = [".gpg", ".pgp", ".age", ".enc"];
+
+/// Verify the name of an archive member denotes something this crate can read.
+fn check_member_name(name: &str) -> Result<()> {
+    if name == "debian-binary" || name.starts_with('_') {
+        return Ok(());
+    }
+
+    let tail = name
+        .strip_prefix("control.tar")
+        .or_else(|| name.strip_prefix("data.tar"))
+        .ok_or_else(|| DebianError::DebUnknownBinaryPackageEntry(name.to_string()))?;
+
+    if ENCRYPTED_MEMBER_EXTENSIONS
+        .iter()
+        .any(|ext| tail.ends_with(ext))
+    {
+        Err(DebianError::DebEncryptedMember(name.to_string()))
+    } else if MEMBER_COMPRESSION_EXTENSIONS.contains(&tail) {
+        Ok(())
+    } else {
+        Err(DebianError::DebUnknownCompression(tail.to_string()))
+    }
+}
+
+/// Verify the content of a `debian-binary` member denotes a supported format version.
+fn check_debian_binary(data: &[u8]) -> Result<String> {
+    let version = String::from_utf8_lossy(data)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+
+    if version.split('.').next() == Some("2") {
+        Ok(version)
+    } else {
+        Err(DebianError::DebUnsupportedVersion(version))
+    }
+}
+
+/// The result of [probe()].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DebProbe {
+    /// A binary package this crate can read.
+    Supported {
+        /// The format version from `debian-binary`. e.g. `2.0`.
+        version: String,
+        /// Names of the ar members, in archive order.
+        members: Vec<String>,
+    },
+    /// An old-format package predating the ar based format, with its version.
+    OldFormat(String),
+    /// An ar based package with an unsupported format version.
+    UnsupportedVersion(String),
+    /// A package with an encrypted member, identified by name.
+    EncryptedMember(String),
+    /// A package with a member that isn't part of the format or uses unknown compression.
+    UnknownMember(String),
+    /// A package lacking a required member.
+    MissingMember(&'static str),
+    /// Not a binary package.
+    NotPackage,
+}
+
+/// Classify a `.deb` file without decompressing its members.
+///
+/// Only ar headers and the `debian-binary` member are parsed. Problems with the file's
+/// format are reported as [DebProbe] variants. I/O errors are returned as errors.
+pub fn probe(reader: impl Read) -> Result<DebProbe> {
+    let mut reader = match BinaryPackageReader::new(reader) {
+        Ok(reader) => reader,
+        Err(DebianError::DebOldFormat(version)) => return Ok(DebProbe::OldFormat(version)),
+        Err(DebianError::DebNotArchive) => return Ok(DebProbe::NotPackage),
+        Err(e) => return Err(e),
+    };
+
+    let mut version = None;
+    let mut members = vec![];
+
+    while let Some(entry) = reader.archive.next_entry() {
+        let mut entry = entry?;
+        let name = String::from_utf8_lossy(entry.header().identifier()).to_string();
+
+        match check_member_name(&name) {
+            Ok(()) => {}
+            Err(DebianError::DebEncryptedMember(name)) => {
+                return Ok(DebProbe::EncryptedMember(name))
+            }
+            Err(_) => return Ok(DebProbe::UnknownMember(name)),
+        }
+
+        if name == "debian-binary" {
+            let mut data = vec![];
+            entry.read_to_end(&mut data)?;
+
+            match check_debian_binary(&data) {
+                Ok(v) => version = Some(v),
+                Err(DebianError::DebUnsupportedVersion(v)) => {
+                    return Ok(DebProbe::UnsupportedVersion(v))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        members.push(name);
+    }
+
+    let Some(version) = version else {
+        return Ok(DebProbe::MissingMember("debian-binary"));
+    };
+    for required in ["control.tar", "data.tar"] {
+        if !members.iter().any(|m| m.starts_with(required)) {
+            return Ok(DebProbe::MissingMember(required));
+        }
+    }
+
+    Ok(DebProbe::Supported { version, members })
+}
+
 /// A reader of .deb files.
 ///
 /// A .deb binary package file is an ar archive with 3 entries:
@@ -52,15 +198,36 @@ fn reader_from_filename_async(
 /// 1. `debian-binary` holding the version of the binary package format.
 /// 2. `control.tar` holding package metadata.
 /// 3. `data.tar[.<ext>]` holding file content.
+///
+/// Like dpkg, members whose names begin with `_` are ignored. These hold data such as
+/// signatures (see [crate::deb::signing]).
 pub struct BinaryPackageReader<R: Read> {
-    archive: ar::Archive<R>,
+    archive: ar::Archive<std::io::Chain<Cursor<Vec<u8>>, R>>,
 }
 
 impl<R: Read> BinaryPackageReader<R> {
     /// Construct a new instance from a reader.
-    pub fn new(reader: R) -> Result<Self> {
+    ///
+    /// The start of the file is read to identify its format. Old-format packages error
+    /// with [DebianError::DebOldFormat] and other non-ar files with
+    /// [DebianError::DebNotArchive].
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = vec![];
+        reader
+            .by_ref()
+            .take(AR_MAGIC.len() as _)
+            .read_to_end(&mut magic)?;
+
+        if magic.starts_with(b"0.9") {
+            return Err(DebianError::DebOldFormat(
+                String::from_utf8_lossy(&magic).trim().to_string(),
+            ));
+        } else if magic != AR_MAGIC {
+            return Err(DebianError::DebNotArchive);
+        }
+
         Ok(Self {
-            archive: ar::Archive::new(reader),
+            archive: ar::Archive::new(Cursor::new(magic).chain(reader)),
         })
     }
 
@@ -68,51 +235,508 @@ impl<R: Read> BinaryPackageReader<R> {
     ///
     /// The entry will be converted to an enum that richly represents its content.
     pub fn next_entry(&mut self) -> Option<Result<BinaryPackageEntry>> {
-        if let Some(entry) = self.archive.next_entry() {
-            match entry {
-                Ok(mut entry) => {
-                    // We could do this in the domain of bytes. But filenames should be ASCII,
-                    // so converting to strings feels reasonably safe.
-                    let filename = String::from_utf8_lossy(entry.header().identifier()).to_string();
-
-                    let mut data = vec![];
-                    match entry.read_to_end(&mut data) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            return Some(Err(e.into()));
+        loop {
+            let mut entry = match self.archive.next_entry()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e.into())),
+            };
+
+            // We could do this in the domain of bytes. But filenames should be ASCII,
+            // so converting to strings feels reasonably safe.
+            let filename = String::from_utf8_lossy(entry.header().identifier()).to_string();
+
+            if filename.starts_with('_') {
+                continue;
+            }
+
+            if let Err(e) = check_member_name(&filename) {
+                return Some(Err(e));
+            }
+
+            let mut data = vec![];
+            if let Err(e) = entry.read_to_end(&mut data) {
+                return Some(Err(e.into()));
+            }
+
+            return if filename == "debian-binary" {
+                if let Err(e) = check_debian_binary(&data) {
+                    return Some(Err(e));
+                }
+
+                Some(Ok(BinaryPackageEntry::DebianBinary(std::io::Cursor::new(
+                    data,
+                ))))
+            } else if let Some(tail) = filename.strip_prefix("control.tar") {
+                match reader_from_filename(tail, std::io::Cursor::new(data)) {
+                    Ok(res) => Some(Ok(BinaryPackageEntry::Control(ControlTarReader {
+                        archive: tar::Archive::new(res),
+                    }))),
+                    Err(e) => Some(Err(e)),
+                }
+            } else if let Some(tail) = filename.strip_prefix("data.tar") {
+                match reader_from_filename_async(tail, futures::io::Cursor::new(data)) {
+                    Ok(res) => Some(Ok(BinaryPackageEntry::Data(DataTarReader {
+                        archive: async_tar::Archive::new(res),
+                    }))),
+                    Err(e) => Some(Err(e)),
+                }
+            } else {
+                Some(Err(DebianError::DebUnknownBinaryPackageEntry(
+                    filename.to_string(),
+                )))
+            };
+        }
+    }
+
+    /// Obtain the next entry from the underlying ar archive without buffering `data.tar`.
+    ///
+    /// This is like [Self::next_entry()] except the `data.tar` member isn't read into
+    /// memory. Instead, it is decompressed incrementally as its entries are consumed
+    /// and file content is only read when requested. This makes it cheap to extract
+    /// a single file from a large package.
+    ///
+    /// Iteration ends at the first member whose name begins with `_`. Such members
+    /// are appended after `data.tar`.
+    pub fn next_entry_streaming(&mut self) -> Option<Result<BinaryPackageStreamingEntry<'_>>> {
+        let mut entry = match self.archive.next_entry()? {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        // Members such as signatures are appended after `data.tar` and are ignored.
+        if entry.header().identifier().starts_with(b"_") {
+            return None;
+        }
+
+        let filename = String::from_utf8_lossy(entry.header().identifier()).to_string();
+
+        if let Err(e) = check_member_name(&filename) {
+            return Some(Err(e));
+        }
+
+        if let Some(tail) = filename.strip_prefix("data.tar") {
+            return Some(reader_from_filename(tail, entry).map(|res| {
+                BinaryPackageStreamingEntry::Data(StreamingDataTarReader {
+                    archive: tar::Archive::new(res),
+                })
+            }));
+        }
+
+        let mut data = vec![];
+        if let Err(e) = entry.read_to_end(&mut data) {
+            return Some(Err(e.into()));
+        }
+
+        if filename == "debian-binary" {
+            if let Err(e) = check_debian_binary(&data) {
+                return Some(Err(e));
+            }
+
+            Some(Ok(BinaryPackageStreamingEntry::DebianBinary(
+                std::io::Cursor::new(data),
+            )))
+        } else if let Some(tail) = filename.strip_prefix("control.tar") {
+            Some(
+                reader_from_filename(tail, std::io::Cursor::new(data)).map(|res| {
+                    BinaryPackageStreamingEntry::Control(ControlTarReader {
+                        archive: tar::Archive::new(res),
+                    })
+                }),
+            )
+        } else {
+            Some(Err(DebianError::DebUnknownBinaryPackageEntry(filename)))
+        }
+    }
+
+    /// Read package metadata from the `control.tar` member.
+    ///
+    /// This must be called on a freshly constructed reader. Reading stops once
+    /// `control.tar` is consumed: `data.tar` is neither read nor decompressed, making
+    /// this suitable for scanning large numbers of packages.
+    pub fn read_control_metadata(&mut self) -> Result<ControlMetadata> {
+        while let Some(entry) = self.next_entry_streaming() {
+            if let BinaryPackageStreamingEntry::Control(mut control) = entry? {
+                let mut control_file = None;
+                let mut conffiles = vec![];
+                let mut maintainer_scripts = vec![];
+
+                for entry in control.entries()? {
+                    let mut entry = entry?;
+                    let path = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+
+                    if let Some(script) = MaintainerScript::ALL
+                        .into_iter()
+                        .find(|s| s.filename() == path.trim_start_matches("./"))
+                    {
+                        maintainer_scripts.push(script);
+                    }
+
+                    match entry.to_control_file()?.1 {
+                        ControlTarFile::Control(cf) => {
+                            control_file = Some(cf);
+                        }
+                        ControlTarFile::Conffiles(data) => {
+                            conffiles = parse_conffiles(&data);
                         }
+                        _ => {}
                     }
+                }
+
+                return Ok(ControlMetadata {
+                    control: control_file.ok_or(DebianError::ControlFileNotFound)?,
+                    conffiles,
+                    maintainer_scripts,
+                });
+            }
+        }
+
+        Err(DebianError::DebMissingMember("control.tar"))
+    }
 
-                    if filename == "debian-binary" {
-                        Some(Ok(BinaryPackageEntry::DebianBinary(std::io::Cursor::new(
-                            data,
-                        ))))
-                    } else if let Some(tail) = filename.strip_prefix("control.tar") {
-                        match reader_from_filename(tail, std::io::Cursor::new(data)) {
-                            Ok(res) => Some(Ok(BinaryPackageEntry::Control(ControlTarReader {
-                                archive: tar::Archive::new(res),
-                            }))),
-                            Err(e) => Some(Err(e)),
+    /// Verify the `md5sums` control member against the content of `data.tar`.
+    ///
+    /// This must be called on a freshly constructed reader. It consumes the remaining
+    /// entries, hashing every regular file in `data.tar` as it is streamed.
+    ///
+    /// Returns a result for every path listed in `md5sums` or present as a regular file
+    /// in `data.tar`, sorted by path. Files declared as conffiles aren't expected in
+    /// `md5sums`, so they are only reported if listed. Packages without an `md5sums`
+    /// member report every file as [Md5sumStatus::Unlisted].
+    pub fn verify_md5sums(&mut self) -> Result<Vec<Md5sumVerification>> {
+        let mut expected = BTreeMap::new();
+        let mut conffiles = BTreeSet::new();
+        let mut actual = BTreeMap::new();
+
+        while let Some(entry) = self.next_entry_streaming() {
+            match entry? {
+                BinaryPackageStreamingEntry::DebianBinary(_) => {}
+                BinaryPackageStreamingEntry::Control(mut control) => {
+                    for entry in control.entries()? {
+                        match entry?.to_control_file()?.1 {
+                            ControlTarFile::Conffiles(data) => {
+                                conffiles.extend(
+                                    parse_conffiles(&data)
+                                        .into_iter()
+                                        .map(|p| p.trim_start_matches('/').to_string()),
+                                );
+                            }
+                            ControlTarFile::Other(path, data) if path.ends_with(b"md5sums") => {
+                                for line in String::from_utf8_lossy(&data).lines() {
+                                    if let Some((digest, path)) =
+                                        line.split_once(char::is_whitespace)
+                                    {
+                                        expected.insert(
+                                            path.trim()
+                                                .trim_start_matches("./")
+                                                .trim_start_matches('/')
+                                                .to_string(),
+                                            digest.to_ascii_lowercase(),
+                                        );
+                                    }
+                                }
+                            }
+                            _ => {}
                         }
-                    } else if let Some(tail) = filename.strip_prefix("data.tar") {
-                        match reader_from_filename_async(tail, futures::io::Cursor::new(data)) {
-                            Ok(res) => Some(Ok(BinaryPackageEntry::Data(DataTarReader {
-                                archive: async_tar::Archive::new(res),
-                            }))),
-                            Err(e) => Some(Err(e)),
+                    }
+                }
+                BinaryPackageStreamingEntry::Data(mut data) => {
+                    for entry in data.entries()? {
+                        let mut entry = entry?;
+                        if !entry.header().entry_type().is_file() {
+                            continue;
                         }
-                    } else {
-                        Some(Err(DebianError::DebUnknownBinaryPackageEntry(
-                            filename.to_string(),
-                        )))
+
+                        let path = sanitize_data_path(&entry.path_bytes())?
+                            .display()
+                            .to_string();
+
+                        let mut context = md5::Md5::new();
+                        std::io::copy(&mut entry, &mut context)?;
+                        actual.insert(path, hex::encode(context.finalize()));
                     }
                 }
-                Err(e) => Some(Err(e.into())),
             }
-        } else {
-            None
+        }
+
+        let paths = expected
+            .keys()
+            .chain(actual.keys())
+            .collect::<BTreeSet<_>>();
+
+        Ok(paths
+            .into_iter()
+            .filter_map(|path| {
+                let status = match (expected.get(path), actual.get(path)) {
+                    (Some(expected), Some(actual)) if expected == actual => Md5sumStatus::Match,
+                    (Some(expected), Some(actual)) => Md5sumStatus::Mismatch {
+                        expected: expected.clone(),
+                        actual: actual.clone(),
+                    },
+                    (Some(_), None) => Md5sumStatus::Missing,
+                    (None, Some(_)) if conffiles.contains(path) => return None,
+                    (None, _) => Md5sumStatus::Unlisted,
+                };
+
+                Some(Md5sumVerification {
+                    path: path.clone(),
+                    status,
+                })
+            })
+            .collect())
+    }
+
+    /// Extract the files in `data.tar` to a directory.
+    ///
+    /// This is [Self::extract_to_with_options()] with default [DataExtractOptions].
+    pub fn extract_to(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.extract_to_with_options(path, &DataExtractOptions::default())
+    }
+
+    /// Extract the files in `data.tar` to a directory using custom options.
+    ///
+    /// This behaves like `dpkg-deb -x`. Regular files, directories, symlinks and
+    /// hardlinks are materialized under `path`. Paths containing `..` are rejected
+    /// with an error and nothing is ever written outside `path`, including through
+    /// previously extracted symlinks. Directory permissions and modification times are
+    /// applied after all files are written so read-only directories can be populated.
+    pub fn extract_to_with_options(
+        &mut self,
+        path: impl AsRef<Path>,
+        options: &DataExtractOptions,
+    ) -> Result<()> {
+        let dest = path.as_ref();
+
+        while let Some(entry) = self.next_entry_streaming() {
+            let BinaryPackageStreamingEntry::Data(mut data) = entry? else {
+                continue;
+            };
+
+            std::fs::create_dir_all(dest)?;
+
+            let mut directories = vec![];
+
+            for entry in data.entries()? {
+                let mut entry = entry?;
+
+                let rel_path = sanitize_data_path(&entry.path_bytes())?;
+                if rel_path.as_os_str().is_empty() {
+                    continue;
+                }
+
+                let header = entry.header();
+                let mode = header.mode()?;
+                let mtime = header.mtime()?;
+                let owner = (header.uid()?, header.gid()?);
+
+                // Entries beneath a previously extracted symlink could escape `dest`.
+                let is_dir = header.entry_type().is_dir();
+                let checked_path = if is_dir {
+                    Some(rel_path.as_path())
+                } else {
+                    rel_path.parent()
+                };
+                if has_symlink_component(dest, checked_path.unwrap_or(Path::new("")))? {
+                    return Err(DebianError::DebDataTarIllegalPath(
+                        rel_path.display().to_string(),
+                    ));
+                }
+
+                if is_dir {
+                    std::fs::create_dir_all(dest.join(&rel_path))?;
+                    directories.push((rel_path, mode, mtime, owner));
+                    continue;
+                }
+
+                entry.set_preserve_permissions(options.preserve_permissions);
+                entry.set_preserve_mtime(options.preserve_mtime);
+
+                if !entry.unpack_in(dest)? {
+                    return Err(DebianError::DebDataTarIllegalPath(
+                        rel_path.display().to_string(),
+                    ));
+                }
+
+                options.apply_ownership(&dest.join(&rel_path), owner)?;
+            }
+
+            // Apply in reverse so children are handled before their parents.
+            for (rel_path, mode, mtime, owner) in directories.into_iter().rev() {
+                // A later entry may have replaced the directory with a symlink, whose
+                // target must not be modified.
+                if has_symlink_component(dest, &rel_path)? {
+                    continue;
+                }
+
+                let dir_path = dest.join(rel_path);
+
+                if options.preserve_mtime {
+                    std::fs::File::open(&dir_path)?
+                        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime))?;
+                }
+                options.apply_ownership(&dir_path, owner)?;
+                #[cfg(unix)]
+                if options.preserve_permissions {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(&dir_path, std::fs::Permissions::from_mode(mode))?;
+                }
+                #[cfg(not(unix))]
+                let _ = mode;
+            }
+
+            return Ok(());
+        }
+
+        Err(DebianError::DebMissingMember("data.tar"))
+    }
+}
+
+/// Package metadata obtained from a `control.tar` member.
+#[derive(Clone, Debug)]
+pub struct ControlMetadata {
+    /// The parsed `control` file.
+    pub control: BinaryPackageControlFile<'static>,
+    /// Absolute paths of files registered as conffiles.
+    pub conffiles: Vec<String>,
+    /// Maintainer scripts present in the archive, in archive order.
+    pub maintainer_scripts: Vec<MaintainerScript>,
+}
+
+/// Parse the paths in a `conffiles` control member.
+///
+/// Lines may carry flags before the path, such as `remove-on-upgrade`.
+fn parse_conffiles(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .map(|path| path.to_string())
+        .collect()
+}
+
+/// The outcome of verifying a single file against the `md5sums` control member.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Md5sumVerification {
+    /// Installed path of the file, relative to the filesystem root.
+    pub path: String,
+    /// The verification outcome.
+    pub status: Md5sumStatus,
+}
+
+/// Describes how a file compares against its `md5sums` entry.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Md5sumStatus {
+    /// The file content matches the recorded digest.
+    Match,
+    /// The file content doesn't match the recorded digest.
+    Mismatch {
+        /// Hex digest recorded in `md5sums`.
+        expected: String,
+        /// Hex digest of the content in `data.tar`.
+        actual: String,
+    },
+    /// The file is listed in `md5sums` but isn't a regular file in `data.tar`.
+    Missing,
+    /// The file is in `data.tar` but not listed in `md5sums`.
+    Unlisted,
+}
+
+/// Maps a uid and gid recorded in an archive to the owner of an extracted file.
+type OwnershipMapFn = dyn Fn(u64, u64) -> Option<(u32, u32)>;
+
+/// Options controlling how `data.tar` content is extracted to the filesystem.
+///
+/// See [BinaryPackageReader::extract_to_with_options()].
+pub struct DataExtractOptions {
+    preserve_permissions: bool,
+    preserve_mtime: bool,
+    ownership_map: Option<Box<OwnershipMapFn>>,
+}
+
+impl Default for DataExtractOptions {
+    fn default() -> Self {
+        Self {
+            preserve_permissions: true,
+            preserve_mtime: true,
+            ownership_map: None,
+        }
+    }
+}
+
+impl DataExtractOptions {
+    /// Set whether to apply file modes recorded in the archive.
+    ///
+    /// Defaults to true.
+    pub fn set_preserve_permissions(&mut self, value: bool) {
+        self.preserve_permissions = value;
+    }
+
+    /// Set whether to apply modification times recorded in the archive.
+    ///
+    /// Defaults to true.
+    pub fn set_preserve_mtime(&mut self, value: bool) {
+        self.preserve_mtime = value;
+    }
+
+    /// Set a function mapping the archive's uid and gid to the owner of extracted files.
+    ///
+    /// The function receives the uid and gid recorded in the archive and returns the
+    /// uid and gid to assign, or `None` to leave ownership untouched. Changing ownership
+    /// typically requires elevated privileges. Ownership is only applied on Unix.
+    ///
+    /// By default, extracted files are owned by the current user.
+    pub fn set_ownership_map(&mut self, f: impl Fn(u64, u64) -> Option<(u32, u32)> + 'static) {
+        self.ownership_map = Some(Box::new(f));
+    }
+
+    fn apply_ownership(&self, path: &Path, owner: (u64, u64)) -> Result<()> {
+        if let Some((uid, gid)) = self
+            .ownership_map
+            .as_ref()
+            .and_then(|f| f(owner.0, owner.1))
+        {
+            #[cfg(unix)]
+            std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+            #[cfg(not(unix))]
+            let _ = (path, uid, gid);
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether any existing component of the relative path `path` under `root` is a symlink.
+fn has_symlink_component(root: &Path, path: &Path) -> Result<bool> {
+    let mut current = root.to_path_buf();
+
+    for component in path.components() {
+        current.push(component);
+
+        match std::fs::symlink_metadata(&current) {
+            Ok(metadata) if metadata.file_type().is_symlink() => return Ok(true),
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e.into()),
         }
     }
+
+    Ok(false)
+}
+
+/// Normalize a path in a `data.tar` to a relative path, rejecting `..` components.
+pub(crate) fn sanitize_data_path(path: &[u8]) -> Result<PathBuf> {
+    let path_str = String::from_utf8_lossy(path);
+
+    let mut res = PathBuf::new();
+    for component in Path::new(path_str.as_ref()).components() {
+        match component {
+            Component::Normal(part) => res.push(part),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => {
+                return Err(DebianError::DebDataTarIllegalPath(path_str.to_string()));
+            }
+        }
+    }
+
+    Ok(res)
 }
 
 /// Represents an entry in a .deb archive.
@@ -125,6 +749,18 @@ pub enum BinaryPackageEntry {
     Data(DataTarReader),
 }
 
+/// Represents an entry in a .deb archive whose `data.tar` is read incrementally.
+///
+/// Instances are obtained from [BinaryPackageReader::next_entry_streaming()].
+pub enum BinaryPackageStreamingEntry<'a> {
+    /// The `debian-binary` file.
+    DebianBinary(std::io::Cursor<Vec<u8>>),
+    /// The `control.tar` tar archive.
+    Control(ControlTarReader),
+    /// The `data.tar[.<ext>]` tar archive.
+    Data(StreamingDataTarReader<'a>),
+}
+
 /// A reader for `control.tar` files.
 pub struct ControlTarReader {
     archive: tar::Archive<Box<dyn Read>>,
@@ -294,6 +930,95 @@ impl DataTarReader {
     }
 }
 
+/// A reader for `data.tar` files that decompresses content on demand.
+///
+/// Content is read directly from the underlying `.deb` archive, so at most one entry
+/// can be read at a time and entries can only be visited once, in archive order.
+pub struct StreamingDataTarReader<'a> {
+    archive: tar::Archive<Box<dyn Read + 'a>>,
+}
+
+impl<'a> Deref for StreamingDataTarReader<'a> {
+    type Target = tar::Archive<Box<dyn Read + 'a>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.archive
+    }
+}
+
+impl<'a> DerefMut for StreamingDataTarReader<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.archive
+    }
+}
+
+impl<'a> StreamingDataTarReader<'a> {
+    /// Obtain the entries in the `data.tar` file.
+    ///
+    /// This can only be called once. Each yielded [tar::Entry] reads its content from
+    /// the archive when read from. Content not read is skipped when advancing to the
+    /// next entry.
+    pub fn entries(&mut self) -> Result<StreamingDataTarEntries<'_, 'a>> {
+        let entries = self.archive.entries()?;
+
+        Ok(StreamingDataTarEntries { entries })
+    }
+}
+
+/// Represents entries in a `data.tar` file being read incrementally.
+pub struct StreamingDataTarEntries<'b, 'a> {
+    entries: tar::Entries<'b, Box<dyn Read + 'a>>,
+}
+
+impl<'b, 'a> Iterator for StreamingDataTarEntries<'b, 'a> {
+    type Item = Result<tar::Entry<'b, Box<dyn Read + 'a>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries
+            .next()
+            .map(|entry| entry.map_err(DebianError::from))
+    }
+}
+
+/// Resolve the content of a single file in the `data.tar` file within a `.deb` archive.
+///
+/// `path` is the installed path of the file, with or without a leading `/` or `./`.
+/// Only entries preceding the file are decompressed and file content is only read for
+/// the matching entry. Returns `None` if the file isn't present.
+pub fn resolve_data_file(reader: impl Read, path: &str) -> Result<Option<Vec<u8>>> {
+    let wanted = path.trim_start_matches("./").trim_start_matches('/');
+    let mut reader = BinaryPackageReader::new(reader)?;
+
+    while let Some(entry) = reader.next_entry_streaming() {
+        if let BinaryPackageStreamingEntry::Data(mut data) = entry? {
+            for entry in data.entries()? {
+                let mut entry = entry?;
+
+                let entry_path = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+                if entry_path.trim_start_matches("./").trim_start_matches('/') == wanted
+                    && entry.header().entry_type().is_file()
+                {
+                    let mut content = vec![];
+                    entry.read_to_end(&mut content)?;
+
+                    return Ok(Some(content));
+                }
+            }
+
+            return Ok(None);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Resolve metadata from the `control.tar` file within a `.deb` archive.
+///
+/// See [BinaryPackageReader::read_control_metadata()].
+pub fn resolve_control_metadata(reader: impl Read) -> Result<ControlMetadata> {
+    BinaryPackageReader::new(reader)?.read_control_metadata()
+}
+
 /// Resolve the `control` file from the `control.tar` file within a `.deb` archive.
 pub fn resolve_control_file(reader: impl Read) -> Result<BinaryPackageControlFile<'static>> {
     let mut reader = BinaryPackageReader::new(reader)?;
@@ -305,8 +1030,461 @@ pub fn resolve_control_file(reader: impl Read) -> Result<BinaryPackageControlFil
                     return Ok(control);
                 }
             }
+
+            return Err(DebianError::ControlFileNotFound);
         }
     }
 
-    Err(DebianError::ControlFileNotFound)
+    Err(DebianError::DebMissingMember("control.tar"))
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            control::{ControlFile, ControlParagraph},
+            deb::{builder::DebBuilder, DebCompression},
+        },
+        simple_file_manifest::FileEntry,
+        std::io::Write,
+    };
+
+    #[test]
+    fn streaming_data_entries() -> Result<()> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "mypackage".into());
+        para.set_field_from_string("Architecture".into(), "amd64".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(para);
+
+        let builder = DebBuilder::new(control)
+            .set_compression(DebCompression::Xz(6))
+            .install_file("usr/bin/myapp", FileEntry::new_from_data(vec![42], true))?
+            .install_file(
+                "usr/share/doc/mypackage/README",
+                FileEntry::new_from_data(b"hello".to_vec(), false),
+            )?;
+
+        let mut deb = vec![];
+        builder.write(&mut deb)?;
+
+        let mut reader = BinaryPackageReader::new(std::io::Cursor::new(&deb))?;
+        let mut paths = vec![];
+        while let Some(entry) = reader.next_entry_streaming() {
+            if let BinaryPackageStreamingEntry::Data(mut data) = entry? {
+                for entry in data.entries()? {
+                    paths.push(String::from_utf8_lossy(&entry?.path_bytes()).to_string());
+                }
+            }
+        }
+        assert!(paths.contains(&"./usr/bin/myapp".to_string()));
+
+        assert_eq!(
+            resolve_data_file(
+                std::io::Cursor::new(&deb),
+                "/usr/share/doc/mypackage/README"
+            )?,
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(
+            resolve_data_file(std::io::Cursor::new(&deb), "usr/bin/myapp")?,
+            Some(vec![42])
+        );
+        assert_eq!(
+            resolve_data_file(std::io::Cursor::new(&deb), "usr/bin/missing")?,
+            None
+        );
+
+        Ok(())
+    }
+
+    /// A reader that records how many bytes were read from it.
+    struct CountingReader<R> {
+        inner: R,
+        count: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let size = self.inner.read(buf)?;
+            self.count.set(self.count.get() + size);
+            Ok(size)
+        }
+    }
+
+    #[test]
+    fn probe_formats() -> Result<()> {
+        use crate::deb::archive::ArArchiveBuilder;
+
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "mypackage".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(para);
+        let mut deb = vec![];
+        DebBuilder::new(control).write(&mut deb)?;
+
+        assert_eq!(
+            probe(deb.as_slice())?,
+            DebProbe::Supported {
+                version: "2.0".into(),
+                members: vec![
+                    "debian-binary".into(),
+                    "control.tar.gz".into(),
+                    "data.tar.gz".into()
+                ],
+            }
+        );
+
+        let old = b"0.939000\n12345\n".to_vec();
+        assert_eq!(
+            probe(old.as_slice())?,
+            DebProbe::OldFormat("0.939000".into())
+        );
+        assert!(matches!(
+            BinaryPackageReader::new(old.as_slice()),
+            Err(DebianError::DebOldFormat(v)) if v == "0.939000"
+        ));
+        assert_eq!(probe(&b"hello"[..])?, DebProbe::NotPackage);
+
+        let archive = |members: &[(&str, &[u8])]| -> Result<Vec<u8>> {
+            let mut builder = ArArchiveBuilder::default();
+            for (name, data) in members {
+                builder = builder.add_member(*name, data.to_vec())?;
+            }
+            builder.write(vec![])
+        };
+
+        let v3 = archive(&[("debian-binary", b"3.0\n")])?;
+        assert_eq!(
+            probe(v3.as_slice())?,
+            DebProbe::UnsupportedVersion("3.0".into())
+        );
+        assert!(matches!(
+            BinaryPackageReader::new(v3.as_slice())?.next_entry(),
+            Some(Err(DebianError::DebUnsupportedVersion(_)))
+        ));
+
+        let encrypted = archive(&[("debian-binary", b"2.0\n"), ("control.tar.xz.gpg", b"")])?;
+        assert_eq!(
+            probe(encrypted.as_slice())?,
+            DebProbe::EncryptedMember("control.tar.xz.gpg".into())
+        );
+        let mut reader = BinaryPackageReader::new(encrypted.as_slice())?;
+        reader.next_entry().unwrap()?;
+        assert!(matches!(
+            reader.next_entry(),
+            Some(Err(DebianError::DebEncryptedMember(_)))
+        ));
+
+        let unknown = archive(&[("debian-binary", b"2.0\n"), ("other", b"")])?;
+        assert_eq!(
+            probe(unknown.as_slice())?,
+            DebProbe::UnknownMember("other".into())
+        );
+
+        let incomplete = archive(&[("debian-binary", b"2.0\n")])?;
+        assert_eq!(
+            probe(incomplete.as_slice())?,
+            DebProbe::MissingMember("control.tar")
+        );
+        assert!(matches!(
+            resolve_control_file(incomplete.as_slice()),
+            Err(DebianError::DebMissingMember("control.tar"))
+        ));
+        assert!(matches!(
+            resolve_control_metadata(incomplete.as_slice()),
+            Err(DebianError::DebMissingMember("control.tar"))
+        ));
+        assert!(matches!(
+            BinaryPackageReader::new(incomplete.as_slice())?.extract_to("unused"),
+            Err(DebianError::DebMissingMember("data.tar"))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn control_metadata() -> Result<()> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "mypackage".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(para);
+
+        let builder = DebBuilder::new(control)
+            .set_compression(DebCompression::Uncompressed)
+            .install_file("etc/myapp.conf", FileEntry::new_from_data(vec![1], false))?
+            .install_file(
+                "usr/bin/myapp",
+                FileEntry::new_from_data(vec![0; 1048576], true),
+            )?
+            .add_conffile("etc/myapp.conf")
+            .add_maintainer_script(MaintainerScript::Postinst, "#!/bin/sh\n")?
+            .add_maintainer_script(MaintainerScript::Config, "#!/bin/sh\n")?;
+        let mut deb = vec![];
+        builder.write(&mut deb)?;
+
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let metadata = resolve_control_metadata(CountingReader {
+            inner: std::io::Cursor::new(&deb),
+            count: count.clone(),
+        })?;
+
+        assert_eq!(metadata.control.package()?, "mypackage");
+        assert_eq!(metadata.conffiles, vec!["/etc/myapp.conf".to_string()]);
+        assert_eq!(
+            metadata.maintainer_scripts,
+            vec![MaintainerScript::Config, MaintainerScript::Postinst]
+        );
+        assert!(count.get() < 65536, "data.tar was not read");
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_md5sums() -> Result<()> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "mypackage".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(para);
+
+        let builder = DebBuilder::new(control.clone())
+            .install_file("usr/bin/myapp", vec![42])?
+            .install_file("etc/myapp.conf", vec![1])?;
+        let mut deb = vec![];
+        builder.write(&mut deb)?;
+
+        let results = BinaryPackageReader::new(std::io::Cursor::new(&deb))?.verify_md5sums()?;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.status == Md5sumStatus::Match));
+
+        // Construct a package whose md5sums disagrees with its content.
+        let control_builder = crate::deb::builder::ControlTarBuilder::new(control)
+            .add_data_file("usr/bin/changed", &mut std::io::Cursor::new("original"))?
+            .add_data_file("usr/bin/missing", &mut std::io::Cursor::new("x"))?
+            .add_data_file("usr/bin/same", &mut std::io::Cursor::new("same"))?
+            .add_data_file("etc/listed.conf", &mut std::io::Cursor::new("c"))?
+            .add_extra_file(
+                "conffiles",
+                b"/etc/listed.conf\n/etc/unlisted.conf\n".to_vec(),
+            )?;
+        let mut control_tar = vec![];
+        control_builder.write(&mut control_tar)?;
+
+        let mut manifest = simple_file_manifest::FileManifest::default();
+        manifest.add_file_entry("usr/bin/changed", b"modified".to_vec())?;
+        manifest.add_file_entry("usr/bin/same", b"same".to_vec())?;
+        manifest.add_file_entry("usr/bin/extra", b"extra".to_vec())?;
+        manifest.add_file_entry("etc/listed.conf", b"c".to_vec())?;
+        manifest.add_file_entry("etc/unlisted.conf", b"u".to_vec())?;
+        let mut data_tar = vec![];
+        crate::deb::builder::write_deb_tar(&mut data_tar, &manifest, 0)?;
+
+        let mut deb = vec![];
+        {
+            let mut builder = ar::Builder::new(&mut deb);
+            builder.append(
+                &ar::Header::new(b"debian-binary".to_vec(), 4),
+                &b"2.0\n"[..],
+            )?;
+            builder.append(
+                &ar::Header::new(b"control.tar".to_vec(), control_tar.len() as _),
+                control_tar.as_slice(),
+            )?;
+            builder.append(
+                &ar::Header::new(b"data.tar".to_vec(), data_tar.len() as _),
+                data_tar.as_slice(),
+            )?;
+        }
+
+        let results = BinaryPackageReader::new(std::io::Cursor::new(&deb))?
+            .verify_md5sums()?
+            .into_iter()
+            .map(|r| (r.path, r.status))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            results,
+            vec![
+                ("etc/listed.conf".to_string(), Md5sumStatus::Match),
+                (
+                    "usr/bin/changed".to_string(),
+                    Md5sumStatus::Mismatch {
+                        expected: hex::encode(md5::Md5::digest(b"original")),
+                        actual: hex::encode(md5::Md5::digest(b"modified")),
+                    }
+                ),
+                ("usr/bin/extra".to_string(), Md5sumStatus::Unlisted),
+                ("usr/bin/missing".to_string(), Md5sumStatus::Missing),
+                ("usr/bin/same".to_string(), Md5sumStatus::Match),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_member_compression() -> Result<()> {
+        let mut manifest = simple_file_manifest::FileManifest::default();
+        manifest.add_file_entry("usr/bin/myapp", vec![42])?;
+        let mut data_tar = vec![];
+        crate::deb::builder::write_deb_tar(&mut data_tar, &manifest, 0)?;
+
+        let mut bz2 = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::default());
+        bz2.write_all(&data_tar)?;
+        let bz2 = bz2.finish()?;
+
+        let stream = xz2::stream::Stream::new_lzma_encoder(
+            &xz2::stream::LzmaOptions::new_preset(6).map_err(std::io::Error::other)?,
+        )
+        .map_err(std::io::Error::other)?;
+        let mut lzma = xz2::write::XzEncoder::new_stream(vec![], stream);
+        lzma.write_all(&data_tar)?;
+        let lzma = lzma.finish()?;
+
+        for (name, data) in [
+            ("data.tar", data_tar.clone()),
+            ("data.tar.bz2", bz2),
+            ("data.tar.lzma", lzma),
+        ] {
+            let mut deb = vec![];
+            {
+                let mut builder = ar::Builder::new(&mut deb);
+                builder.append(
+                    &ar::Header::new(b"debian-binary".to_vec(), 4),
+                    &b"2.0\n"[..],
+                )?;
+                builder.append(
+                    &ar::Header::new(name.as_bytes().to_vec(), data.len() as _),
+                    data.as_slice(),
+                )?;
+            }
+
+            assert_eq!(
+                resolve_data_file(std::io::Cursor::new(&deb), "usr/bin/myapp")?,
+                Some(vec![42]),
+                "{}",
+                name
+            );
+
+            let mut reader = BinaryPackageReader::new(std::io::Cursor::new(&deb))?;
+            while let Some(entry) = reader.next_entry() {
+                if let BinaryPackageEntry::Data(data) = entry? {
+                    use futures::TryStreamExt;
+                    let entries = futures::executor::block_on(
+                        data.into_inner().entries()?.try_collect::<Vec<_>>(),
+                    )?;
+                    assert_eq!(entries.len(), 4, "{}", name);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn extract_to() -> Result<()> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "mypackage".into());
+        para.set_field_from_string("Architecture".into(), "amd64".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(para);
+
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let builder = DebBuilder::new(control)
+            .set_mtime(Some(mtime))
+            .install_file("usr/bin/myapp", FileEntry::new_from_data(vec![42], true))?
+            .install_file(
+                "usr/share/doc/mypackage/README",
+                FileEntry::new_from_data(b"hello".to_vec(), false),
+            )?;
+
+        let mut deb = vec![];
+        builder.write(&mut deb)?;
+
+        let td = tempfile::TempDir::new()?;
+        let dest = td.path().join("root");
+        BinaryPackageReader::new(std::io::Cursor::new(&deb))?.extract_to(&dest)?;
+
+        assert_eq!(std::fs::read(dest.join("usr/bin/myapp"))?, vec![42]);
+        assert_eq!(
+            std::fs::read(dest.join("usr/share/doc/mypackage/README"))?,
+            b"hello".to_vec()
+        );
+        assert_eq!(
+            std::fs::metadata(dest.join("usr/share/doc"))?.modified()?,
+            mtime
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &str| -> Result<u32> {
+                Ok(std::fs::metadata(dest.join(p))?.permissions().mode() & 0o777)
+            };
+            assert_eq!(mode("usr/bin/myapp")?, 0o755);
+            assert_eq!(mode("usr/share/doc/mypackage/README")?, 0o644);
+        }
+
+        assert_eq!(
+            sanitize_data_path(b"./usr/bin/myapp")?,
+            PathBuf::from("usr/bin/myapp")
+        );
+        assert_eq!(sanitize_data_path(b"/etc/foo")?, PathBuf::from("etc/foo"));
+        assert!(matches!(
+            sanitize_data_path(b"./usr/../../etc/passwd"),
+            Err(DebianError::DebDataTarIllegalPath(_))
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_to_symlink_escape() -> Result<()> {
+        let td = tempfile::TempDir::new()?;
+        let outside = td.path().join("outside");
+        std::fs::create_dir(&outside)?;
+
+        let header = |entry_type, mode| {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(entry_type);
+            header.set_size(0);
+            header.set_mode(mode);
+            header.set_uid(0);
+            header.set_gid(0);
+            header.set_mtime(0);
+            header
+        };
+
+        let mut data_tar = tar::Builder::new(vec![]);
+        data_tar.append_link(&mut header(tar::EntryType::Symlink, 0o777), "./a", &outside)?;
+        data_tar.append_data(
+            &mut header(tar::EntryType::Directory, 0o755),
+            "./a/sub/",
+            std::io::empty(),
+        )?;
+        let data_tar = data_tar.into_inner()?;
+
+        let mut deb = vec![];
+        {
+            let mut builder = ar::Builder::new(&mut deb);
+            builder.append(
+                &ar::Header::new(b"debian-binary".to_vec(), 4),
+                &b"2.0\n"[..],
+            )?;
+            builder.append(
+                &ar::Header::new(b"data.tar".to_vec(), data_tar.len() as _),
+                data_tar.as_slice(),
+            )?;
+        }
+
+        let dest = td.path().join("root");
+        assert!(matches!(
+            BinaryPackageReader::new(std::io::Cursor::new(&deb))?.extract_to(&dest),
+            Err(DebianError::DebDataTarIllegalPath(_))
+        ));
+        assert!(!outside.join("sub").exists());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,220 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Search for files within `.deb` packages.
+
+[PathMatcher] selects installed paths by glob or regular expression. [search_data()]
+visits matching `data.tar` entries with a reader of their content, without extracting
+the package. The same matcher can query repository `Contents` indices via
+[crate::repository::contents::ContentsFile::find_paths()].
+*/
+
+use {
+    crate::{
+        deb::reader::{BinaryPackageReader, BinaryPackageStreamingEntry},
+        error::Result,
+    },
+    regex::Regex,
+    std::io::Read,
+};
+
+/// Normalize an installed path to be relative to the filesystem root.
+pub(crate) fn normalize_path(path: &str) -> &str {
+    path.trim_start_matches("./").trim_start_matches('/')
+}
+
+/// Selects installed paths.
+///
+/// Paths are matched without a leading `/` or `./`, e.g. `usr/bin/foo`.
+#[derive(Clone, Debug)]
+pub enum PathMatcher {
+    /// A glob pattern. `*` matches across `/`, so `*.service` matches service files in
+    /// any directory.
+    Glob(glob::Pattern),
+    /// A regular expression, searched for anywhere in the path unless anchored.
+    Regex(Regex),
+}
+
+impl PathMatcher {
+    /// Construct an instance from a glob pattern.
+    ///
+    /// A leading `/` or `./` in the pattern is ignored.
+    pub fn glob(pattern: &str) -> Result<Self> {
+        Ok(Self::Glob(glob::Pattern::new(normalize_path(pattern))?))
+    }
+
+    /// Construct an instance from a regular expression.
+    pub fn regex(expression: &str) -> Result<Self> {
+        Ok(Self::Regex(Regex::new(expression)?))
+    }
+
+    /// Whether a path matches.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = normalize_path(path);
+
+        match self {
+            Self::Glob(pattern) => pattern.matches(path),
+            Self::Regex(re) => re.is_match(path),
+        }
+    }
+}
+
+/// Metadata of a `data.tar` entry matched by a search.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DataFileMatch {
+    /// Installed path, without a leading `/` or `./` or a trailing `/`.
+    pub path: String,
+    /// The tar entry type.
+    pub entry_type: tar::EntryType,
+    /// Size in bytes of the entry content.
+    pub size: u64,
+    /// Permission bits.
+    pub mode: u32,
+    /// Numeric owner.
+    pub uid: u64,
+    /// Numeric group.
+    pub gid: u64,
+    /// Modified time, in seconds since the UNIX epoch.
+    pub mtime: u64,
+    /// Target of symlinks and hard links.
+    pub link_target: Option<String>,
+}
+
+impl DataFileMatch {
+    pub(crate) fn from_entry(path: String, entry: &tar::Entry<'_, impl Read>) -> Result<Self> {
+        let header = entry.header();
+
+        Ok(Self {
+            path,
+            entry_type: header.entry_type(),
+            size: header.size()?,
+            mode: header.mode()?,
+            uid: header.uid()?,
+            gid: header.gid()?,
+            mtime: header.mtime()?,
+            link_target: entry
+                .link_name_bytes()
+                .map(|v| String::from_utf8_lossy(&v).to_string()),
+        })
+    }
+}
+
+/// Visit `data.tar` entries of a `.deb` whose paths match.
+///
+/// `visit` is called for every matching entry (including directories and links) with
+/// its metadata and a reader of its content. `data.tar` is decompressed incrementally
+/// and content not read by `visit` is skipped.
+pub fn search_data(
+    reader: impl Read,
+    matcher: &PathMatcher,
+    mut visit: impl FnMut(&DataFileMatch, &mut dyn Read) -> Result<()>,
+) -> Result<()> {
+    let mut reader = BinaryPackageReader::new(reader)?;
+
+    while let Some(entry) = reader.next_entry_streaming() {
+        if let BinaryPackageStreamingEntry::Data(mut data) = entry? {
+            for entry in data.entries()? {
+                let mut entry = entry?;
+
+                let path = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+                let path = normalize_path(&path).trim_end_matches('/');
+                if path.is_empty() || !matcher.matches(path) {
+                    continue;
+                }
+
+                let info = DataFileMatch::from_entry(path.into(), &entry)?;
+                visit(&info, &mut entry)?;
+            }
+
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Find `data.tar` entries of a `.deb` whose paths match.
+///
+/// Content of entries isn't read.
+pub fn find_data_files(reader: impl Read, matcher: &PathMatcher) -> Result<Vec<DataFileMatch>> {
+    let mut res = vec![];
+    search_data(reader, matcher, |info, _| {
+        res.push(info.clone());
+        Ok(())
+    })?;
+
+    Ok(res)
+}
+
+/// Read the content of regular files in a `.deb` whose paths match.
+pub fn read_data_files(
+    reader: impl Read,
+    matcher: &PathMatcher,
+) -> Result<Vec<(DataFileMatch, Vec<u8>)>> {
+    let mut res = vec![];
+    search_data(reader, matcher, |info, content| {
+        if info.entry_type.is_file() {
+            let mut data = vec![];
+            content.read_to_end(&mut data)?;
+            res.push((info.clone(), data));
+        }
+        Ok(())
+    })?;
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            control::{ControlFile, ControlParagraph},
+            deb::builder::DebBuilder,
+        },
+        simple_file_manifest::FileEntry,
+    };
+
+    #[test]
+    fn search() -> Result<()> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "hello".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(para);
+
+        let mut deb = vec![];
+        DebBuilder::new(control)
+            .install_file(
+                "lib/systemd/system/hello.service",
+                FileEntry::new_from_data(b"[Unit]".to_vec(), false),
+            )?
+            .install_file(
+                "usr/bin/hello",
+                FileEntry::new_from_data(b"binary".to_vec(), true),
+            )?
+            .install_symlink("usr/bin/hi", "hello")?
+            .write(&mut deb)?;
+
+        let matcher = PathMatcher::glob("*.service")?;
+        let files = read_data_files(std::io::Cursor::new(&deb), &matcher)?;
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0.path, "lib/systemd/system/hello.service");
+        assert_eq!(files[0].0.mode, 0o644);
+        assert_eq!(files[0].1, b"[Unit]");
+
+        let matcher = PathMatcher::glob("/usr/bin/*")?;
+        let files = find_data_files(std::io::Cursor::new(&deb), &matcher)?;
+        assert_eq!(
+            files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(),
+            vec!["usr/bin/hello", "usr/bin/hi"]
+        );
+        assert_eq!(files[1].link_target.as_deref(), Some("hello"));
+
+        let matcher = PathMatcher::regex("^usr/bin$")?;
+        let files = find_data_files(std::io::Cursor::new(&deb), &matcher)?;
+        assert_eq!(files[0].entry_type, tar::EntryType::Directory);
+
+        Ok(())
+    }
+}
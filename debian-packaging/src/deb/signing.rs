@@ -0,0 +1,411 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! PGP signatures embedded in .deb files.
+
+In addition to repository signatures, individual `.deb` files can carry signatures
+as extra ar members named `_gpg<role>`, as produced by `debsigs` and verified by
+`debsig-verify`. Each member holds a detached OpenPGP signature over the
+concatenated content of the `debian-binary`, `control.tar` and `data.tar` members,
+in archive order. The role describes who made the signature. e.g. `_gpgorigin`
+is the signature of the package's originator and `_gpgbuilder` of its builder.
+
+Signature members are appended after `data.tar`. Readers ignore them, so signing
+doesn't change how a package is installed.
+
+[verify_package_signature()] checks signatures against a single key.
+[verify_package_signatures()] checks them against a [Keyring], subject to a [TrustPolicy].
+*/
+
+use {
+    crate::{
+        deb::archive::ArArchiveBuilder,
+        error::{DebianError, Result},
+        keyring::{Keyring, SignerIdentity, TrustPolicy},
+        repository::signing::sign_detached,
+    },
+    pgp::{
+        crypto::hash::HashAlgorithm, ser::Serialize, types::SecretKeyTrait, Deserializable,
+        Signature, SignedPublicKey, StandaloneSignature,
+    },
+    std::io::{Cursor, Read, Write},
+};
+
+/// The role of the party making a signature embedded in a `.deb` file.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum SignatureRole {
+    /// The originator of the package, in `_gpgorigin`.
+    Origin,
+    /// The maintainer of the package, in `_gpgmaint`.
+    Maintainer,
+    /// The archive the package was published in, in `_gpgarchive`.
+    Archive,
+    /// The builder of the package, in `_gpgbuilder`.
+    Builder,
+}
+
+impl SignatureRole {
+    /// All roles.
+    pub const ALL: [Self; 4] = [Self::Origin, Self::Maintainer, Self::Archive, Self::Builder];
+
+    /// The name of the ar member holding signatures for this role.
+    pub fn member_name(&self) -> &'static str {
+        match self {
+            Self::Origin => "_gpgorigin",
+            Self::Maintainer => "_gpgmaint",
+            Self::Archive => "_gpgarchive",
+            Self::Builder => "_gpgbuilder",
+        }
+    }
+
+    /// Resolve the role from an ar member name.
+    pub fn from_member_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.member_name() == name)
+    }
+}
+
+/// A signature embedded in a `.deb` file.
+#[derive(Clone, Debug)]
+pub struct PackageSignature {
+    /// The role of the signer.
+    pub role: SignatureRole,
+    /// The signature.
+    pub signature: Signature,
+}
+
+/// The members of a `.deb` ar archive.
+struct DebMembers {
+    members: Vec<(ar::Header, Vec<u8>)>,
+}
+
+impl DebMembers {
+    fn read(reader: impl Read) -> Result<Self> {
+        let mut archive = ar::Archive::new(reader);
+        let mut members = vec![];
+
+        while let Some(entry) = archive.next_entry() {
+            let mut entry = entry?;
+            let mut data = vec![];
+            entry.read_to_end(&mut data)?;
+            members.push((entry.header().clone(), data));
+        }
+
+        Ok(Self { members })
+    }
+
+    /// The content covered by signatures.
+    fn signed_data(&self) -> Vec<u8> {
+        self.members
+            .iter()
+            .filter(|(header, _)| !header.identifier().starts_with(b"_"))
+            .flat_map(|(_, data)| data.iter().copied())
+            .collect()
+    }
+
+    fn signatures(&self) -> Result<Vec<PackageSignature>> {
+        let mut res = vec![];
+
+        for (header, data) in &self.members {
+            let Some(role) =
+                SignatureRole::from_member_name(&String::from_utf8_lossy(header.identifier()))
+            else {
+                continue;
+            };
+
+            let signatures = if data.starts_with(b"-----BEGIN") {
+                StandaloneSignature::from_armor_many(Cursor::new(data))?
+                    .0
+                    .collect::<std::result::Result<Vec<_>, _>>()?
+            } else {
+                StandaloneSignature::from_bytes_many(Cursor::new(data))
+                    .collect::<std::result::Result<Vec<_>, _>>()?
+            };
+
+            res.extend(signatures.into_iter().map(|sig| PackageSignature {
+                role,
+                signature: sig.signature,
+            }));
+        }
+
+        Ok(res)
+    }
+}
+
+/// Obtain the data covered by signatures in a `.deb` file.
+///
+/// This is the concatenated content of all members whose names don't begin with `_`.
+pub fn signed_data(reader: impl Read) -> Result<Vec<u8>> {
+    Ok(DebMembers::read(reader)?.signed_data())
+}
+
+/// Obtain the signatures embedded in a `.deb` file.
+///
+/// Members of unknown roles are ignored.
+pub fn package_signatures(reader: impl Read) -> Result<Vec<PackageSignature>> {
+    DebMembers::read(reader)?.signatures()
+}
+
+/// Sign a `.deb` file, writing the signed package to a writer.
+///
+/// The signature is stored in the member for `role` as a binary OpenPGP signature, as
+/// `debsigs` does. An existing signature member for the same role is replaced. Other
+/// members are copied with their content and mode. All members are given the latest
+/// modification time of the original members.
+pub fn sign_package<PW>(
+    reader: impl Read,
+    writer: impl Write,
+    role: SignatureRole,
+    key: &impl SecretKeyTrait,
+    key_pw: PW,
+    hash: HashAlgorithm,
+) -> Result<()>
+where
+    PW: FnOnce() -> String,
+{
+    let members = DebMembers::read(reader)?;
+    let signature = sign_detached(key, key_pw, hash, &members.signed_data())?;
+    let data = StandaloneSignature::new(signature).to_bytes()?;
+
+    let mut builder = ArArchiveBuilder::default().set_deb(true).set_mtime(
+        members
+            .members
+            .iter()
+            .map(|(header, _)| header.mtime())
+            .max()
+            .unwrap_or_default(),
+    );
+
+    for (header, content) in &members.members {
+        if header.identifier() != role.member_name().as_bytes() {
+            builder = builder.add_member_with_mode(
+                header.identifier(),
+                header.mode(),
+                content.clone(),
+            )?;
+        }
+    }
+
+    builder
+        .add_member(role.member_name(), data)?
+        .write(writer)?;
+
+    Ok(())
+}
+
+/// Verify the signatures of a given role in a `.deb` file against a key.
+///
+/// Succeeds if any signature of the role was made by the key or one of its subkeys.
+/// Errors if the package has no signature for the role or if none verifies.
+pub fn verify_package_signature(
+    reader: impl Read,
+    role: SignatureRole,
+    key: &SignedPublicKey,
+) -> Result<()> {
+    let members = DebMembers::read(reader)?;
+    let data = members.signed_data();
+
+    let signatures = members
+        .signatures()?
+        .into_iter()
+        .filter(|sig| sig.role == role)
+        .collect::<Vec<_>>();
+
+    if signatures.is_empty() {
+        return Err(DebianError::DebSignatureMissing(role.member_name()));
+    }
+
+    let verified = signatures.iter().any(|sig| {
+        sig.signature.verify(key, Cursor::new(&data)).is_ok()
+            || key
+                .public_subkeys
+                .iter()
+                .any(|subkey| sig.signature.verify(subkey, Cursor::new(&data)).is_ok())
+    });
+
+    if verified {
+        Ok(())
+    } else {
+        Err(DebianError::DebSignatureUntrusted(role.member_name()))
+    }
+}
+
+/// Verify the signatures of a given role in a `.deb` file against a keyring.
+///
+/// Returns the identities of the keys that made acceptable signatures of the role. Errors
+/// with [DebianError::DebSignatureMissing] if the package has no signature for the role
+/// and with [DebianError::DebSignatureUntrusted] if no key in the keyring made one.
+/// Otherwise errors are as for [TrustPolicy::evaluate()].
+pub fn verify_package_signatures(
+    reader: impl Read,
+    role: SignatureRole,
+    keyring: &Keyring,
+    policy: &TrustPolicy,
+) -> Result<Vec<SignerIdentity>> {
+    let members = DebMembers::read(reader)?;
+
+    let signatures = members
+        .signatures()?
+        .into_iter()
+        .filter(|sig| sig.role == role)
+        .map(|sig| sig.signature)
+        .collect::<Vec<_>>();
+
+    if signatures.is_empty() {
+        return Err(DebianError::DebSignatureMissing(role.member_name()));
+    }
+
+    match keyring.verify_detached_trusted(&signatures, &members.signed_data(), policy) {
+        Err(DebianError::PgpSignatureUntrusted) => {
+            Err(DebianError::DebSignatureUntrusted(role.member_name()))
+        }
+        res => res,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            control::{ControlFile, ControlParagraph},
+            deb::{builder::DebBuilder, reader::resolve_control_file},
+            signing_key::{create_self_signed_key, signing_secret_key_params_builder},
+        },
+    };
+
+    #[test]
+    fn sign_and_verify() -> Result<()> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), "mypackage".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(para);
+
+        let mut deb = vec![];
+        DebBuilder::new(control)
+            .install_file("usr/bin/myapp", vec![42])?
+            .write(&mut deb)?;
+
+        let (key, public_key) = create_self_signed_key(
+            signing_secret_key_params_builder("signer@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+        let (_, other_public_key) = create_self_signed_key(
+            signing_secret_key_params_builder("other@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+
+        assert!(matches!(
+            verify_package_signature(deb.as_slice(), SignatureRole::Origin, &public_key),
+            Err(DebianError::DebSignatureMissing(_))
+        ));
+
+        let mut signed = vec![];
+        sign_package(
+            deb.as_slice(),
+            &mut signed,
+            SignatureRole::Origin,
+            &key,
+            String::new,
+            HashAlgorithm::SHA2_256,
+        )?;
+
+        assert_eq!(
+            signed_data(signed.as_slice())?,
+            signed_data(deb.as_slice())?
+        );
+        assert_eq!(package_signatures(signed.as_slice())?.len(), 1);
+        verify_package_signature(signed.as_slice(), SignatureRole::Origin, &public_key)?;
+        assert!(matches!(
+            verify_package_signature(signed.as_slice(), SignatureRole::Origin, &other_public_key),
+            Err(DebianError::DebSignatureUntrusted(_))
+        ));
+        assert!(matches!(
+            verify_package_signature(signed.as_slice(), SignatureRole::Builder, &public_key),
+            Err(DebianError::DebSignatureMissing(_))
+        ));
+
+        let keyring = Keyring::from_iter([public_key.clone()]);
+        let signers = verify_package_signatures(
+            signed.as_slice(),
+            SignatureRole::Origin,
+            &keyring,
+            &TrustPolicy::strict(),
+        )?;
+        assert_eq!(signers[0].user_ids, vec!["signer@example.com"]);
+        assert!(matches!(
+            verify_package_signatures(
+                signed.as_slice(),
+                SignatureRole::Origin,
+                &keyring,
+                &TrustPolicy {
+                    min_key_bits: Some(4096),
+                    ..TrustPolicy::strict()
+                },
+            ),
+            Err(DebianError::PgpSignatureRejected(_, reason)) if reason.contains("too small")
+        ));
+        assert!(matches!(
+            verify_package_signatures(
+                signed.as_slice(),
+                SignatureRole::Origin,
+                &Keyring::from_iter([other_public_key.clone()]),
+                &TrustPolicy::default(),
+            ),
+            Err(DebianError::DebSignatureUntrusted(_))
+        ));
+
+        // Signing again replaces the signature for the role.
+        let mut resigned = vec![];
+        sign_package(
+            signed.as_slice(),
+            &mut resigned,
+            SignatureRole::Origin,
+            &key,
+            String::new,
+            HashAlgorithm::SHA2_256,
+        )?;
+        assert_eq!(package_signatures(resigned.as_slice())?.len(), 1);
+
+        // Readers ignore signature members.
+        assert_eq!(
+            resolve_control_file(signed.as_slice())?.package()?,
+            "mypackage"
+        );
+
+        assert!(
+            crate::deb::reader::BinaryPackageReader::new(signed.as_slice())?
+                .verify_md5sums()?
+                .iter()
+                .all(|v| v.status == crate::deb::reader::Md5sumStatus::Match)
+        );
+
+        // Tampering with the content invalidates the signature.
+        let mut tampered = vec![];
+        {
+            let mut builder = ar::Builder::new(&mut tampered);
+            for (header, mut data) in DebMembers::read(signed.as_slice())?.members {
+                if header.identifier().starts_with(b"data.tar") {
+                    data[0] ^= 0xff;
+                }
+                builder.append(&header, data.as_slice())?;
+            }
+        }
+        assert_ne!(
+            signed_data(tampered.as_slice())?,
+            signed_data(signed.as_slice())?
+        );
+        assert!(
+            verify_package_signature(tampered.as_slice(), SignatureRole::Origin, &public_key)
+                .is_err()
+        );
+
+        Ok(())
+    }
+}
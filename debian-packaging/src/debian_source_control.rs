@@ -9,14 +9,16 @@ use {
         control::{ControlParagraph, ControlParagraphReader},
         dependency::{DependencyList, PackageDependencyFields},
         error::{DebianError, Result},
+        file_list::{parse_file_list, validate_file_lists, FileListEntry},
         io::ContentDigest,
+        keyring::{Keyring, SignerIdentity, TrustPolicy},
+        maintainer::{parse_contact_list, split_contact_list, Contact},
         package_version::PackageVersion,
         repository::release::ChecksumType,
     },
     std::{
         io::BufRead,
         ops::{Deref, DerefMut},
-        str::FromStr,
     },
 };
 
@@ -33,6 +35,16 @@ pub struct DebianSourceControlFileEntry<'a> {
     pub size: u64,
 }
 
+impl<'a> From<FileListEntry<'a>> for DebianSourceControlFileEntry<'a> {
+    fn from(entry: FileListEntry<'a>) -> Self {
+        Self {
+            filename: entry.filename,
+            digest: entry.digest,
+            size: entry.size,
+        }
+    }
+}
+
 impl<'a> DebianSourceControlFileEntry<'a> {
     /// Convert this instance to a [DebianSourceControlFileFetch].
     ///
@@ -63,6 +75,151 @@ pub struct DebianSourceControlFilePackage<'a> {
     pub extra: Vec<&'a str>,
 }
 
+/// A version control system named by a `Vcs-*` field.
+///
+/// See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#version-control-system-vcs-fields>.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum VcsKind {
+    Arch,
+    Bzr,
+    Cvs,
+    Darcs,
+    Git,
+    Hg,
+    Mtn,
+    Svn,
+}
+
+impl VcsKind {
+    /// All variants.
+    pub const ALL: [Self; 8] = [
+        Self::Arch,
+        Self::Bzr,
+        Self::Cvs,
+        Self::Darcs,
+        Self::Git,
+        Self::Hg,
+        Self::Mtn,
+        Self::Svn,
+    ];
+
+    /// The name of the control field, e.g. `Vcs-Git`.
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            Self::Arch => "Vcs-Arch",
+            Self::Bzr => "Vcs-Bzr",
+            Self::Cvs => "Vcs-Cvs",
+            Self::Darcs => "Vcs-Darcs",
+            Self::Git => "Vcs-Git",
+            Self::Hg => "Vcs-Hg",
+            Self::Mtn => "Vcs-Mtn",
+            Self::Svn => "Vcs-Svn",
+        }
+    }
+
+    /// Whether field values start with a URL.
+    ///
+    /// `Vcs-Cvs` values are a CVSROOT, `Vcs-Mtn` values a server address and `Vcs-Arch`
+    /// values an archive location, none of which are URLs.
+    pub fn has_url(&self) -> bool {
+        !matches!(self, Self::Arch | Self::Cvs | Self::Mtn)
+    }
+}
+
+impl std::fmt::Display for VcsKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.field_name())
+    }
+}
+
+/// A parsed `Vcs-*` field.
+///
+/// Values have the form `location [-b branch] [[path]]`. `-b` names a branch other than the
+/// default and `[path]` a subdirectory the package lives in. For `Vcs-Cvs` and `Vcs-Mtn`,
+/// the word after the location is the module or branch, respectively, and is stored in
+/// [Self::path] or [Self::branch].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VcsRepository {
+    /// The version control system.
+    pub kind: VcsKind,
+    /// The repository location.
+    ///
+    /// This is validated as a URL if [VcsKind::has_url()].
+    pub location: String,
+    /// The branch, if not the default.
+    pub branch: Option<String>,
+    /// Path of the package within the repository, or CVS module.
+    pub path: Option<String>,
+}
+
+impl VcsRepository {
+    /// Parse the value of a `Vcs-*` field.
+    pub fn parse(kind: VcsKind, value: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            DebianError::VcsFieldInvalid(kind.field_name(), format!("{}: {}", reason, value))
+        };
+
+        let mut words = value.split_whitespace();
+        let location = words.next().ok_or_else(|| invalid("empty value"))?;
+        if kind.has_url() {
+            url::Url::parse(location).map_err(|e| invalid(&e.to_string()))?;
+        }
+
+        let mut res = Self {
+            kind,
+            location: location.to_string(),
+            branch: None,
+            path: None,
+        };
+
+        while let Some(word) = words.next() {
+            if word == "-b" {
+                let branch = words.next().ok_or_else(|| invalid("-b lacks a branch"))?;
+                res.branch = Some(branch.to_string());
+            } else if let Some(path) = word.strip_prefix('[').and_then(|w| w.strip_suffix(']')) {
+                res.path = Some(path.to_string());
+            } else if kind == VcsKind::Cvs && res.path.is_none() {
+                res.path = Some(word.to_string());
+            } else if kind == VcsKind::Mtn && res.branch.is_none() {
+                res.branch = Some(word.to_string());
+            } else {
+                return Err(invalid(&format!("unexpected {}", word)));
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+impl std::fmt::Display for VcsRepository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.location)?;
+
+        match self.kind {
+            VcsKind::Cvs => {
+                if let Some(module) = &self.path {
+                    write!(f, " {}", module)?;
+                }
+            }
+            VcsKind::Mtn => {
+                if let Some(branch) = &self.branch {
+                    write!(f, " {}", branch)?;
+                }
+            }
+            _ => {
+                if let Some(branch) = &self.branch {
+                    write!(f, " -b {}", branch)?;
+                }
+                if let Some(path) = &self.path {
+                    write!(f, " [{}]", path)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DebianSourceControlFileFetch {
     /// The path relative to the repository root to fetch.
@@ -156,7 +313,7 @@ impl<'a> DebianSourceControlFile<'a> {
     ///
     /// The PGP signature is NOT validated. The file will be parsed despite lack of
     /// signature verification. This is conceptually insecure. But since Rust has memory
-    /// safety, some risk is prevented.
+    /// safety, some risk is prevented. Use [Self::verify_signatures()] to verify it.
     pub fn from_armored_reader<R: BufRead>(reader: R) -> Result<Self> {
         let reader = pgp_cleartext::CleartextSignatureReader::new(reader);
         let mut reader = std::io::BufReader::new(reader);
@@ -181,6 +338,24 @@ impl<'a> DebianSourceControlFile<'a> {
         self.signatures.as_ref()
     }
 
+    /// Verify the PGP signatures of this file against a keyring, subject to a policy.
+    ///
+    /// Returns the identities of the keys that made acceptable signatures. Errors with
+    /// [DebianError::ReleaseNoSignatures] if this file isn't signed. Otherwise errors are
+    /// as for [Keyring::verify_trusted()].
+    pub fn verify_signatures(
+        &self,
+        keyring: &Keyring,
+        policy: &TrustPolicy,
+    ) -> Result<Vec<SignerIdentity>> {
+        let signatures = self
+            .signatures
+            .as_ref()
+            .ok_or(DebianError::ReleaseNoSignatures)?;
+
+        keyring.verify_trusted(signatures, policy)
+    }
+
     /// The format of the source package.
     ///
     /// See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#s-f-format>.
@@ -233,8 +408,21 @@ impl<'a> DebianSourceControlFile<'a> {
     /// The list of uploaders and co-maintainers.
     ///
     /// See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#s-f-uploaders>.
+    ///
+    /// Commas within quoted names don't delimit items.
     pub fn uploaders(&self) -> Option<Box<(dyn Iterator<Item = &str> + '_)>> {
-        self.iter_field_comma_delimited("Uploaders")
+        self.field_str("Uploaders")
+            .map(|v| Box::new(split_contact_list(v)) as Box<dyn Iterator<Item = &str>>)
+    }
+
+    /// The parsed package maintainer.
+    pub fn typed_maintainer(&self) -> Result<Contact> {
+        Contact::parse(self.maintainer()?)
+    }
+
+    /// The parsed list of uploaders and co-maintainers.
+    pub fn typed_uploaders(&self) -> Option<Result<Vec<Contact>>> {
+        self.field_str("Uploaders").map(parse_contact_list)
     }
 
     /// The URL from which the source of this package can be obtained.
@@ -251,6 +439,38 @@ impl<'a> DebianSourceControlFile<'a> {
         self.iter_field_comma_delimited("Testsuite")
     }
 
+    /// The `Vcs-Browser` field, parsed to a URL.
+    ///
+    /// See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#version-control-system-vcs-fields>.
+    pub fn vcs_browser(&self) -> Option<Result<url::Url>> {
+        self.field_str("Vcs-Browser")
+            .map(|value| Ok(url::Url::parse(value.trim())?))
+    }
+
+    /// All `Vcs-*` fields naming a repository, parsed to [VcsRepository].
+    ///
+    /// Fields are returned in [VcsKind::ALL] order.
+    pub fn vcs_repositories(&self) -> Result<Vec<VcsRepository>> {
+        VcsKind::ALL
+            .into_iter()
+            .filter_map(|kind| {
+                self.field_str(kind.field_name())
+                    .map(|value| VcsRepository::parse(kind, value))
+            })
+            .collect()
+    }
+
+    /// The repository this package is maintained in.
+    ///
+    /// Policy permits only one `Vcs-*` field naming a repository. If there are more, the
+    /// first in [VcsKind::ALL] order is returned.
+    pub fn vcs(&self) -> Option<Result<VcsRepository>> {
+        VcsKind::ALL.into_iter().find_map(|kind| {
+            self.field_str(kind.field_name())
+                .map(|value| VcsRepository::parse(kind, value))
+        })
+    }
+
     /// Describes the Git source from which this package came.
     ///
     /// See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#s-f-dgit>.
@@ -360,57 +580,52 @@ impl<'a> DebianSourceControlFile<'a> {
             .ok_or_else(|| DebianError::ControlRequiredFieldMissing("Files".to_string()))
     }
 
+    /// List of associated files with digests of the given flavor.
+    ///
+    /// [ChecksumType::Md5] digests are read from the `Files` field. Other flavors are read
+    /// from their `Checksums-*` field.
+    pub fn checksums(
+        &self,
+        checksum: ChecksumType,
+    ) -> Option<Box<dyn Iterator<Item = Result<DebianSourceControlFileEntry<'_>>> + '_>> {
+        self.iter_files(checksum.source_field_name(), checksum)
+    }
+
     fn iter_files(
         &self,
         field: &str,
         checksum: ChecksumType,
     ) -> Option<Box<(dyn Iterator<Item = Result<DebianSourceControlFileEntry<'_>>> + '_)>> {
-        if let Some(iter) = self.iter_field_lines(field) {
-            Some(Box::new(iter.map(move |v| {
-                // Values are of form: <digest> <size> <path>
-
-                let mut parts = v.split_ascii_whitespace();
+        let lines = self.iter_field_lines(field)?;
 
-                let digest = parts.next().ok_or(DebianError::ReleaseMissingDigest)?;
-                let size = parts.next().ok_or(DebianError::ReleaseMissingSize)?;
-                let filename = parts.next().ok_or(DebianError::ReleaseMissingPath)?;
-
-                // Are paths with spaces allowed?
-                if parts.next().is_some() {
-                    return Err(DebianError::ReleasePathWithSpaces(v.to_string()));
-                }
-
-                let digest = ContentDigest::from_hex_digest(checksum, digest)?;
-                let size = u64::from_str(size)?;
+        Some(Box::new(
+            parse_file_list(checksum, false, lines).map(|entry| entry.map(Into::into)),
+        ))
+    }
 
-                Ok(DebianSourceControlFileEntry {
-                    filename,
-                    digest,
-                    size,
-                })
-            })))
-        } else {
-            None
-        }
+    /// Validate that the `Files` and `Checksums-*` fields describe the same files.
+    ///
+    /// `Files` is required. Every filename must occur once per field and every present
+    /// `Checksums-*` field must list the same filenames with the same sizes as `Files`.
+    /// Mismatches are reported as [DebianError::FileListInconsistent].
+    pub fn validate_checksums(&self) -> Result<()> {
+        validate_file_lists(self, false)
     }
 
     /// Obtain [DebianSourceControlFileFetch] for a given digest variant.
     ///
     /// This obtains records that instruct how to fetch the files that compose this
-    /// source package.
+    /// source package. The file lists are validated with [Self::validate_checksums()]
+    /// first.
     pub fn file_fetches(
         &self,
         checksum: ChecksumType,
     ) -> Result<Box<(dyn Iterator<Item = Result<DebianSourceControlFileFetch>> + '_)>> {
-        let entries = match checksum {
-            ChecksumType::Md5 => self.files()?,
-            ChecksumType::Sha1 => self.checksums_sha1().ok_or_else(|| {
-                DebianError::ControlRequiredFieldMissing("Checksums-Sha1".to_string())
-            })?,
-            ChecksumType::Sha256 => self.checksums_sha256().ok_or_else(|| {
-                DebianError::ControlRequiredFieldMissing("Checksums-Sha256".to_string())
-            })?,
-        };
+        self.validate_checksums()?;
+
+        let entries = self.checksums(checksum).ok_or_else(|| {
+            DebianError::ControlRequiredFieldMissing(checksum.source_field_name().to_string())
+        })?;
 
         Ok(Box::new(entries.map(move |entry| {
             let entry = entry?;
@@ -423,10 +638,33 @@ impl<'a> DebianSourceControlFile<'a> {
 
 #[cfg(test)]
 mod test {
-    use super::*;
+    use {super::*, pgp::KeyType};
 
     const ZSTD_DSC: &[u8] = include_bytes!("testdata/libzstd_1.4.8+dfsg-3.dsc");
 
+    #[test]
+    fn vcs_repository() -> Result<()> {
+        let repo = VcsRepository::parse(
+            VcsKind::Git,
+            "https://salsa.debian.org/foo.git -b debian/latest [packages/foo]",
+        )?;
+        assert_eq!(repo.branch.as_deref(), Some("debian/latest"));
+        assert_eq!(repo.path.as_deref(), Some("packages/foo"));
+        assert_eq!(
+            repo.to_string(),
+            "https://salsa.debian.org/foo.git -b debian/latest [packages/foo]"
+        );
+
+        let repo = VcsRepository::parse(VcsKind::Cvs, ":pserver:anon@cvs.example.org:/cvs foo")?;
+        assert_eq!(repo.path.as_deref(), Some("foo"));
+
+        assert!(VcsRepository::parse(VcsKind::Git, "not a url").is_err());
+        assert!(VcsRepository::parse(VcsKind::Git, "https://example.com/foo.git -b").is_err());
+        assert!(VcsRepository::parse(VcsKind::Svn, "svn://example.com/foo extra").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn parse_cleartext_armored() -> Result<()> {
         let cf = DebianSourceControlFile::from_armored_reader(std::io::Cursor::new(ZSTD_DSC))?;
@@ -454,7 +692,28 @@ mod test {
                 "Alexandre Mestiashvili <mestia@debian.org>",
             ]
         );
+        assert_eq!(
+            cf.typed_maintainer()?.email,
+            "debian-med-packaging@lists.alioth.debian.org"
+        );
+        assert_eq!(
+            cf.typed_uploaders().unwrap()?[1],
+            Contact::new("Olivier Sallou", "osallou@debian.org")
+        );
         assert_eq!(cf.homepage(), Some("https://github.com/facebook/zstd"));
+        assert_eq!(
+            cf.vcs_browser().unwrap()?.as_str(),
+            "https://salsa.debian.org/med-team/libzstd"
+        );
+        assert_eq!(
+            cf.vcs().unwrap()?,
+            VcsRepository {
+                kind: VcsKind::Git,
+                location: "https://salsa.debian.org/med-team/libzstd.git".into(),
+                branch: None,
+                path: None,
+            }
+        );
         assert_eq!(cf.standards_version()?, "4.6.0");
         assert_eq!(
             cf.testsuite().unwrap().collect::<Vec<_>>(),
@@ -543,6 +802,55 @@ mod test {
             ]
         );
 
+        cf.validate_checksums()?;
+
+        let data = String::from_utf8(ZSTD_DSC.to_vec())
+            .unwrap()
+            .replace("bb4 1331996 ", "bb4 1331997 ");
+        let cf = DebianSourceControlFile::from_armored_reader(std::io::Cursor::new(data))?;
+        assert!(matches!(
+            cf.validate_checksums(),
+            Err(DebianError::FileListInconsistent(msg))
+                if msg == "libzstd_1.4.8+dfsg.orig.tar.xz has size 1331997 in Files but 1331996 in Checksums-Sha1"
+        ));
+        assert!(cf.file_fetches(ChecksumType::Sha256).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_signatures() -> Result<()> {
+        use crate::{
+            repository::signing::{cleartext_signed_document, sign_cleartext},
+            signing_key::{generate_signing_key, signing_hash_algorithm},
+        };
+
+        let (key, public_key) = generate_signing_key("uploader@example.com", KeyType::Ed25519)?;
+        let text = "Format: 3.0 (native)\nSource: hello\nVersion: 1.0-1\n";
+        let signature = sign_cleartext(&key, String::new, signing_hash_algorithm(&key), text)?;
+        let signed = cleartext_signed_document(text, &[signature])?;
+
+        let cf = DebianSourceControlFile::from_armored_reader(std::io::Cursor::new(signed))?;
+        let keyring = Keyring::from_iter([public_key]);
+        let signers = cf.verify_signatures(&keyring, &TrustPolicy::strict())?;
+        assert_eq!(signers[0].user_ids, vec!["uploader@example.com"]);
+
+        assert!(matches!(
+            cf.verify_signatures(&Keyring::default(), &TrustPolicy::strict()),
+            Err(DebianError::PgpSignatureUntrusted)
+        ));
+        assert!(matches!(
+            cf.clone_no_signatures()
+                .verify_signatures(&keyring, &TrustPolicy::default()),
+            Err(DebianError::ReleaseNoSignatures)
+        ));
+
+        let cf = DebianSourceControlFile::from_armored_reader(std::io::Cursor::new(ZSTD_DSC))?;
+        assert!(matches!(
+            cf.verify_signatures(&keyring, &TrustPolicy::default()),
+            Err(DebianError::PgpSignatureUntrusted)
+        ));
+
         Ok(())
     }
 }
@@ -51,12 +51,14 @@ pub static RE_DEPENDENCY: Lazy<Regex> = Lazy::new(|| {
         (?:\[
             # Optional whitespace after [
             \s*
-            # Optional negation operator.
-            (?P<arch_negate>!)?
-            \s*
-            # The architecture. May have spaces to delimit multiple values.
+            # The architectures, each optionally negated with !. May have spaces to
+            # delimit multiple values.
             (?P<arch>[^\]]+)
         \])?
+        # Any amount of space after optional architecture restrictions.
+        \s*
+        # Any number of build profile restrictions, each within <..>.
+        (?P<profiles>(?:<[^>]*>\s*)*)
         ",
     )
     .unwrap()
@@ -90,13 +92,313 @@ pub struct DependencyVersionConstraint {
     pub version: PackageVersion,
 }
 
+impl DependencyVersionConstraint {
+    /// Whether a version satisfies this constraint.
+    pub fn is_satisfied_by(&self, version: &PackageVersion) -> bool {
+        matches!(
+            (version.cmp(&self.version), self.relationship),
+            (
+                Ordering::Equal,
+                VersionRelationship::ExactlyEqual
+                    | VersionRelationship::LaterOrEqual
+                    | VersionRelationship::EarlierOrEqual,
+            ) | (
+                Ordering::Less,
+                VersionRelationship::StrictlyEarlier | VersionRelationship::EarlierOrEqual,
+            ) | (
+                Ordering::Greater,
+                VersionRelationship::StrictlyLater | VersionRelationship::LaterOrEqual,
+            )
+        )
+    }
+}
+
+/// A bound of a [VersionConstraint].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VersionBound {
+    pub version: PackageVersion,
+    /// Whether [Self::version] itself is within the bound.
+    pub inclusive: bool,
+}
+
+/// A range of package versions, like `>= 1.2, << 2.0`.
+///
+/// Instances are the intersection of any number of relationship terms, which always forms
+/// a single interval with optional lower and upper bounds. The default instance accepts any
+/// version.
+///
+/// ```rust
+/// use debian_packaging::{dependency::VersionConstraint, package_version::PackageVersion};
+///
+/// let range = VersionConstraint::parse(">= 1.2, << 2.0").unwrap();
+/// assert!(range.is_satisfied_by(&PackageVersion::parse("1.5-1").unwrap()));
+/// assert!(range
+///     .intersect(&VersionConstraint::parse(">> 2.0").unwrap())
+///     .is_empty());
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct VersionConstraint {
+    lower: Option<VersionBound>,
+    upper: Option<VersionBound>,
+}
+
+impl VersionConstraint {
+    /// Parse comma separated relationship terms, like `>= 1.2, << 2.0`.
+    ///
+    /// An empty string accepts any version.
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut res = Self::default();
+
+        for term in s.split(',').map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            let (relationship, version) = [
+                ("<<", VersionRelationship::StrictlyEarlier),
+                ("<=", VersionRelationship::EarlierOrEqual),
+                (">=", VersionRelationship::LaterOrEqual),
+                (">>", VersionRelationship::StrictlyLater),
+                ("=", VersionRelationship::ExactlyEqual),
+            ]
+            .into_iter()
+            .find_map(|(op, relationship)| {
+                term.strip_prefix(op)
+                    .map(|version| (relationship, version.trim()))
+            })
+            .ok_or_else(|| DebianError::VersionConstraintParse(term.to_string()))?;
+
+            res = res.intersect(&Self::from(&DependencyVersionConstraint {
+                relationship,
+                version: PackageVersion::parse(version)?,
+            }));
+        }
+
+        Ok(res)
+    }
+
+    /// The lower bound, if any.
+    pub fn lower(&self) -> Option<&VersionBound> {
+        self.lower.as_ref()
+    }
+
+    /// The upper bound, if any.
+    pub fn upper(&self) -> Option<&VersionBound> {
+        self.upper.as_ref()
+    }
+
+    /// Whether any version is accepted.
+    pub fn is_any(&self) -> bool {
+        self.lower.is_none() && self.upper.is_none()
+    }
+
+    /// Whether a version is within this range.
+    pub fn is_satisfied_by(&self, version: &PackageVersion) -> bool {
+        self.lower
+            .as_ref()
+            .map_or(true, |bound| match version.cmp(&bound.version) {
+                Ordering::Greater => true,
+                Ordering::Equal => bound.inclusive,
+                Ordering::Less => false,
+            })
+            && self
+                .upper
+                .as_ref()
+                .map_or(true, |bound| match version.cmp(&bound.version) {
+                    Ordering::Less => true,
+                    Ordering::Equal => bound.inclusive,
+                    Ordering::Greater => false,
+                })
+    }
+
+    /// The range of versions satisfying both this and another range.
+    pub fn intersect(&self, other: &Self) -> Self {
+        // Of bounds with the same version, the exclusive one is tighter.
+        fn tighter(
+            a: &Option<VersionBound>,
+            b: &Option<VersionBound>,
+            prefer: Ordering,
+        ) -> Option<VersionBound> {
+            match (a, b) {
+                (Some(a), Some(b)) => match a.version.cmp(&b.version) {
+                    Ordering::Equal => Some(if a.inclusive { b.clone() } else { a.clone() }),
+                    ordering if ordering == prefer => Some(a.clone()),
+                    _ => Some(b.clone()),
+                },
+                (a, b) => a.clone().or_else(|| b.clone()),
+            }
+        }
+
+        Self {
+            lower: tighter(&self.lower, &other.lower, Ordering::Greater),
+            upper: tighter(&self.upper, &other.upper, Ordering::Less),
+        }
+    }
+
+    /// Whether no version can satisfy this range.
+    ///
+    /// Versions are treated as dense: there is always a version between two distinct
+    /// versions.
+    pub fn is_empty(&self) -> bool {
+        match (&self.lower, &self.upper) {
+            (Some(lower), Some(upper)) => match lower.version.cmp(&upper.version) {
+                Ordering::Greater => true,
+                Ordering::Equal => !(lower.inclusive && upper.inclusive),
+                Ordering::Less => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl From<&DependencyVersionConstraint> for VersionConstraint {
+    fn from(constraint: &DependencyVersionConstraint) -> Self {
+        let bound = |inclusive| {
+            Some(VersionBound {
+                version: constraint.version.clone(),
+                inclusive,
+            })
+        };
+
+        match constraint.relationship {
+            VersionRelationship::StrictlyEarlier => Self {
+                lower: None,
+                upper: bound(false),
+            },
+            VersionRelationship::EarlierOrEqual => Self {
+                lower: None,
+                upper: bound(true),
+            },
+            VersionRelationship::ExactlyEqual => Self {
+                lower: bound(true),
+                upper: bound(true),
+            },
+            VersionRelationship::LaterOrEqual => Self {
+                lower: bound(true),
+                upper: None,
+            },
+            VersionRelationship::StrictlyLater => Self {
+                lower: bound(false),
+                upper: None,
+            },
+        }
+    }
+}
+
+impl Display for VersionConstraint {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match (&self.lower, &self.upper) {
+            (Some(lower), Some(upper))
+                if lower.inclusive
+                    && upper.inclusive
+                    && lower.version.cmp(&upper.version).is_eq() =>
+            {
+                write!(f, "= {}", lower.version)
+            }
+            (lower, upper) => {
+                let terms = lower
+                    .iter()
+                    .map(|b| format!("{} {}", if b.inclusive { ">=" } else { ">>" }, b.version))
+                    .chain(upper.iter().map(|b| {
+                        format!("{} {}", if b.inclusive { "<=" } else { "<<" }, b.version)
+                    }))
+                    .collect::<Vec<_>>();
+
+                write!(f, "{}", terms.join(", "))
+            }
+        }
+    }
+}
+
+/// Split an architecture name into its operating system and CPU.
+///
+/// This approximates the architecture tuples of `dpkg-architecture`: names without an
+/// operating system prefix are Linux and ABI variants like `armhf` and `x32` share the CPU
+/// of their base architecture.
+fn architecture_os_cpu(architecture: &str) -> (&str, &str) {
+    let (os, cpu) = match architecture.rsplit_once('-') {
+        Some((os, cpu)) => (os, cpu),
+        None => ("linux", architecture),
+    };
+
+    let cpu = match cpu {
+        "armel" | "armhf" => "arm",
+        "x32" => "amd64",
+        cpu => cpu,
+    };
+
+    (os, cpu)
+}
+
+/// Whether an architecture matches an architecture name or wildcard.
+///
+/// Wildcards are `any`, `<os>-any` (e.g. `linux-any`), and `any-<cpu>` (e.g. `any-amd64`).
+pub fn architecture_matches(architecture: &str, pattern: &str) -> bool {
+    if pattern == "any" || pattern == architecture {
+        return true;
+    }
+
+    let (os, cpu) = architecture_os_cpu(architecture);
+
+    match pattern.split_once('-') {
+        Some(("any", "any")) => true,
+        Some(("any", pattern_cpu)) => pattern_cpu == cpu,
+        Some((pattern_os, "any")) => pattern_os == os,
+        _ => false,
+    }
+}
+
+/// A term in an architecture restriction, e.g. `!i386`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArchitectureRestriction {
+    /// Whether the term excludes the architecture.
+    pub negated: bool,
+    /// The architecture name or wildcard.
+    pub architecture: String,
+}
+
+impl Display for ArchitectureRestriction {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            if self.negated { "!" } else { "" },
+            self.architecture
+        )
+    }
+}
+
+/// A term in a build profile restriction, e.g. `!nocheck`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuildProfileTerm {
+    /// Whether the term is satisfied when the profile is *not* active.
+    pub negated: bool,
+    /// Name of the build profile.
+    pub profile: String,
+}
+
+impl Display for BuildProfileTerm {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", if self.negated { "!" } else { "" }, self.profile)
+    }
+}
+
+impl BuildProfileTerm {
+    /// Whether this term is satisfied given a set of active build profiles.
+    pub fn is_satisfied(&self, active_profiles: &[&str]) -> bool {
+        active_profiles.contains(&self.profile.as_str()) != self.negated
+    }
+}
+
 /// A dependency of a package.
 #[derive(Clone, Debug, PartialEq)]
 pub struct SingleDependency {
     /// Package the dependency is on.
     pub package: String,
     pub version_constraint: Option<DependencyVersionConstraint>,
-    pub architectures: Option<(bool, Vec<String>)>,
+    /// Architecture restrictions, e.g. `[amd64 !i386]`. Empty if there are no restrictions.
+    pub architectures: Vec<ArchitectureRestriction>,
+    /// Build profile restrictions, e.g. `<!nocheck> <stage1>`.
+    ///
+    /// Each inner list is a `<..>` group whose terms must all be satisfied. The dependency
+    /// applies if any group is satisfied. Empty if there are no restrictions.
+    pub build_profiles: Vec<Vec<BuildProfileTerm>>,
 }
 
 impl Display for SingleDependency {
@@ -105,8 +407,27 @@ impl Display for SingleDependency {
         if let Some(constraint) = &self.version_constraint {
             write!(f, " ({} {})", constraint.relationship, constraint.version)?;
         }
-        if let Some((negate, arch)) = &self.architectures {
-            write!(f, " [{}{}]", if *negate { "!" } else { "" }, arch.join(" "))?;
+        if !self.architectures.is_empty() {
+            write!(
+                f,
+                " [{}]",
+                self.architectures
+                    .iter()
+                    .map(|term| term.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )?;
+        }
+        for group in &self.build_profiles {
+            write!(
+                f,
+                " <{}>",
+                group
+                    .iter()
+                    .map(|term| term.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )?;
         }
 
         Ok(())
@@ -142,31 +463,90 @@ impl SingleDependency {
             _ => None,
         };
 
-        let architectures = match (caps.name("arch_negate"), caps.name("arch")) {
-            (Some(_), Some(arch)) => Some((
-                true,
+        let architectures = caps
+            .name("arch")
+            .map(|arch| {
                 arch.as_str()
                     .split_ascii_whitespace()
-                    .map(|x| x.to_string())
-                    .collect::<Vec<_>>(),
-            )),
-            (None, Some(arch)) => Some((
-                false,
-                arch.as_str()
-                    .split_ascii_whitespace()
-                    .map(|x| x.to_string())
-                    .collect::<Vec<_>>(),
-            )),
-            _ => None,
-        };
+                    .map(|term| match term.strip_prefix('!') {
+                        Some(architecture) => ArchitectureRestriction {
+                            negated: true,
+                            architecture: architecture.to_string(),
+                        },
+                        None => ArchitectureRestriction {
+                            negated: false,
+                            architecture: term.to_string(),
+                        },
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let build_profiles = caps
+            .name("profiles")
+            .map(|profiles| {
+                profiles
+                    .as_str()
+                    .split('>')
+                    .map(|group| group.trim().trim_start_matches('<'))
+                    .filter(|group| !group.trim().is_empty())
+                    .map(|group| {
+                        group
+                            .split_ascii_whitespace()
+                            .map(|term| match term.strip_prefix('!') {
+                                Some(profile) => BuildProfileTerm {
+                                    negated: true,
+                                    profile: profile.to_string(),
+                                },
+                                None => BuildProfileTerm {
+                                    negated: false,
+                                    profile: term.to_string(),
+                                },
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
 
         Ok(Self {
             package,
             version_constraint: dependency,
             architectures,
+            build_profiles,
         })
     }
 
+    /// Whether this dependency applies to an architecture.
+    ///
+    /// Dependencies without architecture restrictions always apply. Otherwise the
+    /// architecture must not match a negated term and, if there are terms that aren't
+    /// negated, must match one of them. Wildcards are evaluated by [architecture_matches()].
+    pub fn architecture_satisfied(&self, architecture: &str) -> bool {
+        let mut positive = self.architectures.iter().filter(|t| !t.negated).peekable();
+
+        let excluded = self
+            .architectures
+            .iter()
+            .filter(|t| t.negated)
+            .any(|t| architecture_matches(architecture, &t.architecture));
+
+        !excluded
+            && (positive.peek().is_none()
+                || positive.any(|t| architecture_matches(architecture, &t.architecture)))
+    }
+
+    /// Whether this dependency applies given a set of active build profiles.
+    ///
+    /// Dependencies without build profile restrictions always apply.
+    pub fn build_profiles_satisfied(&self, active_profiles: &[&str]) -> bool {
+        self.build_profiles.is_empty()
+            || self
+                .build_profiles
+                .iter()
+                .any(|group| group.iter().all(|term| term.is_satisfied(active_profiles)))
+    }
+
     /// Evaluate whether a package satisfies the requirements of this parsed expression.
     ///
     /// This takes as arguments the low-level package components needed for checking.
@@ -177,36 +557,16 @@ impl SingleDependency {
         architecture: &str,
     ) -> bool {
         if self.package == package {
-            if let Some((negate, arches)) = &self.architectures {
-                let contains = arches.iter().any(|x| x == architecture);
-
-                // Requesting an arch mismatch.
-                if (*negate && contains) || (!*negate && !contains) {
-                    return false;
-                }
+            // Requesting an arch mismatch.
+            if !self.architecture_satisfied(architecture) {
+                return false;
             }
 
             // Package and arch requirements match. Go on to version compare.
-            if let Some(constaint) = &self.version_constraint {
-                matches!(
-                    (version.cmp(&constaint.version), constaint.relationship),
-                    (
-                        Ordering::Equal,
-                        VersionRelationship::ExactlyEqual
-                            | VersionRelationship::LaterOrEqual
-                            | VersionRelationship::EarlierOrEqual,
-                    ) | (
-                        Ordering::Less,
-                        VersionRelationship::StrictlyEarlier | VersionRelationship::EarlierOrEqual,
-                    ) | (
-                        Ordering::Greater,
-                        VersionRelationship::StrictlyLater | VersionRelationship::LaterOrEqual,
-                    )
-                )
-            } else {
-                // No version constraint means yes.
-                true
-            }
+            // No version constraint means yes.
+            self.version_constraint
+                .as_ref()
+                .map_or(true, |constraint| constraint.is_satisfied_by(version))
         } else {
             false
         }
@@ -228,6 +588,19 @@ impl SingleDependency {
             if let (Some(wanted_constraint), Some(provides)) =
                 (&self.version_constraint.as_ref(), provides)
             {
+                // Policy only permits `=` in Provides, so the provided version must satisfy
+                // the wanted constraint.
+                if provides.relationship == VersionRelationship::ExactlyEqual {
+                    let virtual_dep = Self {
+                        package: package.to_string(),
+                        version_constraint: Some((*wanted_constraint).clone()),
+                        architectures: vec![],
+                        build_profiles: vec![],
+                    };
+
+                    return virtual_dep.package_satisfies(package, &provides.version, "");
+                }
+
                 matches!(
                     (
                         provides.version.cmp(&wanted_constraint.version),
@@ -311,7 +684,9 @@ impl DependencyVariants {
 }
 
 /// Represents an ordered list of dependencies, delimited by commas (`,`).
-#[derive(Clone, Debug, PartialEq)]
+///
+/// With the `serde` feature, instances serialize as their string representation.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct DependencyList {
     dependencies: Vec<DependencyVariants>,
 }
@@ -335,7 +710,7 @@ impl DependencyList {
     ///
     /// A dependency list is a comma-delimited list of expressions. Each expression is a
     /// `|` delimited list of expressions of the form
-    /// `package (version_relationship version) [arch]`.
+    /// `package (version_relationship version) [arch] <build profiles>`.
     pub fn parse(s: &str) -> Result<Self> {
         let mut els = vec![];
 
@@ -365,6 +740,11 @@ impl DependencyList {
             .any(|variants| variants.package_satisfies(package, version, arch))
     }
 
+    /// Append a requirement to this list.
+    pub fn push(&mut self, variants: DependencyVariants) {
+        self.dependencies.push(variants);
+    }
+
     /// Obtain the individual requirements constituting this list of dependencies.
     ///
     /// Each requirement is itself a set of expressions to match against. The length of
@@ -372,13 +752,49 @@ impl DependencyList {
     pub fn requirements(&self) -> impl Iterator<Item = &DependencyVariants> {
         self.dependencies.iter()
     }
+
+    /// Obtain the dependencies that apply to an architecture.
+    ///
+    /// Expressions whose architecture restrictions aren't satisfied are removed. Requirements
+    /// left without any expressions are removed entirely.
+    pub fn restrict_to_architecture(&self, architecture: &str) -> Self {
+        self.filter_expressions(|dep| dep.architecture_satisfied(architecture))
+    }
+
+    /// Retain expressions matching a predicate, removing requirements left empty.
+    fn filter_expressions(&self, predicate: impl Fn(&SingleDependency) -> bool) -> Self {
+        Self {
+            dependencies: self
+                .dependencies
+                .iter()
+                .map(|variants| {
+                    DependencyVariants(
+                        variants
+                            .iter()
+                            .filter(|dep| predicate(dep))
+                            .cloned()
+                            .collect(),
+                    )
+                })
+                .filter(|variants| !variants.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Obtain the dependencies that apply given a set of active build profiles.
+    ///
+    /// Expressions whose build profile restrictions aren't satisfied are removed. Requirements
+    /// left without any expressions are removed entirely.
+    pub fn restrict_to_build_profiles(&self, active_profiles: &[&str]) -> Self {
+        self.filter_expressions(|dep| dep.build_profiles_satisfied(active_profiles))
+    }
 }
 
 /// Describes the dependency relationship for a binary package.
 ///
 /// Variants correspond to fields in binary control file, as described at
 /// <https://www.debian.org/doc/debian-policy/ch-relationships.html#binary-dependencies-depends-recommends-suggests-enhances-pre-depends>.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum BinaryDependency {
     Depends,
     Recommends,
@@ -431,6 +847,156 @@ impl BinaryDependency {
     }
 }
 
+/// A binary package relationship field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RelationshipField {
+    PreDepends,
+    Depends,
+    Recommends,
+    Suggests,
+    Breaks,
+    Conflicts,
+    Provides,
+    Replaces,
+    Enhances,
+}
+
+impl RelationshipField {
+    /// All variants, in the order `dpkg-gencontrol` emits them.
+    pub const ALL: [Self; 9] = [
+        Self::PreDepends,
+        Self::Depends,
+        Self::Recommends,
+        Self::Suggests,
+        Self::Breaks,
+        Self::Conflicts,
+        Self::Provides,
+        Self::Replaces,
+        Self::Enhances,
+    ];
+
+    /// The name of the control field.
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            Self::PreDepends => "Pre-Depends",
+            Self::Depends => "Depends",
+            Self::Recommends => "Recommends",
+            Self::Suggests => "Suggests",
+            Self::Breaks => "Breaks",
+            Self::Conflicts => "Conflicts",
+            Self::Provides => "Provides",
+            Self::Replaces => "Replaces",
+            Self::Enhances => "Enhances",
+        }
+    }
+
+    /// Whether the field must be satisfied for the declaring package to be installed.
+    pub fn is_hard_dependency(&self) -> bool {
+        matches!(self, Self::PreDepends | Self::Depends)
+    }
+}
+
+impl Display for RelationshipField {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str(self.field_name())
+    }
+}
+
+impl From<BinaryDependency> for RelationshipField {
+    fn from(field: BinaryDependency) -> Self {
+        match field {
+            BinaryDependency::Depends => Self::Depends,
+            BinaryDependency::Recommends => Self::Recommends,
+            BinaryDependency::Suggests => Self::Suggests,
+            BinaryDependency::Enhances => Self::Enhances,
+            BinaryDependency::PreDepends => Self::PreDepends,
+        }
+    }
+}
+
+/// Parsed values of all binary package relationship fields.
+///
+/// Absent fields are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PackageRelations {
+    /// `Pre-Depends`.
+    pub pre_depends: Option<DependencyList>,
+    /// `Depends`.
+    pub depends: Option<DependencyList>,
+    /// `Recommends`.
+    pub recommends: Option<DependencyList>,
+    /// `Suggests`.
+    pub suggests: Option<DependencyList>,
+    /// `Breaks`.
+    pub breaks: Option<DependencyList>,
+    /// `Conflicts`.
+    pub conflicts: Option<DependencyList>,
+    /// `Provides`.
+    pub provides: Option<DependencyList>,
+    /// `Replaces`.
+    pub replaces: Option<DependencyList>,
+    /// `Enhances`.
+    pub enhances: Option<DependencyList>,
+}
+
+impl PackageRelations {
+    /// Construct an instance from a control paragraph.
+    ///
+    /// Parse failures are reported as [DebianError::RelationshipFieldParse] naming the
+    /// offending field.
+    pub fn from_paragraph(para: &ControlParagraph) -> Result<Self> {
+        let mut res = Self::default();
+
+        for field in RelationshipField::ALL {
+            if let Some(value) = para.field_str(field.field_name()) {
+                let value = DependencyList::parse(value).map_err(|e| {
+                    DebianError::RelationshipFieldParse(field.field_name(), Box::new(e))
+                })?;
+                *res.get_mut(field) = Some(value);
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// The value of a field.
+    pub fn get(&self, field: RelationshipField) -> Option<&DependencyList> {
+        match field {
+            RelationshipField::PreDepends => self.pre_depends.as_ref(),
+            RelationshipField::Depends => self.depends.as_ref(),
+            RelationshipField::Recommends => self.recommends.as_ref(),
+            RelationshipField::Suggests => self.suggests.as_ref(),
+            RelationshipField::Breaks => self.breaks.as_ref(),
+            RelationshipField::Conflicts => self.conflicts.as_ref(),
+            RelationshipField::Provides => self.provides.as_ref(),
+            RelationshipField::Replaces => self.replaces.as_ref(),
+            RelationshipField::Enhances => self.enhances.as_ref(),
+        }
+    }
+
+    /// A mutable reference to the value of a field.
+    pub fn get_mut(&mut self, field: RelationshipField) -> &mut Option<DependencyList> {
+        match field {
+            RelationshipField::PreDepends => &mut self.pre_depends,
+            RelationshipField::Depends => &mut self.depends,
+            RelationshipField::Recommends => &mut self.recommends,
+            RelationshipField::Suggests => &mut self.suggests,
+            RelationshipField::Breaks => &mut self.breaks,
+            RelationshipField::Conflicts => &mut self.conflicts,
+            RelationshipField::Provides => &mut self.provides,
+            RelationshipField::Replaces => &mut self.replaces,
+            RelationshipField::Enhances => &mut self.enhances,
+        }
+    }
+
+    /// Iterate over present fields and their values, in [RelationshipField::ALL] order.
+    pub fn iter(&self) -> impl Iterator<Item = (RelationshipField, &DependencyList)> + '_ {
+        RelationshipField::ALL
+            .into_iter()
+            .filter_map(|field| self.get(field).map(|value| (field, value)))
+    }
+}
+
 /// Holds all fields related to package dependency metadata.
 ///
 /// Instances of this type effectively describe the relationships between the package it
@@ -532,6 +1098,26 @@ impl PackageDependencyFields {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for DependencyList {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DependencyList {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -551,7 +1137,8 @@ mod test {
                     relationship: VersionRelationship::LaterOrEqual,
                     version: PackageVersion::parse("2.4").unwrap()
                 }),
-                architectures: None,
+                architectures: vec![],
+                build_profiles: vec![],
             }
         );
         assert_eq!(
@@ -559,7 +1146,8 @@ mod test {
             SingleDependency {
                 package: "libx11-6".into(),
                 version_constraint: None,
-                architectures: None,
+                architectures: vec![],
+                build_profiles: vec![],
             }
         );
 
@@ -571,7 +1159,11 @@ mod test {
             SingleDependency {
                 package: "libc".into(),
                 version_constraint: None,
-                architectures: Some((false, vec!["amd64".into()])),
+                architectures: vec![ArchitectureRestriction {
+                    negated: false,
+                    architecture: "amd64".into()
+                }],
+                build_profiles: vec![],
             }
         );
 
@@ -583,13 +1175,65 @@ mod test {
             SingleDependency {
                 package: "libc".into(),
                 version_constraint: None,
-                architectures: Some((true, vec!["amd64".into(), "i386".into()])),
+                architectures: vec![
+                    ArchitectureRestriction {
+                        negated: true,
+                        architecture: "amd64".into()
+                    },
+                    ArchitectureRestriction {
+                        negated: false,
+                        architecture: "i386".into()
+                    }
+                ],
+                build_profiles: vec![],
             }
         );
 
         Ok(())
     }
 
+    #[test]
+    fn build_profiles() -> Result<()> {
+        let dl = DependencyList::parse(
+            "debhelper-compat (= 13), python3 [amd64] <!nocheck>, \
+            doxygen <!nodoc> <stage1 cross> | docbook <!nodoc>",
+        )?;
+
+        let python = &dl.dependencies[1].0[0];
+        assert_eq!(python.architectures.len(), 1);
+        assert_eq!(
+            python.build_profiles,
+            vec![vec![BuildProfileTerm {
+                negated: true,
+                profile: "nocheck".into()
+            }]]
+        );
+        assert_eq!(dl.dependencies[2].0[0].build_profiles.len(), 2);
+        assert_eq!(
+            dl.to_string(),
+            "debhelper-compat (= 13), python3 [amd64] <!nocheck>, \
+            doxygen <!nodoc> <stage1 cross> | docbook <!nodoc>"
+        );
+
+        assert_eq!(
+            dl.restrict_to_build_profiles(&[]).to_string(),
+            dl.to_string()
+        );
+        assert_eq!(
+            dl.restrict_to_build_profiles(&["nocheck", "nodoc"])
+                .to_string(),
+            "debhelper-compat (= 13)"
+        );
+        assert_eq!(
+            dl.restrict_to_build_profiles(&["nodoc", "stage1", "cross"])
+                .to_string(),
+            "debhelper-compat (= 13), python3 [amd64] <!nocheck>, \
+            doxygen <!nodoc> <stage1 cross>"
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn satisfies_version_constraints() -> Result<()> {
         let dl = DependencyList::parse("libc (= 2.4)")?;
@@ -705,6 +1349,70 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn package_relations() -> Result<()> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Depends".into(), "libc6 (>= 2.4), foo | bar".into());
+        para.set_field_from_string("Provides".into(), "mail-transport-agent".into());
+
+        let relations = PackageRelations::from_paragraph(&para)?;
+        assert_eq!(
+            relations
+                .get(RelationshipField::Depends)
+                .unwrap()
+                .to_string(),
+            "libc6 (>= 2.4), foo | bar"
+        );
+        assert!(relations.breaks.is_none());
+        assert_eq!(
+            relations.iter().map(|(field, _)| field).collect::<Vec<_>>(),
+            vec![RelationshipField::Depends, RelationshipField::Provides]
+        );
+
+        para.set_field_from_string("Breaks".into(), "foo (>= a:1.0)".into());
+        let err = PackageRelations::from_paragraph(&para).unwrap_err();
+        assert!(matches!(
+            err,
+            DebianError::RelationshipFieldParse("Breaks", _)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn version_constraint() -> Result<()> {
+        let v = |s: &str| PackageVersion::parse(s).unwrap();
+
+        let range = VersionConstraint::parse(">= 1.2, << 2.0, >= 1.0")?;
+        assert_eq!(range.to_string(), ">= 1.2, << 2.0");
+        assert!(range.is_satisfied_by(&v("1.2")));
+        assert!(range.is_satisfied_by(&v("2.0~rc1")));
+        assert!(!range.is_satisfied_by(&v("2.0")));
+        assert!(!range.is_satisfied_by(&v("1.1")));
+
+        assert!(VersionConstraint::parse("")?.is_any());
+        assert!(!range.is_empty());
+        assert!(range
+            .intersect(&VersionConstraint::parse(">= 2.0")?)
+            .is_empty());
+        assert!(VersionConstraint::parse(">= 1.0, << 1.0")?.is_empty());
+        assert!(VersionConstraint::parse(">> 1.0, <= 1.0")?.is_empty());
+
+        let exact = VersionConstraint::parse(">= 1.0, <= 1.0-0")?;
+        assert!(!exact.is_empty());
+        assert_eq!(exact.to_string(), "= 1.0");
+        assert_eq!(
+            range
+                .intersect(&VersionConstraint::parse(">> 1.2, <= 3")?)
+                .to_string(),
+            ">> 1.2, << 2.0"
+        );
+
+        assert!(VersionConstraint::parse("~ 1.0").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn satisfies_architecture_constraints() -> Result<()> {
         let dl = DependencyList::parse("libc [amd64]")?;
@@ -748,12 +1456,25 @@ mod test {
             "x86"
         ));
 
+        // Negation applies to individual terms.
         let dl = DependencyList::parse("libc [!amd64 i386]")?;
         assert!(!dl.dependencies[0].package_satisfies(
             "libc",
             &PackageVersion::parse("2.4")?,
             "amd64"
         ));
+        assert!(dl.dependencies[0].package_satisfies(
+            "libc",
+            &PackageVersion::parse("2.3")?,
+            "i386"
+        ));
+        assert!(!dl.dependencies[0].package_satisfies(
+            "libc",
+            &PackageVersion::parse("2.3")?,
+            "arm64"
+        ));
+
+        let dl = DependencyList::parse("libc [!amd64 !i386]")?;
         assert!(!dl.dependencies[0].package_satisfies(
             "libc",
             &PackageVersion::parse("2.3")?,
@@ -767,4 +1488,29 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn architecture_wildcards() -> Result<()> {
+        assert!(architecture_matches("amd64", "any"));
+        assert!(architecture_matches("amd64", "linux-any"));
+        assert!(architecture_matches("kfreebsd-amd64", "any-amd64"));
+        assert!(!architecture_matches("kfreebsd-amd64", "linux-any"));
+        assert!(architecture_matches("armhf", "any-arm"));
+        assert!(!architecture_matches("arm64", "any-arm"));
+
+        let dl = DependencyList::parse(
+            "libc6-dev, libseccomp-dev [linux-any], libkvm-dev [kfreebsd-any], \
+            libnuma-dev [any-amd64 !x32] | libfoo",
+        )?;
+        assert_eq!(
+            dl.restrict_to_architecture("amd64").to_string(),
+            "libc6-dev, libseccomp-dev [linux-any], libnuma-dev [any-amd64 !x32] | libfoo"
+        );
+        assert_eq!(
+            dl.restrict_to_architecture("kfreebsd-i386").to_string(),
+            "libc6-dev, libkvm-dev [kfreebsd-any], libfoo"
+        );
+
+        Ok(())
+    }
 }
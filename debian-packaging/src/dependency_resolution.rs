@@ -7,12 +7,10 @@
 use {
     crate::{
         binary_package_control::BinaryPackageControlFile,
-        dependency::{
-            BinaryDependency, DependencyVersionConstraint, PackageDependencyFields,
-            SingleDependency,
-        },
+        dependency::{BinaryDependency, SingleDependency},
         error::Result,
         package_version::PackageVersion,
+        provides::ProvidesIndex,
     },
     std::collections::{HashMap, HashSet, VecDeque},
 };
@@ -212,23 +210,6 @@ struct BinaryPackageEntry<'file, 'data: 'file> {
     name: String,
     version: PackageVersion,
     arch: String,
-    deps: PackageDependencyFields,
-}
-
-#[derive(Clone, Debug)]
-struct VirtualBinaryPackageEntry<'file, 'data: 'file> {
-    file: &'file BinaryPackageControlFile<'data>,
-
-    /// The version of the virtual package being provided.
-    provided_version: Option<DependencyVersionConstraint>,
-
-    /// The package providing it.
-    #[allow(unused)]
-    name: String,
-
-    /// The version of the package providing it.
-    #[allow(unused)]
-    version: PackageVersion,
 }
 
 /// An entity for resolving dependencies between packages.
@@ -237,8 +218,8 @@ pub struct DependencyResolver<'file, 'data: 'file> {
     /// Map of package name to entries for each package
     binary_packages: HashMap<String, Vec<BinaryPackageEntry<'file, 'data>>>,
 
-    /// Map of provided package name to packages that provide.
-    virtual_binary_packages: HashMap<String, Vec<VirtualBinaryPackageEntry<'file, 'data>>>,
+    /// Index of provided package names to packages that provide.
+    provides: ProvidesIndex<'file, 'data>,
 }
 
 impl<'file, 'data: 'file> DependencyResolver<'file, 'data> {
@@ -258,26 +239,9 @@ impl<'file, 'data: 'file> DependencyResolver<'file, 'data> {
                 name: package.to_string(),
                 version: cf.version()?,
                 arch: cf.architecture()?.to_string(),
-                deps: cf.package_dependency_fields()?,
             };
 
-            if let Some(provides) = &entry.deps.provides {
-                for variants in provides.requirements() {
-                    for dep in variants.iter() {
-                        let virtual_entry = VirtualBinaryPackageEntry {
-                            file: cf,
-                            provided_version: dep.version_constraint.clone(),
-                            name: entry.name.clone(),
-                            version: entry.version.clone(),
-                        };
-
-                        self.virtual_binary_packages
-                            .entry(dep.package.clone())
-                            .or_default()
-                            .push(virtual_entry);
-                    }
-                }
-            }
+            self.provides.load_binary_packages(std::iter::once(cf))?;
 
             self.binary_packages
                 .entry(package.to_string())
@@ -321,16 +285,12 @@ impl<'file, 'data: 'file> DependencyResolver<'file, 'data> {
                     }
 
                     // Look for virtual packages with this name satisfying the constraints.
-                    if let Some(entries) = self.virtual_binary_packages.get(&alt.package) {
-                        for entry in entries {
-                            if alt.package_satisfies_virtual(
-                                &alt.package,
-                                entry.provided_version.as_ref(),
-                            ) {
-                                deps_res.candidates.push(entry.file);
-                            }
-                        }
-                    }
+                    deps_res.candidates.extend(
+                        self.provides
+                            .providers_satisfying(alt)
+                            .into_iter()
+                            .map(|provider| provider.package),
+                    );
 
                     variants_res.alternatives.push(deps_res);
                 }
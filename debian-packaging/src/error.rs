@@ -25,6 +25,19 @@ pub enum DebianError {
     #[error("HTTP error: {0:?}")]
     Reqwest(#[from] reqwest::Error),
 
+    #[cfg(feature = "shlibdeps")]
+    #[error("ELF parsing error: {0:?}")]
+    Elf(#[from] object::read::Error),
+
+    #[error("glob pattern error: {0:?}")]
+    GlobPattern(#[from] glob::PatternError),
+
+    #[error("regular expression error: {0:?}")]
+    Regex(#[from] regex::Error),
+
+    #[error("JSON error: {0:?}")]
+    SerdeJson(#[from] serde_json::Error),
+
     #[error("I/O error: {0:?}")]
     Io(#[from] std::io::Error),
 
@@ -37,6 +50,9 @@ pub enum DebianError {
     #[error("control file parse error: {0}")]
     ControlParseError(String),
 
+    #[error("{0}: {1}")]
+    ControlParseAt(Box<crate::control::ParseLocation>, Box<DebianError>),
+
     #[error("Control file lacks a paragraph")]
     ControlFileNoParagraph,
 
@@ -52,21 +68,189 @@ pub enum DebianError {
     #[error("control field {0} can not be parsed as an integer: {0:?}")]
     ControlFieldIntParse(String, std::num::ParseIntError),
 
+    #[error("control field {0} has value {1:?}; expected yes or no")]
+    ControlFieldBoolParse(String, String),
+
+    #[error("field or line exceeds parse limit of {0} bytes")]
+    ParseFieldTooLong(usize),
+
+    #[error("paragraph exceeds parse limit of {0} bytes")]
+    ParseParagraphTooLarge(usize),
+
+    #[error("input exceeds parse limit of {0} entries")]
+    ParseTooManyEntries(usize),
+
+    #[error("input exceeds parse limit of {0} bytes")]
+    ParseInputTooLarge(u64),
+
     #[error("failed to parse control field timestamp")]
     ControlFieldTimestampParse,
 
+    #[error("invalid binary package name: {0}")]
+    BinaryPackageNameInvalid(String),
+
+    #[error("invalid architecture name: {0}")]
+    ArchitectureInvalid(String),
+
+    #[error("invalid Multi-Arch value: {0}")]
+    MultiArchInvalid(String),
+
+    #[error("Multi-Arch: {0} {1}")]
+    MultiArchIncompatible(String, &'static str),
+
     #[error("missing field {0} in Package-List entry")]
     ControlPackageListMissingField(&'static str),
 
+    #[error("refusing to remove {1} package {0}")]
+    RemovalRefused(String, &'static str),
+
+    #[error("refusing to remove {0}: {1} package {2} depends on it")]
+    RemovalBreaksProtected(String, &'static str, String),
+
+    #[error("failed to parse contact: {0}")]
+    ContactParse(String),
+
+    #[error("invalid {0} field: {1}")]
+    VcsFieldInvalid(&'static str, String),
+
+    #[error("invalid package list cache: {0}")]
+    PackageListCacheInvalid(&'static str),
+
+    #[error("unsupported package list cache format version {0}")]
+    PackageListCacheVersion(u32),
+
+    #[error("content store objects are addressed by SHA256 digests; got {0} digest")]
+    ContentStoreDigestUnsupported(&'static str),
+
+    #[error("content store objects are addressed by 32 byte SHA256 digests; got {0} bytes")]
+    ContentStoreDigestLength(usize),
+
+    #[error("content store object digest mismatch: expected {0}, got {1}")]
+    ContentStoreDigestMismatch(String, String),
+
+    #[error("invalid keybox: {0}")]
+    KeyboxInvalid(&'static str),
+
+    #[error("no valid PGP signature from a trusted key")]
+    PgpSignatureUntrusted,
+
+    #[error("PGP signature from key {0} rejected: {1}")]
+    PgpSignatureRejected(String, String),
+
+    #[error("no acceptable PGP signature from required key {0}")]
+    PgpSignatureRequiredKeyMissing(String),
+
+    #[error("acceptable PGP signatures from {0} keys; {1} required")]
+    PgpSignatureCountInsufficient(usize, usize),
+
+    #[error("no PGP key with fingerprint {0} found")]
+    PgpKeyNotFound(String),
+
+    #[error("unsupported keyserver URL: {0}")]
+    PgpKeyserverInvalid(String),
+
+    #[error("invalid email address for WKD lookup: {0}")]
+    WkdEmailInvalid(String),
+
     #[error("expected 1 control paragraph in Debian source control file; got {0}")]
     DebianSourceControlFileParagraphMismatch(usize),
 
+    #[error("expected 1 control paragraph in Debian changes file; got {0}")]
+    DebianChangesFileParagraphMismatch(usize),
+
+    #[error("changelog parse error at line {0}: {1}")]
+    ChangelogParse(usize, String),
+
+    #[error("invalid DEP-5 copyright file: {0}")]
+    CopyrightParse(String),
+
+    #[error("inconsistent files in Debian changes file: {0}")]
+    DebianChangesFileInconsistent(String),
+
+    #[error("invalid filename in Debian changes file: {0:?}")]
+    DebianChangesFileInvalidFilename(String),
+
+    #[error("inconsistent Files and Checksums-* fields: {0}")]
+    FileListInconsistent(String),
+
     #[error("unknown entry in binary package archive: {0}")]
     DebUnknownBinaryPackageEntry(String),
 
+    #[error("not a deb archive")]
+    DebNotArchive,
+
+    #[error("old-format deb (version {0}) is not supported")]
+    DebOldFormat(String),
+
+    #[error("unsupported deb format version: {0}")]
+    DebUnsupportedVersion(String),
+
+    #[error("encrypted member in deb archive is not supported: {0}")]
+    DebEncryptedMember(String),
+
+    #[error("deb archive lacks required member: {0}")]
+    DebMissingMember(&'static str),
+
     #[error("unknown compression in deb archive file: {0}")]
     DebUnknownCompression(String),
 
+    #[error("illegal path in deb data archive: {0}")]
+    DebDataTarIllegalPath(String),
+
+    #[error("maintainer script {0} {1}")]
+    DebMaintainerScriptInvalid(String, &'static str),
+
+    #[error("conffile {0} {1}")]
+    DebConffileInvalid(String, &'static str),
+
+    #[error("invalid {0} control file at line {1}: {2}")]
+    DebControlMemberInvalid(&'static str, usize, &'static str),
+
+    #[error("unsupported file type in staging directory: {0}")]
+    DebStagingUnsupportedFile(String),
+
+    #[error("invalid ar member name: {0}")]
+    ArMemberNameInvalid(String),
+
+    #[error("ar member name too long for .deb file: {0}")]
+    ArMemberNameTooLong(String),
+
+    #[error("package has no {0} signature member")]
+    DebSignatureMissing(&'static str),
+
+    #[error("package {0} signature not made by a trusted key")]
+    DebSignatureUntrusted(&'static str),
+
+    #[error("source package is missing {0}")]
+    SourcePackageMissingFile(&'static str),
+
+    #[error("unsupported source package format: {0}")]
+    SourcePackageFormatUnsupported(String),
+
+    #[error("3.0 (quilt) source packages require a Debian revision in version: {0}")]
+    SourcePackageVersionInvalid(String),
+
+    #[error("unsupported compression for source package tarball: {0}")]
+    SourcePackageCompressionUnsupported(String),
+
+    #[error("size or digest mismatch on source package file: {0}")]
+    SourcePackageFileIntegrity(String),
+
+    #[error("unsupported entry in source package tarball: {0}")]
+    SourcePackageUnsupportedEntry(String),
+
+    #[error("invalid file name in source package: {0}")]
+    SourcePackageFilenameInvalid(String),
+
+    #[error("invalid quilt series entry: {0}")]
+    QuiltSeriesInvalid(String),
+
+    #[error("invalid patch {0}: {1}")]
+    QuiltPatchInvalid(String, String),
+
+    #[error("patch {0} does not apply to {1}")]
+    QuiltPatchFailed(String, String),
+
     #[error("do not know how to construct repository reader from URL: {0}")]
     RepositoryReaderUnrecognizedUrl(String),
 
@@ -94,6 +278,18 @@ pub enum DebianError {
     #[error("repository I/O error on path {0}: {1:?}")]
     RepositoryIoPath(String, std::io::Error),
 
+    #[error("repository writer does not support operation: {0}")]
+    RepositoryWriterOperationUnsupported(&'static str),
+
+    #[error("repository copy of {0} bytes exceeds size budget of {1} bytes")]
+    RepositoryCopySizeBudgetExceeded(u64, u64),
+
+    #[error("rewriting release files during copy requires a signing key")]
+    RepositoryCopyRewriteWithoutSigningKey,
+
+    #[error("filtering binary packages during copy requires a signing key")]
+    RepositoryCopyFilterWithoutSigningKey,
+
     #[error("attempting to add package to undefined component: {0}")]
     RepositoryBuildUnknownComponent(String),
 
@@ -103,6 +299,36 @@ pub enum DebianError {
     #[error("pool layout cannot be changed after content is indexed")]
     RepositoryBuildPoolLayoutImmutable,
 
+    #[error("repository publish vetoed: {0}")]
+    RepositoryPublishVetoed(String),
+
+    #[error("source distribution release file is not PGP signed: {0}")]
+    RepositoryCopySourceUnsigned(String),
+
+    #[error("source distribution release file not signed by a trusted key: {0}")]
+    RepositoryCopySourceUntrusted(String),
+
+    #[error("source distribution Release file differs from its InRelease file: {0}")]
+    RepositoryCopyReleaseMismatch(String),
+
+    #[error("illegal path in installer manifest {0}: {1}")]
+    RepositoryInstallerManifestIllegalPath(String, String),
+
+    #[error("changes file is not PGP signed: {0}")]
+    IncomingUnsigned(String),
+
+    #[error("changes file not signed by a trusted key: {0}")]
+    IncomingUntrusted(String),
+
+    #[error("illegal filename in changes file: {0}")]
+    IncomingIllegalFilename(String),
+
+    #[error("file referenced by upload not listed in changes file: {0}")]
+    IncomingFileMissing(String),
+
+    #[error("size or digest mismatch on uploaded file: {0}")]
+    IncomingFileIntegrity(String),
+
     #[error(".deb not available: {0}")]
     RepositoryBuildDebNotAvailable(&'static str),
 
@@ -130,12 +356,33 @@ pub enum DebianError {
     #[error("No PGP signatures found from the specified key")]
     ReleaseNoSignaturesByKey,
 
+    #[error("malformed PGP cleartext signed document: {0}")]
+    CleartextMalformed(String),
+
     #[error("indices files not found in Release file")]
     ReleaseNoIndicesFiles,
 
+    #[error("no shared library dependency information for {0}")]
+    ShlibdepsUnresolved(String),
+
+    #[error("invalid apt preferences: {0}")]
+    AptPreferencesInvalid(String),
+
+    #[error("unsatisfiable dependencies: {0}")]
+    DependencyUnsatisfiable(Box<crate::resolver::Unsatisfiable>),
+
+    #[error("unsatisfiable dependencies: {0}")]
+    DependencyUnsatisfiableCore(Box<crate::resolver::UnsatisfiableCore>),
+
     #[error("failed to parse dependency expression: {0}")]
     DependencyParse(String),
 
+    #[error("failed to parse {0} field: {1}")]
+    RelationshipFieldParse(&'static str, Box<DebianError>),
+
+    #[error("failed to parse version constraint: {0}")]
+    VersionConstraintParse(String),
+
     #[error("unknown binary dependency field: {0}")]
     UnknownBinaryDependencyField(String),
 
@@ -148,6 +395,9 @@ pub enum DebianError {
     #[error("debian_revision component has illegal character: {0}")]
     DebianRevisionIllegalChar(String),
 
+    #[error("version number too large to increment: {0}")]
+    VersionNumberOverflow(String),
+
     #[error("unknown S3 region: {0}")]
     S3BadRegion(String),
 
@@ -160,3 +410,37 @@ pub enum DebianError {
 
 /// Result wrapper for this crate.
 pub type Result<T> = std::result::Result<T, DebianError>;
+
+impl DebianError {
+    /// The location of a control file parse error, if known.
+    pub fn parse_location(&self) -> Option<&crate::control::ParseLocation> {
+        match self {
+            Self::ControlParseAt(location, _) => Some(location),
+            _ => None,
+        }
+    }
+
+    /// The error without any [DebianError::ControlParseAt] location wrapper.
+    pub fn unlocated(&self) -> &Self {
+        match self {
+            Self::ControlParseAt(_, inner) => inner.unlocated(),
+            _ => self,
+        }
+    }
+
+    /// Describe the input a located parse error occurred in, if not already described.
+    ///
+    /// Other errors are returned unchanged.
+    pub fn with_parse_source(self, source: impl ToString) -> Self {
+        match self {
+            Self::ControlParseAt(mut location, inner) => {
+                if location.source.is_none() {
+                    location.source = Some(source.to_string());
+                }
+
+                Self::ControlParseAt(location, inner)
+            }
+            e => e,
+        }
+    }
+}
@@ -0,0 +1,253 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! File lists in `Files` and `Checksums-*` fields.
+
+`.dsc` files, `.changes` files and `Sources` entries list the files they reference in
+multiple line fields, one field per digest flavor. Each line is of form
+`<digest> <size> <filename>`. The `Files` field of `.changes` files also has the archive
+section and priority of each file: `<digest> <size> <section> <priority> <filename>`.
+
+[FileListEntry] represents one line. [parse_file_list()] parses the lines of a field and
+[file_list_field()] produces a field from entries. [validate_file_lists()] checks that
+the fields of a paragraph agree with each other.
+
+See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#s-f-files>.
+*/
+
+use {
+    crate::{
+        control::{ControlField, ControlParagraph},
+        error::{DebianError, Result},
+        io::ContentDigest,
+        repository::release::ChecksumType,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt::{Display, Formatter},
+        str::FromStr,
+    },
+};
+
+/// A single file as described by a `Files` or `Checksums-*` field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileListEntry<'a> {
+    /// The filename.
+    pub filename: &'a str,
+
+    /// The content digest of this file.
+    pub digest: ContentDigest,
+
+    /// The size in bytes of the file.
+    pub size: u64,
+
+    /// The archive section of the file.
+    ///
+    /// Only present on entries from the `Files` field of `.changes` files.
+    pub section: Option<&'a str>,
+
+    /// The priority of the file.
+    ///
+    /// Only present on entries from the `Files` field of `.changes` files.
+    pub priority: Option<&'a str>,
+}
+
+impl<'a> FileListEntry<'a> {
+    /// Parse a line of a file list.
+    ///
+    /// `sectioned` says whether the line has section and priority columns.
+    pub fn parse(checksum: ChecksumType, line: &'a str, sectioned: bool) -> Result<Self> {
+        let parts = line.split_ascii_whitespace().collect::<Vec<_>>();
+
+        let (digest, size, section, priority, filename) = match (sectioned, parts.as_slice()) {
+            (true, [digest, size, section, priority, filename]) => {
+                (digest, size, Some(*section), Some(*priority), filename)
+            }
+            (false, [digest, size, filename]) => (digest, size, None, None, filename),
+            (_, []) => return Err(DebianError::ReleaseMissingDigest),
+            (_, [_]) => return Err(DebianError::ReleaseMissingSize),
+            (true, [_, _] | [_, _, _] | [_, _, _, _]) | (false, [_, _]) => {
+                return Err(DebianError::ReleaseMissingPath)
+            }
+            _ => return Err(DebianError::ReleasePathWithSpaces(line.to_string())),
+        };
+
+        Ok(Self {
+            filename,
+            digest: ContentDigest::from_hex_digest(checksum, digest)?,
+            size: u64::from_str(size)?,
+            section,
+            priority,
+        })
+    }
+}
+
+impl<'a> Display for FileListEntry<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ", self.digest.digest_hex(), self.size)?;
+
+        if let (Some(section), Some(priority)) = (self.section, self.priority) {
+            write!(f, "{} {} ", section, priority)?;
+        }
+
+        f.write_str(self.filename)
+    }
+}
+
+/// Parse the lines of a `Files` or `Checksums-*` field.
+///
+/// Empty lines are ignored.
+pub fn parse_file_list<'a>(
+    checksum: ChecksumType,
+    sectioned: bool,
+    lines: impl Iterator<Item = &'a str> + 'a,
+) -> impl Iterator<Item = Result<FileListEntry<'a>>> + 'a {
+    lines
+        .filter(|line| !line.trim().is_empty())
+        .map(move |line| FileListEntry::parse(checksum, line, sectioned))
+}
+
+/// Produce the `Files` or `Checksums-*` field listing the given entries.
+///
+/// The field name is derived from `checksum`. Entries are written in order on their own
+/// lines, following an empty first line.
+pub fn file_list_field<'e>(
+    checksum: ChecksumType,
+    entries: impl IntoIterator<Item = FileListEntry<'e>>,
+) -> ControlField<'static> {
+    ControlField::from_lines(
+        checksum.source_field_name().into(),
+        std::iter::once("".to_string()).chain(entries.into_iter().map(|entry| entry.to_string())),
+    )
+}
+
+/// Validate that the `Files` and `Checksums-*` fields of a paragraph describe the same files.
+///
+/// `Files` is required. Every filename must occur once per field and every present
+/// `Checksums-*` field must list the same filenames with the same sizes as `Files`.
+/// `sectioned` says whether `Files` has section and priority columns, as in `.changes`
+/// files.
+///
+/// Mismatches are reported as [DebianError::FileListInconsistent], naming the file and
+/// fields involved. Digests can't be compared across flavors and aren't checked.
+pub fn validate_file_lists(paragraph: &ControlParagraph, sectioned: bool) -> Result<()> {
+    let inconsistent = |msg: String| Err(DebianError::FileListInconsistent(msg));
+
+    let lines = paragraph
+        .iter_field_lines("Files")
+        .ok_or_else(|| DebianError::ControlRequiredFieldMissing("Files".to_string()))?;
+
+    let mut expected = BTreeMap::new();
+
+    for entry in parse_file_list(ChecksumType::Md5, sectioned, lines) {
+        let entry = entry?;
+
+        if expected.insert(entry.filename, entry.size).is_some() {
+            return inconsistent(format!("{} listed multiple times in Files", entry.filename));
+        }
+    }
+
+    for checksum in ChecksumType::all().filter(|c| *c != ChecksumType::Md5) {
+        let field = checksum.source_field_name();
+
+        let lines = if let Some(lines) = paragraph.iter_field_lines(field) {
+            lines
+        } else {
+            continue;
+        };
+
+        let mut seen = BTreeSet::new();
+
+        for entry in parse_file_list(checksum, false, lines) {
+            let entry = entry?;
+
+            if !seen.insert(entry.filename) {
+                return inconsistent(format!(
+                    "{} listed multiple times in {}",
+                    entry.filename, field
+                ));
+            }
+
+            match expected.get(entry.filename) {
+                Some(size) if *size == entry.size => {}
+                Some(size) => {
+                    return inconsistent(format!(
+                        "{} has size {} in Files but {} in {}",
+                        entry.filename, size, entry.size, field
+                    ));
+                }
+                None => {
+                    return inconsistent(format!(
+                        "{} listed in {} but not in Files",
+                        entry.filename, field
+                    ));
+                }
+            }
+        }
+
+        if let Some(filename) = expected.keys().find(|k| !seen.contains(*k)) {
+            return inconsistent(format!("{} listed in Files but not in {}", filename, field));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let field = file_list_field(
+            ChecksumType::Md5,
+            [
+                FileListEntry {
+                    filename: "hello_1.0.dsc",
+                    digest: ContentDigest::md5_hex("5d41402abc4b2a76b9719d911017c592")?,
+                    size: 5,
+                    section: Some("devel"),
+                    priority: Some("optional"),
+                },
+                FileListEntry {
+                    filename: "hello_1.0.tar.xz",
+                    digest: ContentDigest::md5_hex("7d793037a0760186574b0282f2f435e7")?,
+                    size: 42,
+                    section: None,
+                    priority: None,
+                },
+            ],
+        );
+        assert_eq!(
+            field.to_string(),
+            "Files: \n 5d41402abc4b2a76b9719d911017c592 5 devel optional hello_1.0.dsc\n \
+             7d793037a0760186574b0282f2f435e7 42 hello_1.0.tar.xz\n"
+        );
+
+        let entries = parse_file_list(ChecksumType::Md5, true, field.iter_lines())
+            .take(1)
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries[0].section, Some("devel"));
+        assert_eq!(entries[0].filename, "hello_1.0.dsc");
+
+        let entries =
+            parse_file_list(ChecksumType::Md5, false, field.iter_lines()).collect::<Vec<_>>();
+        assert!(matches!(
+            entries[0],
+            Err(DebianError::ReleasePathWithSpaces(_))
+        ));
+        assert_eq!(entries[1].as_ref().unwrap().size, 42);
+
+        assert!(matches!(
+            FileListEntry::parse(ChecksumType::Md5, "abcd 5 x", true),
+            Err(DebianError::ReleaseMissingPath)
+        ));
+        assert!(matches!(
+            FileListEntry::parse(ChecksumType::Md5, "abcd", false),
+            Err(DebianError::ReleaseMissingSize)
+        ));
+
+        Ok(())
+    }
+}
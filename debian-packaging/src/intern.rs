@@ -0,0 +1,244 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Static storage of well-known control field strings.
+
+Parsing a full `Packages` file yields millions of small strings, most of them repeats: every
+paragraph has a `Package` field name, and values like `Architecture: amd64` or
+`Priority: optional` occur in nearly every paragraph.
+
+This module holds static copies of well-known field names and of common values of fields
+with a small set of legal values, such as `Architecture`, `Section`, `Priority` and
+`Multi-Arch`. [crate::control::ControlParagraph::into_interned()] replaces such strings with
+references to these copies, so they occupy no memory per package. Other strings are copied
+as with [crate::control::ControlParagraph::into_owned()].
+
+Field names are only interned if their case matches, so interning never changes content.
+*/
+
+use {once_cell::sync::Lazy, std::collections::HashSet};
+
+/// Field names with static copies.
+pub const FIELD_NAMES: &[&str] = &[
+    "Architecture",
+    "Auto-Built-Package",
+    "Binary",
+    "Breaks",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Build-Essential",
+    "Build-Ids",
+    "Built-Using",
+    "Checksums-Sha1",
+    "Checksums-Sha256",
+    "Checksums-Sha512",
+    "Conflicts",
+    "Depends",
+    "Description",
+    "Description-md5",
+    "Directory",
+    "Enhances",
+    "Essential",
+    "Filename",
+    "Files",
+    "Format",
+    "Homepage",
+    "Important",
+    "Installed-Size",
+    "MD5sum",
+    "Maintainer",
+    "Multi-Arch",
+    "Original-Maintainer",
+    "Package",
+    "Package-List",
+    "Pre-Depends",
+    "Priority",
+    "Protected",
+    "Provides",
+    "Recommends",
+    "Replaces",
+    "Rules-Requires-Root",
+    "SHA1",
+    "SHA256",
+    "SHA512",
+    "Section",
+    "Size",
+    "Source",
+    "Standards-Version",
+    "Static-Built-Using",
+    "Suggests",
+    "Tag",
+    "Task",
+    "Testsuite",
+    "Testsuite-Triggers",
+    "Uploaders",
+    "Vcs-Browser",
+    "Vcs-Git",
+    "Version",
+];
+
+/// `Architecture` values with static copies.
+pub const ARCHITECTURES: &[&str] = &[
+    "all",
+    "alpha",
+    "amd64",
+    "any",
+    "arm64",
+    "armel",
+    "armhf",
+    "hppa",
+    "hurd-amd64",
+    "hurd-i386",
+    "i386",
+    "ia64",
+    "loong64",
+    "m68k",
+    "mips64el",
+    "mipsel",
+    "powerpc",
+    "ppc64",
+    "ppc64el",
+    "riscv64",
+    "s390x",
+    "sh4",
+    "sparc64",
+    "source",
+    "x32",
+];
+
+/// Archive areas that may prefix a `Section` value, as in `contrib/libs`.
+pub const AREAS: &[&str] = &[
+    "contrib",
+    "multiverse",
+    "non-free",
+    "non-free-firmware",
+    "restricted",
+    "universe",
+];
+
+/// `Section` values with static copies, without an area prefix.
+///
+/// See <https://www.debian.org/doc/debian-policy/ch-archive.html#sections>.
+pub const SECTIONS: &[&str] = &[
+    "admin",
+    "cli-mono",
+    "comm",
+    "database",
+    "debian-installer",
+    "debug",
+    "devel",
+    "doc",
+    "editors",
+    "education",
+    "electronics",
+    "embedded",
+    "fonts",
+    "games",
+    "gnome",
+    "gnu-r",
+    "gnustep",
+    "graphics",
+    "hamradio",
+    "haskell",
+    "httpd",
+    "interpreters",
+    "introspection",
+    "java",
+    "javascript",
+    "kde",
+    "kernel",
+    "libdevel",
+    "libs",
+    "lisp",
+    "localization",
+    "mail",
+    "math",
+    "metapackages",
+    "misc",
+    "net",
+    "news",
+    "ocaml",
+    "oldlibs",
+    "otherosfs",
+    "perl",
+    "php",
+    "python",
+    "ruby",
+    "rust",
+    "science",
+    "shells",
+    "sound",
+    "tasks",
+    "tex",
+    "text",
+    "utils",
+    "vcs",
+    "video",
+    "web",
+    "x11",
+    "xfce",
+    "zope",
+];
+
+/// `Priority` values with static copies.
+pub const PRIORITIES: &[&str] = &["extra", "important", "optional", "required", "standard"];
+
+/// `Multi-Arch` values with static copies.
+pub const MULTI_ARCH_VALUES: &[&str] = &["allowed", "foreign", "no", "same"];
+
+/// Values of `yes`/`no` fields with static copies.
+const YES_NO: &[&str] = &["no", "yes"];
+
+static NAMES: Lazy<HashSet<&'static str>> = Lazy::new(|| FIELD_NAMES.iter().copied().collect());
+
+/// `Section` values with an area prefix.
+static AREA_SECTIONS: Lazy<Vec<String>> = Lazy::new(|| {
+    AREAS
+        .iter()
+        .flat_map(|area| {
+            SECTIONS
+                .iter()
+                .map(move |section| format!("{}/{}", area, section))
+        })
+        .collect()
+});
+
+/// Values by field name.
+static VALUES: Lazy<Vec<(&'static str, HashSet<&'static str>)>> = Lazy::new(|| {
+    let sections = SECTIONS
+        .iter()
+        .chain(AREAS)
+        .copied()
+        .chain(AREA_SECTIONS.iter().map(|s| s.as_str()))
+        .collect();
+
+    vec![
+        ("Architecture", ARCHITECTURES.iter().copied().collect()),
+        ("Build-Essential", YES_NO.iter().copied().collect()),
+        ("Essential", YES_NO.iter().copied().collect()),
+        ("Important", YES_NO.iter().copied().collect()),
+        ("Multi-Arch", MULTI_ARCH_VALUES.iter().copied().collect()),
+        ("Priority", PRIORITIES.iter().copied().collect()),
+        ("Protected", YES_NO.iter().copied().collect()),
+        ("Section", sections),
+    ]
+});
+
+/// Obtain the static copy of a field name, if there is one.
+pub fn field_name(name: &str) -> Option<&'static str> {
+    NAMES.get(name).copied()
+}
+
+/// Obtain the static copy of a field's value, if there is one.
+pub fn field_value(name: &str, value: &str) -> Option<&'static str> {
+    VALUES
+        .iter()
+        .find(|(field, _)| field.eq_ignore_ascii_case(name))
+        .and_then(|(_, values)| values.get(value))
+        .copied()
+}
@@ -9,24 +9,30 @@ use {
         error::{DebianError, Result},
         repository::release::ChecksumType,
     },
-    async_compression::futures::bufread::{
-        BzDecoder, BzEncoder, GzipDecoder, GzipEncoder, LzmaDecoder, LzmaEncoder, XzDecoder,
-        XzEncoder,
+    async_compression::futures::{
+        bufread::{
+            BzDecoder, BzEncoder, GzipDecoder, GzipEncoder, LzmaDecoder, LzmaEncoder, XzDecoder,
+            XzEncoder, ZstdDecoder, ZstdEncoder,
+        },
+        write,
     },
     async_trait::async_trait,
-    futures::{AsyncBufRead, AsyncRead, AsyncWrite},
+    futures::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWrite},
     pgp::crypto::hash::Hasher,
-    pgp_cleartext::CleartextHasher,
     pin_project::pin_project,
     std::{
         collections::HashMap,
         fmt::Formatter,
+        ops::Range,
+        path::PathBuf,
         pin::Pin,
         task::{Context, Poll},
     },
 };
 
 /// Represents a content digest.
+///
+/// With the `serde` feature, instances serialize as a `checksum` type and hex `digest`.
 #[derive(Clone, Eq, PartialEq, PartialOrd)]
 pub enum ContentDigest {
     /// An MD5 digest.
@@ -35,19 +41,35 @@ pub enum ContentDigest {
     Sha1(Vec<u8>),
     /// A SHA-256 digest.
     Sha256(Vec<u8>),
+    /// A SHA-512 digest.
+    Sha512(Vec<u8>),
 }
 
 impl std::fmt::Debug for ContentDigest {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Md5(data) => write!(f, "Md5({})", hex::encode(data)),
-            Self::Sha1(data) => write!(f, "Sha1({})", hex::encode(data)),
-            Self::Sha256(data) => write!(f, "Sha256({})", hex::encode(data)),
-        }
+        write!(f, "{:?}({})", self.checksum_type(), self.digest_hex())
     }
 }
 
 impl ContentDigest {
+    /// Create a new instance from digest bytes of a given [ChecksumType].
+    pub fn new(checksum: ChecksumType, digest: Vec<u8>) -> Self {
+        match checksum {
+            ChecksumType::Md5 => Self::Md5(digest),
+            ChecksumType::Sha1 => Self::Sha1(digest),
+            ChecksumType::Sha256 => Self::Sha256(digest),
+            ChecksumType::Sha512 => Self::Sha512(digest),
+        }
+    }
+
+    /// Compute the digest of data using a given [ChecksumType].
+    pub fn compute(checksum: ChecksumType, data: &[u8]) -> Self {
+        let mut h = checksum.new_hasher();
+        h.update(data);
+
+        Self::new(checksum, h.finish())
+    }
+
     /// Create a new MD5 instance by parsing a hex digest.
     pub fn md5_hex(digest: &str) -> Result<Self> {
         Self::from_hex_digest(ChecksumType::Md5, digest)
@@ -63,25 +85,22 @@ impl ContentDigest {
         Self::from_hex_digest(ChecksumType::Sha256, digest)
     }
 
+    /// Create a new SHA-512 instance by parsing a hex digest.
+    pub fn sha512_hex(digest: &str) -> Result<Self> {
+        Self::from_hex_digest(ChecksumType::Sha512, digest)
+    }
+
     /// Obtain an instance by parsing a hex string as a [ChecksumType].
     pub fn from_hex_digest(checksum: ChecksumType, digest: &str) -> Result<Self> {
         let digest = hex::decode(digest)
             .map_err(|e| DebianError::ContentDigestBadHex(digest.to_string(), e))?;
 
-        Ok(match checksum {
-            ChecksumType::Md5 => Self::Md5(digest),
-            ChecksumType::Sha1 => Self::Sha1(digest),
-            ChecksumType::Sha256 => Self::Sha256(digest),
-        })
+        Ok(Self::new(checksum, digest))
     }
 
     /// Create a new hasher matching for the type of this digest.
     pub fn new_hasher(&self) -> Box<dyn Hasher + Send> {
-        Box::new(match self {
-            Self::Md5(_) => CleartextHasher::md5(),
-            Self::Sha1(_) => CleartextHasher::sha1(),
-            Self::Sha256(_) => CleartextHasher::sha256(),
-        })
+        self.checksum_type().new_hasher()
     }
 
     /// Obtain the digest bytes for this content digest.
@@ -90,6 +109,7 @@ impl ContentDigest {
             Self::Md5(x) => x,
             Self::Sha1(x) => x,
             Self::Sha256(x) => x,
+            Self::Sha512(x) => x,
         }
     }
 
@@ -104,6 +124,7 @@ impl ContentDigest {
             Self::Md5(_) => ChecksumType::Md5,
             Self::Sha1(_) => ChecksumType::Sha1,
             Self::Sha256(_) => ChecksumType::Sha256,
+            Self::Sha512(_) => ChecksumType::Sha512,
         }
     }
 
@@ -115,6 +136,38 @@ impl ContentDigest {
     }
 }
 
+/// Serialized form of a [ContentDigest].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize, serde::Serialize)]
+struct SerdeContentDigest<'a> {
+    checksum: ChecksumType,
+    digest: std::borrow::Cow<'a, str>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ContentDigest {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        SerdeContentDigest {
+            checksum: self.checksum_type(),
+            digest: self.digest_hex().into(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ContentDigest {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let value = SerdeContentDigest::deserialize(deserializer)?;
+        Self::from_hex_digest(value.checksum, &value.digest).map_err(serde::de::Error::custom)
+    }
+}
+
 /// Compression format used by Debian primitives.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Compression {
@@ -132,6 +185,9 @@ pub enum Compression {
 
     /// LZMA compression (.lzma extension).
     Lzma,
+
+    /// Zstandard compression (.zst extension).
+    Zstd,
 }
 
 impl Compression {
@@ -143,12 +199,21 @@ impl Compression {
             Self::Gzip => ".gz",
             Self::Bzip2 => ".bz2",
             Self::Lzma => ".lzma",
+            Self::Zstd => ".zst",
         }
     }
 
     /// The default retrieval preference order for client.
     pub fn default_preferred_order() -> impl Iterator<Item = Compression> {
-        [Self::Xz, Self::Lzma, Self::Gzip, Self::Bzip2, Self::None].into_iter()
+        [
+            Self::Xz,
+            Self::Zstd,
+            Self::Lzma,
+            Self::Gzip,
+            Self::Bzip2,
+            Self::None,
+        ]
+        .into_iter()
     }
 }
 
@@ -163,6 +228,7 @@ pub async fn read_decompressed(
         Compression::Xz => Box::pin(XzDecoder::new(stream)),
         Compression::Bzip2 => Box::pin(BzDecoder::new(stream)),
         Compression::Lzma => Box::pin(LzmaDecoder::new(stream)),
+        Compression::Zstd => Box::pin(ZstdDecoder::new(stream)),
     })
 }
 
@@ -177,6 +243,25 @@ pub fn read_compressed<'a>(
         Compression::Xz => Box::pin(XzEncoder::new(stream)),
         Compression::Bzip2 => Box::pin(BzEncoder::new(stream)),
         Compression::Lzma => Box::pin(LzmaEncoder::new(stream)),
+        Compression::Zstd => Box::pin(ZstdEncoder::new(stream)),
+    }
+}
+
+/// Wrap a writer with transparent compression.
+///
+/// Data written to the returned writer is compressed and written to `writer`. The
+/// returned writer must be closed to flush the end of the compressed stream.
+pub fn write_compressed<'a>(
+    writer: impl AsyncWrite + Send + 'a,
+    compression: Compression,
+) -> Pin<Box<dyn AsyncWrite + Send + 'a>> {
+    match compression {
+        Compression::None => Box::pin(writer),
+        Compression::Gzip => Box::pin(write::GzipEncoder::new(writer)),
+        Compression::Xz => Box::pin(write::XzEncoder::new(writer)),
+        Compression::Bzip2 => Box::pin(write::BzEncoder::new(writer)),
+        Compression::Lzma => Box::pin(write::LzmaEncoder::new(writer)),
+        Compression::Zstd => Box::pin(write::ZstdEncoder::new(writer)),
     }
 }
 
@@ -188,7 +273,9 @@ pub async fn drain_reader(reader: impl AsyncRead) -> std::io::Result<u64> {
 
 /// An adapter for [AsyncRead] streams that validates source size and digest.
 ///
-/// Validation only occurs once the expected source size bytes have been read.
+/// Validation only occurs once the expected source size bytes have been read. Instances
+/// constructed via [ContentValidatingReader::new_digest_only()] validate once the source
+/// reaches EOF.
 ///
 /// If the reader consumes less than the expected number of bytes, no validation
 /// occurs and incorrect data could have been read. Therefore it is **strongly recommended**
@@ -196,7 +283,7 @@ pub async fn drain_reader(reader: impl AsyncRead) -> std::io::Result<u64> {
 #[pin_project]
 pub struct ContentValidatingReader<R> {
     hasher: Option<Box<dyn pgp::crypto::hash::Hasher + Send>>,
-    expected_size: u64,
+    expected_size: Option<u64>,
     expected_digest: ContentDigest,
     #[pin]
     source: R,
@@ -208,7 +295,21 @@ impl<R> ContentValidatingReader<R> {
     pub fn new(source: R, expected_size: u64, expected_digest: ContentDigest) -> Self {
         Self {
             hasher: Some(expected_digest.new_hasher()),
-            expected_size,
+            expected_size: Some(expected_size),
+            expected_digest,
+            source,
+            bytes_read: 0,
+        }
+    }
+
+    /// Create a new instance bound to a source having an expected content digest.
+    ///
+    /// This is for content of unknown size. The digest is validated once the source is
+    /// exhausted.
+    pub fn new_digest_only(source: R, expected_digest: ContentDigest) -> Self {
+        Self {
+            hasher: Some(expected_digest.new_hasher()),
+            expected_size: None,
             expected_digest,
             source,
             bytes_read: 0,
@@ -239,33 +340,38 @@ where
                     *this.bytes_read += size as u64;
                 }
 
-                match this.bytes_read.cmp(&this.expected_size) {
-                    std::cmp::Ordering::Equal => {
-                        if let Some(hasher) = this.hasher.take() {
-                            let got_digest = hasher.finish();
-
-                            if got_digest != this.expected_digest.digest_bytes() {
-                                return Poll::Ready(Err(std::io::Error::new(
-                                    std::io::ErrorKind::Other,
-                                    format!(
-                                        "digest mismatch of retrieved content: expected {}, got {}",
-                                        this.expected_digest.digest_hex(),
-                                        hex::encode(got_digest)
-                                    ),
-                                )));
-                            }
+                let complete = match *this.expected_size {
+                    Some(expected_size) => match (*this.bytes_read).cmp(&expected_size) {
+                        std::cmp::Ordering::Equal => true,
+                        std::cmp::Ordering::Greater => {
+                            return Poll::Ready(Err(std::io::Error::new(
+                                std::io::ErrorKind::Other,
+                                format!(
+                                    "extra bytes read: expected {}; got {}",
+                                    expected_size, this.bytes_read
+                                ),
+                            )));
+                        }
+                        std::cmp::Ordering::Less => false,
+                    },
+                    None => size == 0 && !buf.is_empty(),
+                };
+
+                if complete {
+                    if let Some(hasher) = this.hasher.take() {
+                        let got_digest = hasher.finish();
+
+                        if got_digest != this.expected_digest.digest_bytes() {
+                            return Poll::Ready(Err(std::io::Error::new(
+                                std::io::ErrorKind::Other,
+                                format!(
+                                    "digest mismatch of retrieved content: expected {}, got {}",
+                                    this.expected_digest.digest_hex(),
+                                    hex::encode(got_digest)
+                                ),
+                            )));
                         }
                     }
-                    std::cmp::Ordering::Greater => {
-                        return Poll::Ready(Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!(
-                                "extra bytes read: expected {}; got {}",
-                                this.expected_size, this.bytes_read
-                            ),
-                        )));
-                    }
-                    std::cmp::Ordering::Less => {}
                 }
 
                 Poll::Ready(Ok(size))
@@ -276,61 +382,77 @@ where
 }
 
 /// Holds multiple flavors of content digests.
+///
+/// Instances produced by [MultiDigester::default()] have a digest for every [ChecksumType],
+/// in the order of [ChecksumType::all()].
 #[derive(Clone, Debug)]
 pub struct MultiContentDigest {
-    pub md5: ContentDigest,
-    pub sha1: ContentDigest,
-    pub sha256: ContentDigest,
+    digests: Vec<ContentDigest>,
 }
 
 impl MultiContentDigest {
     /// Whether this digest matches another one.
+    ///
+    /// Returns false if no digest of the other's [ChecksumType] was computed.
     pub fn matches_digest(&self, other: &ContentDigest) -> bool {
-        match other {
-            ContentDigest::Md5(_) => &self.md5 == other,
-            ContentDigest::Sha1(_) => &self.sha1 == other,
-            ContentDigest::Sha256(_) => &self.sha256 == other,
-        }
+        self.digest(other.checksum_type()) == Some(other)
+    }
+
+    /// Obtain the [ContentDigest] for a given [ChecksumType], if it was computed.
+    pub fn digest(&self, checksum: ChecksumType) -> Option<&ContentDigest> {
+        self.digests
+            .iter()
+            .find(|digest| digest.checksum_type() == checksum)
     }
 
     /// Obtain the [ContentDigest] for a given [ChecksumType].
+    ///
+    /// # Panics
+    ///
+    /// Panics if no digest of the given type was computed.
     pub fn digest_from_checksum(&self, checksum: ChecksumType) -> &ContentDigest {
-        match checksum {
-            ChecksumType::Md5 => &self.md5,
-            ChecksumType::Sha1 => &self.sha1,
-            ChecksumType::Sha256 => &self.sha256,
-        }
+        self.digest(checksum)
+            .expect("digest should be computed for checksum type")
     }
 
     /// Obtain an iterator of [ContentDigest] in this instance.
     pub fn iter_digests(&self) -> impl Iterator<Item = &ContentDigest> + '_ {
-        [&self.md5, &self.sha1, &self.sha256].into_iter()
+        self.digests.iter()
     }
 }
 
 /// A content digester that simultaneously computes multiple digest types.
+///
+/// The default instance computes digests of every [ChecksumType].
 pub struct MultiDigester {
-    md5: Box<dyn Hasher + Send>,
-    sha1: Box<dyn Hasher + Send>,
-    sha256: Box<dyn Hasher + Send>,
+    hashers: Vec<(ChecksumType, Box<dyn Hasher + Send>)>,
 }
 
 impl Default for MultiDigester {
     fn default() -> Self {
-        Self {
-            md5: Box::new(CleartextHasher::md5()),
-            sha1: Box::new(CleartextHasher::sha1()),
-            sha256: Box::new(CleartextHasher::sha256()),
-        }
+        Self::new(ChecksumType::all())
     }
 }
 
 impl MultiDigester {
+    /// Construct an instance computing digests of the given types.
+    pub fn new(checksums: impl IntoIterator<Item = ChecksumType>) -> Self {
+        let mut hashers: Vec<(ChecksumType, Box<dyn Hasher + Send>)> = vec![];
+
+        for checksum in checksums {
+            if !hashers.iter().any(|(c, _)| *c == checksum) {
+                hashers.push((checksum, checksum.new_hasher()));
+            }
+        }
+
+        Self { hashers }
+    }
+
     /// Write content into the digesters.
     pub fn update(&mut self, data: &[u8]) {
-        self.md5.update(data);
-        self.sha1.update(data);
-        self.sha256.update(data);
+        for (_, hasher) in self.hashers.iter_mut() {
+            hasher.update(data);
+        }
     }
 
     /// Finish digesting content.
@@ -338,30 +460,61 @@ impl MultiDigester {
     /// Consumes the instance and returns a [MultiContentDigest] holding all the digests.
     pub fn finish(self) -> MultiContentDigest {
         MultiContentDigest {
-            md5: ContentDigest::Md5(self.md5.finish()),
-            sha1: ContentDigest::Sha1(self.sha1.finish()),
-            sha256: ContentDigest::Sha256(self.sha256.finish()),
+            digests: self
+                .hashers
+                .into_iter()
+                .map(|(checksum, hasher)| ContentDigest::new(checksum, hasher.finish()))
+                .collect(),
         }
     }
 }
 
-/// An [AsyncRead] stream adapter that computes multiple [ContentDigest] as data is read.
+/// A stream adapter that computes multiple [ContentDigest] as data is read.
+///
+/// This implements [std::io::Read] over sync sources and [AsyncRead] over async sources.
+/// An optional progress callback is called with the total number of bytes read after
+/// every read yielding data.
 #[pin_project]
 pub struct DigestingReader<R> {
     digester: MultiDigester,
+    bytes_read: u64,
+    progress_cb: Option<Box<dyn FnMut(u64) + Send>>,
     #[pin]
     source: R,
 }
 
 impl<R> DigestingReader<R> {
     /// Construct a new instance from a source reader.
+    ///
+    /// Digests of every [ChecksumType] are computed.
     pub fn new(source: R) -> Self {
+        Self::with_digester(source, MultiDigester::default())
+    }
+
+    /// Construct a new instance computing digests of the given types.
+    pub fn with_checksums(source: R, checksums: impl IntoIterator<Item = ChecksumType>) -> Self {
+        Self::with_digester(source, MultiDigester::new(checksums))
+    }
+
+    fn with_digester(source: R, digester: MultiDigester) -> Self {
         Self {
-            digester: MultiDigester::default(),
+            digester,
+            bytes_read: 0,
+            progress_cb: None,
             source,
         }
     }
 
+    /// Set a callback to be called with the total number of bytes read so far.
+    pub fn set_progress_callback(&mut self, cb: impl FnMut(u64) + Send + 'static) {
+        self.progress_cb = Some(Box::new(cb));
+    }
+
+    /// The number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
     /// Finish the stream.
     ///
     /// Returns the source reader and a resolved [MultiContentDigest].
@@ -370,6 +523,22 @@ impl<R> DigestingReader<R> {
     }
 }
 
+fn record_read(
+    digester: &mut MultiDigester,
+    bytes_read: &mut u64,
+    progress_cb: &mut Option<Box<dyn FnMut(u64) + Send>>,
+    data: &[u8],
+) {
+    if !data.is_empty() {
+        digester.update(data);
+        *bytes_read += data.len() as u64;
+
+        if let Some(cb) = progress_cb {
+            cb(*bytes_read);
+        }
+    }
+}
+
 impl<R> AsyncRead for DigestingReader<R>
 where
     R: AsyncRead + Unpin,
@@ -383,9 +552,12 @@ where
 
         match this.source.as_mut().poll_read(cx, buf) {
             Poll::Ready(Ok(size)) => {
-                if size > 0 {
-                    this.digester.update(&buf[0..size]);
-                }
+                record_read(
+                    this.digester,
+                    this.bytes_read,
+                    this.progress_cb,
+                    &buf[0..size],
+                );
 
                 Poll::Ready(Ok(size))
             }
@@ -394,6 +566,24 @@ where
     }
 }
 
+impl<R> std::io::Read for DigestingReader<R>
+where
+    R: std::io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.source.read(buf)?;
+
+        record_read(
+            &mut self.digester,
+            &mut self.bytes_read,
+            &mut self.progress_cb,
+            &buf[0..size],
+        );
+
+        Ok(size)
+    }
+}
+
 /// An [AsyncWrite] stream adapter that computes multiple [ContentDigest] as data is written.
 #[pin_project]
 pub struct DigestingWriter<W> {
@@ -466,6 +656,31 @@ pub trait DataResolver: Sync {
     /// decoding applied.
     async fn get_path(&self, path: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>>;
 
+    /// Obtain the local filesystem path holding the content of a relative path.
+    ///
+    /// Resolvers backed by local files return [Some], allowing consumers to use filesystem
+    /// primitives to access content. The file isn't guaranteed to exist. The default
+    /// implementation returns [None].
+    fn local_path(&self, path: &str) -> Option<PathBuf> {
+        let _ = path;
+        None
+    }
+
+    /// Get a byte range of the content of a relative path as an async reader.
+    ///
+    /// An `end` of [u64::MAX] reads through the end of the content. The reader ends early
+    /// if the content is shorter than the range.
+    ///
+    /// The default implementation reads and discards content before the range.
+    /// Implementations should override it to only fetch the requested bytes.
+    async fn get_path_with_range(
+        &self,
+        path: &str,
+        range: Range<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        read_range(self.get_path(path).await?, range).await
+    }
+
     /// Obtain a reader that performs content integrity checking.
     ///
     /// Because content digests can only be computed once all content is read, the reader
@@ -519,6 +734,34 @@ pub trait DataResolver: Sync {
     }
 }
 
+/// Format a non-empty byte range as the value of an HTTP `Range` header.
+///
+/// A range ending at [u64::MAX] extends to the end of content.
+#[cfg(any(feature = "http", feature = "s3"))]
+pub(crate) fn range_header_value(range: &Range<u64>) -> String {
+    if range.end == u64::MAX {
+        format!("bytes={}-", range.start)
+    } else {
+        format!("bytes={}-{}", range.start, range.end - 1)
+    }
+}
+
+/// Limit a reader to a byte range of its content.
+///
+/// Content before the range is read and discarded.
+pub(crate) async fn read_range(
+    mut reader: Pin<Box<dyn AsyncRead + Send>>,
+    range: Range<u64>,
+) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+    futures::io::copy(
+        &mut (&mut reader).take(range.start),
+        &mut futures::io::sink(),
+    )
+    .await?;
+
+    Ok(Box::pin(reader.take(range.end.saturating_sub(range.start))))
+}
+
 /// A [DataResolver] that maintains a path translation table and transparently redirects lookups.
 pub struct PathMappingDataResolver<R> {
     source: R,
@@ -550,4 +793,110 @@ impl<R: DataResolver + Send> DataResolver for PathMappingDataResolver<R> {
             .get_path(self.path_map.get(path).map(|s| s.as_str()).unwrap_or(path))
             .await
     }
+
+    fn local_path(&self, path: &str) -> Option<PathBuf> {
+        self.source
+            .local_path(self.path_map.get(path).map(|s| s.as_str()).unwrap_or(path))
+    }
+
+    async fn get_path_with_range(
+        &self,
+        path: &str,
+        range: Range<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        self.source
+            .get_path_with_range(
+                self.path_map.get(path).map(|s| s.as_str()).unwrap_or(path),
+                range,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        std::{
+            io::Read,
+            sync::{Arc, Mutex},
+        },
+    };
+
+    struct MemoryResolver(Vec<u8>);
+
+    #[async_trait]
+    impl DataResolver for MemoryResolver {
+        async fn get_path(&self, _path: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+            Ok(Box::pin(futures::io::Cursor::new(self.0.clone())))
+        }
+    }
+
+    #[tokio::test]
+    async fn get_path_with_range() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        std::fs::write(td.path().join("data"), b"0123456789")?;
+
+        let resolvers: [Box<dyn DataResolver>; 2] = [
+            Box::new(crate::repository::filesystem::FilesystemRepositoryReader::new(td.path())),
+            Box::new(MemoryResolver(b"0123456789".to_vec())),
+        ];
+
+        for resolver in resolvers {
+            for (range, expected) in [
+                (2..5, &b"234"[..]),
+                (7..u64::MAX, b"789"),
+                (8..20, b"89"),
+                (4..4, b""),
+                (20..30, b""),
+            ] {
+                let mut buf = vec![];
+                resolver
+                    .get_path_with_range("data", range)
+                    .await?
+                    .read_to_end(&mut buf)
+                    .await?;
+                assert_eq!(buf, expected);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn digesting_reader() -> Result<()> {
+        let data = b"hello".repeat(1000);
+
+        let progress = Arc::new(Mutex::new(vec![]));
+        let progress_cb = progress.clone();
+
+        let mut reader = DigestingReader::with_checksums(
+            std::io::Cursor::new(&data),
+            [ChecksumType::Sha256, ChecksumType::Sha256],
+        );
+        reader.set_progress_callback(move |size| progress_cb.lock().unwrap().push(size));
+
+        let mut buf = [0u8; 1024];
+        while reader.read(&mut buf)? > 0 {}
+        assert_eq!(reader.bytes_read(), 5000);
+        assert_eq!(
+            progress.lock().unwrap().as_slice(),
+            &[1024, 2048, 3072, 4096, 5000]
+        );
+
+        let (_, digests) = reader.finish();
+        assert_eq!(digests.iter_digests().count(), 1);
+        assert!(digests.digest(ChecksumType::Md5).is_none());
+        let expected = ContentDigest::compute(ChecksumType::Sha256, &data);
+        assert!(digests.matches_digest(&expected));
+        assert!(!digests.matches_digest(&ContentDigest::compute(ChecksumType::Md5, &data)));
+
+        let mut reader = DigestingReader::new(futures::io::Cursor::new(&data));
+        futures::executor::block_on(reader.read_to_end(&mut vec![]))?;
+        let (_, digests) = reader.finish();
+        assert_eq!(digests.iter_digests().count(), ChecksumType::all().count());
+        assert!(digests.matches_digest(&expected));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,338 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Fetching PGP public keys from keyservers and Web Key Directories.
+
+Rather than shipping key files, a mirror can be configured with only the fingerprint of
+the key that signs its upstream repository. [KeyFetcher] obtains the key with that
+fingerprint from:
+
+* HKP keyservers, such as `hkps://keys.openpgp.org`. See
+  <https://datatracker.ietf.org/doc/html/draft-shaw-openpgp-hkp-00>.
+* The Web Key Directory (WKD) of an email address's domain, if an email address is
+  given. See <https://datatracker.ietf.org/doc/html/draft-koch-openpgp-webkey-service>.
+
+Neither source is trusted: only keys whose primary key or a subkey has the requested
+fingerprint and whose self-signatures verify are returned. Fetched keys can be cached in a
+local directory, which later fetches consult before the network.
+*/
+
+use {
+    crate::{
+        error::{DebianError, Result},
+        keyring::{fingerprint_hex, minimal_public_key, normalize_fingerprint, Keyring},
+        repository::http::USER_AGENT,
+    },
+    pgp::{ser::Serialize, SignedPublicKey},
+    reqwest::{Client, ClientBuilder, StatusCode, Url},
+    sha1::{Digest, Sha1},
+    std::path::{Path, PathBuf},
+};
+
+/// The keyserver used by default.
+pub const DEFAULT_KEYSERVER: &str = "hkps://keys.openpgp.org";
+
+/// Alphabet of the z-base-32 encoding used by WKD.
+const ZBASE32_ALPHABET: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// Obtain the URL of the HKP request for the key with the given fingerprint.
+///
+/// `hkps://` keyserver URLs are requested over HTTPS and `hkp://` URLs over HTTP on port
+/// 11371 if no port is given. `https://` and `http://` URLs are used as is.
+pub fn hkp_lookup_url(keyserver: &str, fingerprint: &str) -> Result<Url> {
+    let (scheme, rest) = keyserver
+        .split_once("://")
+        .ok_or_else(|| DebianError::PgpKeyserverInvalid(keyserver.to_string()))?;
+
+    let mut url = match scheme {
+        "hkps" | "https" => Url::parse(&format!("https://{}", rest))?,
+        "hkp" | "http" => {
+            let mut url = Url::parse(&format!("http://{}", rest))?;
+            if scheme == "hkp" && url.port().is_none() {
+                url.set_port(Some(11371)).ok();
+            }
+            url
+        }
+        _ => return Err(DebianError::PgpKeyserverInvalid(keyserver.to_string())),
+    };
+
+    url.set_path("/pks/lookup");
+    url.query_pairs_mut()
+        .append_pair("op", "get")
+        .append_pair("options", "mr")
+        .append_pair(
+            "search",
+            &format!("0x{}", normalize_fingerprint(fingerprint)),
+        );
+
+    Ok(url)
+}
+
+/// Obtain the WKD URLs for an email address.
+///
+/// Returns the URLs of the advanced method, which uses an `openpgpkey` subdomain, and of
+/// the direct method, in the order they should be tried.
+pub fn wkd_urls(email: &str) -> Result<[Url; 2]> {
+    let (local, domain) = email
+        .rsplit_once('@')
+        .ok_or_else(|| DebianError::WkdEmailInvalid(email.to_string()))?;
+    let domain = domain.to_ascii_lowercase();
+
+    let digest = Sha1::digest(local.to_lowercase().as_bytes());
+    let hash = zbase32(&digest);
+
+    let mut advanced = Url::parse(&format!(
+        "https://openpgpkey.{domain}/.well-known/openpgpkey/{domain}/hu/{hash}"
+    ))?;
+    let mut direct = Url::parse(&format!(
+        "https://{domain}/.well-known/openpgpkey/hu/{hash}"
+    ))?;
+    advanced.query_pairs_mut().append_pair("l", local);
+    direct.query_pairs_mut().append_pair("l", local);
+
+    Ok([advanced, direct])
+}
+
+fn zbase32(data: &[u8]) -> String {
+    let mut res = String::with_capacity((data.len() * 8).div_ceil(5));
+
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            res.push(ZBASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        res.push(ZBASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    res
+}
+
+/// Whether a key's primary key or a subkey has the given normalized fingerprint.
+fn has_fingerprint(key: &SignedPublicKey, fingerprint: &str) -> bool {
+    std::iter::once(fingerprint_hex(key))
+        .chain(key.public_subkeys.iter().map(fingerprint_hex))
+        .any(|candidate| candidate == fingerprint)
+}
+
+/// Fetches PGP public keys by fingerprint.
+///
+/// Sources are tried in order: the cache directory, the WKD of the email address given to
+/// [Self::fetch()], then each keyserver.
+#[derive(Clone, Debug)]
+pub struct KeyFetcher {
+    client: Client,
+    keyservers: Vec<String>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl KeyFetcher {
+    /// Construct an instance querying [DEFAULT_KEYSERVER].
+    pub fn new() -> Result<Self> {
+        let builder = ClientBuilder::new().user_agent(USER_AGENT);
+
+        Ok(Self::new_client(builder.build()?))
+    }
+
+    /// Construct an instance using the given [Client] and querying [DEFAULT_KEYSERVER].
+    pub fn new_client(client: Client) -> Self {
+        Self {
+            client,
+            keyservers: vec![DEFAULT_KEYSERVER.to_string()],
+            cache_dir: None,
+        }
+    }
+
+    /// Set the keyservers to query.
+    ///
+    /// Values are URLs like `hkps://keys.openpgp.org`. See [hkp_lookup_url()]. An empty
+    /// list disables keyserver queries.
+    pub fn set_keyservers(&mut self, keyservers: impl Iterator<Item = impl ToString>) {
+        self.keyservers = keyservers.map(|x| x.to_string()).collect();
+    }
+
+    /// Set a directory to cache fetched keys in.
+    ///
+    /// Keys are stored as `<fingerprint>.gpg`, so the directory can also be loaded with
+    /// [Keyring::load_directory()]. Cached keys are used without contacting the network,
+    /// so updates such as revocations are only picked up once the cached file is deleted.
+    pub fn set_cache_dir(&mut self, path: impl AsRef<Path>) {
+        self.cache_dir = Some(path.as_ref().to_path_buf());
+    }
+
+    /// Fetch the key having the given fingerprint.
+    ///
+    /// The fingerprint can be that of the primary key or of a subkey. Case and whitespace
+    /// are ignored. If `email` is given, its WKD is queried before the keyservers.
+    ///
+    /// Errors with [DebianError::PgpKeyNotFound] if no source has the key. If a source
+    /// could not be queried and no later source has the key, the error querying it is
+    /// returned instead.
+    pub async fn fetch(&self, fingerprint: &str, email: Option<&str>) -> Result<SignedPublicKey> {
+        let fingerprint = normalize_fingerprint(fingerprint);
+
+        if let Some(key) = self.cached(&fingerprint)? {
+            return Ok(key);
+        }
+
+        let mut urls = vec![];
+        if let Some(email) = email {
+            urls.extend(wkd_urls(email)?);
+        }
+        for keyserver in &self.keyservers {
+            urls.push(hkp_lookup_url(keyserver, &fingerprint)?);
+        }
+
+        let mut error = None;
+
+        for url in urls {
+            match self.fetch_url(url, &fingerprint).await {
+                Ok(Some(key)) => {
+                    self.store(&fingerprint, &key)?;
+                    return Ok(key);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error = Some(e);
+                }
+            }
+        }
+
+        Err(error.unwrap_or(DebianError::PgpKeyNotFound(fingerprint)))
+    }
+
+    /// Fetch the keys having the given fingerprints into a [Keyring].
+    ///
+    /// No WKD is queried.
+    pub async fn fetch_keyring(
+        &self,
+        fingerprints: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Keyring> {
+        let mut keyring = Keyring::default();
+
+        for fingerprint in fingerprints {
+            keyring.add_key(self.fetch(fingerprint.as_ref(), None).await?);
+        }
+
+        Ok(keyring)
+    }
+
+    /// Fetch the key with a normalized fingerprint from a URL, if it has it.
+    async fn fetch_url(&self, url: Url, fingerprint: &str) -> Result<Option<SignedPublicKey>> {
+        let res = self.client.get(url).send().await?;
+
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let data = res.error_for_status()?.bytes().await?;
+
+        let mut keyring = Keyring::default();
+        keyring.load_data(&data)?;
+
+        Ok(keyring.into_iter().find(|key| {
+            has_fingerprint(key, fingerprint) && minimal_public_key(key).verify().is_ok()
+        }))
+    }
+
+    fn cache_path(&self, fingerprint: &str) -> Option<PathBuf> {
+        self.cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.gpg", fingerprint)))
+    }
+
+    fn cached(&self, fingerprint: &str) -> Result<Option<SignedPublicKey>> {
+        let path = match self.cache_path(fingerprint) {
+            Some(path) if path.exists() => path,
+            _ => return Ok(None),
+        };
+
+        let mut keyring = Keyring::default();
+        keyring.load_file(&path)?;
+
+        Ok(keyring
+            .into_iter()
+            .find(|key| has_fingerprint(key, fingerprint)))
+    }
+
+    fn store(&self, fingerprint: &str, key: &SignedPublicKey) -> Result<()> {
+        if let Some(path) = self.cache_path(fingerprint) {
+            let io_error = |e| DebianError::RepositoryIoPath(format!("{}", path.display()), e);
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(io_error)?;
+            }
+            std::fs::write(&path, key.to_bytes()?).map_err(io_error)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, pgp::types::PublicKeyTrait};
+
+    #[test]
+    fn urls() -> Result<()> {
+        let [advanced, direct] = wkd_urls("Joe.Doe@Example.ORG")?;
+        assert_eq!(
+            advanced.as_str(),
+            "https://openpgpkey.example.org/.well-known/openpgpkey/example.org/hu/\
+             iy9q119eutrkn8s1mk4r39qejnbu3n5q?l=Joe.Doe"
+        );
+        assert_eq!(
+            direct.as_str(),
+            "https://example.org/.well-known/openpgpkey/hu/iy9q119eutrkn8s1mk4r39qejnbu3n5q?l=Joe.Doe"
+        );
+
+        assert_eq!(
+            hkp_lookup_url("hkps://keys.openpgp.org", "abcd 1234")?.as_str(),
+            "https://keys.openpgp.org/pks/lookup?op=get&options=mr&search=0xABCD1234"
+        );
+        assert_eq!(
+            hkp_lookup_url("hkp://keyserver.ubuntu.com", "ABCD1234")?.as_str(),
+            "http://keyserver.ubuntu.com:11371/pks/lookup?op=get&options=mr&search=0xABCD1234"
+        );
+        assert!(hkp_lookup_url("keys.openpgp.org", "ABCD1234").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache() -> Result<()> {
+        let mut keyring = Keyring::default();
+        keyring.load_armored(include_bytes!("testdata/policy-expiring-key.asc"))?;
+        let key = keyring.keys()[0].clone();
+        let fingerprint = fingerprint_hex(&key);
+
+        let temp = tempfile::tempdir()?;
+        let mut fetcher = KeyFetcher::new()?;
+        fetcher.set_keyservers(std::iter::empty::<String>());
+        fetcher.set_cache_dir(temp.path().join("keys"));
+
+        assert!(matches!(
+            futures::executor::block_on(fetcher.fetch(&fingerprint, None)),
+            Err(DebianError::PgpKeyNotFound(x)) if x == fingerprint
+        ));
+
+        fetcher.store(&fingerprint, &key)?;
+
+        let fetched =
+            futures::executor::block_on(fetcher.fetch(&fingerprint.to_lowercase(), None))?;
+        assert_eq!(fetched.fingerprint(), key.fingerprint());
+
+        let mut cached = Keyring::default();
+        assert_eq!(cached.load_directory(temp.path().join("keys"))?, 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1210 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Collections of trusted PGP public keys.
+
+apt trusts the keys in `/etc/apt/trusted.gpg` and in the `.asc` and `.gpg` files of
+`/etc/apt/trusted.gpg.d`, as well as keyrings named by `Signed-By` options. [Keyring] loads
+keys from the same sources so repositories can be verified as apt would.
+
+Three file formats are supported:
+
+* ASCII armored keys, conventionally in `.asc` files.
+* Binary OpenPGP keyrings, which are concatenated key packets as written by
+  `gpg --export`, conventionally in `.gpg` files.
+* GnuPG keybox files, as written by GnuPG 2.1 and newer to `pubring.kbx` and sometimes
+  found in `.gpg` files created with `gpg --no-default-keyring --keyring`.
+
+[Keyring::load_data()] detects the format from content.
+[Keyring::verify_signatures()] verifies the PGP signatures of an `InRelease` file (see
+[crate::repository::release::ReleaseFile::signatures()]) against the keyring.
+[Keyring::verify_signatures_with_policy()] additionally enforces key expiration, key
+revocation and signature validity windows according to a [VerificationPolicy].
+[Keyring::verify_report()] reports the outcome of every signature in a
+[VerificationReport], identifying the key that made it.
+
+A [TrustPolicy] extends a [VerificationPolicy] with algorithm requirements and decides
+whether the acceptable signatures of a document suffice, e.g. by requiring signatures from
+particular keys. It is accepted by every verification entry point:
+[Keyring::verify_trusted()] and the `verify_signatures()` methods of release, `.dsc` and
+`.changes` files, as well as [crate::deb::signing::verify_package_signatures()].
+
+[Keyring::to_minimal()], [Keyring::to_binary()] and [Keyring::to_armored()] export keys,
+e.g. for distribution in a `keyring` package. [minimal_public_key()] strips a key down to
+what verifiers need.
+
+Fingerprints are upper case hex, as produced by [fingerprint_hex()], and key IDs are lower
+case hex, as produced by [key_id_hex()]. [normalize_fingerprint()] converts fingerprints
+in other forms, e.g. as [format_fingerprint()] and `gpg` print them.
+*/
+
+use {
+    crate::error::{DebianError, Result},
+    chrono::{DateTime, Duration, Utc},
+    pgp::{
+        armor::BlockType,
+        crypto::hash::HashAlgorithm,
+        packet::{Signature, SignatureType},
+        ser::Serialize,
+        types::{PublicKeyTrait, PublicParams, SignedUser},
+        Deserializable, SignedPublicKey, SignedPublicSubKey,
+    },
+    pgp_cleartext::CleartextSignatures,
+    std::path::Path,
+};
+
+/// Magic bytes in the header blob of keybox files.
+const KEYBOX_MAGIC: &[u8] = b"KBXf";
+
+/// Keybox blob type holding an OpenPGP keyblock.
+const KEYBOX_BLOB_OPENPGP: u8 = 2;
+
+/// The line starting an ASCII armored public key block.
+const ARMORED_PUBLIC_KEY_BEGIN: &[u8] = b"-----BEGIN PGP PUBLIC KEY BLOCK-----";
+
+/// Split data holding concatenated ASCII armored public key blocks into the blocks.
+///
+/// Text before the first block is kept with it.
+fn armored_public_key_blocks(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = (1..data.len())
+        .filter(|i| data[*i..].starts_with(ARMORED_PUBLIC_KEY_BEGIN))
+        .collect::<Vec<_>>();
+    starts.insert(0, 0);
+    starts.push(data.len());
+
+    starts
+        .windows(2)
+        .map(|window| &data[window[0]..window[1]])
+        .collect()
+}
+
+/// Checks applied to PGP signatures in addition to cryptographic validity.
+///
+/// The default policy applies no checks. [Self::strict()] rejects what `gpgv`, which apt
+/// uses to verify repositories, would reject.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct VerificationPolicy {
+    /// Reject signatures from keys or subkeys that have expired.
+    pub reject_expired_keys: bool,
+    /// Reject signatures from keys or subkeys that have been revoked.
+    ///
+    /// Any valid revocation signature rejects all signatures from a key, regardless of the
+    /// reason for the revocation.
+    pub reject_revoked_keys: bool,
+    /// Reject signatures that have passed their expiration time.
+    pub reject_expired_signatures: bool,
+    /// Reject signatures created more than this far in the future.
+    ///
+    /// `None` disables the check. Signatures without a creation time are rejected if set.
+    pub max_clock_skew: Option<Duration>,
+    /// Reject signatures created longer than this ago.
+    ///
+    /// `None` disables the check. Signatures without a creation time are rejected if set.
+    pub max_signature_age: Option<Duration>,
+    /// The time at which expiration is evaluated.
+    ///
+    /// `None` uses the current time.
+    pub time: Option<DateTime<Utc>>,
+}
+
+impl VerificationPolicy {
+    /// A policy rejecting expired or revoked keys, expired signatures and signatures
+    /// created more than 5 minutes in the future.
+    pub fn strict() -> Self {
+        Self {
+            reject_expired_keys: true,
+            reject_revoked_keys: true,
+            reject_expired_signatures: true,
+            max_clock_skew: Some(Duration::minutes(5)),
+            max_signature_age: None,
+            time: None,
+        }
+    }
+
+    /// Check a primary key, returning the reason it is unacceptable.
+    fn check_key(
+        &self,
+        key: &SignedPublicKey,
+        time: DateTime<Utc>,
+    ) -> std::result::Result<(), String> {
+        if self.reject_revoked_keys
+            && key
+                .details
+                .revocation_signatures
+                .iter()
+                .any(|sig| sig.verify_key(&key.primary_key).is_ok())
+        {
+            return Err("key is revoked".to_string());
+        }
+
+        if self.reject_expired_keys {
+            if let Some(expires) = key.expires_at().filter(|expires| *expires <= time) {
+                return Err(format!("key expired at {}", expires));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a subkey, returning the reason it is unacceptable.
+    fn check_subkey(
+        &self,
+        key: &SignedPublicKey,
+        subkey: &SignedPublicSubKey,
+        time: DateTime<Utc>,
+    ) -> std::result::Result<(), String> {
+        let valid_signatures = || {
+            subkey.signatures.iter().filter(|sig| {
+                sig.verify_key_binding(&key.primary_key, &subkey.key)
+                    .is_ok()
+            })
+        };
+
+        if self.reject_revoked_keys
+            && valid_signatures().any(|sig| sig.typ() == SignatureType::SubkeyRevocation)
+        {
+            return Err("subkey is revoked".to_string());
+        }
+
+        if self.reject_expired_keys {
+            let expires = valid_signatures()
+                .filter(|sig| sig.typ() == SignatureType::SubkeyBinding)
+                .filter_map(|sig| sig.key_expiration_time())
+                .max()
+                .map(|expiration| *subkey.key.created_at() + *expiration);
+
+            if let Some(expires) = expires.filter(|expires| *expires <= time) {
+                return Err(format!("subkey expired at {}", expires));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a signature, returning the reason it is unacceptable.
+    fn check_signature(
+        &self,
+        sig: &Signature,
+        time: DateTime<Utc>,
+    ) -> std::result::Result<(), String> {
+        let created = sig.created();
+
+        if self.reject_expired_signatures {
+            if let (Some(created), Some(expiration)) = (created, sig.signature_expiration_time()) {
+                // An expiration time of 0 means the signature never expires.
+                let expires = *created + *expiration;
+                if !expiration.is_zero() && expires <= time {
+                    return Err(format!("signature expired at {}", expires));
+                }
+            }
+        }
+
+        if self.max_clock_skew.is_some() || self.max_signature_age.is_some() {
+            let created = created.ok_or_else(|| "signature has no creation time".to_string())?;
+
+            if let Some(skew) = self.max_clock_skew {
+                if *created > time + skew {
+                    return Err(format!("signature created in the future at {}", created));
+                }
+            }
+
+            if let Some(age) = self.max_signature_age {
+                if *created + age < time {
+                    return Err(format!("signature created too long ago at {}", created));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Requirements for trusting a signed document.
+///
+/// Each signature must satisfy [Self::verification] and the algorithm requirements. The
+/// document is trusted if the acceptable signatures come from enough distinct keys,
+/// including every required key.
+///
+/// The default policy trusts any cryptographically valid signature from a key in the
+/// keyring, as does [VerificationPolicy::default()].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TrustPolicy {
+    /// Checks applied to each signature.
+    ///
+    /// Expired keys are accepted unless [VerificationPolicy::reject_expired_keys] is set.
+    pub verification: VerificationPolicy,
+    /// Fingerprints of keys that must each make an acceptable signature.
+    ///
+    /// The fingerprint may be that of the signing subkey or of its primary key. Case and
+    /// whitespace are ignored.
+    pub required_fingerprints: Vec<String>,
+    /// The number of distinct keys that must make acceptable signatures.
+    ///
+    /// Values below 1 are treated as 1.
+    pub min_signatures: usize,
+    /// Reject signatures using the MD5, SHA-1 or RIPEMD-160 digests.
+    pub reject_weak_hashes: bool,
+    /// Reject signatures from RSA, DSA and Elgamal keys with fewer bits than this.
+    pub min_key_bits: Option<usize>,
+}
+
+impl Default for TrustPolicy {
+    fn default() -> Self {
+        Self {
+            verification: VerificationPolicy::default(),
+            required_fingerprints: vec![],
+            min_signatures: 1,
+            reject_weak_hashes: false,
+            min_key_bits: None,
+        }
+    }
+}
+
+impl From<VerificationPolicy> for TrustPolicy {
+    fn from(verification: VerificationPolicy) -> Self {
+        Self {
+            verification,
+            ..Default::default()
+        }
+    }
+}
+
+impl TrustPolicy {
+    /// A policy applying [VerificationPolicy::strict()], rejecting weak digests and
+    /// requiring keys of at least 2048 bits.
+    pub fn strict() -> Self {
+        Self {
+            verification: VerificationPolicy::strict(),
+            reject_weak_hashes: true,
+            min_key_bits: Some(2048),
+            ..Default::default()
+        }
+    }
+
+    /// Decide whether the outcome of verifying a document satisfies this policy.
+    ///
+    /// Returns the identities of the distinct keys that made acceptable signatures, in
+    /// signature order. If there are none, errors as
+    /// [Keyring::verify_signatures_with_policy()] does. Missing signatures from required
+    /// keys are reported as [DebianError::PgpSignatureRequiredKeyMissing] and too few
+    /// signatures as [DebianError::PgpSignatureCountInsufficient].
+    pub fn evaluate(&self, report: &VerificationReport) -> Result<Vec<SignerIdentity>> {
+        let mut signers = Vec::<SignerIdentity>::new();
+        for signer in report.valid_signers() {
+            if !signers
+                .iter()
+                .any(|s| s.primary_fingerprint == signer.primary_fingerprint)
+            {
+                signers.push(signer.clone());
+            }
+        }
+
+        if signers.is_empty() {
+            return Err(report
+                .signatures
+                .iter()
+                .find_map(|verification| {
+                    Some(DebianError::PgpSignatureRejected(
+                        verification.signer.as_ref()?.key_id.clone(),
+                        verification.error.clone()?,
+                    ))
+                })
+                .unwrap_or(DebianError::PgpSignatureUntrusted));
+        }
+
+        if let Some(fingerprint) = self
+            .required_fingerprints
+            .iter()
+            .find(|fingerprint| !report.is_signed_by(fingerprint))
+        {
+            return Err(DebianError::PgpSignatureRequiredKeyMissing(
+                normalize_fingerprint(fingerprint),
+            ));
+        }
+
+        let required = self.min_signatures.max(1);
+        if signers.len() < required {
+            return Err(DebianError::PgpSignatureCountInsufficient(
+                signers.len(),
+                required,
+            ));
+        }
+
+        Ok(signers)
+    }
+
+    /// Check the algorithms of a signature and the key that made it.
+    fn check_algorithms(
+        &self,
+        key: &impl PublicKeyTrait,
+        sig: &Signature,
+    ) -> std::result::Result<(), String> {
+        let hash = sig.hash_alg();
+        if self.reject_weak_hashes
+            && matches!(
+                hash,
+                HashAlgorithm::MD5 | HashAlgorithm::SHA1 | HashAlgorithm::RIPEMD160
+            )
+        {
+            return Err(format!("weak digest algorithm {:?}", hash));
+        }
+
+        if let Some(min_bits) = self.min_key_bits {
+            let modulus = match key.public_params() {
+                PublicParams::RSA { n, .. } => Some(n),
+                PublicParams::DSA { p, .. } | PublicParams::Elgamal { p, .. } => Some(p),
+                _ => None,
+            };
+
+            if let Some(modulus) = modulus {
+                let bytes = modulus.as_bytes();
+                let bits = (bytes.len() * 8).saturating_sub(
+                    bytes
+                        .first()
+                        .map_or(0, |byte| byte.leading_zeros() as usize),
+                );
+
+                if bits < min_bits {
+                    return Err(format!(
+                        "{}-bit {:?} key is too small",
+                        bits,
+                        key.algorithm()
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Obtain the fingerprint of a key or subkey as upper case hex.
+pub fn fingerprint_hex(key: &impl PublicKeyTrait) -> String {
+    hex::encode_upper(key.fingerprint().as_bytes())
+}
+
+/// Obtain the key ID of a key or subkey as lower case hex.
+pub fn key_id_hex(key: &impl PublicKeyTrait) -> String {
+    format!("{:x}", key.key_id())
+}
+
+/// Normalize a hex fingerprint to the upper case form without whitespace used by this crate.
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .split_whitespace()
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+/// Format a hex fingerprint for display.
+///
+/// Like `gpg`, this prints groups of 4 characters separated by spaces, with an extra space
+/// between the two halves.
+pub fn format_fingerprint(fingerprint: &str) -> String {
+    let fingerprint = normalize_fingerprint(fingerprint);
+    let groups = fingerprint
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk))
+        .collect::<Vec<_>>();
+
+    let (first, second) = groups.split_at(groups.len().div_ceil(2));
+
+    [first.join(" "), second.join(" ")]
+        .into_iter()
+        .filter(|half| !half.is_empty())
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// Parse ASCII armored public keys.
+///
+/// The data can hold multiple keys, in one or several armored blocks. Self-signatures
+/// are verified, so keys with invalid or missing binding signatures are rejected.
+/// Certifications by other keys aren't verified.
+pub fn parse_armored_public_keys(data: &[u8]) -> Result<Vec<SignedPublicKey>> {
+    let mut keyring = Keyring::default();
+    keyring.load_armored(data)?;
+
+    for key in keyring.keys() {
+        minimal_public_key(key).verify()?;
+    }
+
+    Ok(keyring.keys)
+}
+
+/// Obtain a copy of a key with only the packets needed to verify signatures.
+///
+/// Like `gpg --export-options export-minimal`, signatures made by other keys, all but
+/// the most recent self-signature of each user ID and user attributes such as photos are
+/// removed. Revocations are retained.
+pub fn minimal_public_key(key: &SignedPublicKey) -> SignedPublicKey {
+    let is_self_signature = |sig: &Signature| {
+        let issuers = sig.issuer();
+        let issuer_fingerprints = sig.issuer_fingerprint();
+
+        (issuers.is_empty() && issuer_fingerprints.is_empty())
+            || issuers.contains(&&key.key_id())
+            || issuer_fingerprints.contains(&&key.fingerprint())
+    };
+
+    let mut minimal = key.clone();
+    minimal.details.user_attributes.clear();
+    minimal
+        .details
+        .revocation_signatures
+        .retain(is_self_signature);
+    minimal.details.direct_signatures.retain(is_self_signature);
+    minimal.details.users = key
+        .details
+        .users
+        .iter()
+        .map(|user| {
+            let (revocations, certifications): (Vec<_>, Vec<_>) = user
+                .signatures
+                .iter()
+                .filter(|sig| is_self_signature(sig))
+                .partition(|sig| sig.typ() == SignatureType::CertRevocation);
+
+            SignedUser {
+                id: user.id.clone(),
+                signatures: revocations
+                    .into_iter()
+                    .chain(certifications.into_iter().max_by_key(|sig| sig.created()))
+                    .cloned()
+                    .collect(),
+            }
+        })
+        .collect();
+
+    minimal
+}
+
+/// Identifies the key that made a signature.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SignerIdentity {
+    /// Hex key ID of the key or subkey that made the signature.
+    pub key_id: String,
+    /// Hex fingerprint of the key or subkey that made the signature.
+    pub fingerprint: String,
+    /// Hex fingerprint of the primary key.
+    ///
+    /// This is the same as [Self::fingerprint] if the primary key made the signature.
+    pub primary_fingerprint: String,
+    /// User IDs of the primary key, e.g. `Name <email>`.
+    pub user_ids: Vec<String>,
+}
+
+impl SignerIdentity {
+    fn new(key: &SignedPublicKey, subkey: Option<&SignedPublicSubKey>) -> Self {
+        let primary_fingerprint = fingerprint_hex(key);
+
+        Self {
+            key_id: match subkey {
+                Some(subkey) => key_id_hex(subkey),
+                None => key_id_hex(key),
+            },
+            fingerprint: match subkey {
+                Some(subkey) => fingerprint_hex(subkey),
+                None => primary_fingerprint.clone(),
+            },
+            primary_fingerprint,
+            user_ids: key
+                .details
+                .users
+                .iter()
+                .map(|user| String::from_utf8_lossy(user.id.id()).to_string())
+                .collect(),
+        }
+    }
+}
+
+/// The outcome of verifying a single signature.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SignatureVerification {
+    /// Hex key IDs of the issuer named by the signature.
+    pub issuer_key_ids: Vec<String>,
+    /// When the signature was created, if recorded.
+    pub created: Option<DateTime<Utc>>,
+    /// The key in the keyring that made the signature, if any.
+    pub signer: Option<SignerIdentity>,
+    /// Why the signature was rejected.
+    ///
+    /// `None` if the signature is valid and satisfies the verification policy.
+    pub error: Option<String>,
+}
+
+impl SignatureVerification {
+    /// Whether the signature is valid and satisfies the verification policy.
+    pub fn is_valid(&self) -> bool {
+        self.signer.is_some() && self.error.is_none()
+    }
+}
+
+/// The outcome of verifying all signatures of a document.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct VerificationReport {
+    /// Outcomes, in the order signatures appear in the document.
+    pub signatures: Vec<SignatureVerification>,
+}
+
+impl VerificationReport {
+    /// Whether at least one signature is valid.
+    pub fn is_valid(&self) -> bool {
+        self.signatures.iter().any(|sig| sig.is_valid())
+    }
+
+    /// Identities of the keys that made valid signatures.
+    pub fn valid_signers(&self) -> impl Iterator<Item = &SignerIdentity> {
+        self.signatures
+            .iter()
+            .filter(|sig| sig.is_valid())
+            .filter_map(|sig| sig.signer.as_ref())
+    }
+
+    /// Whether a valid signature was made by the key with the given fingerprint.
+    ///
+    /// The fingerprint may be that of the signing subkey or of its primary key. Case and
+    /// whitespace are ignored, so fingerprints can be given as `gpg` prints them.
+    pub fn is_signed_by(&self, fingerprint: &str) -> bool {
+        let fingerprint = normalize_fingerprint(fingerprint);
+
+        self.valid_signers().any(|signer| {
+            signer.fingerprint == fingerprint || signer.primary_fingerprint == fingerprint
+        })
+    }
+}
+
+/// A collection of trusted PGP public keys.
+#[derive(Clone, Debug, Default)]
+pub struct Keyring {
+    keys: Vec<SignedPublicKey>,
+}
+
+impl FromIterator<SignedPublicKey> for Keyring {
+    fn from_iter<T: IntoIterator<Item = SignedPublicKey>>(iter: T) -> Self {
+        Self {
+            keys: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<SignedPublicKey> for Keyring {
+    fn extend<T: IntoIterator<Item = SignedPublicKey>>(&mut self, iter: T) {
+        self.keys.extend(iter)
+    }
+}
+
+impl IntoIterator for Keyring {
+    type Item = SignedPublicKey;
+    type IntoIter = std::vec::IntoIter<SignedPublicKey>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.keys.into_iter()
+    }
+}
+
+impl Keyring {
+    /// Construct a keyring with the keys apt trusts on a system.
+    ///
+    /// Keys are loaded from `etc/apt/trusted.gpg` and `etc/apt/trusted.gpg.d` under `root`,
+    /// which is usually `/`. Missing files and directories are ignored.
+    pub fn from_apt_root(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref();
+        let mut keyring = Self::default();
+
+        let path = root.join("etc/apt/trusted.gpg");
+        if path.is_file() {
+            keyring.load_file(path)?;
+        }
+
+        let path = root.join("etc/apt/trusted.gpg.d");
+        if path.is_dir() {
+            keyring.load_directory(path)?;
+        }
+
+        Ok(keyring)
+    }
+
+    /// Obtain the keys in this keyring.
+    pub fn keys(&self) -> &[SignedPublicKey] {
+        &self.keys
+    }
+
+    /// Whether this keyring has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Add a key to this keyring.
+    pub fn add_key(&mut self, key: SignedPublicKey) {
+        self.keys.push(key);
+    }
+
+    /// Obtain a copy of this keyring with keys reduced by [minimal_public_key()].
+    pub fn to_minimal(&self) -> Self {
+        self.keys.iter().map(minimal_public_key).collect()
+    }
+
+    /// Serialize the keys in this keyring to a binary OpenPGP keyring.
+    ///
+    /// This is the format of `.gpg` files in `/usr/share/keyrings` and
+    /// `/etc/apt/trusted.gpg.d`.
+    pub fn to_binary(&self) -> Result<Vec<u8>> {
+        Ok(self.keys.to_bytes()?)
+    }
+
+    /// Serialize the keys in this keyring to a single ASCII armored block.
+    ///
+    /// This is the format of `.asc` files in `/etc/apt/trusted.gpg.d`.
+    pub fn to_armored(&self) -> Result<String> {
+        let mut data = vec![];
+        pgp::armor::write(&self.keys, BlockType::PublicKey, &mut data, None, true)?;
+
+        String::from_utf8(data)
+            .map_err(|e| DebianError::Pgp(pgp::errors::Error::Utf8Error(e.utf8_error())))
+    }
+
+    /// Load keys from ASCII armored data.
+    ///
+    /// The data can hold several concatenated armored blocks. Returns the number of keys
+    /// loaded.
+    pub fn load_armored(&mut self, data: &[u8]) -> Result<usize> {
+        let mut count = 0;
+
+        for block in armored_public_key_blocks(data) {
+            let (keys, _) = SignedPublicKey::from_armor_many(std::io::Cursor::new(block))?;
+            count += self.extend_keys(keys)?;
+        }
+
+        Ok(count)
+    }
+
+    /// Load keys from a binary OpenPGP keyring.
+    ///
+    /// Returns the number of keys loaded.
+    pub fn load_binary(&mut self, data: &[u8]) -> Result<usize> {
+        self.extend_keys(SignedPublicKey::from_bytes_many(std::io::Cursor::new(data)))
+    }
+
+    /// Load keys from a GnuPG keybox.
+    ///
+    /// Blobs holding X.509 certificates are ignored. Returns the number of keys loaded.
+    pub fn load_keybox(&mut self, data: &[u8]) -> Result<usize> {
+        let mut count = 0;
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let blob = data
+                .get(offset..offset + 5)
+                .ok_or(DebianError::KeyboxInvalid("truncated blob header"))?;
+            let length = u32::from_be_bytes([blob[0], blob[1], blob[2], blob[3]]) as usize;
+            let blob = data
+                .get(offset..offset + length)
+                .filter(|blob| blob.len() >= 5)
+                .ok_or(DebianError::KeyboxInvalid("invalid blob length"))?;
+
+            if blob[4] == KEYBOX_BLOB_OPENPGP {
+                // The blob type is followed by a version byte, 2 bytes of flags and the offset
+                // and length of the keyblock relative to the start of the blob.
+                let field = |start: usize| -> Result<usize> {
+                    let bytes = blob
+                        .get(start..start + 4)
+                        .ok_or(DebianError::KeyboxInvalid("truncated OpenPGP blob"))?;
+
+                    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+                };
+                let start = field(8)?;
+                let end = start + field(12)?;
+
+                let keyblock = blob
+                    .get(start..end)
+                    .ok_or(DebianError::KeyboxInvalid("keyblock outside of blob"))?;
+
+                count += self.load_binary(keyblock)?;
+            }
+
+            offset += length;
+        }
+
+        Ok(count)
+    }
+
+    /// Load keys from data in any supported format.
+    ///
+    /// Returns the number of keys loaded.
+    pub fn load_data(&mut self, data: &[u8]) -> Result<usize> {
+        if data.get(8..12) == Some(KEYBOX_MAGIC) {
+            self.load_keybox(data)
+        } else if data.starts_with(b"-----BEGIN PGP") {
+            self.load_armored(data)
+        } else {
+            self.load_binary(data)
+        }
+    }
+
+    /// Load keys from a file in any supported format.
+    ///
+    /// Returns the number of keys loaded.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| DebianError::RepositoryIoPath(format!("{}", path.display()), e))?;
+
+        self.load_data(&data)
+    }
+
+    /// Load keys from the `.asc` and `.gpg` files in a directory, as apt does for
+    /// `trusted.gpg.d`.
+    ///
+    /// Other files and subdirectories are ignored. Files are loaded in name order. Returns
+    /// the number of keys loaded.
+    pub fn load_directory(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+
+        let mut paths = std::fs::read_dir(path)
+            .map_err(|e| DebianError::RepositoryIoPath(format!("{}", path.display()), e))?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        paths.retain(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == "asc" || extension == "gpg")
+        });
+        paths.sort();
+
+        let mut count = 0;
+        for path in paths {
+            count += self.load_file(path)?;
+        }
+
+        Ok(count)
+    }
+
+    /// Verify PGP signatures against the keys in this keyring.
+    ///
+    /// Returns the hex key ID of the first key or subkey with a valid signature, or `None`
+    /// if no key in this keyring produced a valid signature.
+    ///
+    /// Any cryptographically valid signature is accepted. Use
+    /// [Self::verify_signatures_with_policy()] to also check expiration and revocation.
+    pub fn verify_signatures(&self, signatures: &CleartextSignatures) -> Option<String> {
+        self.verify_signatures_with_policy(signatures, &VerificationPolicy::default())
+            .ok()
+    }
+
+    /// Verify PGP signatures against the keys in this keyring, subject to a policy.
+    ///
+    /// Returns the hex key ID of the key or subkey that made the first acceptable
+    /// signature. Otherwise, if a key in this keyring made a signature,
+    /// [DebianError::PgpSignatureRejected] describes why the first such signature was
+    /// rejected. If there are no signatures from keys in this keyring, the error is
+    /// [DebianError::PgpSignatureUntrusted].
+    ///
+    /// Use [Self::verify_report()] to obtain the outcome of every signature.
+    pub fn verify_signatures_with_policy(
+        &self,
+        signatures: &CleartextSignatures,
+        policy: &VerificationPolicy,
+    ) -> Result<String> {
+        let mut signers = self.verify_trusted(signatures, &TrustPolicy::from(*policy))?;
+
+        Ok(signers.swap_remove(0).key_id)
+    }
+
+    /// Verify PGP signatures against the keys in this keyring, subject to a trust policy.
+    ///
+    /// Returns the identities of the distinct keys that made acceptable signatures. See
+    /// [TrustPolicy::evaluate()] for errors.
+    pub fn verify_trusted(
+        &self,
+        signatures: &CleartextSignatures,
+        policy: &TrustPolicy,
+    ) -> Result<Vec<SignerIdentity>> {
+        policy.evaluate(&self.trust_report(signatures, policy))
+    }
+
+    /// Verify detached PGP signatures over data, subject to a trust policy.
+    ///
+    /// Like [Self::verify_trusted()], for signatures stored apart from the data, e.g. in
+    /// `Release.gpg` or `.deb` files.
+    pub fn verify_detached_trusted(
+        &self,
+        signatures: &[Signature],
+        data: &[u8],
+        policy: &TrustPolicy,
+    ) -> Result<Vec<SignerIdentity>> {
+        let report = self.report(
+            signatures.iter(),
+            |sig, key, subkey| match subkey {
+                Some(subkey) => sig.verify(subkey, std::io::Cursor::new(data)),
+                None => sig.verify(key, std::io::Cursor::new(data)),
+            },
+            policy,
+        );
+
+        policy.evaluate(&report)
+    }
+
+    /// Verify each PGP signature against the keys in this keyring, subject to a policy.
+    ///
+    /// The report has an entry for every signature, identifying the key that made it and
+    /// why it was rejected, if it was.
+    ///
+    /// Signatures are matched to keys by the issuer key ID they name. Cryptographic
+    /// validity is established for all signatures from a key at once, so an invalid
+    /// signature from a key marks all signatures from that key invalid.
+    pub fn verify_report(
+        &self,
+        signatures: &CleartextSignatures,
+        policy: &VerificationPolicy,
+    ) -> VerificationReport {
+        self.trust_report(signatures, &TrustPolicy::from(*policy))
+    }
+
+    /// Verify each PGP signature against the keys in this keyring, subject to a trust
+    /// policy.
+    ///
+    /// Like [Self::verify_report()], but signatures must also satisfy the algorithm
+    /// requirements of the policy. Whether the document is trusted is decided by
+    /// [TrustPolicy::evaluate()].
+    pub fn trust_report(
+        &self,
+        signatures: &CleartextSignatures,
+        policy: &TrustPolicy,
+    ) -> VerificationReport {
+        self.report(
+            signatures.iter_signatures(),
+            |_, key, subkey| {
+                match subkey {
+                    Some(subkey) => signatures.verify(subkey),
+                    None => signatures.verify(key),
+                }
+                .map(|_| ())
+            },
+            policy,
+        )
+    }
+
+    /// Build a report on signatures, verifying them against a key with `verify`.
+    fn report<'a>(
+        &self,
+        signatures: impl Iterator<Item = &'a Signature>,
+        verify: impl Fn(
+            &Signature,
+            &SignedPublicKey,
+            Option<&SignedPublicSubKey>,
+        ) -> pgp::errors::Result<()>,
+        trust: &TrustPolicy,
+    ) -> VerificationReport {
+        let policy = &trust.verification;
+        let time = policy.time.unwrap_or_else(Utc::now);
+
+        let signatures = signatures
+            .map(|sig| {
+                let issuers = sig.issuer();
+
+                let signer = self.keys.iter().find_map(|key| {
+                    if issuers.contains(&&key.key_id()) {
+                        Some((key, None))
+                    } else {
+                        key.public_subkeys
+                            .iter()
+                            .find(|subkey| issuers.contains(&&subkey.key_id()))
+                            .map(|subkey| (key, Some(subkey)))
+                    }
+                });
+
+                let error = match signer {
+                    None => Some("no key in keyring".to_string()),
+                    Some((key, subkey)) => verify(sig, key, subkey)
+                        .map_err(|e| format!("invalid signature: {}", e))
+                        .and_then(|_| policy.check_key(key, time))
+                        .and_then(|_| match subkey {
+                            Some(subkey) => policy.check_subkey(key, subkey, time),
+                            None => Ok(()),
+                        })
+                        .and_then(|_| policy.check_signature(sig, time))
+                        .and_then(|_| match subkey {
+                            Some(subkey) => trust.check_algorithms(subkey, sig),
+                            None => trust.check_algorithms(key, sig),
+                        })
+                        .err(),
+                };
+
+                SignatureVerification {
+                    issuer_key_ids: issuers.iter().map(|id| format!("{:x}", id)).collect(),
+                    created: sig.created().copied(),
+                    signer: signer.map(|(key, subkey)| SignerIdentity::new(key, subkey)),
+                    error,
+                }
+            })
+            .collect();
+
+        VerificationReport { signatures }
+    }
+
+    fn extend_keys(
+        &mut self,
+        keys: impl Iterator<Item = pgp::errors::Result<SignedPublicKey>>,
+    ) -> Result<usize> {
+        let start = self.keys.len();
+
+        for key in keys {
+            self.keys.push(key?);
+        }
+
+        Ok(self.keys.len() - start)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::repository::release::ReleaseFile, crate::signing_key::DistroSigningKey};
+
+    const KEYBOX: &[u8] = include_bytes!("testdata/debian-11.kbx");
+
+    #[test]
+    fn load_formats() -> Result<()> {
+        let mut keyring = Keyring::default();
+        assert_eq!(keyring.load_data(KEYBOX)?, 2);
+
+        let release = ReleaseFile::from_armored_reader(std::io::Cursor::new(include_bytes!(
+            "testdata/inrelease-debian-bullseye"
+        )))?;
+        let signatures = release.signatures().unwrap();
+        assert!(keyring.verify_signatures(signatures).is_some());
+
+        let td = tempfile::TempDir::new()?;
+        let key = DistroSigningKey::Debian11Release.public_key();
+        std::fs::write(td.path().join("a.gpg"), key.to_bytes()?)?;
+        std::fs::write(
+            td.path().join("b.asc"),
+            DistroSigningKey::Debian11Archive.armored_public_key(),
+        )?;
+        std::fs::write(td.path().join("c.kbx"), KEYBOX)?;
+
+        let keyring = Keyring::from_apt_root(td.path())?;
+        assert!(keyring.is_empty());
+
+        let mut keyring = Keyring::default();
+        assert_eq!(keyring.load_directory(td.path())?, 2);
+        assert_eq!(keyring.keys()[0].key_id(), key.key_id());
+        assert!(keyring.verify_signatures(signatures).is_some());
+
+        let keyring = Keyring::from_iter([DistroSigningKey::Debian10Release.public_key()]);
+        assert!(keyring.verify_signatures(signatures).is_none());
+
+        assert!(matches!(
+            Keyring::default().load_keybox(&KEYBOX[0..40]),
+            Err(DebianError::KeyboxInvalid(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn verification_policy() -> Result<()> {
+        // Signed on 2024-06-01 by a key created on 2024-01-01 expiring on 2024-12-31 and by
+        // a key revoked on 2024-01-01.
+        let release = ReleaseFile::from_armored_reader(std::io::Cursor::new(include_bytes!(
+            "testdata/inrelease-policy"
+        )))?;
+        let signatures = release.signatures().unwrap();
+
+        let mut expiring = Keyring::default();
+        expiring.load_armored(include_bytes!("testdata/policy-expiring-key.asc"))?;
+        let mut revoked = Keyring::default();
+        revoked.load_armored(include_bytes!("testdata/policy-revoked-key.asc"))?;
+
+        let time = |s: &str| Some(DateTime::parse_from_rfc3339(s).unwrap().to_utc());
+        let policy = VerificationPolicy {
+            time: time("2024-07-01T00:00:00Z"),
+            ..VerificationPolicy::strict()
+        };
+
+        assert!(expiring.verify_signatures(signatures).is_some());
+        assert!(revoked.verify_signatures(signatures).is_some());
+
+        assert!(expiring
+            .verify_signatures_with_policy(signatures, &policy)
+            .is_ok());
+        assert!(matches!(
+            revoked.verify_signatures_with_policy(signatures, &policy),
+            Err(DebianError::PgpSignatureRejected(_, reason)) if reason == "key is revoked"
+        ));
+
+        let policy = VerificationPolicy {
+            time: time("2025-01-01T00:00:00Z"),
+            ..policy
+        };
+        assert!(matches!(
+            expiring.verify_signatures_with_policy(signatures, &policy),
+            Err(DebianError::PgpSignatureRejected(_, reason)) if reason.starts_with("key expired")
+        ));
+        assert!(expiring
+            .verify_signatures_with_policy(
+                signatures,
+                &VerificationPolicy {
+                    reject_expired_keys: false,
+                    ..policy
+                }
+            )
+            .is_ok());
+
+        let policy = VerificationPolicy {
+            time: time("2024-05-01T00:00:00Z"),
+            ..policy
+        };
+        assert!(matches!(
+            expiring.verify_signatures_with_policy(signatures, &policy),
+            Err(DebianError::PgpSignatureRejected(_, reason)) if reason.contains("in the future")
+        ));
+
+        let policy = VerificationPolicy {
+            time: time("2024-07-01T00:00:00Z"),
+            max_signature_age: Some(Duration::days(7)),
+            ..policy
+        };
+        assert!(matches!(
+            expiring.verify_signatures_with_policy(signatures, &policy),
+            Err(DebianError::PgpSignatureRejected(_, reason)) if reason.contains("too long ago")
+        ));
+
+        assert!(matches!(
+            Keyring::default().verify_signatures_with_policy(signatures, &policy),
+            Err(DebianError::PgpSignatureUntrusted)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn verification_report() -> Result<()> {
+        let release = ReleaseFile::from_armored_reader(std::io::Cursor::new(include_bytes!(
+            "testdata/inrelease-policy"
+        )))?;
+        let signatures = release.signatures().unwrap();
+
+        let mut keyring = Keyring::default();
+        keyring.load_armored(include_bytes!("testdata/policy-expiring-key.asc"))?;
+        let expiring = SignerIdentity::new(&keyring.keys()[0], None);
+        keyring.load_armored(include_bytes!("testdata/policy-revoked-key.asc"))?;
+
+        let policy = VerificationPolicy {
+            time: Some(
+                DateTime::parse_from_rfc3339("2024-07-01T00:00:00Z")
+                    .unwrap()
+                    .to_utc(),
+            ),
+            ..VerificationPolicy::strict()
+        };
+
+        let report = keyring.verify_report(signatures, &policy);
+        assert!(report.is_valid());
+        assert_eq!(report.signatures.len(), 2);
+        assert_eq!(report.valid_signers().collect::<Vec<_>>(), vec![&expiring]);
+        assert_eq!(expiring.fingerprint.len(), 40);
+        assert!(!expiring.user_ids.is_empty());
+
+        let rejected = report
+            .signatures
+            .iter()
+            .find(|sig| !sig.is_valid())
+            .unwrap();
+        assert!(rejected.signer.is_some());
+        assert_eq!(rejected.error.as_deref(), Some("key is revoked"));
+        assert_eq!(
+            rejected.created,
+            Some(
+                DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z")
+                    .unwrap()
+                    .to_utc()
+            )
+        );
+
+        assert!(report.is_signed_by(&expiring.fingerprint.to_ascii_lowercase()));
+        assert!(!report.is_signed_by(&rejected.signer.as_ref().unwrap().primary_fingerprint));
+
+        let report = Keyring::default().verify_report(signatures, &policy);
+        assert!(!report.is_valid());
+        assert!(report
+            .signatures
+            .iter()
+            .all(|sig| sig.signer.is_none() && !sig.issuer_key_ids.is_empty()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_export() -> Result<()> {
+        assert_eq!(
+            format_fingerprint("a4285295fc7b1a81600062a9605c66f00d6c9793"),
+            "A428 5295 FC7B 1A81 6000  62A9 605C 66F0 0D6C 9793"
+        );
+        assert_eq!(
+            normalize_fingerprint("A428 5295 FC7B 1A81 6000  62A9 605C 66F0 0D6C 9793"),
+            "A4285295FC7B1A81600062A9605C66F00D6C9793"
+        );
+
+        let armored = [
+            DistroSigningKey::Debian11Release,
+            DistroSigningKey::Debian11Archive,
+        ]
+        .map(|key| key.armored_public_key())
+        .join("\n");
+        let keys = parse_armored_public_keys(armored.as_bytes())?;
+        assert_eq!(keys.len(), 2);
+
+        let key = &keys[0];
+        assert_eq!(
+            fingerprint_hex(key),
+            "A4285295FC7B1A81600062A9605C66F00D6C9793"
+        );
+        assert_eq!(key_id_hex(key), "605c66f00d6c9793");
+
+        // The release key is certified by other Debian keys.
+        let minimal = minimal_public_key(key);
+        let signature_count = |key: &SignedPublicKey| {
+            key.details
+                .users
+                .iter()
+                .map(|user| user.signatures.len())
+                .sum::<usize>()
+        };
+        assert!(signature_count(key) > key.details.users.len());
+        assert_eq!(signature_count(&minimal), minimal.details.users.len());
+        minimal.verify()?;
+
+        let release = ReleaseFile::from_armored_reader(std::io::Cursor::new(include_bytes!(
+            "testdata/inrelease-debian-bullseye"
+        )))?;
+        let signatures = release.signatures().unwrap();
+
+        let keyring = Keyring::from_iter(keys).to_minimal();
+
+        let mut binary = Keyring::default();
+        assert_eq!(binary.load_binary(&keyring.to_binary()?)?, 2);
+        assert!(binary.verify_signatures(signatures).is_some());
+
+        let mut armored = Keyring::default();
+        assert_eq!(armored.load_armored(keyring.to_armored()?.as_bytes())?, 2);
+        assert!(armored.verify_signatures(signatures).is_some());
+        assert_eq!(
+            armored
+                .keys()
+                .iter()
+                .map(fingerprint_hex)
+                .collect::<Vec<_>>(),
+            keyring
+                .keys()
+                .iter()
+                .map(fingerprint_hex)
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+}
@@ -46,6 +46,8 @@ Read operations like parsing control files or version strings should be able to
 A `.deb` file defines a Debian package. Readers and writers of `.deb` files exist in the
 [deb] module. To read the contents of a `.deb` defining a binary package, use
 [deb::reader::BinaryPackageReader]. To create new `.deb` files, use [deb::builder::DebBuilder].
+To create source packages (a `.dsc` and its tarballs), use
+[source::builder::SourcePackageBuilder].
 
 A common primitive within Debian packaging is *control files*. These consist of *paragraphs*
 of key-value metadata. Low-level control file primitives are defined in the [control] module.
@@ -53,18 +55,32 @@ of key-value metadata. Low-level control file primitives are defined in the [con
 [control::ControlFile] provides an interface for a *control file*, which consists of multiple
 paragraphs. [control::ControlParagraphReader] implements a streaming reader of control files
 and [control::ControlParagraphAsyncReader] implements an asynchronous streaming reader.
+[control::ControlParagraphSliceReader] parses in-memory data without copying it, borrowing
+field names and values from the input.
+[control::ControlParagraph::into_interned()] uses static copies of well-known field names and
+values from the [intern] module, reducing the memory used by large package lists.
+[control::ControlParagraphAsyncWriter] streams paragraphs to an asynchronous writer.
+[control_editor::ControlFileEditor] edits control files while preserving the formatting
+and comments of untouched content. [control_format] normalizes paragraphs following
+`wrap-and-sort` conventions.
 
 There are different flavors of *control files* within Debian packaging.
 [binary_package_control::BinaryPackageControlFile] defines a *control file* for a binary package.
 This type provides helper functions for resolving common fields on binary control files.
 [debian_source_control::DebianSourceControlFile] defines a *control file* for a source package,
-as expressed in a `.dsc` file.
+as expressed in a `.dsc` file. [changes::DebianChangesFile] defines a `.changes` file
+describing an upload. [file_list] parses and writes the `Files` and `Checksums-*` fields
+listing the files these reference. [copyright::CopyrightFile] defines a machine-readable
+`debian/copyright` file. [maintainer::Contact] parses the people named in fields like
+`Maintainer` and `Uploaders`.
 
 There is a meta language for expressing dependencies between Debian packages. The
 [dependency] module defines types for parsing and writing this language. e.g.
 [dependency::DependencyList] represents a parsed list of dependencies like
 `libc6 (>= 2.4), libx11-6`. [dependency::PackageDependencyFields] represents a collection
 of control fields that define relationships between packages.
+[dependency::PackageRelations] holds the relationship fields of a binary package, keyed by
+[dependency::RelationshipField].
 
 The [package_version] module implements Debian package version string parsing,
 serialization, and comparison. [package_version::PackageVersion] is the main type used for this.
@@ -73,6 +89,13 @@ The [dependency_resolution] module implements functionality related to resolving
 e.g. [dependency_resolution::DependencyResolver] can be used to index known binary packages
 and find direct and transitive dependencies. This could be used as the basis for a package
 manager or other tool wishing to walk the dependency tree for a given package.
+[resolver::InstallResolver] goes further and computes a consistent installation set,
+choosing one version of each package and an installation order.
+[provides::ProvidesIndex] maps virtual package names to the packages providing them and
+selects a default provider. [reverse_dependencies::ReverseDependencyIndex] answers the
+inverse question of which packages depend on, recommend, or break a given package.
+[apt_preferences::AptPreferences] evaluates apt pin priorities to predict which version of
+a package apt would select.
 
 The [repository] module provides functionality related to Debian repositories, which are
 publications of Debian packages and metadata. The [repository::RepositoryRootReader] trait
@@ -83,6 +106,10 @@ to a repository. Repository interaction involves many support primitives.
 [repository::release::ReleaseFile] represents an `[In]Release` file. Support for verifying
 PGP signatures is provided. [repository::contents::ContentsFile] represents a `Contents`
 file.
+[package_list_cache::PackageListCache] reloads package lists parsed from a repository
+from a binary cache, avoiding parsing the index files again.
+[store::ContentStore] caches fetched artifacts on disk by content digest, deduplicating
+downloads across repositories.
 
 Concrete implementations of repository interaction exist. [repository::http::HttpRepositoryClient]
 enables reading from an HTTP-hosted repository (e.g. `http://archive.canonical.com/ubuntu`).
@@ -96,11 +123,16 @@ publishing Debian repositories.
 The [repository::copier] module contains functionality for copying Debian repositories.
 [repository::copier::RepositoryCopier] is the main type for copying Debian repositories.
 
+The [checks] module implements lintian-like quality checks of binary packages.
+[checks::PackageChecker] runs [checks::PackageCheck] implementations against `.deb` files or
+all binary packages in a repository.
+
 The [signing_key] module provides functionality related to PGP signing.
 [signing_key::DistroSigningKey] defines PGP public keys for well-known signing keys used by
 popular Linux distributions. [signing_key::signing_secret_key_params_builder()] and
 [signing_key::create_self_signed_key()] enable easily creating signing keys for Debian
-repositories.
+repositories. [keyring::Keyring] loads trusted keys from keyring files and directories, as
+apt does, and verifies `InRelease` signatures against them.
 
 Various other modules provide miscellaneous functionality. [io] defines I/O helpers, including
 stream adapters for validating content digests on read and computing content digests on write.
@@ -108,21 +140,45 @@ stream adapters for validating content digests on read and computing content dig
 # Crate Features
 
 The optional and enabled-by-default `http` feature enables HTTP client support for interacting
-with Debian repositories via HTTP.
+with Debian repositories via HTTP. It also enables the `key_fetch` module, which fetches PGP
+keys by fingerprint from keyservers and Web Key Directories.
+
+The optional `shlibdeps` feature enables the `shlibdeps` module, which derives package
+dependencies from the shared libraries that ELF binaries link against, like `dpkg-shlibdeps`.
 */
 
+pub mod apt_preferences;
 pub mod binary_package_control;
 pub mod binary_package_list;
 pub mod changelog;
+pub mod changes;
+pub mod checks;
 pub mod control;
+pub mod control_editor;
+pub mod control_format;
+pub mod copyright;
 pub mod deb;
 pub mod debian_source_control;
 pub mod debian_source_package_list;
 pub mod dependency;
 pub mod dependency_resolution;
 pub mod error;
+pub mod file_list;
+pub mod intern;
 pub mod io;
+#[cfg(feature = "http")]
+pub mod key_fetch;
+pub mod keyring;
+pub mod maintainer;
+pub mod package_list_cache;
 pub mod package_version;
+pub mod provides;
 pub mod repository;
+pub mod resolver;
+pub mod reverse_dependencies;
+#[cfg(feature = "shlibdeps")]
+pub mod shlibdeps;
 pub mod signing_key;
+pub mod source;
 pub mod source_package_control;
+pub mod store;
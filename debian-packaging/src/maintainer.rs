@@ -0,0 +1,216 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! People named in control fields.
+
+Fields like `Maintainer`, `Uploaders` and `Changed-By` name people in the RFC 5322 mailbox
+form `name <email>`. [Contact] represents one such person.
+
+Lists like `Uploaders` are comma delimited. Names containing a comma must be quoted (e.g.
+`"Doe, Jane" <jane@example.com>`), so splitting values on every comma is wrong.
+[split_contact_list()] splits on commas outside quoted strings and angle brackets and
+[parse_contact_list()] parses each item.
+
+See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#s-f-maintainer>.
+*/
+
+use {
+    crate::error::{DebianError, Result},
+    std::{
+        fmt::{Display, Formatter},
+        str::FromStr,
+    },
+};
+
+/// Characters requiring a name to be quoted, per the RFC 5322 `specials` production.
+///
+/// `.` is omitted. It is common in unquoted names (e.g. initials) and tolerated by Debian
+/// tooling.
+const SPECIALS: &[char] = &['(', ')', '<', '>', '[', ']', ':', ';', '@', '\\', ',', '"'];
+
+/// A person named in a control field, as `name <email>`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Contact {
+    /// The display name, with any quoting removed.
+    pub name: String,
+    /// The email address.
+    pub email: String,
+}
+
+impl Contact {
+    /// Construct an instance from a name and email address.
+    pub fn new(name: impl ToString, email: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            email: email.to_string(),
+        }
+    }
+
+    /// Parse a `name <email>` value.
+    ///
+    /// The name may be a quoted string, in which case `\` escapes the following character.
+    pub fn parse(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| DebianError::ContactParse(format!("{}: {}", reason, s));
+
+        let value = s.trim();
+        let value = value
+            .strip_suffix('>')
+            .ok_or_else(|| invalid("missing <email>"))?;
+
+        // Find the start of the address, which is the last `<` outside a quoted string.
+        let mut quoted = false;
+        let mut escaped = false;
+        let mut open = None;
+        for (i, c) in value.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                '<' if !quoted => open = Some(i),
+                _ => {}
+            }
+        }
+
+        let open = open.ok_or_else(|| invalid("missing <email>"))?;
+        let email = value[open + 1..].trim();
+        if email.is_empty() || !email.contains('@') || email.contains(['<', '>']) {
+            return Err(invalid("invalid email address"));
+        }
+
+        let name = value[..open].trim();
+        let name = if let Some(inner) = name.strip_prefix('"') {
+            let inner = inner
+                .strip_suffix('"')
+                .ok_or_else(|| invalid("unterminated quoted name"))?;
+
+            let mut res = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                if c == '\\' {
+                    res.extend(chars.next());
+                } else {
+                    res.push(c);
+                }
+            }
+
+            res
+        } else if name.contains('"') {
+            return Err(invalid("misplaced quote"));
+        } else {
+            name.to_string()
+        };
+
+        if name.is_empty() {
+            return Err(invalid("missing name"));
+        }
+
+        Ok(Self {
+            name,
+            email: email.to_string(),
+        })
+    }
+}
+
+impl Display for Contact {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.name.contains(SPECIALS) {
+            f.write_str("\"")?;
+            for c in self.name.chars() {
+                if matches!(c, '"' | '\\') {
+                    f.write_str("\\")?;
+                }
+                write!(f, "{}", c)?;
+            }
+            f.write_str("\"")?;
+        } else {
+            f.write_str(&self.name)?;
+        }
+
+        write!(f, " <{}>", self.email)
+    }
+}
+
+impl FromStr for Contact {
+    type Err = DebianError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Split a comma delimited list of contacts.
+///
+/// Commas within quoted strings and angle brackets don't delimit items. Items are trimmed
+/// and empty items are skipped.
+pub fn split_contact_list(s: &str) -> impl Iterator<Item = &str> {
+    let mut items = vec![];
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut bracketed = false;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '<' if !quoted => bracketed = true,
+            '>' if !quoted => bracketed = false,
+            ',' if !quoted && !bracketed => {
+                items.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&s[start..]);
+
+    items
+        .into_iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+}
+
+/// Parse a comma delimited list of contacts, such as an `Uploaders` field.
+pub fn parse_contact_list(s: &str) -> Result<Vec<Contact>> {
+    split_contact_list(s).map(Contact::parse).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn contacts() -> Result<()> {
+        let contacts = parse_contact_list(
+            "Jane Doe <jane@example.com>,\n \"Smith, John \\\"JS\\\"\" <john@example.com>, ,\
+             J. Random <jr@example.com>",
+        )?;
+        assert_eq!(
+            contacts,
+            vec![
+                Contact::new("Jane Doe", "jane@example.com"),
+                Contact::new("Smith, John \"JS\"", "john@example.com"),
+                Contact::new("J. Random", "jr@example.com"),
+            ]
+        );
+        assert_eq!(
+            contacts[1].to_string(),
+            "\"Smith, John \\\"JS\\\"\" <john@example.com>"
+        );
+        assert_eq!(contacts[2].to_string(), "J. Random <jr@example.com>");
+
+        assert_eq!(
+            Contact::parse("\"a <b>\" <c@example.com>")?,
+            Contact::new("a <b>", "c@example.com")
+        );
+
+        assert!(Contact::parse("Jane Doe").is_err());
+        assert!(Contact::parse("<jane@example.com>").is_err());
+        assert!(Contact::parse("Jane <nobody>").is_err());
+        assert!(Contact::parse("\"Jane <jane@example.com>").is_err());
+
+        Ok(())
+    }
+}
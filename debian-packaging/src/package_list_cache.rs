@@ -0,0 +1,455 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Binary caches of parsed package lists.
+
+Parsing the `Packages` and `Sources` files of a full archive means scanning hundreds of MB
+of text. This module stores parsed [BinaryPackageList] and [DebianSourcePackageList]
+instances in a compact binary form which can be reloaded in a fraction of the time.
+
+A cache records the [ContentDigest] of the index file it was parsed from. Callers compare
+it against the digest in the current `[In]Release` file via [PackageListCache::is_fresh()]
+to decide whether the cache can be used.
+
+Loading a cache validates its header. Paragraphs are decoded by
+[PackageListCache::binary_packages()] and [PackageListCache::source_packages()], which
+borrow field names and values from the cache rather than copying them.
+
+# Format
+
+All integers are unsigned LEB128 unless noted.
+
+* The magic `DPKGLIST`.
+* The format version, as a little-endian `u32`. This is [FORMAT_VERSION].
+* The [PackageListKind], as a byte.
+* The digest type, as a byte, followed by the length of the digest and its bytes.
+* The number of distinct field names, followed by each name as a length and UTF-8 bytes.
+* The number of paragraphs. Each paragraph is its number of fields followed by each field,
+  as the index of its name and its value as a length and UTF-8 bytes.
+
+Caches with a different format version are rejected. They should be rebuilt from the
+index files.
+*/
+
+use {
+    crate::{
+        binary_package_control::BinaryPackageControlFile,
+        binary_package_list::BinaryPackageList,
+        control::{ControlField, ControlParagraph},
+        debian_source_control::DebianSourceControlFile,
+        debian_source_package_list::DebianSourcePackageList,
+        error::{DebianError, Result},
+        io::ContentDigest,
+    },
+    std::{
+        borrow::Cow,
+        collections::HashMap,
+        io::{Read, Write},
+    },
+};
+
+/// Identifies package list caches.
+const MAGIC: &[u8; 8] = b"DPKGLIST";
+
+/// The version of the cache format written by this crate.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The type of package list in a cache.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PackageListKind {
+    /// A [BinaryPackageList], from a `Packages` file.
+    Binary,
+    /// A [DebianSourcePackageList], from a `Sources` file.
+    Source,
+}
+
+impl PackageListKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Binary => 1,
+            Self::Source => 2,
+        }
+    }
+
+    fn from_byte(v: u8) -> Result<Self> {
+        match v {
+            1 => Ok(Self::Binary),
+            2 => Ok(Self::Source),
+            _ => Err(DebianError::PackageListCacheInvalid(
+                "unknown package list kind",
+            )),
+        }
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+
+        if v == 0 {
+            buf.push(byte);
+            return;
+        }
+
+        buf.push(byte | 0x80);
+    }
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    write_varint(buf, s.len() as u64);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Write paragraphs to a cache.
+fn write_cache<'p, 'a: 'p>(
+    writer: &mut impl Write,
+    kind: PackageListKind,
+    digest: &ContentDigest,
+    paragraphs: impl Iterator<Item = &'p ControlParagraph<'a>> + Clone,
+) -> Result<()> {
+    let mut buf = MAGIC.to_vec();
+    buf.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    buf.push(kind.to_byte());
+
+    buf.push(match digest {
+        ContentDigest::Md5(_) => 1,
+        ContentDigest::Sha1(_) => 2,
+        ContentDigest::Sha256(_) => 3,
+        ContentDigest::Sha512(_) => 4,
+    });
+    write_varint(&mut buf, digest.digest_bytes().len() as u64);
+    buf.extend_from_slice(digest.digest_bytes());
+
+    let mut names = HashMap::new();
+    for field in paragraphs.clone().flat_map(|p| p.iter_fields()) {
+        let next = names.len();
+        names.entry(field.name()).or_insert(next);
+    }
+
+    let mut ordered = vec![""; names.len()];
+    for (name, index) in &names {
+        ordered[*index] = name;
+    }
+
+    write_varint(&mut buf, ordered.len() as u64);
+    for name in ordered {
+        write_str(&mut buf, name);
+    }
+
+    let count = paragraphs.clone().count();
+    write_varint(&mut buf, count as u64);
+
+    for paragraph in paragraphs {
+        write_varint(&mut buf, paragraph.iter_fields().count() as u64);
+
+        for field in paragraph.iter_fields() {
+            write_varint(&mut buf, names[field.name()] as u64);
+            write_str(&mut buf, field.value_str());
+        }
+
+        // Bound memory use by writing in pieces.
+        if buf.len() > 1024 * 1024 {
+            writer.write_all(&buf)?;
+            buf.clear();
+        }
+    }
+
+    writer.write_all(&buf)?;
+
+    Ok(())
+}
+
+/// Write a cache of a [BinaryPackageList] parsed from an index with the given digest.
+pub fn write_binary_package_list(
+    writer: &mut impl Write,
+    digest: &ContentDigest,
+    packages: &BinaryPackageList,
+) -> Result<()> {
+    write_cache(
+        writer,
+        PackageListKind::Binary,
+        digest,
+        packages.iter().map(|cf| &**cf),
+    )
+}
+
+/// Write a cache of a [DebianSourcePackageList] parsed from an index with the given digest.
+pub fn write_source_package_list(
+    writer: &mut impl Write,
+    digest: &ContentDigest,
+    packages: &DebianSourcePackageList,
+) -> Result<()> {
+    write_cache(
+        writer,
+        PackageListKind::Source,
+        digest,
+        packages.iter().map(|cf| &**cf),
+    )
+}
+
+/// Decodes values from cache data.
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn truncated() -> DebianError {
+        DebianError::PackageListCacheInvalid("truncated data")
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(Self::truncated());
+        }
+
+        let (res, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Ok(res)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut res = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            res |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(res);
+            }
+        }
+
+        Err(DebianError::PackageListCacheInvalid("invalid integer"))
+    }
+
+    /// Read a count of items, each occupying at least one byte.
+    fn count(&mut self) -> Result<usize> {
+        let count = self.varint()?;
+
+        if count > self.data.len() as u64 {
+            Err(Self::truncated())
+        } else {
+            Ok(count as usize)
+        }
+    }
+
+    fn str(&mut self) -> Result<&'a str> {
+        let len = self.count()?;
+
+        std::str::from_utf8(self.bytes(len)?)
+            .map_err(|_| DebianError::PackageListCacheInvalid("invalid UTF-8"))
+    }
+}
+
+/// A loaded package list cache.
+///
+/// See the [module documentation](self) for details.
+#[derive(Clone)]
+pub struct PackageListCache {
+    data: Vec<u8>,
+    kind: PackageListKind,
+    digest: ContentDigest,
+    /// Offset of the field names table.
+    body: usize,
+}
+
+impl PackageListCache {
+    /// Load a cache from its serialized bytes.
+    ///
+    /// The header is validated. Errors in the rest of the data are reported when
+    /// packages are decoded.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let mut decoder = Decoder { data: &data };
+
+        if decoder.bytes(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(DebianError::PackageListCacheInvalid(
+                "not a package list cache",
+            ));
+        }
+
+        let version = u32::from_le_bytes(decoder.bytes(4)?.try_into().expect("length is 4"));
+        if version != FORMAT_VERSION {
+            return Err(DebianError::PackageListCacheVersion(version));
+        }
+
+        let kind = PackageListKind::from_byte(decoder.byte()?)?;
+
+        let digest_type = decoder.byte()?;
+        let len = decoder.count()?;
+        let digest = decoder.bytes(len)?.to_vec();
+        let digest = match digest_type {
+            1 => ContentDigest::Md5(digest),
+            2 => ContentDigest::Sha1(digest),
+            3 => ContentDigest::Sha256(digest),
+            4 => ContentDigest::Sha512(digest),
+            _ => return Err(DebianError::PackageListCacheInvalid("unknown digest type")),
+        };
+
+        let body = data.len() - decoder.data.len();
+
+        Ok(Self {
+            data,
+            kind,
+            digest,
+            body,
+        })
+    }
+
+    /// Load a cache from a reader.
+    pub fn from_reader(reader: &mut impl Read) -> Result<Self> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        Self::from_bytes(data)
+    }
+
+    /// The type of package list in this cache.
+    pub fn kind(&self) -> PackageListKind {
+        self.kind
+    }
+
+    /// The digest of the index file the cached packages were parsed from.
+    pub fn digest(&self) -> &ContentDigest {
+        &self.digest
+    }
+
+    /// Whether this cache was built from an index file with the given digest.
+    ///
+    /// Digests of different types never match.
+    pub fn is_fresh(&self, digest: &ContentDigest) -> bool {
+        &self.digest == digest
+    }
+
+    /// Decode the paragraphs in this cache.
+    fn paragraphs(&self, kind: PackageListKind) -> Result<Vec<ControlParagraph<'_>>> {
+        if self.kind != kind {
+            return Err(DebianError::PackageListCacheInvalid(
+                "cache holds a different kind of package list",
+            ));
+        }
+
+        let mut decoder = Decoder {
+            data: &self.data[self.body..],
+        };
+
+        let names = (0..decoder.count()?)
+            .map(|_| decoder.str())
+            .collect::<Result<Vec<_>>>()?;
+
+        let count = decoder.count()?;
+        let mut res = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let fields = decoder.count()?;
+            let mut paragraph = ControlParagraph::with_capacity(fields);
+            let mut seen = 0;
+
+            for _ in 0..fields {
+                let name = names.get(decoder.varint()? as usize).ok_or(
+                    DebianError::PackageListCacheInvalid("invalid field name index"),
+                )?;
+                let value = decoder.str()?;
+
+                paragraph.add_field(
+                    &mut seen,
+                    ControlField::new(Cow::Borrowed(name), Cow::Borrowed(value)),
+                );
+            }
+
+            res.push(paragraph);
+        }
+
+        if !decoder.data.is_empty() {
+            return Err(DebianError::PackageListCacheInvalid("trailing data"));
+        }
+
+        Ok(res)
+    }
+
+    /// Decode the cached [BinaryPackageList].
+    ///
+    /// Fields borrow from this instance. Use [BinaryPackageList::into_interned()] to detach
+    /// them.
+    pub fn binary_packages(&self) -> Result<BinaryPackageList<'_>> {
+        let mut res = BinaryPackageList::default();
+        res.extend(
+            self.paragraphs(PackageListKind::Binary)?
+                .into_iter()
+                .map(BinaryPackageControlFile::from),
+        );
+
+        Ok(res)
+    }
+
+    /// Decode the cached [DebianSourcePackageList].
+    ///
+    /// Fields borrow from this instance.
+    pub fn source_packages(&self) -> Result<DebianSourcePackageList<'_>> {
+        let mut res = DebianSourcePackageList::default();
+        res.extend(
+            self.paragraphs(PackageListKind::Source)?
+                .into_iter()
+                .map(DebianSourceControlFile::from),
+        );
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::control::ControlParagraphReader};
+
+    const PACKAGES: &str = "Package: foo\nVersion: 1.0\nDescription: foo\n more\n\n\
+                            Package: bar\nVersion: 2.0\nX-Custom: \u{e9}\n";
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let mut packages = BinaryPackageList::default();
+        packages.extend(
+            ControlParagraphReader::new(PACKAGES.as_bytes())
+                .map(|p| p.map(BinaryPackageControlFile::from))
+                .collect::<Result<Vec<_>>>()?,
+        );
+        let digest = ContentDigest::sha256_hex(&"ab".repeat(32))?;
+
+        let mut data = vec![];
+        write_binary_package_list(&mut data, &digest, &packages)?;
+
+        let cache = PackageListCache::from_bytes(data.clone())?;
+        assert_eq!(cache.kind(), PackageListKind::Binary);
+        assert!(cache.is_fresh(&digest));
+        assert!(!cache.is_fresh(&ContentDigest::sha256_hex(&"cd".repeat(32))?));
+
+        let loaded = cache.binary_packages()?;
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            packages.iter().collect::<Vec<_>>()
+        );
+        assert!(matches!(
+            cache.source_packages(),
+            Err(DebianError::PackageListCacheInvalid(_))
+        ));
+
+        let mut truncated = data.clone();
+        truncated.pop();
+        assert!(PackageListCache::from_bytes(truncated)?
+            .binary_packages()
+            .is_err());
+
+        data[8] = 2;
+        assert!(matches!(
+            PackageListCache::from_bytes(data),
+            Err(DebianError::PackageListCacheVersion(2))
+        ));
+
+        Ok(())
+    }
+}
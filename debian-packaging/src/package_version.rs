@@ -39,6 +39,8 @@ use {
 ///
 /// assert!(v < PackageVersion::parse("1:4.7.0+dfsg1-3").unwrap());
 /// ```
+///
+/// With the `serde` feature, instances serialize as their string representation.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct PackageVersion {
     epoch: Option<u32>,
@@ -148,6 +150,180 @@ impl PackageVersion {
     pub fn debian_revision(&self) -> Option<&str> {
         self.debian_revision.as_deref()
     }
+
+    /// The component bump operations modify: the debian revision, or upstream version if absent.
+    fn last_component_mut(&mut self) -> &mut String {
+        match &mut self.debian_revision {
+            Some(revision) => revision,
+            None => &mut self.upstream_version,
+        }
+    }
+
+    /// The binNMU number of this version, if it is a binNMU (a `+bN` suffix).
+    pub fn binnmu(&self) -> Option<u32> {
+        let component = self
+            .debian_revision
+            .as_deref()
+            .unwrap_or(&self.upstream_version);
+        let (_, number) = component.rsplit_once("+b")?;
+
+        if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+            u32::from_str(number).ok()
+        } else {
+            None
+        }
+    }
+
+    /// This version with any binNMU suffix removed.
+    ///
+    /// This is the version of the source package a binNMU was built from.
+    pub fn without_binnmu(&self) -> Self {
+        let mut res = self.clone();
+
+        if let Some(number) = self.binnmu() {
+            let component = res.last_component_mut();
+            let len = component.len() - format!("+b{}", number).len();
+            component.truncate(len);
+        }
+
+        res
+    }
+
+    /// Construct the version of the next binNMU of this version.
+    ///
+    /// `1.0-2` becomes `1.0-2+b1` and `1.0-2+b1` becomes `1.0-2+b2`.
+    pub fn next_binnmu(&self) -> Result<Self> {
+        let number = self
+            .binnmu()
+            .unwrap_or_default()
+            .checked_add(1)
+            .ok_or_else(|| DebianError::VersionNumberOverflow(self.to_string()))?;
+        let mut res = self.without_binnmu();
+        res.last_component_mut().push_str(&format!("+b{}", number));
+
+        Ok(res)
+    }
+
+    /// Construct the version of the next upload of this version.
+    ///
+    /// The trailing number of the debian revision is incremented, e.g. `1.0-2` becomes
+    /// `1.0-3` and `1.0-1ubuntu1` becomes `1.0-1ubuntu2`. If the revision doesn't end in a
+    /// number, `1` is appended. Native versions lacking a debian revision have their upstream
+    /// version incremented instead. Any binNMU suffix is removed first.
+    pub fn increment_debian_revision(&self) -> Result<Self> {
+        let mut res = self.without_binnmu();
+        let component = res.last_component_mut();
+
+        let digits = component
+            .chars()
+            .rev()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let (prefix, number) = component.split_at(component.len() - digits);
+
+        *component = if number.is_empty() {
+            format!("{}1", component)
+        } else {
+            let number = u64::from_str(number)
+                .ok()
+                .and_then(|number| number.checked_add(1))
+                .ok_or_else(|| DebianError::VersionNumberOverflow(self.to_string()))?;
+            format!("{}{}", prefix, number)
+        };
+
+        Ok(res)
+    }
+
+    /// Append a suffix to the debian revision, or upstream version if absent.
+    ///
+    /// The suffix may only contain alphanumerics and `+`, `.` and `~`.
+    pub fn append_suffix(&self, suffix: &str) -> Result<Self> {
+        if !suffix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '~'))
+        {
+            return Err(DebianError::DebianRevisionIllegalChar(format!(
+                "{}{}",
+                self, suffix
+            )));
+        }
+
+        let mut res = self.clone();
+        res.last_component_mut().push_str(suffix);
+
+        Ok(res)
+    }
+
+    /// Construct the version of the next stable update for a Debian release.
+    ///
+    /// A `+debNuM` suffix is appended, e.g. `1.0-2` becomes `1.0-2+deb11u1` for
+    /// `release` `11`. If the version is already a stable update for the release, `M` is
+    /// incremented. Any binNMU suffix is removed first.
+    pub fn next_stable_update(&self, release: u32) -> Result<Self> {
+        let mut res = self.without_binnmu();
+        let marker = format!("+deb{}u", release);
+        let component = res.last_component_mut();
+
+        let existing = component.rfind(&marker).and_then(|pos| {
+            let number = &component[pos + marker.len()..];
+            if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+                u32::from_str(number).ok().map(|number| (pos, number))
+            } else {
+                None
+            }
+        });
+
+        match existing {
+            Some((pos, number)) => {
+                let number = number
+                    .checked_add(1)
+                    .ok_or_else(|| DebianError::VersionNumberOverflow(self.to_string()))?;
+                component.truncate(pos);
+                component.push_str(&format!("{}{}", marker, number));
+            }
+            None => {
+                component.push_str(&format!("{}1", marker));
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Sort versions in ascending order.
+pub fn sort_versions(versions: &mut [PackageVersion]) {
+    versions.sort();
+}
+
+/// Sort items by a version derived from each, in ascending order.
+///
+/// The sort is stable.
+pub fn sort_by_version<T>(items: &mut [T], key: impl Fn(&T) -> &PackageVersion) {
+    items.sort_by(|a, b| key(a).cmp(key(b)));
+}
+
+/// Sort version strings in ascending order.
+///
+/// All strings are parsed before sorting and an error is returned if any is invalid.
+pub fn sort_version_strings<S: AsRef<str>>(versions: &mut [S]) -> Result<()> {
+    let parsed = versions
+        .iter()
+        .map(|s| PackageVersion::parse(s.as_ref()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut order = (0..versions.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| parsed[*a].cmp(&parsed[*b]));
+
+    // Apply the permutation by swapping each element into place.
+    for i in 0..order.len() {
+        let mut j = order[i];
+        while j < i {
+            j = order[j];
+        }
+        versions.swap(i, j);
+    }
+
+    Ok(())
 }
 
 impl Display for PackageVersion {
@@ -340,6 +516,26 @@ impl Ord for PackageVersion {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for PackageVersion {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PackageVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -419,6 +615,68 @@ mod test {
         assert_eq!(compare_component("1.0~beta1", "1.0"), Ordering::Less);
     }
 
+    #[test]
+    fn bump() -> Result<()> {
+        let bump = |s: &str, f: fn(&PackageVersion) -> Result<PackageVersion>| {
+            let v = PackageVersion::parse(s).unwrap();
+            let bumped = f(&v).unwrap();
+            assert!(bumped > v);
+            bumped.to_string()
+        };
+
+        assert_eq!(bump("1.0-2", |v| v.increment_debian_revision()), "1.0-3");
+        assert_eq!(
+            bump("1:1.0-1ubuntu9", |v| v.increment_debian_revision()),
+            "1:1.0-1ubuntu10"
+        );
+        assert_eq!(bump("1.0-2a", |v| v.increment_debian_revision()), "1.0-2a1");
+        assert_eq!(bump("1.9", |v| v.increment_debian_revision()), "1.10");
+        assert_eq!(bump("1.0-2+b3", |v| v.increment_debian_revision()), "1.0-3");
+        assert_eq!(bump("1.0-2", |v| v.next_binnmu()), "1.0-2+b1");
+        assert_eq!(bump("1.0-2+b1", |v| v.next_binnmu()), "1.0-2+b2");
+        assert_eq!(bump("1.0", |v| v.next_binnmu()), "1.0+b1");
+        assert_eq!(bump("1.0-2", |v| v.next_stable_update(11)), "1.0-2+deb11u1");
+        assert_eq!(
+            bump("1.0-2+deb11u1+b1", |v| v.next_stable_update(11)),
+            "1.0-2+deb11u2"
+        );
+        assert_eq!(
+            bump("1.0-2+deb11u1", |v| v.next_stable_update(12)),
+            "1.0-2+deb11u1+deb12u1"
+        );
+
+        for (version, f) in [
+            (
+                "1.0-18446744073709551615",
+                PackageVersion::increment_debian_revision as fn(&PackageVersion) -> _,
+            ),
+            (
+                "1.0-99999999999999999999",
+                PackageVersion::increment_debian_revision,
+            ),
+            ("1.0-2+b4294967295", PackageVersion::next_binnmu),
+            ("1.0-2+deb11u4294967295", |v| v.next_stable_update(11)),
+        ] {
+            assert!(matches!(
+                f(&PackageVersion::parse(version)?),
+                Err(DebianError::VersionNumberOverflow(_))
+            ));
+        }
+
+        let v = PackageVersion::parse("1.0-2")?;
+        assert_eq!(v.append_suffix("~bpo11+1")?.to_string(), "1.0-2~bpo11+1");
+        assert!(v.append_suffix("-1").is_err());
+        assert_eq!(PackageVersion::parse("1.0-2+b7")?.binnmu(), Some(7));
+        assert_eq!(v.binnmu(), None);
+
+        let mut versions = vec!["1.0-1", "1:0.1", "1.0~rc1-1", "1.0-1+b1"];
+        sort_version_strings(&mut versions)?;
+        assert_eq!(versions, vec!["1.0~rc1-1", "1.0-1", "1.0-1+b1", "1:0.1"]);
+        assert!(sort_version_strings(&mut ["1.0", "a:b"]).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn compare_version() {
         assert_eq!(
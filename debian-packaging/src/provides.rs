@@ -0,0 +1,266 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Virtual package resolution.
+
+Binary packages can declare that they provide other, virtual, packages via the `Provides`
+field, optionally with a version (e.g. `Provides: foo (= 1.0)`). [ProvidesIndex] maps
+virtual package names to the concrete packages providing them.
+
+When multiple packages provide a virtual package, a default provider is chosen by
+applying a sequence of [ProviderTieBreaker]s. By default providers with a more important
+`Priority` are preferred, then higher versions, then names sorting first.
+*/
+
+use {
+    crate::{
+        binary_package_control::BinaryPackageControlFile,
+        binary_package_list::BinaryPackageList,
+        dependency::{DependencyVersionConstraint, SingleDependency},
+        error::Result,
+        package_version::PackageVersion,
+    },
+    std::{cmp::Ordering, collections::HashMap, sync::Arc},
+};
+
+/// A concrete package providing a virtual package.
+#[derive(Clone, Debug)]
+pub struct Provider<'file, 'data: 'file> {
+    /// The providing package.
+    pub package: &'file BinaryPackageControlFile<'data>,
+    /// Name of the providing package.
+    pub name: String,
+    /// Version of the providing package.
+    pub version: PackageVersion,
+    /// The provided version of the virtual package, if versioned.
+    pub provided_version: Option<DependencyVersionConstraint>,
+}
+
+/// Function comparing providers. [Ordering::Less] means the first is preferred.
+pub type ProviderComparator =
+    Arc<dyn Fn(&Provider<'_, '_>, &Provider<'_, '_>) -> Ordering + Send + Sync>;
+
+/// A criteria for ordering providers of the same virtual package.
+#[derive(Clone)]
+pub enum ProviderTieBreaker {
+    /// Prefer providers with a more important `Priority`.
+    Priority,
+    /// Prefer providers with higher versions.
+    Version,
+    /// Prefer providers whose names sort first.
+    Name,
+    /// A custom comparison.
+    Custom(ProviderComparator),
+}
+
+impl std::fmt::Debug for ProviderTieBreaker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Priority => write!(f, "Priority"),
+            Self::Version => write!(f, "Version"),
+            Self::Name => write!(f, "Name"),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl ProviderTieBreaker {
+    /// Compare two providers. [Ordering::Less] means `a` is preferred.
+    pub fn compare(&self, a: &Provider, b: &Provider) -> Ordering {
+        match self {
+            Self::Priority => {
+                // Missing priorities rank after all others.
+                let key = |p: &Provider| {
+                    let priority = p.package.typed_priority();
+                    (priority.is_none(), priority)
+                };
+                key(a).cmp(&key(b))
+            }
+            Self::Version => b.version.cmp(&a.version),
+            Self::Name => a.name.cmp(&b.name),
+            Self::Custom(f) => f(a, b),
+        }
+    }
+}
+
+/// Maps virtual package names to the packages providing them.
+#[derive(Clone, Debug)]
+pub struct ProvidesIndex<'file, 'data: 'file> {
+    providers: HashMap<String, Vec<Provider<'file, 'data>>>,
+    tie_breakers: Vec<ProviderTieBreaker>,
+}
+
+impl<'file, 'data: 'file> Default for ProvidesIndex<'file, 'data> {
+    fn default() -> Self {
+        Self {
+            providers: HashMap::new(),
+            tie_breakers: vec![
+                ProviderTieBreaker::Priority,
+                ProviderTieBreaker::Version,
+                ProviderTieBreaker::Name,
+            ],
+        }
+    }
+}
+
+impl<'file, 'data: 'file> ProvidesIndex<'file, 'data> {
+    /// Set the criteria used to order providers, most significant first.
+    ///
+    /// Providers equal under all criteria retain the order they were loaded in.
+    pub fn set_tie_breakers(&mut self, tie_breakers: Vec<ProviderTieBreaker>) {
+        self.tie_breakers = tie_breakers;
+    }
+
+    /// Load all packages in a [BinaryPackageList] into the index.
+    pub fn load_binary_package_list(
+        &mut self,
+        list: &'file BinaryPackageList<'data>,
+    ) -> Result<()> {
+        self.load_binary_packages(list.iter())
+    }
+
+    /// Load an iterable of binary packages into the index.
+    ///
+    /// Only packages having a `Provides` field are retained.
+    pub fn load_binary_packages(
+        &mut self,
+        files: impl Iterator<Item = &'file BinaryPackageControlFile<'data>>,
+    ) -> Result<()> {
+        for cf in files {
+            let provides = match cf.package_dependency_fields()?.provides {
+                Some(provides) => provides,
+                None => continue,
+            };
+
+            let name = cf.package()?;
+            let version = cf.version()?;
+
+            for variants in provides.requirements() {
+                for dep in variants.iter() {
+                    self.providers
+                        .entry(dep.package.clone())
+                        .or_default()
+                        .push(Provider {
+                            package: cf,
+                            name: name.to_string(),
+                            version: version.clone(),
+                            provided_version: dep.version_constraint.clone(),
+                        });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether any package provides a virtual package.
+    pub fn is_provided(&self, name: &str) -> bool {
+        self.providers.contains_key(name)
+    }
+
+    /// Iterate over names of all provided virtual packages.
+    pub fn virtual_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.providers.keys().map(|k| k.as_str())
+    }
+
+    /// Compare providers using the configured tie breakers.
+    pub fn compare(&self, a: &Provider, b: &Provider) -> Ordering {
+        self.tie_breakers
+            .iter()
+            .map(|t| t.compare(a, b))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// All providers of a virtual package, most preferred first.
+    pub fn providers(&self, name: &str) -> Vec<&Provider<'file, 'data>> {
+        let mut res = self
+            .providers
+            .get(name)
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        res.sort_by(|a, b| self.compare(a, b));
+
+        res
+    }
+
+    /// Providers satisfying a dependency expression, most preferred first.
+    ///
+    /// The expression's version constraint is evaluated against provided versions.
+    pub fn providers_satisfying(&self, dep: &SingleDependency) -> Vec<&Provider<'file, 'data>> {
+        let mut res = self.providers(&dep.package);
+        res.retain(|p| dep.package_satisfies_virtual(&dep.package, p.provided_version.as_ref()));
+
+        res
+    }
+
+    /// The most preferred provider satisfying a dependency expression.
+    pub fn default_provider(&self, dep: &SingleDependency) -> Option<&Provider<'file, 'data>> {
+        self.providers_satisfying(dep).into_iter().next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::control::ControlParagraph};
+
+    fn package(fields: &[(&str, &str)]) -> BinaryPackageControlFile<'static> {
+        let mut para = ControlParagraph::default();
+        for (name, value) in fields {
+            para.set_field_from_string(name.to_string().into(), value.to_string().into());
+        }
+
+        para.into()
+    }
+
+    #[test]
+    fn providers() -> Result<()> {
+        let mut list = BinaryPackageList::default();
+        list.push(package(&[
+            ("Package", "exim4"),
+            ("Version", "4.0"),
+            ("Priority", "optional"),
+            ("Provides", "mail-transport-agent"),
+        ]));
+        list.push(package(&[
+            ("Package", "postfix"),
+            ("Version", "3.0"),
+            ("Priority", "standard"),
+            ("Provides", "mail-transport-agent"),
+        ]));
+        list.push(package(&[
+            ("Package", "libfoo-compat"),
+            ("Version", "1.0"),
+            ("Provides", "libfoo (= 1.5)"),
+        ]));
+
+        let mut index = list.provides_index()?;
+        assert!(index.is_provided("libfoo"));
+        assert!(!index.is_provided("exim4"));
+
+        let mta = SingleDependency::parse("mail-transport-agent")?;
+        assert_eq!(index.default_provider(&mta).unwrap().name, "postfix");
+
+        index.set_tie_breakers(vec![ProviderTieBreaker::Version]);
+        assert_eq!(index.default_provider(&mta).unwrap().name, "exim4");
+
+        index.set_tie_breakers(vec![ProviderTieBreaker::Custom(Arc::new(|a, b| {
+            (a.name != "postfix").cmp(&(b.name != "postfix"))
+        }))]);
+        assert_eq!(index.default_provider(&mta).unwrap().name, "postfix");
+
+        assert_eq!(
+            index
+                .providers_satisfying(&SingleDependency::parse("libfoo (>= 1.2)")?)
+                .len(),
+            1
+        );
+        assert!(index
+            .default_provider(&SingleDependency::parse("libfoo (>= 2)")?)
+            .is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Repository publish audit logs.
+
+An audit log is a JSON manifest recording every path written to a repository during
+publish and copy operations, along with the size, SHA-256 digest, and time of each write.
+Each publish/copy operation appends an [AuditLogEntry] to the [AuditLog]. Existing entries
+are never modified, so consumers can diff successive versions of the log to see exactly
+what changed in each publish and can verify the log was only appended to via
+[AuditLog::is_append_of()].
+
+[AuditingWriter] is a [RepositoryWriter] that wraps another writer and records each
+write. [crate::repository::builder::RepositoryBuilder] and
+[crate::repository::copier::RepositoryCopier] can be configured to use it automatically.
+
+The log is written alongside the repository by [AuditLog::publish()]. When a signing key is
+provided, a PGP cleartext signed copy of the log is written to a `.asc` sibling path.
+*/
+
+use {
+    crate::{
+        error::{DebianError, Result},
+        io::{ContentDigest, DataResolver},
+        repository::{RepositoryPathVerification, RepositoryWrite, RepositoryWriter},
+        signing_key::signing_hash_algorithm,
+    },
+    async_trait::async_trait,
+    chrono::{SecondsFormat, Utc},
+    futures::{AsyncRead, AsyncReadExt},
+    pgp::types::SecretKeyTrait,
+    pgp_cleartext::cleartext_sign,
+    serde::{Deserialize, Serialize},
+    std::{
+        borrow::Cow,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+    },
+};
+
+/// A path written as part of a publish operation.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AuditedPath {
+    /// The path relative to the repository root.
+    pub path: String,
+    /// The size in bytes of the written content.
+    pub size: u64,
+    /// The hex encoded SHA-256 digest of the written content.
+    pub sha256: String,
+    /// RFC 3339 timestamp of when the write completed.
+    pub timestamp: String,
+}
+
+/// Describes a single publish or copy operation in an [AuditLog].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AuditLogEntry {
+    /// The kind of operation that was performed. e.g. `publish` or `copy`.
+    pub operation: String,
+    /// RFC 3339 timestamp of when the operation started.
+    pub started: String,
+    /// RFC 3339 timestamp of when the operation finished.
+    pub finished: String,
+    /// Paths that were written, sorted by path.
+    pub paths: Vec<AuditedPath>,
+}
+
+/// An append-only log of repository publish operations.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AuditLog {
+    /// Publish operations, in the order they were performed.
+    pub entries: Vec<AuditLogEntry>,
+}
+
+impl AuditLog {
+    /// Parse an instance from JSON data.
+    pub fn from_json_slice(data: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(data)?)
+    }
+
+    /// Load an existing audit log from a [DataResolver].
+    ///
+    /// If the path does not exist, an empty log is returned.
+    pub async fn load(resolver: &(impl DataResolver + ?Sized), path: &str) -> Result<Self> {
+        let mut reader = match resolver.get_path(path).await {
+            Ok(reader) => reader,
+            Err(DebianError::RepositoryIoPath(_, e))
+                if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                return Ok(Self::default());
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut data = vec![];
+        reader
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| DebianError::RepositoryIoPath(path.to_string(), e))?;
+
+        Self::from_json_slice(&data)
+    }
+
+    /// Append an entry to the log.
+    pub fn push_entry(&mut self, entry: AuditLogEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Whether this log is the result of only appending entries to `previous`.
+    pub fn is_append_of(&self, previous: &AuditLog) -> bool {
+        self.entries.len() >= previous.entries.len()
+            && self.entries[0..previous.entries.len()] == previous.entries[..]
+    }
+
+    /// Serialize this instance to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        let mut s = serde_json::to_string_pretty(self)?;
+        s.push('\n');
+
+        Ok(s)
+    }
+
+    /// Write this log to a [RepositoryWriter].
+    ///
+    /// The JSON manifest is written to `path`. If a signing key is provided, a PGP cleartext
+    /// signed copy of the manifest is written to `<path>.asc`.
+    pub async fn publish<PW>(
+        &self,
+        writer: &(impl RepositoryWriter + ?Sized),
+        path: &str,
+        signing_key: Option<(&impl SecretKeyTrait, PW)>,
+    ) -> Result<()>
+    where
+        PW: FnOnce() -> String,
+    {
+        let json = self.to_json()?;
+
+        if let Some((key, password)) = signing_key {
+            let signed = cleartext_sign(
+                key,
+                password,
+                signing_hash_algorithm(key),
+                std::io::Cursor::new(json.as_bytes()),
+            )?;
+
+            writer
+                .write_path(
+                    format!("{}.asc", path).into(),
+                    Box::pin(futures::io::Cursor::new(signed.into_bytes())),
+                )
+                .await?;
+        }
+
+        writer
+            .write_path(
+                path.to_string().into(),
+                Box::pin(futures::io::Cursor::new(json.into_bytes())),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// An [AsyncRead] adapter that feeds read data into a shared hasher.
+struct SharedDigestingReader<'a> {
+    source: Pin<Box<dyn AsyncRead + Send + 'a>>,
+    hasher: Arc<Mutex<Box<dyn pgp::crypto::hash::Hasher + Send>>>,
+}
+
+impl<'a> AsyncRead for SharedDigestingReader<'a> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.source.as_mut().poll_read(cx, buf) {
+            Poll::Ready(Ok(size)) => {
+                if size > 0 {
+                    self.hasher
+                        .lock()
+                        .map_err(|_| std::io::Error::other("error acquiring hasher mutex"))?
+                        .update(&buf[0..size]);
+                }
+
+                Poll::Ready(Ok(size))
+            }
+            res => res,
+        }
+    }
+}
+
+/// A [RepositoryWriter] that records every path written to an inner writer.
+///
+/// [RepositoryWriter::copy_from()] uses the default implementation so copied paths are
+/// recorded. This means any custom `copy_from()` behavior of the inner writer is bypassed.
+pub struct AuditingWriter<'a> {
+    inner: &'a dyn RepositoryWriter,
+    operation: String,
+    started: String,
+    paths: Mutex<Vec<AuditedPath>>,
+}
+
+impl<'a> AuditingWriter<'a> {
+    /// Construct a new instance wrapping a writer.
+    ///
+    /// `operation` describes the operation being audited. e.g. `publish`.
+    pub fn new(inner: &'a dyn RepositoryWriter, operation: impl ToString) -> Self {
+        Self {
+            inner,
+            operation: operation.to_string(),
+            started: now(),
+            paths: Mutex::new(vec![]),
+        }
+    }
+
+    /// Finish auditing, returning an [AuditLogEntry] describing writes performed.
+    pub fn finish(self) -> AuditLogEntry {
+        let mut paths = self.paths.into_inner().unwrap_or_else(|e| e.into_inner());
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+
+        AuditLogEntry {
+            operation: self.operation,
+            started: self.started,
+            finished: now(),
+            paths,
+        }
+    }
+}
+
+#[async_trait]
+impl<'a> RepositoryWriter for AuditingWriter<'a> {
+    async fn verify_path<'path>(
+        &self,
+        path: &'path str,
+        expected_content: Option<(u64, ContentDigest)>,
+    ) -> Result<RepositoryPathVerification<'path>> {
+        self.inner.verify_path(path, expected_content).await
+    }
+
+    async fn path_size(&self, path: &str) -> Result<Option<u64>> {
+        self.inner.path_size(path).await
+    }
+
+    async fn write_path<'path, 'reader>(
+        &self,
+        path: Cow<'path, str>,
+        reader: Pin<Box<dyn AsyncRead + Send + 'reader>>,
+    ) -> Result<RepositoryWrite<'path>> {
+        let hasher = Arc::new(Mutex::new(ContentDigest::Sha256(vec![]).new_hasher()));
+
+        let reader = SharedDigestingReader {
+            source: reader,
+            hasher: hasher.clone(),
+        };
+
+        let write = self.inner.write_path(path, Box::pin(reader)).await?;
+
+        let poisoned = || {
+            DebianError::RepositoryIoPath(
+                write.path.to_string(),
+                std::io::Error::other("error acquiring audit mutex"),
+            )
+        };
+
+        let hasher = Arc::try_unwrap(hasher)
+            .map_err(|_| poisoned())?
+            .into_inner()
+            .map_err(|_| poisoned())?;
+
+        self.paths
+            .lock()
+            .map_err(|_| poisoned())?
+            .push(AuditedPath {
+                path: write.path.to_string(),
+                size: write.bytes_written,
+                sha256: hex::encode(hasher.finish()),
+                timestamp: now(),
+            });
+
+        Ok(write)
+    }
+
+    async fn list_paths(&self, prefix: &str) -> Result<Vec<String>> {
+        self.inner.list_paths(prefix).await
+    }
+
+    async fn delete_path(&self, path: &str) -> Result<()> {
+        self.inner.delete_path(path).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            repository::filesystem::{FilesystemRepositoryReader, FilesystemRepositoryWriter},
+            signing_key::{create_self_signed_key, signing_secret_key_params_builder},
+        },
+        tempfile::TempDir,
+    };
+
+    #[tokio::test]
+    async fn audit_log_append() -> Result<()> {
+        let td = TempDir::new()?;
+        let writer = FilesystemRepositoryWriter::new(td.path());
+        let reader = FilesystemRepositoryReader::new(td.path());
+
+        let auditing = AuditingWriter::new(&writer, "publish");
+        auditing
+            .write_path(
+                "dists/foo/Release".into(),
+                Box::pin(futures::io::Cursor::new(b"hello".to_vec())),
+            )
+            .await?;
+        let entry = auditing.finish();
+
+        assert_eq!(entry.paths.len(), 1);
+        assert_eq!(entry.paths[0].size, 5);
+        assert_eq!(
+            entry.paths[0].sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+
+        let key = create_self_signed_key(
+            signing_secret_key_params_builder("someone@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?
+        .0;
+
+        let previous = AuditLog::load(&reader, "audit-log.json").await?;
+        assert!(previous.entries.is_empty());
+
+        let mut log = previous.clone();
+        log.push_entry(entry);
+        log.publish(&writer, "audit-log.json", Some((&key, String::new)))
+            .await?;
+
+        let loaded = AuditLog::load(&reader, "audit-log.json").await?;
+        assert_eq!(loaded, log);
+        assert!(loaded.is_append_of(&previous));
+        assert!(!previous.is_append_of(&loaded));
+        assert!(td.path().join("audit-log.json.asc").exists());
+
+        Ok(())
+    }
+}
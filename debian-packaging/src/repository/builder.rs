@@ -15,23 +15,34 @@ Primitives in this module facilitate constructing your own repositories.
 use {
     crate::{
         binary_package_control::BinaryPackageControlFile,
+        binary_package_list::{BinaryPackageList, MultiArchIssue},
         control::{ControlField, ControlParagraph},
         deb::reader::resolve_control_file,
+        debian_source_control::DebianSourceControlFile,
         error::{DebianError, Result},
-        io::{read_compressed, ContentDigest, DataResolver, MultiContentDigest, MultiDigester},
+        file_list::{file_list_field, FileListEntry},
+        io::{
+            read_compressed, write_compressed, ContentDigest, DataResolver, MultiContentDigest,
+            MultiDigester,
+        },
         repository::{
+            audit::{AuditLog, AuditingWriter},
             release::{ChecksumType, ReleaseFile, DATE_FORMAT},
+            signing::{cleartext_signed_document, sign_cleartext},
             Compression, PublishEvent, RepositoryPathVerificationState, RepositoryWriter,
         },
+        signing_key::signing_hash_algorithm,
     },
+    async_trait::async_trait,
     chrono::{DateTime, Utc},
-    futures::{AsyncRead, AsyncReadExt, StreamExt, TryStreamExt},
-    pgp::{crypto::hash::HashAlgorithm, types::SecretKeyTrait},
+    futures::{AsyncRead, AsyncWrite, AsyncWriteExt, StreamExt, TryStreamExt},
+    pgp::{crypto::hash::HashAlgorithm, types::SecretKeyTrait, SignedSecretKey},
     pgp_cleartext::cleartext_sign,
     std::{
         borrow::Cow,
         collections::{BTreeMap, BTreeSet, HashMap},
         pin::Pin,
+        sync::Mutex,
     },
 };
 
@@ -71,7 +82,7 @@ impl PoolLayout {
     pub fn path(&self, component: &str, package: &str, filename: &str) -> String {
         match self {
             Self::ComponentThenNamePrefix => {
-                let name_prefix = if package.starts_with("lib") {
+                let name_prefix = if package.starts_with("lib") && package.len() > 3 {
                     format!("{}/{}", &package[0..4], package)
                 } else {
                     format!("{}/{}", &package[0..1], package)
@@ -131,15 +142,7 @@ impl<'cf> DebPackageReference<'cf> for InMemoryDebFile {
     }
 
     fn deb_digest(&self, checksum: ChecksumType) -> Result<ContentDigest> {
-        let mut h = checksum.new_hasher();
-        h.update(&self.data);
-        let digest = h.finish().to_vec();
-
-        Ok(match checksum {
-            ChecksumType::Md5 => ContentDigest::Md5(digest),
-            ChecksumType::Sha1 => ContentDigest::Sha1(digest),
-            ChecksumType::Sha256 => ContentDigest::Sha256(digest),
-        })
+        Ok(ContentDigest::compute(checksum, &self.data))
     }
 
     fn deb_filename(&self) -> Result<String> {
@@ -151,6 +154,65 @@ impl<'cf> DebPackageReference<'cf> for InMemoryDebFile {
     }
 }
 
+/// User code invoked at well-defined points during [RepositoryBuilder::publish()].
+///
+/// Hooks enable integrations like approval gates, metadata customization, and CDN
+/// invalidation without reimplementing publishing logic. All methods have default
+/// implementations that do nothing, so implementations only need to define the hook
+/// points they care about.
+///
+/// Returning an error from a hook aborts the publish. By convention, hooks wishing to
+/// refuse a publish should return [DebianError::RepositoryPublishVetoed].
+#[async_trait]
+pub trait PublishHook: Send + Sync {
+    /// Called after pool artifacts have been published, before any indices are written.
+    ///
+    /// Since indices aren't written yet, vetoing here leaves the repository as clients
+    /// see it unchanged.
+    async fn after_pool_published(&self, _distribution_path: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with the derived `Release` file before it is written and signed.
+    ///
+    /// The release file can be mutated, e.g. to add custom fields. This is called after
+    /// indices files are written.
+    async fn before_release_signing(
+        &self,
+        _distribution_path: &str,
+        _release: &mut ReleaseFile<'_>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after all repository content has been published.
+    async fn after_publish(&self, _distribution_path: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Holds registered [PublishHook].
+#[derive(Default)]
+struct PublishHooks(Vec<Box<dyn PublishHook>>);
+
+impl std::fmt::Debug for PublishHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PublishHooks({} hooks)", self.0.len())
+    }
+}
+
+type KeyPasswordFn = dyn Fn() -> String + Send + Sync;
+
+/// Holds keys registered via [RepositoryBuilder::add_signing_key()].
+#[derive(Default)]
+struct SigningKeys(Vec<(SignedSecretKey, Box<KeyPasswordFn>)>);
+
+impl std::fmt::Debug for SigningKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SigningKeys({} keys)", self.0.len())
+    }
+}
+
 /// Describes an index file to write.
 pub struct IndexFileReader<'a> {
     /// Provides the uncompressed content of the file.
@@ -166,25 +228,73 @@ pub struct IndexFileReader<'a> {
 impl<'a> IndexFileReader<'a> {
     /// Obtain the canonical path of this entry as it would appear in an `[In]Release` file.
     pub fn canonical_path(&self) -> String {
-        format!(
-            "{}/{}{}",
-            self.directory,
-            self.filename,
-            self.compression.extension()
-        )
+        index_file_path(&self.directory, &self.filename, self.compression)
     }
 
     /// Obtain the `by-hash` path given a [ContentDigest].
     pub fn by_hash_path(&self, digest: &ContentDigest) -> String {
-        format!(
-            "{}/by-hash/{}/{}",
-            self.directory,
-            digest.release_field_name(),
-            digest.digest_hex()
-        )
+        index_file_by_hash_path(&self.directory, digest)
+    }
+}
+
+fn index_file_path(directory: &str, filename: &str, compression: Compression) -> String {
+    format!("{}/{}{}", directory, filename, compression.extension())
+}
+
+fn index_file_by_hash_path(directory: &str, digest: &ContentDigest) -> String {
+    format!(
+        "{}/by-hash/{}/{}",
+        directory,
+        digest.release_field_name(),
+        digest.digest_hex()
+    )
+}
+
+/// The packages listed by an index file.
+#[derive(Clone, Copy, Debug)]
+enum IndexFileContent<'a> {
+    /// The `Packages` file of a component and architecture.
+    BinaryPackages(&'a str, &'a str),
+    /// The `Sources` file of a component.
+    SourcePackages(&'a str),
+}
+
+impl<'a> IndexFileContent<'a> {
+    fn directory(&self) -> String {
+        match self {
+            Self::BinaryPackages(component, architecture) => {
+                format!("{}/binary-{}", component, architecture)
+            }
+            Self::SourcePackages(component) => format!("{}/source", component),
+        }
+    }
+
+    fn filename(&self) -> &'static str {
+        match self {
+            Self::BinaryPackages(..) => "Packages",
+            Self::SourcePackages(_) => "Sources",
+        }
     }
 }
 
+/// Serialize paragraphs to a writer, separated by empty lines.
+async fn write_paragraphs<'a, 'cf: 'a>(
+    paragraphs: impl Iterator<Item = &'a ControlParagraph<'cf>>,
+    writer: &mut (impl AsyncWrite + Unpin + ?Sized),
+) -> Result<()> {
+    let mut buf = vec![];
+
+    for paragraph in paragraphs {
+        buf.clear();
+        paragraph.write(&mut buf)?;
+        buf.push(b'\n');
+
+        writer.write_all(&buf).await?;
+    }
+
+    Ok(())
+}
+
 struct ExpandedIndexFile {
     canonical_path: String,
     write_path: String,
@@ -192,7 +302,7 @@ struct ExpandedIndexFile {
     data: Vec<u8>,
 }
 
-/// Describes a file in the *pool* to support a binary package.
+/// Describes a file in the *pool* to support a binary or source package.
 #[derive(Debug)]
 pub struct BinaryPackagePoolArtifact<'a> {
     /// The file path relative to the repository root.
@@ -240,7 +350,7 @@ type ComponentBinaryPackages<'a> = BTreeMap<(String, String), IndexedBinaryPacka
 /// fields are used for.
 ///
 /// After basic metadata is in place, `.deb` packages are registered against the builder via
-/// [Self::add_binary_deb()].
+/// [Self::add_binary_deb()] and source packages via [Self::add_source_dsc()].
 ///
 /// Once everything is registered against the builder, it is time to *publish* (read: write)
 /// the repository content.
@@ -265,6 +375,10 @@ type ComponentBinaryPackages<'a> = BTreeMap<(String, String), IndexedBinaryPacka
 /// For convenience, the [Self::publish()] method exists to perform both pool and indices
 /// publishing. It is strongly recommended to call this method instead of the lower-level
 /// methods for writing out content.
+///
+/// [Self::set_audit_log()] can be used to have [Self::publish()] record every written path
+/// in an [AuditLog]. [Self::add_publish_hook()] registers a [PublishHook] to run custom
+/// logic at various points during publishing.
 #[derive(Debug, Default)]
 pub struct RepositoryBuilder<'cf> {
     // Release file fields.
@@ -285,7 +399,12 @@ pub struct RepositoryBuilder<'cf> {
     binary_packages: ComponentBinaryPackages<'cf>,
     installer_packages: ComponentBinaryPackages<'cf>,
     source_packages: BTreeMap<String, IndexedBinaryPackages<'cf>>,
+    source_pool_artifacts: BTreeMap<String, (u64, ContentDigest)>,
     translations: BTreeMap<String, ()>,
+    audit_log: Mutex<Option<(String, AuditLog)>>,
+    hooks: PublishHooks,
+    signing_keys: SigningKeys,
+    signing_hash: Option<HashAlgorithm>,
 }
 
 impl<'cf> RepositoryBuilder<'cf> {
@@ -316,7 +435,12 @@ impl<'cf> RepositoryBuilder<'cf> {
             binary_packages: ComponentBinaryPackages::default(),
             installer_packages: ComponentBinaryPackages::default(),
             source_packages: BTreeMap::default(),
+            source_pool_artifacts: BTreeMap::default(),
             translations: BTreeMap::default(),
+            audit_log: Mutex::new(None),
+            hooks: PublishHooks::default(),
+            signing_keys: SigningKeys::default(),
+            signing_hash: None,
         }
     }
 
@@ -438,6 +562,46 @@ impl<'cf> RepositoryBuilder<'cf> {
         }
     }
 
+    /// Record paths written by [Self::publish()] in an [AuditLog].
+    ///
+    /// `previous` is the existing log for the repository, typically obtained via
+    /// [AuditLog::load()]. Each publish appends an entry to it and writes the log
+    /// to `path`, relative to the writer's root, after the `[In]Release` files are
+    /// written. If a signing key is given to [Self::publish()], the log is signed with it.
+    pub fn set_audit_log(&mut self, path: impl ToString, previous: AuditLog) {
+        self.audit_log = Mutex::new(Some((path.to_string(), previous)));
+    }
+
+    /// Register a [PublishHook] to be called during [Self::publish()].
+    ///
+    /// Hooks are called in the order they are registered.
+    pub fn add_publish_hook(&mut self, hook: impl PublishHook + 'static) {
+        self.hooks.0.push(Box::new(hook));
+    }
+
+    /// Register an additional key to sign the `InRelease` file with.
+    ///
+    /// The `InRelease` file carries a signature from each registered key in addition to
+    /// the key given to [Self::publish_indices()] or [Self::publish()], if any. Signing with
+    /// the old and the new key during a key rotation lets clients trusting either verify
+    /// the repository.
+    pub fn add_signing_key(
+        &mut self,
+        key: SignedSecretKey,
+        key_pw: impl Fn() -> String + Send + Sync + 'static,
+    ) {
+        self.signing_keys.0.push((key, Box::new(key_pw)));
+    }
+
+    /// Set the hash algorithm of `InRelease` signatures.
+    ///
+    /// By default, each key signs with the algorithm chosen by [signing_hash_algorithm()].
+    /// ECDSA keys require a digest at least as large as the key, so signing fails if
+    /// the algorithm is too weak for a key. ECDSA keys on brainpool curves can't sign.
+    pub fn set_signing_hash_algorithm(&mut self, hash: HashAlgorithm) {
+        self.signing_hash = Some(hash);
+    }
+
     fn have_entries(&self) -> bool {
         !self.binary_packages.is_empty()
             || !self.source_packages.is_empty()
@@ -526,11 +690,7 @@ impl<'cf> RepositoryBuilder<'cf> {
         // The `Filename` is derived from the pool layout scheme in effect.
         let filename = self.pool_layout.path(
             component,
-            if let Some(name) = original_control_file.source() {
-                name
-            } else {
-                package
-            },
+            original_control_file.source_package_name()?,
             &deb.deb_filename()?,
         );
         para.set_field_from_string("Filename".into(), filename.clone().into());
@@ -556,6 +716,115 @@ impl<'cf> RepositoryBuilder<'cf> {
         Ok(filename)
     }
 
+    /// Add a source package to this repository in the given component.
+    ///
+    /// The source package is defined by the content of its `.dsc` file, which may be PGP
+    /// armored. `dsc_filename` is the file name of the `.dsc` file, without any directory
+    /// components.
+    ///
+    /// The `.dsc` must have checksum fields for every checksum flavor registered with this
+    /// instance.
+    ///
+    /// Returns the pool directory / `Directory` field that the `.dsc` and all files it
+    /// references will occupy in the repository.
+    pub fn add_source_dsc(
+        &mut self,
+        component: &str,
+        dsc_filename: &str,
+        dsc_data: &[u8],
+    ) -> Result<String> {
+        if !self.components.contains(component) {
+            return Err(DebianError::RepositoryBuildUnknownComponent(
+                component.to_string(),
+            ));
+        }
+
+        let dsc = if dsc_data.starts_with(b"-----BEGIN PGP SIGNED MESSAGE-----") {
+            DebianSourceControlFile::from_armored_reader(std::io::Cursor::new(dsc_data))?
+        } else {
+            DebianSourceControlFile::from_reader(std::io::Cursor::new(dsc_data))?
+        };
+
+        let package = dsc.source()?;
+        let version = dsc.version_str()?;
+
+        let directory = self
+            .pool_layout
+            .path(component, package, "")
+            .trim_end_matches('/')
+            .to_string();
+
+        // `Sources` entries are the `.dsc` paragraph with `Source` renamed to `Package`, a
+        // `Directory` field, and file lists that also cover the `.dsc` itself.
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Package".into(), package.to_string().into());
+
+        for field in dsc.iter_fields() {
+            if !["Source", "Files", "Checksums-Sha1", "Checksums-Sha256"].contains(&field.name()) {
+                para.set_field(field.clone());
+            }
+        }
+
+        para.set_field_from_string("Directory".into(), directory.clone().into());
+
+        let strongest_checksum = *self
+            .checksums
+            .iter()
+            .last()
+            .expect("should have at least 1 checksum defined");
+
+        for checksum in &self.checksums {
+            let dsc_digest = ContentDigest::compute(*checksum, dsc_data);
+
+            let entries = dsc
+                .checksums(*checksum)
+                .ok_or_else(|| {
+                    DebianError::ControlRequiredFieldMissing(
+                        checksum.source_field_name().to_string(),
+                    )
+                })?
+                .map(|entry| {
+                    let entry = entry?;
+                    Ok((entry.filename.to_string(), entry.size, entry.digest))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let entries =
+                std::iter::once((dsc_filename.to_string(), dsc_data.len() as u64, dsc_digest))
+                    .chain(entries)
+                    .collect::<Vec<_>>();
+
+            if *checksum == strongest_checksum {
+                for (filename, size, digest) in &entries {
+                    self.source_pool_artifacts.insert(
+                        format!("{}/{}", directory, filename),
+                        (*size, digest.clone()),
+                    );
+                }
+            }
+
+            para.set_field(file_list_field(
+                *checksum,
+                entries
+                    .iter()
+                    .map(|(filename, size, digest)| FileListEntry {
+                        filename,
+                        digest: digest.clone(),
+                        size: *size,
+                        section: None,
+                        priority: None,
+                    }),
+            ));
+        }
+
+        self.source_packages
+            .entry(component.to_string())
+            .or_default()
+            .insert((package.to_string(), version.to_string()), para);
+
+        Ok(directory)
+    }
+
     /// Obtain all components having binary packages.
     ///
     /// The iterator contains 2-tuples of `(component, architecture)`.
@@ -565,6 +834,23 @@ impl<'cf> RepositoryBuilder<'cf> {
             .map(|(a, b)| (a.as_str(), b.as_str()))
     }
 
+    /// Find problems with `Multi-Arch` metadata of binary packages in a component.
+    ///
+    /// Packages of all architectures in the component are checked together. See
+    /// [BinaryPackageList::multi_arch_issues()] for what is checked.
+    pub fn multi_arch_issues(&self, component: &str) -> Vec<MultiArchIssue> {
+        let mut packages = BinaryPackageList::default();
+        packages.extend(
+            self.binary_packages
+                .iter()
+                .filter(|((c, _), _)| c == component)
+                .flat_map(|(_, indexed)| indexed.values())
+                .map(|para| BinaryPackageControlFile::from(para.clone())),
+        );
+
+        packages.multi_arch_issues()
+    }
+
     /// Obtain an iterator of [ControlParagraph] for binary packages in a given component + architecture.
     ///
     /// This method forms the basic building block for constructing `Packages` files. `Packages`
@@ -633,6 +919,23 @@ impl<'cf> RepositoryBuilder<'cf> {
         .into_async_read()
     }
 
+    /// Write the content of a `Packages` file for binary packages to a writer.
+    ///
+    /// Paragraphs are serialized directly into the writer. Wrap the writer with
+    /// [write_compressed()] to write compressed content.
+    pub async fn write_component_binary_packages(
+        &self,
+        component: impl ToString,
+        architecture: impl ToString,
+        writer: &mut (impl AsyncWrite + Unpin + ?Sized),
+    ) -> Result<()> {
+        write_paragraphs(
+            self.iter_component_binary_packages(component, architecture),
+            writer,
+        )
+        .await
+    }
+
     /// Like [Self::component_binary_packages_reader()] except data is compressed.
     pub fn component_binary_packages_reader_compression(
         &self,
@@ -671,11 +974,69 @@ impl<'cf> RepositoryBuilder<'cf> {
             })
     }
 
+    /// Obtain an iterator of [ControlParagraph] for source packages in a given component.
+    ///
+    /// This method forms the basic building block for constructing `Sources` files.
+    pub fn iter_component_source_packages(
+        &self,
+        component: impl ToString,
+    ) -> Box<dyn Iterator<Item = &'_ ControlParagraph<'cf>> + Send + '_> {
+        if let Some(packages) = self.source_packages.get(&component.to_string()) {
+            Box::new(packages.values())
+        } else {
+            Box::new(std::iter::empty())
+        }
+    }
+
+    /// Obtain an [AsyncRead] that reads contents of a `Sources` file for source packages.
+    pub fn component_source_packages_reader(
+        &self,
+        component: impl ToString,
+    ) -> impl AsyncRead + '_ {
+        futures::stream::iter(
+            self.iter_component_source_packages(component)
+                .map(|p| Ok(format!("{}\n", p))),
+        )
+        .into_async_read()
+    }
+
+    /// Write the content of a `Sources` file for source packages to a writer.
+    ///
+    /// Paragraphs are serialized directly into the writer. Wrap the writer with
+    /// [write_compressed()] to write compressed content.
+    pub async fn write_component_source_packages(
+        &self,
+        component: impl ToString,
+        writer: &mut (impl AsyncWrite + Unpin + ?Sized),
+    ) -> Result<()> {
+        write_paragraphs(self.iter_component_source_packages(component), writer).await
+    }
+
+    /// Obtain [IndexFileReader] for each logical `Sources` file.
+    pub fn source_packages_index_readers(&self) -> impl Iterator<Item = IndexFileReader<'_>> + '_ {
+        self.source_packages.keys().flat_map(move |component| {
+            self.index_file_compressions
+                .iter()
+                .map(move |compression| IndexFileReader {
+                    reader: read_compressed(
+                        futures::io::BufReader::new(
+                            self.component_source_packages_reader(component),
+                        ),
+                        *compression,
+                    ),
+                    compression: *compression,
+                    directory: format!("{}/source", component),
+                    filename: "Sources".to_string(),
+                })
+        })
+    }
+
     /// Obtain all [IndexFileReader] to be published.
     ///
     /// Each item corresponds to a logical item in an `[In]Release`.
     pub fn index_file_readers(&self) -> impl Iterator<Item = IndexFileReader<'_>> + '_ {
         self.binary_packages_index_readers()
+            .chain(self.source_packages_index_readers())
     }
 
     /// Obtain records describing pool artifacts needed to support binary packages.
@@ -689,6 +1050,19 @@ impl<'cf> RepositoryBuilder<'cf> {
             })
     }
 
+    /// Obtain records describing pool artifacts needed to support source packages.
+    pub fn iter_source_packages_pool_artifacts(
+        &self,
+    ) -> impl Iterator<Item = BinaryPackagePoolArtifact<'_>> + '_ {
+        self.source_pool_artifacts
+            .iter()
+            .map(|(path, (size, digest))| BinaryPackagePoolArtifact {
+                path,
+                size: *size,
+                digest: digest.clone(),
+            })
+    }
+
     /// Publish artifacts to the *pool*.
     ///
     /// The *pool* is the area of a Debian repository holding files like the .deb packages.
@@ -707,6 +1081,7 @@ impl<'cf> RepositoryBuilder<'cf> {
     {
         let artifacts = self
             .iter_binary_packages_pool_artifacts()
+            .chain(self.iter_source_packages_pool_artifacts().map(Ok))
             .collect::<Result<Vec<_>>>()?;
 
         if let Some(ref cb) = progress_cb {
@@ -772,30 +1147,72 @@ impl<'cf> RepositoryBuilder<'cf> {
         Ok(())
     }
 
-    async fn expand_index_file_reader<'ifr, 'slf: 'ifr>(
-        &'slf self,
-        mut ifr: IndexFileReader<'ifr>,
-    ) -> Result<Box<dyn Iterator<Item = ExpandedIndexFile> + 'ifr>> {
+    /// Obtain the index files to publish and their compression.
+    fn index_files(&self) -> impl Iterator<Item = (IndexFileContent<'_>, Compression)> + '_ {
+        self.binary_packages
+            .keys()
+            .map(|(component, architecture)| {
+                IndexFileContent::BinaryPackages(component, architecture)
+            })
+            .chain(
+                self.source_packages
+                    .keys()
+                    .map(|component| IndexFileContent::SourcePackages(component)),
+            )
+            .flat_map(move |content| {
+                self.index_file_compressions
+                    .iter()
+                    .map(move |compression| (content, *compression))
+            })
+    }
+
+    /// Serialize and compress an index file, yielding the files to write for it.
+    ///
+    /// Paragraphs are streamed into the compressor, so only compressed content is buffered.
+    async fn expand_index_file(
+        &self,
+        content: IndexFileContent<'_>,
+        compression: Compression,
+    ) -> Result<Box<dyn Iterator<Item = ExpandedIndexFile> + '_>> {
         let mut buf = vec![];
-        ifr.reader.read_to_end(&mut buf).await?;
+
+        let mut writer = write_compressed(&mut buf, compression);
+        match content {
+            IndexFileContent::BinaryPackages(component, architecture) => {
+                self.write_component_binary_packages(component, architecture, &mut writer)
+                    .await?
+            }
+            IndexFileContent::SourcePackages(component) => {
+                self.write_component_source_packages(component, &mut writer)
+                    .await?
+            }
+        }
+        writer.close().await?;
+        drop(writer);
 
         let mut digester = MultiDigester::default();
         digester.update(&buf);
         let digests = digester.finish();
 
+        let directory = content.directory();
+        let canonical_path = index_file_path(&directory, content.filename(), compression);
+
         if self.acquire_by_hash == Some(true) {
             Ok(Box::new(self.checksums.iter().map(move |checksum| {
                 ExpandedIndexFile {
-                    canonical_path: ifr.canonical_path(),
-                    write_path: ifr.by_hash_path(digests.digest_from_checksum(*checksum)),
+                    canonical_path: canonical_path.clone(),
+                    write_path: index_file_by_hash_path(
+                        &directory,
+                        digests.digest_from_checksum(*checksum),
+                    ),
                     digests: digests.clone(),
                     data: buf.clone(),
                 }
             })))
         } else {
             Ok(Box::new(std::iter::once(ExpandedIndexFile {
-                canonical_path: ifr.canonical_path(),
-                write_path: ifr.canonical_path(),
+                write_path: canonical_path.clone(),
+                canonical_path,
                 digests,
                 data: buf,
             })))
@@ -898,7 +1315,6 @@ impl<'cf> RepositoryBuilder<'cf> {
                 .get(checksum.field_name())
                 .unwrap_or(&default);
 
-            let longest_path = entries.keys().map(|x| x.len()).max().unwrap_or_default();
             let longest_size = entries
                 .values()
                 .map(|(size, _)| format!("{}", size).len())
@@ -910,11 +1326,10 @@ impl<'cf> RepositoryBuilder<'cf> {
                 std::iter::once("".to_string())
                     .chain(entries.iter().map(|(path, (size, digest))| {
                         format!(
-                            " {:<path_width$} {:>size_width$} {}",
-                            path,
-                            size,
+                            " {} {:>size_width$} {}",
                             digest,
-                            path_width = longest_path,
+                            size,
+                            path,
                             size_width = longest_size
                         )
                     }))
@@ -949,12 +1364,12 @@ impl<'cf> RepositoryBuilder<'cf> {
     {
         let mut index_paths = BTreeMap::new();
 
-        // This will effectively buffer all indices files in memory. This could be avoided if
-        // we want to limit memory use.
+        // This will effectively buffer all compressed indices files in memory, as their
+        // digests are needed to name by-hash paths. Uncompressed content isn't buffered.
 
         let mut fs = futures::stream::iter(
-            self.index_file_readers()
-                .map(|ifr| self.expand_index_file_reader(ifr)),
+            self.index_files()
+                .map(|(content, compression)| self.expand_index_file(content, compression)),
         )
         .buffer_unordered(threads);
 
@@ -998,7 +1413,12 @@ impl<'cf> RepositoryBuilder<'cf> {
 
         // Now with all the indices files written, we can write the `[In]Release` files.
 
-        let release = self.create_release_file(index_paths.into_iter())?;
+        let mut release = self.create_release_file(index_paths.into_iter())?;
+
+        for hook in &self.hooks.0 {
+            hook.before_release_signing(path_prefix.unwrap_or_default(), &mut release)
+                .await?;
+        }
 
         let (release_path, inrelease_path) = if let Some(prefix) = path_prefix {
             (
@@ -1027,14 +1447,43 @@ impl<'cf> RepositoryBuilder<'cf> {
             ));
         }
 
-        if let Some((key, password)) = signing_key {
-            let inrelease_content = cleartext_sign(
+        let inrelease_content = match signing_key {
+            Some((key, password)) if self.signing_keys.0.is_empty() => Some(cleartext_sign(
                 key,
                 password,
-                HashAlgorithm::SHA2_256,
+                self.signing_hash
+                    .unwrap_or_else(|| signing_hash_algorithm(key)),
                 std::io::Cursor::new(release.to_string().as_bytes()),
-            )?;
+            )?),
+            _ if self.signing_keys.0.is_empty() => None,
+            signing_key => {
+                let text = release.to_string();
+
+                let mut signatures = vec![];
+                if let Some((key, password)) = signing_key {
+                    signatures.push(sign_cleartext(
+                        key,
+                        password,
+                        self.signing_hash
+                            .unwrap_or_else(|| signing_hash_algorithm(key)),
+                        &text,
+                    )?);
+                }
+                for (key, password) in &self.signing_keys.0 {
+                    signatures.push(sign_cleartext(
+                        key,
+                        password,
+                        self.signing_hash
+                            .unwrap_or_else(|| signing_hash_algorithm(key)),
+                        &text,
+                    )?);
+                }
+
+                Some(cleartext_signed_document(&text, &signatures)?)
+            }
+        };
 
+        if let Some(inrelease_content) = inrelease_content {
             if let Some(cb) = progress_cb {
                 cb(PublishEvent::IndexFileToWrite(inrelease_path.clone()));
             }
@@ -1083,6 +1532,9 @@ impl<'cf> RepositoryBuilder<'cf> {
     /// and `Option::<(&pgp::SignedSecretKey, fn() -> String)>::None` for `signing_key`.
     /// Alternatively, use the `NO_PROGRESS_CB` or `NO_SIGNING_KEY` module constants to avoid
     /// some typing.
+    ///
+    /// If an audit log is configured via [Self::set_audit_log()], all writes are recorded
+    /// and the updated log is written once everything else is published.
     pub async fn publish<F, PW>(
         &self,
         writer: &impl RepositoryWriter,
@@ -1092,6 +1544,88 @@ impl<'cf> RepositoryBuilder<'cf> {
         progress_cb: &Option<F>,
         signing_key: Option<(&impl SecretKeyTrait, PW)>,
     ) -> Result<()>
+    where
+        F: Fn(PublishEvent),
+        PW: FnOnce() -> String,
+    {
+        if self.audit_log_lock()?.is_none() {
+            self.publish_unaudited(
+                writer,
+                resolver,
+                distribution_path,
+                threads,
+                progress_cb,
+                signing_key,
+            )
+            .await?;
+
+            for hook in &self.hooks.0 {
+                hook.after_publish(distribution_path).await?;
+            }
+
+            return Ok(());
+        }
+
+        // The password is needed for both the `InRelease` and the audit log.
+        let signing_key = signing_key.map(|(key, password)| (key, password()));
+
+        let auditing = AuditingWriter::new(writer, "publish");
+
+        self.publish_unaudited(
+            &auditing,
+            resolver,
+            distribution_path,
+            threads,
+            progress_cb,
+            signing_key
+                .as_ref()
+                .map(|(key, password)| (*key, || password.clone())),
+        )
+        .await?;
+
+        let entry = auditing.finish();
+
+        let (path, log) = {
+            let mut guard = self.audit_log_lock()?;
+            let (path, log) = guard.as_mut().expect("audit log presence checked above");
+            log.push_entry(entry);
+
+            (path.clone(), log.clone())
+        };
+
+        if let Some(cb) = progress_cb {
+            cb(PublishEvent::IndexFileToWrite(path.clone()));
+        }
+
+        log.publish(
+            writer,
+            &path,
+            signing_key.map(|(key, password)| (key, move || password)),
+        )
+        .await?;
+
+        for hook in &self.hooks.0 {
+            hook.after_publish(distribution_path).await?;
+        }
+
+        Ok(())
+    }
+
+    fn audit_log_lock(&self) -> Result<std::sync::MutexGuard<'_, Option<(String, AuditLog)>>> {
+        self.audit_log
+            .lock()
+            .map_err(|_| DebianError::Other("audit log mutex poisoned".to_string()))
+    }
+
+    async fn publish_unaudited<F, PW>(
+        &self,
+        writer: &impl RepositoryWriter,
+        resolver: &impl DataResolver,
+        distribution_path: &str,
+        threads: usize,
+        progress_cb: &Option<F>,
+        signing_key: Option<(&impl SecretKeyTrait, PW)>,
+    ) -> Result<()>
     where
         F: Fn(PublishEvent),
         PW: FnOnce() -> String,
@@ -1099,6 +1633,10 @@ impl<'cf> RepositoryBuilder<'cf> {
         self.publish_pool_artifacts(resolver, writer, threads, progress_cb)
             .await?;
 
+        for hook in &self.hooks.0 {
+            hook.after_pool_published(distribution_path).await?;
+        }
+
         self.publish_indices(
             writer,
             Some(distribution_path),
@@ -1327,9 +1865,14 @@ mod tests {
     use {
         super::*,
         crate::{
-            repository::{filesystem::FilesystemRepositoryWriter, reader_from_str},
+            control::ControlParagraphReader,
+            repository::{
+                filesystem::{FilesystemRepositoryReader, FilesystemRepositoryWriter},
+                reader_from_str,
+            },
             signing_key::{create_self_signed_key, signing_secret_key_params_builder},
         },
+        futures::AsyncReadExt,
         tempfile::TempDir,
     };
 
@@ -1358,7 +1901,14 @@ mod tests {
         let key_params = signing_secret_key_params_builder("someone@example.com")
             .build()
             .unwrap();
-        let key = create_self_signed_key(key_params, String::new)?.0;
+        let (key, public_key) = create_self_signed_key(key_params, String::new)?;
+        let (rotated_key, rotated_public_key) = create_self_signed_key(
+            signing_secret_key_params_builder("rotated@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+        builder.add_signing_key(rotated_key, String::new);
 
         builder
             .publish_indices(
@@ -1377,6 +1927,209 @@ mod tests {
         let indices = release_reader.classified_indices_entries()?;
         assert!(indices.is_empty());
 
+        let signatures = release_reader.release_file().signatures().unwrap();
+        assert_eq!(signatures.verify(&public_key)?, 1);
+        assert_eq!(signatures.verify(&rotated_public_key)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn release_file_checksum_lines() -> Result<()> {
+        let builder = RepositoryBuilder::new_recommended(
+            ["amd64"].into_iter(),
+            ["main"].into_iter(),
+            "suite",
+            "codename",
+        );
+
+        let mut digester = MultiDigester::default();
+        digester.update(b"content");
+        let digests = digester.finish();
+
+        let release = builder.create_release_file(
+            [
+                (
+                    "main/binary-amd64/Packages".to_string(),
+                    (7, digests.clone()),
+                ),
+                (
+                    "main/binary-amd64/Packages.xz".to_string(),
+                    (1024, digests.clone()),
+                ),
+            ]
+            .into_iter(),
+        )?;
+
+        assert_eq!(
+            release.field_str("SHA256"),
+            Some(
+                format!(
+                    "\n {digest}    7 main/binary-amd64/Packages\n {digest} 1024 main/binary-amd64/Packages.xz",
+                    digest = digests.digest_from_checksum(ChecksumType::Sha256).digest_hex()
+                )
+                .as_str()
+            )
+        );
+
+        // Entries must survive a round trip through serialization.
+        let release = ReleaseFile::from_reader(std::io::Cursor::new(release.to_string()))?;
+        let entries = release
+            .iter_index_files(ChecksumType::Sha256)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "main/binary-amd64/Packages");
+        assert_eq!(entries[0].size, 7);
+        assert_eq!(
+            &entries[0].digest,
+            digests.digest_from_checksum(ChecksumType::Sha256)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_compressed_packages() -> Result<()> {
+        let mut builder = RepositoryBuilder::new_recommended(
+            ["amd64"].into_iter(),
+            ["main"].into_iter(),
+            "suite",
+            "codename",
+        );
+
+        for i in 0..3 {
+            let data = format!(
+                "Package: foo{i}\n\
+                 Version: 1.{i}\n\
+                 Architecture: amd64\n\
+                 Description: foo\n \
+                 multiple lines\n\
+                 Filename: foo{i}_1.{i}_amd64.deb\n\
+                 Size: 42\n\
+                 MD5sum: 5d41402abc4b2a76b9719d911017c592\n\
+                 SHA1: aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d\n\
+                 SHA256: 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824\n"
+            );
+            let cf = BinaryPackageControlFile::from(
+                ControlParagraphReader::new(data.as_bytes())
+                    .next()
+                    .unwrap()?,
+            );
+            builder.add_binary_deb("main", &cf)?;
+        }
+
+        let mut expected = vec![];
+        builder
+            .component_binary_packages_reader("main", "amd64")
+            .read_to_end(&mut expected)
+            .await?;
+        assert!(expected.starts_with(b"Package: foo0\n"));
+
+        let mut plain = vec![];
+        builder
+            .write_component_binary_packages("main", "amd64", &mut plain)
+            .await?;
+        assert_eq!(plain, expected);
+
+        for compression in [
+            Compression::Gzip,
+            Compression::Xz,
+            Compression::Bzip2,
+            Compression::Lzma,
+            Compression::Zstd,
+        ] {
+            let mut data = vec![];
+            let mut writer = write_compressed(&mut data, compression);
+            builder
+                .write_component_binary_packages("main", "amd64", &mut writer)
+                .await?;
+            writer.close().await?;
+            drop(writer);
+            assert_ne!(data, expected);
+
+            let mut reader =
+                crate::io::read_decompressed(Box::pin(futures::io::Cursor::new(data)), compression)
+                    .await?;
+            let mut decompressed = vec![];
+            reader.read_to_end(&mut decompressed).await?;
+            assert_eq!(decompressed, expected, "{:?}", compression);
+        }
+
+        Ok(())
+    }
+
+    struct FieldHook;
+
+    #[async_trait]
+    impl PublishHook for FieldHook {
+        async fn before_release_signing(
+            &self,
+            _distribution_path: &str,
+            release: &mut ReleaseFile<'_>,
+        ) -> Result<()> {
+            release.set_field_from_string("X-Custom".into(), "value".into());
+
+            Ok(())
+        }
+    }
+
+    struct VetoHook;
+
+    #[async_trait]
+    impl PublishHook for VetoHook {
+        async fn after_pool_published(&self, distribution_path: &str) -> Result<()> {
+            Err(DebianError::RepositoryPublishVetoed(format!(
+                "{} not approved",
+                distribution_path
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn publish_hooks() -> Result<()> {
+        let td = temp_dir()?;
+        let writer = FilesystemRepositoryWriter::new(td.path());
+        let resolver = FilesystemRepositoryReader::new(td.path());
+
+        let mut builder = RepositoryBuilder::new_recommended(
+            ["amd64"].into_iter(),
+            ["main"].into_iter(),
+            "suite",
+            "codename",
+        );
+        builder.add_publish_hook(FieldHook);
+
+        builder
+            .publish(
+                &writer,
+                &resolver,
+                "dists/dist",
+                1,
+                &NO_PROGRESS_CB,
+                NO_SIGNING_KEY,
+            )
+            .await?;
+
+        let release = ReleaseFile::from_reader(std::io::BufReader::new(std::fs::File::open(
+            td.path().join("dists/dist/Release"),
+        )?))?;
+        assert_eq!(release.field_str("X-Custom"), Some("value"));
+
+        builder.add_publish_hook(VetoHook);
+        let res = builder
+            .publish(
+                &writer,
+                &resolver,
+                "dists/other",
+                1,
+                &NO_PROGRESS_CB,
+                NO_SIGNING_KEY,
+            )
+            .await;
+        assert!(matches!(res, Err(DebianError::RepositoryPublishVetoed(_))));
+        assert!(!td.path().join("dists/other/Release").exists());
+
         Ok(())
     }
 }
@@ -2,18 +2,34 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-/*! `Contents` index file handling. */
+/*! `Contents` index file handling.
+
+[ContentsFile] holds a parsed `Contents` file. [ContentsFileReader] and
+[ContentsFileAsyncReader] parse `Contents` files from readers, enforcing [ParseLimits] on
+line lengths, the number of lines and the total size. [ContentsFileWriter] serializes path to package
+mappings into `Contents` files suitable for publishing.
+*/
 
 use {
-    crate::error::Result,
-    futures::{AsyncBufRead, AsyncBufReadExt},
+    crate::{
+        control::ParseLimits,
+        deb::search::PathMatcher,
+        error::Result,
+        io::{read_compressed, Compression},
+        repository::builder::IndexFileReader,
+    },
+    futures::{AsyncBufRead, AsyncRead},
     pin_project::pin_project,
     std::{
         collections::{BTreeMap, BTreeSet},
         io::{BufRead, Write},
+        pin::Pin,
     },
 };
 
+/// The column header line that historically appears before the table in `Contents` files.
+pub const CONTENTS_TABLE_HEADER: &str = "FILE    LOCATION";
+
 /// Represents a `Contents` file.
 ///
 /// A `Contents` file maps paths to packages. It facilitates lookups of which paths
@@ -37,7 +53,7 @@ impl ContentsFile {
 
         let words = line.split_ascii_whitespace().collect::<Vec<_>>();
 
-        if words.len() != 2 {
+        if words.len() != 2 || words == ["FILE", "LOCATION"] {
             return Ok(());
         }
 
@@ -76,6 +92,25 @@ impl ContentsFile {
         }
     }
 
+    /// Obtain an iterator of all paths and the packages they occur in.
+    ///
+    /// Paths are emitted in sorted order.
+    pub fn iter_paths(&self) -> impl Iterator<Item = (&str, impl Iterator<Item = &str>)> + '_ {
+        self.paths
+            .iter()
+            .map(|(path, packages)| (path.as_str(), packages.iter().map(|x| x.as_str())))
+    }
+
+    /// Obtain an iterator of paths matching a [PathMatcher] and the packages they occur in.
+    ///
+    /// Paths are emitted in sorted order.
+    pub fn find_paths<'a>(
+        &'a self,
+        matcher: &'a PathMatcher,
+    ) -> impl Iterator<Item = (&'a str, impl Iterator<Item = &'a str>)> + 'a {
+        self.iter_paths().filter(|(path, _)| matcher.matches(path))
+    }
+
     /// Obtain an iterator of paths in a given package.
     pub fn package_paths(&self, package: &str) -> Box<dyn Iterator<Item = &str> + '_> {
         if let Some(paths) = self.packages.get(package) {
@@ -91,7 +126,7 @@ impl ContentsFile {
             // BTreeSet doesn't have a .join(). So we need to build a collection that does.
             let packages = packages.iter().map(|s| s.as_str()).collect::<Vec<_>>();
 
-            format!("{}    {}\n", path, packages.join(",'"))
+            format!("{}    {}\n", path, packages.join(","))
         })
     }
 
@@ -110,10 +145,153 @@ impl ContentsFile {
     }
 }
 
+/// Writes `Contents` files.
+///
+/// Instances are populated with path to package mappings, either by registering
+/// individual entries or by converting from an existing [ContentsFile]. The
+/// mappings are then serialized to a `Contents` file, optionally with compression.
+///
+/// Paths are emitted in sorted order and packages for a given path are sorted and
+/// comma delimited, as required by the repository format. Leading `/` are stripped
+/// from paths.
+///
+/// An optional freeform header can be emitted before the table. When a header is
+/// defined, the `FILE LOCATION` column header line is emitted after it, mirroring
+/// the format historically produced by Debian's archive tooling.
+#[derive(Clone, Debug, Default)]
+pub struct ContentsFileWriter {
+    contents: ContentsFile,
+    header: Option<String>,
+}
+
+impl From<ContentsFile> for ContentsFileWriter {
+    fn from(contents: ContentsFile) -> Self {
+        Self {
+            contents,
+            header: None,
+        }
+    }
+}
+
+impl ContentsFileWriter {
+    /// Construct an instance from an iterable of `(path, package)` entries.
+    pub fn from_entries(entries: impl IntoIterator<Item = (impl ToString, impl ToString)>) -> Self {
+        let mut res = Self::default();
+        res.add_entries(entries);
+
+        res
+    }
+
+    /// Set the freeform header text to emit before the table.
+    pub fn set_header(&mut self, header: impl ToString) {
+        self.header = Some(header.to_string());
+    }
+
+    /// Register a path as belonging to a package.
+    ///
+    /// `package` is typically qualified with its section. e.g. `admin/apt`.
+    pub fn add_entry(&mut self, path: impl ToString, package: impl ToString) {
+        self.contents.add_package_path(
+            path.to_string().trim_start_matches('/').to_string(),
+            package.to_string(),
+        );
+    }
+
+    /// Register multiple `(path, package)` entries.
+    pub fn add_entries(
+        &mut self,
+        entries: impl IntoIterator<Item = (impl ToString, impl ToString)>,
+    ) {
+        for (path, package) in entries {
+            self.add_entry(path, package);
+        }
+    }
+
+    /// Register a path as belonging to multiple packages.
+    pub fn add_path_packages(
+        &mut self,
+        path: impl ToString,
+        packages: impl IntoIterator<Item = impl ToString>,
+    ) {
+        let path = path.to_string();
+
+        for package in packages {
+            self.add_entry(&path, package);
+        }
+    }
+
+    /// Obtain the [ContentsFile] holding registered entries.
+    pub fn contents(&self) -> &ContentsFile {
+        &self.contents
+    }
+
+    /// Write the uncompressed `Contents` file to a writer.
+    ///
+    /// Returns the total number of bytes written.
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<usize> {
+        let mut bytes_count = 0;
+
+        if let Some(header) = &self.header {
+            for line in header.lines() {
+                writer.write_all(line.as_bytes())?;
+                writer.write_all(b"\n")?;
+                bytes_count += line.len() + 1;
+            }
+
+            writer.write_all(b"\n")?;
+            writer.write_all(CONTENTS_TABLE_HEADER.as_bytes())?;
+            writer.write_all(b"\n")?;
+            bytes_count += CONTENTS_TABLE_HEADER.len() + 2;
+        }
+
+        bytes_count += self.contents.write_to(writer)?;
+
+        Ok(bytes_count)
+    }
+
+    /// Obtain the uncompressed `Contents` file content.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.write_to(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    /// Obtain an [AsyncRead] emitting the `Contents` file with the given compression applied.
+    pub fn reader(&self, compression: Compression) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        Ok(read_compressed(
+            futures::io::Cursor::new(self.to_vec()?),
+            compression,
+        ))
+    }
+
+    /// Obtain an [IndexFileReader] for publishing this file.
+    ///
+    /// `directory` is the directory relative to the distribution root the file is written to
+    /// (e.g. `main`) and `filename` is the filename without a compression extension
+    /// (e.g. `Contents-amd64`).
+    pub fn index_file_reader(
+        &self,
+        directory: impl ToString,
+        filename: impl ToString,
+        compression: Compression,
+    ) -> Result<IndexFileReader<'static>> {
+        Ok(IndexFileReader {
+            reader: self.reader(compression)?,
+            compression,
+            directory: directory.to_string(),
+            filename: filename.to_string(),
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ContentsFileReader<R> {
     reader: R,
     contents: ContentsFile,
+    limits: ParseLimits,
+    lines: usize,
+    bytes: u64,
 }
 
 impl<R: BufRead> ContentsFileReader<R> {
@@ -122,9 +300,20 @@ impl<R: BufRead> ContentsFileReader<R> {
         Self {
             reader,
             contents: ContentsFile::default(),
+            limits: ParseLimits::default(),
+            lines: 0,
+            bytes: 0,
         }
     }
 
+    /// Set the limits enforced on input.
+    ///
+    /// [ParseLimits::max_field_length] bounds line lengths, [ParseLimits::max_entries]
+    /// the number of lines and [ParseLimits::max_total_size] the total size.
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        self.limits = limits;
+    }
+
     /// Consumes the instance, returning the original reader.
     pub fn into_inner(self) -> R {
         self.reader
@@ -134,7 +323,8 @@ impl<R: BufRead> ContentsFileReader<R> {
     pub fn read_all(&mut self) -> Result<usize> {
         let mut bytes_read = 0;
 
-        while let Ok(read_size) = self.read_line() {
+        loop {
+            let read_size = self.read_line()?;
             if read_size == 0 {
                 break;
             }
@@ -148,9 +338,13 @@ impl<R: BufRead> ContentsFileReader<R> {
     /// Read and parse a single line from the reader.
     pub fn read_line(&mut self) -> Result<usize> {
         let mut line = String::new();
-        let read_size = self.reader.read_line(&mut line)?;
+        let read_size = self.limits.read_line(&mut self.reader, &mut line)?;
 
         if read_size != 0 {
+            self.lines += 1;
+            self.bytes += read_size as u64;
+            self.limits.check_entries(self.lines)?;
+            self.limits.check_total_size(self.bytes)?;
             self.contents.parse_and_add_line(&line)?;
         }
 
@@ -168,6 +362,9 @@ pub struct ContentsFileAsyncReader<R> {
     #[pin]
     reader: R,
     contents: ContentsFile,
+    limits: ParseLimits,
+    lines: usize,
+    bytes: u64,
 }
 
 impl<R> ContentsFileAsyncReader<R>
@@ -179,9 +376,20 @@ where
         Self {
             reader,
             contents: ContentsFile::default(),
+            limits: ParseLimits::default(),
+            lines: 0,
+            bytes: 0,
         }
     }
 
+    /// Set the limits enforced on input.
+    ///
+    /// [ParseLimits::max_field_length] bounds line lengths, [ParseLimits::max_entries]
+    /// the number of lines and [ParseLimits::max_total_size] the total size.
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        self.limits = limits;
+    }
+
     /// Consumes self, returning the inner reader.
     pub fn into_inner(self) -> R {
         self.reader
@@ -191,7 +399,8 @@ where
     pub async fn read_all(&mut self) -> Result<usize> {
         let mut bytes_read = 0;
 
-        while let Ok(read_size) = self.read_line().await {
+        loop {
+            let read_size = self.read_line().await?;
             if read_size == 0 {
                 break;
             }
@@ -205,9 +414,16 @@ where
     /// Read and parse a single line from the reader.
     pub async fn read_line(&mut self) -> Result<usize> {
         let mut line = String::new();
-        let read_size = self.reader.read_line(&mut line).await?;
+        let read_size = self
+            .limits
+            .read_line_async(&mut self.reader, &mut line)
+            .await?;
 
         if read_size != 0 {
+            self.lines += 1;
+            self.bytes += read_size as u64;
+            self.limits.check_entries(self.lines)?;
+            self.limits.check_total_size(self.bytes)?;
             self.contents.parse_and_add_line(&line)?;
         }
 
@@ -219,3 +435,148 @@ where
         (self.contents, self.reader)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::error::DebianError, futures::AsyncReadExt};
+
+    #[test]
+    fn writer_round_trip() -> Result<()> {
+        let mut writer = ContentsFileWriter::from_entries([
+            ("/usr/bin/zstd", "utils/zstd"),
+            ("usr/bin/apt", "admin/apt"),
+            ("usr/share/doc/common", "admin/apt"),
+            ("usr/share/doc/common", "libs/libc6"),
+        ]);
+        writer.set_header("This file maps files to packages.");
+
+        let data = writer.to_vec()?;
+        assert_eq!(
+            String::from_utf8(data.clone()).unwrap(),
+            "This file maps files to packages.\n\nFILE    LOCATION\n\
+            usr/bin/apt    admin/apt\n\
+            usr/bin/zstd    utils/zstd\n\
+            usr/share/doc/common    admin/apt,libs/libc6\n"
+        );
+
+        let mut reader = ContentsFileReader::new(std::io::Cursor::new(data));
+        reader.read_all()?;
+        let (contents, _) = reader.consume();
+
+        assert_eq!(contents.iter_paths().count(), 3);
+        assert_eq!(
+            contents
+                .packages_with_path("usr/share/doc/common")
+                .collect::<Vec<_>>(),
+            vec!["admin/apt", "libs/libc6"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn find_paths() -> Result<()> {
+        let mut contents = ContentsFile::default();
+        contents.add_package_path("usr/bin/apt".into(), "admin/apt".into());
+        contents.add_package_path("usr/bin/zstd".into(), "utils/zstd".into());
+        contents.add_package_path(
+            "usr/lib/systemd/system/apt.service".into(),
+            "admin/apt".into(),
+        );
+        contents.add_package_path("usr/share/doc/common".into(), "admin/apt".into());
+        contents.add_package_path("usr/share/doc/common".into(), "libs/libc6".into());
+
+        let find = |matcher: PathMatcher| {
+            contents
+                .find_paths(&matcher)
+                .map(|(path, packages)| (path.to_string(), packages.collect::<Vec<_>>().join(",")))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            find(PathMatcher::glob("/usr/bin/*")?),
+            vec![
+                ("usr/bin/apt".to_string(), "admin/apt".to_string()),
+                ("usr/bin/zstd".to_string(), "utils/zstd".to_string()),
+            ]
+        );
+        assert_eq!(
+            find(PathMatcher::glob("*.service")?),
+            vec![(
+                "usr/lib/systemd/system/apt.service".to_string(),
+                "admin/apt".to_string()
+            )]
+        );
+        assert_eq!(
+            find(PathMatcher::regex("doc/")?),
+            vec![(
+                "usr/share/doc/common".to_string(),
+                "admin/apt,libs/libc6".to_string()
+            )]
+        );
+        assert!(find(PathMatcher::regex("^bin/")?).is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writer_compressed() -> Result<()> {
+        let writer = ContentsFileWriter::from_entries([("usr/bin/apt", "admin/apt")]);
+
+        let mut compressed = vec![];
+        writer
+            .reader(Compression::Gzip)?
+            .read_to_end(&mut compressed)
+            .await?;
+
+        let mut decoded = vec![];
+        crate::io::read_decompressed(
+            Box::pin(futures::io::Cursor::new(compressed)),
+            Compression::Gzip,
+        )
+        .await?
+        .read_to_end(&mut decoded)
+        .await?;
+
+        assert_eq!(decoded, writer.to_vec()?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reader_limits() -> Result<()> {
+        let data = b"usr/bin/apt    admin/apt\nusr/bin/zstd    utils/zstd\n";
+
+        let mut reader = ContentsFileAsyncReader::new(futures::io::Cursor::new(data));
+        reader.set_limits(ParseLimits {
+            max_entries: 1,
+            ..Default::default()
+        });
+        assert!(matches!(
+            reader.read_all().await,
+            Err(DebianError::ParseTooManyEntries(1))
+        ));
+
+        let mut reader = ContentsFileAsyncReader::new(futures::io::Cursor::new(data));
+        reader.set_limits(ParseLimits {
+            max_field_length: 16,
+            ..Default::default()
+        });
+        assert!(matches!(
+            reader.read_all().await,
+            Err(DebianError::ParseFieldTooLong(16))
+        ));
+
+        let mut reader = ContentsFileAsyncReader::new(futures::io::Cursor::new(data));
+        reader.set_limits(ParseLimits {
+            max_total_size: 40,
+            ..Default::default()
+        });
+        assert!(matches!(
+            reader.read_all().await,
+            Err(DebianError::ParseInputTooLarge(40))
+        ));
+
+        Ok(())
+    }
+}
@@ -6,20 +6,50 @@
 
 use {
     crate::{
+        binary_package_control::{BinaryPackageControlFile, Priority, Section},
+        control::{ControlField, ControlParagraph},
         error::{DebianError, Result},
-        io::ContentDigest,
+        io::{
+            write_compressed, Compression, ContentDigest, ContentValidatingReader,
+            MultiContentDigest, MultiDigester,
+        },
+        keyring::{Keyring, TrustPolicy, VerificationPolicy},
         repository::{
-            reader_from_str, writer_from_str, CopyPhase, PublishEvent, ReleaseReader,
-            RepositoryRootReader, RepositoryWriteOperation, RepositoryWriter,
+            audit::{AuditLog, AuditingWriter},
+            builder::NO_SIGNING_KEY,
+            reader_from_str,
+            release::{
+                ChecksumType, ClassifiedReleaseFileEntry, FileManifestEntry, PackagesFileEntry,
+                ReleaseFile, ReleaseFileEntry,
+            },
+            signing::write_signed_release_with_keys,
+            writer_from_str, CopyPhase, PublishEvent, ReleaseReader,
+            RepositoryPathVerificationState, RepositoryRootReader, RepositoryWriteOperation,
+            RepositoryWriter,
         },
+        signing_key::parse_armored_secret_key,
     },
-    futures::StreamExt,
+    futures::{AsyncReadExt, AsyncWriteExt, StreamExt, TryStreamExt},
+    pgp::{SignedPublicKey, SignedSecretKey},
+    rand::Rng,
     serde::{Deserialize, Serialize},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+        io::Write,
+        path::Path,
+        sync::{Arc, Mutex},
+    },
 };
 
 /// Well-known files at the root of distribution/release directories.
 const RELEASE_FILES: &[&str; 4] = &["ChangeLog", "InRelease", "Release", "Release.gpg"];
 
+/// Release files holding signatures, which are regenerated when re-signing.
+const SIGNATURE_FILES: &[&str; 2] = &["InRelease", "Release.gpg"];
+
+type KeyPasswordFn = dyn Fn() -> String + Send + Sync;
+
 /// A configuration for initializing a [RepositoryCopier].
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -52,6 +82,28 @@ pub struct RepositoryCopierConfig {
     /// being copied.
     pub binary_packages_only_architectures: Option<Vec<String>>,
 
+    /// Glob patterns of names of binary packages to copy.
+    ///
+    /// If defined, only packages whose name matches at least 1 pattern are copied.
+    /// `Packages` files are regenerated to only list copied packages when any binary
+    /// package filter is defined, which requires `resign_key_path`.
+    pub binary_packages_only_names: Option<Vec<String>>,
+
+    /// Filter of sections of binary packages to copy.
+    ///
+    /// Requires `resign_key_path`.
+    pub binary_packages_only_sections: Option<Vec<String>>,
+
+    /// Filter of priorities of binary packages to copy.
+    ///
+    /// Requires `resign_key_path`.
+    pub binary_packages_only_priorities: Option<Vec<String>>,
+
+    /// Maximum size in bytes of binary packages to copy.
+    ///
+    /// Requires `resign_key_path`.
+    pub binary_packages_max_size: Option<u64>,
+
     /// Whether to copy installer binary packages.
     pub installer_binary_packages_copy: Option<bool>,
 
@@ -63,628 +115,4091 @@ pub struct RepositoryCopierConfig {
 
     /// Whether to copy source packages.
     pub sources_copy: Option<bool>,
-}
 
-struct GenericCopy {
-    source_path: String,
-    dest_path: String,
-    expected_content: Option<(u64, ContentDigest)>,
-}
+    /// Whether to copy installer image trees.
+    pub installers_copy: Option<bool>,
 
-/// Entity for copying Debian repository content.
-///
-/// Instances of this type can be used to copy select Debian repository content
-/// between a reader and a writer.
-///
-/// The file layout and content is preserved, so existing PGP signatures can be preserved.
-/// However, the copier does have the ability to selectively filter which files are copied.
-/// So the destination repository may reference content that doesn't exist in that location.
-///
-/// Because repositories do not have a standardized mechanism for discovering dists/releases
-/// within, this type must be told which distributions to copy. Copying is performed 1
-/// distribution at a time.
-///
-/// By default, instances copy all copyable content. Installer files are currently not
-/// supported. Incomplete copies for all other files is considered a bug and should be
-/// reported.
-///
-/// Various `set_*` methods exist to control the copying behavior.
-pub struct RepositoryCopier {
-    /// Filter of components that should be copied.
-    only_components: Option<Vec<String>>,
+    /// Filter of architectures of installer image trees to copy.
+    ///
+    /// If not defined, all architectures will be copied if installer image trees are
+    /// being copied.
+    pub installers_only_architectures: Option<Vec<String>>,
 
-    /// Whether to copy non-installer binary packages.
-    binary_packages_copy: bool,
-    /// Filter of architectures of binary packages to copy.
-    binary_packages_only_arches: Option<Vec<String>>,
+    /// Path to a local file containing PGP public keys.
+    ///
+    /// The file can be ASCII armored, a binary keyring or a keybox. See [Keyring].
+    ///
+    /// If defined, the source distribution's `InRelease` file must be signed by one of
+    /// these keys or the copy is aborted before anything is written.
+    pub source_keyring_path: Option<String>,
 
-    /// Whether to copy installer binary packages.
-    installer_binary_packages_copy: bool,
-    /// Filter of architectures of installer binary packages to copy.
-    installer_binary_packages_only_arches: Option<Vec<String>>,
+    /// Fingerprints of PGP keys to fetch from keyservers and trust.
+    ///
+    /// Fetched keys are trusted alongside those in `source_keyring_path`. Requires the
+    /// `http` feature. See [crate::key_fetch].
+    #[serde(default)]
+    pub source_key_fingerprints: Vec<String>,
 
-    /// Whether to copy source packages.
-    sources_copy: bool,
+    /// Keyservers to fetch `source_key_fingerprints` from.
+    ///
+    /// Defaults to `hkps://keys.openpgp.org`.
+    #[serde(default)]
+    pub source_keyservers: Vec<String>,
 
-    /// Whether to copy installers files.
-    installers_copy: bool,
-    /// Filter of architectures of installers to copy.
-    #[allow(unused)]
-    installers_only_arches: Option<Vec<String>>,
+    /// Path to a local directory to cache keys fetched for `source_key_fingerprints` in.
+    pub source_key_cache_path: Option<String>,
+
+    /// Concurrency limits for individual copy phases.
+    ///
+    /// Keys are phase names, e.g. `binary_packages` or `release_indices`. Phases not
+    /// defined use the global concurrency limit.
+    #[serde(default)]
+    pub phase_concurrency: HashMap<CopyPhase, CopyConcurrency>,
+
+    /// Error policies for individual copy phases.
+    ///
+    /// Keys are phase names, e.g. `binary_packages`. Phases not defined abort upon the
+    /// first failed copy.
+    #[serde(default)]
+    pub error_policy: HashMap<CopyPhase, CopyErrorPolicy>,
+
+    /// Path to a local file to write a JSON report of skipped copies to.
+    pub skipped_report_path: Option<String>,
+
+    /// Path to a local file to write a JSON [CopyReport] to.
+    pub report_path: Option<String>,
+
+    /// Whether to delete destination files not referenced by the copied distributions.
+    ///
+    /// All distributions sharing the destination's pool must be copied by the same
+    /// config, otherwise files referenced only by other distributions are deleted.
+    pub delete: Option<bool>,
+
+    /// Path to a local file recording completed copies.
+    ///
+    /// If defined, an interrupted copy can be re-run and will skip all work recorded
+    /// as completed in this file.
+    pub state_path: Option<String>,
+
+    /// Path to a local file containing an ASCII armored PGP secret key.
+    ///
+    /// If defined, upstream signatures are discarded and the copied release files are
+    /// signed with this key instead. The key must not be passphrase protected.
+    pub resign_key_path: Option<String>,
+
+    /// Paths to local files containing further ASCII armored PGP secret keys.
+    ///
+    /// The copied release files are signed with these keys in addition to the key in
+    /// `resign_key_path`. Signing with the old and the new key during a key rotation lets
+    /// clients trusting either verify the copy.
+    #[serde(default)]
+    pub resign_additional_key_paths: Vec<String>,
+
+    /// Fields to set in copied `Release` files.
+    ///
+    /// Requires `resign_key_path`.
+    #[serde(default)]
+    pub release_set_fields: BTreeMap<String, String>,
+
+    /// Fields to remove from copied `Release` files.
+    ///
+    /// Requires `resign_key_path`.
+    #[serde(default)]
+    pub release_remove_fields: Vec<String>,
+
+    /// Glob patterns of distribution relative paths of indices files to not copy.
+    ///
+    /// Matching files are also removed from copied `Release` files. Requires
+    /// `resign_key_path`.
+    #[serde(default)]
+    pub release_drop_indices: Vec<String>,
+
+    /// Whether to incrementally sync against existing destination content.
+    ///
+    /// Requires `destination_url` to also be readable.
+    pub sync: Option<bool>,
+
+    /// Declarative description of what to mirror.
+    ///
+    /// Applied before the other fields of this config, which take precedence.
+    pub mirror: Option<MirrorConfig>,
+
+    /// Path relative to the destination root of an audit log to append to.
+    ///
+    /// The existing log is read from the destination. A new log is started if it doesn't
+    /// exist or the destination is a `null://` URL. Other destinations that can't be read
+    /// fail the copy rather than overwriting the existing log.
+    pub audit_log_path: Option<String>,
+
+    /// Path to a local file containing an ASCII armored PGP secret key.
+    ///
+    /// If defined, a cleartext signed copy of the audit log is written next to it with a
+    /// `.asc` extension. The key must not be passphrase protected.
+    pub audit_log_signing_key_path: Option<String>,
 }
 
-impl Default for RepositoryCopier {
-    fn default() -> Self {
-        Self {
-            only_components: None,
-            binary_packages_copy: true,
-            binary_packages_only_arches: None,
-            installer_binary_packages_copy: true,
-            installer_binary_packages_only_arches: None,
-            sources_copy: true,
-            // TODO enable once implemented
-            installers_copy: false,
-            installers_only_arches: None,
-        }
-    }
+type BinaryPackageFilterFn = dyn Fn(&BinaryPackageControlFile) -> bool + Send + Sync;
+
+/// Criteria for selecting which binary packages a [RepositoryCopier] copies.
+///
+/// A package is copied only if it satisfies every configured criterion. The default
+/// instance has no criteria and matches every package.
+#[derive(Clone, Default)]
+pub struct BinaryPackageFilter {
+    names: Option<Vec<glob::Pattern>>,
+    /// Sections and their optional archive areas.
+    sections: Option<Vec<(Option<String>, Section)>>,
+    priorities: Option<Vec<Priority>>,
+    max_size: Option<u64>,
+    custom: Vec<Arc<BinaryPackageFilterFn>>,
 }
 
-impl RepositoryCopier {
-    /// Set an explicit list of components whose files to copy.
-    pub fn set_only_components(&mut self, components: impl Iterator<Item = String>) {
-        self.only_components = Some(components.collect());
+impl std::fmt::Debug for BinaryPackageFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BinaryPackageFilter")
+            .field("names", &self.names)
+            .field("sections", &self.sections)
+            .field("priorities", &self.priorities)
+            .field("max_size", &self.max_size)
+            .field("custom", &format!("{} functions", self.custom.len()))
+            .finish()
     }
+}
 
-    /// Set whether to copy non-installer binary packages.
-    pub fn set_binary_packages_copy(&mut self, value: bool) {
-        self.binary_packages_copy = value;
+impl BinaryPackageFilter {
+    /// Only match packages whose name matches at least 1 of the given glob patterns.
+    ///
+    /// e.g. `libc6*` or `python3.?`.
+    pub fn set_only_names(
+        &mut self,
+        patterns: impl Iterator<Item = impl AsRef<str>>,
+    ) -> Result<()> {
+        self.names = Some(
+            patterns
+                .map(|p| glob::Pattern::new(p.as_ref()))
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+        );
+
+        Ok(())
     }
 
-    /// Set a filter for architectures of non-installer binary packages to copy.
+    /// Only match packages in one of the given sections.
     ///
-    /// Binary packages for architectures not in this set will be ignored.
-    pub fn set_binary_packages_only_arches(&mut self, value: impl Iterator<Item = String>) {
-        self.binary_packages_only_arches = Some(value.collect::<Vec<_>>());
+    /// Sections qualified with an archive area (e.g. `contrib/net`) match either the full
+    /// value or the unqualified section (`net`).
+    pub fn set_only_sections(&mut self, sections: impl Iterator<Item = impl ToString>) {
+        self.sections = Some(
+            sections
+                .map(|x| {
+                    let x = x.to_string();
+                    match x.split_once('/') {
+                        Some((area, section)) => (Some(area.to_string()), Section::from(section)),
+                        None => (None, Section::from(x.as_str())),
+                    }
+                })
+                .collect(),
+        );
     }
 
-    /// Set whether to copy installer binary packages.
-    pub fn set_installer_binary_packages_copy(&mut self, value: bool) {
-        self.installer_binary_packages_copy = value;
+    /// Only match packages having one of the given priorities.
+    pub fn set_only_priorities(&mut self, priorities: impl Iterator<Item = impl ToString>) {
+        self.priorities = Some(
+            priorities
+                .map(|x| Priority::from(x.to_string().as_str()))
+                .collect(),
+        );
     }
 
-    /// Set a filter for architectures of installer binary packages to copy.
+    /// Only match packages whose `.deb` is at most this many bytes.
     ///
-    /// Binary packages for architectures not in this set will be ignored.
-    pub fn set_installer_binary_packages_only_arches(
+    /// Packages without a valid `Size` field don't match.
+    pub fn set_max_size(&mut self, size: u64) {
+        self.max_size = Some(size);
+    }
+
+    /// Register a custom function that must return true for a package to match.
+    pub fn add_filter(
         &mut self,
-        value: impl Iterator<Item = String>,
+        f: impl Fn(&BinaryPackageControlFile) -> bool + Send + Sync + 'static,
     ) {
-        self.installer_binary_packages_only_arches = Some(value.collect::<Vec<_>>());
+        self.custom.push(Arc::new(f));
     }
 
-    /// Set whether to copy sources package files.
-    pub fn set_sources_copy(&mut self, value: bool) {
-        self.sources_copy = value;
+    /// Whether no criteria are defined, so every package matches.
+    fn is_empty(&self) -> bool {
+        self.names.is_none()
+            && self.sections.is_none()
+            && self.priorities.is_none()
+            && self.max_size.is_none()
+            && self.custom.is_empty()
     }
 
-    /// Perform a copy operation as defined by a [RepositoryCopierConfig].
-    pub async fn copy_from_config(
-        config: RepositoryCopierConfig,
-        max_copy_operations: usize,
-        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
-    ) -> Result<()> {
-        let root_reader = reader_from_str(config.source_url)?;
-        let writer = writer_from_str(config.destination_url).await?;
-
-        let mut copier = Self::default();
-
-        if let Some(v) = config.only_components {
-            copier.set_only_components(v.into_iter());
-        }
-        if let Some(v) = config.binary_packages_copy {
-            copier.set_binary_packages_copy(v);
-        }
-        if let Some(v) = config.binary_packages_only_architectures {
-            copier.set_binary_packages_only_arches(v.into_iter());
-        }
-        if let Some(v) = config.installer_binary_packages_copy {
-            copier.set_installer_binary_packages_copy(v);
-        }
-        if let Some(v) = config.installer_binary_packages_only_architectures {
-            copier.set_installer_binary_packages_only_arches(v.into_iter());
+    /// Whether a binary package satisfies all criteria.
+    pub fn matches(&self, cf: &BinaryPackageControlFile) -> bool {
+        if let Some(names) = &self.names {
+            match cf.package() {
+                Ok(name) if names.iter().any(|p| p.matches(name)) => {}
+                _ => return false,
+            }
         }
-        if let Some(v) = config.sources_copy {
-            copier.set_sources_copy(v);
+
+        if let Some(sections) = &self.sections {
+            match cf.typed_section() {
+                Some(section)
+                    if sections.iter().any(|(area, s)| {
+                        s == &section
+                            && area
+                                .as_deref()
+                                .map_or(true, |area| cf.archive_area() == Some(area))
+                    }) => {}
+                _ => return false,
+            }
         }
 
-        for dist in config.distributions {
-            copier
-                .copy_distribution(
-                    root_reader.as_ref(),
-                    writer.as_ref(),
-                    &dist,
-                    max_copy_operations,
-                    progress_cb,
-                )
-                .await?;
+        if let Some(priorities) = &self.priorities {
+            match cf.typed_priority() {
+                Some(priority) if priorities.contains(&priority) => {}
+                _ => return false,
+            }
         }
-        for path in config.distribution_paths {
-            copier
-                .copy_distribution_path(
-                    root_reader.as_ref(),
-                    writer.as_ref(),
-                    &path,
-                    max_copy_operations,
-                    progress_cb,
-                )
-                .await?;
+
+        if let Some(max_size) = self.max_size {
+            match cf.field_u64("Size") {
+                Some(Ok(size)) if size <= max_size => {}
+                _ => return false,
+            }
         }
 
-        Ok(())
+        self.custom.iter().all(|f| f(cf))
     }
+}
 
-    /// Copy content for a given distribution given a distribution name.
+type ReleaseRewriteFn = dyn Fn(&mut ReleaseFile<'static>) + Send + Sync;
+
+/// Rewrites of a distribution's `Release` file applied by a [RepositoryCopier].
+///
+/// Rewriting invalidates upstream signatures. So the rewritten file is signed with the key
+/// set via [RepositoryCopier::set_resign_key()], which is required.
+#[derive(Clone, Default)]
+pub struct ReleaseRewriter {
+    fields: Vec<(String, Option<String>)>,
+    drop_indices: Vec<glob::Pattern>,
+    hooks: Vec<Arc<ReleaseRewriteFn>>,
+}
+
+impl std::fmt::Debug for ReleaseRewriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReleaseRewriter")
+            .field("fields", &self.fields)
+            .field("drop_indices", &self.drop_indices)
+            .field("hooks", &format!("{} functions", self.hooks.len()))
+            .finish()
+    }
+}
+
+impl ReleaseRewriter {
+    /// Set the value of a field, e.g. `Origin`, `Label`, `Suite`, `Codename`, or `Valid-Until`.
     ///
-    /// This is a proxy for [Self::copy_distribution_path()] which simply passes
-    /// `dists/{distribution}` as the path value. This is the standard layout for Debian
-    /// repositories.
-    pub async fn copy_distribution(
-        &self,
-        root_reader: &dyn RepositoryRootReader,
-        writer: &dyn RepositoryWriter,
-        distribution: &str,
-        max_copy_operations: usize,
-        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
-    ) -> Result<()> {
-        self.copy_distribution_path(
-            root_reader,
-            writer,
-            &format!("dists/{}", distribution),
-            max_copy_operations,
-            progress_cb,
-        )
-        .await
+    /// Existing fields are replaced in place. New fields are appended.
+    pub fn set_field(&mut self, name: impl ToString, value: impl ToString) {
+        self.fields
+            .push((name.to_string(), Some(value.to_string())));
     }
 
-    /// Copy content for a given distribution at a path relative to the repository root.
+    /// Remove a field.
+    pub fn remove_field(&mut self, name: impl ToString) {
+        self.fields.push((name.to_string(), None));
+    }
+
+    /// Drop indices files whose path matches at least 1 of the given glob patterns.
     ///
-    /// The given `distribution_path` is usually prefixed with `dists/`. e.g. `dists/bullseye`.
-    /// But it can be something else for non-standard repository layouts.
-    pub async fn copy_distribution_path(
-        &self,
-        root_reader: &dyn RepositoryRootReader,
-        writer: &dyn RepositoryWriter,
-        distribution_path: &str,
-        max_copy_operations: usize,
-        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    /// Paths are relative to the distribution directory. e.g. `*/i18n/*` drops all
+    /// translations. Dropped files are removed from the checksum fields and aren't copied.
+    pub fn set_drop_indices(
+        &mut self,
+        patterns: impl Iterator<Item = impl AsRef<str>>,
     ) -> Result<()> {
-        let release = root_reader
-            .release_reader_with_distribution_path(distribution_path)
-            .await?;
+        self.drop_indices = patterns
+            .map(|p| glob::Pattern::new(p.as_ref()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
 
-        // We copy all the pool artifacts first because otherwise a client could fetch an indices
-        // file referring to a pool file that isn't available yet.
+        Ok(())
+    }
 
-        if self.binary_packages_copy {
-            if let Some(cb) = progress_cb {
-                cb(PublishEvent::CopyPhaseBegin(CopyPhase::BinaryPackages));
-            }
-            self.copy_binary_packages(
-                root_reader,
-                writer,
-                release.as_ref(),
-                false,
-                max_copy_operations,
-                progress_cb,
-            )
-            .await?;
-            if let Some(cb) = progress_cb {
-                cb(PublishEvent::CopyPhaseEnd(CopyPhase::BinaryPackages));
+    /// Register a custom function to modify the release file.
+    ///
+    /// Hooks run after field and indices rewrites, in registration order.
+    pub fn add_hook(&mut self, f: impl Fn(&mut ReleaseFile<'static>) + Send + Sync + 'static) {
+        self.hooks.push(Arc::new(f));
+    }
+
+    /// Whether an indices file at a distribution relative path is dropped.
+    pub fn drops_index(&self, path: &str) -> bool {
+        self.drop_indices.iter().any(|p| p.matches(path))
+    }
+
+    /// Produce the rewritten variant of a release file.
+    pub fn rewrite(&self, release: &ReleaseFile<'_>) -> ReleaseFile<'static> {
+        let checksum_fields = ChecksumType::preferred_order()
+            .map(|c| c.field_name().to_lowercase())
+            .collect::<Vec<_>>();
+
+        let mut fields = release
+            .iter_fields()
+            .map(|field| {
+                let value = if checksum_fields.contains(&field.name().to_lowercase()) {
+                    // Entries are of form ` <digest> <size> <path>`.
+                    std::iter::once("".to_string())
+                        .chain(
+                            field
+                                .iter_lines()
+                                .filter(|line| match line.split_ascii_whitespace().nth(2) {
+                                    Some(path) => !self.drops_index(path),
+                                    None => false,
+                                })
+                                .map(|line| format!(" {}", line)),
+                        )
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                } else {
+                    field.value_str().to_string()
+                };
+
+                (field.name().to_string(), Some(value))
+            })
+            .collect::<Vec<_>>();
+
+        for (name, value) in &self.fields {
+            if let Some(existing) = fields
+                .iter_mut()
+                .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            {
+                existing.1 = value.clone();
+            } else if value.is_some() {
+                fields.push((name.clone(), value.clone()));
             }
         }
 
-        if self.installer_binary_packages_copy {
-            if let Some(cb) = progress_cb {
-                cb(PublishEvent::CopyPhaseBegin(
-                    CopyPhase::InstallerBinaryPackages,
-                ));
+        let mut para = ControlParagraph::default();
+        for (name, value) in fields {
+            if let Some(value) = value {
+                para.set_field(ControlField::new(name.into(), value.into()));
             }
-            self.copy_binary_packages(
+        }
+
+        let mut release = ReleaseFile::from(para);
+        for hook in &self.hooks {
+            hook(&mut release);
+        }
+
+        release
+    }
+}
+
+/// A `Packages` file regenerated to only list packages matching a [BinaryPackageFilter].
+struct FilteredPackagesIndex {
+    /// Distribution relative path of the uncompressed file.
+    path: String,
+    /// Compression formats the release file lists the file in.
+    compressions: Vec<Compression>,
+    /// Paragraphs of the matching packages.
+    data: Vec<u8>,
+}
+
+struct GenericCopy {
+    source_path: String,
+    dest_path: String,
+    expected_content: Option<(u64, ContentDigest)>,
+    /// Expected digest of content whose size isn't known.
+    expected_digest: Option<ContentDigest>,
+}
+
+/// Concurrency limits for a phase of a copy operation.
+///
+/// Within a phase, copies are started largest first.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CopyConcurrency {
+    /// Maximum number of concurrent copy operations.
+    ///
+    /// Defaults to the limit passed to the copy methods of [RepositoryCopier].
+    pub max_operations: Option<usize>,
+
+    /// Maximum number of bytes being copied concurrently.
+    ///
+    /// Only copies with a known size count towards this limit. A copy larger than the limit
+    /// is performed by itself.
+    pub max_in_flight_bytes: Option<u64>,
+}
+
+/// How to handle failed copies in a phase of a copy operation.
+///
+/// The default policy aborts the copy operation upon the first failure.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CopyErrorPolicy {
+    /// Number of times to retry a failed copy.
+    pub retries: usize,
+
+    /// Delay in milliseconds before the first retry.
+    ///
+    /// The delay doubles with each further retry, up to [MAX_RETRY_DELAY_MS]. A random
+    /// jitter of up to half the delay is subtracted so concurrent retries spread out.
+    pub retry_delay_ms: u64,
+
+    /// Whether to skip copies still failing after all retries instead of aborting.
+    ///
+    /// Skipped copies are recorded and available via [RepositoryCopier::skipped_copies()].
+    pub skip: bool,
+}
+
+/// Upper bound of the delay between retries of a failed copy, in milliseconds.
+pub const MAX_RETRY_DELAY_MS: u64 = 30_000;
+
+impl Default for CopyErrorPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            retry_delay_ms: 500,
+            skip: false,
+        }
+    }
+}
+
+impl CopyErrorPolicy {
+    /// The delay before the given retry, starting at 1.
+    pub fn retry_delay(&self, attempt: usize) -> std::time::Duration {
+        let delay = self
+            .retry_delay_ms
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(MAX_RETRY_DELAY_MS);
+        let jitter = rand::thread_rng().gen_range(0..=delay / 2);
+
+        std::time::Duration::from_millis(delay - jitter)
+    }
+}
+
+/// A copy skipped due to a [CopyErrorPolicy].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SkippedCopy {
+    /// The phase the copy was part of.
+    pub phase: CopyPhase,
+
+    /// The destination path that wasn't copied.
+    pub path: String,
+
+    /// Description of the error that caused the copy to fail.
+    pub error: String,
+}
+
+/// Outcome of a phase of a copy operation.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PhaseCopyReport {
+    /// The phase.
+    pub phase: CopyPhase,
+
+    /// Number of paths written to the destination.
+    pub copied_paths: usize,
+
+    /// Number of bytes written to the destination.
+    pub copied_bytes: u64,
+
+    /// Number of paths already present at the destination.
+    pub noop_paths: usize,
+
+    /// Number of bytes of paths already present at the destination.
+    pub noop_bytes: u64,
+
+    /// Number of paths skipped because a [CopyState] recorded them as completed.
+    pub previously_completed_paths: usize,
+
+    /// Paths missing from the source that are allowed to be missing.
+    pub not_found_paths: Vec<String>,
+
+    /// Copies that failed and were skipped due to a [CopyErrorPolicy].
+    pub failed: Vec<SkippedCopy>,
+
+    /// Wall time spent in the phase, in seconds.
+    pub elapsed_seconds: f64,
+}
+
+impl PhaseCopyReport {
+    fn new(phase: CopyPhase) -> Self {
+        Self {
+            phase,
+            copied_paths: 0,
+            copied_bytes: 0,
+            noop_paths: 0,
+            noop_bytes: 0,
+            previously_completed_paths: 0,
+            not_found_paths: vec![],
+            failed: vec![],
+            elapsed_seconds: 0.0,
+        }
+    }
+}
+
+/// Outcome of copying a distribution.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DistributionCopyReport {
+    /// Path of the distribution relative to the repository root.
+    pub distribution_path: String,
+
+    /// Hex key ID of the trusted key that verified the source release file.
+    ///
+    /// Only defined if a source keyring is configured.
+    pub verified_key_id: Option<String>,
+
+    /// Whether the distribution was unchanged in sync mode and nothing was copied.
+    pub unchanged: bool,
+
+    /// Outcomes of each phase, in the order they were performed.
+    pub phases: Vec<PhaseCopyReport>,
+
+    /// Wall time spent copying the distribution, in seconds.
+    pub elapsed_seconds: f64,
+}
+
+impl DistributionCopyReport {
+    /// Total number of bytes written to the destination.
+    pub fn copied_bytes(&self) -> u64 {
+        self.phases.iter().map(|p| p.copied_bytes).sum()
+    }
+
+    /// Total number of failed copies.
+    pub fn failed_paths(&self) -> usize {
+        self.phases.iter().map(|p| p.failed.len()).sum()
+    }
+}
+
+/// Outcome of a copy operation defined by a [RepositoryCopierConfig].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CopyReport {
+    /// Outcomes of each copied distribution.
+    pub distributions: Vec<DistributionCopyReport>,
+
+    /// Destination paths deleted because they were no longer referenced.
+    pub deleted_paths: Vec<String>,
+
+    /// Wall time spent on the entire operation, in seconds.
+    pub elapsed_seconds: f64,
+
+    /// The error that aborted the operation, if any.
+    pub error: Option<String>,
+}
+
+/// A declarative description of which parts of a repository to mirror.
+///
+/// Fields that aren't defined leave the corresponding [RepositoryCopier] setting
+/// untouched. Install via [RepositoryCopier::apply_mirror_config()].
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MirrorConfig {
+    /// Components to mirror.
+    pub components: Option<Vec<String>>,
+
+    /// Architectures of binary packages, installer binary packages, and installer image
+    /// trees to mirror.
+    pub architectures: Option<Vec<String>>,
+
+    /// Whether to mirror installer binary packages and installer image trees.
+    pub installers: Option<bool>,
+
+    /// Whether to mirror source packages.
+    pub sources: Option<bool>,
+
+    /// Maximum total size in bytes of pool and indices files to fetch per distribution.
+    ///
+    /// If the resolved copy exceeds this budget, the copy fails before anything is
+    /// written.
+    pub max_total_size: Option<u64>,
+}
+
+/// Summary of a phase of a [CopyPlan].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CopyPlanPhase {
+    /// The phase.
+    pub phase: CopyPhase,
+
+    /// Number of paths the phase would copy.
+    pub paths: usize,
+
+    /// Total size in bytes of the paths the phase would copy.
+    pub size: u64,
+}
+
+/// What copying a distribution would fetch.
+///
+/// Obtained via [RepositoryCopier::plan_distribution_path()].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CopyPlan {
+    /// Phases in the order they would be performed.
+    pub phases: Vec<CopyPlanPhase>,
+}
+
+impl CopyPlan {
+    fn from_copies(phases: &[(CopyPhase, Vec<GenericCopy>)]) -> Self {
+        Self {
+            phases: phases
+                .iter()
+                .map(|(phase, copies)| CopyPlanPhase {
+                    phase: *phase,
+                    paths: copies.len(),
+                    size: copies
+                        .iter()
+                        .filter_map(|op| op.expected_content.as_ref())
+                        .map(|(size, _)| size)
+                        .sum(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Total number of paths that would be copied.
+    pub fn total_paths(&self) -> usize {
+        self.phases.iter().map(|p| p.paths).sum()
+    }
+
+    /// Total size in bytes of paths that would be copied.
+    pub fn total_size(&self) -> u64 {
+        self.phases.iter().map(|p| p.size).sum()
+    }
+}
+
+/// A record in a [CopyState] file.
+#[derive(Deserialize, Serialize)]
+struct CopyStateRecord {
+    path: String,
+    digest: String,
+}
+
+/// Persisted record of completed copy operations, enabling interrupted copies to resume.
+///
+/// The state is stored in a local file containing 1 JSON object per line. Each line
+/// records a destination path and the hex digest of its content. A line is appended as
+/// soon as the destination path is verified or written, so the file remains usable if
+/// the process is interrupted.
+///
+/// Only content addressed copies (pool files and indices files) are recorded. Release
+/// files are always copied.
+pub struct CopyState {
+    completed: HashMap<String, String>,
+    file: std::fs::File,
+}
+
+impl CopyState {
+    /// Open a state file, creating it if it doesn't exist.
+    ///
+    /// Records from previous runs are loaded. A truncated final line, as can be left by an
+    /// interrupted write, is ignored.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let io_error = |e| DebianError::RepositoryIoPath(format!("{}", path.display()), e);
+
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let mut completed = HashMap::new();
+        let mut lines = data.lines().peekable();
+        while let Some(line) = lines.next() {
+            match serde_json::from_str::<CopyStateRecord>(line) {
+                Ok(record) => {
+                    completed.insert(record.path, record.digest);
+                }
+                Err(_) if lines.peek().is_none() => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(io_error)?;
+
+        // Ensure records we append start on a new line.
+        if !data.is_empty() && !data.ends_with('\n') {
+            file.write_all(b"\n").map_err(io_error)?;
+        }
+
+        Ok(Self { completed, file })
+    }
+
+    /// Whether a destination path with the given content was previously copied.
+    pub fn is_completed(&self, path: &str, digest: &ContentDigest) -> bool {
+        self.completed.get(path) == Some(&digest.digest_hex())
+    }
+
+    /// Record that a destination path with the given content was copied.
+    pub fn record(&mut self, path: &str, digest: &ContentDigest) -> Result<()> {
+        let mut line = serde_json::to_string(&CopyStateRecord {
+            path: path.to_string(),
+            digest: digest.digest_hex(),
+        })?;
+        line.push('\n');
+
+        self.file.write_all(line.as_bytes())?;
+        self.completed.insert(path.to_string(), digest.digest_hex());
+
+        Ok(())
+    }
+}
+
+/// Entity for copying Debian repository content.
+///
+/// Instances of this type can be used to copy select Debian repository content
+/// between a reader and a writer.
+///
+/// The file layout and content is preserved, so existing PGP signatures can be preserved.
+/// However, the copier does have the ability to selectively filter which files are copied.
+/// So the destination repository may reference content that doesn't exist in that location.
+///
+/// Because repositories do not have a standardized mechanism for discovering dists/releases
+/// within, this type must be told which distributions to copy. Copying is performed 1
+/// distribution at a time.
+///
+/// By default, instances copy all copyable content, including installer image trees
+/// described by `SHA256SUMS` manifests. Incomplete copies are considered a bug and should
+/// be reported.
+///
+/// Various `set_*` methods exist to control the copying behavior.
+pub struct RepositoryCopier {
+    /// Filter of components that should be copied.
+    only_components: Option<Vec<String>>,
+
+    /// Whether to copy non-installer binary packages.
+    binary_packages_copy: bool,
+    /// Filter of architectures of binary packages to copy.
+    binary_packages_only_arches: Option<Vec<String>>,
+    /// Filter of individual binary packages to copy.
+    binary_package_filter: BinaryPackageFilter,
+
+    /// Whether to copy installer binary packages.
+    installer_binary_packages_copy: bool,
+    /// Filter of architectures of installer binary packages to copy.
+    installer_binary_packages_only_arches: Option<Vec<String>>,
+
+    /// Whether to copy source packages.
+    sources_copy: bool,
+
+    /// Whether to copy installers files.
+    installers_copy: bool,
+    /// Filter of architectures of installers to copy.
+    installers_only_arches: Option<Vec<String>>,
+
+    /// Audit log path and content to record copied paths in.
+    audit_log: Mutex<Option<(String, AuditLog)>>,
+    /// Key and password function used to sign the audit log.
+    audit_log_signing_key: Option<(SignedSecretKey, Box<KeyPasswordFn>)>,
+
+    /// Reader of the destination repository, used for incremental syncing.
+    sync_reader: Option<Box<dyn RepositoryRootReader>>,
+
+    /// Keys trusted to sign the source distribution's release file.
+    source_keyring: Option<Keyring>,
+    source_trust_policy: TrustPolicy,
+
+    /// Keys and password functions used to sign the copied release files.
+    resign_keys: Vec<(SignedSecretKey, Box<KeyPasswordFn>)>,
+
+    /// Record of completed copies to skip and append to.
+    state: Option<Mutex<CopyState>>,
+
+    /// Concurrency limits overriding the default for individual phases.
+    phase_concurrency: HashMap<CopyPhase, CopyConcurrency>,
+
+    /// Maximum total size in bytes of files to copy per distribution.
+    max_total_size: Option<u64>,
+
+    /// Rewrites applied to release files.
+    release_rewriter: Option<ReleaseRewriter>,
+
+    /// Error policies overriding the default of aborting for individual phases.
+    error_policy: HashMap<CopyPhase, CopyErrorPolicy>,
+
+    /// Copies skipped due to error policies.
+    skipped: Mutex<Vec<SkippedCopy>>,
+
+    /// Paths of regenerated indices files written to the destination.
+    regenerated_indices: Mutex<HashSet<String>>,
+}
+
+impl Default for RepositoryCopier {
+    fn default() -> Self {
+        Self {
+            only_components: None,
+            binary_packages_copy: true,
+            binary_packages_only_arches: None,
+            binary_package_filter: BinaryPackageFilter::default(),
+            installer_binary_packages_copy: true,
+            installer_binary_packages_only_arches: None,
+            sources_copy: true,
+            installers_copy: false,
+            installers_only_arches: None,
+            audit_log: Mutex::new(None),
+            audit_log_signing_key: None,
+            sync_reader: None,
+            source_keyring: None,
+            source_trust_policy: TrustPolicy::default(),
+            resign_keys: vec![],
+            state: None,
+            phase_concurrency: HashMap::new(),
+            max_total_size: None,
+            release_rewriter: None,
+            error_policy: HashMap::new(),
+            skipped: Mutex::new(vec![]),
+            regenerated_indices: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+impl RepositoryCopier {
+    /// Set an explicit list of components whose files to copy.
+    pub fn set_only_components(&mut self, components: impl Iterator<Item = String>) {
+        self.only_components = Some(components.collect());
+    }
+
+    /// Set whether to copy non-installer binary packages.
+    pub fn set_binary_packages_copy(&mut self, value: bool) {
+        self.binary_packages_copy = value;
+    }
+
+    /// Set a filter for architectures of non-installer binary packages to copy.
+    ///
+    /// Binary packages for architectures not in this set will be ignored.
+    pub fn set_binary_packages_only_arches(&mut self, value: impl Iterator<Item = String>) {
+        self.binary_packages_only_arches = Some(value.collect::<Vec<_>>());
+    }
+
+    /// Set a filter of which non-installer binary packages to copy.
+    ///
+    /// The filter is evaluated against each package's entry in the `Packages` indices.
+    /// `Packages` files of copied components and architectures are regenerated in every
+    /// compression format to only list matching packages, and the release file is updated
+    /// to reference them. This invalidates upstream signatures, so a key set via
+    /// [Self::set_resign_key()] is required.
+    pub fn set_binary_package_filter(&mut self, filter: BinaryPackageFilter) {
+        self.binary_package_filter = filter;
+    }
+
+    /// Set whether to copy installer binary packages.
+    pub fn set_installer_binary_packages_copy(&mut self, value: bool) {
+        self.installer_binary_packages_copy = value;
+    }
+
+    /// Set a filter for architectures of installer binary packages to copy.
+    ///
+    /// Binary packages for architectures not in this set will be ignored.
+    pub fn set_installer_binary_packages_only_arches(
+        &mut self,
+        value: impl Iterator<Item = String>,
+    ) {
+        self.installer_binary_packages_only_arches = Some(value.collect::<Vec<_>>());
+    }
+
+    /// Set whether to copy sources package files.
+    pub fn set_sources_copy(&mut self, value: bool) {
+        self.sources_copy = value;
+    }
+
+    /// Set whether to copy installer image trees.
+    ///
+    /// These are the `<component>/installer-<arch>/` trees in distribution directories
+    /// holding netboot images, kernels, and initrds. Files are verified against the trees'
+    /// `SHA256SUMS` manifests. Not enabled by default.
+    pub fn set_installers_copy(&mut self, value: bool) {
+        self.installers_copy = value;
+    }
+
+    /// Set a filter for architectures of installer image trees to copy.
+    pub fn set_installers_only_arches(&mut self, value: impl Iterator<Item = String>) {
+        self.installers_only_arches = Some(value.collect::<Vec<_>>());
+    }
+
+    /// Record paths written by copy operations in an [AuditLog].
+    ///
+    /// `previous` is the existing log for the destination repository, typically obtained
+    /// via [AuditLog::load()]. Each distribution copy appends an entry to it and writes
+    /// the log to `path`, relative to the destination root, after the release files are
+    /// copied.
+    pub fn set_audit_log(&mut self, path: impl ToString, previous: AuditLog) {
+        self.audit_log = Mutex::new(Some((path.to_string(), previous)));
+    }
+
+    /// Sign the audit log set via [Self::set_audit_log()] with a key.
+    ///
+    /// A PGP cleartext signed copy of the log is written to `<path>.asc` alongside it.
+    pub fn set_audit_log_signing_key(
+        &mut self,
+        key: SignedSecretKey,
+        key_pw: impl Fn() -> String + Send + Sync + 'static,
+    ) {
+        self.audit_log_signing_key = Some((key, Box::new(key_pw)));
+    }
+
+    /// Enable incremental sync mode, using a reader of the destination repository.
+    ///
+    /// Before a distribution is copied, its `[In]Release` file is read from the destination.
+    /// If it is identical to the source's, nothing is copied. Otherwise only indices files
+    /// whose digests differ from the destination's are copied and only pool files referenced
+    /// by those changed indices are considered for copying.
+    ///
+    /// This assumes the destination content was produced by a prior copy with the same
+    /// filtering settings. Files removed from the destination out-of-band won't be
+    /// restored until the indices referencing them change.
+    pub fn set_sync_reader(&mut self, reader: Box<dyn RepositoryRootReader>) {
+        self.sync_reader = Some(reader);
+    }
+
+    /// Require the source distribution's release file to be signed by a trusted key.
+    ///
+    /// When set, the `InRelease` file of each source distribution must carry a valid
+    /// signature from one of these keys (or their subkeys) and the copy fails before
+    /// any content is written otherwise. A [PublishEvent::ReleaseSignatureVerified] is
+    /// emitted upon successful verification.
+    pub fn set_source_keyring(&mut self, keys: impl IntoIterator<Item = SignedPublicKey>) {
+        self.source_keyring = Some(keys.into_iter().collect());
+    }
+
+    /// Set the policy for verifying signatures against the source keyring.
+    ///
+    /// By default, any cryptographically valid signature is accepted. Signatures rejected
+    /// by the policy fail the copy with [DebianError::PgpSignatureRejected].
+    pub fn set_source_verification_policy(&mut self, policy: VerificationPolicy) {
+        self.source_trust_policy.verification = policy;
+    }
+
+    /// Set the policy for trusting signatures against the source keyring.
+    ///
+    /// This replaces the policy set by [Self::set_source_verification_policy()]. Release
+    /// files not satisfying the policy fail the copy with the error of
+    /// [TrustPolicy::evaluate()].
+    pub fn set_source_trust_policy(&mut self, policy: TrustPolicy) {
+        self.source_trust_policy = policy;
+    }
+
+    /// Sign the copied release files with a different key.
+    ///
+    /// When set, the source's release files aren't copied. Instead, `Release`, `InRelease`
+    /// and `Release.gpg` are written from the release file read from the source, which is
+    /// the content verified against [Self::set_source_keyring()] if set, and signed with
+    /// only this key. The release fields are unaltered, so all digests continue to match
+    /// the copied indices.
+    pub fn set_resign_key(
+        &mut self,
+        key: SignedSecretKey,
+        key_pw: impl Fn() -> String + Send + Sync + 'static,
+    ) {
+        self.resign_keys = vec![(key, Box::new(key_pw))];
+    }
+
+    /// Add a key to sign the copied release files with.
+    ///
+    /// This is like [Self::set_resign_key()] except keys set previously are kept and the
+    /// release files carry a signature from each key. This is useful during key rotations.
+    pub fn add_resign_key(
+        &mut self,
+        key: SignedSecretKey,
+        key_pw: impl Fn() -> String + Send + Sync + 'static,
+    ) {
+        self.resign_keys.push((key, Box::new(key_pw)));
+    }
+
+    /// Persist completed copies in a [CopyState] so interrupted copies can be resumed.
+    ///
+    /// Copies recorded in the state as completed are skipped without verifying the
+    /// destination. This assumes the destination isn't modified out-of-band. Delete the
+    /// state file to force re-verification of all destination content.
+    pub fn set_state(&mut self, state: CopyState) {
+        self.state = Some(Mutex::new(state));
+    }
+
+    /// Set concurrency limits for a phase of copy operations.
+    ///
+    /// This overrides the `max_copy_operations` passed to copy methods for that phase.
+    /// e.g. many small indices files benefit from high concurrency while a few large pool
+    /// files may saturate bandwidth with low concurrency.
+    pub fn set_phase_concurrency(&mut self, phase: CopyPhase, concurrency: CopyConcurrency) {
+        self.phase_concurrency.insert(phase, concurrency);
+    }
+
+    /// Set the maximum total size in bytes of files to copy per distribution.
+    ///
+    /// Pool and indices files to copy are resolved before anything is written. If their
+    /// combined size exceeds this budget, the copy fails with
+    /// [DebianError::RepositoryCopySizeBudgetExceeded].
+    pub fn set_max_total_size(&mut self, size: u64) {
+        self.max_total_size = Some(size);
+    }
+
+    /// Rewrite the `Release` file of copied distributions.
+    ///
+    /// The rewritten `Release` file is signed with the keys set via [Self::set_resign_key()],
+    /// which must be set or copies fail with
+    /// [DebianError::RepositoryCopyRewriteWithoutSigningKey]. Indices files dropped by the
+    /// rewriter aren't copied.
+    pub fn set_release_rewriter(&mut self, rewriter: ReleaseRewriter) {
+        self.release_rewriter = Some(rewriter);
+    }
+
+    /// Apply the settings of a [MirrorConfig].
+    pub fn apply_mirror_config(&mut self, config: &MirrorConfig) {
+        if let Some(v) = &config.components {
+            self.set_only_components(v.iter().cloned());
+        }
+        if let Some(v) = &config.architectures {
+            self.set_binary_packages_only_arches(v.iter().cloned());
+            self.set_installer_binary_packages_only_arches(v.iter().cloned());
+            self.set_installers_only_arches(v.iter().cloned());
+        }
+        if let Some(v) = config.installers {
+            self.set_installer_binary_packages_copy(v);
+            self.set_installers_copy(v);
+        }
+        if let Some(v) = config.sources {
+            self.set_sources_copy(v);
+        }
+        if let Some(v) = config.max_total_size {
+            self.set_max_total_size(v);
+        }
+    }
+
+    /// Set how failed copies in a phase are handled.
+    ///
+    /// By default, the first failed copy aborts the copy operation.
+    pub fn set_error_policy(&mut self, phase: CopyPhase, policy: CopyErrorPolicy) {
+        self.error_policy.insert(phase, policy);
+    }
+
+    /// Copies skipped so far due to error policies.
+    pub fn skipped_copies(&self) -> Vec<SkippedCopy> {
+        self.skipped
+            .lock()
+            .map(|skipped| skipped.clone())
+            .unwrap_or_default()
+    }
+
+    /// Resolve the operation and byte concurrency limits of a phase.
+    fn phase_concurrency(
+        &self,
+        phase: CopyPhase,
+        max_copy_operations: usize,
+    ) -> (usize, Option<u64>) {
+        let concurrency = self
+            .phase_concurrency
+            .get(&phase)
+            .copied()
+            .unwrap_or_default();
+
+        (
+            concurrency.max_operations.unwrap_or(max_copy_operations),
+            concurrency.max_in_flight_bytes,
+        )
+    }
+
+    /// Perform a copy operation as defined by a [RepositoryCopierConfig].
+    pub async fn copy_from_config(
+        config: RepositoryCopierConfig,
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    ) -> Result<CopyReport> {
+        let start = std::time::Instant::now();
+
+        let root_reader = reader_from_str(config.source_url)?;
+        let writer = writer_from_str(&config.destination_url).await?;
+
+        let mut copier = Self::default();
+
+        if let Some(path) = config.audit_log_path.clone() {
+            // Starting a new log would discard the entries of the existing one.
+            let previous = match reader_from_str(&config.destination_url) {
+                Ok(reader) => AuditLog::load(reader.as_ref(), &path).await?,
+                #[cfg(feature = "s3")]
+                Err(DebianError::RepositoryReaderUnrecognizedUrl(url))
+                    if url.starts_with("s3://") =>
+                {
+                    let reader = super::s3::S3Writer::from_url(&url::Url::parse(&url)?).await?;
+                    AuditLog::load(&reader, &path).await?
+                }
+                Err(DebianError::RepositoryReaderUnrecognizedUrl(url))
+                    if url.starts_with("null://") =>
+                {
+                    AuditLog::default()
+                }
+                Err(e) => return Err(e),
+            };
+
+            copier.set_audit_log(path, previous);
+        }
+
+        if let Some(path) = &config.audit_log_signing_key_path {
+            let data =
+                std::fs::read(path).map_err(|e| DebianError::RepositoryIoPath(path.clone(), e))?;
+            copier.set_audit_log_signing_key(parse_armored_secret_key(&data)?, String::new);
+        }
+
+        if config.source_keyring_path.is_some() || !config.source_key_fingerprints.is_empty() {
+            let mut keyring = Keyring::default();
+
+            if let Some(path) = config.source_keyring_path {
+                keyring.load_file(path)?;
+            }
+
+            #[cfg(feature = "http")]
+            if !config.source_key_fingerprints.is_empty() {
+                let mut fetcher = crate::key_fetch::KeyFetcher::new()?;
+                if !config.source_keyservers.is_empty() {
+                    fetcher.set_keyservers(config.source_keyservers.iter());
+                }
+                if let Some(path) = &config.source_key_cache_path {
+                    fetcher.set_cache_dir(path);
+                }
+
+                keyring.extend(
+                    fetcher
+                        .fetch_keyring(&config.source_key_fingerprints)
+                        .await?,
+                );
+            }
+
+            #[cfg(not(feature = "http"))]
+            if let Some(fingerprint) = config.source_key_fingerprints.first() {
+                return Err(DebianError::PgpKeyNotFound(fingerprint.clone()));
+            }
+
+            copier.set_source_keyring(keyring);
+        }
+
+        if let Some(path) = config.state_path {
+            copier.set_state(CopyState::open(path)?);
+        }
+
+        for path in config
+            .resign_key_path
+            .iter()
+            .chain(&config.resign_additional_key_paths)
+        {
+            let data =
+                std::fs::read(path).map_err(|e| DebianError::RepositoryIoPath(path.clone(), e))?;
+            copier.add_resign_key(parse_armored_secret_key(&data)?, String::new);
+        }
+
+        if !config.release_set_fields.is_empty()
+            || !config.release_remove_fields.is_empty()
+            || !config.release_drop_indices.is_empty()
+        {
+            let mut rewriter = ReleaseRewriter::default();
+            for (name, value) in config.release_set_fields {
+                rewriter.set_field(name, value);
+            }
+            for name in config.release_remove_fields {
+                rewriter.remove_field(name);
+            }
+            rewriter.set_drop_indices(config.release_drop_indices.iter())?;
+
+            copier.set_release_rewriter(rewriter);
+        }
+
+        if config.sync == Some(true) {
+            copier.set_sync_reader(reader_from_str(&config.destination_url)?);
+        }
+
+        for (phase, concurrency) in config.phase_concurrency {
+            copier.set_phase_concurrency(phase, concurrency);
+        }
+
+        for (phase, policy) in config.error_policy {
+            copier.set_error_policy(phase, policy);
+        }
+
+        if let Some(mirror) = &config.mirror {
+            copier.apply_mirror_config(mirror);
+        }
+
+        if let Some(v) = config.only_components {
+            copier.set_only_components(v.into_iter());
+        }
+        if let Some(v) = config.binary_packages_copy {
+            copier.set_binary_packages_copy(v);
+        }
+        if let Some(v) = config.binary_packages_only_architectures {
+            copier.set_binary_packages_only_arches(v.into_iter());
+        }
+
+        let mut filter = BinaryPackageFilter::default();
+        if let Some(v) = config.binary_packages_only_names {
+            filter.set_only_names(v.iter())?;
+        }
+        if let Some(v) = config.binary_packages_only_sections {
+            filter.set_only_sections(v.iter());
+        }
+        if let Some(v) = config.binary_packages_only_priorities {
+            filter.set_only_priorities(v.iter());
+        }
+        if let Some(v) = config.binary_packages_max_size {
+            filter.set_max_size(v);
+        }
+        copier.set_binary_package_filter(filter);
+
+        if let Some(v) = config.installer_binary_packages_copy {
+            copier.set_installer_binary_packages_copy(v);
+        }
+        if let Some(v) = config.installer_binary_packages_only_architectures {
+            copier.set_installer_binary_packages_only_arches(v.into_iter());
+        }
+        if let Some(v) = config.sources_copy {
+            copier.set_sources_copy(v);
+        }
+        if let Some(v) = config.installers_copy {
+            copier.set_installers_copy(v);
+        }
+        if let Some(v) = config.installers_only_architectures {
+            copier.set_installers_only_arches(v.into_iter());
+        }
+
+        let distribution_paths = config
+            .distributions
+            .iter()
+            .map(|dist| format!("dists/{}", dist))
+            .chain(config.distribution_paths)
+            .collect::<Vec<_>>();
+
+        let mut report = CopyReport::default();
+
+        let res = async {
+            for path in &distribution_paths {
+                report.distributions.push(
+                    copier
+                        .copy_distribution_path(
+                            root_reader.as_ref(),
+                            writer.as_ref(),
+                            path,
+                            max_copy_operations,
+                            progress_cb,
+                        )
+                        .await?,
+                );
+            }
+
+            if config.delete == Some(true) {
+                let mut keep = vec![];
+                if let Some(path) = &config.audit_log_path {
+                    keep.push(path.clone());
+                    keep.push(format!("{}.asc", path));
+                }
+
+                report.deleted_paths = copier
+                    .delete_unreferenced(
+                        root_reader.as_ref(),
+                        writer.as_ref(),
+                        &distribution_paths,
+                        &keep,
+                        max_copy_operations,
+                        progress_cb,
+                    )
+                    .await?;
+            }
+
+            Ok::<(), DebianError>(())
+        }
+        .await;
+        report.elapsed_seconds = start.elapsed().as_secs_f64();
+        report.error = res.as_ref().err().map(|e| e.to_string());
+
+        // The reports are written even if the copy aborted so operators can follow up on
+        // everything done and skipped so far. Failing to write one doesn't prevent writing
+        // the other and never masks the error that aborted the copy.
+        let write_report = |path: String, data: serde_json::Result<String>| -> Result<()> {
+            std::fs::write(&path, data?).map_err(|e| DebianError::RepositoryIoPath(path, e))
+        };
+
+        let report_res = if let Some(path) = config.report_path {
+            write_report(path, serde_json::to_string_pretty(&report))
+        } else {
+            Ok(())
+        };
+
+        let skipped_res = if let Some(path) = config.skipped_report_path {
+            write_report(path, serde_json::to_string_pretty(&copier.skipped_copies()))
+        } else {
+            Ok(())
+        };
+
+        res?;
+        report_res?;
+        skipped_res?;
+
+        Ok(report)
+    }
+
+    /// Delete destination files not referenced by a set of distributions.
+    ///
+    /// This is used to make a destination mirror its source, removing files the source
+    /// no longer has. The release files, indices files (including `by-hash` variants),
+    /// installer image files, and pool files referenced by each distribution in
+    /// `root_reader` are retained, as are indices files this instance regenerated. All
+    /// other files under the distribution directories and under the pool directories are
+    /// deleted from `writer`, which must support [RepositoryWriter::list_paths()] and
+    /// [RepositoryWriter::delete_path()]. Paths in `keep` are never deleted.
+    ///
+    /// Pool directories are shared by distributions. So `distribution_paths` must contain
+    /// every distribution in the destination or files only referenced by the omitted
+    /// distributions will be deleted.
+    ///
+    /// This should be called after the distributions are copied so clients never see
+    /// release files referencing deleted content. Returns the deleted paths.
+    pub async fn delete_unreferenced(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        writer: &dyn RepositoryWriter,
+        distribution_paths: &[String],
+        keep: &[String],
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    ) -> Result<Vec<String>> {
+        let mut referenced = keep.iter().cloned().collect::<HashSet<_>>();
+        referenced.extend(
+            self.regenerated_indices
+                .lock()
+                .map(|paths| paths.clone())
+                .unwrap_or_default(),
+        );
+        let mut prefixes = BTreeSet::from(["pool".to_string()]);
+
+        for distribution_path in distribution_paths {
+            let distribution_path = distribution_path.trim_matches('/');
+            let release = root_reader
+                .release_reader_with_distribution_path(distribution_path)
+                .await?;
+
+            prefixes.insert(distribution_path.to_string());
+
+            referenced.extend(
+                RELEASE_FILES
+                    .iter()
+                    .map(|path| format!("{}/{}", distribution_path, path)),
+            );
+
+            // Retain `by-hash` paths for every checksum flavor, not just the one we copy.
+            for checksum in ChecksumType::preferred_order() {
+                if let Some(entries) = release.release_file().iter_classified_index_files(checksum)
+                {
+                    for entry in entries {
+                        let entry = entry?;
+                        referenced.insert(format!("{}/{}", distribution_path, entry.path));
+                        referenced.insert(format!(
+                            "{}/{}",
+                            distribution_path,
+                            entry.by_hash_path()
+                        ));
+                    }
+                }
+            }
+
+            for (_, files) in installer_manifests(release.as_ref()).await? {
+                referenced.extend(files.into_iter().map(|(path, _)| path));
+            }
+
+            let pool_paths = release
+                .resolve_package_fetches(
+                    Box::new(|_| true),
+                    Box::new(|_| true),
+                    max_copy_operations,
+                )
+                .await?
+                .into_iter()
+                .map(|fetch| fetch.path)
+                .chain(
+                    release
+                        .resolve_source_fetches(
+                            Box::new(|_| true),
+                            Box::new(|_| true),
+                            max_copy_operations,
+                        )
+                        .await?
+                        .into_iter()
+                        .map(|fetch| fetch.path.clone()),
+                );
+
+            for path in pool_paths {
+                if let Some((prefix, _)) = path.split_once('/') {
+                    prefixes.insert(prefix.to_string());
+                }
+                referenced.insert(path);
+            }
+        }
+
+        let mut deleted = vec![];
+        for prefix in prefixes {
+            for path in writer.list_paths(&prefix).await? {
+                if referenced.contains(&path) {
+                    continue;
+                }
+
+                writer.delete_path(&path).await?;
+
+                if let Some(cb) = progress_cb {
+                    cb(PublishEvent::PathDeleted(path.clone()));
+                }
+                deleted.push(path);
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    /// Copy content for a given distribution given a distribution name.
+    ///
+    /// This is a proxy for [Self::copy_distribution_path()] which simply passes
+    /// `dists/{distribution}` as the path value. This is the standard layout for Debian
+    /// repositories.
+    pub async fn copy_distribution(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        writer: &dyn RepositoryWriter,
+        distribution: &str,
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    ) -> Result<DistributionCopyReport> {
+        self.copy_distribution_path(
+            root_reader,
+            writer,
+            &format!("dists/{}", distribution),
+            max_copy_operations,
+            progress_cb,
+        )
+        .await
+    }
+
+    /// Copy content for a given distribution at a path relative to the repository root.
+    ///
+    /// The given `distribution_path` is usually prefixed with `dists/`. e.g. `dists/bullseye`.
+    /// But it can be something else for non-standard repository layouts.
+    ///
+    /// Returns a report of the work performed.
+    pub async fn copy_distribution_path(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        writer: &dyn RepositoryWriter,
+        distribution_path: &str,
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    ) -> Result<DistributionCopyReport> {
+        if self.audit_log_lock()?.is_none() {
+            return self
+                .copy_distribution_path_unaudited(
+                    root_reader,
+                    writer,
+                    distribution_path,
+                    max_copy_operations,
+                    progress_cb,
+                )
+                .await;
+        }
+
+        let auditing = AuditingWriter::new(writer, "copy");
+
+        let report = self
+            .copy_distribution_path_unaudited(
+                root_reader,
+                &auditing,
+                distribution_path,
+                max_copy_operations,
+                progress_cb,
+            )
+            .await?;
+
+        let entry = auditing.finish();
+
+        let (path, log) = {
+            let mut guard = self.audit_log_lock()?;
+            let (path, log) = guard.as_mut().expect("audit log presence checked above");
+            log.push_entry(entry);
+
+            (path.clone(), log.clone())
+        };
+
+        match &self.audit_log_signing_key {
+            Some((key, key_pw)) => log.publish(writer, &path, Some((key, key_pw))).await?,
+            None => log.publish(writer, &path, NO_SIGNING_KEY).await?,
+        }
+
+        Ok(report)
+    }
+
+    fn audit_log_lock(&self) -> Result<std::sync::MutexGuard<'_, Option<(String, AuditLog)>>> {
+        self.audit_log
+            .lock()
+            .map_err(|_| DebianError::Other("audit log mutex poisoned".to_string()))
+    }
+
+    async fn copy_distribution_path_unaudited(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        writer: &dyn RepositoryWriter,
+        distribution_path: &str,
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    ) -> Result<DistributionCopyReport> {
+        if self.release_rewriter.is_some() && self.resign_keys.is_empty() {
+            return Err(DebianError::RepositoryCopyRewriteWithoutSigningKey);
+        }
+        if self.binary_packages_copy
+            && !self.binary_package_filter.is_empty()
+            && self.resign_keys.is_empty()
+        {
+            return Err(DebianError::RepositoryCopyFilterWithoutSigningKey);
+        }
+
+        let start = std::time::Instant::now();
+        let mut report = DistributionCopyReport {
+            distribution_path: distribution_path.to_string(),
+            verified_key_id: None,
+            unchanged: false,
+            phases: vec![],
+            elapsed_seconds: 0.0,
+        };
+
+        let release = root_reader
+            .release_reader_with_distribution_path(distribution_path)
+            .await?;
+
+        if let Some(key_id) = self.verify_source_release(distribution_path, release.as_ref())? {
+            if let Some(cb) = progress_cb {
+                cb(PublishEvent::ReleaseSignatureVerified(
+                    distribution_path.to_string(),
+                    key_id.clone(),
+                ));
+            }
+            report.verified_key_id = Some(key_id);
+        }
+
+        let unchanged_indices = if let Some(unchanged) = self
+            .unchanged_indices(distribution_path, release.as_ref())
+            .await?
+        {
+            unchanged
+        } else {
+            if let Some(cb) = progress_cb {
+                cb(PublishEvent::DistributionUnchanged(
+                    distribution_path.to_string(),
+                ));
+            }
+
+            report.unchanged = true;
+            report.elapsed_seconds = start.elapsed().as_secs_f64();
+            return Ok(report);
+        };
+
+        // Resolve everything to copy up front so we can report on and enforce the size
+        // budget before anything is written.
+        let (mut phases, filtered_indices) = self
+            .resolve_copies(release.as_ref(), unchanged_indices, max_copy_operations)
+            .await?;
+
+        let plan = CopyPlan::from_copies(&phases);
+
+        if let Some(cb) = progress_cb {
+            for phase in &plan.phases {
+                cb(PublishEvent::CopyPlanned(
+                    phase.phase,
+                    phase.paths,
+                    phase.size,
+                ));
+            }
+        }
+
+        if let Some(budget) = self.max_total_size {
+            if plan.total_size() > budget {
+                return Err(DebianError::RepositoryCopySizeBudgetExceeded(
+                    plan.total_size(),
+                    budget,
+                ));
+            }
+        }
+
+        // We copy all the pool artifacts first because otherwise a client could fetch an indices
+        // file referring to a pool file that isn't available yet. Indices files are resolved
+        // last, so they're the final phase.
+        let indices = phases
+            .pop()
+            .expect("release indices phase is always present");
+
+        for (phase, copies) in phases {
+            report.phases.push(
+                self.perform_phase(
+                    root_reader,
+                    writer,
+                    phase,
+                    copies,
+                    false,
+                    max_copy_operations,
+                    progress_cb,
+                )
+                .await?,
+            );
+        }
+
+        // All the pool artifacts are in place. Publish the indices files.
+
+        // Some indices files don't actually exist! For example, the release file will publish
+        // the checksums of the uncompressed file variant but the uncompressed file won't
+        // actually be present in the repository! These errors are OK to ignore. But we still
+        // report on them.
+        let (phase, copies) = indices;
+        let mut phase_report = self
+            .perform_phase(
+                root_reader,
+                writer,
+                phase,
+                copies,
+                true,
+                max_copy_operations,
+                progress_cb,
+            )
+            .await?;
+
+        // `Packages` files regenerated due to a binary package filter replace the copied ones.
+        let filtered_entries = self
+            .write_filtered_indices(
+                writer,
+                release.as_ref(),
+                filtered_indices,
+                &mut phase_report,
+                progress_cb,
+            )
+            .await?;
+        report.phases.push(phase_report);
+
+        // And finally publish the Release files.
+        if let Some(cb) = progress_cb {
+            cb(PublishEvent::CopyPhaseBegin(CopyPhase::ReleaseFiles));
+        }
+        report.phases.push(
+            self.copy_release_files(
+                root_reader,
+                writer,
+                distribution_path,
+                release.as_ref(),
+                &filtered_entries,
+                max_copy_operations,
+                progress_cb,
+            )
+            .await?,
+        );
+        if let Some(cb) = progress_cb {
+            cb(PublishEvent::CopyPhaseEnd(CopyPhase::ReleaseFiles));
+        }
+
+        report.elapsed_seconds = start.elapsed().as_secs_f64();
+
+        Ok(report)
+    }
+
+    /// Compute what copying a distribution would fetch without copying anything.
+    ///
+    /// Sizes are upper bounds: files already present at the destination are counted
+    /// since determining their presence requires verifying the destination. Release files
+    /// aren't included. If the distribution is unchanged in sync mode, the plan is empty.
+    pub async fn plan_distribution_path(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        distribution_path: &str,
+        max_copy_operations: usize,
+    ) -> Result<CopyPlan> {
+        let release = root_reader
+            .release_reader_with_distribution_path(distribution_path)
+            .await?;
+
+        let unchanged_indices = if let Some(unchanged) = self
+            .unchanged_indices(distribution_path, release.as_ref())
+            .await?
+        {
+            unchanged
+        } else {
+            return Ok(CopyPlan::default());
+        };
+
+        let (phases, _) = self
+            .resolve_copies(release.as_ref(), unchanged_indices, max_copy_operations)
+            .await?;
+
+        Ok(CopyPlan::from_copies(&phases))
+    }
+
+    /// Resolve indices files identical at the destination when syncing.
+    ///
+    /// Returns `None` if the destination's release file is identical to `release`.
+    async fn unchanged_indices(
+        &self,
+        distribution_path: &str,
+        release: &dyn ReleaseReader,
+    ) -> Result<Option<Arc<HashSet<(String, String)>>>> {
+        let unchanged =
+            if let Some(previous) = self.previous_release_reader(distribution_path).await? {
+                let release_text = match &self.release_rewriter {
+                    Some(rewriter) => rewriter.rewrite(release.release_file()).to_string(),
+                    None => release.release_file().to_string(),
+                };
+
+                if previous.release_file().to_string() == release_text {
+                    return Ok(None);
+                }
+
+                previous
+                    .classified_indices_entries()?
+                    .into_iter()
+                    .map(|entry| (entry.path.to_string(), entry.digest.digest_hex()))
+                    .collect::<HashSet<_>>()
+            } else {
+                HashSet::new()
+            };
+
+        Ok(Some(Arc::new(unchanged)))
+    }
+
+    /// Resolve the copies to perform for each enabled phase.
+    ///
+    /// Pool and installer phases are emitted in copy order. The [CopyPhase::ReleaseIndices] phase is
+    /// always present and last. `Packages` files to regenerate because of a
+    /// [BinaryPackageFilter] are returned alongside and excluded from the indices copies.
+    async fn resolve_copies(
+        &self,
+        release: &dyn ReleaseReader,
+        unchanged_indices: Arc<HashSet<(String, String)>>,
+        max_copy_operations: usize,
+    ) -> Result<(
+        Vec<(CopyPhase, Vec<GenericCopy>)>,
+        Vec<FilteredPackagesIndex>,
+    )> {
+        let mut phases = vec![];
+        let mut filtered_indices = vec![];
+
+        if self.binary_packages_copy {
+            let (copies, filtered) = self
+                .resolve_binary_package_copies(
+                    release,
+                    &unchanged_indices,
+                    false,
+                    max_copy_operations,
+                )
+                .await?;
+            phases.push((CopyPhase::BinaryPackages, copies));
+            filtered_indices = filtered;
+        }
+
+        if self.installer_binary_packages_copy {
+            phases.push((
+                CopyPhase::InstallerBinaryPackages,
+                self.resolve_binary_package_copies(
+                    release,
+                    &unchanged_indices,
+                    true,
+                    max_copy_operations,
+                )
+                .await?
+                .0,
+            ));
+        }
+
+        if self.sources_copy {
+            phases.push((
+                CopyPhase::Sources,
+                self.resolve_source_package_copies(
+                    release,
+                    &unchanged_indices,
+                    max_copy_operations,
+                )
+                .await?,
+            ));
+        }
+
+        if self.installers_copy {
+            phases.push((
+                CopyPhase::Installers,
+                self.resolve_installer_copies(release, &unchanged_indices)
+                    .await?,
+            ));
+        }
+
+        let regenerated = filtered_indices
+            .iter()
+            .flat_map(|index| {
+                index
+                    .compressions
+                    .iter()
+                    .map(|compression| format!("{}{}", index.path, compression.extension()))
+            })
+            .collect::<HashSet<_>>();
+
+        phases.push((
+            CopyPhase::ReleaseIndices,
+            self.resolve_release_indices_copies(release, &unchanged_indices, &regenerated)?,
+        ));
+
+        Ok((phases, filtered_indices))
+    }
+
+    /// Perform the copies of a phase, emitting phase begin and end events.
+    #[allow(clippy::too_many_arguments)]
+    async fn perform_phase(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        writer: &dyn RepositoryWriter,
+        phase: CopyPhase,
+        copies: Vec<GenericCopy>,
+        allow_not_found: bool,
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    ) -> Result<PhaseCopyReport> {
+        if let Some(cb) = progress_cb {
+            cb(PublishEvent::CopyPhaseBegin(phase));
+        }
+        let report = self
+            .perform_copies(
+                root_reader,
+                writer,
+                phase,
+                copies,
+                allow_not_found,
+                max_copy_operations,
+                progress_cb,
+            )
+            .await?;
+        if let Some(cb) = progress_cb {
+            cb(PublishEvent::CopyPhaseEnd(phase));
+        }
+
+        Ok(report)
+    }
+
+    /// Verify the source release file against the keyring, if one is configured.
+    ///
+    /// Returns the hex key ID of the trusted key that verified the signature.
+    fn verify_source_release(
+        &self,
+        distribution_path: &str,
+        release: &dyn ReleaseReader,
+    ) -> Result<Option<String>> {
+        let keyring = if let Some(keyring) = &self.source_keyring {
+            keyring
+        } else {
+            return Ok(None);
+        };
+
+        let signatures = release.release_file().signatures().ok_or_else(|| {
+            DebianError::RepositoryCopySourceUnsigned(distribution_path.to_string())
+        })?;
+
+        match keyring.verify_trusted(signatures, &self.source_trust_policy) {
+            Ok(mut signers) => Ok(Some(signers.swap_remove(0).key_id)),
+            Err(DebianError::PgpSignatureUntrusted) => Err(
+                DebianError::RepositoryCopySourceUntrusted(distribution_path.to_string()),
+            ),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Obtain the destination's release reader for a distribution when syncing.
+    async fn previous_release_reader(
+        &self,
+        distribution_path: &str,
+    ) -> Result<Option<Box<dyn ReleaseReader>>> {
+        let reader = if let Some(reader) = &self.sync_reader {
+            reader
+        } else {
+            return Ok(None);
+        };
+
+        match reader
+            .release_reader_with_distribution_path(distribution_path)
+            .await
+        {
+            Ok(release) => Ok(Some(release)),
+            Err(DebianError::RepositoryIoPath(_, e))
+                if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Resolve the binary packages to copy.
+    ///
+    /// With a [BinaryPackageFilter], the `Packages` files listing only the matching packages
+    /// are also returned.
+    async fn resolve_binary_package_copies(
+        &self,
+        release: &dyn ReleaseReader,
+        unchanged_indices: &Arc<HashSet<(String, String)>>,
+        installer_packages: bool,
+        max_copy_operations: usize,
+    ) -> Result<(Vec<GenericCopy>, Vec<FilteredPackagesIndex>)> {
+        let (max_copy_operations, _) = self.phase_concurrency(
+            if installer_packages {
+                CopyPhase::InstallerBinaryPackages
+            } else {
+                CopyPhase::BinaryPackages
+            },
+            max_copy_operations,
+        );
+
+        let only_arches = if installer_packages {
+            self.installer_binary_packages_only_arches.clone()
+        } else {
+            self.binary_packages_only_arches.clone()
+        };
+        let only_components = self.only_components.clone();
+        let unchanged_indices = unchanged_indices.clone();
+        let package_filter = if installer_packages {
+            BinaryPackageFilter::default()
+        } else {
+            self.binary_package_filter.clone()
+        };
+
+        let entry_allowed = move |entry: &PackagesFileEntry| {
+            let component_allowed = if let Some(only_components) = &only_components {
+                only_components.contains(&entry.component.to_string())
+            } else {
+                true
+            };
+
+            let arch_allowed = if let Some(only_arches) = &only_arches {
+                only_arches.contains(&entry.architecture.to_string())
+            } else {
+                true
+            };
+
+            let changed =
+                !unchanged_indices.contains(&(entry.path.to_string(), entry.digest.digest_hex()));
+
+            component_allowed && arch_allowed && changed && entry.is_installer == installer_packages
+        };
+
+        if package_filter.is_empty() {
+            let copies = release
+                .resolve_package_fetches(
+                    Box::new(move |entry| entry_allowed(&entry)),
+                    Box::new(|_| true),
+                    max_copy_operations,
+                )
+                .await?
+                .into_iter()
+                .map(|bpf| GenericCopy {
+                    source_path: bpf.path.clone(),
+                    dest_path: bpf.path,
+                    expected_content: Some((bpf.size, bpf.digest)),
+                    expected_digest: None,
+                })
+                .collect::<Vec<_>>();
+
+            return Ok((copies, vec![]));
+        }
+
+        // Each `Packages` file is resolved on its own so a variant listing only the matching
+        // packages can be published in its place.
+        let all_entries = release.packages_indices_entries()?;
+        let entries = release
+            .packages_indices_entries_preferred_compression()?
+            .into_iter()
+            .filter(|entry| entry_allowed(entry))
+            .collect::<Vec<_>>();
+
+        let fs = entries.iter().map(|entry| {
+            let path = entry.path.to_string();
+            let package_filter = package_filter.clone();
+
+            async move {
+                let fetches = release
+                    .resolve_package_fetches(
+                        Box::new(move |candidate| candidate.path == path),
+                        Box::new(move |cf| package_filter.matches(&cf)),
+                        1,
+                    )
+                    .await?;
+
+                Ok::<_, DebianError>((entry, fetches))
+            }
+        });
+
+        let mut fs = futures::stream::iter(fs).buffer_unordered(max_copy_operations);
+
+        let mut copies = vec![];
+        let mut indices = vec![];
+
+        while let Some((entry, fetches)) = fs.try_next().await? {
+            let mut data = vec![];
+            for fetch in &fetches {
+                fetch.control_file.write(&mut data)?;
+                data.push(b'\n');
+            }
+
+            let compressions = all_entries
+                .iter()
+                .filter(|candidate| {
+                    candidate.component == entry.component
+                        && candidate.architecture == entry.architecture
+                        && candidate.is_installer == entry.is_installer
+                })
+                .map(|candidate| candidate.compression)
+                .collect::<BTreeSet<_>>();
+
+            indices.push(FilteredPackagesIndex {
+                path: entry
+                    .path
+                    .strip_suffix(entry.compression.extension())
+                    .unwrap_or(entry.path)
+                    .to_string(),
+                compressions: compressions.into_iter().collect(),
+                data,
+            });
+
+            copies.extend(fetches.into_iter().map(|bpf| GenericCopy {
+                source_path: bpf.path.clone(),
+                dest_path: bpf.path,
+                expected_content: Some((bpf.size, bpf.digest)),
+                expected_digest: None,
+            }));
+        }
+
+        Ok((copies, indices))
+    }
+
+    async fn resolve_source_package_copies(
+        &self,
+        release: &dyn ReleaseReader,
+        unchanged_indices: &Arc<HashSet<(String, String)>>,
+        max_copy_operations: usize,
+    ) -> Result<Vec<GenericCopy>> {
+        let (max_copy_operations, _) =
+            self.phase_concurrency(CopyPhase::Sources, max_copy_operations);
+        let only_components = self.only_components.clone();
+        let unchanged_indices = unchanged_indices.clone();
+
+        let copies = release
+            .resolve_source_fetches(
+                Box::new(move |entry| {
+                    let component_allowed = if let Some(only_components) = &only_components {
+                        only_components.contains(&entry.component.to_string())
+                    } else {
+                        true
+                    };
+
+                    component_allowed
+                        && !unchanged_indices
+                            .contains(&(entry.path.to_string(), entry.digest.digest_hex()))
+                }),
+                Box::new(move |_| true),
+                max_copy_operations,
+            )
+            .await?
+            .into_iter()
+            .map(|spf| GenericCopy {
+                source_path: spf.path.clone(),
+                dest_path: spf.path.clone(),
+                expected_content: Some((spf.size, spf.digest.clone())),
+                expected_digest: None,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(copies)
+    }
+
+    async fn resolve_installer_copies(
+        &self,
+        release: &dyn ReleaseReader,
+        unchanged_indices: &Arc<HashSet<(String, String)>>,
+    ) -> Result<Vec<GenericCopy>> {
+        let mut copies = vec![];
+
+        for (entry, files) in installer_manifests(release).await? {
+            // Manifests are at `<component>/installer-<arch>/...`.
+            let mut parts = entry.path.split('/');
+            let component = parts.next().unwrap_or_default();
+            let arch = parts
+                .next()
+                .and_then(|x| x.strip_prefix("installer-"))
+                .unwrap_or_default();
+
+            if let Some(only_components) = &self.only_components {
+                if !only_components.iter().any(|x| x == component) {
+                    continue;
+                }
+            }
+            if let Some(only_arches) = &self.installers_only_arches {
+                if !only_arches.iter().any(|x| x == arch) {
+                    continue;
+                }
+            }
+            if unchanged_indices.contains(&(entry.path.to_string(), entry.digest.digest_hex())) {
+                continue;
+            }
+
+            copies.extend(files.into_iter().map(|(path, digest)| GenericCopy {
+                source_path: path.clone(),
+                dest_path: path,
+                expected_content: None,
+                expected_digest: Some(digest),
+            }));
+        }
+
+        Ok(copies)
+    }
+
+    fn resolve_release_indices_copies(
+        &self,
+        release: &dyn ReleaseReader,
+        unchanged_indices: &HashSet<(String, String)>,
+        regenerated: &HashSet<String>,
+    ) -> Result<Vec<GenericCopy>> {
+        let by_hash = release.release_file().acquire_by_hash().unwrap_or(false);
+
+        let copies = release
+            .classified_indices_entries()?
+            .into_iter()
+            .filter(|entry| {
+                // Indices files are copied regardless of component/architecture filters.
+                // But files identical to the destination's when syncing can be skipped.
+                !unchanged_indices.contains(&(entry.path.to_string(), entry.digest.digest_hex()))
+            })
+            .filter(|entry| match &self.release_rewriter {
+                Some(rewriter) => !rewriter.drops_index(entry.path),
+                None => true,
+            })
+            .filter(|entry| !regenerated.contains(entry.path))
+            .map(move |entry| {
+                // Installers fetch file manifests by their canonical path.
+                let path =
+                    if by_hash && !matches!(entry, ClassifiedReleaseFileEntry::FileManifest(_)) {
+                        entry.by_hash_path()
+                    } else {
+                        entry.path.to_string()
+                    };
+
+                let path = format!("{}/{}", release.root_relative_path(), path);
+
+                GenericCopy {
+                    source_path: path.clone(),
+                    dest_path: path,
+                    expected_content: Some((entry.size, entry.digest.clone())),
+                    expected_digest: None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(copies)
+    }
+
+    /// Write regenerated `Packages` files in each of their compression formats.
+    ///
+    /// Returns the size and digests of each written file, keyed by distribution relative
+    /// path.
+    async fn write_filtered_indices(
+        &self,
+        writer: &dyn RepositoryWriter,
+        release: &dyn ReleaseReader,
+        indices: Vec<FilteredPackagesIndex>,
+        report: &mut PhaseCopyReport,
+        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    ) -> Result<HashMap<String, (u64, MultiContentDigest)>> {
+        let checksums = ChecksumType::all()
+            .filter(|checksum| {
+                release
+                    .release_file()
+                    .field(checksum.field_name())
+                    .is_some()
+            })
+            .collect::<Vec<_>>();
+        let retrieve_checksum = release.retrieve_checksum()?;
+        let by_hash = release.release_file().acquire_by_hash().unwrap_or(false);
+
+        let mut entries = HashMap::new();
+
+        for index in indices {
+            for compression in index.compressions {
+                let path = format!("{}{}", index.path, compression.extension());
+
+                if let Some(rewriter) = &self.release_rewriter {
+                    if rewriter.drops_index(&path) {
+                        continue;
+                    }
+                }
+
+                let mut data = vec![];
+                let mut compressor = write_compressed(&mut data, compression);
+                compressor.write_all(&index.data).await?;
+                compressor.close().await?;
+                drop(compressor);
+
+                let mut digester = MultiDigester::new(checksums.iter().copied());
+                digester.update(&data);
+                let digests = digester.finish();
+
+                // Like copied indices, only the `by-hash` variant of the retrieval checksum
+                // is written.
+                let write_path = if by_hash {
+                    ReleaseFileEntry {
+                        path: &path,
+                        digest: digests.digest_from_checksum(retrieve_checksum).clone(),
+                        size: data.len() as u64,
+                    }
+                    .by_hash_path()
+                } else {
+                    path.clone()
+                };
+                let write_path = format!("{}/{}", release.root_relative_path(), write_path);
+
+                if let Some(cb) = progress_cb {
+                    cb(PublishEvent::IndexFileToWrite(write_path.clone()));
+                }
+
+                let size = data.len() as u64;
+                let write = writer
+                    .write_path(
+                        write_path.clone().into(),
+                        Box::pin(futures::io::Cursor::new(data)),
+                    )
+                    .await?;
+
+                if let Some(cb) = progress_cb {
+                    cb(PublishEvent::IndexFileWritten(
+                        write.path.to_string(),
+                        write.bytes_written,
+                    ));
+                }
+                report.copied_paths += 1;
+                report.copied_bytes += write.bytes_written;
+
+                self.regenerated_indices
+                    .lock()
+                    .map_err(|_| {
+                        DebianError::Other("regenerated indices mutex poisoned".to_string())
+                    })?
+                    .insert(write_path);
+
+                entries.insert(path, (size, digests));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_release_files(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        writer: &dyn RepositoryWriter,
+        distribution_path: &str,
+        release: &dyn ReleaseReader,
+        filtered_entries: &HashMap<String, (u64, MultiContentDigest)>,
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    ) -> Result<PhaseCopyReport> {
+        let release_path = format!("{}/Release", distribution_path);
+
+        // `Release` isn't copied blindly. It is checked against the release file being copied
+        // before anything is written so a mismatch leaves the destination untouched.
+        let release_data = if self.release_rewriter.is_none() {
+            fetch_release(root_reader, &release_path, release).await?
+        } else {
+            None
+        };
+
+        let copies = RELEASE_FILES
+            .iter()
+            .filter(|path| **path != "Release")
+            .filter(|path| self.resign_keys.is_empty() || !SIGNATURE_FILES.contains(path))
+            .map(|path| {
+                let path = format!("{}/{}", distribution_path, path);
+
+                GenericCopy {
+                    source_path: path.clone(),
+                    dest_path: path,
+                    expected_content: None,
+                    expected_digest: None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Not all the well-known files exist. So ignore missing file errors.
+        // TODO we probably want a hard error if `Release` or `InRelease` fail.
+        let mut report = self
+            .perform_copies(
                 root_reader,
                 writer,
-                release.as_ref(),
+                CopyPhase::ReleaseFiles,
+                copies,
                 true,
                 max_copy_operations,
                 progress_cb,
             )
             .await?;
-            if let Some(cb) = progress_cb {
-                cb(PublishEvent::CopyPhaseEnd(
-                    CopyPhase::InstallerBinaryPackages,
-                ));
-            }
+
+        let resign_keys = self
+            .resign_keys
+            .iter()
+            .map(|(key, key_pw)| (key, key_pw))
+            .collect::<Vec<_>>();
+
+        if resign_keys.is_empty() {
+            if let Some(data) = release_data {
+                if let Some(cb) = progress_cb {
+                    cb(PublishEvent::CopyingPath(
+                        release_path.clone(),
+                        release_path.clone(),
+                    ));
+                }
+
+                let write = writer
+                    .write_path(
+                        release_path.into(),
+                        Box::pin(futures::io::Cursor::new(data)),
+                    )
+                    .await?;
+
+                if let Some(cb) = progress_cb {
+                    cb(PublishEvent::PathCopied(
+                        write.path.to_string(),
+                        write.bytes_written,
+                    ));
+                }
+                report.copied_paths += 1;
+                report.copied_bytes += write.bytes_written;
+            }
+
+            return Ok(report);
+        }
+
+        // Only sign content matching the release file that was read, and possibly verified.
+        // The source's `Release` is preferred to preserve its formatting.
+        let release_text = if !filtered_entries.is_empty() {
+            let filtered = replace_release_indices(release.release_file(), filtered_entries);
+
+            match &self.release_rewriter {
+                Some(rewriter) => rewriter.rewrite(&filtered).to_string(),
+                None => filtered.to_string(),
+            }
+        } else {
+            match (&self.release_rewriter, release_data) {
+                (Some(rewriter), _) => rewriter.rewrite(release.release_file()).to_string(),
+                (None, Some(data)) => String::from_utf8(data).map_err(|e| {
+                    DebianError::Other(format!("{} is not UTF-8: {}", release_path, e))
+                })?,
+                (None, None) => release.release_file().to_string(),
+            }
+        };
+
+        let writes = write_signed_release_with_keys(
+            writer,
+            distribution_path,
+            &release_text,
+            &resign_keys,
+            progress_cb,
+        )
+        .await?;
+
+        for write in writes {
+            report.copied_paths += 1;
+            report.copied_bytes += write.bytes_written;
+        }
+
+        Ok(report)
+    }
+
+    /// Perform a sequence of copy operations of a phase between a reader and writer.
+    ///
+    /// Failed copies are retried and skipped as defined by the phase's [CopyErrorPolicy].
+    #[allow(clippy::too_many_arguments)]
+    async fn perform_copies(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        writer: &dyn RepositoryWriter,
+        phase: CopyPhase,
+        copies: Vec<GenericCopy>,
+        allow_not_found: bool,
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+    ) -> Result<PhaseCopyReport> {
+        let start = std::time::Instant::now();
+        let mut report = PhaseCopyReport::new(phase);
+        let state = self.state.as_ref();
+        let (max_copy_operations, max_in_flight_bytes) =
+            self.phase_concurrency(phase, max_copy_operations);
+        let policy = self.error_policy.get(&phase).copied().unwrap_or_default();
+        let mut total_size = 0;
+
+        let mut fs = vec![];
+        for op in copies {
+            let completed_digest = op
+                .expected_content
+                .as_ref()
+                .map(|(_, digest)| digest)
+                .or(op.expected_digest.as_ref());
+
+            if let (Some(state), Some(digest)) = (state, completed_digest) {
+                if lock_copy_state(state)?.is_completed(&op.dest_path, digest) {
+                    report.previously_completed_paths += 1;
+                    if let Some(cb) = progress_cb {
+                        cb(PublishEvent::PathCopyPreviouslyCompleted(op.dest_path));
+                    }
+                    continue;
+                }
+            }
+
+            let size = op
+                .expected_content
+                .as_ref()
+                .map(|(size, _)| *size)
+                .unwrap_or(0);
+            total_size += size;
+
+            fs.push((size, async move {
+                let mut attempt = 0;
+
+                let res = loop {
+                    let res = async {
+                        let write = if let Some(digest) = &op.expected_digest {
+                            copy_with_digest(
+                                root_reader,
+                                writer,
+                                &op.source_path,
+                                op.dest_path.clone().into(),
+                                digest,
+                                progress_cb,
+                            )
+                            .await?
+                        } else {
+                            writer
+                                .copy_from(
+                                    root_reader,
+                                    op.source_path.clone().into(),
+                                    op.expected_content.clone(),
+                                    op.dest_path.clone().into(),
+                                    progress_cb,
+                                )
+                                .await?
+                        };
+
+                        let completed_digest = op
+                            .expected_content
+                            .as_ref()
+                            .map(|(_, digest)| digest)
+                            .or(op.expected_digest.as_ref());
+
+                        if let (Some(state), Some(digest)) = (state, completed_digest) {
+                            lock_copy_state(state)?.record(&op.dest_path, digest)?;
+                        }
+
+                        Ok(write)
+                    }
+                    .await;
+
+                    match res {
+                        Err(e)
+                            if attempt < policy.retries
+                                && !(allow_not_found && is_not_found(&e)) =>
+                        {
+                            attempt += 1;
+                            if let Some(cb) = progress_cb {
+                                cb(PublishEvent::PathCopyRetry(op.dest_path.clone(), attempt));
+                            }
+                            async_std::task::sleep(policy.retry_delay(attempt)).await;
+                        }
+                        res => break res,
+                    }
+                };
+
+                (size, op.dest_path, res)
+            }));
+        }
+
+        if let Some(cb) = progress_cb {
+            cb(PublishEvent::WriteSequenceBeginWithTotalBytes(total_size));
+        }
+
+        // Schedule the largest copies first so a few huge files don't trail behind everything
+        // else. Copies are started as long as the operation and byte limits allow. A copy larger
+        // than the byte limit runs by itself.
+        fs.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+        let mut pending = fs.into_iter().peekable();
+        let mut running = futures::stream::FuturesUnordered::new();
+        let mut in_flight_bytes = 0;
+
+        loop {
+            while running.len() < max_copy_operations.max(1) {
+                let size = if let Some((size, _)) = pending.peek() {
+                    *size
+                } else {
+                    break;
+                };
+
+                if let Some(max_bytes) = max_in_flight_bytes {
+                    if !running.is_empty() && in_flight_bytes + size > max_bytes {
+                        break;
+                    }
+                }
+
+                let (size, fut) = pending.next().expect("peeked above");
+                in_flight_bytes += size;
+                running.push(fut);
+            }
+
+            let (size, dest_path, res) = if let Some(v) = running.next().await {
+                v
+            } else {
+                break;
+            };
+            in_flight_bytes -= size;
+
+            match res {
+                Ok(write) => {
+                    match &write {
+                        RepositoryWriteOperation::PathWritten(write) => {
+                            report.copied_paths += 1;
+                            report.copied_bytes += write.bytes_written;
+                        }
+                        RepositoryWriteOperation::Noop(_, size) => {
+                            report.noop_paths += 1;
+                            report.noop_bytes += size;
+                        }
+                    }
+
+                    if let Some(cb) = progress_cb {
+                        cb(PublishEvent::WriteSequenceProgressBytes(
+                            write.bytes_written(),
+                        ));
+
+                        match write {
+                            RepositoryWriteOperation::PathWritten(write) => {
+                                cb(PublishEvent::PathCopied(
+                                    write.path.to_string(),
+                                    write.bytes_written,
+                                ));
+                            }
+                            RepositoryWriteOperation::Noop(path, _) => {
+                                cb(PublishEvent::PathCopyNoop(path.to_string()));
+                            }
+                        }
+                    }
+                }
+                Err(DebianError::RepositoryIoPath(path, err))
+                    if allow_not_found && matches!(err.kind(), std::io::ErrorKind::NotFound) =>
+                {
+                    if let Some(cb) = progress_cb {
+                        cb(PublishEvent::CopyIndicesPathNotFound(path.clone()));
+                    }
+                    report.not_found_paths.push(path);
+                }
+                Err(e) if policy.skip => {
+                    if let Some(cb) = progress_cb {
+                        cb(PublishEvent::PathCopySkipped(
+                            dest_path.clone(),
+                            e.to_string(),
+                        ));
+                    }
+
+                    let skipped = SkippedCopy {
+                        phase,
+                        path: dest_path,
+                        error: e.to_string(),
+                    };
+                    self.skipped
+                        .lock()
+                        .map_err(|_| {
+                            DebianError::Other("skipped copies mutex poisoned".to_string())
+                        })?
+                        .push(skipped.clone());
+                    report.failed.push(skipped);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Some(cb) = progress_cb {
+            cb(PublishEvent::WriteSequenceFinished);
+        }
+
+        report.elapsed_seconds = start.elapsed().as_secs_f64();
+
+        Ok(report)
+    }
+}
+
+/// Produce a variant of a release file with the size and digests of some indices files replaced.
+///
+/// `entries` is keyed by distribution relative path. Entries for other paths are retained.
+fn replace_release_indices(
+    release: &ReleaseFile<'_>,
+    entries: &HashMap<String, (u64, MultiContentDigest)>,
+) -> ReleaseFile<'static> {
+    let mut para = ControlParagraph::default();
+
+    for field in release.iter_fields() {
+        let value = if let Some(checksum) =
+            ChecksumType::all().find(|c| c.field_name().eq_ignore_ascii_case(field.name()))
+        {
+            // Entries are of form ` <digest> <size> <path>`.
+            std::iter::once("".to_string())
+                .chain(
+                    field
+                        .iter_lines()
+                        .filter(|line| !line.trim().is_empty())
+                        .map(|line| {
+                            match line
+                                .split_ascii_whitespace()
+                                .nth(2)
+                                .and_then(|path| entries.get_key_value(path))
+                            {
+                                Some((path, (size, digests))) => format!(
+                                    " {} {} {}",
+                                    digests.digest_from_checksum(checksum).digest_hex(),
+                                    size,
+                                    path
+                                ),
+                                None => format!(" {}", line.trim()),
+                            }
+                        }),
+                )
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            field.value_str().to_string()
+        };
+
+        para.set_field(ControlField::new(
+            field.name().to_string().into(),
+            value.into(),
+        ));
+    }
+
+    ReleaseFile::from(para)
+}
+
+fn lock_copy_state(state: &Mutex<CopyState>) -> Result<std::sync::MutexGuard<'_, CopyState>> {
+    state
+        .lock()
+        .map_err(|_| DebianError::Other("copy state mutex poisoned".to_string()))
+}
+
+/// Resolve the files listed by the `SHA256SUMS` manifests of installer image trees.
+///
+/// Returns each manifest entry and the repository root relative paths and digests of
+/// the files it lists. Manifests are verified against the release file as they are read.
+async fn installer_manifests<'release>(
+    release: &'release dyn ReleaseReader,
+) -> Result<Vec<(FileManifestEntry<'release>, Vec<(String, ContentDigest)>)>> {
+    let mut res = vec![];
+
+    for entry in release.classified_indices_entries()? {
+        let entry = match entry {
+            ClassifiedReleaseFileEntry::FileManifest(entry)
+                if entry.checksum == ChecksumType::Sha256
+                    && entry
+                        .path
+                        .split('/')
+                        .nth(1)
+                        .map(|x| x.starts_with("installer-"))
+                        .unwrap_or_default() =>
+            {
+                entry
+            }
+            _ => continue,
+        };
+
+        let mut reader = release
+            .get_path_with_digest_verification(entry.path, entry.size, entry.digest.clone())
+            .await?;
+        let mut data = vec![];
+        reader
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| DebianError::RepositoryIoPath(entry.path.to_string(), e))?;
+        let data = String::from_utf8(data)
+            .map_err(|e| DebianError::Other(format!("{} is not UTF-8: {}", entry.path, e)))?;
+        let files = parse_installer_manifest(release, &entry, &data)?;
+
+        res.push((entry, files));
+    }
+
+    Ok(res)
+}
+
+/// Parse the content of an installer image tree's `SHA256SUMS` manifest.
+///
+/// Returns the repository root relative paths and digests of the listed files. Paths
+/// escaping the tree are rejected.
+fn parse_installer_manifest(
+    release: &dyn ReleaseReader,
+    entry: &FileManifestEntry<'_>,
+    data: &str,
+) -> Result<Vec<(String, ContentDigest)>> {
+    // Lines are of form `<digest>  ./<path>`.
+    data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (digest, path) = line.split_once(char::is_whitespace).ok_or_else(|| {
+                DebianError::Other(format!("malformed line in {}: {}", entry.path, line))
+            })?;
+            let path = path.trim().trim_start_matches("./");
+
+            // Listed files must stay within the manifest's tree.
+            if path.starts_with('/') || path.split('/').any(|part| part == "..") {
+                return Err(DebianError::RepositoryInstallerManifestIllegalPath(
+                    entry.path.to_string(),
+                    path.to_string(),
+                ));
+            }
+
+            Ok((
+                format!(
+                    "{}/{}/{}",
+                    release.root_relative_path(),
+                    entry.root_path,
+                    path
+                ),
+                ContentDigest::sha256_hex(digest)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()
+}
+
+/// Fetch the `Release` file of a distribution if the source has one.
+///
+/// The release file being copied is parsed from `InRelease` if present, whose signature
+/// may have been verified. The source's `Release` file must have the same content or this
+/// fails with [DebianError::RepositoryCopyReleaseMismatch]. Callers should use the
+/// returned content rather than fetching it again.
+async fn fetch_release(
+    root_reader: &dyn RepositoryRootReader,
+    path: &str,
+    release: &dyn ReleaseReader,
+) -> Result<Option<Vec<u8>>> {
+    let reader = match root_reader.get_path(path).await {
+        Ok(reader) => reader,
+        Err(e) if is_not_found(&e) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let limits = root_reader.parse_limits();
+    let mut data = vec![];
+    limits.read_to_end_async(reader, &mut data).await?;
+
+    let source_release = ReleaseFile::from_reader_with_limits(std::io::Cursor::new(&data), limits)?;
+    if source_release.to_string() != release.release_file().to_string() {
+        return Err(DebianError::RepositoryCopyReleaseMismatch(path.to_string()));
+    }
+
+    Ok(Some(data))
+}
+
+/// Copy a path having a known content digest but an unknown size.
+///
+/// The size of existing destination content is used to verify it, so content already
+/// present isn't fetched. Otherwise the source content is streamed to the destination and
+/// the write fails if its digest doesn't match. The digest is only known once all content
+/// is written, so the destination is deleted if the write fails.
+async fn copy_with_digest<'path>(
+    root_reader: &dyn RepositoryRootReader,
+    writer: &dyn RepositoryWriter,
+    source_path: &str,
+    dest_path: Cow<'path, str>,
+    digest: &ContentDigest,
+    progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+) -> Result<RepositoryWriteOperation<'path>> {
+    if let Some(cb) = progress_cb {
+        cb(PublishEvent::VerifyingDestinationPath(
+            dest_path.to_string(),
+        ));
+    }
+
+    let size = writer.path_size(dest_path.as_ref()).await?;
+    let verification = writer
+        .verify_path(dest_path.as_ref(), size.map(|size| (size, digest.clone())))
+        .await?;
+    if matches!(
+        verification.state,
+        RepositoryPathVerificationState::ExistsIntegrityVerified
+    ) {
+        return Ok(RepositoryWriteOperation::Noop(
+            dest_path,
+            size.unwrap_or_default(),
+        ));
+    }
+
+    if let Some(cb) = progress_cb {
+        cb(PublishEvent::CopyingPath(
+            source_path.to_string(),
+            dest_path.to_string(),
+        ));
+    }
+
+    let reader = ContentValidatingReader::new_digest_only(
+        root_reader.get_path(source_path).await?,
+        digest.clone(),
+    );
+
+    match writer.write_path(dest_path.clone(), Box::pin(reader)).await {
+        Ok(write) => Ok(RepositoryWriteOperation::PathWritten(write)),
+        Err(e) => {
+            // Don't leave unverified content at the destination. The write error is more
+            // relevant than a failure to clean up.
+            let _ = writer.delete_path(dest_path.as_ref()).await;
+
+            Err(e)
+        }
+    }
+}
+
+fn is_not_found(e: &DebianError) -> bool {
+    matches!(e, DebianError::RepositoryIoPath(_, err) if err.kind() == std::io::ErrorKind::NotFound)
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            control::{ControlFile, ControlParagraph},
+            deb::builder::DebBuilder,
+            repository::{
+                builder::{InMemoryDebFile, RepositoryBuilder, NO_PROGRESS_CB},
+                filesystem::{FilesystemRepositoryReader, FilesystemRepositoryWriter},
+                proxy_writer::{ProxyVerifyBehavior, ProxyWriter},
+                signing::{parse_armored_signatures, write_signed_release},
+                sink_writer::SinkWriter,
+                RepositoryPathVerification, RepositoryWrite,
+            },
+            signing_key::{create_self_signed_key, signing_secret_key_params_builder},
+        },
+        async_trait::async_trait,
+        futures::AsyncRead,
+        pgp::types::PublicKeyTrait,
+        simple_file_manifest::FileEntry,
+        std::{
+            borrow::Cow,
+            path::Path,
+            pin::Pin,
+            sync::atomic::{AtomicUsize, Ordering},
+        },
+        tempfile::TempDir,
+    };
+    #[cfg(feature = "http")]
+    use crate::repository::http::HttpRepositoryClient;
+
+    const DEBIAN_URL: &str = "http://snapshot.debian.org/archive/debian/20211120T085721Z";
+
+    /// Publish a signed repository with `dists/test` holding the given packages.
+    ///
+    /// Packages are defined as `(name, architecture, section, priority, payload size)`.
+    /// Returns the public key of the signing key.
+    async fn publish_test_repository(
+        root: &Path,
+        packages: &[(&str, &str, &str, &str, usize)],
+    ) -> Result<SignedPublicKey> {
+        let mut builder = RepositoryBuilder::new_recommended(
+            ["amd64", "arm64"].into_iter(),
+            ["main"].into_iter(),
+            "test",
+            "test",
+        );
+
+        for (name, arch, section, priority, size) in packages {
+            let mut para = ControlParagraph::default();
+            para.set_field_from_string("Package".into(), name.to_string().into());
+            para.set_field_from_string("Version".into(), "1.0".into());
+            para.set_field_from_string("Architecture".into(), arch.to_string().into());
+            para.set_field_from_string("Section".into(), section.to_string().into());
+            para.set_field_from_string("Priority".into(), priority.to_string().into());
+            let mut control = ControlFile::default();
+            control.add_paragraph(para);
+
+            // Random content so compression doesn't defeat the requested size. Seeded so
+            // republishing produces identical packages.
+            let mut payload = vec![0; *size];
+            rand::RngCore::fill_bytes(
+                &mut <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(*size as u64),
+                &mut payload,
+            );
+
+            let mut data = vec![];
+            // Packages must be reproducible so republishing leaves them unchanged.
+            DebBuilder::new(control)
+                .set_compression(crate::deb::DebCompression::Uncompressed)
+                .set_mtime(Some(std::time::UNIX_EPOCH))
+                .install_file(
+                    "usr/share/payload",
+                    FileEntry::new_from_data(payload, false),
+                )?
+                .write(&mut data)?;
+
+            let deb = InMemoryDebFile::new(format!("{}_1.0_{}.deb", name, arch), data.clone());
+            let pool_path = builder.add_binary_deb("main", &deb)?;
+
+            let path = root.join(pool_path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, data)?;
+        }
+
+        let (key, public_key) = create_self_signed_key(
+            signing_secret_key_params_builder("test@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+
+        builder
+            .publish(
+                &FilesystemRepositoryWriter::new(root),
+                &FilesystemRepositoryReader::new(root),
+                "dists/test",
+                1,
+                &NO_PROGRESS_CB,
+                Some((&key, String::new)),
+            )
+            .await?;
+
+        Ok(public_key)
+    }
+
+    #[tokio::test]
+    async fn binary_package_filter() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        publish_test_repository(
+            &source,
+            &[
+                ("libfoo1", "amd64", "libs", "optional", 10),
+                ("libfoo-dev", "amd64", "libdevel", "optional", 10),
+                ("bar", "amd64", "contrib/utils", "important", 10),
+                ("big", "amd64", "utils", "important", 1_000_000),
+            ],
+        )
+        .await?;
+
+        let mut filter = BinaryPackageFilter::default();
+        filter.set_only_sections(["libs", "utils"].into_iter());
+        filter.set_max_size(100_000);
+        filter.add_filter(|cf| cf.package().map(|name| name != "nope").unwrap_or(false));
+
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_binary_package_filter(filter);
+
+        // Filtering rewrites the release file, so it must be re-signed.
+        assert!(matches!(
+            copier
+                .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+                .await,
+            Err(DebianError::RepositoryCopyFilterWithoutSigningKey)
+        ));
+        assert!(!dest.exists());
+
+        let (mirror_key, mirror_public_key) = create_self_signed_key(
+            signing_secret_key_params_builder("mirror@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+        copier.set_resign_key(mirror_key, String::new);
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+            .await?;
+
+        assert!(dest
+            .join("pool/main/libf/libfoo1/libfoo1_1.0_amd64.deb")
+            .exists());
+        assert!(!dest
+            .join("pool/main/libf/libfoo-dev/libfoo-dev_1.0_amd64.deb")
+            .exists());
+        assert!(dest.join("pool/main/b/bar/bar_1.0_amd64.deb").exists());
+        assert!(!dest.join("pool/main/b/big/big_1.0_amd64.deb").exists());
+
+        // `Packages` files in every compression format only list the copied packages and
+        // match the re-signed release file.
+        let mut release = FilesystemRepositoryReader::new(&dest)
+            .release_reader("test")
+            .await?;
+        assert_eq!(
+            release
+                .release_file()
+                .signatures()
+                .unwrap()
+                .verify(&mirror_public_key)?,
+            1
+        );
+        for compression in [Compression::None, Compression::Gzip, Compression::Xz] {
+            release.set_preferred_compression(compression);
+            let entry = release.packages_entry("main", "amd64", false)?;
+            assert_eq!(entry.compression, compression);
+
+            let packages = release.resolve_packages_from_entry(&entry).await?;
+            assert_eq!(
+                packages
+                    .iter()
+                    .map(|cf| cf.package().unwrap().to_string())
+                    .collect::<BTreeSet<_>>(),
+                BTreeSet::from(["bar".to_string(), "libfoo1".to_string()])
+            );
         }
 
-        if self.sources_copy {
-            if let Some(cb) = progress_cb {
-                cb(PublishEvent::CopyPhaseBegin(CopyPhase::Sources));
-            }
-            self.copy_source_packages(
-                root_reader,
-                writer,
-                release.as_ref(),
-                max_copy_operations,
-                progress_cb,
+        // Regenerated files aren't considered unreferenced.
+        assert!(copier
+            .delete_unreferenced(
+                &source_reader,
+                &dest_writer,
+                &["dists/test".to_string()],
+                &[],
+                1,
+                &None,
             )
+            .await?
+            .is_empty());
+
+        let mut filter = BinaryPackageFilter::default();
+        filter.set_only_names(["lib*"].into_iter())?;
+        filter.set_only_priorities(["optional"].into_iter());
+        let cf = |name: &str, priority: &str| {
+            let mut para = ControlParagraph::default();
+            para.set_field_from_string("Package".into(), name.to_string().into());
+            para.set_field_from_string("Priority".into(), priority.to_string().into());
+            BinaryPackageControlFile::from(para)
+        };
+        assert!(filter.matches(&cf("libfoo", "optional")));
+        assert!(!filter.matches(&cf("libfoo", "important")));
+        assert!(!filter.matches(&cf("foo", "optional")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "http")]
+    async fn bullseye_copy() -> Result<()> {
+        let root =
+            Box::new(HttpRepositoryClient::new(DEBIAN_URL)?) as Box<dyn RepositoryRootReader>;
+        let mut writer = ProxyWriter::new(SinkWriter::default());
+        writer.set_verify_behavior(ProxyVerifyBehavior::AlwaysExistsIntegrityVerified);
+        let writer: Box<dyn RepositoryWriter> = Box::new(writer);
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_binary_packages_copy(false);
+        copier.set_installer_binary_packages_copy(false);
+        copier.set_sources_copy(false);
+        copier.set_installers_copy(false);
+
+        let cb = Box::new(|_| {});
+
+        copier
+            .copy_distribution(root.as_ref(), writer.as_ref(), "bullseye", 8, &Some(cb))
             .await?;
-            if let Some(cb) = progress_cb {
-                cb(PublishEvent::CopyPhaseEnd(CopyPhase::Sources));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sync() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let events_cb = events.clone();
+        let cb: Option<Box<dyn Fn(PublishEvent) + Sync>> = Some(Box::new(move |event| {
+            if event.is_loggable() {
+                events_cb.lock().unwrap().push(event.to_string());
             }
+        }));
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_sync_reader(Box::new(FilesystemRepositoryReader::new(&dest)));
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
+
+        // Initial sync to an empty destination copies everything.
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &cb)
+            .await?;
+        assert!(dest.join("pool/main/f/foo/foo_1.0_amd64.deb").exists());
+
+        // Syncing again is a no-op.
+        events.lock().unwrap().clear();
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &cb)
+            .await?;
+        assert_eq!(
+            events.lock().unwrap().as_slice(),
+            &["distribution dists/test is unchanged; nothing to copy".to_string()]
+        );
+
+        // Changing only arm64 packages only copies arm64 content.
+        publish_test_repository(
+            &source,
+            &[
+                ("foo", "amd64", "utils", "optional", 10),
+                ("bar", "arm64", "utils", "optional", 10),
+            ],
+        )
+        .await?;
+        events.lock().unwrap().clear();
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &cb)
+            .await?;
+        assert!(dest.join("pool/main/b/bar/bar_1.0_arm64.deb").exists());
+
+        let events = events.lock().unwrap();
+        assert!(events.iter().all(|e| !e.contains("amd64")));
+        assert!(events.iter().any(|e| e.contains("binary-arm64")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn signed_audit_log() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+
+        let key = create_self_signed_key(
+            signing_secret_key_params_builder("audit@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?
+        .0;
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_audit_log("audit.json", AuditLog::default());
+        copier.set_audit_log_signing_key(key, String::new);
+        copier
+            .copy_distribution(
+                &FilesystemRepositoryReader::new(&source),
+                &FilesystemRepositoryWriter::new(&dest),
+                "test",
+                1,
+                &None,
+            )
+            .await?;
+
+        let log = std::fs::read_to_string(dest.join("audit.json"))?;
+        assert!(log.contains("pool/main/f/foo/foo_1.0_amd64.deb"));
+        let signed = std::fs::read_to_string(dest.join("audit.json.asc"))?;
+        assert!(signed.starts_with("-----BEGIN PGP SIGNED MESSAGE-----"));
+        assert!(signed.contains("pool/main/f/foo/foo_1.0_amd64.deb"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delete_keeps_audit_log() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+        let key_path = td.path().join("audit.key");
+
+        publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+
+        let key = create_self_signed_key(
+            signing_secret_key_params_builder("audit@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?
+        .0;
+        std::fs::write(&key_path, key.to_armored_string(Default::default())?)?;
+
+        // The log is under a directory whose unreferenced files are deleted.
+        let config = serde_json::json!({
+            "source_url": source.display().to_string(),
+            "destination_url": dest.display().to_string(),
+            "distributions": ["test"],
+            "delete": true,
+            "audit_log_path": "dists/test/audit.json",
+            "audit_log_signing_key_path": key_path.display().to_string(),
+        });
+
+        for _ in 0..2 {
+            let report = RepositoryCopier::copy_from_config(
+                serde_json::from_value(config.clone())?,
+                1,
+                &None,
+            )
+            .await?;
+            assert!(report.deleted_paths.is_empty());
         }
 
-        if self.installers_copy {
-            if let Some(cb) = progress_cb {
-                cb(PublishEvent::CopyPhaseBegin(CopyPhase::Installers));
+        let log = AuditLog::from_json_slice(&std::fs::read(dest.join("dists/test/audit.json"))?)?;
+        assert_eq!(log.entries.len(), 2);
+        assert!(dest.join("dists/test/audit.json.asc").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn copy_report() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+
+        let copier = RepositoryCopier::default();
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
+
+        let report = copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+            .await?;
+        assert_eq!(report.distribution_path, "dists/test");
+        assert!(!report.unchanged);
+        let binary = report
+            .phases
+            .iter()
+            .find(|phase| phase.phase == CopyPhase::BinaryPackages)
+            .unwrap();
+        assert_eq!(binary.copied_paths, 1);
+        assert_eq!(binary.noop_paths, 0);
+        assert!(report.copied_bytes() > 0);
+        assert_eq!(report.failed_paths(), 0);
+
+        // Copying again finds everything already present.
+        let report = copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+            .await?;
+        let binary = report
+            .phases
+            .iter()
+            .find(|phase| phase.phase == CopyPhase::BinaryPackages)
+            .unwrap();
+        assert_eq!(binary.copied_paths, 0);
+        assert_eq!(binary.noop_paths, 1);
+
+        let value = serde_json::to_value(&report)?;
+        assert_eq!(value["distribution_path"], "dists/test");
+        assert_eq!(value["phases"][0]["noop_paths"], 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn copy_report_failure() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+        let report_path = td.path().join("report.json");
+
+        publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+
+        let config = serde_json::from_value::<RepositoryCopierConfig>(serde_json::json!({
+            "source_url": source.display().to_string(),
+            "destination_url": dest.display().to_string(),
+            "distributions": ["test", "missing"],
+            "report_path": report_path.display().to_string(),
+            "skipped_report_path": td.path().join("missing/skipped.json").display().to_string(),
+        }))?;
+
+        // Failing to write the skipped report doesn't replace the copy error.
+        let err = RepositoryCopier::copy_from_config(config, 1, &None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("dists/missing"), "{err}");
+
+        // The report covers the distribution copied before the copy aborted.
+        let report = serde_json::from_slice::<CopyReport>(&std::fs::read(&report_path)?)?;
+        assert_eq!(report.distributions.len(), 1);
+        assert_eq!(report.distributions[0].distribution_path, "dists/test");
+        assert!(report.error.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn source_keyring() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        let public_key =
+            publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+        let other_key = create_self_signed_key(
+            signing_secret_key_params_builder("other@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?
+        .1;
+
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_source_keyring([other_key]);
+        assert!(matches!(
+            copier
+                .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+                .await,
+            Err(DebianError::RepositoryCopySourceUntrusted(_))
+        ));
+        assert!(!dest.exists());
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let events_cb = events.clone();
+        let cb: Option<Box<dyn Fn(PublishEvent) + Sync>> = Some(Box::new(move |event| {
+            if let PublishEvent::ReleaseSignatureVerified(path, key_id) = event {
+                events_cb.lock().unwrap().push((path, key_id));
             }
-            self.copy_installers(
-                root_reader,
-                writer,
-                release.as_ref(),
-                max_copy_operations,
-                progress_cb,
+        }));
+
+        let key_id = format!("{:x}", public_key.key_id());
+        copier.set_source_keyring([public_key]);
+        copier.set_source_verification_policy(VerificationPolicy {
+            time: Some(chrono::DateTime::UNIX_EPOCH),
+            ..VerificationPolicy::strict()
+        });
+        assert!(matches!(
+            copier
+                .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+                .await,
+            Err(DebianError::PgpSignatureRejected(..))
+        ));
+
+        copier.set_source_trust_policy(TrustPolicy {
+            required_fingerprints: vec!["0123456789ABCDEF0123456789ABCDEF01234567".into()],
+            ..TrustPolicy::strict()
+        });
+        assert!(matches!(
+            copier
+                .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+                .await,
+            Err(DebianError::PgpSignatureRequiredKeyMissing(_))
+        ));
+
+        copier.set_source_trust_policy(TrustPolicy::strict());
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &cb)
+            .await?;
+        assert!(dest.join("dists/test/InRelease").exists());
+        assert_eq!(
+            events.lock().unwrap().as_slice(),
+            &[("dists/test".to_string(), key_id)]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resign() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        let upstream_key =
+            publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+        let (mirror_key, mirror_public_key) = create_self_signed_key(
+            signing_secret_key_params_builder("mirror@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+        let (rotated_key, rotated_public_key) = create_self_signed_key(
+            signing_secret_key_params_builder("mirror-2@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_resign_key(mirror_key, String::new);
+        copier.add_resign_key(rotated_key, String::new);
+        let report = copier
+            .copy_distribution(
+                &FilesystemRepositoryReader::new(&source),
+                &FilesystemRepositoryWriter::new(&dest),
+                "test",
+                1,
+                &None,
             )
             .await?;
-            if let Some(cb) = progress_cb {
-                cb(PublishEvent::CopyPhaseEnd(CopyPhase::Installers));
-            }
-        }
 
-        // All the pool artifacts are in place. Publish the indices files.
+        // Release is copied and InRelease and Release.gpg are written by re-signing.
+        let phase = report
+            .phases
+            .iter()
+            .find(|p| p.phase == CopyPhase::ReleaseFiles)
+            .unwrap();
+        assert_eq!(phase.copied_paths, 3);
+        assert_eq!(
+            phase.copied_bytes,
+            ["Release", "InRelease", "Release.gpg"]
+                .iter()
+                .map(|p| std::fs::metadata(dest.join("dists/test").join(p))
+                    .unwrap()
+                    .len())
+                .sum::<u64>()
+        );
 
-        if let Some(cb) = progress_cb {
-            cb(PublishEvent::CopyPhaseBegin(CopyPhase::ReleaseIndices));
-        }
-        self.copy_release_indices(
-            root_reader,
-            writer,
-            release.as_ref(),
-            max_copy_operations,
-            progress_cb,
+        assert_eq!(
+            std::fs::read(dest.join("dists/test/Release"))?,
+            std::fs::read(source.join("dists/test/Release"))?
+        );
+
+        let release = FilesystemRepositoryReader::new(&dest)
+            .release_reader("test")
+            .await?;
+        let signatures = release.release_file().signatures().unwrap();
+        assert_eq!(signatures.verify(&mirror_public_key)?, 1);
+        assert_eq!(signatures.verify(&rotated_public_key)?, 1);
+        assert!(signatures.verify(&upstream_key).is_err());
+
+        let release_data = std::fs::read(dest.join("dists/test/Release"))?;
+        let detached =
+            parse_armored_signatures(&std::fs::read(dest.join("dists/test/Release.gpg"))?)?;
+        assert_eq!(detached.len(), 2);
+        detached[0].verify(&mirror_public_key, &release_data[..])?;
+        detached[1].verify(&rotated_public_key, &release_data[..])?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn release_mismatch() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        let public_key =
+            publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+
+        // A `Release` file differing from the signed `InRelease`.
+        let release_path = source.join("dists/test/Release");
+        let release = std::fs::read_to_string(&release_path)?;
+        std::fs::write(
+            &release_path,
+            release.replace("Suite: test", "Suite: tampered"),
+        )?;
+
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_source_keyring([public_key]);
+        assert!(matches!(
+            copier
+                .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+                .await,
+            Err(DebianError::RepositoryCopyReleaseMismatch(_))
+        ));
+        assert!(!dest.join("dists/test/Release").exists());
+        assert!(!dest.join("dists/test/InRelease").exists());
+
+        // Re-signing doesn't sign the mismatched content either.
+        let (mirror_key, _) = create_self_signed_key(
+            signing_secret_key_params_builder("mirror@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+        copier.set_resign_key(mirror_key, String::new);
+        assert!(matches!(
+            copier
+                .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+                .await,
+            Err(DebianError::RepositoryCopyReleaseMismatch(_))
+        ));
+        assert!(!dest.join("dists/test/Release").exists());
+        assert!(!dest.join("dists/test/InRelease").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn release_rewrite() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        publish_test_repository(
+            &source,
+            &[
+                ("foo", "amd64", "utils", "optional", 10),
+                ("bar", "arm64", "utils", "optional", 10),
+            ],
         )
         .await?;
-        if let Some(cb) = progress_cb {
-            cb(PublishEvent::CopyPhaseEnd(CopyPhase::ReleaseIndices));
-        }
+        let (mirror_key, mirror_public_key) = create_self_signed_key(
+            signing_secret_key_params_builder("mirror@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
 
-        // And finally publish the Release files.
-        if let Some(cb) = progress_cb {
-            cb(PublishEvent::CopyPhaseBegin(CopyPhase::ReleaseFiles));
-        }
-        self.copy_release_files(
-            root_reader,
-            writer,
-            distribution_path,
-            max_copy_operations,
-            progress_cb,
+        let mut rewriter = ReleaseRewriter::default();
+        rewriter.set_field("Origin", "Internal");
+        rewriter.set_field("Valid-Until", "Sat, 02 Nov 2030 12:00:00 UTC");
+        rewriter.set_drop_indices(["main/binary-arm64/*"].iter())?;
+
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_release_rewriter(rewriter);
+        assert!(matches!(
+            copier
+                .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+                .await,
+            Err(DebianError::RepositoryCopyRewriteWithoutSigningKey)
+        ));
+
+        copier.set_resign_key(mirror_key, String::new);
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+            .await?;
+
+        let release = FilesystemRepositoryReader::new(&dest)
+            .release_reader("test")
+            .await?;
+        let signatures = release.release_file().signatures().unwrap();
+        assert_eq!(signatures.verify(&mirror_public_key)?, 1);
+        assert_eq!(release.release_file().origin(), Some("Internal"));
+        assert!(release.release_file().valid_until().is_some());
+        assert!(!release.release_file().to_string().contains("binary-arm64"));
+        assert!(std::fs::read_to_string(dest.join("dists/test/Release"))?
+            .contains("Origin: Internal\n"));
+        assert!(!dest.join("dists/test/main/binary-arm64").exists());
+
+        // Remaining indices still verify against the rewritten release file.
+        let packages = release.resolve_packages("main", "amd64", false).await?;
+        assert_eq!(packages.len(), 1);
+        assert_eq!(
+            release
+                .resolve_packages_parallel("main", "amd64", false, 4)
+                .await?
+                .iter()
+                .collect::<Vec<_>>(),
+            packages.iter().collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn error_policy() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        publish_test_repository(
+            &source,
+            &[
+                ("foo", "amd64", "utils", "optional", 10),
+                ("bar", "amd64", "utils", "optional", 10),
+            ],
         )
         .await?;
-        if let Some(cb) = progress_cb {
-            cb(PublishEvent::CopyPhaseEnd(CopyPhase::ReleaseFiles));
-        }
+        std::fs::remove_file(source.join("pool/main/b/bar/bar_1.0_amd64.deb"))?;
+
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
+
+        let mut copier = RepositoryCopier::default();
+        assert!(copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+            .await
+            .is_err());
+        assert!(!dest.join("dists/test/Release").exists());
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let events_cb = events.clone();
+        let cb: Option<Box<dyn Fn(PublishEvent) + Sync>> = Some(Box::new(move |event| {
+            if matches!(event, PublishEvent::PathCopyRetry(..)) {
+                events_cb.lock().unwrap().push(event.to_string());
+            }
+        }));
+
+        copier.set_error_policy(
+            CopyPhase::BinaryPackages,
+            CopyErrorPolicy {
+                retries: 2,
+                retry_delay_ms: 1,
+                skip: true,
+            },
+        );
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &cb)
+            .await?;
+
+        assert!(dest.join("dists/test/Release").exists());
+        assert!(dest.join("pool/main/f/foo/foo_1.0_amd64.deb").exists());
+        assert_eq!(
+            events.lock().unwrap().as_slice(),
+            &[
+                "retrying copy of pool/main/b/bar/bar_1.0_amd64.deb (attempt 1)".to_string(),
+                "retrying copy of pool/main/b/bar/bar_1.0_amd64.deb (attempt 2)".to_string(),
+            ]
+        );
+
+        let skipped = copier.skipped_copies();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].phase, CopyPhase::BinaryPackages);
+        assert_eq!(skipped[0].path, "pool/main/b/bar/bar_1.0_amd64.deb");
 
         Ok(())
     }
 
-    async fn copy_binary_packages(
-        &self,
-        root_reader: &dyn RepositoryRootReader,
-        writer: &dyn RepositoryWriter,
-        release: &dyn ReleaseReader,
-        installer_packages: bool,
-        max_copy_operations: usize,
-        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
-    ) -> Result<()> {
-        let only_arches = if installer_packages {
-            self.installer_binary_packages_only_arches.clone()
-        } else {
-            self.binary_packages_only_arches.clone()
-        };
-        let only_components = self.only_components.clone();
+    #[test]
+    fn retry_delay() {
+        // Delays grow exponentially up to a bound, minus up to half in jitter.
+        let policy = CopyErrorPolicy::default();
+        for (attempt, max) in [(1, 500), (2, 1000), (3, 2000), (20, MAX_RETRY_DELAY_MS)] {
+            let delay = policy.retry_delay(attempt).as_millis() as u64;
+            assert!(delay <= max && delay >= max / 2, "{attempt}: {delay}");
+        }
+    }
 
-        let copies = release
-            .resolve_package_fetches(
-                Box::new(move |entry| {
-                    let component_allowed = if let Some(only_components) = &only_components {
-                        only_components.contains(&entry.component.to_string())
-                    } else {
-                        true
-                    };
+    /// Add installer image trees for the given architectures to `dists/test`.
+    ///
+    /// The release file is amended to reference the trees' `SHA256SUMS` and re-signed.
+    async fn add_test_installers(root: &Path, arches: &[&str]) -> Result<()> {
+        let dist = root.join("dists/test");
+        let mut release = std::fs::read_to_string(dist.join("Release"))?;
 
-                    let arch_allowed = if let Some(only_arches) = &only_arches {
-                        only_arches.contains(&entry.architecture.to_string())
-                    } else {
-                        true
-                    };
+        for arch in arches {
+            let images = format!("main/installer-{}/current/images", arch);
+            let kernel = format!("kernel for {}", arch);
 
-                    component_allowed && arch_allowed && entry.is_installer == installer_packages
-                }),
-                Box::new(move |_| true),
-                max_copy_operations,
-            )
-            .await?
-            .into_iter()
-            .map(|bpf| GenericCopy {
-                source_path: bpf.path.clone(),
-                dest_path: bpf.path,
-                expected_content: Some((bpf.size, bpf.digest)),
-            })
-            .collect::<Vec<_>>();
+            let mut hasher = ChecksumType::Sha256.new_hasher();
+            hasher.update(kernel.as_bytes());
+            let manifest = format!("{}  ./netboot/linux\n", hex::encode(hasher.finish()));
 
-        perform_copies(
-            root_reader,
-            writer,
-            copies,
-            max_copy_operations,
-            false,
-            progress_cb,
+            std::fs::create_dir_all(dist.join(&images).join("netboot"))?;
+            std::fs::write(dist.join(&images).join("netboot/linux"), &kernel)?;
+            std::fs::write(dist.join(&images).join("SHA256SUMS"), &manifest)?;
+
+            let mut hasher = ChecksumType::Sha256.new_hasher();
+            hasher.update(manifest.as_bytes());
+            release = release.replacen(
+                "SHA256: \n",
+                &format!(
+                    "SHA256: \n {} {} {}/SHA256SUMS\n",
+                    hex::encode(hasher.finish()),
+                    manifest.len(),
+                    images
+                ),
+                1,
+            );
+        }
+
+        let (key, _) = create_self_signed_key(
+            signing_secret_key_params_builder("test@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+        write_signed_release(
+            &FilesystemRepositoryWriter::new(root),
+            "dists/test",
+            &release,
+            (&key, String::new),
+            &None,
         )
         .await?;
 
         Ok(())
     }
 
-    async fn copy_source_packages(
-        &self,
-        root_reader: &dyn RepositoryRootReader,
-        writer: &dyn RepositoryWriter,
-        release: &dyn ReleaseReader,
-        max_copy_operations: usize,
-        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
-    ) -> Result<()> {
-        let only_components = self.only_components.clone();
+    #[tokio::test]
+    async fn installers() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
 
-        let copies = release
-            .resolve_source_fetches(
-                Box::new(move |entry| {
-                    if let Some(only_components) = &only_components {
-                        only_components.contains(&entry.component.to_string())
-                    } else {
-                        true
-                    }
-                }),
-                Box::new(move |_| true),
-                max_copy_operations,
+        publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+        add_test_installers(&source, &["amd64", "arm64"]).await?;
+
+        let source_reader = FilesystemRepositoryReader::new(&source);
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_installers_copy(true);
+        copier.set_installers_only_arches(["amd64".to_string()].into_iter());
+        copier
+            .copy_distribution(
+                &source_reader,
+                &FilesystemRepositoryWriter::new(&dest),
+                "test",
+                1,
+                &None,
             )
-            .await?
-            .into_iter()
-            .map(|spf| GenericCopy {
-                source_path: spf.path.clone(),
-                dest_path: spf.path.clone(),
-                expected_content: Some((spf.size, spf.digest.clone())),
-            })
-            .collect::<Vec<_>>();
+            .await?;
 
-        perform_copies(
-            root_reader,
-            writer,
-            copies,
-            max_copy_operations,
-            false,
-            progress_cb,
-        )
-        .await?;
+        let images = dest.join("dists/test/main/installer-amd64/current/images");
+        assert_eq!(
+            std::fs::read_to_string(images.join("netboot/linux"))?,
+            "kernel for amd64"
+        );
+        assert!(images.join("SHA256SUMS").exists());
+        assert!(!dest
+            .join("dists/test/main/installer-arm64/current/images/netboot/linux")
+            .exists());
+
+        // Content already present at the destination isn't fetched again.
+        std::fs::remove_file(
+            source.join("dists/test/main/installer-amd64/current/images/netboot/linux"),
+        )?;
+        copier
+            .copy_distribution(
+                &source_reader,
+                &FilesystemRepositoryWriter::new(&dest),
+                "test",
+                1,
+                &None,
+            )
+            .await?;
+
+        // Content not matching the manifest is rejected.
+        std::fs::write(
+            source.join("dists/test/main/installer-amd64/current/images/netboot/linux"),
+            "tampered",
+        )?;
+        assert!(copier
+            .copy_distribution(
+                &source_reader,
+                &FilesystemRepositoryWriter::new(td.path().join("dest2")),
+                "test",
+                1,
+                &None,
+            )
+            .await
+            .is_err());
+        assert!(!td
+            .path()
+            .join("dest2/dists/test/main/installer-amd64/current/images/netboot/linux")
+            .exists());
+
+        // Skipping failed copies doesn't publish the rejected content either.
+        copier.set_error_policy(
+            CopyPhase::Installers,
+            CopyErrorPolicy {
+                skip: true,
+                ..Default::default()
+            },
+        );
+        copier
+            .copy_distribution(
+                &source_reader,
+                &FilesystemRepositoryWriter::new(td.path().join("dest3")),
+                "test",
+                1,
+                &None,
+            )
+            .await?;
+        assert!(!td
+            .path()
+            .join("dest3/dists/test/main/installer-amd64/current/images/netboot/linux")
+            .exists());
+        assert!(td.path().join("dest3/dists/test/InRelease").exists());
 
         Ok(())
     }
 
-    async fn copy_installers(
-        &self,
-        _root_reader: &dyn RepositoryRootReader,
-        _writer: &dyn RepositoryWriter,
-        _release: &dyn ReleaseReader,
-        _max_copy_operations: usize,
-        _progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
-    ) -> Result<()> {
-        // Not yet supported since this requires teaching content validating fetching about
-        // optional sizes.
-        todo!();
-    }
+    #[tokio::test]
+    async fn installer_manifest_illegal_path() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
 
-    async fn copy_release_indices(
-        &self,
-        root_reader: &dyn RepositoryRootReader,
-        writer: &dyn RepositoryWriter,
-        release: &dyn ReleaseReader,
-        max_copy_operations: usize,
-        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
-    ) -> Result<()> {
-        let by_hash = release.release_file().acquire_by_hash().unwrap_or(false);
+        publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+        add_test_installers(&source, &["amd64"]).await?;
 
-        let copies = release
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let release = source_reader.release_reader("test").await?;
+        let entry = release
             .classified_indices_entries()?
             .into_iter()
-            .filter(|_| {
-                // For now we always copy all the indices files. There is certainly room to filter the
-                // files that are copied. We can do that when we feel like writing the code...
-                true
+            .find_map(|entry| match entry {
+                ClassifiedReleaseFileEntry::FileManifest(entry) => Some(entry),
+                _ => None,
             })
-            .map(move |entry| {
-                let path = if by_hash {
-                    entry.by_hash_path()
-                } else {
-                    entry.path.to_string()
-                };
-
-                let path = format!("{}/{}", release.root_relative_path(), path);
+            .unwrap();
 
-                GenericCopy {
-                    source_path: path.clone(),
-                    dest_path: path,
-                    expected_content: Some((entry.size, entry.digest.clone())),
-                }
-            })
-            .collect::<Vec<_>>();
+        let files = parse_installer_manifest(
+            release.as_ref(),
+            &entry,
+            &format!("{}  ./netboot/linux\n", "00".repeat(32)),
+        )?;
+        assert_eq!(
+            files[0].0,
+            "dists/test/main/installer-amd64/current/images/netboot/linux"
+        );
 
-        // Some indices files don't actually exist! For example, the release file will publish
-        // the checksums of the uncompressed file variant but the uncompressed file won't
-        // actually be present in the repository! These errors are OK to ignore. But we still
-        // report on them.
-        perform_copies(
-            root_reader,
-            writer,
-            copies,
-            max_copy_operations,
-            true,
-            progress_cb,
-        )
-        .await?;
+        for path in [
+            "../../../../../etc/passwd",
+            "/etc/passwd",
+            "netboot/../../x",
+        ] {
+            let lines = format!("{}  {}\n", "00".repeat(32), path);
+            assert!(matches!(
+                parse_installer_manifest(release.as_ref(), &entry, &lines),
+                Err(DebianError::RepositoryInstallerManifestIllegalPath(_, _))
+            ));
+        }
 
         Ok(())
     }
 
-    async fn copy_release_files(
-        &self,
-        root_reader: &dyn RepositoryRootReader,
-        writer: &dyn RepositoryWriter,
-        distribution_path: &str,
-        max_copy_operations: usize,
-        progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
-    ) -> Result<()> {
-        let copies = RELEASE_FILES
-            .iter()
-            .map(|path| {
-                let path = format!("{}/{}", distribution_path, path);
+    #[tokio::test]
+    async fn resume_from_state() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+        let state_path = td.path().join("state.jsonl");
 
-                GenericCopy {
-                    source_path: path.clone(),
-                    dest_path: path,
-                    expected_content: None,
-                }
-            })
-            .collect::<Vec<_>>();
+        publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
 
-        // Not all the well-known files exist. So ignore missing file errors.
-        // TODO we probably want a hard error if `Release` or `InRelease` fail.
-        perform_copies(
-            root_reader,
-            writer,
-            copies,
-            max_copy_operations,
-            true,
-            progress_cb,
-        )
-        .await?;
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_state(CopyState::open(&state_path)?);
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+            .await?;
+
+        // Simulate a write interrupted by a crash.
+        let mut fh = std::fs::OpenOptions::new().append(true).open(&state_path)?;
+        fh.write_all(b"{\"path\": \"pool/")?;
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let events_cb = events.clone();
+        let cb: Option<Box<dyn Fn(PublishEvent) + Sync>> = Some(Box::new(move |event| {
+            if event.is_loggable() {
+                events_cb.lock().unwrap().push(event.to_string());
+            }
+        }));
+
+        let mut copier = RepositoryCopier::default();
+        copier.set_state(CopyState::open(&state_path)?);
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &cb)
+            .await?;
+
+        let events = events.lock().unwrap();
+        assert!(events.contains(
+            &"copy of pool/main/f/foo/foo_1.0_amd64.deb skipped; previously completed".to_string()
+        ));
+        assert!(!events
+            .iter()
+            .any(|e| e.starts_with("verifying destination path pool/")));
 
         Ok(())
     }
-}
 
-/// Perform a sequence of copy operations between a reader and writer.
-async fn perform_copies(
-    root_reader: &dyn RepositoryRootReader,
-    writer: &dyn RepositoryWriter,
-    copies: Vec<GenericCopy>,
-    max_copy_operations: usize,
-    allow_not_found: bool,
-    progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
-) -> Result<()> {
-    let mut total_size = 0;
+    #[tokio::test]
+    async fn delete_unreferenced() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
 
-    let fs = copies
-        .into_iter()
-        .map(|op| {
-            if let Some((size, _)) = op.expected_content {
-                total_size += size;
-            }
+        publish_test_repository(
+            &source,
+            &[
+                ("foo", "amd64", "utils", "optional", 10),
+                ("bar", "amd64", "utils", "optional", 10),
+            ],
+        )
+        .await?;
 
-            writer.copy_from(
-                root_reader,
-                op.source_path.into(),
-                op.expected_content,
-                op.dest_path.into(),
-                progress_cb,
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
+        let copier = RepositoryCopier::default();
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+            .await?;
+        std::fs::write(dest.join("pool/main/stray"), b"stray")?;
+        std::fs::write(dest.join("audit.json"), b"[]")?;
+
+        // Upstream drops a package.
+        std::fs::remove_dir_all(&source)?;
+        publish_test_repository(&source, &[("foo", "amd64", "utils", "optional", 10)]).await?;
+        copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+            .await?;
+        assert!(dest.join("pool/main/b/bar/bar_1.0_amd64.deb").exists());
+
+        copier
+            .delete_unreferenced(
+                &source_reader,
+                &dest_writer,
+                &["dists/test".to_string()],
+                &["audit.json".to_string()],
+                1,
+                &None,
             )
-        })
-        .collect::<Vec<_>>();
+            .await?;
 
-    if let Some(cb) = progress_cb {
-        cb(PublishEvent::WriteSequenceBeginWithTotalBytes(total_size));
+        assert!(!dest.join("pool/main/b/bar/bar_1.0_amd64.deb").exists());
+        assert!(!dest.join("pool/main/stray").exists());
+        assert!(dest.join("pool/main/f/foo/foo_1.0_amd64.deb").exists());
+        assert!(dest.join("audit.json").exists());
+
+        // Only by-hash indices of the current release remain.
+        assert_eq!(
+            dest_writer
+                .list_paths("dists/test/main/binary-amd64/by-hash/SHA256")
+                .await?,
+            FilesystemRepositoryWriter::new(&source)
+                .list_paths("dists/test/main/binary-amd64/by-hash/SHA256")
+                .await?
+        );
+
+        Ok(())
     }
 
-    let mut buffered = futures::stream::iter(fs).buffer_unordered(max_copy_operations);
+    /// A writer recording the order and peak concurrency of pool writes.
+    #[derive(Default)]
+    struct SchedulingWriter {
+        active: AtomicUsize,
+        peak: AtomicUsize,
+        pool_writes: Mutex<Vec<String>>,
+    }
 
-    while let Some(res) = buffered.next().await {
-        match res {
-            Ok(write) => {
-                if let Some(cb) = progress_cb {
-                    cb(PublishEvent::WriteSequenceProgressBytes(
-                        write.bytes_written(),
-                    ));
+    #[async_trait]
+    impl RepositoryWriter for SchedulingWriter {
+        async fn verify_path<'path>(
+            &self,
+            path: &'path str,
+            expected_content: Option<(u64, ContentDigest)>,
+        ) -> Result<RepositoryPathVerification<'path>> {
+            SinkWriter::default()
+                .verify_path(path, expected_content)
+                .await
+        }
 
-                    match write {
-                        RepositoryWriteOperation::PathWritten(write) => {
-                            cb(PublishEvent::PathCopied(
-                                write.path.to_string(),
-                                write.bytes_written,
-                            ));
-                        }
-                        RepositoryWriteOperation::Noop(path, _) => {
-                            cb(PublishEvent::PathCopyNoop(path.to_string()));
-                        }
-                    }
-                }
-            }
-            Err(DebianError::RepositoryIoPath(path, err))
-                if allow_not_found && matches!(err.kind(), std::io::ErrorKind::NotFound) =>
-            {
-                if let Some(cb) = progress_cb {
-                    cb(PublishEvent::CopyIndicesPathNotFound(path));
-                }
+        async fn write_path<'path, 'reader>(
+            &self,
+            path: Cow<'path, str>,
+            reader: Pin<Box<dyn AsyncRead + Send + 'reader>>,
+        ) -> Result<RepositoryWrite<'path>> {
+            if !path.starts_with("pool/") {
+                return SinkWriter::default().write_path(path, reader).await;
             }
-            Err(e) => return Err(e),
+
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(active, Ordering::SeqCst);
+            self.pool_writes.lock().unwrap().push(path.to_string());
+
+            // Give other copies a chance to start.
+            tokio::task::yield_now().await;
+            let res = SinkWriter::default().write_path(path, reader).await;
+
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            res
         }
     }
 
-    if let Some(cb) = progress_cb {
-        cb(PublishEvent::WriteSequenceFinished);
-    }
+    #[tokio::test]
+    async fn phase_concurrency() -> Result<()> {
+        let td = TempDir::new()?;
 
-    Ok(())
-}
+        publish_test_repository(
+            td.path(),
+            &[
+                ("small", "amd64", "utils", "optional", 1000),
+                ("large", "amd64", "utils", "optional", 3000),
+                ("medium", "amd64", "utils", "optional", 2000),
+            ],
+        )
+        .await?;
+        let reader = FilesystemRepositoryReader::new(td.path());
 
-#[cfg(test)]
-mod test {
-    use {
-        super::*,
-        crate::repository::{
-            proxy_writer::{ProxyVerifyBehavior, ProxyWriter},
-            sink_writer::SinkWriter,
-        },
-    };
-    #[cfg(feature = "http")]
-    use crate::repository::http::HttpRepositoryClient;
+        let mut copier = RepositoryCopier::default();
+        copier.set_phase_concurrency(
+            CopyPhase::BinaryPackages,
+            CopyConcurrency {
+                max_operations: None,
+                max_in_flight_bytes: Some(1),
+            },
+        );
 
-    const DEBIAN_URL: &str = "http://snapshot.debian.org/archive/debian/20211120T085721Z";
+        let writer = SchedulingWriter::default();
+        copier
+            .copy_distribution(&reader, &writer, "test", 8, &None)
+            .await?;
+
+        // The byte limit serializes pool copies, which are started largest first.
+        assert_eq!(writer.peak.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            writer.pool_writes.lock().unwrap().as_slice(),
+            &[
+                "pool/main/l/large/large_1.0_amd64.deb".to_string(),
+                "pool/main/m/medium/medium_1.0_amd64.deb".to_string(),
+                "pool/main/s/small/small_1.0_amd64.deb".to_string(),
+            ]
+        );
+
+        Ok(())
+    }
 
     #[tokio::test]
-    #[cfg(feature = "http")]
-    async fn bullseye_copy() -> Result<()> {
-        let root =
-            Box::new(HttpRepositoryClient::new(DEBIAN_URL)?) as Box<dyn RepositoryRootReader>;
-        let mut writer = ProxyWriter::new(SinkWriter::default());
-        writer.set_verify_behavior(ProxyVerifyBehavior::AlwaysExistsIntegrityVerified);
-        let writer: Box<dyn RepositoryWriter> = Box::new(writer);
+    async fn mirror_config() -> Result<()> {
+        let td = TempDir::new()?;
+        let source = td.path().join("source");
+        let dest = td.path().join("dest");
+
+        publish_test_repository(
+            &source,
+            &[
+                ("foo", "amd64", "utils", "optional", 10),
+                ("bar", "arm64", "utils", "optional", 1000),
+            ],
+        )
+        .await?;
+
+        let source_reader = FilesystemRepositoryReader::new(&source);
+        let dest_writer = FilesystemRepositoryWriter::new(&dest);
 
         let mut copier = RepositoryCopier::default();
-        copier.set_binary_packages_copy(false);
-        copier.set_installer_binary_packages_copy(false);
-        copier.set_sources_copy(false);
+        copier.apply_mirror_config(&MirrorConfig {
+            architectures: Some(vec!["amd64".to_string()]),
+            installers: Some(false),
+            sources: Some(false),
+            ..Default::default()
+        });
 
-        let cb = Box::new(|_| {});
+        let plan = copier
+            .plan_distribution_path(&source_reader, "dists/test", 1)
+            .await?;
+        assert_eq!(
+            plan.phases.iter().map(|p| p.phase).collect::<Vec<_>>(),
+            vec![CopyPhase::BinaryPackages, CopyPhase::ReleaseIndices]
+        );
+        assert_eq!(plan.phases[0].paths, 1);
 
+        // Exceeding the budget fails before anything is written.
+        copier.set_max_total_size(plan.total_size() - 1);
+        let res = copier
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
+            .await;
+        assert!(matches!(
+            res,
+            Err(DebianError::RepositoryCopySizeBudgetExceeded(..))
+        ));
+        assert!(!dest.exists());
+
+        copier.set_max_total_size(plan.total_size());
         copier
-            .copy_distribution(root.as_ref(), writer.as_ref(), "bullseye", 8, &Some(cb))
+            .copy_distribution(&source_reader, &dest_writer, "test", 1, &None)
             .await?;
+        assert!(dest.join("pool/main/f/foo/foo_1.0_amd64.deb").exists());
+        assert!(!dest.join("pool/main/b/bar/bar_1.0_arm64.deb").exists());
 
         Ok(())
     }
@@ -6,6 +6,7 @@
 
 use {
     crate::{
+        control::ParseLimits,
         error::{DebianError, Result},
         io::{Compression, ContentDigest, DataResolver, DigestingReader},
         repository::{
@@ -18,16 +19,30 @@ use {
     futures::{io::BufReader, AsyncRead, AsyncReadExt},
     std::{
         borrow::Cow,
+        io::{Read, Seek, SeekFrom},
+        ops::Range,
         path::{Path, PathBuf},
         pin::Pin,
     },
     url::Url,
 };
 
+/// Open a byte range of a file as an async reader.
+fn open_range(path: &Path, range: Range<u64>) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+    let mut f = std::fs::File::open(path)
+        .map_err(|e| DebianError::RepositoryIoPath(format!("{}", path.display()), e))?;
+    f.seek(SeekFrom::Start(range.start))?;
+
+    Ok(Box::pin(BufReader::new(futures::io::AllowStdIo::new(
+        f.take(range.end.saturating_sub(range.start)),
+    ))))
+}
+
 /// A readable interface to a Debian repository backed by a filesystem.
 #[derive(Clone, Debug)]
 pub struct FilesystemRepositoryReader {
     root_dir: PathBuf,
+    parse_limits: ParseLimits,
 }
 
 impl FilesystemRepositoryReader {
@@ -37,8 +52,16 @@ impl FilesystemRepositoryReader {
     pub fn new(path: impl AsRef<Path>) -> Self {
         Self {
             root_dir: path.as_ref().to_path_buf(),
+            parse_limits: ParseLimits::default(),
         }
     }
+
+    /// Set the [ParseLimits] enforced when parsing fetched files.
+    ///
+    /// Obtained [ReleaseReader] inherit these limits.
+    pub fn set_parse_limits(&mut self, limits: ParseLimits) {
+        self.parse_limits = limits;
+    }
 }
 
 #[async_trait]
@@ -51,6 +74,18 @@ impl DataResolver for FilesystemRepositoryReader {
 
         Ok(Box::pin(futures::io::AllowStdIo::new(f)))
     }
+
+    fn local_path(&self, path: &str) -> Option<PathBuf> {
+        Some(self.root_dir.join(path))
+    }
+
+    async fn get_path_with_range(
+        &self,
+        path: &str,
+        range: Range<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        open_range(&self.root_dir.join(path), range)
+    }
 }
 
 #[async_trait]
@@ -60,6 +95,10 @@ impl RepositoryRootReader for FilesystemRepositoryReader {
             .map_err(|_| DebianError::Other("error converting filesystem path to URL".to_string()))
     }
 
+    fn parse_limits(&self) -> ParseLimits {
+        self.parse_limits
+    }
+
     async fn release_reader_with_distribution_path(
         &self,
         path: &str,
@@ -82,6 +121,7 @@ impl RepositoryRootReader for FilesystemRepositoryReader {
             relative_path: distribution_path,
             release,
             fetch_compression,
+            parse_limits: self.parse_limits,
         }))
     }
 }
@@ -91,6 +131,7 @@ pub struct FilesystemReleaseClient {
     relative_path: String,
     release: ReleaseFile<'static>,
     fetch_compression: Compression,
+    parse_limits: ParseLimits,
 }
 
 #[async_trait]
@@ -103,6 +144,18 @@ impl DataResolver for FilesystemReleaseClient {
 
         Ok(Box::pin(BufReader::new(futures::io::AllowStdIo::new(f))))
     }
+
+    fn local_path(&self, path: &str) -> Option<PathBuf> {
+        Some(self.distribution_dir.join(path))
+    }
+
+    async fn get_path_with_range(
+        &self,
+        path: &str,
+        range: Range<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        open_range(&self.distribution_dir.join(path), range)
+    }
 }
 
 #[async_trait]
@@ -127,6 +180,14 @@ impl ReleaseReader for FilesystemReleaseClient {
     fn set_preferred_compression(&mut self, compression: Compression) {
         self.fetch_compression = compression;
     }
+
+    fn parse_limits(&self) -> ParseLimits {
+        self.parse_limits
+    }
+
+    fn set_parse_limits(&mut self, limits: ParseLimits) {
+        self.parse_limits = limits;
+    }
 }
 
 /// A writable Debian repository backed by a filesystem.
@@ -220,6 +281,15 @@ impl RepositoryWriter for FilesystemRepositoryWriter {
         }
     }
 
+    async fn path_size(&self, path: &str) -> Result<Option<u64>> {
+        match async_std::fs::metadata(self.root_dir.join(path)).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(metadata.len())),
+            Ok(_) => Ok(None),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(DebianError::RepositoryIoPath(path.to_string(), e)),
+        }
+    }
+
     async fn write_path<'path, 'reader>(
         &self,
         path: Cow<'path, str>,
@@ -246,4 +316,265 @@ impl RepositoryWriter for FilesystemRepositoryWriter {
             bytes_written,
         })
     }
+
+    /// Copies with [std::io::copy()], which uses `copy_file_range` on Linux to copy in the
+    /// kernel and share extents on filesystems supporting reflinks. As with
+    /// [Self::write_path()], the destination is created with default permissions rather
+    /// than those of the source.
+    async fn copy_local_path(&self, source: &Path, dest_path: &str) -> Result<Option<u64>> {
+        let dest_path = self.root_dir.join(dest_path);
+
+        // Copying a file onto itself would truncate it.
+        if let (Ok(a), Ok(b)) = (source.canonicalize(), dest_path.canonicalize()) {
+            if a == b {
+                return Ok(Some(std::fs::metadata(&a)?.len()));
+            }
+        }
+
+        if let Some(parent) = dest_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| DebianError::RepositoryIoPath(format!("{}", parent.display()), e))?;
+        }
+
+        let mut source_fh = std::fs::File::open(source)
+            .map_err(|e| DebianError::RepositoryIoPath(format!("{}", source.display()), e))?;
+        let mut dest_fh = std::fs::File::create(&dest_path)
+            .map_err(|e| DebianError::RepositoryIoPath(format!("{}", dest_path.display()), e))?;
+
+        let bytes_written = std::io::copy(&mut source_fh, &mut dest_fh)
+            .map_err(|e| DebianError::RepositoryIoPath(format!("{}", dest_path.display()), e))?;
+
+        Ok(Some(bytes_written))
+    }
+
+    async fn list_paths(&self, prefix: &str) -> Result<Vec<String>> {
+        let prefix = prefix.trim_matches('/');
+        let mut paths = vec![];
+        let mut dirs = vec![self.root_dir.join(prefix)];
+
+        while let Some(dir) = dirs.pop() {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(DebianError::RepositoryIoPath(
+                        format!("{}", dir.display()),
+                        e,
+                    ))
+                }
+            };
+
+            for entry in entries {
+                let path = entry
+                    .map_err(|e| DebianError::RepositoryIoPath(format!("{}", dir.display()), e))?
+                    .path();
+
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if let Ok(rel) = path.strip_prefix(&self.root_dir) {
+                    paths.push(
+                        rel.components()
+                            .map(|c| c.as_os_str().to_string_lossy())
+                            .collect::<Vec<_>>()
+                            .join("/"),
+                    );
+                }
+            }
+        }
+
+        paths.sort();
+
+        Ok(paths)
+    }
+
+    async fn delete_path(&self, path: &str) -> Result<()> {
+        let dest_path = self.root_dir.join(path);
+
+        match std::fs::remove_file(&dest_path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(DebianError::RepositoryIoPath(
+                format!("{}", dest_path.display()),
+                e,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::repository::{release::ChecksumType, RepositoryWriteOperation},
+    };
+
+    #[tokio::test]
+    async fn copy_local() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let source_dir = td.path().join("source");
+        let dest_dir = td.path().join("dest");
+
+        std::fs::create_dir_all(source_dir.join("pool"))?;
+        std::fs::write(source_dir.join("pool/a.deb"), b"deb content")?;
+
+        let digest = ContentDigest::compute(ChecksumType::Sha256, b"deb content");
+
+        let reader = FilesystemRepositoryReader::new(&source_dir);
+        let writer = FilesystemRepositoryWriter::new(&dest_dir);
+
+        let op = writer
+            .copy_from(
+                &reader,
+                "pool/a.deb".into(),
+                Some((11, digest.clone())),
+                "pool/b.deb".into(),
+                &None,
+            )
+            .await?;
+        assert!(
+            matches!(op, RepositoryWriteOperation::PathWritten(ref w) if w.bytes_written == 11)
+        );
+        assert_eq!(std::fs::read(dest_dir.join("pool/b.deb"))?, b"deb content");
+
+        // Copying a file onto itself leaves it intact.
+        let writer = FilesystemRepositoryWriter::new(&source_dir);
+        writer
+            .copy_from(
+                &reader,
+                "pool/a.deb".into(),
+                None,
+                "pool/a.deb".into(),
+                &None,
+            )
+            .await?;
+        assert_eq!(
+            std::fs::read(source_dir.join("pool/a.deb"))?,
+            b"deb content"
+        );
+
+        // Content is verified after copying.
+        std::fs::write(source_dir.join("pool/a.deb"), b"bad content")?;
+        let writer = FilesystemRepositoryWriter::new(&dest_dir);
+        assert!(matches!(
+            writer
+                .copy_from(
+                    &reader,
+                    "pool/a.deb".into(),
+                    Some((11, digest)),
+                    "pool/c.deb".into(),
+                    &None,
+                )
+                .await,
+            Err(DebianError::RepositoryIoPath(path, _)) if path == "pool/a.deb"
+        ));
+        // The corrupt copy is removed.
+        assert!(!dest_dir.join("pool/c.deb").exists());
+
+        // Source permissions aren't copied, as when streaming content.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(
+                source_dir.join("pool/a.deb"),
+                std::fs::Permissions::from_mode(0o755),
+            )?;
+            writer
+                .copy_from(
+                    &reader,
+                    "pool/a.deb".into(),
+                    None,
+                    "pool/d.deb".into(),
+                    &None,
+                )
+                .await?;
+            let mode = std::fs::metadata(dest_dir.join("pool/d.deb"))?
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o111, 0);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn parse_limits() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let dist_dir = td.path().join("dists/test");
+        std::fs::create_dir_all(dist_dir.join("main/binary-amd64"))?;
+
+        let packages = b"Package: a\n\nPackage: b\n\n";
+        std::fs::write(dist_dir.join("main/binary-amd64/Packages"), packages)?;
+        let release = format!(
+            "Suite: test\nComponents: main\nArchitectures: amd64\nSHA256:\n {} {} main/binary-amd64/Packages\n",
+            ContentDigest::compute(ChecksumType::Sha256, packages).digest_hex(),
+            packages.len()
+        );
+        std::fs::write(dist_dir.join("Release"), &release)?;
+
+        let mut reader = FilesystemRepositoryReader::new(td.path());
+        let mut release_reader = reader.release_reader("test").await?;
+        assert_eq!(release_reader.parse_limits(), ParseLimits::default());
+        assert_eq!(
+            release_reader
+                .resolve_packages("main", "amd64", false)
+                .await?
+                .len(),
+            2
+        );
+
+        // Limits apply to index files.
+        release_reader.set_parse_limits(ParseLimits {
+            max_entries: 1,
+            ..Default::default()
+        });
+        assert!(matches!(
+            release_reader
+                .resolve_packages("main", "amd64", false)
+                .await
+                .unwrap_err()
+                .unlocated(),
+            DebianError::ParseTooManyEntries(1)
+        ));
+
+        // Including when parsing in parallel.
+        release_reader.set_parse_limits(ParseLimits::default());
+        assert_eq!(
+            release_reader
+                .resolve_packages_parallel("main", "amd64", false, 2)
+                .await?
+                .len(),
+            2
+        );
+        release_reader.set_parse_limits(ParseLimits {
+            max_total_size: 16,
+            ..Default::default()
+        });
+        assert!(matches!(
+            release_reader
+                .resolve_packages_parallel("main", "amd64", false, 2)
+                .await,
+            Err(DebianError::ParseInputTooLarge(16))
+        ));
+
+        // Limits are inherited from the root reader and apply to the release file.
+        let limits = ParseLimits {
+            max_total_size: release.len() as u64,
+            ..Default::default()
+        };
+        reader.set_parse_limits(limits);
+        let release_reader = reader.release_reader("test").await?;
+        assert_eq!(release_reader.parse_limits(), limits);
+
+        reader.set_parse_limits(ParseLimits {
+            max_total_size: 16,
+            ..Default::default()
+        });
+        assert!(matches!(
+            reader.release_reader("test").await,
+            Err(DebianError::ParseInputTooLarge(16))
+        ));
+
+        Ok(())
+    }
 }
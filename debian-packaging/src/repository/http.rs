@@ -10,14 +10,15 @@ repositories.
 
 use {
     crate::{
+        control::ParseLimits,
         error::{DebianError, Result},
-        io::DataResolver,
+        io::{range_header_value, read_range, DataResolver},
         repository::{release::ReleaseFile, Compression, ReleaseReader, RepositoryRootReader},
     },
     async_trait::async_trait,
     futures::{stream::TryStreamExt, AsyncRead},
-    reqwest::{Client, ClientBuilder, IntoUrl, StatusCode, Url},
-    std::pin::Pin,
+    reqwest::{header::RANGE, Client, ClientBuilder, IntoUrl, StatusCode, Url},
+    std::{ops::Range, pin::Pin},
 };
 
 /// Default HTTP user agent string.
@@ -28,10 +29,22 @@ async fn fetch_url(
     client: &Client,
     root_url: &Url,
     path: &str,
+    range: Option<Range<u64>>,
 ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
     let request_url = root_url.join(path)?;
 
-    let res = client.get(request_url.clone()).send().await.map_err(|e| {
+    let mut request = client.get(request_url.clone());
+
+    if let Some(range) = &range {
+        // An empty range can't be expressed in a Range header.
+        if range.start >= range.end {
+            return Ok(Box::pin(futures::io::empty()));
+        }
+
+        request = request.header(RANGE, range_header_value(range));
+    }
+
+    let res = request.send().await.map_err(|e| {
         DebianError::RepositoryIoPath(
             path.to_string(),
             std::io::Error::new(
@@ -41,6 +54,11 @@ async fn fetch_url(
         )
     })?;
 
+    // A range starting past the end of content is empty.
+    if range.is_some() && res.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok(Box::pin(futures::io::empty()));
+    }
+
     let res = res.error_for_status().map_err(|e| {
         if e.status() == Some(StatusCode::NOT_FOUND) {
             DebianError::RepositoryIoPath(
@@ -61,11 +79,19 @@ async fn fetch_url(
         }
     })?;
 
-    Ok(Box::pin(
+    let partial = res.status() == StatusCode::PARTIAL_CONTENT;
+
+    let reader: Pin<Box<dyn AsyncRead + Send>> = Box::pin(
         res.bytes_stream()
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e)))
             .into_async_read(),
-    ))
+    );
+
+    match range {
+        // Servers not supporting ranges send the full content.
+        Some(range) if !partial => read_range(reader, range).await,
+        _ => Ok(reader),
+    }
 }
 
 /// Client for a Debian repository served via HTTP.
@@ -83,6 +109,9 @@ pub struct HttpRepositoryClient {
     ///
     /// Contains both distributions and the files pool.
     root_url: Url,
+
+    /// Limits enforced when parsing fetched files.
+    parse_limits: ParseLimits,
 }
 
 impl HttpRepositoryClient {
@@ -109,14 +138,33 @@ impl HttpRepositoryClient {
             root_url.set_path(&format!("{}/", root_url.path()));
         }
 
-        Ok(Self { client, root_url })
+        Ok(Self {
+            client,
+            root_url,
+            parse_limits: ParseLimits::default(),
+        })
+    }
+
+    /// Set the [ParseLimits] enforced when parsing fetched files.
+    ///
+    /// Obtained [ReleaseReader] inherit these limits.
+    pub fn set_parse_limits(&mut self, limits: ParseLimits) {
+        self.parse_limits = limits;
     }
 }
 
 #[async_trait]
 impl DataResolver for HttpRepositoryClient {
     async fn get_path(&self, path: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
-        fetch_url(&self.client, &self.root_url, path).await
+        fetch_url(&self.client, &self.root_url, path, None).await
+    }
+
+    async fn get_path_with_range(
+        &self,
+        path: &str,
+        range: Range<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        fetch_url(&self.client, &self.root_url, path, Some(range)).await
     }
 }
 
@@ -126,6 +174,10 @@ impl RepositoryRootReader for HttpRepositoryClient {
         Ok(self.root_url.clone())
     }
 
+    fn parse_limits(&self) -> ParseLimits {
+        self.parse_limits
+    }
+
     async fn release_reader_with_distribution_path(
         &self,
         path: &str,
@@ -155,6 +207,7 @@ impl RepositoryRootReader for HttpRepositoryClient {
             relative_path: distribution_path,
             release,
             fetch_compression,
+            parse_limits: self.parse_limits,
         }))
     }
 }
@@ -170,12 +223,21 @@ pub struct HttpReleaseClient {
     relative_path: String,
     release: ReleaseFile<'static>,
     fetch_compression: Compression,
+    parse_limits: ParseLimits,
 }
 
 #[async_trait]
 impl DataResolver for HttpReleaseClient {
     async fn get_path(&self, path: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
-        fetch_url(&self.client, &self.root_url, path).await
+        fetch_url(&self.client, &self.root_url, path, None).await
+    }
+
+    async fn get_path_with_range(
+        &self,
+        path: &str,
+        range: Range<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        fetch_url(&self.client, &self.root_url, path, Some(range)).await
     }
 }
 
@@ -200,6 +262,14 @@ impl ReleaseReader for HttpReleaseClient {
     fn set_preferred_compression(&mut self, compression: Compression) {
         self.fetch_compression = compression;
     }
+
+    fn parse_limits(&self) -> ParseLimits {
+        self.parse_limits
+    }
+
+    fn set_parse_limits(&mut self, limits: ParseLimits) {
+        self.parse_limits = limits;
+    }
 }
 
 #[cfg(test)]
@@ -214,6 +284,49 @@ mod test {
 
     const BULLSEYE_URL: &str = "http://snapshot.debian.org/archive/debian/20211120T085721Z";
 
+    #[tokio::test]
+    async fn range_not_satisfiable() -> Result<()> {
+        use std::io::{BufRead, Write};
+
+        // Serve a single request, answering it as if the range were past the end of content.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let server = std::thread::spawn(move || -> std::io::Result<String> {
+            let (stream, _) = listener.accept()?;
+            let mut reader = std::io::BufReader::new(&stream);
+            let mut range = String::new();
+
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((_, value)) = line.split_once("range:") {
+                    range = value.trim().to_string();
+                }
+            }
+
+            (&stream).write_all(
+                b"HTTP/1.1 416 Range Not Satisfiable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            )?;
+
+            Ok(range)
+        });
+
+        let client = HttpRepositoryClient::new(url)?;
+        let mut buf = vec![];
+        futures::AsyncReadExt::read_to_end(
+            &mut client.get_path_with_range("data", 20..30).await?,
+            &mut buf,
+        )
+        .await?;
+        assert!(buf.is_empty());
+        assert_eq!(server.join().unwrap()?, "bytes=20-29");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn bullseye_release() -> Result<()> {
         let root = HttpRepositoryClient::new(BULLSEYE_URL)?;
@@ -0,0 +1,482 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Processing of uploads to an incoming queue.
+
+Debian archives accept uploads in the form of a `.changes` file plus the files it
+references (`.deb`, `.dsc`, source tarballs, etc), all placed in an *incoming* directory.
+Before files are accepted into the archive, the `.changes` file's PGP signature is
+verified against a [Keyring] of trusted keys, subject to a [TrustPolicy], and every
+referenced file is checked against the sizes and digests in the `.changes` file.
+
+[IncomingQueue] performs these checks, yielding an [IncomingUpload] holding verified file
+content. [IncomingUpload::add_to_builder()] then registers the upload's binary and source
+packages with a [RepositoryBuilder]. The returned path mappings should be installed on a
+[crate::io::PathMappingDataResolver] wrapping the incoming directory so
+[RepositoryBuilder::publish()] can find pool content.
+*/
+
+use {
+    crate::{
+        changes::DebianChangesFile,
+        debian_source_control::DebianSourceControlFile,
+        error::{DebianError, Result},
+        io::DataResolver,
+        keyring::{Keyring, SignerIdentity, TrustPolicy},
+        repository::{
+            builder::{InMemoryDebFile, RepositoryBuilder},
+            release::ChecksumType,
+        },
+    },
+    futures::AsyncReadExt,
+    pgp::SignedPublicKey,
+    std::collections::BTreeMap,
+};
+
+const PGP_CLEARTEXT_HEADER: &[u8] = b"-----BEGIN PGP SIGNED MESSAGE-----";
+
+/// A file referenced by a `.changes` file whose content has been verified.
+#[derive(Clone, Debug)]
+pub struct IncomingFile {
+    /// The filename as listed in the `.changes` file.
+    pub filename: String,
+    /// The path of the file relative to the incoming queue's [DataResolver].
+    pub path: String,
+    /// The content of the file.
+    pub data: Vec<u8>,
+}
+
+/// An upload whose `.changes` signature and referenced files have been verified.
+pub struct IncomingUpload {
+    changes_path: String,
+    changes: DebianChangesFile<'static>,
+    signers: Vec<SignerIdentity>,
+    files: Vec<IncomingFile>,
+}
+
+impl IncomingUpload {
+    /// The path of the `.changes` file defining this upload.
+    pub fn changes_path(&self) -> &str {
+        &self.changes_path
+    }
+
+    /// The parsed `.changes` file.
+    pub fn changes(&self) -> &DebianChangesFile<'static> {
+        &self.changes
+    }
+
+    /// The trusted keys that signed the `.changes` file.
+    ///
+    /// Empty if signatures aren't required (see [IncomingQueue::set_require_signature()]).
+    pub fn signers(&self) -> &[SignerIdentity] {
+        &self.signers
+    }
+
+    /// Files in this upload, in the order they are listed in the `.changes` file.
+    pub fn files(&self) -> &[IncomingFile] {
+        &self.files
+    }
+
+    /// Register this upload's packages with a [RepositoryBuilder] in the given component.
+    ///
+    /// `.deb` files are added via [RepositoryBuilder::add_binary_deb()] and `.dsc` files via
+    /// [RepositoryBuilder::add_source_dsc()]. Other files (like `.buildinfo`) are ignored
+    /// unless referenced by a `.dsc`.
+    ///
+    /// Returns `(pool path, incoming path)` pairs for every file that will be placed in the
+    /// pool. These are suitable for [crate::io::PathMappingDataResolver::add_path_map()].
+    pub fn add_to_builder(
+        &self,
+        builder: &mut RepositoryBuilder<'_>,
+        component: &str,
+    ) -> Result<Vec<(String, String)>> {
+        let incoming_paths = self
+            .files
+            .iter()
+            .map(|f| (f.filename.as_str(), f.path.as_str()))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut mappings = vec![];
+
+        for file in &self.files {
+            if file.filename.ends_with(".deb") {
+                let deb = InMemoryDebFile::new(file.filename.clone(), file.data.clone());
+                let pool_path = builder.add_binary_deb(component, &deb)?;
+
+                mappings.push((pool_path, file.path.clone()));
+            } else if file.filename.ends_with(".dsc") {
+                let directory = builder.add_source_dsc(component, &file.filename, &file.data)?;
+
+                mappings.push((
+                    format!("{}/{}", directory, file.filename),
+                    file.path.clone(),
+                ));
+
+                let dsc = if file.data.starts_with(PGP_CLEARTEXT_HEADER) {
+                    DebianSourceControlFile::from_armored_reader(std::io::Cursor::new(&file.data))?
+                } else {
+                    DebianSourceControlFile::from_reader(std::io::Cursor::new(&file.data))?
+                };
+
+                for entry in dsc.files()? {
+                    let entry = entry?;
+
+                    let path = incoming_paths.get(entry.filename).ok_or_else(|| {
+                        DebianError::IncomingFileMissing(entry.filename.to_string())
+                    })?;
+
+                    mappings.push((
+                        format!("{}/{}", directory, entry.filename),
+                        path.to_string(),
+                    ));
+                }
+            }
+        }
+
+        Ok(mappings)
+    }
+}
+
+/// Verifies uploads placed in an incoming directory.
+///
+/// Paths given to [Self::process_changes()] are resolved against a [DataResolver], which is
+/// typically a [crate::repository::filesystem::FilesystemRepositoryReader] rooted at the
+/// incoming directory.
+pub struct IncomingQueue<R> {
+    resolver: R,
+    trusted_keys: Keyring,
+    trust_policy: TrustPolicy,
+    require_signature: bool,
+}
+
+impl<R: DataResolver> IncomingQueue<R> {
+    /// Construct a new instance reading uploads from a [DataResolver].
+    ///
+    /// Signatures are required by default. But no keys are trusted, so at least 1 key
+    /// needs to be registered via [Self::add_trusted_key()] for uploads to be accepted.
+    pub fn new(resolver: R) -> Self {
+        Self {
+            resolver,
+            trusted_keys: Keyring::default(),
+            trust_policy: TrustPolicy::default(),
+            require_signature: true,
+        }
+    }
+
+    /// Register a public key whose signatures on `.changes` files are trusted.
+    ///
+    /// Signatures made by the key's subkeys are also trusted.
+    pub fn add_trusted_key(&mut self, key: SignedPublicKey) {
+        self.trusted_keys.add_key(key);
+    }
+
+    /// Trust the keys in a keyring, in addition to already trusted keys.
+    pub fn add_trusted_keyring(&mut self, keyring: Keyring) {
+        self.trusted_keys.extend(keyring);
+    }
+
+    /// Set the policy for trusting signatures of `.changes` files.
+    ///
+    /// By default, any cryptographically valid signature from a trusted key is accepted.
+    /// Uploads not satisfying the policy fail processing with the error of
+    /// [TrustPolicy::evaluate()].
+    pub fn set_trust_policy(&mut self, policy: TrustPolicy) {
+        self.trust_policy = policy;
+    }
+
+    /// Set whether `.changes` files must carry a signature from a trusted key.
+    ///
+    /// Disabling this skips signature verification entirely. Digests of referenced
+    /// files are always verified.
+    pub fn set_require_signature(&mut self, value: bool) {
+        self.require_signature = value;
+    }
+
+    async fn read_path(&self, path: &str) -> Result<Vec<u8>> {
+        let mut reader = self.resolver.get_path(path).await?;
+
+        let mut data = vec![];
+        reader
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| DebianError::RepositoryIoPath(path.to_string(), e))?;
+
+        Ok(data)
+    }
+
+    fn verify_signature(
+        &self,
+        path: &str,
+        changes: &DebianChangesFile,
+    ) -> Result<Vec<SignerIdentity>> {
+        if !self.require_signature {
+            return Ok(vec![]);
+        }
+
+        match changes.verify_signatures(&self.trusted_keys, &self.trust_policy) {
+            Ok(signers) => Ok(signers),
+            Err(DebianError::ReleaseNoSignatures) => {
+                Err(DebianError::IncomingUnsigned(path.to_string()))
+            }
+            Err(DebianError::PgpSignatureUntrusted) => {
+                Err(DebianError::IncomingUntrusted(path.to_string()))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Verify a `.changes` file and the files it references.
+    ///
+    /// The `.changes` file must be signed by a trusted key (unless disabled via
+    /// [Self::set_require_signature()]). Referenced files are resolved relative to the
+    /// directory of the `.changes` file and their sizes and digests are verified against
+    /// every checksum field present in the `.changes` file.
+    pub async fn process_changes(&self, changes_path: &str) -> Result<IncomingUpload> {
+        let data = self.read_path(changes_path).await?;
+
+        let changes = if data.starts_with(PGP_CLEARTEXT_HEADER) {
+            DebianChangesFile::from_armored_reader(std::io::Cursor::new(&data))?
+        } else {
+            DebianChangesFile::from_reader(std::io::Cursor::new(&data))?
+        };
+
+        let signers = self.verify_signature(changes_path, &changes)?;
+
+        let directory = match changes_path.rsplit_once('/') {
+            Some((directory, _)) => format!("{}/", directory),
+            None => "".to_string(),
+        };
+
+        let mut files = vec![];
+
+        for entry in changes.files()? {
+            let entry = entry?;
+
+            if entry.filename.contains('/') || entry.filename.starts_with('.') {
+                return Err(DebianError::IncomingIllegalFilename(
+                    entry.filename.to_string(),
+                ));
+            }
+
+            let path = format!("{}{}", directory, entry.filename);
+            let data = self.read_path(&path).await?;
+
+            files.push(IncomingFile {
+                filename: entry.filename.to_string(),
+                path,
+                data,
+            });
+        }
+
+        for checksum in ChecksumType::preferred_order() {
+            let entries = if let Some(entries) = changes.iter_files(checksum) {
+                entries
+            } else {
+                continue;
+            };
+
+            for entry in entries {
+                let entry = entry?;
+
+                let file = files
+                    .iter()
+                    .find(|f| f.filename == entry.filename)
+                    .ok_or_else(|| DebianError::IncomingFileMissing(entry.filename.to_string()))?;
+
+                let mut h = entry.digest.new_hasher();
+                h.update(&file.data);
+
+                if file.data.len() as u64 != entry.size
+                    || h.finish().as_slice() != entry.digest.digest_bytes()
+                {
+                    return Err(DebianError::IncomingFileIntegrity(file.path.clone()));
+                }
+            }
+        }
+
+        Ok(IncomingUpload {
+            changes_path: changes_path.to_string(),
+            changes,
+            signers,
+            files,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            control::{ControlFile, ControlParagraph},
+            deb::builder::DebBuilder,
+            io::PathMappingDataResolver,
+            keyring::fingerprint_hex,
+            repository::{
+                builder::{NO_PROGRESS_CB, NO_SIGNING_KEY},
+                filesystem::{FilesystemRepositoryReader, FilesystemRepositoryWriter},
+            },
+            signing_key::{create_self_signed_key, signing_secret_key_params_builder},
+        },
+        pgp::crypto::hash::HashAlgorithm,
+        pgp_cleartext::cleartext_sign,
+        tempfile::TempDir,
+    };
+
+    fn file_line(checksum: ChecksumType, filename: &str, data: &[u8], files: bool) -> String {
+        let mut h = checksum.new_hasher();
+        h.update(data);
+
+        if files {
+            format!(
+                " {} {} misc optional {}",
+                hex::encode(h.finish()),
+                data.len(),
+                filename
+            )
+        } else {
+            format!(" {} {} {}", hex::encode(h.finish()), data.len(), filename)
+        }
+    }
+
+    fn file_lines<'a>(
+        checksum: ChecksumType,
+        files: impl Iterator<Item = &'a (&'a str, Vec<u8>)>,
+    ) -> String {
+        files
+            .map(|(filename, data)| {
+                file_line(checksum, filename, data, checksum == ChecksumType::Md5)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[tokio::test]
+    async fn process_and_publish() -> Result<()> {
+        let td = TempDir::new()?;
+        let incoming_dir = td.path().join("incoming");
+        let repo_dir = td.path().join("repo");
+        std::fs::create_dir_all(incoming_dir.join("queue"))?;
+
+        let mut control_para = ControlParagraph::default();
+        control_para.set_field_from_string("Package".into(), "hello".into());
+        control_para.set_field_from_string("Version".into(), "1.0-1".into());
+        control_para.set_field_from_string("Architecture".into(), "amd64".into());
+        let mut control = ControlFile::default();
+        control.add_paragraph(control_para);
+        let mut deb = vec![];
+        DebBuilder::new(control).write(&mut deb)?;
+
+        let tarball = b"tarball".to_vec();
+        let dsc = format!(
+            "Format: 3.0 (native)\nSource: hello\nBinary: hello\nArchitecture: any\nVersion: 1.0-1\nMaintainer: Jane Doe <jane@example.com>\nStandards-Version: 4.6.0\nChecksums-Sha256:\n{}\nFiles:\n{}\n",
+            file_line(ChecksumType::Sha256, "hello_1.0-1.tar.xz", &tarball, false),
+            file_line(ChecksumType::Md5, "hello_1.0-1.tar.xz", &tarball, false),
+        )
+        .into_bytes();
+
+        let files = vec![
+            ("hello_1.0-1.dsc", dsc),
+            ("hello_1.0-1.tar.xz", tarball),
+            ("hello_1.0-1_amd64.deb", deb),
+        ];
+        for (filename, data) in &files {
+            std::fs::write(incoming_dir.join("queue").join(filename), data)?;
+        }
+
+        let changes = format!(
+            "Format: 1.8\nSource: hello\nBinary: hello\nArchitecture: source amd64\nVersion: 1.0-1\nDistribution: unstable\nMaintainer: Jane Doe <jane@example.com>\nChecksums-Sha256:\n{}\nFiles:\n{}\n",
+            file_lines(ChecksumType::Sha256, files.iter()),
+            file_lines(ChecksumType::Md5, files.iter()),
+        );
+
+        let (key, public_key) = create_self_signed_key(
+            signing_secret_key_params_builder("uploader@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?;
+        let signed = cleartext_sign(
+            &key,
+            String::new,
+            HashAlgorithm::SHA2_256,
+            std::io::Cursor::new(changes.as_bytes()),
+        )?;
+        std::fs::write(incoming_dir.join("queue/hello_1.0-1.changes"), &signed)?;
+
+        let mut queue = IncomingQueue::new(FilesystemRepositoryReader::new(&incoming_dir));
+
+        assert!(matches!(
+            queue.process_changes("queue/hello_1.0-1.changes").await,
+            Err(DebianError::IncomingUntrusted(_))
+        ));
+
+        queue.add_trusted_key(public_key.clone());
+        queue.set_trust_policy(TrustPolicy {
+            min_signatures: 2,
+            ..TrustPolicy::strict()
+        });
+        assert!(matches!(
+            queue.process_changes("queue/hello_1.0-1.changes").await,
+            Err(DebianError::PgpSignatureCountInsufficient(1, 2))
+        ));
+
+        queue.set_trust_policy(TrustPolicy::strict());
+        let upload = queue.process_changes("queue/hello_1.0-1.changes").await?;
+        assert_eq!(
+            upload.signers()[0].fingerprint,
+            fingerprint_hex(&public_key)
+        );
+        assert_eq!(upload.files().len(), 3);
+        assert_eq!(upload.changes().source()?, "hello");
+
+        let mut builder = RepositoryBuilder::new_recommended(
+            ["amd64"].into_iter(),
+            ["main"].into_iter(),
+            "unstable",
+            "sid",
+        );
+        builder.set_acquire_by_hash(false);
+        let mappings = upload.add_to_builder(&mut builder, "main")?;
+        assert_eq!(mappings.len(), 3);
+
+        let mut resolver =
+            PathMappingDataResolver::new(FilesystemRepositoryReader::new(&incoming_dir));
+        for (pool_path, incoming_path) in mappings {
+            resolver.add_path_map(pool_path, incoming_path);
+        }
+
+        builder
+            .publish(
+                &FilesystemRepositoryWriter::new(&repo_dir),
+                &resolver,
+                "dists/unstable",
+                1,
+                &NO_PROGRESS_CB,
+                NO_SIGNING_KEY,
+            )
+            .await?;
+
+        assert!(repo_dir
+            .join("pool/main/h/hello/hello_1.0-1_amd64.deb")
+            .exists());
+        assert!(repo_dir.join("pool/main/h/hello/hello_1.0-1.dsc").exists());
+        assert!(repo_dir
+            .join("pool/main/h/hello/hello_1.0-1.tar.xz")
+            .exists());
+
+        let sources = std::fs::read_to_string(repo_dir.join("dists/unstable/main/source/Sources"))?;
+        assert!(sources.starts_with("Package: hello\n"));
+        assert!(sources.contains("Directory: pool/main/h/hello\n"));
+
+        // Tampering with a referenced file is detected.
+        std::fs::write(incoming_dir.join("queue/hello_1.0-1.tar.xz"), b"tampered")?;
+        assert!(matches!(
+            queue.process_changes("queue/hello_1.0-1.changes").await,
+            Err(DebianError::IncomingFileIntegrity(_))
+        ));
+
+        Ok(())
+    }
+}
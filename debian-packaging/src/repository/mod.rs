@@ -63,6 +63,15 @@ repositories, such as `[In]Release` files.
 
 The [builder] module contains functionality for creating/publishing
 repositories.
+
+The [audit] module defines an append-only log of paths written by publish
+and copy operations.
+
+The [incoming] module verifies uploads described by `.changes` files and feeds them
+into a [builder::RepositoryBuilder].
+
+The [signing] module contains functionality for signing `[In]Release` files,
+including re-signing an existing distribution with a new key.
 */
 
 use std::fmt::Formatter;
@@ -70,7 +79,7 @@ use {
     crate::{
         binary_package_control::BinaryPackageControlFile,
         binary_package_list::BinaryPackageList,
-        control::ControlParagraphAsyncReader,
+        control::{ControlParagraphAsyncReader, ControlParagraphSliceReader, ParseLimits},
         deb::reader::BinaryPackageReader,
         debian_source_control::{DebianSourceControlFile, DebianSourceControlFileFetch},
         debian_source_package_list::DebianSourcePackageList,
@@ -80,27 +89,67 @@ use {
             contents::{ContentsFile, ContentsFileAsyncReader},
             release::{
                 ChecksumType, ClassifiedReleaseFileEntry, ContentsFileEntry, PackagesFileEntry,
-                ReleaseFile, SourcesFileEntry,
+                ReleaseFile, ReleaseFileEntry, SourcesFileEntry,
             },
         },
     },
     async_trait::async_trait,
     futures::{AsyncRead, AsyncReadExt, StreamExt, TryStreamExt},
-    std::{borrow::Cow, collections::HashMap, ops::Deref, pin::Pin, str::FromStr},
+    serde::{Deserialize, Serialize},
+    std::{borrow::Cow, collections::HashMap, ops::Deref, path::Path, pin::Pin, str::FromStr},
 };
 
+pub mod audit;
 pub mod builder;
 pub mod contents;
 pub mod copier;
 pub mod filesystem;
 #[cfg(feature = "http")]
 pub mod http;
+pub mod incoming;
 pub mod proxy_writer;
 pub mod release;
 #[cfg(feature = "s3")]
 pub mod s3;
+pub mod signing;
 pub mod sink_writer;
 
+/// Describe a path relative to a repository URL, for use in diagnostics.
+fn describe_path(base: Result<url::Url>, path: &str) -> String {
+    base.ok()
+        .and_then(|url| url.join(path).ok())
+        .map_or_else(|| path.to_string(), |url| url.to_string())
+}
+
+/// The path to fetch an index file from, honoring the release's `Acquire-By-Hash` field.
+fn index_fetch_path(release: &ReleaseFile<'_>, entry: &ReleaseFileEntry<'_>) -> String {
+    if release.acquire_by_hash().unwrap_or_default() {
+        entry.by_hash_path()
+    } else {
+        entry.path.to_string()
+    }
+}
+
+/// Run a blocking function on a dedicated thread, resolving to its result.
+///
+/// This keeps CPU intensive work from blocking the async executor. Panics are propagated.
+async fn spawn_blocking<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = futures::channel::oneshot::channel();
+
+    std::thread::spawn(move || {
+        let _ = tx.send(std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)));
+    });
+
+    match rx.await.expect("thread always sends a result") {
+        Ok(res) => res,
+        Err(e) => std::panic::resume_unwind(e),
+    }
+}
+
 /// Describes how to fetch a binary package from a repository.
 #[derive(Clone, Debug)]
 pub struct BinaryPackageFetch<'a> {
@@ -163,6 +212,14 @@ pub trait RepositoryRootReader: DataResolver + Sync {
         path: &str,
     ) -> Result<Box<dyn ReleaseReader>>;
 
+    /// Obtain the [ParseLimits] enforced when parsing fetched files.
+    ///
+    /// Obtained [ReleaseReader] inherit these limits. The default implementation returns
+    /// [ParseLimits::default()].
+    fn parse_limits(&self) -> ParseLimits {
+        ParseLimits::default()
+    }
+
     /// Fetch and parse an `InRelease` file at the relative path specified.
     ///
     /// `path` is typically a value like `dists/<distribution>/InRelease`. e.g.
@@ -170,14 +227,14 @@ pub trait RepositoryRootReader: DataResolver + Sync {
     ///
     /// The default implementation of this trait should be sufficient for most types.
     async fn fetch_inrelease(&self, path: &str) -> Result<ReleaseFile<'static>> {
-        let mut reader = self.get_path(path).await?;
+        let limits = self.parse_limits();
+        let reader = self.get_path(path).await?;
 
         let mut data = vec![];
-        reader.read_to_end(&mut data).await?;
+        limits.read_to_end_async(reader, &mut data).await?;
 
-        Ok(ReleaseFile::from_armored_reader(std::io::Cursor::new(
-            data,
-        ))?)
+        ReleaseFile::from_armored_reader_with_limits(std::io::Cursor::new(data), limits)
+            .map_err(|e| e.with_parse_source(describe_path(self.url(), path)))
     }
 
     /// Fetch and parse an `Release` file at the relative path specified.
@@ -187,12 +244,14 @@ pub trait RepositoryRootReader: DataResolver + Sync {
     ///
     /// The default implementation of this trait should be sufficient for most types.
     async fn fetch_release(&self, path: &str) -> Result<ReleaseFile<'static>> {
-        let mut reader = self.get_path(path).await?;
+        let limits = self.parse_limits();
+        let reader = self.get_path(path).await?;
 
         let mut data = vec![];
-        reader.read_to_end(&mut data).await?;
+        limits.read_to_end_async(reader, &mut data).await?;
 
-        Ok(ReleaseFile::from_reader(std::io::Cursor::new(data))?)
+        ReleaseFile::from_reader_with_limits(std::io::Cursor::new(data), limits)
+            .map_err(|e| e.with_parse_source(describe_path(self.url(), path)))
     }
     /// Fetch and parse either an `InRelease` or `Release` file at the relative path specified.
     ///
@@ -279,12 +338,9 @@ pub trait ReleaseReader: DataResolver + Sync {
     fn retrieve_checksum(&self) -> Result<ChecksumType> {
         let release = self.release_file();
 
-        let checksum = &[ChecksumType::Sha256, ChecksumType::Sha1, ChecksumType::Md5]
-            .iter()
+        ChecksumType::preferred_order()
             .find(|variant| release.field(variant.field_name()).is_some())
-            .ok_or(DebianError::RepositoryReadReleaseNoKnownChecksum)?;
-
-        Ok(**checksum)
+            .ok_or(DebianError::RepositoryReadReleaseNoKnownChecksum)
     }
 
     /// Obtain the preferred compression format to retrieve index files in.
@@ -297,6 +353,15 @@ pub trait ReleaseReader: DataResolver + Sync {
     /// format to prefer.
     fn set_preferred_compression(&mut self, compression: Compression);
 
+    /// Obtain the [ParseLimits] enforced when parsing fetched index files.
+    fn parse_limits(&self) -> ParseLimits;
+
+    /// Set the [ParseLimits] enforced when parsing fetched index files.
+    ///
+    /// Index files are fetched from repositories that may be malicious. The limits bound
+    /// the resources used to parse them.
+    fn set_parse_limits(&mut self, limits: ParseLimits);
+
     /// Obtain [ClassifiedReleaseFileEntry] within the parsed `Release` file.
     fn classified_indices_entries(&self) -> Result<Vec<ClassifiedReleaseFileEntry<'_>>> {
         self.release_file()
@@ -469,13 +534,7 @@ pub trait ReleaseReader: DataResolver + Sync {
         &'slf self,
         entry: &'entry PackagesFileEntry<'slf>,
     ) -> Result<BinaryPackageList<'static>> {
-        let release = self.release_file();
-
-        let path = if release.acquire_by_hash().unwrap_or_default() {
-            entry.by_hash_path()
-        } else {
-            entry.path.to_string()
-        };
+        let path = index_fetch_path(self.release_file(), entry);
 
         let mut reader = ControlParagraphAsyncReader::new(futures::io::BufReader::new(
             self.get_path_decoded_with_digest_verification(
@@ -486,12 +545,70 @@ pub trait ReleaseReader: DataResolver + Sync {
             )
             .await?,
         ));
+        reader.set_limits(self.parse_limits());
+        reader.set_source(describe_path(self.url(), &path));
+        reader.set_intern(true);
 
         let mut res = BinaryPackageList::default();
 
         while let Some(paragraph) = reader.read_paragraph().await? {
             res.push(BinaryPackageControlFile::from(paragraph));
         }
+        res.shrink_to_fit();
+
+        Ok(res)
+    }
+
+    /// Fetch and parse a `Packages` file described by a [PackagesFileEntry] using up to
+    /// `threads` threads.
+    ///
+    /// The decompressed file is held in memory, bounded by [ParseLimits::max_total_size], and
+    /// parsed off the async executor with
+    /// [ControlParagraphSliceReader::read_all_parallel()]. This substantially reduces wall
+    /// time for large files on machines with many cores. With a single thread, this
+    /// streams the file like [Self::resolve_packages_from_entry()].
+    async fn resolve_packages_from_entry_parallel<'entry, 'slf: 'entry>(
+        &'slf self,
+        entry: &'entry PackagesFileEntry<'slf>,
+        threads: usize,
+    ) -> Result<BinaryPackageList<'static>> {
+        if threads <= 1 {
+            return self.resolve_packages_from_entry(entry).await;
+        }
+
+        let path = index_fetch_path(self.release_file(), entry);
+
+        let limits = self.parse_limits();
+        let source = describe_path(self.url(), &path);
+
+        let mut data = String::new();
+        limits
+            .read_to_string_async(
+                self.get_path_decoded_with_digest_verification(
+                    &path,
+                    entry.compression,
+                    entry.size,
+                    entry.digest.clone(),
+                )
+                .await?,
+                &mut data,
+            )
+            .await?;
+
+        // Parsing is CPU bound, so it happens off the executor.
+        let paragraphs = spawn_blocking(move || {
+            let mut reader = ControlParagraphSliceReader::new(&data);
+            reader.set_limits(limits);
+            reader.set_source(source);
+
+            reader.read_all_parallel(threads, |paragraph| {
+                BinaryPackageControlFile::from(paragraph.into_interned())
+            })
+        })
+        .await?;
+
+        let mut res = BinaryPackageList::default();
+        res.extend(paragraphs);
 
         Ok(res)
     }
@@ -508,6 +625,23 @@ pub trait ReleaseReader: DataResolver + Sync {
         self.resolve_packages_from_entry(&entry).await
     }
 
+    /// Resolve packages given parameters to resolve a `Packages` file, parsing it with up
+    /// to `threads` threads.
+    ///
+    /// See [Self::resolve_packages_from_entry_parallel()].
+    async fn resolve_packages_parallel(
+        &self,
+        component: &str,
+        arch: &str,
+        is_installer: bool,
+        threads: usize,
+    ) -> Result<BinaryPackageList<'static>> {
+        let entry = self.packages_entry(component, arch, is_installer)?;
+
+        self.resolve_packages_from_entry_parallel(&entry, threads)
+            .await
+    }
+
     /// Retrieve fetch instructions for binary packages.
     ///
     /// The caller can specify a filter function to choose which packages to retrieve.
@@ -594,13 +728,7 @@ pub trait ReleaseReader: DataResolver + Sync {
         &'slf self,
         entry: &'entry SourcesFileEntry<'slf>,
     ) -> Result<DebianSourcePackageList<'static>> {
-        let release = self.release_file();
-
-        let path = if release.acquire_by_hash().unwrap_or_default() {
-            entry.by_hash_path()
-        } else {
-            entry.path.to_string()
-        };
+        let path = index_fetch_path(self.release_file(), entry);
 
         let mut reader = ControlParagraphAsyncReader::new(futures::io::BufReader::new(
             self.get_path_decoded_with_digest_verification(
@@ -611,12 +739,66 @@ pub trait ReleaseReader: DataResolver + Sync {
             )
             .await?,
         ));
+        reader.set_limits(self.parse_limits());
+        reader.set_source(describe_path(self.url(), &path));
+        reader.set_intern(true);
 
         let mut res = DebianSourcePackageList::default();
 
         while let Some(paragraph) = reader.read_paragraph().await? {
             res.push(paragraph.into());
         }
+        res.shrink_to_fit();
+
+        Ok(res)
+    }
+
+    /// Fetch a `Sources` file and parse source package entries inside using up to `threads`
+    /// threads.
+    ///
+    /// See [Self::resolve_packages_from_entry_parallel()] for how parsing is parallelized.
+    async fn resolve_sources_from_entry_parallel<'entry, 'slf: 'entry>(
+        &'slf self,
+        entry: &'entry SourcesFileEntry<'slf>,
+        threads: usize,
+    ) -> Result<DebianSourcePackageList<'static>> {
+        if threads <= 1 {
+            return self.resolve_sources_from_entry(entry).await;
+        }
+
+        let path = index_fetch_path(self.release_file(), entry);
+
+        let limits = self.parse_limits();
+        let source = describe_path(self.url(), &path);
+
+        let mut data = String::new();
+        limits
+            .read_to_string_async(
+                self.get_path_decoded_with_digest_verification(
+                    &path,
+                    entry.compression,
+                    entry.size,
+                    entry.digest.clone(),
+                )
+                .await?,
+                &mut data,
+            )
+            .await?;
+
+        // Parsing is CPU bound, so it happens off the executor.
+        let paragraphs = spawn_blocking(move || {
+            let mut reader = ControlParagraphSliceReader::new(&data);
+            reader.set_limits(limits);
+            reader.set_source(source);
+
+            reader.read_all_parallel(threads, |paragraph| {
+                DebianSourceControlFile::from(paragraph.into_interned())
+            })
+        })
+        .await?;
+
+        let mut res = DebianSourcePackageList::default();
+        res.extend(paragraphs);
 
         Ok(res)
     }
@@ -631,6 +813,21 @@ pub trait ReleaseReader: DataResolver + Sync {
         self.resolve_sources_from_entry(&entry).await
     }
 
+    /// Fetch a `Sources` file for the given component and parse it with up to `threads`
+    /// threads.
+    ///
+    /// See [Self::resolve_sources_from_entry_parallel()].
+    async fn resolve_sources_parallel(
+        &self,
+        component: &str,
+        threads: usize,
+    ) -> Result<DebianSourcePackageList<'static>> {
+        let entry = self.sources_entry(component)?;
+
+        self.resolve_sources_from_entry_parallel(&entry, threads)
+            .await
+    }
+
     /// Resolves [SourcePackageFetch] for describing files to fetch for source packages.
     ///
     /// The caller specifies filter functions to choose which source packages' files to
@@ -728,14 +925,8 @@ pub trait ReleaseReader: DataResolver + Sync {
         architecture: &str,
         is_installer: bool,
     ) -> Result<ContentsFile> {
-        let release = self.release_file();
         let entry = self.contents_entry(component, architecture, is_installer)?;
-
-        let path = if release.acquire_by_hash().unwrap_or_default() {
-            entry.by_hash_path()
-        } else {
-            entry.path.to_string()
-        };
+        let path = index_fetch_path(self.release_file(), &entry);
 
         let reader = self
             .get_path_decoded_with_digest_verification(
@@ -747,6 +938,7 @@ pub trait ReleaseReader: DataResolver + Sync {
             .await?;
 
         let mut reader = ContentsFileAsyncReader::new(futures::io::BufReader::new(reader));
+        reader.set_limits(self.parse_limits());
         reader.read_all().await?;
 
         let (contents, reader) = reader.consume();
@@ -801,7 +993,8 @@ impl<'a> std::fmt::Display for RepositoryPathVerification<'a> {
 }
 
 /// A phase during a repository copy operation.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CopyPhase {
     BinaryPackages,
     InstallerBinaryPackages,
@@ -873,6 +1066,27 @@ pub enum PublishEvent {
     /// A path copy was a no-op.
     PathCopyNoop(String),
 
+    /// A path not referenced by the repository was deleted.
+    PathDeleted(String),
+
+    /// A path copy was skipped because a previous copy completed it.
+    PathCopyPreviouslyCompleted(String),
+
+    /// The release file of a distribution at the given path was verified by the given key.
+    ReleaseSignatureVerified(String, String),
+
+    /// A distribution being synced is unchanged and won't be copied.
+    DistributionUnchanged(String),
+
+    /// A copy phase was resolved to copy N paths totaling N bytes.
+    CopyPlanned(CopyPhase, usize, u64),
+
+    /// A failed path copy is being retried for the Nth time.
+    PathCopyRetry(String, usize),
+
+    /// A path copy failed with the given error and was skipped.
+    PathCopySkipped(String, String),
+
     /// Begin a write sequence where we will write N total bytes.
     WriteSequenceBeginWithTotalBytes(u64),
 
@@ -932,6 +1146,35 @@ impl std::fmt::Display for PublishEvent {
             Self::PathCopyNoop(path) => {
                 write!(f, "copy of {} was a no-op", path)
             }
+            Self::PathDeleted(path) => {
+                write!(f, "deleted unreferenced path {}", path)
+            }
+            Self::PathCopyPreviouslyCompleted(path) => {
+                write!(f, "copy of {} skipped; previously completed", path)
+            }
+            Self::ReleaseSignatureVerified(path, key_id) => {
+                write!(
+                    f,
+                    "release file of {} verified by trusted key {}",
+                    path, key_id
+                )
+            }
+            Self::DistributionUnchanged(path) => {
+                write!(f, "distribution {} is unchanged; nothing to copy", path)
+            }
+            Self::PathCopyRetry(path, attempt) => {
+                write!(f, "retrying copy of {} (attempt {})", path, attempt)
+            }
+            Self::PathCopySkipped(path, error) => {
+                write!(f, "skipped copy of {} after error: {}", path, error)
+            }
+            Self::CopyPlanned(phase, count, size) => {
+                write!(
+                    f,
+                    "{} will copy {} paths totaling {} bytes",
+                    phase, count, size
+                )
+            }
             Self::WriteSequenceBeginWithTotalBytes(_)
             | Self::WriteSequenceProgressBytes(_)
             | Self::WriteSequenceFinished => Ok(()),
@@ -1000,6 +1243,18 @@ pub trait RepositoryWriter: Sync {
         expected_content: Option<(u64, ContentDigest)>,
     ) -> Result<RepositoryPathVerification<'path>>;
 
+    /// Obtain the size of the content at a path.
+    ///
+    /// This allows content whose size isn't known upfront to be verified via
+    /// [Self::verify_path()].
+    ///
+    /// Returns [None] if the path doesn't exist or the size can't be determined cheaply.
+    /// The default implementation returns [None].
+    async fn path_size(&self, path: &str) -> Result<Option<u64>> {
+        let _ = path;
+        Ok(None)
+    }
+
     /// Write data to a given path.
     ///
     /// The data to write is provided by an [AsyncRead] reader.
@@ -1015,7 +1270,8 @@ pub trait RepositoryWriter: Sync {
     /// root.
     ///
     /// The default implementation verifies the integrity of the destination and will no-op if
-    /// the desired content is already present.
+    /// the desired content is already present. Local copies made with
+    /// [Self::copy_local_path()] are verified afterwards and deleted if they don't match.
     ///
     /// Implementations of this trait may have a custom implementation that changes semantics.
     /// For example, a writer could operate in a dry-run mode where it doesn't actually attempt
@@ -1059,6 +1315,39 @@ pub trait RepositoryWriter: Sync {
             ));
         }
 
+        if let Some(local_path) = reader.local_path(source_path.as_ref()) {
+            if let Some(bytes_written) = self
+                .copy_local_path(&local_path, dest_path.as_ref())
+                .await?
+            {
+                // Content didn't pass through a verifying reader. So verify the result.
+                if let Some(expected_content) = expected_content {
+                    let verification = self
+                        .verify_path(dest_path.as_ref(), Some(expected_content))
+                        .await?;
+
+                    if !matches!(
+                        verification.state,
+                        RepositoryPathVerificationState::ExistsIntegrityVerified
+                    ) {
+                        // Don't leave corrupt content at the destination. The mismatch is more
+                        // relevant than a failure to clean up.
+                        let _ = self.delete_path(dest_path.as_ref()).await;
+
+                        return Err(DebianError::RepositoryIoPath(
+                            source_path.to_string(),
+                            std::io::Error::other("digest mismatch of copied content"),
+                        ));
+                    }
+                }
+
+                return Ok(RepositoryWriteOperation::PathWritten(RepositoryWrite {
+                    path: dest_path,
+                    bytes_written,
+                }));
+            }
+        }
+
         let reader = if let Some((size, digest)) = expected_content {
             reader
                 .get_path_with_digest_verification(source_path.as_ref(), size, digest)
@@ -1071,6 +1360,44 @@ pub trait RepositoryWriter: Sync {
 
         Ok(RepositoryWriteOperation::PathWritten(write))
     }
+
+    /// Copy a local file to a given path.
+    ///
+    /// [Self::copy_from()] calls this when the source reader stores content in local files,
+    /// as advertised by [DataResolver::local_path()]. Implementations can use filesystem
+    /// primitives instead of streaming content through userspace buffers.
+    ///
+    /// Returns the number of bytes copied, or [None] if local copies aren't supported and
+    /// content should be streamed. The default implementation returns [None].
+    async fn copy_local_path(&self, source: &Path, dest_path: &str) -> Result<Option<u64>> {
+        let _ = (source, dest_path);
+        Ok(None)
+    }
+
+    /// List all paths under a directory prefix.
+    ///
+    /// Returned paths are relative to the repository root and are recursively enumerated.
+    /// A missing prefix yields an empty list.
+    ///
+    /// The default implementation returns an error.
+    async fn list_paths(&self, prefix: &str) -> Result<Vec<String>> {
+        let _ = prefix;
+        Err(DebianError::RepositoryWriterOperationUnsupported(
+            "list_paths",
+        ))
+    }
+
+    /// Delete a path.
+    ///
+    /// Deleting a path that doesn't exist is not an error.
+    ///
+    /// The default implementation returns an error.
+    async fn delete_path(&self, path: &str) -> Result<()> {
+        let _ = path;
+        Err(DebianError::RepositoryWriterOperationUnsupported(
+            "delete_path",
+        ))
+    }
 }
 
 /// Construct a [RepositoryRootReader] from a string/URL.
@@ -1132,19 +1459,7 @@ pub async fn writer_from_str(s: impl ToString) -> Result<Box<dyn RepositoryWrite
                 Ok(Box::new(writer))
             }
             #[cfg(feature = "s3")]
-            "s3" => {
-                let path = url.path();
-
-                if let Some((bucket, prefix)) = path.trim_matches('/').split_once('/') {
-                    let region = s3::get_bucket_region(bucket).await?;
-
-                    Ok(Box::new(s3::S3Writer::new(region, bucket, Some(prefix))))
-                } else {
-                    let region = s3::get_bucket_region(path).await?;
-
-                    Ok(Box::new(s3::S3Writer::new(region, path, None)))
-                }
-            }
+            "s3" => Ok(Box::new(s3::S3Writer::from_url(&url).await?)),
             _ => Err(DebianError::RepositoryWriterUnrecognizedUrl(s)),
         }
     } else {
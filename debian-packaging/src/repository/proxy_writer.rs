@@ -92,6 +92,10 @@ impl<W: RepositoryWriter + Send> RepositoryWriter for ProxyWriter<W> {
         }
     }
 
+    async fn path_size(&self, path: &str) -> Result<Option<u64>> {
+        self.inner.path_size(path).await
+    }
+
     async fn write_path<'path, 'reader>(
         &self,
         path: Cow<'path, str>,
@@ -114,4 +118,12 @@ impl<W: RepositoryWriter + Send> RepositoryWriter for ProxyWriter<W> {
 
         Ok(res)
     }
+
+    async fn list_paths(&self, prefix: &str) -> Result<Vec<String>> {
+        self.inner.list_paths(prefix).await
+    }
+
+    async fn delete_path(&self, path: &str) -> Result<()> {
+        self.inner.delete_path(path).await
+    }
 }
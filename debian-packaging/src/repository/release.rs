@@ -26,16 +26,17 @@ classify each entry as the strongest type possible.
 
 use {
     crate::{
-        control::{ControlParagraph, ControlParagraphReader},
+        control::{ControlParagraph, ControlParagraphReader, ParseLimits},
         error::{DebianError, Result},
         io::ContentDigest,
-        repository::Compression,
+        keyring::{Keyring, SignerIdentity, TrustPolicy},
+        repository::{signing::canonicalize_cleartext_signed_document, Compression},
     },
     chrono::{DateTime, Utc},
     pgp_cleartext::CleartextHasher,
     std::{
         borrow::Cow,
-        io::BufRead,
+        io::{BufRead, Read},
         ops::{Deref, DerefMut},
         str::FromStr,
     },
@@ -46,6 +47,11 @@ pub const DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S %z";
 
 /// Checksum type / digest mechanism used in a release file.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum ChecksumType {
     /// MD5.
     Md5,
@@ -55,12 +61,23 @@ pub enum ChecksumType {
 
     /// SHA-256.
     Sha256,
+
+    /// SHA-512.
+    Sha512,
 }
 
 impl ChecksumType {
+    /// All known variants.
+    ///
+    /// Code handling every digest flavor should iterate this instead of naming variants,
+    /// so new flavors only need to be described by the methods on this type.
+    pub fn all() -> impl Iterator<Item = ChecksumType> {
+        [Self::Md5, Self::Sha1, Self::Sha256, Self::Sha512].into_iter()
+    }
+
     /// Emit variants in their preferred usage order.
     pub fn preferred_order() -> impl Iterator<Item = ChecksumType> {
-        [Self::Sha256, Self::Sha1, Self::Md5].into_iter()
+        [Self::Sha512, Self::Sha256, Self::Sha1, Self::Md5].into_iter()
     }
 
     /// Name of the control field in `Release` files holding this variant type.
@@ -69,6 +86,27 @@ impl ChecksumType {
             Self::Md5 => "MD5Sum",
             Self::Sha1 => "SHA1",
             Self::Sha256 => "SHA256",
+            Self::Sha512 => "SHA512",
+        }
+    }
+
+    /// Name of the control field in `.dsc`, `.changes`, and `Sources` files holding this variant type.
+    pub fn source_field_name(&self) -> &'static str {
+        match self {
+            Self::Md5 => "Files",
+            Self::Sha1 => "Checksums-Sha1",
+            Self::Sha256 => "Checksums-Sha256",
+            Self::Sha512 => "Checksums-Sha512",
+        }
+    }
+
+    /// Filename of installer files listing digests of this variant type.
+    pub fn sums_filename(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5SUMS",
+            Self::Sha1 => "SHA1SUMS",
+            Self::Sha256 => "SHA256SUMS",
+            Self::Sha512 => "SHA512SUMS",
         }
     }
 
@@ -78,6 +116,7 @@ impl ChecksumType {
             Self::Md5 => CleartextHasher::md5(),
             Self::Sha1 => CleartextHasher::sha1(),
             Self::Sha256 => CleartextHasher::sha256(),
+            Self::Sha512 => CleartextHasher::sha512(),
         })
     }
 }
@@ -92,6 +131,7 @@ impl ChecksumType {
 /// via [TryFrom]/[TryInto]. Other entry types include [ContentsFileEntry],
 /// [PackagesFileEntry], and [SourcesFileEntry].
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct ReleaseFileEntry<'a> {
     /// The path to this file within the repository.
     pub path: &'a str,
@@ -182,6 +222,7 @@ impl<'a> TryFrom<ReleaseFileEntry<'a>> for AppStreamComponentsEntry<'a> {
             "yml.gz" => Compression::Gzip,
             "yml.lzma" => Compression::Lzma,
             "yml.xz" => Compression::Xz,
+            "yml.zst" => Compression::Zstd,
             _ => {
                 return Err(DebianError::ReleaseIndicesEntryWrongType);
             }
@@ -262,6 +303,7 @@ impl<'a> TryFrom<ReleaseFileEntry<'a>> for AppStreamIconsFileEntry<'a> {
             "tar.gz" => Compression::Gzip,
             "tar.lzma" => Compression::Lzma,
             "tar.xz" => Compression::Xz,
+            "tar.zst" => Compression::Zstd,
             _ => {
                 return Err(DebianError::ReleaseIndicesEntryWrongType);
             }
@@ -427,6 +469,7 @@ impl<'a> TryFrom<ReleaseFileEntry<'a>> for PackagesFileEntry<'a> {
             "Packages.gz" => Compression::Gzip,
             "Packages.bz2" => Compression::Bzip2,
             "Packages.lzma" => Compression::Lzma,
+            "Packages.zst" => Compression::Zstd,
             _ => {
                 return Err(DebianError::ReleaseIndicesEntryWrongType);
             }
@@ -567,6 +610,7 @@ impl<'a> TryFrom<ReleaseFileEntry<'a>> for SourcesFileEntry<'a> {
             "Sources.xz" => Compression::Xz,
             "Sources.bz2" => Compression::Bzip2,
             "Sources.lzma" => Compression::Lzma,
+            "Sources.zst" => Compression::Zstd,
             _ => {
                 return Err(DebianError::ReleaseIndicesEntryWrongType);
             }
@@ -649,6 +693,7 @@ impl<'a> TryFrom<ReleaseFileEntry<'a>> for TranslationFileEntry<'a> {
                 "bz2" => Compression::Bzip2,
                 "lzma" => Compression::Lzma,
                 "xz" => Compression::Xz,
+                "zst" => Compression::Zstd,
                 _ => {
                     return Err(DebianError::ReleaseIndicesEntryWrongType);
                 }
@@ -670,7 +715,7 @@ impl<'a> TryFrom<ReleaseFileEntry<'a>> for TranslationFileEntry<'a> {
 
 /// A type of [ReleaseFileEntry] that describes a manifest of files with content digests.
 ///
-/// This represents `MD5SUMS` and `SHA256SUMS` files which hold an additional list of files
+/// This represents `MD5SUMS`, `SHA256SUMS`, and similar files which hold an additional list of files
 /// and their content manifests.
 #[derive(Clone, Debug, PartialEq)]
 pub struct FileManifestEntry<'a> {
@@ -714,13 +759,9 @@ impl<'a> TryFrom<ReleaseFileEntry<'a>> for FileManifestEntry<'a> {
             .last()
             .ok_or(DebianError::ReleaseIndicesEntryWrongType)?;
 
-        let checksum = match filename {
-            "MD5SUMS" => ChecksumType::Md5,
-            "SHA256SUMS" => ChecksumType::Sha256,
-            _ => {
-                return Err(DebianError::ReleaseIndicesEntryWrongType);
-            }
-        };
+        let checksum = ChecksumType::all()
+            .find(|checksum| checksum.sums_filename() == filename)
+            .ok_or(DebianError::ReleaseIndicesEntryWrongType)?;
 
         let root_path = entry
             .path
@@ -847,7 +888,16 @@ impl<'a> ReleaseFile<'a> {
     /// The source must not be PGP armored. i.e. do not feed it raw `InRelease`
     /// files that begin with `-----BEGIN PGP SIGNED MESSAGE-----`.
     pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
-        let paragraphs = ControlParagraphReader::new(reader).collect::<Result<Vec<_>>>()?;
+        Self::from_reader_with_limits(reader, ParseLimits::default())
+    }
+
+    /// Construct an instance by reading data from a reader, enforcing [ParseLimits].
+    ///
+    /// See [Self::from_reader()].
+    pub fn from_reader_with_limits<R: BufRead>(reader: R, limits: ParseLimits) -> Result<Self> {
+        let mut reader = ControlParagraphReader::new(reader);
+        reader.set_limits(limits);
+        let paragraphs = reader.collect::<Result<Vec<_>>>()?;
 
         // A Release control file should have a single paragraph.
         if paragraphs.len() != 1 {
@@ -879,20 +929,67 @@ impl<'a> ReleaseFile<'a> {
     /// signature verification. This is conceptually insecure. But since Rust has memory
     /// safety, some risk is prevented.
     pub fn from_armored_reader<R: BufRead>(reader: R) -> Result<Self> {
+        Self::from_armored_reader_with_limits(reader, ParseLimits::default())
+    }
+
+    /// Construct an instance by reading data from a reader containing a PGP cleartext
+    /// signature, enforcing [ParseLimits].
+    ///
+    /// See [Self::from_armored_reader()].
+    pub fn from_armored_reader_with_limits<R: BufRead>(
+        reader: R,
+        limits: ParseLimits,
+    ) -> Result<Self> {
         let reader = pgp_cleartext::CleartextSignatureReader::new(reader);
         let mut reader = std::io::BufReader::new(reader);
 
-        let mut slf = Self::from_reader(&mut reader)?;
+        let mut slf = Self::from_reader_with_limits(&mut reader, limits)?;
         slf.signatures = Some(reader.into_inner().finalize());
 
         Ok(slf)
     }
 
+    /// Construct an instance from an `InRelease` file, parsing its PGP cleartext signature
+    /// framing strictly.
+    ///
+    /// The document is parsed and canonicalized with
+    /// [canonicalize_cleartext_signed_document()], so malformed
+    /// documents are rejected and [Self::signatures()] verify as GnuPG verifies them,
+    /// ignoring trailing whitespace. As with [Self::from_armored_reader()], the signatures
+    /// are NOT validated.
+    pub fn from_armored_reader_strict<R: Read>(mut reader: R) -> Result<Self> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        let data = canonicalize_cleartext_signed_document(&data)?;
+
+        Self::from_armored_reader(std::io::Cursor::new(data.into_bytes()))
+    }
+
     /// Obtain PGP signatures from this `InRelease` file.
     pub fn signatures(&self) -> Option<&pgp_cleartext::CleartextSignatures> {
         self.signatures.as_ref()
     }
 
+    /// Verify the PGP signatures of this `InRelease` file against a keyring, subject to a
+    /// policy.
+    ///
+    /// Returns the identities of the keys that made acceptable signatures. Errors with
+    /// [DebianError::ReleaseNoSignatures] if this file isn't signed. Otherwise errors are
+    /// as for [Keyring::verify_trusted()].
+    pub fn verify_signatures(
+        &self,
+        keyring: &Keyring,
+        policy: &TrustPolicy,
+    ) -> Result<Vec<SignerIdentity>> {
+        let signatures = self
+            .signatures
+            .as_ref()
+            .ok_or(DebianError::ReleaseNoSignatures)?;
+
+        keyring.verify_trusted(signatures, policy)
+    }
+
     /// Description of this repository.
     pub fn description(&self) -> Option<&str> {
         self.field_str("Description")
@@ -1255,7 +1352,53 @@ impl<'a> ReleaseFile<'a> {
 
 #[cfg(test)]
 mod test {
-    use super::*;
+    use {super::*, crate::io::MultiDigester};
+
+    #[test]
+    fn sha512_entries() -> Result<()> {
+        let hello = "9b71d224bd62f3785d96d46ad3ea3d73319bfbc2890caadae2dff72519673ca7\
+                     2323c3d99ba5c11d7c7acc6e14b8c5da0c4663475c2e5c3adef46f73bcdec043";
+
+        let release = ReleaseFile::from_reader(std::io::Cursor::new(format!(
+            "Suite: stable\n\
+             SHA256:\n \
+             2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824 5 main/binary-amd64/Packages\n\
+             SHA512:\n \
+             {hello} 5 main/binary-amd64/Packages\n \
+             {hello} 5 main/installer-amd64/current/images/SHA512SUMS\n"
+        )))?;
+
+        let entries = release
+            .iter_classified_index_files(ChecksumType::Sha512)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(
+            entries[0],
+            ClassifiedReleaseFileEntry::Packages(_)
+        ));
+        assert_eq!(
+            entries[0].by_hash_path(),
+            format!("main/binary-amd64/by-hash/SHA512/{}", hello)
+        );
+        assert!(matches!(
+            &entries[1],
+            ClassifiedReleaseFileEntry::FileManifest(entry)
+                if entry.checksum == ChecksumType::Sha512
+        ));
+
+        let mut digester = MultiDigester::default();
+        digester.update(b"hello");
+        let digests = digester.finish();
+        assert_eq!(digests.iter_digests().count(), ChecksumType::all().count());
+        assert!(digests.matches_digest(&entries[0].digest));
+        assert_eq!(
+            digests.digest_from_checksum(ChecksumType::Sha512),
+            &ContentDigest::sha512_hex(hello)?
+        );
+
+        Ok(())
+    }
 
     #[test]
     fn parse_bullseye_release() -> Result<()> {
@@ -1708,4 +1851,25 @@ mod test {
 
         Ok(())
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn release_file_entry_serde() -> Result<()> {
+        let entry = ReleaseFileEntry {
+            path: "main/binary-amd64/Packages.xz",
+            digest: ContentDigest::sha256_hex(
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            )?,
+            size: 42,
+        };
+
+        let json = serde_json::to_string(&entry)?;
+        assert_eq!(
+            json,
+            r#"{"path":"main/binary-amd64/Packages.xz","digest":{"checksum":"sha256","digest":"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"},"size":42}"#
+        );
+        assert_eq!(serde_json::from_str::<ReleaseFileEntry>(&json)?, entry);
+
+        Ok(())
+    }
 }
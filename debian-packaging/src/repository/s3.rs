@@ -5,23 +5,125 @@
 use {
     crate::{
         error::{DebianError, Result},
-        io::{ContentDigest, MultiDigester},
+        io::{range_header_value, ContentDigest, DataResolver, MultiContentDigest, MultiDigester},
         repository::{
-            RepositoryPathVerification, RepositoryPathVerificationState, RepositoryWrite,
-            RepositoryWriter,
+            release::ChecksumType, RepositoryPathVerification, RepositoryPathVerificationState,
+            RepositoryWrite, RepositoryWriter,
         },
     },
     async_trait::async_trait,
-    futures::{AsyncRead, AsyncReadExt as FuturesAsyncReadExt},
+    futures::{
+        AsyncRead, AsyncReadExt as FuturesAsyncReadExt, AsyncSeekExt, AsyncWriteExt, TryStreamExt,
+    },
     rusoto_core::{ByteStream, Client, Region, RusotoError},
     rusoto_s3::{
-        GetBucketLocationRequest, GetObjectError, GetObjectRequest, HeadObjectError,
-        HeadObjectRequest, PutObjectRequest, S3Client, S3,
+        AbortMultipartUploadRequest, CompleteMultipartUploadRequest, CompletedMultipartUpload,
+        CompletedPart, CreateMultipartUploadRequest, DeleteObjectRequest, GetBucketLocationRequest,
+        GetObjectError, GetObjectRequest, HeadObjectError, HeadObjectRequest, ListObjectsV2Request,
+        PutObjectRequest, S3Client, UploadPartRequest, S3,
     },
-    std::{borrow::Cow, pin::Pin, str::FromStr},
+    std::{borrow::Cow, collections::HashMap, fmt::Debug, ops::Range, pin::Pin, str::FromStr},
     tokio::io::AsyncReadExt as TokioAsyncReadExt,
 };
 
+/// Size in bytes of the parts of multipart uploads.
+///
+/// Content smaller than this is uploaded with a single request. S3 requires all parts
+/// but the last to be at least 5 MiB.
+const MULTIPART_PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// Name of the object metadata key holding a content digest.
+///
+/// Digests of every [ChecksumType] are recorded when objects are written, allowing
+/// [RepositoryWriter::verify_path()] to verify content without downloading it. Object
+/// metadata must be defined when a multipart upload begins, so content uploaded in parts
+/// is spooled to a temporary file to compute its digests first.
+fn digest_metadata_key(checksum: ChecksumType) -> String {
+    format!("{}-digest", checksum.field_name().to_lowercase())
+}
+
+/// Obtain the object metadata recording content digests.
+fn digest_metadata(digests: &MultiContentDigest) -> HashMap<String, String> {
+    digests
+        .iter_digests()
+        .map(|digest| {
+            (
+                digest_metadata_key(digest.checksum_type()),
+                digest.digest_hex(),
+            )
+        })
+        .collect::<HashMap<_, _>>()
+}
+
+/// S3 operation errors having a `NoSuchKey` variant.
+trait NoSuchKey {
+    fn is_no_such_key(&self) -> bool;
+}
+
+impl NoSuchKey for GetObjectError {
+    fn is_no_such_key(&self) -> bool {
+        matches!(self, Self::NoSuchKey(_))
+    }
+}
+
+impl NoSuchKey for HeadObjectError {
+    fn is_no_such_key(&self) -> bool {
+        matches!(self, Self::NoSuchKey(_))
+    }
+}
+
+/// Whether an S3 request failed because the key doesn't exist.
+///
+/// Responses to `HEAD` requests lack a body, so a missing key surfaces as a bare 404
+/// instead of a `NoSuchKey` service error.
+fn is_missing_key<E: NoSuchKey>(e: &RusotoError<E>) -> bool {
+    match e {
+        RusotoError::Service(e) => e.is_no_such_key(),
+        RusotoError::Unknown(res) => res.status.as_u16() == 404,
+        _ => false,
+    }
+}
+
+fn s3_error(e: impl Debug) -> std::io::Error {
+    std::io::Error::other(format!("S3 error: {:?}", e))
+}
+
+/// Read a part of a multipart upload.
+///
+/// The returned buffer is only shorter than [MULTIPART_PART_SIZE] at end of stream.
+async fn read_part(reader: &mut Pin<Box<dyn AsyncRead + Send + '_>>) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    reader
+        .take(MULTIPART_PART_SIZE as u64)
+        .read_to_end(&mut buf)
+        .await?;
+
+    Ok(buf)
+}
+
+/// Spool content to a temporary file while computing its digests.
+///
+/// `first` is the first part of content. The returned file is positioned at its start.
+async fn spool(
+    first: Vec<u8>,
+    reader: &mut Pin<Box<dyn AsyncRead + Send + '_>>,
+) -> std::io::Result<(async_std::fs::File, MultiContentDigest)> {
+    let mut file = async_std::fs::File::from(tempfile::tempfile()?);
+    let mut digester = MultiDigester::default();
+    let mut data = first;
+
+    while !data.is_empty() {
+        digester.update(&data);
+        file.write_all(&data).await?;
+        data = read_part(reader).await?;
+    }
+
+    file.flush().await?;
+    file.seek(std::io::SeekFrom::Start(0)).await?;
+
+    Ok((file, digester.finish()))
+}
+
 pub struct S3Writer {
     client: S3Client,
     bucket: String,
@@ -56,6 +158,121 @@ impl S3Writer {
         }
     }
 
+    /// Create a new S3 writer from an `s3://bucket/prefix` URL.
+    ///
+    /// The bucket's region is resolved via [get_bucket_region()].
+    pub async fn from_url(url: &url::Url) -> Result<Self> {
+        let bucket = url
+            .host_str()
+            .ok_or_else(|| DebianError::RepositoryWriterUnrecognizedUrl(url.to_string()))?;
+        let prefix = match url.path().trim_matches('/') {
+            "" => None,
+            prefix => Some(prefix),
+        };
+
+        let region = get_bucket_region(bucket).await?;
+
+        Ok(Self::new(region, bucket, prefix))
+    }
+
+    /// Upload content in parts.
+    ///
+    /// `first` is the first part. Content is spooled to a temporary file so its digests
+    /// can be recorded when the upload begins. The multipart upload is aborted if any
+    /// part fails.
+    async fn write_multipart(
+        &self,
+        key: &str,
+        first: Vec<u8>,
+        reader: &mut Pin<Box<dyn AsyncRead + Send + '_>>,
+    ) -> std::io::Result<u64> {
+        let (file, digests) = spool(first, reader).await?;
+        let mut reader: Pin<Box<dyn AsyncRead + Send>> = Box::pin(file);
+
+        let req = CreateMultipartUploadRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            metadata: Some(digest_metadata(&digests)),
+            ..Default::default()
+        };
+
+        let upload_id = self
+            .client
+            .create_multipart_upload(req)
+            .await
+            .map_err(s3_error)?
+            .upload_id
+            .ok_or_else(|| std::io::Error::other("S3 didn't return a multipart upload ID"))?;
+
+        match self.upload_parts(key, &upload_id, &mut reader).await {
+            Ok(size) => Ok(size),
+            Err(e) => {
+                let req = AbortMultipartUploadRequest {
+                    bucket: self.bucket.clone(),
+                    key: key.to_string(),
+                    upload_id,
+                    ..Default::default()
+                };
+
+                // The original error is more relevant than a failure to clean up.
+                let _ = self.client.abort_multipart_upload(req).await;
+
+                Err(e)
+            }
+        }
+    }
+
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        reader: &mut Pin<Box<dyn AsyncRead + Send + '_>>,
+    ) -> std::io::Result<u64> {
+        let mut parts = vec![];
+        let mut bytes_written = 0;
+        let mut data = read_part(reader).await?;
+
+        while !data.is_empty() {
+            let part_number = parts.len() as i64 + 1;
+            let size = data.len();
+
+            let req = UploadPartRequest {
+                body: Some(ByteStream::from(data)),
+                bucket: self.bucket.clone(),
+                content_length: Some(size as i64),
+                key: key.to_string(),
+                part_number,
+                upload_id: upload_id.to_string(),
+                ..Default::default()
+            };
+
+            let output = self.client.upload_part(req).await.map_err(s3_error)?;
+
+            parts.push(CompletedPart {
+                e_tag: output.e_tag,
+                part_number: Some(part_number),
+            });
+            bytes_written += size as u64;
+
+            data = read_part(reader).await?;
+        }
+
+        let req = CompleteMultipartUploadRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            multipart_upload: Some(CompletedMultipartUpload { parts: Some(parts) }),
+            upload_id: upload_id.to_string(),
+            ..Default::default()
+        };
+
+        self.client
+            .complete_multipart_upload(req)
+            .await
+            .map_err(s3_error)?;
+
+        Ok(bytes_written)
+    }
+
     /// Compute the S3 key name given a repository relative path.
     pub fn path_to_key(&self, path: &str) -> String {
         if let Some(prefix) = &self.key_prefix {
@@ -64,6 +281,60 @@ impl S3Writer {
             path.trim_matches('/').to_string()
         }
     }
+
+    /// Fetch the content of an object, optionally limited to a byte range.
+    async fn get_object(
+        &self,
+        path: &str,
+        range: Option<Range<u64>>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        let req = GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: self.path_to_key(path),
+            range: range.as_ref().map(range_header_value),
+            ..Default::default()
+        };
+
+        match self.client.get_object(req).await {
+            Ok(output) => Ok(match output.body {
+                Some(body) => Box::pin(TryStreamExt::into_async_read(body)),
+                None => Box::pin(futures::io::empty()),
+            }),
+            Err(e) if is_missing_key(&e) => Err(DebianError::RepositoryIoPath(
+                path.to_string(),
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("S3 key not found: {}", self.path_to_key(path)),
+                ),
+            )),
+            // A range starting past the end of content is empty.
+            Err(RusotoError::Unknown(res)) if range.is_some() && res.status.as_u16() == 416 => {
+                Ok(Box::pin(futures::io::empty()))
+            }
+            Err(e) => Err(DebianError::RepositoryIoPath(path.to_string(), s3_error(e))),
+        }
+    }
+}
+
+/// Objects are readable, e.g. to verify or copy published content.
+#[async_trait]
+impl DataResolver for S3Writer {
+    async fn get_path(&self, path: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        self.get_object(path, None).await
+    }
+
+    async fn get_path_with_range(
+        &self,
+        path: &str,
+        range: Range<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        // An empty range can't be expressed in a Range header.
+        if range.start >= range.end {
+            return Ok(Box::pin(futures::io::empty()));
+        }
+
+        self.get_object(path, Some(range)).await
+    }
 }
 
 #[async_trait]
@@ -74,6 +345,56 @@ impl RepositoryWriter for S3Writer {
         expected_content: Option<(u64, ContentDigest)>,
     ) -> Result<RepositoryPathVerification<'path>> {
         if let Some((expected_size, expected_digest)) = expected_content {
+            // Objects written by us in a single request record their digests in metadata. Compare against them
+            // so verification doesn't need to download content.
+            let req = HeadObjectRequest {
+                bucket: self.bucket.clone(),
+                key: self.path_to_key(path),
+                ..Default::default()
+            };
+
+            match self.client.head_object(req).await {
+                Ok(output) => {
+                    let size_matches = output
+                        .content_length
+                        .map_or(true, |cl| cl as u64 == expected_size);
+
+                    let recorded_digest = output
+                        .metadata
+                        .as_ref()
+                        .and_then(|m| m.get(&digest_metadata_key(expected_digest.checksum_type())));
+
+                    if !size_matches {
+                        return Ok(RepositoryPathVerification {
+                            path,
+                            state: RepositoryPathVerificationState::ExistsIntegrityMismatch,
+                        });
+                    } else if let Some(hex_digest) = recorded_digest {
+                        return Ok(RepositoryPathVerification {
+                            path,
+                            state: if *hex_digest == expected_digest.digest_hex() {
+                                RepositoryPathVerificationState::ExistsIntegrityVerified
+                            } else {
+                                RepositoryPathVerificationState::ExistsIntegrityMismatch
+                            },
+                        });
+                    }
+                }
+                Err(e) if is_missing_key(&e) => {
+                    return Ok(RepositoryPathVerification {
+                        path,
+                        state: RepositoryPathVerificationState::Missing,
+                    });
+                }
+                Err(e) => {
+                    return Err(DebianError::RepositoryIoPath(
+                        path.to_string(),
+                        std::io::Error::other(format!("S3 error: {:?}", e)),
+                    ))
+                }
+            }
+
+            // Objects lacking digest metadata need to be downloaded.
             let req = GetObjectRequest {
                 bucket: self.bucket.clone(),
                 key: self.path_to_key(path),
@@ -133,12 +454,10 @@ impl RepositoryWriter for S3Writer {
                         })
                     }
                 }
-                Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => {
-                    Ok(RepositoryPathVerification {
-                        path,
-                        state: RepositoryPathVerificationState::Missing,
-                    })
-                }
+                Err(e) if is_missing_key(&e) => Ok(RepositoryPathVerification {
+                    path,
+                    state: RepositoryPathVerificationState::Missing,
+                }),
                 Err(e) => Err(DebianError::RepositoryIoPath(
                     path.to_string(),
                     std::io::Error::new(std::io::ErrorKind::Other, format!("S3 error: {:?}", e)),
@@ -156,12 +475,10 @@ impl RepositoryWriter for S3Writer {
                     path,
                     state: RepositoryPathVerificationState::ExistsNoIntegrityCheck,
                 }),
-                Err(RusotoError::Service(HeadObjectError::NoSuchKey(_))) => {
-                    Ok(RepositoryPathVerification {
-                        path,
-                        state: RepositoryPathVerificationState::Missing,
-                    })
-                }
+                Err(e) if is_missing_key(&e) => Ok(RepositoryPathVerification {
+                    path,
+                    state: RepositoryPathVerificationState::Missing,
+                }),
                 Err(e) => Err(DebianError::RepositoryIoPath(
                     path.to_string(),
                     std::io::Error::new(std::io::ErrorKind::Other, format!("S3 error: {:?}", e)),
@@ -170,27 +487,59 @@ impl RepositoryWriter for S3Writer {
         }
     }
 
+    async fn path_size(&self, path: &str) -> Result<Option<u64>> {
+        let req = HeadObjectRequest {
+            bucket: self.bucket.clone(),
+            key: self.path_to_key(path),
+            ..Default::default()
+        };
+
+        match self.client.head_object(req).await {
+            Ok(output) => Ok(output.content_length.map(|size| size as u64)),
+            Err(e) if is_missing_key(&e) => Ok(None),
+            Err(e) => Err(DebianError::RepositoryIoPath(
+                path.to_string(),
+                std::io::Error::other(format!("S3 error: {:?}", e)),
+            )),
+        }
+    }
+
     async fn write_path<'path, 'reader>(
         &self,
         path: Cow<'path, str>,
         mut reader: Pin<Box<dyn AsyncRead + Send + 'reader>>,
     ) -> Result<RepositoryWrite<'path>> {
         // rusoto wants a Stream<Bytes>. There's no easy way to convert from an AsyncRead to a
-        // Stream. So we just buffer content locally.
-        // TODO implement this properly
-        let mut buf = vec![];
-        reader
-            .read_to_end(&mut buf)
+        // Stream. So we buffer content locally, 1 part at a time.
+        let buf = read_part(&mut reader)
             .await
             .map_err(|e| DebianError::RepositoryIoPath(path.to_string(), e))?;
 
+        if buf.len() == MULTIPART_PART_SIZE {
+            let bytes_written = self
+                .write_multipart(&self.path_to_key(path.as_ref()), buf, &mut reader)
+                .await
+                .map_err(|e| DebianError::RepositoryIoPath(path.to_string(), e))?;
+
+            return Ok(RepositoryWrite {
+                path,
+                bytes_written,
+            });
+        }
+
         let bytes_written = buf.len() as u64;
+
+        let mut digester = MultiDigester::default();
+        digester.update(&buf);
+        let metadata = digest_metadata(&digester.finish());
+
         let stream = futures::stream::once(async { Ok(bytes::Bytes::from(buf)) });
 
         let req = PutObjectRequest {
             bucket: self.bucket.clone(),
             key: self.path_to_key(path.as_ref()),
             body: Some(ByteStream::new(stream)),
+            metadata: Some(metadata),
             ..Default::default()
         };
 
@@ -205,6 +554,70 @@ impl RepositoryWriter for S3Writer {
             )),
         }
     }
+
+    async fn list_paths(&self, prefix: &str) -> Result<Vec<String>> {
+        let strip_prefix = if let Some(prefix) = &self.key_prefix {
+            format!("{}/", prefix)
+        } else {
+            String::new()
+        };
+        let key_prefix = match prefix.trim_matches('/') {
+            "" => strip_prefix.clone(),
+            prefix => format!("{}/", self.path_to_key(prefix)),
+        };
+
+        let mut paths = vec![];
+        let mut continuation_token = None;
+
+        loop {
+            let req = ListObjectsV2Request {
+                bucket: self.bucket.clone(),
+                prefix: Some(key_prefix.clone()),
+                continuation_token,
+                ..Default::default()
+            };
+
+            let output = self.client.list_objects_v2(req).await.map_err(|e| {
+                DebianError::RepositoryIoPath(
+                    prefix.to_string(),
+                    std::io::Error::other(format!("S3 error: {:?}", e)),
+                )
+            })?;
+
+            paths.extend(
+                output
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|o| o.key)
+                    .filter_map(|key| key.strip_prefix(&strip_prefix).map(|x| x.to_string())),
+            );
+
+            if output.next_continuation_token.is_none() {
+                break;
+            }
+            continuation_token = output.next_continuation_token;
+        }
+
+        Ok(paths)
+    }
+
+    async fn delete_path(&self, path: &str) -> Result<()> {
+        let req = DeleteObjectRequest {
+            bucket: self.bucket.clone(),
+            key: self.path_to_key(path),
+            ..Default::default()
+        };
+
+        self.client.delete_object(req).await.map_err(|e| {
+            DebianError::RepositoryIoPath(
+                path.to_string(),
+                std::io::Error::other(format!("S3 error: {:?}", e)),
+            )
+        })?;
+
+        Ok(())
+    }
 }
 
 /// Attempt to resolve the AWS region of an S3 bucket.
@@ -237,3 +650,75 @@ pub async fn get_bucket_region_with_client(
         ))),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use {super::*, rusoto_core::request::BufferedHttpResponse};
+
+    fn unknown<E>(status: u16) -> RusotoError<E> {
+        RusotoError::Unknown(BufferedHttpResponse {
+            status: status.try_into().unwrap(),
+            body: Default::default(),
+            headers: Default::default(),
+        })
+    }
+
+    #[test]
+    fn missing_key() {
+        assert!(is_missing_key(&RusotoError::Service(
+            HeadObjectError::NoSuchKey(String::new())
+        )));
+        assert!(is_missing_key(&RusotoError::Service(
+            GetObjectError::NoSuchKey(String::new())
+        )));
+        assert!(is_missing_key::<HeadObjectError>(&unknown(404)));
+        assert!(is_missing_key::<GetObjectError>(&unknown(404)));
+
+        assert!(!is_missing_key::<HeadObjectError>(&unknown(403)));
+        assert!(!is_missing_key::<GetObjectError>(&unknown(500)));
+        assert!(!is_missing_key(&RusotoError::Service(
+            GetObjectError::InvalidObjectState(String::new())
+        )));
+        assert!(!is_missing_key::<HeadObjectError>(
+            &RusotoError::Validation(String::new())
+        ));
+    }
+
+    #[tokio::test]
+    async fn read_parts() -> std::io::Result<()> {
+        let content = (0..MULTIPART_PART_SIZE + 100)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+
+        let mut reader: Pin<Box<dyn AsyncRead + Send>> =
+            Box::pin(futures::io::Cursor::new(content.clone()));
+        let first = read_part(&mut reader).await?;
+        assert_eq!(first, &content[0..MULTIPART_PART_SIZE]);
+
+        // Spooling consumes the remaining content and digests everything.
+        let (file, digests) = spool(first, &mut reader).await?;
+        let mut digester = MultiDigester::default();
+        digester.update(&content);
+        let metadata = digest_metadata(&digests);
+        assert_eq!(metadata, digest_metadata(&digester.finish()));
+        assert_eq!(metadata.len(), ChecksumType::all().count());
+
+        // Spooled content is split into a full part and the remainder.
+        let mut reader: Pin<Box<dyn AsyncRead + Send>> = Box::pin(file);
+        let mut parts = vec![];
+        loop {
+            let part = read_part(&mut reader).await?;
+            if part.is_empty() {
+                break;
+            }
+            parts.push(part);
+        }
+        assert_eq!(
+            parts.iter().map(|p| p.len()).collect::<Vec<_>>(),
+            vec![MULTIPART_PART_SIZE, 100]
+        );
+        assert_eq!(parts.concat(), content);
+
+        Ok(())
+    }
+}
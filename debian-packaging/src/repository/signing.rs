@@ -0,0 +1,791 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! PGP signing of repository release files.
+
+A distribution's `Release` file is signed in 2 ways: `InRelease` holds the `Release`
+content with an inline PGP cleartext signature and `Release.gpg` holds a detached,
+armored signature over the exact bytes of `Release`.
+
+This module contains primitives for producing these signatures, including documents
+having multiple signatures. [resign_distribution()] uses them to replace the
+signatures of an existing distribution without touching any other files, which is
+useful for rotating signing keys.
+
+[sign_cleartext()] and [cleartext_signed_document()] implement the cleartext signature
+framework of RFC 4880 section 7.1 as GnuPG does: lines starting with `-` or `From ` are
+dash-escaped, and signatures cover the text with trailing spaces and tabs stripped from
+every line and `CRLF` line endings. See [canonical_cleartext()].
+
+Documents are usually parsed with [pgp_cleartext], which hashes trailing whitespace and
+accepts lines starting with `-` that aren't dash-escaped. Signatures made by GnuPG over
+lines with trailing whitespace therefore don't verify. [parse_cleartext_signed_document_strict()]
+parses documents strictly, and [canonicalize_cleartext_signed_document()] rewrites them so
+[pgp_cleartext] hashes them as GnuPG does.
+
+Both files can hold signatures from several keys. During a key rotation, archives sign
+with the old and the new key so clients trusting either can verify the distribution.
+[sign_release()], [write_signed_release_with_keys()] and
+[resign_distribution_with_keys()] sign with any number of keys.
+*/
+
+use {
+    crate::{
+        error::{DebianError, Result},
+        repository::{PublishEvent, RepositoryRootReader, RepositoryWrite, RepositoryWriter},
+        signing_key::signing_hash_algorithm,
+    },
+    chrono::SubsecRound,
+    futures::AsyncReadExt,
+    pgp::{
+        armor::BlockType,
+        crypto::hash::HashAlgorithm,
+        packet::{Packet, SignatureConfig, SignatureType, Subpacket, SubpacketData},
+        types::SecretKeyTrait,
+        Deserializable, Signature, StandaloneSignature,
+    },
+    pgp_cleartext::CleartextSignatureReader,
+    std::{
+        collections::BTreeSet,
+        io::{Cursor, Read},
+    },
+};
+
+const CLEARTEXT_HEADER: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+const SIGNATURE_HEADER: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// Strip the whitespace the cleartext signature framework ignores from the end of a line.
+fn trim_line(line: &str) -> &str {
+    line.trim_end_matches([' ', '\t', '\r'])
+}
+
+/// Obtain the form of text covered by cleartext signatures.
+///
+/// Trailing spaces and tabs are stripped from every line and lines are joined with `CRLF`.
+/// The line ending of the final line isn't part of the signed text.
+pub fn canonical_cleartext(text: &str) -> String {
+    text.lines().map(trim_line).collect::<Vec<_>>().join("\r\n")
+}
+
+/// Obtain the name of a hash algorithm as used in cleartext `Hash:` armor headers.
+fn cleartext_hash_name(hash: HashAlgorithm) -> Result<&'static str> {
+    match hash {
+        HashAlgorithm::MD5 => Ok("MD5"),
+        HashAlgorithm::SHA1 => Ok("SHA1"),
+        HashAlgorithm::RIPEMD160 => Ok("RIPEMD160"),
+        HashAlgorithm::SHA2_256 => Ok("SHA256"),
+        HashAlgorithm::SHA2_384 => Ok("SHA384"),
+        HashAlgorithm::SHA2_512 => Ok("SHA512"),
+        HashAlgorithm::SHA2_224 => Ok("SHA224"),
+        _ => Err(DebianError::Pgp(pgp::errors::Error::Unsupported(format!(
+            "hash algorithm {:?} unsupported for cleartext signatures",
+            hash
+        )))),
+    }
+}
+
+fn signature_config(
+    key: &impl SecretKeyTrait,
+    signature_type: SignatureType,
+    hash: HashAlgorithm,
+) -> SignatureConfig {
+    let mut config = SignatureConfig::v4(signature_type, key.algorithm(), hash);
+    config.hashed_subpackets = vec![
+        Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint())),
+        Subpacket::regular(SubpacketData::SignatureCreationTime(
+            chrono::Utc::now().trunc_subsecs(0),
+        )),
+    ];
+    config.unhashed_subpackets = vec![Subpacket::regular(SubpacketData::Issuer(key.key_id()))];
+
+    config
+}
+
+/// Produce a text signature over data for use in a PGP cleartext signed document.
+///
+/// The signature covers [canonical_cleartext()] of `text`, as required by the cleartext
+/// signature framework.
+pub fn sign_cleartext<PW>(
+    key: &impl SecretKeyTrait,
+    key_pw: PW,
+    hash: HashAlgorithm,
+    text: &str,
+) -> Result<Signature>
+where
+    PW: FnOnce() -> String,
+{
+    cleartext_hash_name(hash)?;
+
+    let normalized = canonical_cleartext(text);
+
+    Ok(signature_config(key, SignatureType::Text, hash).sign(
+        key,
+        key_pw,
+        Cursor::new(normalized.into_bytes()),
+    )?)
+}
+
+/// Produce a detached binary signature over data.
+///
+/// This is the signature flavor stored in `Release.gpg` files.
+pub fn sign_detached<PW>(
+    key: &impl SecretKeyTrait,
+    key_pw: PW,
+    hash: HashAlgorithm,
+    data: &[u8],
+) -> Result<Signature>
+where
+    PW: FnOnce() -> String,
+{
+    Ok(signature_config(key, SignatureType::Binary, hash).sign(key, key_pw, Cursor::new(data))?)
+}
+
+/// Produce an armored `-----BEGIN PGP SIGNATURE-----` block holding signatures.
+pub fn armor_signatures(signatures: &[Signature]) -> Result<String> {
+    let packets = signatures
+        .iter()
+        .map(|sig| Packet::Signature(sig.clone()))
+        .collect::<Vec<_>>();
+
+    let mut writer = Cursor::new(Vec::<u8>::new());
+    pgp::armor::write(&packets, BlockType::Signature, &mut writer, None, true)?;
+
+    String::from_utf8(writer.into_inner())
+        .map_err(|e| DebianError::Pgp(pgp::errors::Error::Utf8Error(e.utf8_error())))
+}
+
+/// Produce a PGP cleartext signed document from text and signatures over it.
+///
+/// Signatures should be produced by [sign_cleartext()] (or parsed from an existing
+/// cleartext document over the same text). The `Hash:` header advertises the union of
+/// hash algorithms used by the signatures. Trailing spaces and tabs are stripped from
+/// lines, so the document verifies whether or not verifiers ignore them.
+pub fn cleartext_signed_document(text: &str, signatures: &[Signature]) -> Result<String> {
+    if signatures.is_empty() {
+        return Err(DebianError::ReleaseNoSignatures);
+    }
+
+    let hashes = signatures
+        .iter()
+        .map(|sig| cleartext_hash_name(sig.config.hash_alg))
+        .collect::<Result<BTreeSet<_>>>()?;
+
+    let dashed_lines = text.lines().map(trim_line).map(|line| {
+        if line.starts_with('-') || line.starts_with("From ") {
+            format!("- {}", line)
+        } else {
+            line.to_string()
+        }
+    });
+
+    Ok([
+        CLEARTEXT_HEADER.to_string(),
+        format!("Hash: {}", hashes.into_iter().collect::<Vec<_>>().join(",")),
+        "".to_string(),
+    ]
+    .into_iter()
+    .chain(dashed_lines)
+    .chain(std::iter::once(armor_signatures(signatures)?))
+    .collect::<Vec<_>>()
+    .join("\n"))
+}
+
+/// Parse the cleartext and signatures from a PGP cleartext signed document.
+pub fn parse_cleartext_signed_document(data: &[u8]) -> Result<(String, Vec<Signature>)> {
+    let mut reader = CleartextSignatureReader::new(Cursor::new(data));
+
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    let signatures = reader.finalize().iter_signatures().cloned().collect();
+
+    Ok((text, signatures))
+}
+
+/// Parse a PGP cleartext signed document strictly per RFC 4880 section 7.1.
+///
+/// Unlike [parse_cleartext_signed_document()], the following are rejected with
+/// [DebianError::CleartextMalformed]:
+///
+/// * Armor headers other than `Hash`.
+/// * Lines starting with `-` that aren't dash-escaped with `- `.
+/// * Signatures using a hash algorithm not advertised by present `Hash` headers.
+///
+/// `LF` and `CRLF` line endings are accepted. The returned text has `LF` line endings
+/// and retains trailing whitespace. Signatures cover [canonical_cleartext()] of it.
+pub fn parse_cleartext_signed_document_strict(data: &[u8]) -> Result<(String, Vec<Signature>)> {
+    let malformed = |msg: String| DebianError::CleartextMalformed(msg);
+
+    let mut remaining =
+        std::str::from_utf8(data).map_err(|e| malformed(format!("not UTF-8: {}", e)))?;
+
+    // Consume the next line from `remaining`, without its line ending.
+    let mut next_line = || {
+        let (line, rest) = remaining.split_once('\n').unwrap_or((remaining, ""));
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let consumed = std::mem::replace(&mut remaining, rest);
+
+        (!consumed.is_empty()).then_some((line, consumed))
+    };
+
+    if next_line().map(|(line, _)| trim_line(line)) != Some(CLEARTEXT_HEADER) {
+        return Err(malformed(format!(
+            "does not begin with {}",
+            CLEARTEXT_HEADER
+        )));
+    }
+
+    let mut hashes = BTreeSet::new();
+
+    loop {
+        let (line, _) =
+            next_line().ok_or_else(|| malformed("armor headers not terminated".to_string()))?;
+
+        if line.is_empty() {
+            break;
+        }
+
+        match line.split_once(": ") {
+            Some(("Hash", value)) => {
+                hashes.extend(value.split(',').map(|hash| hash.trim()));
+            }
+            _ => {
+                return Err(malformed(format!("unexpected armor header: {}", line)));
+            }
+        }
+    }
+
+    let mut text = String::new();
+
+    let armor = loop {
+        let (line, consumed) =
+            next_line().ok_or_else(|| malformed(format!("missing {}", SIGNATURE_HEADER)))?;
+
+        if trim_line(line) == SIGNATURE_HEADER {
+            break consumed;
+        }
+
+        let line = if let Some(stripped) = line.strip_prefix("- ") {
+            stripped
+        } else if line.starts_with('-') {
+            return Err(malformed(format!("line not dash-escaped: {}", line)));
+        } else {
+            line
+        };
+
+        text.push_str(line);
+        text.push('\n');
+    };
+
+    let signatures = parse_armored_signatures(armor.as_bytes())?;
+
+    if !hashes.is_empty() {
+        for sig in &signatures {
+            let hash = cleartext_hash_name(sig.config.hash_alg)?;
+
+            if !hashes.contains(hash) {
+                return Err(malformed(format!(
+                    "signature uses {} but Hash header advertises {}",
+                    hash,
+                    hashes.into_iter().collect::<Vec<_>>().join(",")
+                )));
+            }
+        }
+    }
+
+    Ok((text, signatures))
+}
+
+/// Rewrite a PGP cleartext signed document into the canonical form.
+///
+/// The document is parsed with [parse_cleartext_signed_document_strict()] and reproduced
+/// with [cleartext_signed_document()]. Trailing whitespace is stripped, line endings are
+/// normalized to `LF` and only a `Hash` armor header is emitted. Signatures are
+/// unaltered and remain valid, and [pgp_cleartext] hashes the result as GnuPG hashes the
+/// original.
+pub fn canonicalize_cleartext_signed_document(data: &[u8]) -> Result<String> {
+    let (text, signatures) = parse_cleartext_signed_document_strict(data)?;
+
+    cleartext_signed_document(&text, &signatures)
+}
+
+/// Parse signatures from an armored detached signature, such as a `Release.gpg` file.
+pub fn parse_armored_signatures(data: &[u8]) -> Result<Vec<Signature>> {
+    let (signatures, _) = StandaloneSignature::from_armor_many(Cursor::new(data))?;
+
+    signatures
+        .map(|sig| Ok(sig?.signature))
+        .collect::<Result<Vec<_>>>()
+}
+
+/// Sign `Release` content with several keys.
+///
+/// Returns the content of the `InRelease` and `Release.gpg` files, each holding one
+/// signature per key, in the order of `signing_keys`. Each key signs with the hash
+/// algorithm chosen by [signing_hash_algorithm()].
+pub fn sign_release<K, PW>(release: &str, signing_keys: &[(&K, PW)]) -> Result<(String, String)>
+where
+    K: SecretKeyTrait,
+    PW: Fn() -> String,
+{
+    let mut cleartext_signatures = vec![];
+    let mut detached_signatures = vec![];
+
+    for (key, key_pw) in signing_keys {
+        cleartext_signatures.push(sign_cleartext(
+            *key,
+            key_pw,
+            signing_hash_algorithm(*key),
+            release,
+        )?);
+        detached_signatures.push(sign_detached(
+            *key,
+            key_pw,
+            signing_hash_algorithm(*key),
+            release.as_bytes(),
+        )?);
+    }
+
+    Ok((
+        cleartext_signed_document(release, &cleartext_signatures)?,
+        armor_signatures(&detached_signatures)?,
+    ))
+}
+
+async fn read_optional_path(
+    reader: &dyn RepositoryRootReader,
+    path: &str,
+) -> Result<Option<Vec<u8>>> {
+    let mut stream = match reader.get_path(path).await {
+        Ok(stream) => stream,
+        Err(DebianError::RepositoryIoPath(_, e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut data = vec![];
+    stream
+        .read_to_end(&mut data)
+        .await
+        .map_err(|e| DebianError::RepositoryIoPath(path.to_string(), e))?;
+
+    Ok(Some(data))
+}
+
+/// Re-sign the release files of an existing distribution with a new key.
+///
+/// The existing `Release` file (or the cleartext of `InRelease` if `Release` doesn't
+/// exist) is read from `reader` and new `InRelease` and `Release.gpg` files are written
+/// to `writer`. `Release.gpg` is only written if a `Release` file exists. No other files,
+/// including `Release`, indices, and pool files, are touched, so the signed content is
+/// identical to what was previously published. The performed writes are returned.
+///
+/// If `keep_existing_signatures` is true, signatures in the existing `InRelease` and
+/// `Release.gpg` files are preserved and the new signature is added as an additional
+/// signer. This enables clients trusting either the old or the new key to verify the
+/// distribution during a key rotation.
+///
+/// `distribution_path` is the path relative to the repository root holding the release
+/// files. e.g. `dists/bullseye`.
+pub async fn resign_distribution<PW>(
+    reader: &dyn RepositoryRootReader,
+    writer: &dyn RepositoryWriter,
+    distribution_path: &str,
+    signing_key: (&impl SecretKeyTrait, PW),
+    keep_existing_signatures: bool,
+    progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+) -> Result<Vec<RepositoryWrite<'static>>>
+where
+    PW: Fn() -> String,
+{
+    resign_distribution_with_keys(
+        reader,
+        writer,
+        distribution_path,
+        &[signing_key],
+        keep_existing_signatures,
+        progress_cb,
+    )
+    .await
+}
+
+/// Re-sign the release files of an existing distribution with several keys.
+///
+/// This is like [resign_distribution()] except a signature is added for each key in
+/// `signing_keys`.
+pub async fn resign_distribution_with_keys<K, PW>(
+    reader: &dyn RepositoryRootReader,
+    writer: &dyn RepositoryWriter,
+    distribution_path: &str,
+    signing_keys: &[(&K, PW)],
+    keep_existing_signatures: bool,
+    progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+) -> Result<Vec<RepositoryWrite<'static>>>
+where
+    K: SecretKeyTrait,
+    PW: Fn() -> String,
+{
+    let distribution_path = distribution_path.trim_matches('/');
+    let release_path = format!("{}/Release", distribution_path);
+    let inrelease_path = format!("{}/InRelease", distribution_path);
+    let release_gpg_path = format!("{}/Release.gpg", distribution_path);
+
+    let release = read_optional_path(reader, &release_path).await?;
+    let inrelease = read_optional_path(reader, &inrelease_path).await?;
+
+    let (existing_text, existing_cleartext_signatures) = if let Some(data) = &inrelease {
+        let (text, signatures) = parse_cleartext_signed_document(data)?;
+        (Some(text), signatures)
+    } else {
+        (None, vec![])
+    };
+
+    let text = match (&release, existing_text) {
+        (Some(data), _) => String::from_utf8(data.clone())
+            .map_err(|e| DebianError::Other(format!("{} is not UTF-8: {}", release_path, e)))?,
+        (None, Some(text)) => text,
+        (None, None) => {
+            return Err(DebianError::RepositoryIoPath(
+                release_path,
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "neither Release nor InRelease found",
+                ),
+            ));
+        }
+    };
+
+    let mut writes = vec![];
+
+    let mut cleartext_signatures = if keep_existing_signatures {
+        existing_cleartext_signatures
+    } else {
+        vec![]
+    };
+    for (key, key_pw) in signing_keys {
+        cleartext_signatures.push(sign_cleartext(
+            *key,
+            key_pw,
+            signing_hash_algorithm(*key),
+            &text,
+        )?);
+    }
+    writes.push((
+        inrelease_path,
+        cleartext_signed_document(&text, &cleartext_signatures)?,
+    ));
+
+    if let Some(release) = &release {
+        let mut detached_signatures = if keep_existing_signatures {
+            match read_optional_path(reader, &release_gpg_path).await? {
+                Some(data) => parse_armored_signatures(&data)?,
+                None => vec![],
+            }
+        } else {
+            vec![]
+        };
+        for (key, key_pw) in signing_keys {
+            detached_signatures.push(sign_detached(
+                *key,
+                key_pw,
+                signing_hash_algorithm(*key),
+                release,
+            )?);
+        }
+        writes.push((release_gpg_path, armor_signatures(&detached_signatures)?));
+    }
+
+    write_files(writer, writes, progress_cb).await
+}
+
+/// Publish new `Release` content for a distribution signed with a key.
+///
+/// `Release`, `InRelease`, and `Release.gpg` are written to `writer` under
+/// `distribution_path`. Existing signatures aren't preserved since they are invalidated
+/// by the new content. The performed writes are returned.
+pub async fn write_signed_release<PW>(
+    writer: &dyn RepositoryWriter,
+    distribution_path: &str,
+    release: &str,
+    signing_key: (&impl SecretKeyTrait, PW),
+    progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+) -> Result<Vec<RepositoryWrite<'static>>>
+where
+    PW: Fn() -> String,
+{
+    write_signed_release_with_keys(
+        writer,
+        distribution_path,
+        release,
+        &[signing_key],
+        progress_cb,
+    )
+    .await
+}
+
+/// Publish new `Release` content for a distribution signed with several keys.
+///
+/// This is like [write_signed_release()] except `InRelease` and `Release.gpg` hold a
+/// signature from each key in `signing_keys`. See [sign_release()].
+pub async fn write_signed_release_with_keys<K, PW>(
+    writer: &dyn RepositoryWriter,
+    distribution_path: &str,
+    release: &str,
+    signing_keys: &[(&K, PW)],
+    progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+) -> Result<Vec<RepositoryWrite<'static>>>
+where
+    K: SecretKeyTrait,
+    PW: Fn() -> String,
+{
+    let distribution_path = distribution_path.trim_matches('/');
+    let (inrelease, release_gpg) = sign_release(release, signing_keys)?;
+
+    let writes = vec![
+        (
+            format!("{}/Release", distribution_path),
+            release.to_string(),
+        ),
+        (format!("{}/InRelease", distribution_path), inrelease),
+        (format!("{}/Release.gpg", distribution_path), release_gpg),
+    ];
+
+    write_files(writer, writes, progress_cb).await
+}
+
+async fn write_files(
+    writer: &dyn RepositoryWriter,
+    writes: Vec<(String, String)>,
+    progress_cb: &Option<Box<dyn Fn(PublishEvent) + Sync>>,
+) -> Result<Vec<RepositoryWrite<'static>>> {
+    let mut written = vec![];
+
+    for (path, content) in writes {
+        if let Some(cb) = progress_cb {
+            cb(PublishEvent::IndexFileToWrite(path.clone()));
+        }
+
+        let write = writer
+            .write_path(
+                path.into(),
+                Box::pin(futures::io::Cursor::new(content.into_bytes())),
+            )
+            .await?;
+
+        if let Some(cb) = progress_cb {
+            cb(PublishEvent::IndexFileWritten(
+                write.path.to_string(),
+                write.bytes_written,
+            ));
+        }
+
+        written.push(RepositoryWrite {
+            path: write.path.into_owned().into(),
+            bytes_written: write.bytes_written,
+        });
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            repository::{
+                builder::{RepositoryBuilder, NO_PROGRESS_CB},
+                filesystem::{FilesystemRepositoryReader, FilesystemRepositoryWriter},
+                release::ReleaseFile,
+            },
+            signing_key::{create_self_signed_key, signing_secret_key_params_builder},
+        },
+        tempfile::TempDir,
+    };
+
+    #[tokio::test]
+    async fn resign_keep_existing() -> Result<()> {
+        let td = TempDir::new()?;
+        let writer = FilesystemRepositoryWriter::new(td.path());
+        let reader = FilesystemRepositoryReader::new(td.path());
+
+        let old_key = create_self_signed_key(
+            signing_secret_key_params_builder("old@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?
+        .0;
+        let new_key = create_self_signed_key(
+            signing_secret_key_params_builder("new@example.com")
+                .build()
+                .unwrap(),
+            String::new,
+        )?
+        .0;
+
+        let builder = RepositoryBuilder::new_recommended(
+            ["amd64"].into_iter(),
+            ["main"].into_iter(),
+            "suite",
+            "codename",
+        );
+        builder
+            .publish_indices(
+                &writer,
+                Some("dists/dist"),
+                1,
+                &NO_PROGRESS_CB,
+                Some((&old_key, String::new)),
+            )
+            .await?;
+
+        let release_before = std::fs::read(td.path().join("dists/dist/Release"))?;
+
+        let writes = resign_distribution(
+            &reader,
+            &writer,
+            "dists/dist",
+            (&new_key, String::new),
+            true,
+            &None,
+        )
+        .await?;
+        assert_eq!(
+            writes.iter().map(|w| w.path.as_ref()).collect::<Vec<_>>(),
+            vec!["dists/dist/InRelease", "dists/dist/Release.gpg"]
+        );
+
+        assert_eq!(
+            std::fs::read(td.path().join("dists/dist/Release"))?,
+            release_before
+        );
+
+        let inrelease = ReleaseFile::from_armored_reader(std::io::Cursor::new(std::fs::read(
+            td.path().join("dists/dist/InRelease"),
+        )?))?;
+        let signatures = inrelease.signatures().unwrap();
+        assert_eq!(signatures.iter_signatures().count(), 2);
+        signatures.verify(&old_key)?;
+        signatures.verify(&new_key)?;
+
+        let detached =
+            parse_armored_signatures(&std::fs::read(td.path().join("dists/dist/Release.gpg"))?)?;
+        assert_eq!(detached.len(), 1);
+        detached[0].verify(&new_key, &release_before[..])?;
+
+        resign_distribution(
+            &reader,
+            &writer,
+            "dists/dist",
+            (&new_key, String::new),
+            false,
+            &None,
+        )
+        .await?;
+
+        let inrelease = ReleaseFile::from_armored_reader(std::io::Cursor::new(std::fs::read(
+            td.path().join("dists/dist/InRelease"),
+        )?))?;
+        let signatures = inrelease.signatures().unwrap();
+        assert_eq!(signatures.iter_signatures().count(), 1);
+        assert!(signatures.verify(&old_key).is_err());
+
+        Ok(())
+    }
+    #[test]
+    fn cleartext_gpg_roundtrip() -> Result<()> {
+        // Produced by `gpg --clearsign`. The text has trailing whitespace and a line
+        // starting with `-----`.
+        let data = include_bytes!("../testdata/inrelease-csf");
+        let (key, _) = pgp::SignedPublicKey::from_armor_single(Cursor::new(include_bytes!(
+            "../testdata/csf-key.asc"
+        )))?;
+
+        // pgp_cleartext hashes trailing whitespace, which GnuPG ignores.
+        let lenient = ReleaseFile::from_armored_reader(Cursor::new(data))?;
+        assert!(lenient.signatures().unwrap().verify(&key).is_err());
+
+        let (text, signatures) = parse_cleartext_signed_document_strict(data)?;
+        assert!(text.starts_with("Origin: Test\nLabel: Test  \nSuite: stable\t\n"));
+        assert!(text.contains("\n-----Dashes: not armor\n"));
+        signatures[0].verify(&key, Cursor::new(canonical_cleartext(&text)))?;
+
+        let strict = ReleaseFile::from_armored_reader_strict(Cursor::new(data))?;
+        assert_eq!(strict.signatures().unwrap().verify(&key)?, 1);
+        assert_eq!(strict.label(), Some("Test"));
+        assert_eq!(strict.field_str("-----Dashes"), Some("not armor"));
+
+        let crlf = String::from_utf8(data.to_vec())
+            .unwrap()
+            .replace('\n', "\r\n");
+        let strict = ReleaseFile::from_armored_reader_strict(Cursor::new(crlf.as_bytes()))?;
+        assert_eq!(strict.signatures().unwrap().verify(&key)?, 1);
+
+        let canonical = cleartext_signed_document(&text, &signatures)?;
+        assert_eq!(canonical, canonicalize_cleartext_signed_document(data)?);
+        assert!(canonical.contains("\nFrom: the start\n- -----Dashes: not armor\n"));
+        let (reparsed, _) = parse_cleartext_signed_document_strict(canonical.as_bytes())?;
+        assert_eq!(canonical_cleartext(&reparsed), canonical_cleartext(&text));
+
+        let data = String::from_utf8(data.to_vec()).unwrap();
+        for (from, to) in [
+            ("- -----Dashes", "-----Dashes"),
+            ("Hash: SHA256", "Hash: SHA256\nComment: hello"),
+            ("Hash: SHA256", "Hash: SHA512"),
+            ("-----BEGIN PGP SIGNATURE-----", ""),
+        ] {
+            assert!(matches!(
+                parse_cleartext_signed_document_strict(data.replace(from, to).as_bytes()),
+                Err(DebianError::CleartextMalformed(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_release_multiple_keys() -> Result<()> {
+        let keys = ["old@example.com", "new@example.com"]
+            .into_iter()
+            .map(|email| {
+                create_self_signed_key(
+                    signing_secret_key_params_builder(email).build().unwrap(),
+                    String::new,
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let release = "Origin: test\nSuite: stable\n";
+        let (inrelease, release_gpg) = sign_release(
+            release,
+            &keys
+                .iter()
+                .map(|(key, _)| (key, String::new))
+                .collect::<Vec<_>>(),
+        )?;
+
+        let inrelease =
+            ReleaseFile::from_armored_reader(std::io::Cursor::new(inrelease.into_bytes()))?;
+        let signatures = inrelease.signatures().unwrap();
+        let detached = parse_armored_signatures(release_gpg.as_bytes())?;
+        assert_eq!(detached.len(), 2);
+
+        for ((_, public_key), detached) in keys.iter().zip(&detached) {
+            assert_eq!(signatures.verify(public_key)?, 1);
+            detached.verify(public_key, release.as_bytes())?;
+        }
+
+        assert!(matches!(
+            sign_release(
+                release,
+                &Vec::<(&pgp::SignedSecretKey, fn() -> String)>::new()
+            ),
+            Err(DebianError::ReleaseNoSignatures)
+        ));
+
+        Ok(())
+    }
+}
@@ -102,4 +102,12 @@ impl RepositoryWriter for SinkWriter {
             bytes_written,
         })
     }
+
+    async fn list_paths(&self, _prefix: &str) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    async fn delete_path(&self, _path: &str) -> Result<()> {
+        Ok(())
+    }
 }
@@ -0,0 +1,1207 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Installation set resolution.
+
+[InstallResolver] computes the complete set of binary packages needed to install a set of
+requested packages. Unlike [crate::dependency_resolution::DependencyResolver], which
+reports every candidate satisfying a dependency, the resolver picks exactly one version of
+each package such that the `Pre-Depends` and `Depends` of every picked package are
+satisfied.
+
+Alternatives are tried in the order they are declared. For each alternative, real packages
+are preferred over packages providing it virtually and higher versions are preferred over
+lower ones. Providers of virtual packages are ordered by a [ProvidesIndex].
+
+Two [ResolverBackend]s are available. The default backtracking search tries the next
+candidate when a choice leads to an unsatisfiable requirement. Candidates outside the
+[VersionConstraint] demanded by pending requirements on their package are pruned up front.
+It is fast but ignores `Conflicts` and `Breaks` and reports failures as an [Unsatisfiable]
+via [DebianError::DependencyUnsatisfiable].
+
+The SAT backend encodes the problem as boolean satisfiability, including `Conflicts` and
+`Breaks`, and solves it with conflict-driven clause learning. It finds a solution whenever
+one exists. Failures are reported as a minimal set of conflicting constraints via
+[DebianError::DependencyUnsatisfiableCore].
+
+[InstallResolver::set_include_essential()] makes every resolution also install the
+`Essential` packages, as they are present on every system. Packages conflicting with them
+then can't be installed, as that would require removing an essential package.
+
+Either way, the result is an [InstallPlan] ordering packages so dependencies come before
+the packages depending on them.
+*/
+
+mod sat;
+
+use {
+    crate::{
+        binary_package_control::BinaryPackageControlFile,
+        binary_package_list::BinaryPackageList,
+        dependency::{
+            BinaryDependency, DependencyList, DependencyVariants, SingleDependency,
+            VersionConstraint,
+        },
+        dependency_resolution::BinaryPackageDependencySource,
+        error::{DebianError, Result},
+        package_version::PackageVersion,
+        provides::{ProviderTieBreaker, ProvidesIndex},
+    },
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    },
+};
+
+/// Dependency fields that must be satisfied to install a package, in evaluation order.
+const INSTALL_FIELDS: [BinaryDependency; 2] =
+    [BinaryDependency::PreDepends, BinaryDependency::Depends];
+
+/// Strip an architecture qualifier like `:any` from a dependency's package name.
+fn unqualified(dep: &SingleDependency) -> Cow<'_, SingleDependency> {
+    match dep.package.split_once(':') {
+        Some((name, _)) => {
+            let mut dep = dep.clone();
+            dep.package = name.to_string();
+            Cow::Owned(dep)
+        }
+        None => Cow::Borrowed(dep),
+    }
+}
+
+/// Why a requirement could not be satisfied.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnsatisfiableReason {
+    /// No known package satisfies any alternative.
+    NotFound,
+    /// Packages satisfying the requirement exist but a different version of each was
+    /// already selected.
+    ///
+    /// Holds the name and selected version of the conflicting packages.
+    Conflicts(Vec<(String, PackageVersion)>),
+    /// Packages satisfying the requirement exist but none is within the version range
+    /// demanded by other pending requirements.
+    ///
+    /// Holds the package names and required ranges.
+    OutsidePendingRange(Vec<(String, VersionConstraint)>),
+}
+
+/// Explanation of why a set of packages cannot be installed.
+#[derive(Clone, Debug)]
+pub struct Unsatisfiable {
+    /// The requirement that could not be satisfied.
+    pub requirement: DependencyVariants,
+    /// The control field the requirement came from. `None` for requested packages.
+    pub field: Option<BinaryDependency>,
+    /// Packages leading to the requirement, starting at a requested package.
+    ///
+    /// The last entry declared the requirement. Empty for requested packages.
+    pub chain: Vec<(String, PackageVersion)>,
+    /// Why the requirement could not be satisfied.
+    pub reason: UnsatisfiableReason,
+}
+
+impl std::fmt::Display for Unsatisfiable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.requirement)?;
+
+        if let Some(field) = self.field {
+            write!(
+                f,
+                " ({} of {})",
+                field,
+                self.chain
+                    .iter()
+                    .map(|(name, version)| format!("{} {}", name, version))
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            )?;
+        }
+
+        match &self.reason {
+            UnsatisfiableReason::NotFound => write!(f, ": no package satisfies it"),
+            UnsatisfiableReason::Conflicts(packages) => write!(
+                f,
+                ": conflicts with selected {}",
+                packages
+                    .iter()
+                    .map(|(name, version)| format!("{} {}", name, version))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            UnsatisfiableReason::OutsidePendingRange(ranges) => write!(
+                f,
+                ": other requirements restrict {}",
+                ranges
+                    .iter()
+                    .map(|(name, range)| format!("{} to {}", name, range))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// A package name and version.
+pub type PackageIdentity = (String, PackageVersion);
+
+fn format_identity((name, version): &PackageIdentity) -> String {
+    format!("{} {}", name, version)
+}
+
+/// A constraint in an [UnsatisfiableCore].
+#[derive(Clone, Debug, PartialEq)]
+pub enum CoreConstraint {
+    /// A requested dependency expression must be satisfied.
+    Requested(DependencyVariants),
+    /// A package requires another.
+    Depends {
+        /// The requiring package.
+        package: PackageIdentity,
+        /// The field the requirement is from.
+        field: BinaryDependency,
+        /// The requirement.
+        requirement: DependencyVariants,
+    },
+    /// A package conflicts with or breaks another.
+    Conflicts {
+        /// The package declaring the conflict.
+        package: PackageIdentity,
+        /// `Conflicts` or `Breaks`.
+        field: &'static str,
+        /// The expression matching `other`.
+        conflict: SingleDependency,
+        /// The conflicting package.
+        other: PackageIdentity,
+    },
+    /// Only one version of a package can be installed.
+    SingleVersion {
+        /// The package name.
+        package: String,
+        /// Two versions that cannot both be installed.
+        versions: (PackageVersion, PackageVersion),
+    },
+}
+
+impl std::fmt::Display for CoreConstraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Requested(requirement) => write!(f, "{} is requested", requirement),
+            Self::Depends {
+                package,
+                field,
+                requirement,
+            } => write!(
+                f,
+                "{} {} on {}",
+                format_identity(package),
+                field,
+                requirement
+            ),
+            Self::Conflicts {
+                package,
+                field,
+                conflict,
+                other,
+            } => write!(
+                f,
+                "{} {} {}, matching {}",
+                format_identity(package),
+                field,
+                conflict,
+                format_identity(other)
+            ),
+            Self::SingleVersion { package, versions } => write!(
+                f,
+                "{} {} and {} cannot both be installed",
+                package, versions.0, versions.1
+            ),
+        }
+    }
+}
+
+/// A minimal set of constraints that cannot be satisfied together.
+///
+/// Removing any one constraint makes the remaining constraints satisfiable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnsatisfiableCore {
+    /// The conflicting constraints.
+    pub constraints: Vec<CoreConstraint>,
+}
+
+impl std::fmt::Display for UnsatisfiableCore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.constraints
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        )
+    }
+}
+
+/// The algorithm used by [InstallResolver::resolve()].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ResolverBackend {
+    /// Depth-first search with chronological backtracking.
+    ///
+    /// `Conflicts` and `Breaks` are not considered.
+    #[default]
+    Backtracking,
+    /// Boolean satisfiability with conflict-driven clause learning.
+    ///
+    /// Honors `Conflicts` and `Breaks` and reports minimal unsatisfiable cores.
+    Sat,
+}
+
+/// A package in an [InstallPlan].
+#[derive(Clone, Debug)]
+pub struct PlannedPackage<'file, 'data: 'file> {
+    /// The package to install.
+    pub package: &'file BinaryPackageControlFile<'data>,
+    /// Whether the package satisfies a requested dependency expression.
+    pub requested: bool,
+    /// Dependencies of other packages in the plan that this package satisfies.
+    pub required_by: Vec<BinaryPackageDependencySource<'file, 'data>>,
+}
+
+/// The result of a successful resolution.
+///
+/// Packages are ordered so each package comes after the packages it depends on. Packages
+/// in dependency cycles are ordered arbitrarily relative to each other.
+#[derive(Clone, Debug, Default)]
+pub struct InstallPlan<'file, 'data: 'file> {
+    packages: Vec<PlannedPackage<'file, 'data>>,
+}
+
+impl<'file, 'data: 'file> InstallPlan<'file, 'data> {
+    /// Packages to install, in installation order.
+    pub fn packages(&self) -> impl Iterator<Item = &'file BinaryPackageControlFile<'data>> + '_ {
+        self.packages.iter().map(|p| p.package)
+    }
+
+    /// Packages to install with annotations of why they are present, in installation order.
+    pub fn planned(&self) -> &[PlannedPackage<'file, 'data>] {
+        &self.packages
+    }
+
+    /// The number of packages to install.
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    /// Whether there is nothing to install.
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Candidate<'file, 'data: 'file> {
+    file: &'file BinaryPackageControlFile<'data>,
+    name: String,
+    version: PackageVersion,
+    requirements: Vec<(BinaryDependency, DependencyVariants)>,
+    /// `Conflicts` and `Breaks` expressions.
+    conflicts: Vec<(&'static str, SingleDependency)>,
+}
+
+/// A requirement waiting to be satisfied.
+///
+/// `source` is the candidate index and field the requirement came from.
+#[derive(Clone, Debug)]
+struct Requirement {
+    variants: DependencyVariants,
+    source: Option<(usize, BinaryDependency)>,
+}
+
+/// Records that candidate `target` satisfies `constraint` of a requirement.
+#[derive(Clone, Debug)]
+struct Edge {
+    source: Option<(usize, BinaryDependency)>,
+    constraint: SingleDependency,
+    target: usize,
+}
+
+/// Mutable solver state. Everything is append-only so it can be rolled back by truncation.
+#[derive(Default)]
+struct State {
+    /// Package name to selected candidate and the candidate that first required it.
+    selected: HashMap<String, (usize, Option<usize>)>,
+    /// Package names in order of selection.
+    selection_log: Vec<String>,
+    queue: Vec<Requirement>,
+    position: usize,
+    edges: Vec<Edge>,
+}
+
+/// A requirement having multiple candidates, to return to when backtracking.
+struct ChoicePoint {
+    candidates: Vec<(usize, SingleDependency)>,
+    next: usize,
+    position: usize,
+    selection_len: usize,
+    queue_len: usize,
+    edges_len: usize,
+}
+
+/// Computes installation sets from indexed binary packages.
+#[derive(Clone, Debug)]
+pub struct InstallResolver<'file, 'data: 'file> {
+    architecture: String,
+    backend: ResolverBackend,
+    include_essential: bool,
+    candidates: Vec<Candidate<'file, 'data>>,
+    /// Package name to candidate indices, highest version first.
+    packages: HashMap<String, Vec<usize>>,
+    /// Provided package name to candidate indices and provided versions.
+    provides: ProvidesIndex<'file, 'data>,
+    /// Control file to candidate index.
+    indices: HashMap<&'file BinaryPackageControlFile<'data>, usize>,
+}
+
+impl<'file, 'data: 'file> InstallResolver<'file, 'data> {
+    /// Construct an instance resolving packages for the given architecture.
+    ///
+    /// Only packages for this architecture or `all` are considered.
+    pub fn new(architecture: impl ToString) -> Self {
+        Self {
+            architecture: architecture.to_string(),
+            backend: ResolverBackend::default(),
+            include_essential: false,
+            candidates: vec![],
+            packages: HashMap::new(),
+            provides: ProvidesIndex::default(),
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Set the algorithm used to resolve packages.
+    pub fn set_backend(&mut self, backend: ResolverBackend) {
+        self.backend = backend;
+    }
+
+    /// Set whether to install `Essential` packages as if they were requested.
+    ///
+    /// Essential packages are installed on every system and must not be removed. With this
+    /// enabled, plans include them and packages conflicting with them are rejected.
+    pub fn set_include_essential(&mut self, value: bool) {
+        self.include_essential = value;
+    }
+
+    /// Set the criteria used to order providers of virtual packages.
+    ///
+    /// See [ProvidesIndex::set_tie_breakers()].
+    pub fn set_provider_tie_breakers(&mut self, tie_breakers: Vec<ProviderTieBreaker>) {
+        self.provides.set_tie_breakers(tie_breakers);
+    }
+
+    /// Load all packages in a [BinaryPackageList] into the resolver.
+    pub fn load_binary_package_list(
+        &mut self,
+        list: &'file BinaryPackageList<'data>,
+    ) -> Result<()> {
+        self.load_binary_packages(list.iter())
+    }
+
+    /// Load an iterable of binary packages into the resolver.
+    ///
+    /// Packages for other architectures are ignored.
+    pub fn load_binary_packages(
+        &mut self,
+        files: impl Iterator<Item = &'file BinaryPackageControlFile<'data>>,
+    ) -> Result<()> {
+        for cf in files {
+            let arch = cf.architecture()?;
+            if arch != "all" && arch != self.architecture {
+                continue;
+            }
+
+            let fields = cf.package_dependency_fields()?;
+            let index = self.candidates.len();
+
+            let candidate = Candidate {
+                file: cf,
+                name: cf.package()?.to_string(),
+                version: cf.version()?,
+                requirements: INSTALL_FIELDS
+                    .into_iter()
+                    .filter_map(|field| fields.binary_dependency(field).map(|deps| (field, deps)))
+                    .flat_map(|(field, deps)| {
+                        deps.requirements()
+                            .map(move |variants| (field, variants.clone()))
+                    })
+                    .collect(),
+                conflicts: [("Conflicts", &fields.conflicts), ("Breaks", &fields.breaks)]
+                    .into_iter()
+                    .filter_map(|(field, deps)| deps.as_ref().map(|deps| (field, deps)))
+                    .flat_map(|(field, deps)| {
+                        deps.requirements()
+                            .flat_map(|variants| variants.iter())
+                            .map(move |dep| (field, dep.clone()))
+                    })
+                    .collect(),
+            };
+
+            self.provides.load_binary_packages(std::iter::once(cf))?;
+            self.indices.entry(cf).or_insert(index);
+
+            let entries = self.packages.entry(candidate.name.clone()).or_default();
+            let insert = entries
+                .iter()
+                .position(|i| self.candidates[*i].version < candidate.version)
+                .unwrap_or(entries.len());
+            entries.insert(insert, index);
+
+            self.candidates.push(candidate);
+        }
+
+        Ok(())
+    }
+
+    /// Candidates satisfying a dependency expression, in order of preference.
+    fn satisfying<'a>(&'a self, dep: &'a SingleDependency) -> impl Iterator<Item = usize> + 'a {
+        let concrete = self
+            .packages
+            .get(&dep.package)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(move |i| {
+                let candidate = &self.candidates[*i];
+                dep.package_satisfies(&candidate.name, &candidate.version, &self.architecture)
+            });
+
+        let provided = self
+            .provides
+            .providers_satisfying(dep)
+            .into_iter()
+            .filter_map(|provider| self.indices.get(provider.package).copied());
+
+        concrete.chain(provided)
+    }
+
+    /// Find a selected candidate satisfying a requirement.
+    fn selected_satisfying(
+        &self,
+        state: &State,
+        variants: &DependencyVariants,
+    ) -> Option<(usize, SingleDependency)> {
+        variants.iter().find_map(|dep| {
+            let dep = unqualified(dep);
+
+            let found = self.satisfying(&dep).find(|i| {
+                state
+                    .selected
+                    .get(&self.candidates[*i].name)
+                    .is_some_and(|(selected, _)| selected == i)
+            });
+
+            found.map(|i| (i, dep.into_owned()))
+        })
+    }
+
+    /// The version range a package must be in to satisfy pending requirements.
+    ///
+    /// Only requirements after the current queue position having a single alternative are
+    /// considered, as whichever version of the package is selected must satisfy them.
+    /// Packages provided virtually aren't restricted, as a provider could satisfy them.
+    fn pending_range(&self, state: &State, name: &str) -> VersionConstraint {
+        if self.provides.is_provided(name) {
+            return VersionConstraint::default();
+        }
+
+        state.queue[state.position + 1..]
+            .iter()
+            .filter_map(|requirement| match &requirement.variants[..] {
+                [dep] => Some(unqualified(dep)),
+                _ => None,
+            })
+            .filter(|dep| dep.package == name && dep.architecture_satisfied(&self.architecture))
+            .filter_map(|dep| dep.version_constraint.as_ref().map(VersionConstraint::from))
+            .fold(VersionConstraint::default(), |range, constraint| {
+                range.intersect(&constraint)
+            })
+    }
+
+    /// Find candidates that could be selected to satisfy a requirement.
+    ///
+    /// Candidates outside the [Self::pending_range()] of their package are pruned.
+    fn selectable(
+        &self,
+        state: &State,
+        variants: &DependencyVariants,
+    ) -> std::result::Result<Vec<(usize, SingleDependency)>, UnsatisfiableReason> {
+        let mut res: Vec<(usize, SingleDependency)> = vec![];
+        let mut conflicts = vec![];
+        let mut ranges: Vec<(String, VersionConstraint)> = vec![];
+
+        for dep in variants.iter() {
+            let dep = unqualified(dep);
+
+            for i in self.satisfying(&dep) {
+                let candidate = &self.candidates[i];
+
+                if let Some((selected, _)) = state.selected.get(&candidate.name) {
+                    let selected = &self.candidates[*selected];
+                    let conflict = (selected.name.clone(), selected.version.clone());
+                    if !conflicts.contains(&conflict) {
+                        conflicts.push(conflict);
+                    }
+                    continue;
+                }
+
+                let range = self.pending_range(state, &candidate.name);
+                if !range.is_satisfied_by(&candidate.version) {
+                    if !ranges.iter().any(|(name, _)| name == &candidate.name) {
+                        ranges.push((candidate.name.clone(), range));
+                    }
+                } else if !res.iter().any(|(seen, _)| *seen == i) {
+                    res.push((i, dep.clone().into_owned()));
+                }
+            }
+        }
+
+        if !res.is_empty() {
+            Ok(res)
+        } else if !conflicts.is_empty() {
+            Err(UnsatisfiableReason::Conflicts(conflicts))
+        } else if !ranges.is_empty() {
+            Err(UnsatisfiableReason::OutsidePendingRange(ranges))
+        } else {
+            Err(UnsatisfiableReason::NotFound)
+        }
+    }
+
+    /// Select a candidate to satisfy the requirement at the current queue position.
+    fn select(&self, state: &mut State, candidate: usize, constraint: SingleDependency) {
+        let requirement = &state.queue[state.position];
+        let source = requirement.source;
+
+        let entry = &self.candidates[candidate];
+        state.selected.insert(
+            entry.name.clone(),
+            (candidate, source.map(|(index, _)| index)),
+        );
+        state.selection_log.push(entry.name.clone());
+        state.edges.push(Edge {
+            source,
+            constraint,
+            target: candidate,
+        });
+        state.queue.extend(
+            entry
+                .requirements
+                .iter()
+                .map(|(field, variants)| Requirement {
+                    variants: variants.clone(),
+                    source: Some((candidate, *field)),
+                }),
+        );
+        state.position += 1;
+    }
+
+    /// Roll back state to a choice point.
+    fn restore(&self, state: &mut State, point: &ChoicePoint) {
+        while state.selection_log.len() > point.selection_len {
+            let name = state
+                .selection_log
+                .pop()
+                .expect("selection log should not be empty");
+            state.selected.remove(&name);
+        }
+        state.queue.truncate(point.queue_len);
+        state.edges.truncate(point.edges_len);
+        state.position = point.position;
+    }
+
+    /// Describe why the requirement at the current queue position can't be satisfied.
+    fn explain(&self, state: &State, reason: UnsatisfiableReason) -> Unsatisfiable {
+        let requirement = &state.queue[state.position];
+
+        let mut chain = vec![];
+        let mut current = requirement.source.map(|(index, _)| index);
+        while let Some(index) = current {
+            let candidate = &self.candidates[index];
+            chain.push((candidate.name.clone(), candidate.version.clone()));
+            current = state
+                .selected
+                .get(&candidate.name)
+                .and_then(|(_, parent)| *parent);
+        }
+        chain.reverse();
+
+        Unsatisfiable {
+            requirement: requirement.variants.clone(),
+            field: requirement.source.map(|(_, field)| field),
+            chain,
+            reason,
+        }
+    }
+
+    /// Compute the set of packages needed to install packages satisfying `requests`.
+    ///
+    /// Each entry in `requests` is a requirement like those in a `Depends` field, e.g.
+    /// `DependencyList::parse("foo (>= 1.0), bar | baz")`.
+    ///
+    /// How failures are reported depends on the [ResolverBackend].
+    pub fn resolve(&self, requests: &DependencyList) -> Result<InstallPlan<'file, 'data>> {
+        let requests = if self.include_essential {
+            let mut requests = requests.clone();
+            for name in self.essential_names() {
+                let mut variants = DependencyVariants::default();
+                variants.push(SingleDependency::parse(name)?);
+                requests.push(variants);
+            }
+
+            Cow::Owned(requests)
+        } else {
+            Cow::Borrowed(requests)
+        };
+
+        match self.backend {
+            ResolverBackend::Backtracking => self.resolve_backtracking(&requests),
+            ResolverBackend::Sat => self.resolve_sat(&requests),
+        }
+    }
+
+    /// Sorted names of loaded packages having `Essential: yes`.
+    fn essential_names(&self) -> BTreeSet<&str> {
+        self.candidates
+            .iter()
+            .filter(|candidate| candidate.file.typed_essential() == Some(true))
+            .map(|candidate| candidate.name.as_str())
+            .collect()
+    }
+
+    /// Resolve using [ResolverBackend::Backtracking].
+    ///
+    /// If no installation set exists, the returned [DebianError::DependencyUnsatisfiable]
+    /// describes an unsatisfiable requirement. Of the failures seen while backtracking, the
+    /// one depending on the fewest alternative choices is reported, as it is the most
+    /// likely to be the root cause.
+    fn resolve_backtracking(&self, requests: &DependencyList) -> Result<InstallPlan<'file, 'data>> {
+        let mut state = State {
+            queue: requests
+                .requirements()
+                .map(|variants| Requirement {
+                    variants: variants.clone(),
+                    source: None,
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let mut choices: Vec<ChoicePoint> = vec![];
+        // The failure having the fewest choice points to backtrack to.
+        let mut failure: Option<(usize, Unsatisfiable)> = None;
+
+        while state.position < state.queue.len() {
+            let variants = &state.queue[state.position].variants;
+
+            if let Some((target, constraint)) = self.selected_satisfying(&state, variants) {
+                state.edges.push(Edge {
+                    source: state.queue[state.position].source,
+                    constraint,
+                    target,
+                });
+                state.position += 1;
+                continue;
+            }
+
+            match self.selectable(&state, variants) {
+                Ok(mut candidates) => {
+                    let (candidate, constraint) = candidates.remove(0);
+
+                    if !candidates.is_empty() {
+                        choices.push(ChoicePoint {
+                            candidates,
+                            next: 0,
+                            position: state.position,
+                            selection_len: state.selection_log.len(),
+                            queue_len: state.queue.len(),
+                            edges_len: state.edges.len(),
+                        });
+                    }
+
+                    self.select(&mut state, candidate, constraint);
+                }
+                Err(reason) => {
+                    if failure
+                        .as_ref()
+                        .map_or(true, |(depth, _)| choices.len() < *depth)
+                    {
+                        failure = Some((choices.len(), self.explain(&state, reason)));
+                    }
+
+                    // Resume from the most recent choice point having untried candidates.
+                    let Some(mut point) = choices.pop() else {
+                        let (_, failure) = failure.expect("failure should be recorded");
+                        return Err(DebianError::DependencyUnsatisfiable(Box::new(failure)));
+                    };
+
+                    self.restore(&mut state, &point);
+
+                    let (candidate, constraint) = point.candidates[point.next].clone();
+                    point.next += 1;
+                    if point.next < point.candidates.len() {
+                        choices.push(point);
+                    }
+
+                    self.select(&mut state, candidate, constraint);
+                }
+            }
+        }
+
+        Ok(self.plan(&state.edges))
+    }
+
+    /// Candidates satisfying any alternative, in order of preference and without duplicates.
+    fn requirement_candidates(
+        &self,
+        variants: &DependencyVariants,
+    ) -> Vec<(usize, SingleDependency)> {
+        let mut res: Vec<(usize, SingleDependency)> = vec![];
+
+        for dep in variants.iter() {
+            let dep = unqualified(dep);
+            for i in self.satisfying(&dep) {
+                if !res.iter().any(|(seen, _)| *seen == i) {
+                    res.push((i, dep.clone().into_owned()));
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Resolve using [ResolverBackend::Sat].
+    ///
+    /// Each candidate reachable from `requests` is a boolean variable. Requirements become
+    /// clauses of the form `¬package ∨ candidate1 ∨ candidate2 ...`. `Conflicts`, `Breaks`,
+    /// and the rule that only one version of a package may be installed become clauses of
+    /// the form `¬a ∨ ¬b`.
+    fn resolve_sat(&self, requests: &DependencyList) -> Result<InstallPlan<'file, 'data>> {
+        fn variable(
+            vars: &mut HashMap<usize, usize>,
+            order: &mut Vec<usize>,
+            candidate: usize,
+        ) -> usize {
+            *vars.entry(candidate).or_insert_with(|| {
+                order.push(candidate);
+                order.len() - 1
+            })
+        }
+
+        let identity = |candidate: usize| -> PackageIdentity {
+            let candidate = &self.candidates[candidate];
+            (candidate.name.clone(), candidate.version.clone())
+        };
+
+        // Candidate index to variable and variable to candidate index.
+        let mut vars = HashMap::new();
+        let mut order = vec![];
+
+        let mut clauses = vec![];
+        let mut constraints = vec![];
+        // Requirement clauses guide decisions. Each is recorded with its source variable
+        // and field and its candidate variables and the alternatives they satisfy.
+        let mut goals = vec![];
+        let mut requirements = vec![];
+
+        let mut add_requirement = |source: Option<(usize, BinaryDependency)>,
+                                   variants: &DependencyVariants,
+                                   constraint: CoreConstraint,
+                                   vars: &mut HashMap<usize, usize>,
+                                   order: &mut Vec<usize>| {
+            let candidates = self
+                .requirement_candidates(variants)
+                .into_iter()
+                .map(|(candidate, dep)| (variable(vars, order, candidate), dep))
+                .collect::<Vec<_>>();
+
+            goals.push(clauses.len());
+            clauses.push(
+                source
+                    .map(|(var, _)| sat::Lit::negative(var))
+                    .into_iter()
+                    .chain(candidates.iter().map(|(var, _)| sat::Lit::positive(*var)))
+                    .collect::<Vec<_>>(),
+            );
+            constraints.push(constraint);
+            requirements.push((source, candidates));
+        };
+
+        for variants in requests.requirements() {
+            add_requirement(
+                None,
+                variants,
+                CoreConstraint::Requested(variants.clone()),
+                &mut vars,
+                &mut order,
+            );
+        }
+
+        let mut var = 0;
+        while var < order.len() {
+            let candidate = order[var];
+            for (field, variants) in &self.candidates[candidate].requirements {
+                add_requirement(
+                    Some((var, *field)),
+                    variants,
+                    CoreConstraint::Depends {
+                        package: identity(candidate),
+                        field: *field,
+                        requirement: variants.clone(),
+                    },
+                    &mut vars,
+                    &mut order,
+                );
+            }
+            var += 1;
+        }
+
+        for (var, candidate) in order.iter().enumerate() {
+            let entry = &self.candidates[*candidate];
+
+            for (field, dep) in &entry.conflicts {
+                let dep = unqualified(dep);
+
+                // Packages may conflict with virtual packages they provide. Other versions
+                // of the same package are handled below.
+                for other in self.satisfying(&dep) {
+                    if self.candidates[other].name == entry.name {
+                        continue;
+                    }
+                    if let Some(other_var) = vars.get(&other) {
+                        clauses.push(vec![
+                            sat::Lit::negative(var),
+                            sat::Lit::negative(*other_var),
+                        ]);
+                        constraints.push(CoreConstraint::Conflicts {
+                            package: identity(*candidate),
+                            field,
+                            conflict: dep.clone().into_owned(),
+                            other: identity(other),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut by_name: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (var, candidate) in order.iter().enumerate() {
+            by_name
+                .entry(&self.candidates[*candidate].name)
+                .or_default()
+                .push(var);
+        }
+        for (name, versions) in by_name {
+            for (i, a) in versions.iter().enumerate() {
+                for b in &versions[i + 1..] {
+                    clauses.push(vec![sat::Lit::negative(*a), sat::Lit::negative(*b)]);
+                    constraints.push(CoreConstraint::SingleVersion {
+                        package: name.to_string(),
+                        versions: (
+                            self.candidates[order[*a]].version.clone(),
+                            self.candidates[order[*b]].version.clone(),
+                        ),
+                    });
+                }
+            }
+        }
+
+        match sat::solve(order.len(), clauses.clone(), &goals) {
+            Ok(model) => {
+                let edges = requirements
+                    .into_iter()
+                    .filter(|(source, _)| source.map_or(true, |(var, _)| model[var]))
+                    .filter_map(|(source, candidates)| {
+                        candidates.into_iter().find(|(var, _)| model[*var]).map(
+                            |(var, constraint)| Edge {
+                                source: source.map(|(source, field)| (order[source], field)),
+                                constraint,
+                                target: order[var],
+                            },
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                Ok(self.plan(&edges))
+            }
+            Err(core) => {
+                let core = sat::minimize_core(order.len(), &clauses, core);
+
+                Err(DebianError::DependencyUnsatisfiableCore(Box::new(
+                    UnsatisfiableCore {
+                        constraints: core.into_iter().map(|i| constraints[i].clone()).collect(),
+                    },
+                )))
+            }
+        }
+    }
+
+    /// Order selected packages so dependencies come first.
+    fn plan(&self, edges: &[Edge]) -> InstallPlan<'file, 'data> {
+        let mut dependencies: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut required_by: HashMap<usize, Vec<BinaryPackageDependencySource>> = HashMap::new();
+        let mut requested = vec![];
+
+        for edge in edges {
+            match edge.source {
+                Some((source, field)) => {
+                    dependencies.entry(source).or_default().push(edge.target);
+                    required_by.entry(edge.target).or_default().push(
+                        BinaryPackageDependencySource {
+                            package: self.candidates[source].file,
+                            field,
+                            constraint: edge.constraint.clone(),
+                        },
+                    );
+                }
+                None => requested.push(edge.target),
+            }
+        }
+
+        // Depth-first post-order traversal from requested packages. Packages currently
+        // being visited are marked seen, which breaks cycles.
+        fn visit(
+            index: usize,
+            dependencies: &HashMap<usize, Vec<usize>>,
+            seen: &mut HashSet<usize>,
+            order: &mut Vec<usize>,
+        ) {
+            if !seen.insert(index) {
+                return;
+            }
+            for dep in dependencies.get(&index).into_iter().flatten() {
+                visit(*dep, dependencies, seen, order);
+            }
+            order.push(index);
+        }
+
+        let mut seen = HashSet::new();
+        let mut order = vec![];
+        for index in &requested {
+            visit(*index, &dependencies, &mut seen, &mut order);
+        }
+
+        InstallPlan {
+            packages: order
+                .into_iter()
+                .map(|index| PlannedPackage {
+                    package: self.candidates[index].file,
+                    requested: requested.contains(&index),
+                    required_by: required_by.remove(&index).unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::control::ControlParagraph};
+
+    fn package(fields: &[(&str, &str)]) -> BinaryPackageControlFile<'static> {
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Architecture".into(), "amd64".into());
+        for (name, value) in fields {
+            para.set_field_from_string(name.to_string().into(), value.to_string().into());
+        }
+
+        para.into()
+    }
+
+    #[test]
+    fn resolve() -> Result<()> {
+        let mut list = BinaryPackageList::default();
+        list.push(package(&[
+            ("Package", "app"),
+            ("Version", "1.0"),
+            ("Depends", "libfoo (>= 2), mta | mail-transport-agent"),
+            ("Pre-Depends", "base"),
+        ]));
+        list.push(package(&[("Package", "libfoo"), ("Version", "1.0")]));
+        list.push(package(&[
+            ("Package", "libfoo"),
+            ("Version", "2.0"),
+            ("Depends", "base (>= 3)"),
+        ]));
+        list.push(package(&[("Package", "base"), ("Version", "3.0")]));
+        list.push(package(&[
+            ("Package", "postfix"),
+            ("Version", "1.0"),
+            ("Provides", "mail-transport-agent"),
+            ("Depends", "missing"),
+        ]));
+        list.push(package(&[
+            ("Package", "exim"),
+            ("Version", "1.0"),
+            ("Provides", "mail-transport-agent"),
+        ]));
+        list.push(package(&[
+            ("Package", "old"),
+            ("Version", "1.0"),
+            ("Depends", "libfoo (<< 2)"),
+        ]));
+        let mut other = BinaryPackageList::default();
+        other.push(package(&[
+            ("Package", "base"),
+            ("Version", "9.0"),
+            ("Architecture", "arm64"),
+        ]));
+
+        let mut resolver = InstallResolver::new("amd64");
+        resolver.load_binary_package_list(&list)?;
+        resolver.load_binary_package_list(&other)?;
+
+        // postfix is tried first but its dependency is missing, so exim is used instead.
+        let plan = resolver.resolve(&DependencyList::parse("app")?)?;
+        assert_eq!(
+            plan.packages()
+                .map(|cf| format!("{} {}", cf.package().unwrap(), cf.version_str().unwrap()))
+                .collect::<Vec<_>>(),
+            vec!["base 3.0", "libfoo 2.0", "exim 1.0", "app 1.0"]
+        );
+        assert!(plan.planned()[3].requested);
+        assert_eq!(plan.planned()[0].required_by.len(), 2);
+
+        let err = resolver
+            .resolve(&DependencyList::parse("app, old")?)
+            .unwrap_err();
+        let DebianError::DependencyUnsatisfiable(explanation) = err else {
+            panic!("expected unsatisfiable error");
+        };
+        assert_eq!(explanation.requirement.to_string(), "libfoo (>= 2)");
+        assert_eq!(
+            explanation.chain,
+            vec![("app".to_string(), PackageVersion::parse("1.0")?)]
+        );
+        assert_eq!(
+            explanation.reason,
+            UnsatisfiableReason::OutsidePendingRange(vec![(
+                "libfoo".to_string(),
+                VersionConstraint::parse("<< 2")?
+            )])
+        );
+
+        let err = resolver
+            .resolve(&DependencyList::parse("postfix")?)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unsatisfiable dependencies: missing (Depends of postfix 1.0): no package satisfies it"
+        );
+
+        // The pending requirement prunes libfoo 2.0.
+        let plan = resolver.resolve(&DependencyList::parse("libfoo, libfoo (<< 2)")?)?;
+        assert_eq!(plan.planned()[0].package.version_str()?, "1.0");
+
+        let err = resolver
+            .resolve(&DependencyList::parse("libfoo (>= 2), libfoo (<< 2)")?)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unsatisfiable dependencies: libfoo (>= 2): other requirements restrict libfoo to << 2"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_sat() -> Result<()> {
+        let mut list = BinaryPackageList::default();
+        list.push(package(&[
+            ("Package", "app"),
+            ("Version", "1.0"),
+            ("Depends", "editor, tools"),
+        ]));
+        list.push(package(&[
+            ("Package", "vim"),
+            ("Version", "1.0"),
+            ("Priority", "standard"),
+            ("Provides", "editor"),
+            ("Conflicts", "tools (<< 2)"),
+        ]));
+        list.push(package(&[
+            ("Package", "nano"),
+            ("Version", "1.0"),
+            ("Provides", "editor"),
+        ]));
+        list.push(package(&[("Package", "tools"), ("Version", "1.0")]));
+        list.push(package(&[
+            ("Package", "legacy"),
+            ("Version", "1.0"),
+            ("Breaks", "app"),
+        ]));
+        list.push(package(&[
+            ("Package", "base"),
+            ("Version", "1.0"),
+            ("Essential", "yes"),
+        ]));
+        list.push(package(&[
+            ("Package", "rebase"),
+            ("Version", "1.0"),
+            ("Conflicts", "base"),
+        ]));
+
+        let mut resolver = InstallResolver::new("amd64");
+        resolver.load_binary_package_list(&list)?;
+
+        // The backtracking backend ignores the conflict.
+        let plan = resolver.resolve(&DependencyList::parse("app")?)?;
+        assert_eq!(
+            plan.packages()
+                .map(|cf| cf.package().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["vim", "tools", "app"]
+        );
+
+        resolver.set_backend(ResolverBackend::Sat);
+        let plan = resolver.resolve(&DependencyList::parse("app")?)?;
+        assert_eq!(
+            plan.packages()
+                .map(|cf| cf.package().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["nano", "tools", "app"]
+        );
+        assert_eq!(
+            plan.planned()[0].required_by[0].constraint.package,
+            "editor"
+        );
+
+        let err = resolver
+            .resolve(&DependencyList::parse("app, legacy")?)
+            .unwrap_err();
+        let DebianError::DependencyUnsatisfiableCore(core) = err else {
+            panic!("expected unsatisfiable core");
+        };
+        assert_eq!(
+            core.to_string(),
+            "app is requested; legacy is requested; legacy 1.0 Breaks app, matching app 1.0"
+        );
+
+        // Installing rebase would remove an essential package.
+        assert!(resolver.resolve(&DependencyList::parse("rebase")?).is_ok());
+        resolver.set_include_essential(true);
+        let plan = resolver.resolve(&DependencyList::parse("app")?)?;
+        assert!(plan.packages().any(|cf| cf.package().unwrap() == "base"));
+        let err = resolver
+            .resolve(&DependencyList::parse("rebase")?)
+            .unwrap_err();
+        let DebianError::DependencyUnsatisfiableCore(core) = err else {
+            panic!("expected unsatisfiable core");
+        };
+        assert_eq!(
+            core.to_string(),
+            "rebase is requested; base is requested; rebase 1.0 Conflicts base, matching base 1.0"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! A small CDCL boolean satisfiability solver.
+
+This implements conflict-driven clause learning with two watched literals and first-UIP
+conflict analysis. It is sized for dependency problems, not general SAT workloads: there
+are no restarts and learned clauses are never deleted.
+
+Decisions are guided by *goal* clauses, which are visited in order. The first goal not
+yet satisfied whose negative literals are all false has its first unassigned positive
+literal set to true. Once no goal applies, remaining variables are set to false. For
+dependency problems this means preferred candidates are tried first and packages aren't
+installed unless something requires them.
+
+When a problem is unsatisfiable, the input clauses involved in the refutation are
+reported. [minimize_core()] reduces these to a minimal unsatisfiable subset.
+*/
+
+use std::collections::{BTreeSet, HashSet};
+
+/// A boolean variable or its negation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Lit(usize);
+
+impl Lit {
+    /// The literal that is true when `var` is true.
+    pub fn positive(var: usize) -> Self {
+        Self(var * 2)
+    }
+
+    /// The literal that is true when `var` is false.
+    pub fn negative(var: usize) -> Self {
+        Self(var * 2 + 1)
+    }
+
+    /// The variable this literal refers to.
+    pub fn var(self) -> usize {
+        self.0 / 2
+    }
+
+    /// Whether this literal is true when its variable is true.
+    pub fn is_positive(self) -> bool {
+        self.0 % 2 == 0
+    }
+}
+
+impl std::ops::Not for Lit {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(self.0 ^ 1)
+    }
+}
+
+struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    /// Clauses each learned clause was derived from. Empty for input clauses.
+    antecedents: Vec<Vec<usize>>,
+    input_count: usize,
+    /// Goal clauses with literals in preference order.
+    ///
+    /// Watching reorders literals in `clauses`, so copies are retained.
+    goals: Vec<Vec<Lit>>,
+    /// Clauses watching each literal, indexed by literal.
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    /// Trail length at the start of each decision level.
+    trail_limits: Vec<usize>,
+    queue_head: usize,
+}
+
+fn value(values: &[Option<bool>], lit: Lit) -> Option<bool> {
+    values[lit.var()].map(|v| v == lit.is_positive())
+}
+
+impl Solver {
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.values[var] = Some(lit.is_positive());
+        self.levels[var] = self.trail_limits.len();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    fn watch(&mut self, clause: usize) {
+        let lits = &self.clauses[clause];
+        if lits.len() > 1 {
+            let (a, b) = (lits[0], lits[1]);
+            self.watches[a.0].push(clause);
+            self.watches[b.0].push(clause);
+        }
+    }
+
+    /// Propagate assignments on the trail, returning a conflicting clause, if any.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.queue_head < self.trail.len() {
+            let falsified = !self.trail[self.queue_head];
+            self.queue_head += 1;
+
+            let mut watching = std::mem::take(&mut self.watches[falsified.0]);
+            let mut i = 0;
+
+            while i < watching.len() {
+                let clause = watching[i];
+                let lits = &mut self.clauses[clause];
+
+                // Keep the falsified literal at index 1.
+                if lits[0] == falsified {
+                    lits.swap(0, 1);
+                }
+
+                if value(&self.values, lits[0]) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+
+                if let Some(k) =
+                    (2..lits.len()).find(|k| value(&self.values, lits[*k]) != Some(false))
+                {
+                    lits.swap(1, k);
+                    self.watches[lits[1].0].push(clause);
+                    watching.swap_remove(i);
+                    continue;
+                }
+
+                let first = lits[0];
+                if value(&self.values, first) == Some(false) {
+                    self.watches[falsified.0] = watching;
+                    return Some(clause);
+                }
+
+                self.enqueue(first, Some(clause));
+                i += 1;
+            }
+
+            self.watches[falsified.0] = watching;
+        }
+
+        None
+    }
+
+    /// Derive a learned clause from a conflict.
+    ///
+    /// Returns the clause, asserting literal first, the level to backjump to, and the
+    /// clauses it was derived from.
+    fn analyze(&self, conflict: usize) -> (Vec<Lit>, usize, Vec<usize>) {
+        let level = self.trail_limits.len();
+        let mut seen = vec![false; self.values.len()];
+        let mut learned = vec![Lit(0)];
+        let mut antecedents = vec![];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut index = self.trail.len();
+
+        loop {
+            antecedents.push(clause);
+
+            for lit in &self.clauses[clause] {
+                let var = lit.var();
+                if seen[var] {
+                    continue;
+                }
+                seen[var] = true;
+
+                if self.levels[var] == 0 {
+                    antecedents.extend(self.reasons[var]);
+                } else if self.levels[var] == level {
+                    pending += 1;
+                } else {
+                    learned.push(*lit);
+                }
+            }
+
+            let lit = loop {
+                index -= 1;
+                let lit = self.trail[index];
+                if seen[lit.var()] && self.levels[lit.var()] == level {
+                    break lit;
+                }
+            };
+
+            pending -= 1;
+            if pending == 0 {
+                learned[0] = !lit;
+                break;
+            }
+
+            clause = self.reasons[lit.var()].expect("implied literal should have a reason");
+        }
+
+        let mut backjump = 0;
+        if let Some((i, _)) = learned
+            .iter()
+            .enumerate()
+            .skip(1)
+            .max_by_key(|(_, lit)| self.levels[lit.var()])
+        {
+            learned.swap(1, i);
+            backjump = self.levels[learned[1].var()];
+        }
+
+        (learned, backjump, antecedents)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        let limit = self.trail_limits[level];
+        for lit in self.trail.drain(limit..) {
+            self.values[lit.var()] = None;
+            self.reasons[lit.var()] = None;
+        }
+        self.trail_limits.truncate(level);
+        self.queue_head = self.trail.len();
+    }
+
+    fn decide(&self) -> Option<Lit> {
+        for goal in &self.goals {
+            if goal
+                .iter()
+                .any(|lit| value(&self.values, *lit) == Some(true))
+            {
+                continue;
+            }
+            if goal
+                .iter()
+                .any(|lit| !lit.is_positive() && value(&self.values, *lit).is_none())
+            {
+                continue;
+            }
+            if let Some(lit) = goal
+                .iter()
+                .find(|lit| lit.is_positive() && value(&self.values, **lit).is_none())
+            {
+                return Some(*lit);
+            }
+        }
+
+        self.values
+            .iter()
+            .position(|v| v.is_none())
+            .map(Lit::negative)
+    }
+
+    /// Input clauses involved in deriving that `clause` is false at level 0.
+    fn core(&self, clause: usize) -> Vec<usize> {
+        let mut res = BTreeSet::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![clause];
+
+        while let Some(clause) = stack.pop() {
+            if !visited.insert(clause) {
+                continue;
+            }
+
+            if clause < self.input_count {
+                res.insert(clause);
+            } else {
+                stack.extend(&self.antecedents[clause]);
+            }
+
+            for lit in &self.clauses[clause] {
+                let var = lit.var();
+                if self.values[var].is_some() && self.levels[var] == 0 {
+                    stack.extend(self.reasons[var].filter(|reason| *reason != clause));
+                }
+            }
+        }
+
+        res.into_iter().collect()
+    }
+}
+
+/// Solve a satisfiability problem.
+///
+/// Clauses are disjunctions of literals over variables `0..var_count`. `goals` are indices
+/// of clauses guiding decisions, in priority order.
+///
+/// Returns the value of each variable or the sorted indices of clauses forming an
+/// unsatisfiable subset.
+pub(crate) fn solve(
+    var_count: usize,
+    clauses: Vec<Vec<Lit>>,
+    goals: &[usize],
+) -> std::result::Result<Vec<bool>, Vec<usize>> {
+    if let Some(empty) = clauses.iter().position(|lits| lits.is_empty()) {
+        return Err(vec![empty]);
+    }
+
+    let mut solver = Solver {
+        goals: goals.iter().map(|i| clauses[*i].clone()).collect(),
+        antecedents: vec![vec![]; clauses.len()],
+        input_count: clauses.len(),
+        clauses,
+        watches: vec![vec![]; var_count * 2],
+        values: vec![None; var_count],
+        levels: vec![0; var_count],
+        reasons: vec![None; var_count],
+        trail: vec![],
+        trail_limits: vec![],
+        queue_head: 0,
+    };
+
+    for clause in 0..solver.input_count {
+        solver.watch(clause);
+    }
+
+    for clause in 0..solver.input_count {
+        if let [lit] = solver.clauses[clause][..] {
+            match value(&solver.values, lit) {
+                Some(false) => return Err(solver.core(clause)),
+                Some(true) => {}
+                None => solver.enqueue(lit, Some(clause)),
+            }
+        }
+    }
+
+    loop {
+        if let Some(conflict) = solver.propagate() {
+            if solver.trail_limits.is_empty() {
+                return Err(solver.core(conflict));
+            }
+
+            let (learned, backjump, antecedents) = solver.analyze(conflict);
+            solver.backtrack(backjump);
+
+            let clause = solver.clauses.len();
+            let lit = learned[0];
+            solver.clauses.push(learned);
+            solver.antecedents.push(antecedents);
+            solver.watch(clause);
+            solver.enqueue(lit, Some(clause));
+        } else if let Some(lit) = solver.decide() {
+            solver.trail_limits.push(solver.trail.len());
+            solver.enqueue(lit, None);
+        } else {
+            return Ok(solver
+                .values
+                .into_iter()
+                .map(|v| v.unwrap_or_default())
+                .collect());
+        }
+    }
+}
+
+/// Reduce an unsatisfiable subset of clauses to a minimal one.
+///
+/// Each clause in `core` is removed in turn. If the remainder is still unsatisfiable, the
+/// clause isn't needed. Every clause in the result is necessary for unsatisfiability.
+pub(crate) fn minimize_core(
+    var_count: usize,
+    clauses: &[Vec<Lit>],
+    core: Vec<usize>,
+) -> Vec<usize> {
+    let mut core = core;
+    let mut i = 0;
+
+    while i < core.len() {
+        let subset = core
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, clause)| *clause)
+            .collect::<Vec<_>>();
+
+        match solve(
+            var_count,
+            subset
+                .iter()
+                .map(|clause| clauses[*clause].clone())
+                .collect(),
+            &[],
+        ) {
+            Err(reduced) => {
+                core = reduced.into_iter().map(|j| subset[j]).collect();
+            }
+            Ok(_) => {
+                i += 1;
+            }
+        }
+    }
+
+    core
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn solve_and_core() {
+        let (a, b, c) = (0, 1, 2);
+
+        // (a) (¬a ∨ b ∨ c) (¬b) → c must be true.
+        let clauses = vec![
+            vec![Lit::positive(a)],
+            vec![Lit::negative(a), Lit::positive(b), Lit::positive(c)],
+            vec![Lit::negative(b)],
+        ];
+        assert_eq!(solve(3, clauses, &[1]), Ok(vec![true, false, true]));
+
+        // Pigeonhole: a, b, c each true but pairwise exclusive. (c) is redundant with
+        // (a) and (¬a ∨ ¬b) and (b).
+        let clauses = vec![
+            vec![Lit::positive(a)],
+            vec![Lit::positive(b)],
+            vec![Lit::positive(c)],
+            vec![Lit::negative(a), Lit::negative(b)],
+            vec![Lit::negative(b), Lit::negative(c)],
+            vec![Lit::positive(a), Lit::positive(b)],
+        ];
+        let core = solve(3, clauses.clone(), &[]).unwrap_err();
+        assert_eq!(minimize_core(3, &clauses, core), vec![0, 1, 3]);
+    }
+}
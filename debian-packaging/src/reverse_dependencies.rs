@@ -0,0 +1,413 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Reverse dependency lookups.
+
+[ReverseDependencyIndex] indexes the relationship fields of binary packages so it can
+answer which packages depend on, recommend, break, etc a given package, including
+relationships on virtual packages it provides. [ReverseDependencyIndex::removal_impact()]
+reports the packages whose hard dependencies would become unsatisfiable if a package were
+removed. [ReverseDependencyIndex::plan_removal()] additionally refuses to remove `Essential`
+and `Protected` packages or packages they depend on.
+*/
+
+use {
+    crate::{
+        binary_package_control::BinaryPackageControlFile,
+        binary_package_list::BinaryPackageList,
+        dependency::{
+            DependencyVariants, DependencyVersionConstraint, RelationshipField, SingleDependency,
+        },
+        error::{DebianError, Result},
+        package_version::PackageVersion,
+    },
+    std::collections::HashMap,
+};
+
+/// Whether a version satisfies the constraint of an expression.
+///
+/// Architecture restrictions are ignored.
+fn version_satisfies(dep: &SingleDependency, version: &PackageVersion) -> bool {
+    let dep = SingleDependency {
+        architectures: vec![],
+        ..dep.clone()
+    };
+
+    dep.package_satisfies(&dep.package, version, "")
+}
+
+/// `essential` or `protected` if a package must not be removed.
+fn protection(cf: &BinaryPackageControlFile) -> Option<&'static str> {
+    if cf.typed_essential() == Some(true) {
+        Some("essential")
+    } else if cf.typed_protected() == Some(true) {
+        Some("protected")
+    } else {
+        None
+    }
+}
+
+/// A relationship declared by one package on another.
+#[derive(Clone, Debug)]
+pub struct ReverseDependency<'file, 'data: 'file> {
+    /// The package declaring the relationship.
+    pub package: &'file BinaryPackageControlFile<'data>,
+    /// The field the relationship is declared in.
+    pub field: RelationshipField,
+    /// The expression referring to the queried package.
+    pub expression: SingleDependency,
+    /// All alternatives of the requirement containing [Self::expression].
+    pub alternatives: DependencyVariants,
+    /// The virtual package name the relationship is through, if any.
+    pub via_virtual: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct Relationship<'file, 'data: 'file> {
+    package: &'file BinaryPackageControlFile<'data>,
+    field: RelationshipField,
+    expression: SingleDependency,
+    alternatives: DependencyVariants,
+}
+
+#[derive(Clone, Debug)]
+struct PackageEntry {
+    version: PackageVersion,
+    /// Provided virtual package names and versions.
+    provides: Vec<(String, Option<DependencyVersionConstraint>)>,
+    /// `essential` or `protected` if the package must not be removed.
+    protection: Option<&'static str>,
+}
+
+/// Index of relationships between binary packages keyed by the referenced package.
+#[derive(Clone, Debug, Default)]
+pub struct ReverseDependencyIndex<'file, 'data: 'file> {
+    /// Referenced package name to relationships on it.
+    relationships: HashMap<String, Vec<Relationship<'file, 'data>>>,
+    /// Package name to known versions.
+    packages: HashMap<String, Vec<PackageEntry>>,
+    /// Virtual package name to providing package names and provided versions.
+    providers: HashMap<String, Vec<(String, Option<DependencyVersionConstraint>)>>,
+}
+
+impl<'file, 'data: 'file> ReverseDependencyIndex<'file, 'data> {
+    /// Load all packages in a [BinaryPackageList] into the index.
+    pub fn load_binary_package_list(
+        &mut self,
+        list: &'file BinaryPackageList<'data>,
+    ) -> Result<()> {
+        self.load_binary_packages(list.iter())
+    }
+
+    /// Load an iterable of binary packages into the index.
+    pub fn load_binary_packages(
+        &mut self,
+        files: impl Iterator<Item = &'file BinaryPackageControlFile<'data>>,
+    ) -> Result<()> {
+        for cf in files {
+            let name = cf.package()?;
+            let relations = cf.relations()?;
+
+            // Provides is indexed separately below.
+            for (field, value) in relations
+                .iter()
+                .filter(|(field, _)| *field != RelationshipField::Provides)
+            {
+                for variants in value.requirements() {
+                    for dep in variants.iter() {
+                        let target = dep.package.split(':').next().unwrap_or_default();
+
+                        self.relationships
+                            .entry(target.to_string())
+                            .or_default()
+                            .push(Relationship {
+                                package: cf,
+                                field,
+                                expression: dep.clone(),
+                                alternatives: variants.clone(),
+                            });
+                    }
+                }
+            }
+
+            let provides = relations
+                .provides
+                .iter()
+                .flat_map(|l| l.requirements())
+                .flat_map(|variants| variants.iter())
+                .map(|dep| (dep.package.clone(), dep.version_constraint.clone()))
+                .collect::<Vec<_>>();
+
+            for (provided, version) in &provides {
+                self.providers
+                    .entry(provided.clone())
+                    .or_default()
+                    .push((name.to_string(), version.clone()));
+            }
+
+            self.packages
+                .entry(name.to_string())
+                .or_default()
+                .push(PackageEntry {
+                    version: cf.version()?,
+                    provides,
+                    protection: protection(cf),
+                });
+        }
+
+        Ok(())
+    }
+
+    /// Find relationships in `fields` referring to a package.
+    ///
+    /// Relationships on the package name and on virtual packages it provides are returned.
+    /// If `version` is given, only relationships satisfied by that version of the package
+    /// are returned. Otherwise any loaded version is considered and version constraints are
+    /// ignored.
+    pub fn reverse_dependencies(
+        &self,
+        package: &str,
+        version: Option<&PackageVersion>,
+        fields: &[RelationshipField],
+    ) -> Vec<ReverseDependency<'file, 'data>> {
+        let mut res = vec![];
+
+        for rel in self.relationships.get(package).into_iter().flatten() {
+            if fields.contains(&rel.field)
+                && version.map_or(true, |version| version_satisfies(&rel.expression, version))
+            {
+                res.push(ReverseDependency {
+                    package: rel.package,
+                    field: rel.field,
+                    expression: rel.expression.clone(),
+                    alternatives: rel.alternatives.clone(),
+                    via_virtual: None,
+                });
+            }
+        }
+
+        let mut provided: Vec<(&str, Option<&DependencyVersionConstraint>)> = vec![];
+        for entry in self.packages.get(package).into_iter().flatten() {
+            if version.map_or(true, |version| &entry.version == version) {
+                for (name, constraint) in &entry.provides {
+                    if !provided.iter().any(|(seen, _)| *seen == name) {
+                        provided.push((name, constraint.as_ref()));
+                    }
+                }
+            }
+        }
+
+        for (name, constraint) in provided {
+            for rel in self.relationships.get(name).into_iter().flatten() {
+                if fields.contains(&rel.field)
+                    && (version.is_none()
+                        || rel.expression.package_satisfies_virtual(name, constraint))
+                {
+                    res.push(ReverseDependency {
+                        package: rel.package,
+                        field: rel.field,
+                        expression: rel.expression.clone(),
+                        alternatives: rel.alternatives.clone(),
+                        via_virtual: Some(name.to_string()),
+                    });
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Whether a package other than `excluded` satisfies a dependency expression.
+    fn satisfiable_without(&self, dep: &SingleDependency, excluded: &str) -> bool {
+        let name = dep.package.split(':').next().unwrap_or_default();
+
+        let concrete = name != excluded
+            && self.packages.get(name).is_some_and(|entries| {
+                entries
+                    .iter()
+                    .any(|entry| version_satisfies(dep, &entry.version))
+            });
+
+        concrete
+            || self.providers.get(name).is_some_and(|providers| {
+                providers.iter().any(|(provider, constraint)| {
+                    provider != excluded
+                        && dep.package_satisfies_virtual(&dep.package, constraint.as_ref())
+                })
+            })
+    }
+
+    /// Find packages whose `Pre-Depends` or `Depends` could no longer be satisfied if all
+    /// versions of a package were removed.
+    ///
+    /// A requirement is unaffected if another alternative, another package of the same
+    /// name, or another provider of the same virtual package satisfies it.
+    pub fn removal_impact(&self, package: &str) -> Vec<ReverseDependency<'file, 'data>> {
+        self.reverse_dependencies(
+            package,
+            None,
+            &[RelationshipField::PreDepends, RelationshipField::Depends],
+        )
+        .into_iter()
+        .filter(|rel| {
+            rel.package.package().ok() != Some(package)
+                && !rel
+                    .alternatives
+                    .iter()
+                    .any(|dep| self.satisfiable_without(dep, package))
+        })
+        .collect()
+    }
+
+    /// Like [Self::removal_impact()], but refuses to remove `Essential` or `Protected` packages.
+    ///
+    /// Returns [DebianError::RemovalRefused] if any loaded version of the package has either
+    /// field set to `yes` and [DebianError::RemovalBreaksProtected] if any impacted package
+    /// does.
+    pub fn plan_removal(&self, package: &str) -> Result<Vec<ReverseDependency<'file, 'data>>> {
+        if let Some(protection) = self
+            .packages
+            .get(package)
+            .into_iter()
+            .flatten()
+            .find_map(|entry| entry.protection)
+        {
+            return Err(DebianError::RemovalRefused(package.to_string(), protection));
+        }
+
+        let impact = self.removal_impact(package);
+
+        if let Some((impacted, protection)) = impact
+            .iter()
+            .find_map(|rel| protection(rel.package).map(|protection| (rel.package, protection)))
+        {
+            return Err(DebianError::RemovalBreaksProtected(
+                package.to_string(),
+                protection,
+                impacted.package()?.to_string(),
+            ));
+        }
+
+        Ok(impact)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::control::ControlParagraph};
+
+    fn package(fields: &[(&str, &str)]) -> BinaryPackageControlFile<'static> {
+        let mut para = ControlParagraph::default();
+        for (name, value) in fields {
+            para.set_field_from_string(name.to_string().into(), value.to_string().into());
+        }
+
+        para.into()
+    }
+
+    #[test]
+    fn reverse_dependencies() -> Result<()> {
+        let mut list = BinaryPackageList::default();
+        list.push(package(&[
+            ("Package", "libfoo1"),
+            ("Version", "1.0"),
+            ("Provides", "libfoo"),
+        ]));
+        list.push(package(&[
+            ("Package", "app"),
+            ("Version", "1.0"),
+            ("Depends", "libfoo1 (>= 1.0)"),
+            ("Recommends", "libfoo"),
+        ]));
+        list.push(package(&[
+            ("Package", "tool"),
+            ("Version", "1.0"),
+            ("Depends", "libfoo1 | libbar"),
+        ]));
+        list.push(package(&[("Package", "libbar"), ("Version", "1.0")]));
+        list.push(package(&[
+            ("Package", "dpkg"),
+            ("Version", "1.0"),
+            ("Essential", "yes"),
+        ]));
+        list.push(package(&[
+            ("Package", "old"),
+            ("Version", "1.0"),
+            ("Breaks", "libfoo1 (<< 1.0)"),
+        ]));
+        list.push(package(&[("Package", "libc6"), ("Version", "2.36")]));
+        list.push(package(&[
+            ("Package", "tar"),
+            ("Version", "1.34"),
+            ("Essential", "yes"),
+            ("Pre-Depends", "libc6 (>= 2.34)"),
+        ]));
+        list.push(package(&[("Package", "libz1"), ("Version", "1.0")]));
+        list.push(package(&[
+            ("Package", "init"),
+            ("Version", "1.0"),
+            ("Protected", "yes"),
+            ("Depends", "libz1"),
+        ]));
+
+        let mut index = ReverseDependencyIndex::default();
+        index.load_binary_package_list(&list)?;
+
+        let summary = |rels: Vec<ReverseDependency>| {
+            rels.iter()
+                .map(|rel| {
+                    format!(
+                        "{} {} {}",
+                        rel.package.package().unwrap(),
+                        rel.field,
+                        rel.expression
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            summary(index.reverse_dependencies("libfoo1", None, &RelationshipField::ALL)),
+            vec![
+                "app Depends libfoo1 (>= 1.0)",
+                "tool Depends libfoo1",
+                "old Breaks libfoo1 (<< 1.0)",
+                "app Recommends libfoo",
+            ]
+        );
+
+        let version = PackageVersion::parse("1.0")?;
+        assert_eq!(
+            summary(index.reverse_dependencies(
+                "libfoo1",
+                Some(&version),
+                &[RelationshipField::Breaks]
+            )),
+            Vec::<String>::new()
+        );
+
+        assert_eq!(
+            summary(index.removal_impact("libfoo1")),
+            vec!["app Depends libfoo1 (>= 1.0)"]
+        );
+        assert_eq!(index.plan_removal("libfoo1")?.len(), 1);
+        assert!(matches!(
+            index.plan_removal("dpkg"),
+            Err(DebianError::RemovalRefused(_, "essential"))
+        ));
+
+        // Removing a dependency of an essential or protected package is refused too.
+        assert!(matches!(
+            index.plan_removal("libc6"),
+            Err(DebianError::RemovalBreaksProtected(package, "essential", impacted))
+                if package == "libc6" && impacted == "tar"
+        ));
+        assert!(matches!(
+            index.plan_removal("libz1"),
+            Err(DebianError::RemovalBreaksProtected(_, "protected", impacted)) if impacted == "init"
+        ));
+
+        Ok(())
+    }
+}
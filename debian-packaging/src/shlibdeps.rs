@@ -0,0 +1,717 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Shared library dependency scanning.
+
+This module implements functionality similar to `dpkg-shlibdeps`. [ElfObject::parse()]
+extracts the `DT_NEEDED` libraries and imported symbols of an ELF binary.
+[ShlibDependencyResolver] indexes the `shlibs` and `symbols` control members of
+library packages and maps needed libraries to package dependencies. Library
+information can be loaded from `.deb` files or from a repository, using its
+`Contents` indices to locate the packages providing each library.
+
+[crate::deb::builder::DebBuilder::set_shlib_depends()] uses this to populate the
+`Depends` field of a package being built.
+
+This module requires the `shlibdeps` crate feature.
+*/
+
+use {
+    crate::{
+        deb::{
+            builder::control_member_lines,
+            reader::{BinaryPackageReader, BinaryPackageStreamingEntry, ControlTarFile},
+        },
+        dependency::{DependencyList, DependencyVariants, VersionRelationship},
+        error::{DebianError, Result},
+        package_version::PackageVersion,
+        repository::{ReleaseReader, RepositoryRootReader},
+    },
+    futures::{AsyncReadExt, StreamExt, TryStreamExt},
+    object::{
+        elf,
+        read::elf::{Dyn, FileHeader, Sym},
+        read::SymbolIndex,
+        Endianness, FileKind,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        io::{Cursor, Read},
+    },
+};
+
+/// A symbol an ELF object imports from a shared library.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImportedSymbol {
+    /// The symbol name.
+    pub name: String,
+    /// The required symbol version, e.g. `GLIBC_2.14`.
+    pub version: Option<String>,
+    /// The soname of the library providing the version, if known.
+    pub library: Option<String>,
+}
+
+impl ImportedSymbol {
+    /// The name of this symbol as used in `symbols` files, e.g. `memcpy@GLIBC_2.14`.
+    ///
+    /// Unversioned symbols use the `Base` version.
+    pub fn symbols_name(&self) -> String {
+        format!(
+            "{}@{}",
+            self.name,
+            self.version.as_deref().unwrap_or("Base")
+        )
+    }
+}
+
+/// Dynamic linking metadata of an ELF object.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ElfObject {
+    /// The `DT_SONAME` of this object, if it is a shared library.
+    pub soname: Option<String>,
+    /// Libraries from `DT_NEEDED` entries, in order.
+    pub needed: Vec<String>,
+    /// Undefined, non-weak dynamic symbols.
+    pub imported_symbols: Vec<ImportedSymbol>,
+}
+
+impl ElfObject {
+    /// Parse ELF data.
+    ///
+    /// Returns `Ok(None)` if the data isn't an ELF file.
+    pub fn parse(data: &[u8]) -> Result<Option<Self>> {
+        if !data.starts_with(b"\x7fELF") {
+            return Ok(None);
+        }
+
+        match FileKind::parse(data)? {
+            FileKind::Elf32 => Ok(Some(Self::parse_elf::<elf::FileHeader32<Endianness>>(
+                data,
+            )?)),
+            FileKind::Elf64 => Ok(Some(Self::parse_elf::<elf::FileHeader64<Endianness>>(
+                data,
+            )?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_elf<Elf: FileHeader<Endian = Endianness>>(data: &[u8]) -> Result<Self> {
+        let header = Elf::parse(data)?;
+        let endian = header.endian()?;
+        let sections = header.sections(endian, data)?;
+
+        let mut res = Self::default();
+
+        if let Some((entries, index)) = sections.dynamic(endian, data)? {
+            let strings = sections.strings(endian, data, index)?;
+
+            for entry in entries {
+                match entry.tag32(endian) {
+                    Some(elf::DT_NEEDED) => {
+                        res.needed.push(
+                            String::from_utf8_lossy(entry.string(endian, strings)?).to_string(),
+                        );
+                    }
+                    Some(elf::DT_SONAME) => {
+                        res.soname = Some(
+                            String::from_utf8_lossy(entry.string(endian, strings)?).to_string(),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // The object crate's symbol versioning APIs don't expose the library a needed
+        // version belongs to. So build our own mapping of version index to library.
+        let mut version_libraries = HashMap::new();
+        if let Some((mut verneed, index)) = sections.gnu_verneed(endian, data)? {
+            let strings = sections.strings(endian, data, index)?;
+
+            while let Some((entry, mut vernaux_entries)) = verneed.next()? {
+                let library = String::from_utf8_lossy(entry.file(endian, strings)?).to_string();
+
+                while let Some(vernaux) = vernaux_entries.next()? {
+                    let index = vernaux.vna_other.get(endian) & elf::VERSYM_VERSION;
+                    version_libraries.insert(index, library.clone());
+                }
+            }
+        }
+
+        let versions = sections.versions(endian, data)?;
+        let symbols = sections.symbols(endian, data, elf::SHT_DYNSYM)?;
+
+        for (index, symbol) in symbols.iter().enumerate() {
+            if !symbol.is_undefined(endian) || symbol.st_bind() == elf::STB_WEAK {
+                continue;
+            }
+
+            let name = String::from_utf8_lossy(symbol.name(endian, symbols.strings())?);
+            if name.is_empty() {
+                continue;
+            }
+
+            let (version, library) = match &versions {
+                Some(versions) => {
+                    let version_index = versions.version_index(endian, SymbolIndex(index));
+
+                    match versions.version(version_index)? {
+                        Some(version) => (
+                            Some(String::from_utf8_lossy(version.name()).to_string()),
+                            version_libraries.get(&version_index.index()).cloned(),
+                        ),
+                        None => (None, None),
+                    }
+                }
+                None => (None, None),
+            };
+
+            res.imported_symbols.push(ImportedSymbol {
+                name: name.to_string(),
+                version,
+                library,
+            });
+        }
+
+        Ok(res)
+    }
+}
+
+/// An entry in a `shlibs` control member.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShlibsEntry {
+    /// The package type the entry applies to, e.g. `udeb`. `None` for regular packages.
+    pub package_type: Option<String>,
+    /// The library name, e.g. `libz` for `libz.so.1`.
+    pub library: String,
+    /// The soname version, e.g. `1` for `libz.so.1`.
+    pub version: String,
+    /// Dependencies of binaries linking against the library.
+    pub dependencies: String,
+}
+
+/// Parse the content of a `shlibs` control member.
+pub fn parse_shlibs(data: &[u8]) -> Result<Vec<ShlibsEntry>> {
+    let mut res = vec![];
+
+    for (number, line) in control_member_lines("shlibs", data)? {
+        let mut words = line.split_whitespace().peekable();
+
+        let package_type = if words.peek().map(|w| w.ends_with(':')).unwrap_or(false) {
+            words.next().map(|w| w.trim_end_matches(':').to_string())
+        } else {
+            None
+        };
+
+        let (library, version) = match (words.next(), words.next()) {
+            (Some(library), Some(version)) => (library.to_string(), version.to_string()),
+            _ => {
+                return Err(DebianError::DebControlMemberInvalid(
+                    "shlibs",
+                    number,
+                    "expected library name and soname version",
+                ))
+            }
+        };
+
+        let dependencies = words.collect::<Vec<_>>().join(" ");
+        if dependencies.is_empty() || DependencyList::parse(&dependencies).is_err() {
+            return Err(DebianError::DebControlMemberInvalid(
+                "shlibs",
+                number,
+                "invalid dependencies",
+            ));
+        }
+
+        res.push(ShlibsEntry {
+            package_type,
+            library,
+            version,
+            dependencies,
+        });
+    }
+
+    Ok(res)
+}
+
+/// Split a soname into the library name and version used by `shlibs` files.
+///
+/// `libz.so.1` becomes `(libz, 1)` and `libfoo-1.2.so` becomes `(libfoo, 1.2)`.
+pub fn soname_shlibs_key(soname: &str) -> Option<(&str, &str)> {
+    if let Some((library, version)) = soname.split_once(".so.") {
+        Some((library, version))
+    } else {
+        soname.strip_suffix(".so")?.rsplit_once('-')
+    }
+}
+
+/// A library described by a `symbols` control member.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SymbolsLibrary {
+    /// The library soname.
+    pub soname: String,
+    /// The main dependency template, e.g. `libc6 #MINVER#`.
+    pub dependency: String,
+    /// Alternative dependency templates, from `|` lines.
+    pub alternatives: Vec<String>,
+    /// Metadata fields, from `*` lines.
+    pub fields: Vec<(String, String)>,
+    /// Minimal versions of the package providing each symbol, keyed by `name@version`.
+    pub symbols: BTreeMap<String, String>,
+}
+
+impl SymbolsLibrary {
+    /// Resolve the dependency templates given the minimal version required.
+    ///
+    /// `#MINVER#` is replaced by `(>= <version>)`, or removed if no version is given.
+    /// Alternatives are appended with `|`.
+    pub fn dependency(&self, min_version: Option<&PackageVersion>) -> String {
+        let minver = min_version
+            .map(|v| format!("(>= {})", v))
+            .unwrap_or_default();
+
+        std::iter::once(&self.dependency)
+            .chain(self.alternatives.iter())
+            .map(|template| template.replace("#MINVER#", &minver).trim().to_string())
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+}
+
+/// Parse the content of a `symbols` control member.
+///
+/// Symbols tagged `c++`, `regex` or `symver` are patterns rather than literal names and
+/// are ignored. Other tags are discarded.
+pub fn parse_symbols(data: &[u8]) -> Result<Vec<SymbolsLibrary>> {
+    let mut res: Vec<SymbolsLibrary> = vec![];
+
+    for (number, line) in control_member_lines("symbols", data)? {
+        let invalid = |reason| DebianError::DebControlMemberInvalid("symbols", number, reason);
+
+        if line.starts_with(' ') || line.starts_with('|') || line.starts_with('*') {
+            let library = res
+                .last_mut()
+                .ok_or_else(|| invalid("entry precedes library line"))?;
+
+            if let Some(alternative) = line.strip_prefix('|') {
+                library.alternatives.push(alternative.trim().to_string());
+            } else if let Some(meta) = line.strip_prefix('*') {
+                let (name, value) = meta
+                    .split_once(':')
+                    .ok_or_else(|| invalid("expected `* <field>: <value>`"))?;
+                library
+                    .fields
+                    .push((name.trim().to_string(), value.trim().to_string()));
+            } else {
+                let mut entry = line.trim_start();
+
+                let mut pattern = false;
+                if let Some(tagged) = entry.strip_prefix('(') {
+                    let (tags, rest) = tagged
+                        .split_once(')')
+                        .ok_or_else(|| invalid("unterminated symbol tag"))?;
+                    pattern = tags
+                        .split('|')
+                        .any(|tag| matches!(tag.trim(), "c++" | "regex" | "symver"));
+                    entry = rest;
+                }
+
+                let mut words = entry.split_whitespace();
+                let (name, version) = match (words.next(), words.next()) {
+                    (Some(name), Some(version)) => (name, version),
+                    _ => return Err(invalid("expected symbol and version")),
+                };
+
+                if !pattern {
+                    library
+                        .symbols
+                        .insert(name.to_string(), version.to_string());
+                }
+            }
+        } else {
+            let (soname, dependency) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| invalid("expected library soname and dependency template"))?;
+
+            res.push(SymbolsLibrary {
+                soname: soname.to_string(),
+                dependency: dependency.trim().to_string(),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(res)
+}
+
+/// Merge dependency expressions into a [DependencyList].
+///
+/// Duplicate expressions are removed. Unversioned or `>=` requirements on the same
+/// package are combined, keeping the highest version.
+pub fn merge_dependencies(expressions: impl IntoIterator<Item = String>) -> Result<DependencyList> {
+    let mut merged: Vec<DependencyVariants> = vec![];
+
+    for expression in expressions {
+        if expression.trim().is_empty() {
+            continue;
+        }
+
+        for requirement in DependencyList::parse(&expression)?.requirements() {
+            let mergeable = |variants: &DependencyVariants| {
+                variants.len() == 1
+                    && variants[0].architectures.is_empty()
+                    && variants[0].version_constraint.as_ref().map_or(true, |c| {
+                        c.relationship == VersionRelationship::LaterOrEqual
+                    })
+            };
+
+            let existing = if mergeable(requirement) {
+                merged.iter_mut().find(|existing| {
+                    mergeable(existing) && existing[0].package == requirement[0].package
+                })
+            } else {
+                None
+            };
+
+            match existing {
+                Some(existing) => {
+                    let new = &requirement[0].version_constraint;
+                    let old = &existing[0].version_constraint;
+
+                    let replace = match (old, new) {
+                        (None, Some(_)) => true,
+                        (Some(old), Some(new)) => new.version > old.version,
+                        _ => false,
+                    };
+                    if replace {
+                        existing[0].version_constraint = new.clone();
+                    }
+                }
+                None => {
+                    if !merged.contains(requirement) {
+                        merged.push(requirement.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    if merged.is_empty() {
+        return Ok(DependencyList::default());
+    }
+
+    DependencyList::parse(
+        &merged
+            .iter()
+            .map(|variants| variants.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    )
+}
+
+/// Maps shared libraries needed by ELF objects to package dependencies.
+///
+/// Like `dpkg-shlibdeps`, `symbols` information is preferred: the dependency on a
+/// library's package is versioned by the highest minimal version of the symbols used.
+/// If a library has no `symbols` information, its `shlibs` entry is used.
+#[derive(Clone, Debug, Default)]
+pub struct ShlibDependencyResolver {
+    /// `shlibs` dependencies keyed by library name and soname version.
+    shlibs: BTreeMap<(String, String), String>,
+    /// `symbols` information keyed by soname.
+    symbols: BTreeMap<String, SymbolsLibrary>,
+}
+
+impl ShlibDependencyResolver {
+    /// Register libraries from the content of a `shlibs` control member.
+    ///
+    /// Entries for other package types, like `udeb`, are ignored.
+    pub fn add_shlibs(&mut self, data: &[u8]) -> Result<()> {
+        for entry in parse_shlibs(data)? {
+            if entry.package_type.is_none() {
+                self.shlibs
+                    .insert((entry.library, entry.version), entry.dependencies);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Register libraries from the content of a `symbols` control member.
+    pub fn add_symbols(&mut self, data: &[u8]) -> Result<()> {
+        for library in parse_symbols(data)? {
+            self.symbols.insert(library.soname.clone(), library);
+        }
+
+        Ok(())
+    }
+
+    /// Register libraries from the `shlibs` and `symbols` control members of a `.deb`.
+    ///
+    /// `data.tar` isn't read.
+    pub fn add_deb(&mut self, reader: impl Read) -> Result<()> {
+        let mut reader = BinaryPackageReader::new(reader)?;
+
+        while let Some(entry) = reader.next_entry_streaming() {
+            if let BinaryPackageStreamingEntry::Control(mut control) = entry? {
+                for entry in control.entries()? {
+                    match entry?.to_control_file()?.1 {
+                        ControlTarFile::Shlibs(data) => self.add_shlibs(&data)?,
+                        ControlTarFile::Symbols(data) => self.add_symbols(&data)?,
+                        _ => {}
+                    }
+                }
+
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether dependency information is known for a soname.
+    pub fn has_library(&self, soname: &str) -> bool {
+        self.symbols.contains_key(soname)
+            || soname_shlibs_key(soname).is_some_and(|(library, version)| {
+                self.shlibs
+                    .contains_key(&(library.to_string(), version.to_string()))
+            })
+    }
+
+    /// Register libraries needed by ELF objects from packages in a repository.
+    ///
+    /// The `Contents` indices of the release for `architecture` are searched for the
+    /// packages installing each needed library that isn't provided by `objects` itself
+    /// or already known. Those packages are fetched with up to `threads` concurrent
+    /// fetches and registered via [Self::add_deb()].
+    pub async fn add_repository_libraries(
+        &mut self,
+        root: &dyn RepositoryRootReader,
+        release: &dyn ReleaseReader,
+        architecture: &str,
+        objects: &[ElfObject],
+        threads: usize,
+    ) -> Result<()> {
+        let sonames = needed_libraries(objects)
+            .into_iter()
+            .filter(|soname| !self.has_library(soname))
+            .collect::<BTreeSet<_>>();
+        if sonames.is_empty() {
+            return Ok(());
+        }
+
+        let components = release
+            .contents_indices_entries()?
+            .into_iter()
+            .filter(|entry| entry.architecture == architecture && !entry.is_installer)
+            .map(|entry| entry.component.map(|c| c.to_string()))
+            .collect::<BTreeSet<_>>();
+
+        let mut packages = BTreeSet::new();
+        for component in components {
+            let contents = release
+                .resolve_contents(component.as_deref(), architecture, false)
+                .await?;
+
+            for (path, path_packages) in contents.iter_paths() {
+                let (directory, filename) = match path.rsplit_once('/') {
+                    Some(v) => v,
+                    None => continue,
+                };
+
+                if sonames.contains(filename)
+                    && (directory.starts_with("lib") || directory.starts_with("usr/lib"))
+                {
+                    // Packages are qualified by their section, e.g. `libs/zlib1g`.
+                    packages.extend(
+                        path_packages.map(|p| p.rsplit('/').next().unwrap_or(p).to_string()),
+                    );
+                }
+            }
+        }
+
+        let architecture = architecture.to_string();
+        let fetches = release
+            .resolve_package_fetches(
+                Box::new(|_| true),
+                Box::new(move |cf| {
+                    cf.package().is_ok_and(|p| packages.contains(p))
+                        && cf
+                            .architecture()
+                            .is_ok_and(|a| a == architecture || a == "all")
+                }),
+                threads,
+            )
+            .await?;
+
+        let fs = fetches
+            .into_iter()
+            .map(|fetch| async move {
+                let mut reader = root.fetch_binary_package_generic(fetch).await?;
+                let mut data = vec![];
+                reader.read_to_end(&mut data).await?;
+
+                Ok::<_, DebianError>(data)
+            })
+            .collect::<Vec<_>>();
+
+        let debs = futures::stream::iter(fs)
+            .buffer_unordered(threads)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        for deb in debs {
+            self.add_deb(Cursor::new(deb))?;
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the package dependencies of a set of ELF objects.
+    ///
+    /// Libraries provided by objects in the set are ignored. An error is returned if a
+    /// needed library has no `symbols` or `shlibs` information.
+    pub fn resolve(&self, objects: &[ElfObject]) -> Result<DependencyList> {
+        let mut expressions = vec![];
+
+        for object in objects {
+            for soname in &object.needed {
+                if objects
+                    .iter()
+                    .any(|o| o.soname.as_deref() == Some(soname.as_str()))
+                {
+                    continue;
+                }
+
+                if let Some(library) = self.symbols.get(soname) {
+                    let mut min_version: Option<PackageVersion> = None;
+
+                    for symbol in &object.imported_symbols {
+                        if symbol.library.as_ref().is_some_and(|l| l != soname) {
+                            continue;
+                        }
+
+                        if let Some(version) = library.symbols.get(&symbol.symbols_name()) {
+                            let version = PackageVersion::parse(version)?;
+
+                            if min_version.as_ref().map_or(true, |v| version > *v) {
+                                min_version = Some(version);
+                            }
+                        }
+                    }
+
+                    // A minimal version of 0 means the symbol always existed.
+                    let min_version = min_version.filter(|v| v.to_string() != "0");
+
+                    expressions.push(library.dependency(min_version.as_ref()));
+                } else if let Some(dependencies) =
+                    soname_shlibs_key(soname).and_then(|(library, version)| {
+                        self.shlibs.get(&(library.to_string(), version.to_string()))
+                    })
+                {
+                    expressions.push(dependencies.clone());
+                } else {
+                    return Err(DebianError::ShlibdepsUnresolved(soname.clone()));
+                }
+            }
+        }
+
+        merge_dependencies(expressions)
+    }
+}
+
+/// Obtain the libraries needed by a set of ELF objects and not provided by the set itself.
+pub fn needed_libraries(objects: &[ElfObject]) -> BTreeSet<String> {
+    let provided = objects
+        .iter()
+        .filter_map(|o| o.soname.as_deref())
+        .collect::<BTreeSet<_>>();
+
+    objects
+        .iter()
+        .flat_map(|o| o.needed.iter())
+        .filter(|soname| !provided.contains(soname.as_str()))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SYMBOLS: &[u8] = b"libc.so.6 libc6 #MINVER#
+* Build-Depends-Package: libc-dev
+ malloc@GLIBC_2.2.5 2.2.5
+ memcpy@GLIBC_2.14 2.14
+ (c++)\"foo()@Base\" 2.0
+";
+
+    fn object(needed: &[&str], symbols: &[(&str, &str)]) -> ElfObject {
+        ElfObject {
+            soname: None,
+            needed: needed.iter().map(|s| s.to_string()).collect(),
+            imported_symbols: symbols
+                .iter()
+                .map(|(name, version)| ImportedSymbol {
+                    name: name.to_string(),
+                    version: Some(version.to_string()),
+                    library: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn resolve() -> Result<()> {
+        let mut resolver = ShlibDependencyResolver::default();
+        resolver.add_symbols(SYMBOLS)?;
+        resolver.add_shlibs(b"libz 1 zlib1g (>= 1:1.1.4)\nudeb: libz 1 zlib1g-udeb\n")?;
+
+        let libc = &parse_symbols(SYMBOLS)?[0];
+        assert_eq!(libc.symbols.len(), 2);
+        assert_eq!(libc.fields[0].0, "Build-Depends-Package");
+
+        let objects = vec![
+            object(&["libc.so.6"], &[("malloc", "GLIBC_2.2.5")]),
+            object(
+                &["libc.so.6", "libz.so.1", "libfoo.so.2"],
+                &[("memcpy", "GLIBC_2.14")],
+            ),
+            ElfObject {
+                soname: Some("libfoo.so.2".into()),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            resolver.resolve(&objects)?.to_string(),
+            "libc6 (>= 2.14), zlib1g (>= 1:1.1.4)"
+        );
+
+        assert!(matches!(
+            resolver.resolve(&[object(&["libbar.so.3"], &[])]),
+            Err(DebianError::ShlibdepsUnresolved(soname)) if soname == "libbar.so.3"
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    #[test]
+    fn parse_current_exe() -> Result<()> {
+        let object = ElfObject::parse(&std::fs::read(std::env::current_exe()?)?)?
+            .expect("test binary is an ELF object");
+
+        assert!(object.needed.iter().any(|l| l.starts_with("libc.so")));
+        assert!(object.imported_symbols.iter().any(|s| s
+            .version
+            .as_deref()
+            .unwrap_or_default()
+            .starts_with("GLIBC_")));
+
+        assert!(ElfObject::parse(b"#!/bin/sh\n")?.is_none());
+
+        Ok(())
+    }
+}
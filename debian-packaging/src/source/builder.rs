@@ -0,0 +1,750 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Create `3.0 (quilt)` source packages. */
+
+use {
+    crate::{
+        changelog::Changelog,
+        control::{ControlField, ControlParagraph},
+        deb::{
+            builder::{new_tar_header, resolve_mtime, source_date_epoch_from_env, TarOwnership},
+            CompressionOptions, DebCompression,
+        },
+        debian_source_control::DebianSourceControlFile,
+        error::{DebianError, Result},
+        file_list::{file_list_field, FileListEntry},
+        io::MultiDigester,
+        package_version::PackageVersion,
+        repository::release::ChecksumType,
+        source_package_control::SourcePackageControlFile,
+    },
+    simple_file_manifest::{FileEntry, FileManifest},
+    std::{
+        io::Cursor,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+};
+
+/// The only source package format produced by [SourcePackageBuilder].
+pub const SOURCE_FORMAT: &str = "3.0 (quilt)";
+
+/// Names of version control directories never included in source tarballs.
+const VCS_DIRECTORIES: &[&str] = &[".bzr", ".git", ".hg", ".svn", "CVS"];
+
+/// Fields copied from the source paragraph of `debian/control` into the `.dsc`, in order.
+const DSC_COPIED_FIELDS: &[&str] = &[
+    "Maintainer",
+    "Uploaders",
+    "Homepage",
+    "Standards-Version",
+    "Vcs-Browser",
+    "Vcs-Arch",
+    "Vcs-Bzr",
+    "Vcs-Cvs",
+    "Vcs-Darcs",
+    "Vcs-Git",
+    "Vcs-Hg",
+    "Vcs-Mtn",
+    "Vcs-Svn",
+    "Testsuite",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+];
+
+/// Upstream source code of a source package.
+enum UpstreamSource {
+    /// Files to write to a new `.orig.tar` archive.
+    Files(FileManifest),
+    /// An existing `.orig.tar` archive and its filename extension (e.g. `.tar.gz`).
+    Tarball(&'static str, Vec<u8>),
+}
+
+/// Add files under a directory on the filesystem to a manifest.
+///
+/// Paths in the manifest are relative to `source_dir` and prefixed with `prefix`.
+/// Version control directories and relative paths for which `exclude` returns true are
+/// skipped. Empty directories aren't recorded.
+fn add_directory(
+    manifest: &mut FileManifest,
+    source_dir: &Path,
+    prefix: &Path,
+    exclude: impl Fn(&Path) -> bool,
+) -> Result<()> {
+    let mut pending = vec![source_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut children = std::fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        children.sort();
+
+        for source_path in children {
+            let rel_path = source_path
+                .strip_prefix(source_dir)
+                .expect("children are under source directory");
+
+            let is_vcs = source_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| VCS_DIRECTORIES.contains(&name))
+                .unwrap_or_default();
+            if is_vcs || exclude(rel_path) {
+                continue;
+            }
+
+            let metadata = std::fs::symlink_metadata(&source_path)?;
+
+            if metadata.is_dir() {
+                pending.push(source_path);
+            } else if metadata.file_type().is_symlink() {
+                manifest.add_symlink(prefix.join(rel_path), std::fs::read_link(&source_path)?)?;
+            } else if metadata.is_file() {
+                manifest.add_file_entry(
+                    prefix.join(rel_path),
+                    FileEntry::new_from_path(
+                        &source_path,
+                        simple_file_manifest::is_executable(&metadata),
+                    ),
+                )?;
+            } else {
+                return Err(DebianError::DebStagingUnsupportedFile(
+                    source_path.display().to_string(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a tar archive of a manifest with all entries under a `prefix` directory.
+///
+/// Entries are owned by `root:root` and emitted in sorted path order, with directories
+/// preceding their content.
+fn write_source_tar(files: &FileManifest, prefix: Option<&Path>, mtime: u64) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(vec![]);
+    let ownership = TarOwnership::default();
+
+    let mut entries = files
+        .relative_directories()
+        .into_iter()
+        .map(|path| (path, None))
+        .chain(
+            files
+                .iter_entries()
+                .map(|(path, content)| (path.to_path_buf(), Some(content))),
+        )
+        .map(|(path, content)| match prefix {
+            Some(prefix) => (prefix.join(path), content),
+            None => (path, content),
+        })
+        .collect::<Vec<(PathBuf, _)>>();
+    if let Some(prefix) = prefix {
+        entries.push((prefix.to_path_buf(), None));
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (path, content) in entries {
+        let mut header = new_tar_header(mtime, &ownership)?;
+        header.set_size(0);
+
+        match content {
+            None => {
+                header.set_mode(0o755);
+                header.set_entry_type(tar::EntryType::Directory);
+                builder.append_data(&mut header, &path, std::io::empty())?;
+            }
+            Some(content) => {
+                if let Some(target) = content.link_target() {
+                    header.set_mode(0o777);
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    builder.append_link(&mut header, &path, target)?;
+                } else {
+                    let data = content.resolve_content()?;
+                    header.set_mode(if content.is_executable() {
+                        0o755
+                    } else {
+                        0o644
+                    });
+                    header.set_size(data.len() as _);
+                    builder.append_data(&mut header, &path, &*data)?;
+                }
+            }
+        }
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+/// Ensure a compression format can be used for source package tarballs.
+fn validate_compression(compression: &DebCompression) -> Result<()> {
+    match compression {
+        DebCompression::Gzip | DebCompression::Xz(_) => Ok(()),
+        _ => Err(DebianError::SourcePackageCompressionUnsupported(format!(
+            "tar{}",
+            compression.extension()
+        ))),
+    }
+}
+
+/// A built source package: a `.dsc` file and the files it references.
+pub struct SourcePackage {
+    dsc_filename: String,
+    dsc: DebianSourceControlFile<'static>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl SourcePackage {
+    /// The filename of the `.dsc` file.
+    pub fn dsc_filename(&self) -> &str {
+        &self.dsc_filename
+    }
+
+    /// The `.dsc` control file describing this source package.
+    pub fn dsc(&self) -> &DebianSourceControlFile<'static> {
+        &self.dsc
+    }
+
+    /// Files referenced by the `.dsc`, as pairs of filename and content.
+    pub fn files(&self) -> impl Iterator<Item = (&str, &[u8])> + '_ {
+        self.files
+            .iter()
+            .map(|(filename, data)| (filename.as_str(), data.as_slice()))
+    }
+
+    /// Write the `.dsc` and the files it references to a directory.
+    ///
+    /// Returns the paths of written files, with the `.dsc` last.
+    pub fn write_to_directory(&self, directory: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let directory = directory.as_ref();
+        std::fs::create_dir_all(directory)?;
+
+        let mut paths = vec![];
+        for (filename, data) in self.files() {
+            let path = directory.join(filename);
+            std::fs::write(&path, data)?;
+            paths.push(path);
+        }
+
+        let path = directory.join(&self.dsc_filename);
+        let mut fh = std::fs::File::create(&path)?;
+        self.dsc.write(&mut fh)?;
+        paths.push(path);
+
+        Ok(paths)
+    }
+}
+
+/// A builder for `3.0 (quilt)` source packages.
+///
+/// A source package is assembled from upstream source code and a `debian/` directory.
+/// The upstream code is either a directory tree, which is written to a new
+/// `.orig.tar.{gz,xz}` archive, or an existing upstream tarball used verbatim. The
+/// `debian/` directory becomes a `.debian.tar.{gz,xz}` archive. A `.dsc` file referencing
+/// both is derived from `debian/control`.
+///
+/// The source package version comes from the first entry in `debian/changelog` unless
+/// set with [Self::set_version()]. It must have a Debian revision.
+///
+/// Tarball entries are owned by `root:root`, have fixed modes, and are emitted in sorted
+/// order. Combined with [Self::set_mtime()] or [Self::set_source_date_epoch()], identical
+/// inputs produce bit-identical output.
+pub struct SourcePackageBuilder {
+    upstream: Option<UpstreamSource>,
+
+    /// Files in the `debian/` directory, with paths beginning with `debian/`.
+    debian_files: FileManifest,
+
+    version: Option<String>,
+
+    orig_compression: DebCompression,
+
+    debian_compression: DebCompression,
+
+    compression_options: CompressionOptions,
+
+    mtime: Option<SystemTime>,
+
+    /// Upper bound for modified times of tarball entries.
+    source_date_epoch: Option<u64>,
+}
+
+impl Default for SourcePackageBuilder {
+    fn default() -> Self {
+        Self {
+            upstream: None,
+            debian_files: FileManifest::new_with_links(),
+            version: None,
+            orig_compression: DebCompression::Xz(6),
+            debian_compression: DebCompression::Xz(6),
+            compression_options: CompressionOptions::default(),
+            mtime: None,
+            source_date_epoch: None,
+        }
+    }
+}
+
+impl SourcePackageBuilder {
+    /// Use the files under a directory as the upstream source code.
+    ///
+    /// A top-level `debian` directory and version control directories are ignored.
+    pub fn set_upstream_directory(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let mut files = FileManifest::new_with_links();
+        add_directory(&mut files, path.as_ref(), Path::new(""), |p| {
+            p == Path::new("debian")
+        })?;
+        self.upstream = Some(UpstreamSource::Files(files));
+
+        Ok(self)
+    }
+
+    /// Add a file to the upstream source code.
+    ///
+    /// `path` is relative to the root of the upstream source tree.
+    pub fn add_upstream_file(mut self, path: impl AsRef<Path>, entry: FileEntry) -> Result<Self> {
+        if !matches!(self.upstream, Some(UpstreamSource::Files(_))) {
+            self.upstream = Some(UpstreamSource::Files(FileManifest::new_with_links()));
+        }
+        if let Some(UpstreamSource::Files(files)) = &mut self.upstream {
+            files.add_file_entry(path, entry)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Use an existing upstream tarball as the `.orig.tar` archive.
+    ///
+    /// The tarball is used verbatim. Its compression is derived from the filename,
+    /// which must end in `.tar.gz`, `.tar.xz`, or `.tar.bz2`.
+    pub fn set_upstream_tarball(
+        mut self,
+        filename: impl AsRef<str>,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<Self> {
+        let filename = filename.as_ref();
+
+        let extension = [".tar.gz", ".tar.xz", ".tar.bz2"]
+            .into_iter()
+            .find(|ext| filename.ends_with(ext))
+            .ok_or_else(|| DebianError::SourcePackageCompressionUnsupported(filename.into()))?;
+        self.upstream = Some(UpstreamSource::Tarball(extension, data.into()));
+
+        Ok(self)
+    }
+
+    /// Use the files under a directory as the `debian/` directory.
+    ///
+    /// `path` is the `debian` directory itself. Version control directories are ignored.
+    pub fn set_debian_directory(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        self.debian_files = FileManifest::new_with_links();
+        add_directory(
+            &mut self.debian_files,
+            path.as_ref(),
+            Path::new("debian"),
+            |_| false,
+        )?;
+
+        Ok(self)
+    }
+
+    /// Add a file to the `debian/` directory.
+    ///
+    /// `path` is relative to the `debian/` directory, e.g. `control`.
+    pub fn add_debian_file(mut self, path: impl AsRef<Path>, entry: FileEntry) -> Result<Self> {
+        self.debian_files
+            .add_file_entry(Path::new("debian").join(path), entry)?;
+
+        Ok(self)
+    }
+
+    /// Set the version of the source package, overriding `debian/changelog`.
+    #[must_use]
+    pub fn set_version(mut self, version: impl ToString) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Set the compression of a generated `.orig.tar` archive.
+    ///
+    /// Only gzip and xz are supported. Defaults to xz.
+    pub fn set_orig_compression(mut self, compression: DebCompression) -> Result<Self> {
+        validate_compression(&compression)?;
+        self.orig_compression = compression;
+
+        Ok(self)
+    }
+
+    /// Set the compression of the `.debian.tar` archive.
+    ///
+    /// Only gzip and xz are supported. Defaults to xz.
+    pub fn set_debian_compression(mut self, compression: DebCompression) -> Result<Self> {
+        validate_compression(&compression)?;
+        self.debian_compression = compression;
+
+        Ok(self)
+    }
+
+    /// Set options for the compression encoder, such as the number of threads.
+    #[must_use]
+    pub fn set_compression_options(mut self, options: CompressionOptions) -> Self {
+        self.compression_options = options;
+        self
+    }
+
+    /// Set the modified time to use on tarball entries.
+    ///
+    /// If not called, the current time will be used.
+    #[must_use]
+    pub fn set_mtime(mut self, time: Option<SystemTime>) -> Self {
+        self.mtime = time;
+        self
+    }
+
+    /// Clamp modified times of tarball entries to a `SOURCE_DATE_EPOCH` value.
+    #[must_use]
+    pub fn set_source_date_epoch(mut self, epoch: Option<u64>) -> Self {
+        self.source_date_epoch = epoch;
+        self
+    }
+
+    /// Clamp modified times to the `SOURCE_DATE_EPOCH` environment variable, if set.
+    pub fn set_source_date_epoch_from_env(self) -> Result<Self> {
+        Ok(self.set_source_date_epoch(source_date_epoch_from_env()?))
+    }
+
+    fn debian_file(&self, path: &'static str) -> Result<Option<Vec<u8>>> {
+        Ok(match self.debian_files.get(path) {
+            Some(entry) => Some(entry.resolve_content()?),
+            None => None,
+        })
+    }
+
+    /// Build the source package.
+    pub fn build(&self) -> Result<SourcePackage> {
+        let control = self
+            .debian_file("debian/control")?
+            .ok_or(DebianError::SourcePackageMissingFile("debian/control"))?;
+        let control = SourcePackageControlFile::parse_str(&String::from_utf8_lossy(&control))?;
+        let general = control.general_paragraph();
+        let source = general.required_field_str("Source")?;
+
+        let mut debian_files = self.debian_files.clone();
+        match self.debian_file("debian/source/format")? {
+            Some(format) => {
+                let format = String::from_utf8_lossy(&format).trim().to_string();
+                if format != SOURCE_FORMAT {
+                    return Err(DebianError::SourcePackageFormatUnsupported(format));
+                }
+            }
+            None => {
+                debian_files.add_file_entry(
+                    "debian/source/format",
+                    FileEntry::new_from_data(format!("{}\n", SOURCE_FORMAT).into_bytes(), false),
+                )?;
+            }
+        }
+
+        let version = match &self.version {
+            Some(version) => version.clone(),
+            None => {
+                let data = self
+                    .debian_file("debian/changelog")?
+                    .ok_or(DebianError::SourcePackageMissingFile("debian/changelog"))?;
+
+                Changelog::parse_str(&String::from_utf8_lossy(&data))?
+                    .latest()
+                    .ok_or(DebianError::SourcePackageMissingFile(
+                        "debian/changelog entry",
+                    ))?
+                    .version
+                    .to_string()
+            }
+        };
+        let parsed = PackageVersion::parse(&version)?;
+        let revision = parsed
+            .debian_revision()
+            .ok_or_else(|| DebianError::SourcePackageVersionInvalid(version.clone()))?;
+        let upstream_version = parsed.upstream_version();
+
+        let mtime = resolve_mtime(self.mtime, self.source_date_epoch);
+
+        let orig = match &self.upstream {
+            Some(UpstreamSource::Files(files)) => {
+                let prefix = PathBuf::from(format!("{}-{}", source, upstream_version));
+                let tar = write_source_tar(files, Some(&prefix), mtime)?;
+                (
+                    format!("tar{}", self.orig_compression.extension()),
+                    self.orig_compression
+                        .compress_with_options(&mut Cursor::new(tar), &self.compression_options)?,
+                )
+            }
+            Some(UpstreamSource::Tarball(extension, data)) => {
+                (extension.trim_start_matches('.').to_string(), data.clone())
+            }
+            None => return Err(DebianError::SourcePackageMissingFile("upstream source")),
+        };
+
+        let debian_tar = write_source_tar(&debian_files, None, mtime)?;
+        let debian_tar = self
+            .debian_compression
+            .compress_with_options(&mut Cursor::new(debian_tar), &self.compression_options)?;
+
+        let files = vec![
+            (
+                format!("{}_{}.orig.{}", source, upstream_version, orig.0),
+                orig.1,
+            ),
+            (
+                format!(
+                    "{}_{}-{}.debian.tar{}",
+                    source,
+                    upstream_version,
+                    revision,
+                    self.debian_compression.extension()
+                ),
+                debian_tar,
+            ),
+        ];
+
+        let mut para = ControlParagraph::default();
+        para.set_field_from_string("Format".into(), SOURCE_FORMAT.into());
+        para.set_field_from_string("Source".into(), source.to_string().into());
+
+        let mut binaries = vec![];
+        let mut architectures = vec![];
+        let mut package_list = vec![];
+        for binary in control.binary_paragraphs() {
+            let package = binary.required_field_str("Package")?;
+            let arches = binary
+                .iter_field_words("Architecture")
+                .ok_or_else(|| {
+                    DebianError::ControlRequiredFieldMissing("Architecture".to_string())
+                })?
+                .collect::<Vec<_>>();
+
+            binaries.push(package);
+            for arch in &arches {
+                if !architectures.contains(arch) {
+                    architectures.push(*arch);
+                }
+            }
+            package_list.push(format!(
+                "{} {} {} {} arch={}",
+                package,
+                binary.field_str("Package-Type").unwrap_or("deb"),
+                binary
+                    .field_str("Section")
+                    .or_else(|| general.field_str("Section"))
+                    .unwrap_or("unknown"),
+                binary
+                    .field_str("Priority")
+                    .or_else(|| general.field_str("Priority"))
+                    .unwrap_or("unknown"),
+                arches.join(",")
+            ));
+        }
+
+        para.set_field_from_string("Binary".into(), binaries.join(", ").into());
+        para.set_field_from_string("Architecture".into(), architectures.join(" ").into());
+        para.set_field_from_string("Version".into(), version.clone().into());
+        general.required_field_str("Maintainer")?;
+        for name in DSC_COPIED_FIELDS {
+            if let Some(value) = general.field_str(name) {
+                para.set_field_from_string((*name).into(), value.to_string().into());
+            }
+        }
+        para.set_field(ControlField::from_lines(
+            "Package-List".into(),
+            std::iter::once("".to_string()).chain(package_list),
+        ));
+
+        let digests = files
+            .iter()
+            .map(|(_, data)| {
+                let mut digester = MultiDigester::default();
+                digester.update(data);
+                digester.finish()
+            })
+            .collect::<Vec<_>>();
+
+        for checksum in [ChecksumType::Sha1, ChecksumType::Sha256, ChecksumType::Md5] {
+            para.set_field(file_list_field(
+                checksum,
+                files
+                    .iter()
+                    .zip(&digests)
+                    .map(|((filename, data), digests)| FileListEntry {
+                        filename,
+                        digest: digests.digest_from_checksum(checksum).clone(),
+                        size: data.len() as u64,
+                        section: None,
+                        priority: None,
+                    }),
+            ));
+        }
+
+        let version_no_epoch = match parsed.epoch() {
+            Some(_) => format!("{}-{}", upstream_version, revision),
+            None => version,
+        };
+
+        Ok(SourcePackage {
+            dsc_filename: format!("{}_{}.dsc", source, version_no_epoch),
+            dsc: para.into(),
+            files,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, std::io::Read};
+
+    const CONTROL: &str = "Source: hello
+Section: devel
+Priority: optional
+Maintainer: Jane Doe <jane@example.com>
+Build-Depends: debhelper-compat (= 13)
+Standards-Version: 4.6.2
+
+Package: hello
+Architecture: any
+Description: example package
+
+Package: hello-doc
+Architecture: all
+Section: doc
+Description: example documentation
+";
+
+    fn builder() -> Result<SourcePackageBuilder> {
+        SourcePackageBuilder::default()
+            .set_mtime(Some(SystemTime::UNIX_EPOCH))
+            .add_upstream_file(
+                "hello.c",
+                FileEntry::new_from_data(b"int main;".to_vec(), false),
+            )?
+            .add_upstream_file("build.sh", FileEntry::new_from_data(b"make".to_vec(), true))?
+            .add_debian_file(
+                "control",
+                FileEntry::new_from_data(CONTROL.as_bytes().to_vec(), false),
+            )?
+            .add_debian_file(
+                "changelog",
+                FileEntry::new_from_data(
+                    b"hello (1:2.10-3) unstable; urgency=medium\n\n  * Upload.\n\n -- Jane Doe <jane@example.com>  Sat, 02 Nov 2024 12:00:00 +0000\n"
+                        .to_vec(),
+                    false,
+                ),
+            )
+    }
+
+    fn tar_paths(data: &[u8]) -> Result<Vec<String>> {
+        let mut tar = vec![];
+        xz2::read::XzDecoder::new(data).read_to_end(&mut tar)?;
+
+        tar::Archive::new(Cursor::new(tar))
+            .entries()?
+            .map(|entry| Ok(entry?.path()?.display().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn build_quilt() -> Result<()> {
+        let package = builder()?.build()?;
+
+        assert_eq!(package.dsc_filename(), "hello_2.10-3.dsc");
+        let files = package.files().collect::<Vec<_>>();
+        assert_eq!(files[0].0, "hello_2.10.orig.tar.xz");
+        assert_eq!(files[1].0, "hello_2.10-3.debian.tar.xz");
+        assert_eq!(
+            tar_paths(files[0].1)?,
+            vec!["hello-2.10", "hello-2.10/build.sh", "hello-2.10/hello.c"]
+        );
+        assert_eq!(
+            tar_paths(files[1].1)?,
+            vec![
+                "debian",
+                "debian/changelog",
+                "debian/control",
+                "debian/source",
+                "debian/source/format"
+            ]
+        );
+
+        let mut dsc = vec![];
+        package.dsc().write(&mut dsc)?;
+        let dsc = DebianSourceControlFile::from_reader(Cursor::new(dsc))?;
+        assert_eq!(dsc.format()?, SOURCE_FORMAT);
+        assert_eq!(dsc.version_str()?, "1:2.10-3");
+        assert_eq!(
+            dsc.binary().unwrap().collect::<Vec<_>>(),
+            vec!["hello", "hello-doc"]
+        );
+        assert_eq!(
+            dsc.architecture().unwrap().collect::<Vec<_>>(),
+            vec!["any", "all"]
+        );
+        assert_eq!(
+            dsc.field_str("Build-Depends"),
+            Some("debhelper-compat (= 13)")
+        );
+
+        let packages = dsc.package_list().unwrap().collect::<Result<Vec<_>>>()?;
+        assert_eq!(packages[1].name, "hello-doc");
+        assert_eq!(packages[1].section, "doc");
+        assert_eq!(packages[1].priority, "optional");
+        assert_eq!(packages[1].extra, vec!["arch=all"]);
+
+        let entries = dsc
+            .checksums_sha256()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].filename, files[1].0);
+        assert_eq!(entries[1].size, files[1].1.len() as u64);
+
+        // Output is deterministic.
+        let again = builder()?.build()?;
+        assert_eq!(again.files().collect::<Vec<_>>(), files);
+
+        Ok(())
+    }
+
+    #[test]
+    fn build_errors() -> Result<()> {
+        assert!(matches!(
+            builder()?.set_version("2.10").build(),
+            Err(DebianError::SourcePackageVersionInvalid(_))
+        ));
+        assert!(matches!(
+            builder()?
+                .add_debian_file(
+                    "source/format",
+                    FileEntry::new_from_data(b"3.0 (native)\n".to_vec(), false)
+                )?
+                .build(),
+            Err(DebianError::SourcePackageFormatUnsupported(_))
+        ));
+        assert!(matches!(
+            builder()?.set_orig_compression(DebCompression::Zstandard(3)),
+            Err(DebianError::SourcePackageCompressionUnsupported(_))
+        ));
+
+        let package = builder()?
+            .set_upstream_tarball("hello-2.10.tar.gz", b"tarball".to_vec())?
+            .build()?;
+        assert_eq!(
+            package.files().next(),
+            Some(("hello_2.10.orig.tar.gz", b"tarball".as_slice()))
+        );
+
+        Ok(())
+    }
+}
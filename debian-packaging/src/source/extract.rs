@@ -0,0 +1,352 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Extract `3.0 (quilt)` source packages. */
+
+use {
+    crate::{
+        deb::reader::{reader_from_filename, sanitize_data_path},
+        debian_source_control::DebianSourceControlFile,
+        error::{DebianError, Result},
+        io::MultiDigester,
+        repository::release::ChecksumType,
+        source::{
+            builder::SOURCE_FORMAT,
+            quilt::{parse_series, Patch},
+        },
+    },
+    simple_file_manifest::{FileEntry, FileManifest},
+    std::{
+        collections::BTreeMap,
+        io::{BufReader, Cursor, Read},
+        path::{Path, PathBuf},
+    },
+};
+
+/// Path of the quilt series file within an extracted source package.
+const SERIES_PATH: &str = "debian/patches/series";
+
+/// Split a tarball filename into its kind (e.g. `orig`, `orig-docs`, `debian`) and
+/// compression extension (e.g. `.xz`).
+fn tarball_kind(filename: &str) -> Option<(&str, &str)> {
+    let (stem, extension) = match filename.rfind(".tar") {
+        Some(pos) => (&filename[..pos], &filename[pos + 4..]),
+        None => return None,
+    };
+
+    let kind = stem.rsplit('.').next()?;
+
+    if kind == "debian" || kind == "orig" || kind.starts_with("orig-") {
+        Some((kind, extension))
+    } else {
+        None
+    }
+}
+
+/// Add entries from a tar archive to a manifest.
+///
+/// `strip` leading path components are removed from each entry and the result is joined
+/// to `prefix`.
+fn add_tar_entries(
+    files: &mut FileManifest,
+    reader: impl Read,
+    strip: usize,
+    prefix: &Path,
+) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        let path = sanitize_data_path(&entry.path_bytes())?
+            .components()
+            .skip(strip)
+            .collect::<PathBuf>();
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let path = prefix.join(path);
+
+        match entry.header().entry_type() {
+            tar::EntryType::Directory => {}
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let executable = entry.header().mode()? & 0o111 != 0;
+                let mut data = vec![];
+                entry.read_to_end(&mut data)?;
+                files.add_file_entry(path, FileEntry::new_from_data(data, executable))?;
+            }
+            tar::EntryType::Symlink => {
+                let target = entry.link_name()?.ok_or_else(|| {
+                    DebianError::SourcePackageUnsupportedEntry(path.display().to_string())
+                })?;
+                files.add_symlink(path, target)?;
+            }
+            _ => {
+                return Err(DebianError::SourcePackageUnsupportedEntry(
+                    path.display().to_string(),
+                ))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Extracts `3.0 (quilt)` source packages to a [FileManifest].
+///
+/// Files referenced by the `.dsc` are verified against its `Files` and `Checksums-*`
+/// fields. The upstream tarball is unpacked with its top-level directory removed.
+/// Additional upstream tarballs (`.orig-<component>.tar.*`) are unpacked into a
+/// `<component>/` directory. Any upstream `debian/` directory is then replaced by the
+/// content of the `.debian.tar.*` archive.
+///
+/// By default, patches in `debian/patches/` are not applied, yielding the tree as stored
+/// in the tarballs. Use [Self::set_apply_patches()] to obtain the patched tree, like
+/// `dpkg-source -x` does.
+#[derive(Clone, Debug, Default)]
+pub struct SourcePackageExtractor {
+    apply_patches: bool,
+}
+
+impl SourcePackageExtractor {
+    /// Set whether to apply the quilt patch series after unpacking.
+    ///
+    /// Patches are applied in the order of `debian/patches/series`, honoring per-patch
+    /// `-pN` options.
+    #[must_use]
+    pub fn set_apply_patches(mut self, value: bool) -> Self {
+        self.apply_patches = value;
+        self
+    }
+
+    /// Extract the source package described by a `.dsc`.
+    ///
+    /// `resolve` is called with the filename of each file referenced by the `.dsc` and
+    /// must return its content.
+    pub fn extract(
+        &self,
+        dsc: &DebianSourceControlFile,
+        resolve: impl Fn(&str) -> Result<Vec<u8>>,
+    ) -> Result<FileManifest> {
+        let format = dsc.format()?;
+        if format != SOURCE_FORMAT {
+            return Err(DebianError::SourcePackageFormatUnsupported(
+                format.to_string(),
+            ));
+        }
+
+        let mut content = BTreeMap::new();
+        for checksum in ChecksumType::preferred_order() {
+            // `Files` is required. Other fields are optional.
+            let entries = if checksum == ChecksumType::Md5 {
+                Some(dsc.files()?)
+            } else {
+                dsc.checksums(checksum)
+            };
+
+            for entry in entries.into_iter().flatten() {
+                let entry = entry?;
+
+                if !content.contains_key(entry.filename) {
+                    content.insert(entry.filename, resolve(entry.filename)?);
+                }
+                let data = &content[entry.filename];
+
+                let mut digester = MultiDigester::default();
+                digester.update(data);
+                if data.len() as u64 != entry.size
+                    || !digester.finish().matches_digest(&entry.digest)
+                {
+                    return Err(DebianError::SourcePackageFileIntegrity(
+                        entry.filename.to_string(),
+                    ));
+                }
+            }
+        }
+
+        let mut upstream = vec![];
+        let mut debian = None;
+        for (filename, data) in &content {
+            match tarball_kind(filename) {
+                Some(("debian", extension)) => debian = Some((extension, data)),
+                Some((kind, extension)) => upstream.push((kind, extension, data)),
+                None => {}
+            }
+        }
+
+        if !upstream.iter().any(|(kind, _, _)| *kind == "orig") {
+            return Err(DebianError::SourcePackageMissingFile("upstream tarball"));
+        }
+        let (extension, data) =
+            debian.ok_or(DebianError::SourcePackageMissingFile("debian tarball"))?;
+
+        let mut files = FileManifest::new_with_links();
+        for (kind, extension, data) in upstream {
+            let prefix = kind.strip_prefix("orig-").unwrap_or_default();
+            let reader = reader_from_filename(extension, Cursor::new(data))?;
+            add_tar_entries(&mut files, reader, 1, Path::new(prefix))?;
+        }
+
+        let upstream_debian = files
+            .iter_entries()
+            .map(|(path, _)| path.to_path_buf())
+            .filter(|path| path.starts_with("debian"))
+            .collect::<Vec<_>>();
+        for path in upstream_debian {
+            files.remove(path);
+        }
+
+        let reader = reader_from_filename(extension, Cursor::new(data))?;
+        add_tar_entries(&mut files, reader, 0, Path::new(""))?;
+
+        if self.apply_patches {
+            if let Some(series) = files.get(SERIES_PATH) {
+                let series = String::from_utf8_lossy(&series.resolve_content()?).to_string();
+
+                for entry in parse_series(&series)? {
+                    let data = files
+                        .get(Path::new("debian/patches").join(&entry.name))
+                        .ok_or_else(|| {
+                            DebianError::QuiltPatchInvalid(entry.name.clone(), "not found".into())
+                        })?
+                        .resolve_content()?;
+
+                    Patch::parse(&entry.name, &data)?.apply(&mut files, entry.strip)?;
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Extract the source package described by a `.dsc` file on the filesystem.
+    ///
+    /// Referenced files are read from the directory containing the `.dsc`. Their names
+    /// must not contain `/` or `..`. The `.dsc` may be PGP armored. Its signature is NOT
+    /// verified.
+    pub fn extract_path(&self, dsc_path: impl AsRef<Path>) -> Result<FileManifest> {
+        let dsc_path = dsc_path.as_ref();
+        let directory = dsc_path.parent().unwrap_or_else(|| Path::new(""));
+
+        let data = std::fs::read(dsc_path)?;
+        let dsc = if data.starts_with(b"-----BEGIN PGP SIGNED MESSAGE-----") {
+            DebianSourceControlFile::from_armored_reader(BufReader::new(Cursor::new(data)))?
+        } else {
+            DebianSourceControlFile::from_reader(BufReader::new(Cursor::new(data)))?
+        };
+
+        self.extract(&dsc, |filename| {
+            if filename.is_empty() || filename.contains('/') || filename.contains("..") {
+                return Err(DebianError::SourcePackageFilenameInvalid(
+                    filename.to_string(),
+                ));
+            }
+
+            Ok(std::fs::read(directory.join(filename))?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::source::builder::SourcePackageBuilder, std::time::SystemTime};
+
+    const PATCH: &[u8] = b"Description: Greet the world
+---
+--- a/hello.c
++++ b/hello.c
+@@ -1 +1 @@
+-hello
++hello, world
+";
+
+    #[test]
+    fn extract_quilt() -> Result<()> {
+        let package = SourcePackageBuilder::default()
+            .set_mtime(Some(SystemTime::UNIX_EPOCH))
+            .set_version("1.0-1")
+            .add_upstream_file(
+                "hello.c",
+                FileEntry::new_from_data(b"hello\n".to_vec(), false),
+            )?
+            .add_upstream_file(
+                "debian/rules",
+                FileEntry::new_from_data(b"upstream".to_vec(), false),
+            )?
+            .add_debian_file(
+                "control",
+                FileEntry::new_from_data(
+                    b"Source: hello\nMaintainer: me\n\nPackage: hello\nArchitecture: all\n"
+                        .to_vec(),
+                    false,
+                ),
+            )?
+            .add_debian_file(
+                "patches/series",
+                FileEntry::new_from_data(b"greet.patch\n".to_vec(), false),
+            )?
+            .add_debian_file(
+                "patches/greet.patch",
+                FileEntry::new_from_data(PATCH.to_vec(), false),
+            )?
+            .build()?;
+
+        let mut dsc = vec![];
+        package.dsc().write(&mut dsc)?;
+        let dsc = DebianSourceControlFile::from_reader(Cursor::new(dsc))?;
+        let resolve = |filename: &str| {
+            package
+                .files()
+                .find(|(name, _)| *name == filename)
+                .map(|(_, data)| data.to_vec())
+                .ok_or_else(|| DebianError::Other(filename.to_string()))
+        };
+
+        let files = SourcePackageExtractor::default().extract(&dsc, resolve)?;
+        assert_eq!(files.get("hello.c").unwrap().resolve_content()?, b"hello\n");
+        assert!(files.get("debian/rules").is_none());
+        assert!(files.get("debian/source/format").is_some());
+
+        let files = SourcePackageExtractor::default()
+            .set_apply_patches(true)
+            .extract(&dsc, resolve)?;
+        assert_eq!(
+            files.get("hello.c").unwrap().resolve_content()?,
+            b"hello, world\n"
+        );
+
+        let corrupt = |filename: &str| {
+            let mut data = resolve(filename)?;
+            data.push(0);
+            Ok(data)
+        };
+        assert!(matches!(
+            SourcePackageExtractor::default().extract(&dsc, corrupt),
+            Err(DebianError::SourcePackageFileIntegrity(_))
+        ));
+
+        let td = tempfile::TempDir::new()?;
+        for (name, data) in package.files() {
+            std::fs::write(td.path().join(name), data)?;
+        }
+        let mut dsc_data = vec![];
+        package.dsc().write(&mut dsc_data)?;
+        std::fs::write(td.path().join("hello.dsc"), &dsc_data)?;
+        let files = SourcePackageExtractor::default().extract_path(td.path().join("hello.dsc"))?;
+        assert!(files.get("hello.c").is_some());
+
+        // Files outside the directory holding the .dsc are never read.
+        let (name, _) = package.files().next().unwrap();
+        let escaping = String::from_utf8(dsc_data)
+            .unwrap()
+            .replace(name, &format!("../{}", name));
+        std::fs::write(td.path().join("escaping.dsc"), escaping)?;
+        assert!(matches!(
+            SourcePackageExtractor::default().extract_path(td.path().join("escaping.dsc")),
+            Err(DebianError::SourcePackageFilenameInvalid(_))
+        ));
+
+        Ok(())
+    }
+}
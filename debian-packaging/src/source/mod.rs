@@ -0,0 +1,13 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Interfaces for Debian source packages.
+
+A source package consists of a `.dsc` file and the files it references. The `3.0 (quilt)`
+format is described at <https://manpages.debian.org/unstable/dpkg-dev/dpkg-source.1.en.html>.
+*/
+
+pub mod builder;
+pub mod extract;
+pub mod quilt;
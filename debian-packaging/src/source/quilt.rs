@@ -0,0 +1,429 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Quilt patch series, as used by `3.0 (quilt)` source packages.
+
+Patches live in `debian/patches/` and are applied in the order listed in
+`debian/patches/series`. Each patch is a unified diff optionally preceded by a free-form
+header, conventionally following [DEP-3](https://dep-team.pages.debian.net/deps/dep3/).
+*/
+
+use {
+    crate::error::{DebianError, Result},
+    simple_file_manifest::{FileEntry, FileManifest},
+    std::path::PathBuf,
+};
+
+/// An entry in a quilt `series` file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SeriesEntry {
+    /// Name of the patch, relative to the patches directory.
+    pub name: String,
+
+    /// Number of leading path components to strip from paths in the patch.
+    pub strip: usize,
+}
+
+/// Parse the content of a quilt `series` file.
+///
+/// Blank lines and `#` comments are ignored. Patches default to stripping 1 path
+/// component. A `-pN` option after the patch name overrides this.
+pub fn parse_series(data: &str) -> Result<Vec<SeriesEntry>> {
+    let mut entries = vec![];
+
+    for line in data.lines() {
+        let line = match line.find('#') {
+            Some(0) => continue,
+            Some(pos) if line[..pos].ends_with(char::is_whitespace) => &line[..pos],
+            _ => line,
+        };
+
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            continue;
+        };
+
+        let mut strip = 1;
+        for option in words {
+            strip = option
+                .strip_prefix("-p")
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| DebianError::QuiltSeriesInvalid(line.to_string()))?;
+        }
+
+        entries.push(SeriesEntry {
+            name: name.to_string(),
+            strip,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// A contiguous change within a file.
+#[derive(Clone, Debug, Default)]
+struct Hunk {
+    /// 1-based line number of the first old line.
+    old_start: usize,
+    /// Lines being replaced, including their line endings.
+    old_lines: Vec<Vec<u8>>,
+    /// Replacement lines, including their line endings.
+    new_lines: Vec<Vec<u8>>,
+}
+
+/// Changes to a single file within a [Patch].
+#[derive(Clone, Debug)]
+struct FilePatch {
+    /// Path before the change. `None` if the file is created.
+    old_path: Option<String>,
+    /// Path after the change. `None` if the file is deleted.
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+/// Parse a path from a `---` or `+++` line.
+fn parse_diff_path(value: &[u8]) -> Option<String> {
+    let value = String::from_utf8_lossy(value);
+    // Anything after a tab is a timestamp.
+    let path = value.split('\t').next().unwrap_or_default().trim_end();
+
+    if path == "/dev/null" {
+        None
+    } else {
+        Some(path.to_string())
+    }
+}
+
+/// Parse a `<start>[,<count>]` range from a hunk header.
+fn parse_hunk_range(value: &str) -> Option<(usize, usize)> {
+    match value.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((value.parse().ok()?, 1)),
+    }
+}
+
+/// A patch in unified diff format.
+#[derive(Clone, Debug)]
+pub struct Patch {
+    name: String,
+    header: String,
+    files: Vec<FilePatch>,
+}
+
+impl Patch {
+    /// Parse a patch from its content.
+    ///
+    /// `name` is used to identify the patch in errors.
+    pub fn parse(name: impl ToString, data: &[u8]) -> Result<Self> {
+        let name = name.to_string();
+        let invalid = |reason: &str| DebianError::QuiltPatchInvalid(name.clone(), reason.into());
+
+        let lines = data.split_inclusive(|c| *c == b'\n').collect::<Vec<_>>();
+
+        let mut header = None;
+        let mut files = vec![];
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = lines[i];
+
+            if !(line.starts_with(b"--- ")
+                && lines.get(i + 1).is_some_and(|l| l.starts_with(b"+++ ")))
+            {
+                if files.is_empty() {
+                    header.get_or_insert_with(Vec::new).extend_from_slice(line);
+                }
+                i += 1;
+                continue;
+            }
+
+            let mut file = FilePatch {
+                old_path: parse_diff_path(&line[4..]),
+                new_path: parse_diff_path(&lines[i + 1][4..]),
+                hunks: vec![],
+            };
+            if file.old_path.is_none() && file.new_path.is_none() {
+                return Err(invalid("diff between /dev/null and /dev/null"));
+            }
+            i += 2;
+
+            while i < lines.len() && lines[i].starts_with(b"@@ ") {
+                let header_line = String::from_utf8_lossy(lines[i]);
+                let mut words = header_line.split_ascii_whitespace().skip(1);
+                let (old_start, old_count) = words
+                    .next()
+                    .and_then(|v| v.strip_prefix('-'))
+                    .and_then(parse_hunk_range)
+                    .ok_or_else(|| invalid("malformed hunk header"))?;
+                let (_, new_count) = words
+                    .next()
+                    .and_then(|v| v.strip_prefix('+'))
+                    .and_then(parse_hunk_range)
+                    .ok_or_else(|| invalid("malformed hunk header"))?;
+                i += 1;
+
+                let mut hunk = Hunk {
+                    old_start,
+                    ..Default::default()
+                };
+
+                while hunk.old_lines.len() < old_count || hunk.new_lines.len() < new_count {
+                    let line = lines.get(i).ok_or_else(|| invalid("truncated hunk"))?;
+                    i += 1;
+
+                    match line.first() {
+                        Some(b' ') => {
+                            hunk.old_lines.push(line[1..].to_vec());
+                            hunk.new_lines.push(line[1..].to_vec());
+                        }
+                        // Context lines consisting of only a newline sometimes lose their space.
+                        Some(b'\n') => {
+                            hunk.old_lines.push(line.to_vec());
+                            hunk.new_lines.push(line.to_vec());
+                        }
+                        Some(b'-') => hunk.old_lines.push(line[1..].to_vec()),
+                        Some(b'+') => hunk.new_lines.push(line[1..].to_vec()),
+                        Some(b'\\') => {}
+                        _ => return Err(invalid("unexpected line in hunk")),
+                    }
+
+                    // `\ No newline at end of file` applies to the line preceding it.
+                    if lines.get(i).is_some_and(|l| l.starts_with(b"\\")) {
+                        let targets = match line.first() {
+                            Some(b'-') => [hunk.old_lines.last_mut(), None],
+                            Some(b'+') => [None, hunk.new_lines.last_mut()],
+                            _ => [hunk.old_lines.last_mut(), hunk.new_lines.last_mut()],
+                        };
+                        for target in targets.into_iter().flatten() {
+                            if target.ends_with(b"\n") {
+                                target.pop();
+                            }
+                        }
+                        i += 1;
+                    }
+                }
+
+                file.hunks.push(hunk);
+            }
+
+            files.push(file);
+        }
+
+        if files.is_empty() {
+            return Err(invalid("no file changes"));
+        }
+
+        Ok(Self {
+            name,
+            header: header
+                .map(|h| String::from_utf8_lossy(&h).to_string())
+                .unwrap_or_default(),
+            files,
+        })
+    }
+
+    /// The name of this patch.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Text preceding the first file change, such as DEP-3 header fields.
+    pub fn header(&self) -> &str {
+        &self.header
+    }
+
+    /// Obtain the value of a `Field: value` line in the header, e.g. `Description`.
+    ///
+    /// Only the first line of the value is returned.
+    pub fn header_field(&self, name: &str) -> Option<&str> {
+        self.header.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+
+            if key.eq_ignore_ascii_case(name) {
+                Some(value.trim())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Apply this patch to files in a manifest.
+    ///
+    /// `strip` leading components are removed from paths in the patch. Hunks must match
+    /// exactly but may be found at an offset from the line numbers recorded in the patch.
+    pub fn apply(&self, files: &mut FileManifest, strip: usize) -> Result<()> {
+        let strip_path = |path: &str| -> Result<PathBuf> {
+            let components = path.split('/').filter(|c| !c.is_empty() && *c != ".");
+
+            let stripped = components.skip(strip).collect::<PathBuf>();
+            if stripped.as_os_str().is_empty() {
+                Err(DebianError::QuiltPatchInvalid(
+                    self.name.clone(),
+                    format!("cannot strip {} components from {}", strip, path),
+                ))
+            } else {
+                Ok(stripped)
+            }
+        };
+
+        for file in &self.files {
+            let old_path = file.old_path.as_deref().map(strip_path).transpose()?;
+            let new_path = file.new_path.as_deref().map(strip_path).transpose()?;
+            let path = new_path
+                .as_ref()
+                .or(old_path.as_ref())
+                .expect("validated at parse time")
+                .clone();
+            let failed =
+                || DebianError::QuiltPatchFailed(self.name.clone(), path.display().to_string());
+
+            let (content, executable) = match &old_path {
+                Some(old_path) => {
+                    let entry = files.get(old_path).ok_or_else(failed)?;
+                    (entry.resolve_content()?, entry.is_executable())
+                }
+                None => (vec![], false),
+            };
+            let lines = content.split_inclusive(|c| *c == b'\n').collect::<Vec<_>>();
+
+            let mut output = Vec::with_capacity(content.len());
+            let mut cursor = 0;
+            let mut offset = 0isize;
+
+            for hunk in &file.hunks {
+                let len = hunk.old_lines.len();
+                let expected = (hunk.old_start.saturating_sub(1) as isize + offset).max(0) as usize;
+                let matches = |pos: usize| {
+                    pos >= cursor
+                        && pos + len <= lines.len()
+                        && lines[pos..pos + len]
+                            .iter()
+                            .zip(&hunk.old_lines)
+                            .all(|(a, b)| *a == b.as_slice())
+                };
+
+                let pos = (0..=lines.len())
+                    .flat_map(|d| [expected.checked_add(d), expected.checked_sub(d)])
+                    .flatten()
+                    .find(|pos| matches(*pos))
+                    .ok_or_else(failed)?;
+
+                for line in &lines[cursor..pos] {
+                    output.extend_from_slice(line);
+                }
+                for line in &hunk.new_lines {
+                    output.extend_from_slice(line);
+                }
+                cursor = pos + len;
+                offset = pos as isize - hunk.old_start.saturating_sub(1) as isize;
+            }
+            for line in &lines[cursor..] {
+                output.extend_from_slice(line);
+            }
+
+            if let Some(old_path) = &old_path {
+                files.remove(old_path);
+            }
+            if let Some(new_path) = new_path {
+                files.add_file_entry(new_path, FileEntry::new_from_data(output, executable))?;
+            } else if !output.is_empty() {
+                return Err(failed());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PATCH: &[u8] = b"Description: Fix greeting
+Author: Jane Doe <jane@example.com>
+
+---
+Index: hello/hello.c
+===================================================================
+--- hello.orig/hello.c\t2024-01-01 00:00:00
++++ hello/hello.c\t2024-01-01 00:00:00
+@@ -2,3 +2,3 @@
+ int main() {
+-  puts(\"hello\");
++  puts(\"hello, world\");
+ }
+--- /dev/null
++++ hello/NEWS
+@@ -0,0 +1 @@
++news
+\\ No newline at end of file
+--- a/README
++++ /dev/null
+@@ -1 +0,0 @@
+-readme
+";
+
+    #[test]
+    fn series() -> Result<()> {
+        let series = parse_series("# comment\nfirst.patch\n\nsecond.patch -p0 # trailing\n")?;
+        assert_eq!(
+            series,
+            vec![
+                SeriesEntry {
+                    name: "first.patch".into(),
+                    strip: 1
+                },
+                SeriesEntry {
+                    name: "second.patch".into(),
+                    strip: 0
+                }
+            ]
+        );
+
+        assert!(matches!(
+            parse_series("bad.patch -R"),
+            Err(DebianError::QuiltSeriesInvalid(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn apply() -> Result<()> {
+        let patch = Patch::parse("fix.patch", PATCH)?;
+        assert_eq!(patch.header_field("description"), Some("Fix greeting"));
+        assert!(patch.header().ends_with("===\n"));
+
+        let mut files = FileManifest::default();
+        // An extra leading line exercises applying hunks at an offset.
+        files.add_file_entry(
+            "hello.c",
+            FileEntry::new_from_data(
+                b"// extra\n#include <stdio.h>\nint main() {\n  puts(\"hello\");\n}\n".to_vec(),
+                false,
+            ),
+        )?;
+        files.add_file_entry(
+            "README",
+            FileEntry::new_from_data(b"readme\n".to_vec(), false),
+        )?;
+
+        patch.apply(&mut files, 1)?;
+
+        assert_eq!(
+            files.get("hello.c").unwrap().resolve_content()?,
+            b"// extra\n#include <stdio.h>\nint main() {\n  puts(\"hello, world\");\n}\n"
+        );
+        assert_eq!(files.get("NEWS").unwrap().resolve_content()?, b"news");
+        assert!(files.get("README").is_none());
+
+        // Applying again fails because the old content is gone.
+        assert!(matches!(
+            patch.apply(&mut files, 1),
+            Err(DebianError::QuiltPatchFailed(_, _))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,372 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Content-addressed storage of fetched artifacts.
+
+[ContentStore] stores files in a local directory keyed by their SHA-256 digest. Since
+repositories publish the digests of every index file and package, content fetched once
+can be reused for every other repository publishing the same bytes.
+
+[ContentStoreResolver] wraps a [DataResolver] so fetches with a known SHA-256 digest are
+served from the store, populating it on first access. [ContentStore::export()] places
+stored objects at arbitrary paths, e.g. to assemble an offline bundle of packages.
+
+# Layout
+
+Objects are stored at `sha256/<first 2 hex digits>/<hex digest>` under the root
+directory. Objects are written to a `tmp` directory first and renamed into place once
+complete, so partially written objects are never visible.
+*/
+
+use {
+    crate::{
+        error::{DebianError, Result},
+        io::{ContentDigest, DataResolver, DigestingReader},
+        repository::release::ChecksumType,
+    },
+    async_trait::async_trait,
+    futures::{io::BufReader, AsyncRead},
+    std::{
+        ops::Range,
+        path::{Path, PathBuf},
+        pin::Pin,
+    },
+};
+
+/// A content-addressed store of files on the local filesystem.
+#[derive(Clone, Debug)]
+pub struct ContentStore {
+    root_dir: PathBuf,
+}
+
+impl ContentStore {
+    /// Construct a new instance, bound to the root directory specified.
+    ///
+    /// The directory does not need to exist.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            root_dir: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The root directory of this store.
+    pub fn root_dir(&self) -> &Path {
+        &self.root_dir
+    }
+
+    /// Obtain the filesystem path of the object having a given digest.
+    ///
+    /// Errors if the digest isn't a 32 byte SHA-256 digest.
+    pub fn object_path(&self, digest: &ContentDigest) -> Result<PathBuf> {
+        if digest.checksum_type() != ChecksumType::Sha256 {
+            return Err(DebianError::ContentStoreDigestUnsupported(
+                digest.release_field_name(),
+            ));
+        }
+
+        if digest.digest_bytes().len() != 32 {
+            return Err(DebianError::ContentStoreDigestLength(
+                digest.digest_bytes().len(),
+            ));
+        }
+
+        let hex = digest.digest_hex();
+
+        Ok(self.root_dir.join("sha256").join(&hex[0..2]).join(hex))
+    }
+
+    /// Whether an object having a given digest is stored.
+    pub fn contains(&self, digest: &ContentDigest) -> bool {
+        self.object_path(digest)
+            .map(|path| path.is_file())
+            .unwrap_or(false)
+    }
+
+    /// Open a stored object for reading.
+    pub fn open(&self, digest: &ContentDigest) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        let path = self.object_path(digest)?;
+
+        let f = std::fs::File::open(&path)
+            .map_err(|e| DebianError::RepositoryIoPath(format!("{}", path.display()), e))?;
+
+        Ok(Box::pin(BufReader::new(futures::io::AllowStdIo::new(f))))
+    }
+
+    /// Store content, returning its digest.
+    pub fn insert(&self, data: &[u8]) -> Result<ContentDigest> {
+        let digest = ContentDigest::compute(ChecksumType::Sha256, data);
+
+        if !self.contains(&digest) {
+            let temp_path = self.temp_path()?;
+            std::fs::write(&temp_path, data)?;
+            self.persist(&temp_path, &digest)?;
+        }
+
+        Ok(digest)
+    }
+
+    /// Store content read from a reader, returning its digest and size.
+    ///
+    /// Content is streamed to disk and never fully buffered in memory.
+    pub async fn insert_reader(
+        &self,
+        reader: impl AsyncRead + Unpin,
+    ) -> Result<(ContentDigest, u64)> {
+        let temp_path = self.temp_path()?;
+
+        let res = self.write_temp(&temp_path, reader).await;
+        let (digest, size) = match res {
+            Ok(v) => v,
+            Err(e) => {
+                std::fs::remove_file(&temp_path).ok();
+                return Err(e);
+            }
+        };
+
+        self.persist(&temp_path, &digest)?;
+
+        Ok((digest, size))
+    }
+
+    /// Ensure content from a [DataResolver] is stored, returning its object path.
+    ///
+    /// Nothing is fetched if an object with the expected digest is already stored.
+    /// Otherwise content is fetched with digest verification. `expected_digest` must be
+    /// SHA-256.
+    pub async fn fetch(
+        &self,
+        resolver: &(impl DataResolver + ?Sized),
+        path: &str,
+        expected_size: u64,
+        expected_digest: ContentDigest,
+    ) -> Result<PathBuf> {
+        let object_path = self.object_path(&expected_digest)?;
+
+        if object_path.is_file() {
+            return Ok(object_path);
+        }
+
+        let reader = resolver
+            .get_path_with_digest_verification(path, expected_size, expected_digest.clone())
+            .await?;
+
+        let (digest, _) = self.insert_reader(reader).await?;
+
+        if digest != expected_digest {
+            return Err(DebianError::ContentStoreDigestMismatch(
+                expected_digest.digest_hex(),
+                digest.digest_hex(),
+            ));
+        }
+
+        Ok(object_path)
+    }
+
+    /// Place a stored object at a filesystem path.
+    ///
+    /// A hard link is created if possible. Otherwise the object is copied. Parent
+    /// directories of `dest_path` are created as needed.
+    pub fn export(&self, digest: &ContentDigest, dest_path: impl AsRef<Path>) -> Result<()> {
+        let object_path = self.object_path(digest)?;
+        let dest_path = dest_path.as_ref();
+
+        if let Some(parent) = dest_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        if dest_path.exists() {
+            std::fs::remove_file(dest_path)?;
+        }
+
+        if std::fs::hard_link(&object_path, dest_path).is_err() {
+            std::fs::copy(&object_path, dest_path).map_err(|e| {
+                DebianError::RepositoryIoPath(format!("{}", object_path.display()), e)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn temp_path(&self) -> Result<PathBuf> {
+        let dir = self.root_dir.join("tmp");
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(dir.join(format!("{:016x}", rand::random::<u64>())))
+    }
+
+    async fn write_temp(
+        &self,
+        temp_path: &Path,
+        reader: impl AsyncRead + Unpin,
+    ) -> Result<(ContentDigest, u64)> {
+        let mut reader = DigestingReader::with_checksums(reader, [ChecksumType::Sha256]);
+        let mut f = futures::io::AllowStdIo::new(std::fs::File::create(temp_path)?);
+
+        let size = futures::io::copy(&mut reader, &mut f).await?;
+
+        let (_, digests) = reader.finish();
+
+        Ok((
+            digests.digest_from_checksum(ChecksumType::Sha256).clone(),
+            size,
+        ))
+    }
+
+    fn persist(&self, temp_path: &Path, digest: &ContentDigest) -> Result<()> {
+        let object_path = self.object_path(digest)?;
+
+        if let Some(parent) = object_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::rename(temp_path, &object_path)
+            .map_err(|e| DebianError::RepositoryIoPath(format!("{}", object_path.display()), e))?;
+
+        Ok(())
+    }
+}
+
+/// A [DataResolver] serving content from a [ContentStore].
+///
+/// Reads with SHA-256 digest verification are served from the store, fetching from the
+/// source resolver and storing content if it isn't present. Other reads are forwarded to
+/// the source resolver.
+pub struct ContentStoreResolver<R> {
+    store: ContentStore,
+    source: R,
+}
+
+impl<R: DataResolver + Send> ContentStoreResolver<R> {
+    /// Construct a new instance that stores content from a source [DataResolver].
+    pub fn new(store: ContentStore, source: R) -> Self {
+        Self { store, source }
+    }
+
+    /// The [ContentStore] backing this instance.
+    pub fn store(&self) -> &ContentStore {
+        &self.store
+    }
+
+    /// Obtain the source [DataResolver].
+    pub fn into_source(self) -> R {
+        self.source
+    }
+}
+
+#[async_trait]
+impl<R: DataResolver + Send> DataResolver for ContentStoreResolver<R> {
+    async fn get_path(&self, path: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        self.source.get_path(path).await
+    }
+
+    async fn get_path_with_range(
+        &self,
+        path: &str,
+        range: Range<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        self.source.get_path_with_range(path, range).await
+    }
+
+    async fn get_path_with_digest_verification(
+        &self,
+        path: &str,
+        expected_size: u64,
+        expected_digest: ContentDigest,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        if expected_digest.checksum_type() != ChecksumType::Sha256 {
+            return self
+                .source
+                .get_path_with_digest_verification(path, expected_size, expected_digest)
+                .await;
+        }
+
+        self.store
+            .fetch(&self.source, path, expected_size, expected_digest.clone())
+            .await?;
+
+        self.store.open(&expected_digest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        futures::AsyncReadExt,
+        std::sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    struct CountingResolver {
+        data: Vec<u8>,
+        fetches: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl DataResolver for CountingResolver {
+        async fn get_path(&self, _path: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+
+            Ok(Box::pin(futures::io::Cursor::new(self.data.clone())))
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_dedup() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let store = ContentStore::new(td.path().join("store"));
+
+        let data = b"package content".to_vec();
+        let digest = ContentDigest::compute(ChecksumType::Sha256, &data);
+        let size = data.len() as u64;
+
+        let resolver = ContentStoreResolver::new(
+            store.clone(),
+            CountingResolver {
+                data: data.clone(),
+                fetches: AtomicUsize::new(0),
+            },
+        );
+
+        for _ in 0..2 {
+            let mut buf = vec![];
+            resolver
+                .get_path_with_digest_verification("pool/a.deb", size, digest.clone())
+                .await?
+                .read_to_end(&mut buf)
+                .await?;
+            assert_eq!(buf, data);
+        }
+        assert_eq!(resolver.into_source().fetches.load(Ordering::SeqCst), 1);
+        assert!(store.contains(&digest));
+        assert_eq!(store.insert(&data)?, digest);
+
+        // Content not matching the expected digest is never stored.
+        let bad = ContentDigest::compute(ChecksumType::Sha256, b"other");
+        let source = CountingResolver {
+            data,
+            fetches: AtomicUsize::new(0),
+        };
+        assert!(store.fetch(&source, "x", size, bad.clone()).await.is_err());
+        assert!(!store.contains(&bad));
+        assert!(matches!(
+            store.object_path(&ContentDigest::compute(ChecksumType::Md5, b"")),
+            Err(DebianError::ContentStoreDigestUnsupported("MD5Sum"))
+        ));
+        // Malformed digests, e.g. from untrusted indices, are rejected.
+        for bytes in [vec![], vec![0xab], vec![0; 33]] {
+            let digest = ContentDigest::new(ChecksumType::Sha256, bytes.clone());
+            assert!(matches!(
+                store.object_path(&digest),
+                Err(DebianError::ContentStoreDigestLength(len)) if len == bytes.len()
+            ));
+            assert!(!store.contains(&digest));
+        }
+
+        let dest = td.path().join("bundle/pool/a.deb");
+        store.export(&digest, &dest)?;
+        assert_eq!(std::fs::read(&dest)?, b"package content");
+
+        Ok(())
+    }
+}
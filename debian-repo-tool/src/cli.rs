@@ -125,6 +125,22 @@ binary_packages_copy (optional) (bool)
 binary_packages_only_architectures (optional) (list[string])
    Filter of architectures of binary packages to copy.
 
+binary_packages_only_names (optional) (list[string])
+   Glob patterns of names of binary packages to copy. e.g. `libc6*`.
+   Packages files are regenerated to only list copied packages when any
+   binary package filter is defined. Requires resign_key_path.
+
+binary_packages_only_sections (optional) (list[string])
+   Filter of sections of binary packages to copy. Requires resign_key_path.
+
+binary_packages_only_priorities (optional) (list[string])
+   Filter of priorities of binary packages to copy. Requires
+   resign_key_path.
+
+binary_packages_max_size (optional) (int)
+   Maximum size in bytes of binary packages to copy. Requires
+   resign_key_path.
+
 installer_binary_packages_copy (optional) (bool)
    Whether to copy installer binary packages (udebs).
 
@@ -134,6 +150,111 @@ installer_binary_packages_only_architectures (optional) (list[string])
 sources_copy (optional) (bool)
    Whether to copy source packages.
 
+installers_copy (optional) (bool)
+   Whether to copy installer image trees (dists/*/main/installer-<arch>/).
+   Files are verified against the trees' SHA256SUMS manifests. Defaults to
+   false.
+
+installers_only_architectures (optional) (list[string])
+   Filter of architectures of installer image trees to copy.
+
+audit_log_path (optional) (string)
+   Path relative to the destination root of a JSON audit log recording
+   every path written by the copy.
+
+audit_log_signing_key_path (optional) (string)
+   Path to an ASCII armored PGP secret key to sign the audit log with.
+
+source_keyring_path (optional) (string)
+   Path to a local file containing PGP public keys, either ASCII armored,
+   a binary keyring or a GnuPG keybox. If defined, the source
+   distribution's InRelease file must be signed by one of these keys or
+   the copy is aborted before anything is written.
+
+source_key_fingerprints (optional) (list[string])
+   Fingerprints of PGP keys to fetch from keyservers and trust alongside
+   the keys in source_keyring_path.
+
+source_keyservers (optional) (list[string])
+   Keyservers to fetch source_key_fingerprints from, e.g.
+   hkps://keyserver.ubuntu.com. Defaults to hkps://keys.openpgp.org.
+
+source_key_cache_path (optional) (string)
+   Path to a local directory to cache fetched keys in. Cached keys are used
+   without contacting keyservers.
+
+phase_concurrency (optional) (map[string, map])
+   Concurrency limits for individual copy phases. Keys are phase names:
+   binary_packages, installer_binary_packages, sources, installers,
+   release_indices, and release_files. Values may define max_operations
+   (int) and max_in_flight_bytes (int). Copies within a phase are started
+   largest first.
+
+error_policy (optional) (map[string, map])
+   How failed copies are handled in individual copy phases. Keys are phase
+   names as for phase_concurrency. Values may define retries (int), the
+   number of times to retry a failed copy, retry_delay_ms (int), the delay
+   before the first retry, which doubles with each further retry, and skip
+   (bool), whether to skip copies still failing after all retries instead of
+   aborting.
+
+skipped_report_path (optional) (string)
+   Path to a local file to write a JSON report of copies skipped due to
+   error_policy to.
+
+report_path (optional) (string)
+   Path to a local file to write a JSON report of the copy to. The report
+   records paths and bytes copied, already present, and failed per phase.
+   It is also written if the copy fails.
+
+delete (optional) (bool)
+   Whether to delete destination files not referenced by the copied
+   distributions. All distributions sharing the destination's pool must be
+   listed in the same config, otherwise files referenced only by other
+   distributions are deleted.
+
+state_path (optional) (string)
+   Path to a local file recording completed copies. If defined, an
+   interrupted copy can be re-run and will skip all work recorded as
+   completed in this file.
+
+resign_key_path (optional) (string)
+   Path to a local file containing an ASCII armored PGP secret key. If
+   defined, upstream signatures are discarded and the copied release files
+   are signed with this key instead. The key must not be passphrase
+   protected.
+
+resign_additional_key_paths (optional) (list[string])
+   Paths to local files containing further ASCII armored PGP secret keys.
+   Copied release files are signed with these keys in addition to the key
+   in resign_key_path, e.g. with the old and the new key during a key
+   rotation.
+
+release_set_fields (optional) (map[string, string])
+   Fields to set in copied Release files, e.g. Origin, Label, Suite,
+   Codename, or Valid-Until. Requires resign_key_path.
+
+release_remove_fields (optional) (list[string])
+   Fields to remove from copied Release files. Requires resign_key_path.
+
+release_drop_indices (optional) (list[string])
+   Glob patterns of distribution relative paths of indices files to not
+   copy, e.g. */i18n/*. Matching files are removed from copied Release
+   files. Requires resign_key_path.
+
+sync (optional) (bool)
+   Whether to only copy content that differs from what the destination
+   already has. If the destination's release file matches the source's,
+   nothing is copied.
+
+mirror (optional) (map)
+   Declarative description of what to mirror. May define components
+   (list[string]), architectures (list[string]), installers (bool),
+   sources (bool), and max_total_size (int). max_total_size is the maximum
+   number of bytes of pool and indices files to fetch per distribution; the
+   copy is aborted before anything is written if it is exceeded. Other keys
+   in this config take precedence.
+
 # Partial Copying
 
 By default, a copy operation will copy all content in the specified
@@ -0,0 +1,771 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! RPM repository copying.
+
+[RepositoryCopier] mirrors a repository from a [RepositoryRootReader] to a
+[RepositoryWriter]. Copying is performed in phases: packages, then delta RPMs, then
+metadata. Files referenced by `repomd.xml` (including comps `group` files) are copied
+before `repomd.xml` itself, so clients of the destination never see metadata
+referencing missing files.
+
+Metadata is copied verbatim, preserving upstream signatures, unless packages are
+filtered out. In that case, `primary`, `filelists`, and `other` metadata is regenerated
+from the copied packages via [RepositoryMetadataBuilder], advisories and module
+metadata are carried over, as are comps `group` files and `prestodelta` verbatim. Other
+upstream metadata (e.g. SQLite databases) describes excluded packages and isn't copied.
+The regenerated `repomd.xml` isn't signed and a `repomd.xml.asc` in the destination is
+deleted if the [RepositoryWriter] supports it.
+*/
+
+use {
+    crate::{
+        error::{Result, RpmRepositoryError},
+        io::{read_decompressed, Compression, ContentDigest, ContentValidatingReader},
+        metadata::{
+            prestodelta::PrestoDelta,
+            primary::{Package, Primary},
+            repomd::{RepoMd, RepoMdData},
+            updateinfo::UpdateInfo,
+            writer::RepositoryMetadataBuilder,
+        },
+        RepositoryRootReader,
+    },
+    debian_packaging::{
+        error::DebianError,
+        repository::{RepositoryPathVerificationState, RepositoryWriter},
+    },
+    futures::{AsyncRead, AsyncReadExt, StreamExt},
+    std::{borrow::Cow, pin::Pin},
+};
+
+/// Path of `repomd.xml` relative to the repository root.
+const REPOMD_PATH: &str = "repodata/repomd.xml";
+
+/// Path of the detached `repomd.xml` signature relative to the repository root.
+const REPOMD_SIGNATURE_PATH: &str = "repodata/repomd.xml.asc";
+
+/// A phase of a copy operation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CopyPhase {
+    Packages,
+    DeltaPackages,
+    Metadata,
+}
+
+impl std::fmt::Display for CopyPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Packages => "packages",
+                Self::DeltaPackages => "delta packages",
+                Self::Metadata => "metadata",
+            }
+        )
+    }
+}
+
+/// Represents a copy event.
+///
+/// Instances are sent to callbacks during copying to inform of activity.
+pub enum CopyEvent {
+    /// A phase in a copy operation has begun.
+    CopyPhaseBegin(CopyPhase),
+
+    /// A phase in a copy operation has finished.
+    CopyPhaseEnd(CopyPhase),
+
+    /// A copy phase was resolved to copy N paths totaling N bytes.
+    CopyPlanned(CopyPhase, usize, u64),
+
+    /// A path was copied.
+    PathCopied(String, u64),
+
+    /// A path copy was a no-op because the destination is current.
+    PathCopyNoop(String),
+}
+
+impl std::fmt::Display for CopyEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CopyPhaseBegin(phase) => write!(f, "beginning copying of {}", phase),
+            Self::CopyPhaseEnd(phase) => write!(f, "finished copying of {}", phase),
+            Self::CopyPlanned(phase, count, size) => {
+                write!(
+                    f,
+                    "{}: copying {} paths totaling {} bytes",
+                    phase, count, size
+                )
+            }
+            Self::PathCopied(path, size) => write!(f, "copied {} bytes to {}", size, path),
+            Self::PathCopyNoop(path) => write!(f, "{} is current", path),
+        }
+    }
+}
+
+/// Criteria for selecting which packages a [RepositoryCopier] copies.
+///
+/// A package is copied only if it satisfies every configured criterion. The default
+/// instance has no criteria and matches every package.
+#[derive(Clone, Debug, Default)]
+pub struct PackageFilter {
+    names: Option<Vec<glob::Pattern>>,
+    arches: Option<Vec<String>>,
+    max_size: Option<u64>,
+}
+
+impl PackageFilter {
+    /// Only match packages whose name matches at least 1 of the given glob patterns.
+    ///
+    /// e.g. `kernel*` or `python3.??`.
+    pub fn set_only_names(
+        &mut self,
+        patterns: impl Iterator<Item = impl AsRef<str>>,
+    ) -> Result<()> {
+        self.names = Some(
+            patterns
+                .map(|p| glob::Pattern::new(p.as_ref()))
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+        );
+
+        Ok(())
+    }
+
+    /// Only match packages having one of the given architectures.
+    ///
+    /// e.g. `x86_64` or `noarch`. Source packages have the `src` architecture.
+    pub fn set_only_arches(&mut self, arches: impl Iterator<Item = impl ToString>) {
+        self.arches = Some(arches.map(|x| x.to_string()).collect());
+    }
+
+    /// Only match packages whose `.rpm` is at most this many bytes.
+    pub fn set_max_size(&mut self, size: u64) {
+        self.max_size = Some(size);
+    }
+
+    /// Whether a package satisfies all criteria.
+    pub fn matches(&self, package: &Package) -> bool {
+        if let Some(names) = &self.names {
+            if !names.iter().any(|p| p.matches(&package.name)) {
+                return false;
+            }
+        }
+
+        if let Some(arches) = &self.arches {
+            if !arches.contains(&package.arch) {
+                return false;
+            }
+        }
+
+        if let Some(max_size) = self.max_size {
+            if package.size.package > max_size {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Describes the work performed by a copy operation.
+#[derive(Clone, Debug, Default)]
+pub struct CopyReport {
+    /// Number of paths that were written.
+    pub copied_paths: usize,
+
+    /// Number of paths that were already current in the destination.
+    pub current_paths: usize,
+
+    /// Total bytes written.
+    pub copied_bytes: u64,
+}
+
+impl CopyReport {
+    fn record(&mut self, bytes_written: Option<u64>) {
+        match bytes_written {
+            Some(size) => {
+                self.copied_paths += 1;
+                self.copied_bytes += size;
+            }
+            None => {
+                self.current_paths += 1;
+            }
+        }
+    }
+}
+
+/// Entity for copying RPM repositories.
+///
+/// By default, instances copy all packages, all delta RPMs, and all metadata. Various
+/// `set_*` methods exist to control the copying behavior.
+pub struct RepositoryCopier {
+    /// Filter of packages to copy.
+    package_filter: PackageFilter,
+
+    /// Whether to copy delta RPMs.
+    deltas_copy: bool,
+}
+
+impl Default for RepositoryCopier {
+    fn default() -> Self {
+        Self {
+            package_filter: PackageFilter::default(),
+            deltas_copy: true,
+        }
+    }
+}
+
+impl RepositoryCopier {
+    /// Set the filter of packages to copy.
+    ///
+    /// Delta RPMs are only copied for packages matching the filter.
+    pub fn set_package_filter(&mut self, filter: PackageFilter) {
+        self.package_filter = filter;
+    }
+
+    /// Set whether to copy delta RPMs.
+    pub fn set_deltas_copy(&mut self, value: bool) {
+        self.deltas_copy = value;
+    }
+
+    /// Copy a repository.
+    ///
+    /// Returns a report of the work performed.
+    pub async fn copy(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        writer: &dyn RepositoryWriter,
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(CopyEvent) + Sync>>,
+    ) -> Result<CopyReport> {
+        // Capture the original bytes so signatures remain valid.
+        let mut repomd_data = vec![];
+        root_reader
+            .get_path(REPOMD_PATH.to_string())
+            .await?
+            .read_to_end(&mut repomd_data)
+            .await
+            .map_err(|e| RpmRepositoryError::IoPath(REPOMD_PATH.to_string(), e))?;
+        let repomd_signature = fetch_optional(root_reader, REPOMD_SIGNATURE_PATH).await?;
+        let repomd = RepoMd::from_reader(std::io::Cursor::new(&repomd_data))?;
+
+        let primary = repomd_entry(&repomd, "primary")
+            .ok_or(RpmRepositoryError::MetadataFileNotFound("primary"))?;
+        let primary = Primary::from_reader(std::io::Cursor::new(
+            fetch_metadata(root_reader, primary).await?,
+        ))?;
+
+        let packages = primary
+            .packages
+            .iter()
+            .filter(|p| self.package_filter.matches(p))
+            .collect::<Vec<_>>();
+        let regenerate_metadata = packages.len() != primary.packages.len();
+
+        let mut report = CopyReport::default();
+
+        let copies = packages
+            .iter()
+            .map(|p| {
+                let digest = ContentDigest::try_from(p.checksum.clone())?;
+                Ok((p.location.href.clone(), Some(p.size.package), digest))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(cb) = progress_cb {
+            cb(CopyEvent::CopyPhaseBegin(CopyPhase::Packages));
+        }
+
+        perform_copies(
+            root_reader,
+            writer,
+            CopyPhase::Packages,
+            copies,
+            max_copy_operations,
+            progress_cb,
+            &mut report,
+        )
+        .await?;
+
+        if let Some(cb) = progress_cb {
+            cb(CopyEvent::CopyPhaseEnd(CopyPhase::Packages));
+        }
+
+        if let Some(entry) = repomd_entry(&repomd, "prestodelta").filter(|_| self.deltas_copy) {
+            let presto = PrestoDelta::from_reader(std::io::Cursor::new(
+                fetch_metadata(root_reader, entry).await?,
+            ))?;
+
+            let copies = presto
+                .packages
+                .iter()
+                .filter(|target| {
+                    packages.iter().any(|p| {
+                        p.name == target.name
+                            && p.arch == target.arch
+                            && p.version == target.version()
+                    })
+                })
+                .flat_map(|target| target.deltas.iter())
+                .map(|delta| {
+                    let digest = ContentDigest::try_from(delta.checksum.clone())?;
+                    Ok((delta.filename.clone(), Some(delta.size), digest))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            if let Some(cb) = progress_cb {
+                cb(CopyEvent::CopyPhaseBegin(CopyPhase::DeltaPackages));
+            }
+
+            perform_copies(
+                root_reader,
+                writer,
+                CopyPhase::DeltaPackages,
+                copies,
+                max_copy_operations,
+                progress_cb,
+                &mut report,
+            )
+            .await?;
+
+            if let Some(cb) = progress_cb {
+                cb(CopyEvent::CopyPhaseEnd(CopyPhase::DeltaPackages));
+            }
+        }
+
+        if let Some(cb) = progress_cb {
+            cb(CopyEvent::CopyPhaseBegin(CopyPhase::Metadata));
+        }
+
+        if regenerate_metadata {
+            self.write_regenerated_metadata(
+                root_reader,
+                writer,
+                &repomd,
+                packages,
+                max_copy_operations,
+                progress_cb,
+                &mut report,
+            )
+            .await?;
+        } else {
+            let copies = repomd
+                .data
+                .iter()
+                .map(metadata_copy)
+                .collect::<Result<Vec<_>>>()?;
+
+            perform_copies(
+                root_reader,
+                writer,
+                CopyPhase::Metadata,
+                copies,
+                max_copy_operations,
+                progress_cb,
+                &mut report,
+            )
+            .await?;
+
+            if let Some(signature) = repomd_signature {
+                write_data(
+                    writer,
+                    REPOMD_SIGNATURE_PATH,
+                    signature,
+                    progress_cb,
+                    &mut report,
+                )
+                .await?;
+            }
+            write_data(writer, REPOMD_PATH, repomd_data, progress_cb, &mut report).await?;
+        }
+
+        if let Some(cb) = progress_cb {
+            cb(CopyEvent::CopyPhaseEnd(CopyPhase::Metadata));
+        }
+
+        Ok(report)
+    }
+
+    /// Write metadata describing a subset of the packages of the source repository.
+    #[allow(clippy::too_many_arguments)]
+    async fn write_regenerated_metadata(
+        &self,
+        root_reader: &dyn RepositoryRootReader,
+        writer: &dyn RepositoryWriter,
+        repomd: &RepoMd,
+        packages: Vec<&Package>,
+        max_copy_operations: usize,
+        progress_cb: &Option<Box<dyn Fn(CopyEvent) + Sync>>,
+        report: &mut CopyReport,
+    ) -> Result<()> {
+        let mut builder = RepositoryMetadataBuilder::default();
+        builder.set_revision(&repomd.revision);
+
+        for package in packages {
+            builder.add_package(package.clone());
+        }
+
+        if let Some(entry) = repomd_entry(repomd, "updateinfo") {
+            let update_info = UpdateInfo::from_reader(std::io::Cursor::new(
+                fetch_metadata(root_reader, entry).await?,
+            ))?;
+
+            for advisory in update_info.updates {
+                builder.add_advisory(advisory);
+            }
+        }
+
+        if let Some(entry) = repomd_entry(repomd, "modules") {
+            let data = fetch_metadata(root_reader, entry).await?;
+            builder.set_modules_yaml(String::from_utf8_lossy(&data));
+        }
+
+        let mut metadata = builder.build().await?;
+
+        let carried = repomd
+            .data
+            .iter()
+            .filter(|entry| {
+                entry.data_type.starts_with("group") || entry.data_type == "prestodelta"
+            })
+            .collect::<Vec<_>>();
+
+        perform_copies(
+            root_reader,
+            writer,
+            CopyPhase::Metadata,
+            carried
+                .iter()
+                .map(|entry| metadata_copy(entry))
+                .collect::<Result<Vec<_>>>()?,
+            max_copy_operations,
+            progress_cb,
+            report,
+        )
+        .await?;
+
+        metadata.repomd.data.extend(carried.into_iter().cloned());
+
+        for file in metadata.files {
+            write_data(writer, &file.path, file.data, progress_cb, report).await?;
+        }
+
+        match writer.delete_path(REPOMD_SIGNATURE_PATH).await {
+            Ok(()) | Err(DebianError::RepositoryWriterOperationUnsupported(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        write_data(
+            writer,
+            REPOMD_PATH,
+            metadata.repomd.to_xml().into_bytes(),
+            progress_cb,
+            report,
+        )
+        .await
+    }
+}
+
+/// Find the entry of a data type in `repomd.xml`.
+fn repomd_entry<'a>(repomd: &'a RepoMd, data_type: &str) -> Option<&'a RepoMdData> {
+    repomd
+        .data
+        .iter()
+        .find(|entry| entry.data_type == data_type)
+}
+
+/// Describe the copy of a metadata file referenced by `repomd.xml`.
+fn metadata_copy(entry: &RepoMdData) -> Result<(String, Option<u64>, ContentDigest)> {
+    Ok((
+        entry.location.href.clone(),
+        entry.size,
+        ContentDigest::try_from(entry.checksum.clone())?,
+    ))
+}
+
+/// Obtain a reader of a path verifying its digest and, if known, its size.
+async fn get_path_verified(
+    root_reader: &dyn RepositoryRootReader,
+    path: String,
+    size: Option<u64>,
+    digest: ContentDigest,
+) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+    if let Some(size) = size {
+        root_reader
+            .get_path_with_digest_verification(path, size, digest)
+            .await
+    } else {
+        Ok(Box::pin(ContentValidatingReader::new_digest_only(
+            root_reader.get_path(path).await?,
+            digest,
+        )))
+    }
+}
+
+/// Fetch the decompressed content of a metadata file referenced by `repomd.xml`.
+async fn fetch_metadata(
+    root_reader: &dyn RepositoryRootReader,
+    entry: &RepoMdData,
+) -> Result<Vec<u8>> {
+    let (path, size, digest) = metadata_copy(entry)?;
+
+    let compression = match path.as_str() {
+        _ if path.ends_with(".gz") => Compression::Gzip,
+        _ if path.ends_with(".xz") => Compression::Xz,
+        _ if path.ends_with(".zst") => Compression::Zstd,
+        _ => Compression::None,
+    };
+
+    let reader = get_path_verified(root_reader, path.clone(), size, digest).await?;
+    let mut reader = read_decompressed(Box::pin(futures::io::BufReader::new(reader)), compression);
+
+    let mut data = vec![];
+    reader
+        .read_to_end(&mut data)
+        .await
+        .map_err(|e| RpmRepositoryError::IoPath(path, e))?;
+
+    Ok(data)
+}
+
+/// Fetch the content of a path that may not exist.
+async fn fetch_optional(
+    root_reader: &dyn RepositoryRootReader,
+    path: &str,
+) -> Result<Option<Vec<u8>>> {
+    let mut reader = match root_reader.get_path(path.to_string()).await {
+        Ok(reader) => reader,
+        Err(RpmRepositoryError::IoPath(_, e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut data = vec![];
+    reader
+        .read_to_end(&mut data)
+        .await
+        .map_err(|e| RpmRepositoryError::IoPath(path.to_string(), e))?;
+
+    Ok(Some(data))
+}
+
+/// Write in-memory data to a path.
+async fn write_data(
+    writer: &dyn RepositoryWriter,
+    path: &str,
+    data: Vec<u8>,
+    progress_cb: &Option<Box<dyn Fn(CopyEvent) + Sync>>,
+    report: &mut CopyReport,
+) -> Result<()> {
+    let write = writer
+        .write_path(
+            Cow::Owned(path.to_string()),
+            Box::pin(futures::io::Cursor::new(data)),
+        )
+        .await?;
+
+    if let Some(cb) = progress_cb {
+        cb(CopyEvent::PathCopied(path.to_string(), write.bytes_written));
+    }
+    report.record(Some(write.bytes_written));
+
+    Ok(())
+}
+
+/// Copy a path, unless the destination already has the expected content.
+///
+/// Returns the number of bytes written or [None] if the destination was current.
+async fn copy_path(
+    root_reader: &dyn RepositoryRootReader,
+    writer: &dyn RepositoryWriter,
+    path: String,
+    expected_size: Option<u64>,
+    expected_digest: ContentDigest,
+    progress_cb: &Option<Box<dyn Fn(CopyEvent) + Sync>>,
+) -> Result<Option<u64>> {
+    let verification = writer
+        .verify_path(
+            &path,
+            expected_size.map(|size| (size, (&expected_digest).into())),
+        )
+        .await?;
+
+    if matches!(
+        verification.state,
+        RepositoryPathVerificationState::ExistsIntegrityVerified
+    ) {
+        if let Some(cb) = progress_cb {
+            cb(CopyEvent::PathCopyNoop(path));
+        }
+
+        return Ok(None);
+    }
+
+    let reader =
+        get_path_verified(root_reader, path.clone(), expected_size, expected_digest).await?;
+
+    let write = writer.write_path(Cow::Owned(path.clone()), reader).await?;
+
+    if let Some(cb) = progress_cb {
+        cb(CopyEvent::PathCopied(path, write.bytes_written));
+    }
+
+    Ok(Some(write.bytes_written))
+}
+
+/// Perform copies concurrently.
+async fn perform_copies(
+    root_reader: &dyn RepositoryRootReader,
+    writer: &dyn RepositoryWriter,
+    phase: CopyPhase,
+    copies: Vec<(String, Option<u64>, ContentDigest)>,
+    max_copy_operations: usize,
+    progress_cb: &Option<Box<dyn Fn(CopyEvent) + Sync>>,
+    report: &mut CopyReport,
+) -> Result<()> {
+    if let Some(cb) = progress_cb {
+        cb(CopyEvent::CopyPlanned(
+            phase,
+            copies.len(),
+            copies.iter().filter_map(|(_, size, _)| *size).sum(),
+        ));
+    }
+
+    let mut fs = futures::stream::iter(copies.into_iter().map(|(path, size, digest)| {
+        copy_path(root_reader, writer, path, size, digest, progress_cb)
+    }))
+    .buffer_unordered(max_copy_operations);
+
+    while let Some(res) = fs.next().await {
+        report.record(res?);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            createrepo::create_repository,
+            filesystem::FilesystemRepositoryReader,
+            header::{tag, test_rpm, test_strings},
+        },
+        debian_packaging::repository::filesystem::FilesystemRepositoryWriter,
+        std::sync::{Arc, Mutex},
+    };
+
+    #[tokio::test]
+    async fn copy_filesystem() -> Result<()> {
+        let source = tempfile::tempdir()?;
+        let source = source.path();
+
+        for name in ["attr", "zstd"] {
+            let data = test_rpm(&[
+                (tag::NAME, 6, test_strings(&[name]), 1),
+                (tag::VERSION, 6, test_strings(&["1.0"]), 1),
+                (tag::RELEASE, 6, test_strings(&["1"]), 1),
+                (tag::ARCH, 6, test_strings(&["x86_64"]), 1),
+                (tag::SOURCERPM, 6, test_strings(&["src.rpm"]), 1),
+            ]);
+
+            std::fs::create_dir_all(source.join("Packages"))?;
+            std::fs::write(source.join(format!("Packages/{name}.rpm")), data)?;
+        }
+
+        let metadata = create_repository(
+            source,
+            RepositoryMetadataBuilder::default(),
+            &FilesystemRepositoryWriter::new(source),
+        )
+        .await?;
+
+        let reader = FilesystemRepositoryReader::new(source);
+        let dest = tempfile::tempdir()?;
+        let dest = dest.path();
+        let writer = FilesystemRepositoryWriter::new(dest);
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let events_cb = events.clone();
+        let cb: Option<Box<dyn Fn(CopyEvent) + Sync>> = Some(Box::new(move |event| {
+            if let CopyEvent::CopyPhaseBegin(phase) = event {
+                events_cb.lock().unwrap().push(phase);
+            }
+        }));
+
+        let copier = RepositoryCopier::default();
+        let report = copier.copy(&reader, &writer, 2, &cb).await?;
+        // 2 packages, metadata files, and repomd.xml.
+        assert_eq!(report.copied_paths, 2 + metadata.files.len() + 1);
+        assert_eq!(report.current_paths, 0);
+        assert_eq!(
+            std::fs::read(dest.join(REPOMD_PATH))?,
+            std::fs::read(source.join(REPOMD_PATH))?
+        );
+        assert!(dest.join("Packages/zstd.rpm").exists());
+        // The source doesn't publish delta RPMs.
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![CopyPhase::Packages, CopyPhase::Metadata]
+        );
+
+        // Copying again only rewrites repomd.xml.
+        let report = copier.copy(&reader, &writer, 2, &None).await?;
+        assert_eq!(report.copied_paths, 1);
+        assert_eq!(report.current_paths, 2 + metadata.files.len());
+
+        let dest = tempfile::tempdir()?;
+        let dest = dest.path();
+
+        let mut filter = PackageFilter::default();
+        filter.set_only_names(["z*"].iter())?;
+        let mut copier = RepositoryCopier::default();
+        copier.set_package_filter(filter);
+        copier
+            .copy(&reader, &FilesystemRepositoryWriter::new(dest), 2, &None)
+            .await?;
+        assert!(dest.join("Packages/zstd.rpm").exists());
+        assert!(!dest.join("Packages/attr.rpm").exists());
+
+        // Metadata of the filtered destination only describes copied packages.
+        let dest_metadata = FilesystemRepositoryReader::new(dest)
+            .metadata_reader()
+            .await?;
+        let primary = dest_metadata.primary_packages().await?;
+        assert_eq!(primary.packages.len(), 1);
+        assert_eq!(primary.packages[0].name, "zstd");
+        assert_eq!(dest_metadata.repomd().revision, metadata.repomd.revision);
+        assert!(!dest.join(REPOMD_SIGNATURE_PATH).exists());
+
+        // Metadata files lacking a size in repomd.xml still have their digest verified.
+        let mut repomd = metadata.repomd.clone();
+        for entry in repomd.data.iter_mut() {
+            entry.size = None;
+        }
+        std::fs::write(source.join(REPOMD_PATH), repomd.to_xml())?;
+        let other = repomd
+            .data
+            .iter()
+            .find(|entry| entry.data_type == "other")
+            .unwrap();
+        std::fs::write(source.join(&other.location.href), b"corrupt")?;
+
+        let dest = tempfile::tempdir()?;
+        let err = RepositoryCopier::default()
+            .copy(
+                &reader,
+                &FilesystemRepositoryWriter::new(dest.path()),
+                2,
+                &None,
+            )
+            .await
+            .unwrap_err();
+        assert!(format!("{}", err).contains("digest mismatch"));
+
+        Ok(())
+    }
+}
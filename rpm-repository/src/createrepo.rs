@@ -0,0 +1,213 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Create repositories from a directory of `.rpm` files.
+
+This is the functionality of `createrepo_c`: [create_repository()] scans a directory
+tree for `.rpm` files, parses their headers, computes their digests, and writes the
+`repodata` describing them via a [RepositoryWriter].
+
+Package locations are relative to the scanned directory, so the scanned directory
+becomes the repository root. The `repodata` directory is not scanned.
+*/
+
+use {
+    crate::{
+        error::{Result, RpmRepositoryError},
+        header::RpmHeader,
+        io::ContentDigest,
+        metadata::writer::{RepositoryMetadata, RepositoryMetadataBuilder},
+    },
+    debian_packaging::repository::RepositoryWriter,
+    sha2::Digest,
+    std::path::{Path, PathBuf},
+};
+
+/// Find `.rpm` files under a directory.
+///
+/// Returns paths relative to `root`, sorted. The top-level `repodata` directory is
+/// skipped.
+pub fn find_rpm_files(root: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    fn walk(root: &Path, relative: &Path, res: &mut Vec<PathBuf>) -> Result<()> {
+        let dir = root.join(relative);
+
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| RpmRepositoryError::IoPath(format!("{}", dir.display()), e))?;
+
+        for entry in entries {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+
+            if entry.file_type()?.is_dir() {
+                if path != Path::new("repodata") {
+                    walk(root, &path, res)?;
+                }
+            } else if path.extension().map(|ext| ext == "rpm").unwrap_or(false) {
+                res.push(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut res = vec![];
+    walk(root.as_ref(), Path::new(""), &mut res)?;
+    res.sort();
+
+    Ok(res)
+}
+
+impl RepositoryMetadataBuilder {
+    /// Register an `.rpm` file with this repository.
+    ///
+    /// `path` is relative to the repository root `root`. The package's header is parsed
+    /// and the file's SHA-256 digest is computed.
+    pub fn add_rpm_file(&mut self, root: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let full_path = root.as_ref().join(path);
+        let io_error = |e| RpmRepositoryError::IoPath(format!("{}", full_path.display()), e);
+
+        let mut f = std::fs::File::open(&full_path).map_err(io_error)?;
+        let metadata = f.metadata().map_err(io_error)?;
+
+        let mut hasher = sha2::Sha256::new();
+        std::io::copy(&mut f, &mut hasher).map_err(io_error)?;
+        let digest = ContentDigest::Sha256(hasher.finalize().to_vec());
+
+        let f = std::fs::File::open(&full_path).map_err(io_error)?;
+        let header = RpmHeader::from_reader(std::io::BufReader::new(f))?;
+
+        let file_time = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let location = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        self.add_package(header.to_package(location, &digest, metadata.len(), file_time)?);
+
+        Ok(())
+    }
+
+    /// Register all `.rpm` files under a repository root directory.
+    ///
+    /// Returns the number of registered files. See [find_rpm_files()].
+    pub fn add_rpm_directory(&mut self, root: impl AsRef<Path>) -> Result<usize> {
+        let root = root.as_ref();
+        let paths = find_rpm_files(root)?;
+
+        for path in &paths {
+            self.add_rpm_file(root, path)?;
+        }
+
+        Ok(paths.len())
+    }
+}
+
+/// Create a repository from the `.rpm` files in a directory.
+///
+/// Packages under `root` are registered with `builder` and the resulting metadata is
+/// written to `writer`, which is typically bound to `root`. Use `builder` to register
+/// signing keys, advisories, and other metadata.
+pub async fn create_repository(
+    root: impl AsRef<Path>,
+    mut builder: RepositoryMetadataBuilder,
+    writer: &dyn RepositoryWriter,
+) -> Result<RepositoryMetadata> {
+    builder.add_rpm_directory(root)?;
+
+    let metadata = builder.build().await?;
+    metadata.write_to(writer).await?;
+
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            header::{tag, test_rpm, test_strings},
+            metadata::{primary::Primary, repomd::RepoMd},
+        },
+        debian_packaging::repository::filesystem::FilesystemRepositoryWriter,
+        futures::AsyncReadExt,
+    };
+
+    #[tokio::test]
+    async fn create_from_directory() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let td = td.path();
+
+        for (dir, name) in [("Packages/a", "attr"), ("Packages/z", "zstd")] {
+            let data = test_rpm(&[
+                (tag::NAME, 6, test_strings(&[name]), 1),
+                (tag::VERSION, 6, test_strings(&["1.0"]), 1),
+                (tag::RELEASE, 6, test_strings(&["1"]), 1),
+                (tag::ARCH, 6, test_strings(&["x86_64"]), 1),
+                (tag::SOURCERPM, 6, test_strings(&["src.rpm"]), 1),
+            ]);
+
+            std::fs::create_dir_all(td.join(dir))?;
+            std::fs::write(td.join(dir).join(format!("{name}.rpm")), data)?;
+        }
+        std::fs::write(td.join("Packages/README"), b"not a package")?;
+
+        let metadata = create_repository(
+            td,
+            RepositoryMetadataBuilder::default(),
+            &FilesystemRepositoryWriter::new(td),
+        )
+        .await?;
+
+        // A second scan doesn't pick up generated metadata.
+        assert_eq!(find_rpm_files(td)?.len(), 2);
+
+        let repomd = RepoMd::from_xml(&std::fs::read_to_string(td.join("repodata/repomd.xml"))?)?;
+        assert_eq!(repomd.data.len(), metadata.repomd.data.len());
+
+        let primary = repomd
+            .data
+            .iter()
+            .find(|entry| entry.data_type == "primary")
+            .unwrap();
+        let mut xml = String::new();
+        crate::io::read_decompressed(
+            futures::io::BufReader::new(futures::io::Cursor::new(std::fs::read(
+                td.join(&primary.location.href),
+            )?)),
+            crate::io::Compression::Gzip,
+        )
+        .read_to_string(&mut xml)
+        .await?;
+
+        let primary = Primary::from_xml(&xml)?;
+        assert_eq!(
+            primary
+                .packages
+                .iter()
+                .map(|p| (p.name.as_str(), p.location.href.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("attr", "Packages/a/attr.rpm"),
+                ("zstd", "Packages/z/zstd.rpm")
+            ]
+        );
+
+        let data = std::fs::read(td.join("Packages/z/zstd.rpm"))?;
+        assert_eq!(
+            primary.packages[1].checksum.value,
+            hex::encode(sha2::Sha256::digest(&data))
+        );
+        assert_eq!(primary.packages[1].size.package, data.len() as u64);
+
+        Ok(())
+    }
+}
@@ -16,6 +16,15 @@ pub enum RpmRepositoryError {
     #[error("XML error: {0:?}")]
     Xml(#[from] serde_xml_rs::Error),
 
+    #[error("YAML error: {0:?}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("glob pattern error: {0:?}")]
+    GlobPattern(#[from] glob::PatternError),
+
+    #[error("I/O error: {0:?}")]
+    Io(#[from] std::io::Error),
+
     #[error("repository I/O error on path {0}: {1:?}")]
     IoPath(String, std::io::Error),
 
@@ -31,8 +40,26 @@ pub enum RpmRepositoryError {
     #[error("unexpected data path: {0}")]
     UnexpectedDataPath(String),
 
+    #[error(".repo file error: {0}")]
+    RepoFile(String),
+
+    #[error("mirror error: {0}")]
+    Mirror(String),
+
+    #[error("RPM header error: {0}")]
+    RpmHeader(String),
+
+    #[error("RPM payload error: {0}")]
+    RpmPayload(String),
+
     #[error("content size missing from metadata entry")]
     MetadataMissingSize,
+
+    #[error("PGP error: {0:?}")]
+    Pgp(#[from] pgp::errors::Error),
+
+    #[error("repository error: {0:?}")]
+    Signing(#[from] debian_packaging::error::DebianError),
 }
 
 /// Result type for this crate.
@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Filesystem based RPM repositories. */
+
+use {
+    crate::{
+        error::{Result, RpmRepositoryError},
+        metadata::repomd::RepoMd,
+        DataResolver, MetadataReader, RepositoryRootReader,
+    },
+    futures::AsyncRead,
+    std::{
+        future::Future,
+        path::{Path, PathBuf},
+        pin::Pin,
+    },
+    url::Url,
+};
+
+fn open_path(path: &Path) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+    let f = std::fs::File::open(path)
+        .map_err(|e| RpmRepositoryError::IoPath(format!("{}", path.display()), e))?;
+
+    Ok(Box::pin(futures::io::BufReader::new(
+        futures::io::AllowStdIo::new(f),
+    )))
+}
+
+/// A readable interface to an RPM repository backed by a filesystem.
+#[derive(Clone, Debug)]
+pub struct FilesystemRepositoryReader {
+    root_dir: PathBuf,
+}
+
+impl FilesystemRepositoryReader {
+    /// Construct a new instance, bound to the root directory specified.
+    ///
+    /// No validation of the passed path is performed.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            root_dir: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl DataResolver for FilesystemRepositoryReader {
+    #[allow(clippy::type_complexity)]
+    fn get_path(
+        &self,
+        path: String,
+    ) -> Pin<Box<dyn Future<Output = Result<Pin<Box<dyn AsyncRead + Send>>>> + Send + '_>> {
+        let res = open_path(&self.root_dir.join(path));
+
+        Box::pin(async move { res })
+    }
+}
+
+impl RepositoryRootReader for FilesystemRepositoryReader {
+    fn url(&self) -> Result<Url> {
+        Url::from_directory_path(&self.root_dir).map_err(|_| {
+            RpmRepositoryError::UnexpectedDataPath(format!("{}", self.root_dir.display()))
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn metadata_reader(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<Box<dyn MetadataReader>>> + Send + '_>> {
+        async fn run(slf: &FilesystemRepositoryReader) -> Result<Box<dyn MetadataReader>> {
+            let relative_path = "repodata".to_string();
+            let repomd = slf
+                .fetch_repomd(format!("{}/repomd.xml", relative_path))
+                .await?;
+
+            Ok(Box::new(FilesystemMetadataReader {
+                root_dir: slf.root_dir.clone(),
+                relative_path,
+                repomd,
+            }))
+        }
+
+        Box::pin(run(self))
+    }
+}
+
+/// Filesystem repository reader bound to a parsed `repomd.xml` file.
+///
+/// Paths are relative to the repository root, like the `href` of metadata entries.
+pub struct FilesystemMetadataReader {
+    root_dir: PathBuf,
+    relative_path: String,
+    repomd: RepoMd,
+}
+
+impl DataResolver for FilesystemMetadataReader {
+    #[allow(clippy::type_complexity)]
+    fn get_path(
+        &self,
+        path: String,
+    ) -> Pin<Box<dyn Future<Output = Result<Pin<Box<dyn AsyncRead + Send>>>> + Send + '_>> {
+        let res = open_path(&self.root_dir.join(path));
+
+        Box::pin(async move { res })
+    }
+}
+
+impl MetadataReader for FilesystemMetadataReader {
+    fn url(&self) -> Result<Url> {
+        Url::from_directory_path(self.root_dir.join(&self.relative_path)).map_err(|_| {
+            RpmRepositoryError::UnexpectedDataPath(format!("{}", self.root_dir.display()))
+        })
+    }
+
+    fn root_relative_path(&self) -> &str {
+        &self.relative_path
+    }
+
+    fn repomd(&self) -> &RepoMd {
+        &self.repomd
+    }
+}
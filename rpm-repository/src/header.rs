@@ -0,0 +1,818 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! RPM package header parsing.
+
+An `.rpm` file consists of a 96 byte lead, a signature header, a main header, and the
+compressed payload. Headers are a set of tagged values describing the package. This
+module parses both headers, which is sufficient to derive repository metadata. The
+payload isn't read.
+
+[RpmHeader::to_package()] converts a header to a [Package] as advertised in
+`primary.xml`.
+*/
+
+use {
+    crate::{
+        error::{Result, RpmRepositoryError},
+        io::ContentDigest,
+        metadata::{
+            primary::{
+                Checksum, Entries, FileEntry, HeaderRange, Package, PackageEntry, PackageFormat,
+                PackageSize, PackageTime, PackageVersion,
+            },
+            repomd::Location,
+        },
+    },
+    std::{collections::BTreeMap, io::Read},
+};
+
+const LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+const HEADER_MAGIC: [u8; 4] = [0x8e, 0xad, 0xe8, 0x01];
+const LEAD_SIZE: u64 = 96;
+
+/// Maximum number of index entries in a header, as enforced by rpm.
+const HEADER_MAX_ENTRIES: usize = 0xffff;
+/// Maximum size in bytes of the data of a header, as enforced by rpm.
+const HEADER_MAX_DATA_SIZE: usize = 256 * 1024 * 1024;
+
+/// Read exactly `size` bytes.
+///
+/// Memory grows as data is read, so truncated input doesn't allocate `size` bytes.
+fn read_vec(reader: &mut impl Read, size: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    reader.take(size as u64).read_to_end(&mut buf)?;
+
+    if buf.len() != size {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(buf)
+}
+
+/// Tags of the main header.
+pub mod tag {
+    pub const NAME: u32 = 1000;
+    pub const VERSION: u32 = 1001;
+    pub const RELEASE: u32 = 1002;
+    pub const EPOCH: u32 = 1003;
+    pub const SUMMARY: u32 = 1004;
+    pub const DESCRIPTION: u32 = 1005;
+    pub const BUILDTIME: u32 = 1006;
+    pub const BUILDHOST: u32 = 1007;
+    pub const SIZE: u32 = 1009;
+    pub const VENDOR: u32 = 1011;
+    pub const LICENSE: u32 = 1014;
+    pub const PACKAGER: u32 = 1015;
+    pub const GROUP: u32 = 1016;
+    pub const URL: u32 = 1020;
+    pub const ARCH: u32 = 1022;
+    pub const FILEMODES: u32 = 1030;
+    pub const FILEFLAGS: u32 = 1037;
+    pub const SOURCERPM: u32 = 1044;
+    pub const ARCHIVESIZE: u32 = 1046;
+    pub const PROVIDENAME: u32 = 1047;
+    pub const REQUIREFLAGS: u32 = 1048;
+    pub const REQUIRENAME: u32 = 1049;
+    pub const REQUIREVERSION: u32 = 1050;
+    pub const CONFLICTFLAGS: u32 = 1053;
+    pub const CONFLICTNAME: u32 = 1054;
+    pub const CONFLICTVERSION: u32 = 1055;
+    pub const OBSOLETENAME: u32 = 1090;
+    pub const PROVIDEFLAGS: u32 = 1112;
+    pub const PROVIDEVERSION: u32 = 1113;
+    pub const OBSOLETEFLAGS: u32 = 1114;
+    pub const OBSOLETEVERSION: u32 = 1115;
+    pub const DIRINDEXES: u32 = 1116;
+    pub const BASENAMES: u32 = 1117;
+    pub const DIRNAMES: u32 = 1118;
+    pub const PAYLOADFORMAT: u32 = 1124;
+    pub const PAYLOADCOMPRESSOR: u32 = 1125;
+    pub const RECOMMENDNAME: u32 = 5046;
+    pub const RECOMMENDVERSION: u32 = 5047;
+    pub const RECOMMENDFLAGS: u32 = 5048;
+    pub const SUGGESTNAME: u32 = 5049;
+    pub const SUGGESTVERSION: u32 = 5050;
+    pub const SUGGESTFLAGS: u32 = 5051;
+    pub const SUPPLEMENTNAME: u32 = 5052;
+    pub const SUPPLEMENTVERSION: u32 = 5053;
+    pub const SUPPLEMENTFLAGS: u32 = 5054;
+}
+
+/// Tag of the signature header holding the uncompressed payload size.
+const SIGNATURE_PAYLOADSIZE: u32 = 1007;
+
+const RPMSENSE_LESS: u64 = 1 << 1;
+const RPMSENSE_GREATER: u64 = 1 << 2;
+const RPMSENSE_EQUAL: u64 = 1 << 3;
+const RPMSENSE_PREREQ: u64 = 1 << 6;
+const RPMSENSE_SCRIPT_PRE: u64 = 1 << 9;
+const RPMSENSE_SCRIPT_POST: u64 = 1 << 10;
+
+const RPMFILE_GHOST: u64 = 1 << 6;
+
+/// The lead of an `.rpm` file.
+///
+/// The lead is a legacy structure. Modern tools rely on headers instead, but it
+/// identifies the file as an RPM.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Lead {
+    /// Major version of the file format.
+    pub major: u8,
+    /// Minor version of the file format.
+    pub minor: u8,
+    /// Package type. 0 for binary and 1 for source packages.
+    pub package_type: u16,
+    /// Architecture number.
+    pub arch: u16,
+    /// Package name, typically `name-version-release`.
+    pub name: String,
+    /// Operating system number.
+    pub os: u16,
+    /// Type of the signature header. 5 for modern packages.
+    pub signature_type: u16,
+}
+
+impl Lead {
+    fn parse(data: &[u8; LEAD_SIZE as usize]) -> Result<Self> {
+        if data[0..4] != LEAD_MAGIC {
+            return Err(RpmRepositoryError::RpmHeader("not an RPM file".into()));
+        }
+
+        let u16_at = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        let name = &data[10..76];
+        let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+
+        Ok(Self {
+            major: data[4],
+            minor: data[5],
+            package_type: u16_at(6),
+            arch: u16_at(8),
+            name: String::from_utf8_lossy(name).to_string(),
+            os: u16_at(76),
+            signature_type: u16_at(78),
+        })
+    }
+}
+
+/// A value in an RPM header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TagValue {
+    /// Integers of any width.
+    Integer(Vec<u64>),
+    /// A string, string array, or internationalized string.
+    ///
+    /// Only the first (untranslated) value of internationalized strings is retained.
+    String(Vec<String>),
+    /// Binary data.
+    Binary(Vec<u8>),
+}
+
+/// A parsed header structure.
+#[derive(Clone, Debug, Default)]
+pub struct Header {
+    tags: BTreeMap<u32, TagValue>,
+}
+
+impl Header {
+    /// Parse a header structure, returning it and its size in bytes.
+    ///
+    /// Headers having more than [HEADER_MAX_ENTRIES] entries or [HEADER_MAX_DATA_SIZE]
+    /// bytes of data are rejected.
+    fn read(reader: &mut impl Read) -> Result<(Self, u64)> {
+        let mut intro = [0u8; 16];
+        reader.read_exact(&mut intro)?;
+
+        if intro[0..4] != HEADER_MAGIC {
+            return Err(RpmRepositoryError::RpmHeader("bad header magic".into()));
+        }
+
+        let count = u32::from_be_bytes(intro[8..12].try_into().unwrap()) as usize;
+        let data_size = u32::from_be_bytes(intro[12..16].try_into().unwrap()) as usize;
+
+        if count > HEADER_MAX_ENTRIES {
+            return Err(RpmRepositoryError::RpmHeader(format!(
+                "header has {count} entries; at most {HEADER_MAX_ENTRIES} are supported"
+            )));
+        }
+        if data_size > HEADER_MAX_DATA_SIZE {
+            return Err(RpmRepositoryError::RpmHeader(format!(
+                "header has {data_size} bytes of data; at most {HEADER_MAX_DATA_SIZE} are supported"
+            )));
+        }
+
+        let index = read_vec(reader, count * 16)?;
+        let data = read_vec(reader, data_size)?;
+
+        let mut tags = BTreeMap::new();
+
+        for entry in index.chunks_exact(16) {
+            let field = |i: usize| u32::from_be_bytes(entry[i..i + 4].try_into().unwrap());
+            let (tag, tag_type, offset, count) =
+                (field(0), field(4), field(8) as usize, field(12) as usize);
+
+            if let Some(value) = parse_value(&data, tag_type, offset, count)
+                .ok_or_else(|| RpmRepositoryError::RpmHeader(format!("bad entry for tag {tag}")))?
+            {
+                tags.insert(tag, value);
+            }
+        }
+
+        Ok((Self { tags }, (16 + index.len() + data.len()) as u64))
+    }
+
+    /// Obtain the raw value of a tag.
+    pub fn get(&self, tag: u32) -> Option<&TagValue> {
+        self.tags.get(&tag)
+    }
+
+    /// Obtain the first string value of a tag.
+    pub fn string(&self, tag: u32) -> Option<&str> {
+        match self.tags.get(&tag) {
+            Some(TagValue::String(values)) => values.first().map(|s| s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Obtain the string values of a tag.
+    pub fn strings(&self, tag: u32) -> &[String] {
+        match self.tags.get(&tag) {
+            Some(TagValue::String(values)) => values,
+            _ => &[],
+        }
+    }
+
+    /// Obtain the first integer value of a tag.
+    pub fn integer(&self, tag: u32) -> Option<u64> {
+        self.integers(tag).first().copied()
+    }
+
+    /// Obtain the integer values of a tag.
+    pub fn integers(&self, tag: u32) -> &[u64] {
+        match self.tags.get(&tag) {
+            Some(TagValue::Integer(values)) => values,
+            _ => &[],
+        }
+    }
+}
+
+/// Parse the value of a header index entry.
+///
+/// Returns `None` if the entry is malformed and `Some(None)` for unsupported types.
+fn parse_value(
+    data: &[u8],
+    tag_type: u32,
+    offset: usize,
+    count: usize,
+) -> Option<Option<TagValue>> {
+    let integers = |width: usize| -> Option<TagValue> {
+        let bytes = data.get(offset..offset.checked_add(width.checked_mul(count)?)?)?;
+
+        Some(TagValue::Integer(
+            bytes
+                .chunks_exact(width)
+                .map(|chunk| chunk.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b)))
+                .collect(),
+        ))
+    };
+
+    Some(match tag_type {
+        0 => None,
+        1 | 2 => Some(integers(1)?),
+        3 => Some(integers(2)?),
+        4 => Some(integers(4)?),
+        5 => Some(integers(8)?),
+        6 | 8 | 9 => {
+            let count = if tag_type == 6 { 1 } else { count };
+            let mut remaining = data.get(offset..)?;
+
+            // Each string has at least a terminating NUL.
+            if count > remaining.len() {
+                return None;
+            }
+            let mut values = Vec::with_capacity(count);
+
+            for _ in 0..count {
+                let end = remaining.iter().position(|b| *b == 0)?;
+                values.push(String::from_utf8_lossy(&remaining[..end]).to_string());
+                remaining = &remaining[end + 1..];
+            }
+
+            if tag_type == 9 {
+                values.truncate(1);
+            }
+
+            Some(TagValue::String(values))
+        }
+        7 => Some(TagValue::Binary(
+            data.get(offset..offset.checked_add(count)?)?.to_vec(),
+        )),
+        _ => None,
+    })
+}
+
+/// The headers of an `.rpm` file.
+#[derive(Clone, Debug)]
+pub struct RpmHeader {
+    /// The lead.
+    pub lead: Lead,
+
+    /// The signature header.
+    pub signature: Header,
+
+    /// The main header.
+    pub header: Header,
+
+    /// Byte offsets of the main header within the file.
+    pub header_range: HeaderRange,
+}
+
+impl RpmHeader {
+    /// Parse the headers of an `.rpm` file.
+    ///
+    /// The reader is positioned at the beginning of the payload upon return.
+    pub fn from_reader(mut reader: impl Read) -> Result<Self> {
+        let mut lead = [0u8; LEAD_SIZE as usize];
+        reader.read_exact(&mut lead)?;
+        let lead = Lead::parse(&lead)?;
+
+        let (signature, signature_size) = Header::read(&mut reader)?;
+
+        // The main header is aligned to 8 bytes.
+        let padding = (8 - signature_size % 8) % 8;
+        std::io::copy(&mut (&mut reader).take(padding), &mut std::io::sink())?;
+
+        let start = LEAD_SIZE + signature_size + padding;
+        let (header, header_size) = Header::read(&mut reader)?;
+
+        Ok(Self {
+            lead,
+            signature,
+            header,
+            header_range: HeaderRange {
+                start,
+                end: start + header_size,
+            },
+        })
+    }
+
+    /// The package name.
+    pub fn name(&self) -> Option<&str> {
+        self.header.string(tag::NAME)
+    }
+
+    /// The package version.
+    pub fn version(&self) -> PackageVersion {
+        PackageVersion {
+            epoch: self.header.integer(tag::EPOCH).unwrap_or(0),
+            version: self
+                .header
+                .string(tag::VERSION)
+                .unwrap_or_default()
+                .to_string(),
+            release: self
+                .header
+                .string(tag::RELEASE)
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+
+    /// Whether this is a source package.
+    pub fn is_source(&self) -> bool {
+        self.header.string(tag::SOURCERPM).is_none()
+    }
+
+    /// The package architecture, as advertised in repository metadata.
+    ///
+    /// Source packages have the `src` architecture.
+    pub fn arch(&self) -> &str {
+        if self.is_source() {
+            "src"
+        } else {
+            self.header.string(tag::ARCH).unwrap_or("noarch")
+        }
+    }
+
+    /// Obtain files installed by this package.
+    pub fn files(&self) -> Vec<FileEntry> {
+        let dirnames = self.header.strings(tag::DIRNAMES);
+        let dirindexes = self.header.integers(tag::DIRINDEXES);
+        let modes = self.header.integers(tag::FILEMODES);
+        let flags = self.header.integers(tag::FILEFLAGS);
+
+        self.header
+            .strings(tag::BASENAMES)
+            .iter()
+            .enumerate()
+            .map(|(i, basename)| {
+                let dirname = dirindexes
+                    .get(i)
+                    .and_then(|index| dirnames.get(*index as usize))
+                    .map(|s| s.as_str())
+                    .unwrap_or_default();
+
+                let file_type = if flags.get(i).copied().unwrap_or(0) & RPMFILE_GHOST != 0 {
+                    Some("ghost".to_string())
+                } else if modes.get(i).copied().unwrap_or(0) & 0o170000 == 0o040000 {
+                    Some("dir".to_string())
+                } else {
+                    None
+                };
+
+                FileEntry {
+                    file_type,
+                    value: format!("{}{}", dirname, basename),
+                }
+            })
+            .collect()
+    }
+
+    fn entries(
+        &self,
+        name_tag: u32,
+        flags_tag: u32,
+        version_tag: u32,
+        with_pre: bool,
+    ) -> Option<Entries> {
+        let names = self.header.strings(name_tag);
+        let flags = self.header.integers(flags_tag);
+        let versions = self.header.strings(version_tag);
+
+        let entries = names
+            .iter()
+            .enumerate()
+            // Dependencies satisfied by rpm itself aren't advertised.
+            .filter(|(_, name)| !name.starts_with("rpmlib("))
+            .map(|(i, name)| {
+                let flags = flags.get(i).copied().unwrap_or(0);
+                let version = versions.get(i).map(|s| s.as_str()).unwrap_or_default();
+
+                let mut entry = PackageEntry {
+                    name: name.clone(),
+                    flags: None,
+                    epoch: None,
+                    version: None,
+                    release: None,
+                    pre: None,
+                };
+
+                if !version.is_empty() {
+                    entry.flags = match flags & (RPMSENSE_LESS | RPMSENSE_GREATER | RPMSENSE_EQUAL)
+                    {
+                        RPMSENSE_LESS => Some("LT"),
+                        RPMSENSE_GREATER => Some("GT"),
+                        RPMSENSE_EQUAL => Some("EQ"),
+                        v if v == RPMSENSE_LESS | RPMSENSE_EQUAL => Some("LE"),
+                        v if v == RPMSENSE_GREATER | RPMSENSE_EQUAL => Some("GE"),
+                        _ => None,
+                    }
+                    .map(|s| s.to_string());
+
+                    let (epoch, rest) = match version.split_once(':') {
+                        Some((epoch, rest)) => (epoch.parse().ok(), rest),
+                        None => (Some(0), version),
+                    };
+                    let (version, release) = match rest.rsplit_once('-') {
+                        Some((version, release)) => (version, Some(release.to_string())),
+                        None => (rest, None),
+                    };
+
+                    entry.epoch = epoch;
+                    entry.version = Some(version.to_string());
+                    entry.release = release;
+                }
+
+                if with_pre
+                    && flags & (RPMSENSE_PREREQ | RPMSENSE_SCRIPT_PRE | RPMSENSE_SCRIPT_POST) != 0
+                {
+                    entry.pre = Some(1);
+                }
+
+                entry
+            })
+            .collect::<Vec<_>>();
+
+        if entries.is_empty() {
+            None
+        } else {
+            Some(Entries { entries })
+        }
+    }
+
+    /// Convert to a [Package] as advertised in `primary.xml`.
+    ///
+    /// `location` is the path of the `.rpm` file relative to the repository root,
+    /// `digest` the digest of the file's content, `file_size` its size, and `file_time`
+    /// its modification time in seconds since the UNIX epoch.
+    pub fn to_package(
+        &self,
+        location: impl ToString,
+        digest: &ContentDigest,
+        file_size: u64,
+        file_time: u64,
+    ) -> Result<Package> {
+        let name = self
+            .name()
+            .ok_or_else(|| RpmRepositoryError::RpmHeader("package has no name".into()))?;
+
+        let string = |tag| self.header.string(tag).map(|s| s.to_string());
+
+        let checksum = crate::metadata::repomd::Checksum::from(digest);
+
+        Ok(Package {
+            package_type: "rpm".to_string(),
+            name: name.to_string(),
+            arch: self.arch().to_string(),
+            version: self.version(),
+            checksum: Checksum {
+                name: checksum.name,
+                value: checksum.value,
+                pkg_id: Some("YES".to_string()),
+            },
+            summary: string(tag::SUMMARY).unwrap_or_default(),
+            description: string(tag::DESCRIPTION).unwrap_or_default(),
+            packager: string(tag::PACKAGER),
+            url: string(tag::URL),
+            time: PackageTime {
+                file: file_time,
+                build: self.header.integer(tag::BUILDTIME).unwrap_or(0),
+            },
+            size: PackageSize {
+                package: file_size,
+                installed: self.header.integer(tag::SIZE).unwrap_or(0),
+                archive: self
+                    .header
+                    .integer(tag::ARCHIVESIZE)
+                    .or_else(|| self.signature.integer(SIGNATURE_PAYLOADSIZE))
+                    .unwrap_or(0),
+            },
+            location: Location {
+                href: location.to_string(),
+            },
+            format: Some(PackageFormat {
+                license: string(tag::LICENSE),
+                vendor: string(tag::VENDOR),
+                group: string(tag::GROUP),
+                build_host: string(tag::BUILDHOST),
+                source_rpm: string(tag::SOURCERPM),
+                header_range: Some(self.header_range.clone()),
+                provides: self.entries(
+                    tag::PROVIDENAME,
+                    tag::PROVIDEFLAGS,
+                    tag::PROVIDEVERSION,
+                    false,
+                ),
+                obsoletes: self.entries(
+                    tag::OBSOLETENAME,
+                    tag::OBSOLETEFLAGS,
+                    tag::OBSOLETEVERSION,
+                    false,
+                ),
+                requires: self.entries(
+                    tag::REQUIRENAME,
+                    tag::REQUIREFLAGS,
+                    tag::REQUIREVERSION,
+                    true,
+                ),
+                conflicts: self.entries(
+                    tag::CONFLICTNAME,
+                    tag::CONFLICTFLAGS,
+                    tag::CONFLICTVERSION,
+                    false,
+                ),
+                suggests: self.entries(
+                    tag::SUGGESTNAME,
+                    tag::SUGGESTFLAGS,
+                    tag::SUGGESTVERSION,
+                    false,
+                ),
+                recommends: self.entries(
+                    tag::RECOMMENDNAME,
+                    tag::RECOMMENDFLAGS,
+                    tag::RECOMMENDVERSION,
+                    false,
+                ),
+                supplements: self.entries(
+                    tag::SUPPLEMENTNAME,
+                    tag::SUPPLEMENTFLAGS,
+                    tag::SUPPLEMENTVERSION,
+                    false,
+                ),
+                files: self.files(),
+            }),
+        })
+    }
+}
+
+/// Encode strings as a `STRING_ARRAY` value.
+#[cfg(test)]
+pub(crate) fn test_strings(values: &[&str]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|s| s.bytes().chain(std::iter::once(0)))
+        .collect()
+}
+
+/// Construct the bytes of a minimal `.rpm` file having the given main header tags.
+///
+/// Values are `(tag, type, data, count)`. The payload is `payload`.
+#[cfg(test)]
+pub(crate) fn test_rpm(tags: &[(u32, u32, Vec<u8>, u32)]) -> Vec<u8> {
+    test_rpm_with_payload(tags, b"payload")
+}
+
+/// Construct the bytes of a minimal `.rpm` file having the given main header tags and payload.
+#[cfg(test)]
+pub(crate) fn test_rpm_with_payload(tags: &[(u32, u32, Vec<u8>, u32)], payload: &[u8]) -> Vec<u8> {
+    fn header(tags: &[(u32, u32, Vec<u8>, u32)]) -> Vec<u8> {
+        let mut index = vec![];
+        let mut data = vec![];
+
+        for (tag, tag_type, value, count) in tags {
+            // Align integers to their width.
+            let align = match tag_type {
+                3 => 2,
+                4 => 4,
+                5 => 8,
+                _ => 1,
+            };
+            while data.len() % align != 0 {
+                data.push(0);
+            }
+
+            for v in [*tag, *tag_type, data.len() as u32, *count] {
+                index.extend_from_slice(&v.to_be_bytes());
+            }
+            data.extend_from_slice(value);
+        }
+
+        let mut res = HEADER_MAGIC.to_vec();
+        res.extend_from_slice(&[0; 4]);
+        res.extend_from_slice(&((tags.len()) as u32).to_be_bytes());
+        res.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        res.extend(index);
+        res.extend(data);
+
+        res
+    }
+
+    let mut res = LEAD_MAGIC.to_vec();
+    res.resize(LEAD_SIZE as usize, 0);
+
+    let signature = header(&[(SIGNATURE_PAYLOADSIZE, 4, 4096u32.to_be_bytes().to_vec(), 1)]);
+    res.extend_from_slice(&signature);
+    while res.len() % 8 != 0 {
+        res.push(0);
+    }
+
+    res.extend(header(tags));
+    res.extend_from_slice(payload);
+
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, test_strings as strings};
+
+    #[test]
+    fn parse_header() -> Result<()> {
+        let data = test_rpm(&[
+            (tag::NAME, 6, strings(&["zstd"]), 1),
+            (tag::VERSION, 6, strings(&["1.5.6"]), 1),
+            (tag::RELEASE, 6, strings(&["2.fc41"]), 1),
+            (
+                tag::SUMMARY,
+                9,
+                strings(&["Zstd compression", "Zstd-Kompression"]),
+                2,
+            ),
+            (tag::BUILDTIME, 4, 1700000000u32.to_be_bytes().to_vec(), 1),
+            (tag::ARCH, 6, strings(&["x86_64"]), 1),
+            (
+                tag::FILEMODES,
+                3,
+                [0o040755u16, 0o100755]
+                    .iter()
+                    .flat_map(|v| v.to_be_bytes())
+                    .collect(),
+                2,
+            ),
+            (
+                tag::SOURCERPM,
+                6,
+                strings(&["zstd-1.5.6-2.fc41.src.rpm"]),
+                1,
+            ),
+            (
+                tag::REQUIREFLAGS,
+                4,
+                [1u32 << 24, (1 << 3) | (1 << 2)]
+                    .iter()
+                    .flat_map(|v| v.to_be_bytes())
+                    .collect(),
+                2,
+            ),
+            (
+                tag::REQUIRENAME,
+                8,
+                strings(&["rpmlib(PayloadIsZstd)", "libzstd"]),
+                2,
+            ),
+            (
+                tag::REQUIREVERSION,
+                8,
+                strings(&["5.4.18-1", "1:1.5.6-2.fc41"]),
+                2,
+            ),
+            (
+                tag::DIRINDEXES,
+                4,
+                [0u32, 1].iter().flat_map(|v| v.to_be_bytes()).collect(),
+                2,
+            ),
+            (tag::BASENAMES, 8, strings(&["zstd", "zstd"]), 2),
+            (
+                tag::DIRNAMES,
+                8,
+                strings(&["/usr/share/doc/", "/usr/bin/"]),
+                2,
+            ),
+        ]);
+
+        let header = RpmHeader::from_reader(std::io::Cursor::new(&data))?;
+        // The 36 byte signature header is padded to 40 bytes.
+        assert_eq!(header.header_range.start, 96 + 40);
+        assert_eq!(
+            &data[header.header_range.end as usize..],
+            b"payload".as_slice()
+        );
+        assert_eq!(header.header.string(tag::SUMMARY), Some("Zstd compression"));
+
+        let digest = ContentDigest::Sha256(vec![0; 32]);
+        let package = header.to_package("Packages/zstd.rpm", &digest, 1024, 1)?;
+        assert_eq!(package.name, "zstd");
+        assert_eq!(package.arch, "x86_64");
+        assert_eq!(package.version.release, "2.fc41");
+        assert_eq!(package.time.build, 1700000000);
+        assert_eq!(package.size.archive, 4096);
+        assert_eq!(package.checksum.value, "00".repeat(32));
+
+        let format = package.format.unwrap();
+        let requires = format.requires.unwrap().entries;
+        assert_eq!(requires.len(), 1);
+        assert_eq!(requires[0].name, "libzstd");
+        assert_eq!(requires[0].flags.as_deref(), Some("GE"));
+        assert_eq!(requires[0].epoch, Some(1));
+        assert_eq!(requires[0].version.as_deref(), Some("1.5.6"));
+        assert_eq!(requires[0].release.as_deref(), Some("2.fc41"));
+        assert!(format.provides.is_none());
+
+        assert_eq!(format.files.len(), 2);
+        assert_eq!(format.files[0].value, "/usr/share/doc/zstd");
+        assert_eq!(format.files[0].file_type.as_deref(), Some("dir"));
+        assert_eq!(format.files[1].value, "/usr/bin/zstd");
+        assert_eq!(format.files[1].file_type, None);
+
+        assert!(matches!(
+            RpmHeader::from_reader(std::io::Cursor::new(&data[1..])),
+            Err(RpmRepositoryError::RpmHeader(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_header() {
+        let intro = |count: u32, data_size: u32| {
+            let mut res = HEADER_MAGIC.to_vec();
+            res.extend_from_slice(&[0; 4]);
+            res.extend_from_slice(&count.to_be_bytes());
+            res.extend_from_slice(&data_size.to_be_bytes());
+            res
+        };
+
+        for (count, data_size) in [(0x10000, 0), (u32::MAX, 0), (1, 0x1000_0001), (1, u32::MAX)] {
+            assert!(matches!(
+                Header::read(&mut intro(count, data_size).as_slice()),
+                Err(RpmRepositoryError::RpmHeader(_))
+            ));
+        }
+
+        // Sizes within limits must be backed by input.
+        assert!(Header::read(&mut intro(0xffff, 0x1000_0000).as_slice()).is_err());
+
+        // String counts are bounded by the data.
+        let mut header = intro(1, 2);
+        for v in [tag::BASENAMES, 8, 0, u32::MAX] {
+            header.extend_from_slice(&v.to_be_bytes());
+        }
+        header.extend_from_slice(b"a\0");
+        assert!(matches!(
+            Header::read(&mut header.as_slice()),
+            Err(RpmRepositoryError::RpmHeader(_))
+        ));
+    }
+}
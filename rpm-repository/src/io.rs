@@ -113,10 +113,19 @@ impl ContentDigest {
     }
 }
 
+impl From<&ContentDigest> for debian_packaging::io::ContentDigest {
+    fn from(v: &ContentDigest) -> Self {
+        match v {
+            ContentDigest::Sha1(x) => Self::Sha1(x.clone()),
+            ContentDigest::Sha256(x) => Self::Sha256(x.clone()),
+        }
+    }
+}
+
 #[pin_project]
 pub struct ContentValidatingReader<R> {
     hasher: Option<Box<dyn digest::DynDigest + Send>>,
-    expected_size: u64,
+    expected_size: Option<u64>,
     expected_digest: ContentDigest,
     #[pin]
     source: R,
@@ -127,7 +136,21 @@ impl<R> ContentValidatingReader<R> {
     pub fn new(source: R, expected_size: u64, expected_digest: ContentDigest) -> Self {
         Self {
             hasher: Some(expected_digest.new_hasher()),
-            expected_size,
+            expected_size: Some(expected_size),
+            expected_digest,
+            source,
+            bytes_read: 0,
+        }
+    }
+
+    /// Create a new instance bound to a source having an expected content digest.
+    ///
+    /// This is for content of unknown size. The digest is validated once the source is
+    /// exhausted.
+    pub fn new_digest_only(source: R, expected_digest: ContentDigest) -> Self {
+        Self {
+            hasher: Some(expected_digest.new_hasher()),
+            expected_size: None,
             expected_digest,
             source,
             bytes_read: 0,
@@ -158,33 +181,38 @@ where
                     *this.bytes_read += size as u64;
                 }
 
-                match this.bytes_read.cmp(&this.expected_size) {
-                    std::cmp::Ordering::Equal => {
-                        if let Some(hasher) = this.hasher.take() {
-                            let got_digest = hasher.finalize();
-
-                            if got_digest.as_ref() != this.expected_digest.digest_bytes() {
-                                return Poll::Ready(Err(std::io::Error::new(
-                                    std::io::ErrorKind::Other,
-                                    format!(
-                                        "digest mismatch of retrieved content: expected {}, got {}",
-                                        this.expected_digest.digest_hex(),
-                                        hex::encode(got_digest)
-                                    ),
-                                )));
-                            }
+                let complete = match *this.expected_size {
+                    Some(expected_size) => match (*this.bytes_read).cmp(&expected_size) {
+                        std::cmp::Ordering::Equal => true,
+                        std::cmp::Ordering::Greater => {
+                            return Poll::Ready(Err(std::io::Error::new(
+                                std::io::ErrorKind::Other,
+                                format!(
+                                    "extra bytes read: expected {}; got {}",
+                                    expected_size, this.bytes_read
+                                ),
+                            )));
+                        }
+                        std::cmp::Ordering::Less => false,
+                    },
+                    None => size == 0 && !buf.is_empty(),
+                };
+
+                if complete {
+                    if let Some(hasher) = this.hasher.take() {
+                        let got_digest = hasher.finalize();
+
+                        if got_digest.as_ref() != this.expected_digest.digest_bytes() {
+                            return Poll::Ready(Err(std::io::Error::new(
+                                std::io::ErrorKind::Other,
+                                format!(
+                                    "digest mismatch of retrieved content: expected {}, got {}",
+                                    this.expected_digest.digest_hex(),
+                                    hex::encode(got_digest)
+                                ),
+                            )));
                         }
                     }
-                    std::cmp::Ordering::Greater => {
-                        return Poll::Ready(Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!(
-                                "extra bytes read: expected {}; got {}",
-                                this.expected_size, this.bytes_read
-                            ),
-                        )));
-                    }
-                    std::cmp::Ordering::Less => {}
                 }
 
                 Poll::Ready(Ok(size))
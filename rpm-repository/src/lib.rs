@@ -19,29 +19,67 @@ base URL. The [MetadataReader] trait defines an interface to repository metadata
 via a parsed `repomd.xml` file.
 
 Concrete repository readers exist. [http::HttpRepositoryClient] provides a reader
-for repositories accessed via HTTP.
+for repositories accessed via HTTP. [filesystem::FilesystemRepositoryReader]
+provides a reader for repositories in a local directory. [repo_file] parses DNF
+`.repo` files and substitutes variables like `$releasever` in their URLs.
+[mirrors::MirrorRepositoryClient] resolves mirror lists and metalinks into a reader
+failing over between mirrors.
+
+[metadata::writer::RepositoryMetadataBuilder] generates `repodata` files from
+package metadata, allowing repositories to be published.
+[createrepo::create_repository()] publishes a directory of `.rpm` files, whose
+headers are parsed by the [header] module. [rpm::RpmReader] reads `.rpm` files,
+including the files in their payload. [copier::RepositoryCopier] mirrors a
+repository to a [RepositoryWriter].
+
+Repository writing uses the [RepositoryWriter] abstraction of the `debian-packaging`
+crate, which is re-exported. [writer_from_str()] constructs writers for local
+directories and, with the `s3` feature, S3 buckets ([S3Writer]). Large objects are
+uploaded to S3 in parts.
 
 */
 
+pub mod copier;
+pub mod createrepo;
 pub mod error;
+pub mod filesystem;
+pub mod header;
 pub mod http;
 pub mod io;
 pub mod metadata;
+pub mod mirrors;
+pub mod repo_file;
+pub mod rpm;
 
 pub use crate::error::{Result, RpmRepositoryError};
 
+#[cfg(feature = "s3")]
+pub use debian_packaging::repository::s3::S3Writer;
+pub use debian_packaging::repository::{filesystem::FilesystemRepositoryWriter, RepositoryWriter};
+
 use {
     crate::{
         io::{read_decompressed, Compression, ContentDigest, ContentValidatingReader},
         metadata::{
+            modules::Modules,
+            prestodelta::PrestoDelta,
             primary::Primary,
             repomd::{RepoMd, RepoMdData},
+            updateinfo::UpdateInfo,
         },
     },
     futures::{AsyncRead, AsyncReadExt},
     std::{future::Future, pin::Pin},
 };
 
+/// Construct a [RepositoryWriter] from a string/URL.
+///
+/// `file://`, `null://`, and `s3://bucket/prefix` URLs are recognized. `s3://` requires the
+/// `s3` feature. Other strings are interpreted as filesystem paths.
+pub async fn writer_from_str(s: impl ToString) -> Result<Box<dyn RepositoryWriter>> {
+    Ok(debian_packaging::repository::writer_from_str(s).await?)
+}
+
 /// Path based content fetching.
 pub trait DataResolver: Sync {
     /// Get the content of a relative path as an async reader.
@@ -198,6 +236,7 @@ pub trait MetadataReader: DataResolver + Sync {
             let compression = match path {
                 _ if path.ends_with(".gz") => Compression::Gzip,
                 _ if path.ends_with(".xz") => Compression::Xz,
+                _ if path.ends_with(".zst") => Compression::Zstd,
                 _ => Compression::None,
             };
 
@@ -236,4 +275,98 @@ pub trait MetadataReader: DataResolver + Sync {
 
         Box::pin(run(self))
     }
+
+    /// Fetch and parse the `updateinfo.xml` file describing advisories.
+    ///
+    /// Errors with [RpmRepositoryError::MetadataFileNotFound] if the repository doesn't
+    /// publish advisories.
+    #[allow(clippy::type_complexity)]
+    fn update_info(&self) -> Pin<Box<dyn Future<Output = Result<UpdateInfo>> + Send + '_>> {
+        async fn run(slf: &(impl MetadataReader + ?Sized)) -> Result<UpdateInfo> {
+            let entry = slf
+                .repomd()
+                .data
+                .iter()
+                .find(|entry| entry.data_type == "updateinfo")
+                .ok_or(RpmRepositoryError::MetadataFileNotFound("updateinfo"))?;
+
+            let mut reader = slf.fetch_data_file(entry).await?;
+            let mut data = vec![];
+
+            reader
+                .read_to_end(&mut data)
+                .await
+                .map_err(|e| RpmRepositoryError::IoPath(entry.location.href.clone(), e))?;
+
+            UpdateInfo::from_reader(std::io::Cursor::new(data))
+        }
+
+        Box::pin(run(self))
+    }
+
+    /// Fetch and parse the `prestodelta.xml` file describing delta RPMs.
+    ///
+    /// Errors with [RpmRepositoryError::MetadataFileNotFound] if the repository doesn't
+    /// publish delta RPMs.
+    #[allow(clippy::type_complexity)]
+    fn presto_delta(&self) -> Pin<Box<dyn Future<Output = Result<PrestoDelta>> + Send + '_>> {
+        async fn run(slf: &(impl MetadataReader + ?Sized)) -> Result<PrestoDelta> {
+            let entry = slf
+                .repomd()
+                .data
+                .iter()
+                .find(|entry| entry.data_type == "prestodelta")
+                .ok_or(RpmRepositoryError::MetadataFileNotFound("prestodelta"))?;
+
+            let mut reader = slf.fetch_data_file(entry).await?;
+            let mut data = vec![];
+
+            reader
+                .read_to_end(&mut data)
+                .await
+                .map_err(|e| RpmRepositoryError::IoPath(entry.location.href.clone(), e))?;
+
+            PrestoDelta::from_reader(std::io::Cursor::new(data))
+        }
+
+        Box::pin(run(self))
+    }
+
+    /// Fetch the `modules.yaml` file of a modular repository.
+    ///
+    /// Errors with [RpmRepositoryError::MetadataFileNotFound] if the repository isn't
+    /// modular.
+    #[allow(clippy::type_complexity)]
+    fn modules_yaml(&self) -> Pin<Box<dyn Future<Output = Result<String>> + Send + '_>> {
+        async fn run(slf: &(impl MetadataReader + ?Sized)) -> Result<String> {
+            let entry = slf
+                .repomd()
+                .data
+                .iter()
+                .find(|entry| entry.data_type == "modules")
+                .ok_or(RpmRepositoryError::MetadataFileNotFound("modules"))?;
+
+            let mut reader = slf.fetch_data_file(entry).await?;
+            let mut data = String::new();
+
+            reader
+                .read_to_string(&mut data)
+                .await
+                .map_err(|e| RpmRepositoryError::IoPath(entry.location.href.clone(), e))?;
+
+            Ok(data)
+        }
+
+        Box::pin(run(self))
+    }
+
+    /// Fetch and parse the `modules.yaml` file of a modular repository.
+    #[allow(clippy::type_complexity)]
+    fn modules(&self) -> Pin<Box<dyn Future<Output = Result<Modules>> + Send + '_>> {
+        async fn run(slf: &(impl MetadataReader + ?Sized)) -> Result<Modules> {
+            Modules::from_yaml(&slf.modules_yaml().await?)
+        }
+
+        Box::pin(run(self))
+    }
 }
@@ -6,7 +6,46 @@
 
 RPM repositories define metadata via a series of XML files. This module defines these
 XML data structures.
+
+[primary] describes packages, [updateinfo] describes advisories (errata) and
+[repomd] describes the other metadata files. Modular repositories also publish the
+YAML [modules] file. [prestodelta] describes delta RPMs.
+
+The [writer] module generates these files from package metadata.
 */
 
+use std::{borrow::Cow, fmt::Write};
+
+pub mod modules;
+pub mod prestodelta;
 pub mod primary;
 pub mod repomd;
+pub mod updateinfo;
+pub mod writer;
+
+/// Escape a string for use in XML text or attribute values.
+pub(crate) fn xml_escape(s: &str) -> Cow<'_, str> {
+    if !s.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(s);
+    }
+
+    let mut res = String::with_capacity(s.len() + 16);
+
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            c => res.push(c),
+        }
+    }
+
+    Cow::Owned(res)
+}
+
+/// Write an element having text content, including a trailing newline.
+pub(crate) fn write_element(s: &mut String, indent: &str, name: &str, value: &str) {
+    writeln!(s, "{indent}<{name}>{}</{name}>", xml_escape(value)).unwrap();
+}
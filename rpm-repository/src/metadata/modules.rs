@@ -0,0 +1,245 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! `modules.yaml` file format.
+
+Modular repositories (e.g. RHEL AppStream) describe module streams in a YAML document
+stream advertised in `repomd.xml` with the `modules` data type. Each document has a
+`document` type, a format `version`, and a `data` mapping.
+
+This module parses `modulemd` (module stream) and `modulemd-defaults` documents into
+typed structures. Other documents are retained as raw YAML values. Since the format
+has many fields not modeled here, repositories should be mirrored by publishing the
+original YAML rather than a serialization of these types.
+*/
+
+use {
+    crate::error::Result,
+    serde::{Deserialize, Deserializer},
+    std::{collections::BTreeMap, io::Read},
+};
+
+/// A parsed `modules.yaml` file.
+#[derive(Clone, Debug, Default)]
+pub struct Modules {
+    /// Documents in this file, in order of appearance.
+    pub documents: Vec<ModuleDocument>,
+}
+
+impl Modules {
+    /// Construct an instance by parsing YAML from a reader.
+    pub fn from_reader(mut reader: impl Read) -> Result<Self> {
+        let mut s = String::new();
+        reader.read_to_string(&mut s)?;
+
+        Self::from_yaml(&s)
+    }
+
+    /// Construct an instance by parsing YAML from a string.
+    pub fn from_yaml(s: &str) -> Result<Self> {
+        let documents = serde_yaml::Deserializer::from_str(s)
+            .map(|de| ModuleDocument::from_raw(RawDocument::deserialize(de)?))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { documents })
+    }
+
+    /// Iterate over module streams.
+    pub fn streams(&self) -> impl Iterator<Item = &ModuleStream> + '_ {
+        self.documents.iter().filter_map(|doc| match doc {
+            ModuleDocument::Stream(stream) => Some(stream),
+            _ => None,
+        })
+    }
+
+    /// Iterate over module defaults.
+    pub fn defaults(&self) -> impl Iterator<Item = &ModuleDefaults> + '_ {
+        self.documents.iter().filter_map(|doc| match doc {
+            ModuleDocument::Defaults(defaults) => Some(defaults),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RawDocument {
+    document: String,
+    version: u64,
+    #[serde(default)]
+    data: serde_yaml::Value,
+}
+
+/// A document in a `modules.yaml` file.
+#[derive(Clone, Debug)]
+pub enum ModuleDocument {
+    /// A `modulemd` document describing a module stream.
+    Stream(ModuleStream),
+
+    /// A `modulemd-defaults` document.
+    Defaults(ModuleDefaults),
+
+    /// A document of another type or format version.
+    Other {
+        /// The value of the `document` key.
+        document: String,
+        /// The value of the `version` key.
+        version: u64,
+        /// The value of the `data` key.
+        data: serde_yaml::Value,
+    },
+}
+
+impl ModuleDocument {
+    fn from_raw(raw: RawDocument) -> Result<Self> {
+        Ok(match (raw.document.as_str(), raw.version) {
+            ("modulemd", 2) => Self::Stream(serde_yaml::from_value(raw.data)?),
+            ("modulemd-defaults", 1) => Self::Defaults(serde_yaml::from_value(raw.data)?),
+            _ => Self::Other {
+                document: raw.document,
+                version: raw.version,
+                data: raw.data,
+            },
+        })
+    }
+}
+
+/// A module stream, as defined by a version 2 `modulemd` document.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModuleStream {
+    /// The name of the module.
+    pub name: String,
+
+    /// The name of the stream.
+    ///
+    /// e.g. `18`.
+    #[serde(deserialize_with = "deserialize_scalar")]
+    pub stream: String,
+
+    /// The version of the stream, which increases with each build.
+    pub version: u64,
+
+    /// Distinguishes builds of the same version against different dependencies.
+    pub context: Option<String>,
+
+    /// The machine architecture of the stream.
+    pub arch: Option<String>,
+
+    /// A text summary of the module.
+    pub summary: String,
+
+    /// A longer text description of the module.
+    pub description: String,
+
+    /// Installation profiles, keyed by name.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ModuleProfile>,
+
+    /// Packages constituting the stream.
+    #[serde(default)]
+    pub artifacts: ModuleArtifacts,
+}
+
+impl ModuleStream {
+    /// Obtain the `name:stream:version:context:arch` identifier of this stream.
+    ///
+    /// Missing parts are empty.
+    pub fn nsvca(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}",
+            self.name,
+            self.stream,
+            self.version,
+            self.context.as_deref().unwrap_or_default(),
+            self.arch.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+/// An installation profile of a module stream.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ModuleProfile {
+    /// A description of the profile.
+    pub description: Option<String>,
+
+    /// Names of packages installed by this profile.
+    #[serde(default)]
+    pub rpms: Vec<String>,
+}
+
+/// Packages constituting a module stream.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ModuleArtifacts {
+    /// Packages, in `name-epoch:version-release.arch` form.
+    #[serde(default)]
+    pub rpms: Vec<String>,
+}
+
+/// Defaults of a module, as defined by a version 1 `modulemd-defaults` document.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModuleDefaults {
+    /// The name of the module.
+    pub module: String,
+
+    /// The default stream.
+    #[serde(default, deserialize_with = "deserialize_optional_scalar")]
+    pub stream: Option<String>,
+
+    /// Default profiles, keyed by stream name.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Vec<String>>,
+}
+
+/// Deserialize a YAML scalar as a string.
+///
+/// Stream names like `8.0` are sometimes unquoted and parse as numbers.
+fn deserialize_scalar<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<String, D::Error> {
+    match serde_yaml::Value::deserialize(d)? {
+        serde_yaml::Value::String(s) => Ok(s),
+        serde_yaml::Value::Number(n) => Ok(n.to_string()),
+        serde_yaml::Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(serde::de::Error::custom("expected a scalar value")),
+    }
+}
+
+fn deserialize_optional_scalar<'de, D: Deserializer<'de>>(
+    d: D,
+) -> std::result::Result<Option<String>, D::Error> {
+    deserialize_scalar(d).map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MODULES_YAML: &str = include_str!("../testdata/modules.yaml");
+
+    #[test]
+    fn parse() -> Result<()> {
+        let modules = Modules::from_yaml(MODULES_YAML)?;
+        assert_eq!(modules.documents.len(), 4);
+
+        let streams = modules.streams().collect::<Vec<_>>();
+        assert_eq!(streams.len(), 2);
+        assert_eq!(
+            streams[0].nsvca(),
+            "nodejs:18:8090020230810:a75119d5:x86_64"
+        );
+        assert_eq!(streams[0].profiles["common"].rpms, vec!["nodejs", "npm"]);
+        assert_eq!(streams[0].artifacts.rpms.len(), 2);
+        assert_eq!(streams[1].stream, "8.0");
+        assert!(streams[1].artifacts.rpms.is_empty());
+
+        let defaults = modules.defaults().collect::<Vec<_>>();
+        assert_eq!(defaults.len(), 1);
+        assert_eq!(defaults[0].stream.as_deref(), Some("18"));
+        assert_eq!(defaults[0].profiles["18"], vec!["common"]);
+
+        assert!(matches!(
+            &modules.documents[3],
+            ModuleDocument::Other { document, version: 1, .. } if document == "modulemd-obsoletes"
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! `prestodelta.xml` file format.
+
+Repositories can publish delta RPMs (`.drpm` files), which hold the difference between
+an old and a new version of a package. Clients having the old version installed fetch
+the delta and reconstruct the new package from it, which usually requires a fraction
+of the bandwidth of fetching the new package.
+
+`prestodelta.xml` describes available deltas. It is advertised in `repomd.xml` with
+the `prestodelta` data type. [PrestoDelta::delta_for_upgrade()] resolves the delta
+applicable to upgrading an installed package to a package in `primary.xml`.
+*/
+
+use {
+    crate::{
+        error::Result,
+        metadata::{
+            primary::{Package, PackageVersion},
+            repomd::Checksum,
+        },
+    },
+    serde::{Deserialize, Serialize},
+    std::io::Read,
+};
+
+/// A `prestodelta.xml` file.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PrestoDelta {
+    /// `<newpackage>` elements in this document.
+    #[serde(default, rename = "newpackage")]
+    pub packages: Vec<DeltaTarget>,
+}
+
+impl PrestoDelta {
+    /// Construct an instance by parsing XML from a reader.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        Ok(serde_xml_rs::from_reader(reader)?)
+    }
+
+    /// Construct an instance by parsing XML from a string.
+    pub fn from_xml(s: &str) -> Result<Self> {
+        Ok(serde_xml_rs::from_str(s)?)
+    }
+
+    /// Iterate over deltas upgrading from a given version of a package.
+    ///
+    /// Yields the package version each delta produces along with the delta.
+    pub fn deltas_from<'a>(
+        &'a self,
+        name: &'a str,
+        arch: &'a str,
+        old: &'a PackageVersion,
+    ) -> impl Iterator<Item = (&'a DeltaTarget, &'a Delta)> + 'a {
+        self.packages
+            .iter()
+            .filter(move |target| target.name == name && target.arch == arch)
+            .flat_map(move |target| {
+                target
+                    .deltas
+                    .iter()
+                    .filter(move |delta| &delta.old_version() == old)
+                    .map(move |delta| (target, delta))
+            })
+    }
+
+    /// Find the delta upgrading a package between two versions.
+    pub fn delta_for(
+        &self,
+        name: &str,
+        arch: &str,
+        old: &PackageVersion,
+        new: &PackageVersion,
+    ) -> Option<&Delta> {
+        self.packages
+            .iter()
+            .filter(|target| target.name == name && target.arch == arch)
+            .filter(|target| &target.version() == new)
+            .flat_map(|target| target.deltas.iter())
+            .find(|delta| &delta.old_version() == old)
+    }
+
+    /// Find the delta upgrading an installed version of a package to a package in `primary.xml`.
+    ///
+    /// Returns `None` if no delta is available, in which case the full package must be
+    /// fetched.
+    pub fn delta_for_upgrade(
+        &self,
+        installed: &PackageVersion,
+        package: &Package,
+    ) -> Option<&Delta> {
+        self.delta_for(&package.name, &package.arch, installed, &package.version)
+    }
+}
+
+/// A package version deltas produce, as defined by a `<newpackage>` element.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeltaTarget {
+    /// The name of the package.
+    pub name: String,
+
+    /// The epoch of the package.
+    #[serde(default)]
+    pub epoch: u64,
+
+    /// The version of the package.
+    pub version: String,
+
+    /// The release of the package.
+    pub release: String,
+
+    /// The machine architecture of the package.
+    pub arch: String,
+
+    /// Deltas producing this package from older versions.
+    #[serde(default, rename = "delta")]
+    pub deltas: Vec<Delta>,
+}
+
+impl DeltaTarget {
+    /// Obtain the version of this package.
+    pub fn version(&self) -> PackageVersion {
+        PackageVersion {
+            epoch: self.epoch,
+            version: self.version.clone(),
+            release: self.release.clone(),
+        }
+    }
+}
+
+/// A delta RPM, as defined by a `<delta>` element.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Delta {
+    /// The epoch of the package version this delta applies to.
+    #[serde(default, rename = "oldepoch")]
+    pub old_epoch: u64,
+
+    /// The version of the package version this delta applies to.
+    #[serde(rename = "oldversion")]
+    pub old_version: String,
+
+    /// The release of the package version this delta applies to.
+    #[serde(rename = "oldrelease")]
+    pub old_release: String,
+
+    /// Path of the `.drpm` file relative to the repository root.
+    pub filename: String,
+
+    /// Identifies the content of the old package the delta applies to.
+    ///
+    /// `applydeltarpm` verifies installed files against this before reconstructing.
+    pub sequence: String,
+
+    /// Size of the `.drpm` file in bytes.
+    pub size: u64,
+
+    /// Content digest of the `.drpm` file.
+    pub checksum: Checksum,
+}
+
+impl Delta {
+    /// Obtain the package version this delta applies to.
+    pub fn old_version(&self) -> PackageVersion {
+        PackageVersion {
+            epoch: self.old_epoch,
+            version: self.old_version.clone(),
+            release: self.old_release.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::io::ContentDigest};
+
+    const PRESTODELTA_XML: &str = include_str!("../testdata/prestodelta.xml");
+
+    fn version(version: &str, release: &str) -> PackageVersion {
+        PackageVersion {
+            epoch: 0,
+            version: version.into(),
+            release: release.into(),
+        }
+    }
+
+    #[test]
+    fn resolve_deltas() -> Result<()> {
+        let presto = PrestoDelta::from_xml(PRESTODELTA_XML)?;
+        assert_eq!(presto.packages.len(), 2);
+
+        let old = version("1.5.5", "3.fc41");
+        let new = version("1.5.6", "2.fc41");
+
+        let delta = presto.delta_for("zstd", "x86_64", &old, &new).unwrap();
+        assert_eq!(
+            delta.filename,
+            "drpms/zstd-1.5.5-3.fc41_1.5.6-2.fc41.x86_64.drpm"
+        );
+        assert_eq!(delta.size, 45678);
+        assert!(matches!(
+            ContentDigest::try_from(delta.checksum.clone())?,
+            ContentDigest::Sha256(_)
+        ));
+
+        assert!(presto.delta_for("zstd", "aarch64", &old, &new).is_none());
+        assert!(presto
+            .delta_for("zstd", "x86_64", &old, &version("1.5.7", "1.fc41"))
+            .is_none());
+
+        let old = version("1.5.6", "1.fc41");
+        let targets = presto
+            .deltas_from("zstd", "aarch64", &old)
+            .map(|(target, _)| target.version())
+            .collect::<Vec<_>>();
+        assert_eq!(targets, vec![new]);
+
+        Ok(())
+    }
+}
@@ -8,10 +8,10 @@ use {
     crate::{
         error::{Result, RpmRepositoryError},
         io::ContentDigest,
-        metadata::repomd::Location,
+        metadata::{repomd::Location, write_element, xml_escape},
     },
     serde::{Deserialize, Serialize},
-    std::io::Read,
+    std::{fmt::Write, io::Read},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +35,30 @@ impl Primary {
     pub fn from_xml(s: &str) -> Result<Self> {
         Ok(serde_xml_rs::from_str(s)?)
     }
+
+    /// Serialize this instance to XML.
+    ///
+    /// The `packages` attribute is derived from the number of packages.
+    pub fn to_xml(&self) -> String {
+        let mut s = String::new();
+
+        s.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        writeln!(
+            s,
+            "<metadata xmlns=\"http://linux.duke.edu/metadata/common\" \
+             xmlns:rpm=\"http://linux.duke.edu/metadata/rpm\" packages=\"{}\">",
+            self.packages.len()
+        )
+        .unwrap();
+
+        for package in &self.packages {
+            package.write_xml(&mut s);
+        }
+
+        s.push_str("</metadata>\n");
+
+        s
+    }
 }
 
 /// A package as advertised in a `primary.xml` file.
@@ -83,8 +107,58 @@ pub struct Package {
     pub format: Option<PackageFormat>,
 }
 
+impl Package {
+    fn write_xml(&self, s: &mut String) {
+        writeln!(s, "<package type=\"{}\">", xml_escape(&self.package_type)).unwrap();
+        write_element(s, "  ", "name", &self.name);
+        write_element(s, "  ", "arch", &self.arch);
+        s.push_str("  ");
+        self.version.write_xml(s);
+        write!(
+            s,
+            "  <checksum type=\"{}\"",
+            xml_escape(&self.checksum.name)
+        )
+        .unwrap();
+        if let Some(pkg_id) = &self.checksum.pkg_id {
+            write!(s, " pkgid=\"{}\"", xml_escape(pkg_id)).unwrap();
+        }
+        writeln!(s, ">{}</checksum>", xml_escape(&self.checksum.value)).unwrap();
+        write_element(s, "  ", "summary", &self.summary);
+        write_element(s, "  ", "description", &self.description);
+        if let Some(packager) = &self.packager {
+            write_element(s, "  ", "packager", packager);
+        }
+        if let Some(url) = &self.url {
+            write_element(s, "  ", "url", url);
+        }
+        writeln!(
+            s,
+            "  <time file=\"{}\" build=\"{}\"/>",
+            self.time.file, self.time.build
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "  <size package=\"{}\" installed=\"{}\" archive=\"{}\"/>",
+            self.size.package, self.size.installed, self.size.archive
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "  <location href=\"{}\"/>",
+            xml_escape(&self.location.href)
+        )
+        .unwrap();
+        if let Some(format) = &self.format {
+            format.write_xml(s);
+        }
+        s.push_str("</package>\n");
+    }
+}
+
 /// Describes a package version.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub struct PackageVersion {
     /// When the version came into existence.
     pub epoch: u64,
@@ -98,6 +172,20 @@ pub struct PackageVersion {
     pub release: String,
 }
 
+impl PackageVersion {
+    /// Write the `<version>` element, including a trailing newline.
+    pub(crate) fn write_xml(&self, s: &mut String) {
+        writeln!(
+            s,
+            "<version epoch=\"{}\" ver=\"{}\" rel=\"{}\"/>",
+            self.epoch,
+            xml_escape(&self.version),
+            xml_escape(&self.release)
+        )
+        .unwrap();
+    }
+}
+
 /// Describes the content checksum of a package.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Checksum {
@@ -192,6 +280,58 @@ pub struct PackageFormat {
     pub files: Vec<FileEntry>,
 }
 
+impl PackageFormat {
+    fn write_xml(&self, s: &mut String) {
+        s.push_str("  <format>\n");
+
+        for (name, value) in [
+            ("rpm:license", &self.license),
+            ("rpm:vendor", &self.vendor),
+            ("rpm:group", &self.group),
+            ("rpm:buildhost", &self.build_host),
+            ("rpm:sourcerpm", &self.source_rpm),
+        ] {
+            if let Some(value) = value {
+                write_element(s, "    ", name, value);
+            }
+        }
+
+        if let Some(range) = &self.header_range {
+            writeln!(
+                s,
+                "    <rpm:header-range start=\"{}\" end=\"{}\"/>",
+                range.start, range.end
+            )
+            .unwrap();
+        }
+
+        for (name, entries) in [
+            ("rpm:provides", &self.provides),
+            ("rpm:requires", &self.requires),
+            ("rpm:conflicts", &self.conflicts),
+            ("rpm:obsoletes", &self.obsoletes),
+            ("rpm:suggests", &self.suggests),
+            ("rpm:recommends", &self.recommends),
+            ("rpm:supplements", &self.supplements),
+        ] {
+            if let Some(entries) = entries {
+                writeln!(s, "    <{name}>").unwrap();
+                for entry in &entries.entries {
+                    entry.write_xml(s);
+                }
+                writeln!(s, "    </{name}>").unwrap();
+            }
+        }
+
+        for file in &self.files {
+            s.push_str("    ");
+            file.write_xml(s);
+        }
+
+        s.push_str("  </format>\n");
+    }
+}
+
 /// Describes the location of a header in a package.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct HeaderRange {
@@ -233,6 +373,28 @@ pub struct PackageEntry {
     pub pre: Option<usize>,
 }
 
+impl PackageEntry {
+    fn write_xml(&self, s: &mut String) {
+        write!(s, "      <rpm:entry name=\"{}\"", xml_escape(&self.name)).unwrap();
+        if let Some(flags) = &self.flags {
+            write!(s, " flags=\"{}\"", xml_escape(flags)).unwrap();
+        }
+        if let Some(epoch) = self.epoch {
+            write!(s, " epoch=\"{}\"", epoch).unwrap();
+        }
+        if let Some(version) = &self.version {
+            write!(s, " ver=\"{}\"", xml_escape(version)).unwrap();
+        }
+        if let Some(release) = &self.release {
+            write!(s, " rel=\"{}\"", xml_escape(release)).unwrap();
+        }
+        if let Some(pre) = self.pre {
+            write!(s, " pre=\"{}\"", pre).unwrap();
+        }
+        s.push_str("/>\n");
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct FileEntry {
     /// Type of file.
@@ -244,3 +406,20 @@ pub struct FileEntry {
     #[serde(rename = "$value")]
     pub value: String,
 }
+
+impl FileEntry {
+    /// Write the `<file>` element, including a trailing newline.
+    pub(crate) fn write_xml(&self, s: &mut String) {
+        if let Some(file_type) = &self.file_type {
+            writeln!(
+                s,
+                "<file type=\"{}\">{}</file>",
+                xml_escape(file_type),
+                xml_escape(&self.value)
+            )
+            .unwrap();
+        } else {
+            writeln!(s, "<file>{}</file>", xml_escape(&self.value)).unwrap();
+        }
+    }
+}
@@ -8,9 +8,10 @@ use {
     crate::{
         error::{Result, RpmRepositoryError},
         io::ContentDigest,
+        metadata::xml_escape,
     },
     serde::{Deserialize, Serialize},
-    std::io::Read,
+    std::{fmt::Write, io::Read},
 };
 
 /// A `repomd.xml` file.
@@ -34,6 +35,49 @@ impl RepoMd {
     pub fn from_xml(s: &str) -> Result<Self> {
         Ok(serde_xml_rs::from_str(s)?)
     }
+
+    /// Serialize this instance to XML.
+    pub fn to_xml(&self) -> String {
+        let mut s = String::new();
+
+        s.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        s.push_str("<repomd xmlns=\"http://linux.duke.edu/metadata/repo\" xmlns:rpm=\"http://linux.duke.edu/metadata/rpm\">\n");
+        writeln!(s, "  <revision>{}</revision>", xml_escape(&self.revision)).unwrap();
+
+        for data in &self.data {
+            writeln!(s, "  <data type=\"{}\">", xml_escape(&data.data_type)).unwrap();
+            data.checksum.write_xml(&mut s, "checksum");
+            if let Some(checksum) = &data.open_checksum {
+                checksum.write_xml(&mut s, "open-checksum");
+            }
+            if let Some(checksum) = &data.header_checksum {
+                checksum.write_xml(&mut s, "header-checksum");
+            }
+            writeln!(
+                s,
+                "    <location href=\"{}\"/>",
+                xml_escape(&data.location.href)
+            )
+            .unwrap();
+
+            for (name, value) in [
+                ("timestamp", data.timestamp),
+                ("size", data.size),
+                ("open-size", data.open_size),
+                ("header-size", data.header_size),
+            ] {
+                if let Some(value) = value {
+                    writeln!(s, "    <{name}>{value}</{name}>").unwrap();
+                }
+            }
+
+            s.push_str("  </data>\n");
+        }
+
+        s.push_str("</repomd>\n");
+
+        s
+    }
 }
 
 /// A `<data>` element in a `repomd.xml` file.
@@ -75,6 +119,31 @@ pub struct Checksum {
     pub value: String,
 }
 
+impl Checksum {
+    fn write_xml(&self, s: &mut String, element: &str) {
+        writeln!(
+            s,
+            "    <{element} type=\"{}\">{}</{element}>",
+            xml_escape(&self.name),
+            xml_escape(&self.value)
+        )
+        .unwrap();
+    }
+}
+
+impl From<&ContentDigest> for Checksum {
+    fn from(v: &ContentDigest) -> Self {
+        Self {
+            name: match v {
+                ContentDigest::Sha1(_) => "sha1",
+                ContentDigest::Sha256(_) => "sha256",
+            }
+            .to_string(),
+            value: v.digest_hex(),
+        }
+    }
+}
+
 impl TryFrom<Checksum> for ContentDigest {
     type Error = RpmRepositoryError;
 
@@ -0,0 +1,447 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! `updateinfo.xml` file format.
+
+`updateinfo.xml` describes advisories (also known as errata): security fixes, bug
+fixes, and enhancements, along with the packages fixing them. It is advertised in
+`repomd.xml` with the `updateinfo` data type.
+*/
+
+use {
+    crate::{
+        error::Result,
+        metadata::{repomd::Checksum, write_element, xml_escape},
+    },
+    serde::{Deserialize, Serialize},
+    std::{fmt::Write, io::Read},
+};
+
+/// An `updateinfo.xml` file.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UpdateInfo {
+    /// `<update>` elements in this document.
+    #[serde(default, rename = "update")]
+    pub updates: Vec<Advisory>,
+}
+
+impl UpdateInfo {
+    /// Construct an instance by parsing XML from a reader.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        Ok(serde_xml_rs::from_reader(reader)?)
+    }
+
+    /// Construct an instance by parsing XML from a string.
+    pub fn from_xml(s: &str) -> Result<Self> {
+        Ok(serde_xml_rs::from_str(s)?)
+    }
+
+    /// Serialize this instance to XML.
+    pub fn to_xml(&self) -> String {
+        let mut s = String::new();
+
+        s.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        s.push_str("<updates>\n");
+
+        for advisory in &self.updates {
+            advisory.write_xml(&mut s);
+        }
+
+        s.push_str("</updates>\n");
+
+        s
+    }
+}
+
+/// The type of an advisory.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum AdvisoryType {
+    /// Fixes security issues.
+    Security,
+    /// Fixes bugs.
+    Bugfix,
+    /// Adds features.
+    Enhancement,
+    /// Introduces new packages.
+    NewPackage,
+    /// A type not known to this crate.
+    Other(String),
+}
+
+impl AdvisoryType {
+    /// The value of the `type` attribute of this type.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Security => "security",
+            Self::Bugfix => "bugfix",
+            Self::Enhancement => "enhancement",
+            Self::NewPackage => "newpackage",
+            Self::Other(v) => v,
+        }
+    }
+}
+
+impl From<String> for AdvisoryType {
+    fn from(v: String) -> Self {
+        match v.as_str() {
+            "security" => Self::Security,
+            "bugfix" => Self::Bugfix,
+            "enhancement" => Self::Enhancement,
+            "newpackage" => Self::NewPackage,
+            _ => Self::Other(v),
+        }
+    }
+}
+
+impl From<AdvisoryType> for String {
+    fn from(v: AdvisoryType) -> Self {
+        v.as_str().to_string()
+    }
+}
+
+/// An advisory, as defined by an `<update>` element.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Advisory {
+    /// Entity that issued the advisory.
+    ///
+    /// Usually an email address.
+    pub from: Option<String>,
+
+    /// Status of the advisory.
+    ///
+    /// e.g. `stable` or `testing`.
+    pub status: Option<String>,
+
+    /// The type of advisory.
+    #[serde(rename = "type")]
+    pub advisory_type: AdvisoryType,
+
+    /// Version of the advisory format.
+    pub version: Option<String>,
+
+    /// Unique identifier of the advisory.
+    ///
+    /// e.g. `FEDORA-2024-0d1b4e0e3b`.
+    pub id: String,
+
+    /// Short title of the advisory.
+    pub title: String,
+
+    /// When the advisory was issued.
+    pub issued: Option<AdvisoryDate>,
+
+    /// When the advisory was last updated.
+    pub updated: Option<AdvisoryDate>,
+
+    /// Copyright statement.
+    pub rights: Option<String>,
+
+    /// Name of the release the advisory applies to.
+    pub release: Option<String>,
+
+    /// Severity of the advisory.
+    ///
+    /// e.g. `Critical`, `Important`, `Moderate`, or `Low`.
+    pub severity: Option<String>,
+
+    /// A text summary of the advisory.
+    pub summary: Option<String>,
+
+    /// A longer text description of the advisory.
+    pub description: Option<String>,
+
+    /// How to apply the fix.
+    pub solution: Option<String>,
+
+    /// References to bug trackers, CVEs, and other resources.
+    pub references: Option<References>,
+
+    /// Packages fixing the advisory.
+    pub pkglist: Option<PackageList>,
+}
+
+impl Advisory {
+    /// Iterate over all packages affected by this advisory.
+    pub fn packages(&self) -> impl Iterator<Item = &AdvisoryPackage> + '_ {
+        self.pkglist
+            .iter()
+            .flat_map(|list| list.collections.iter())
+            .flat_map(|collection| collection.packages.iter())
+    }
+
+    /// Iterate over references of this advisory.
+    pub fn references(&self) -> impl Iterator<Item = &Reference> + '_ {
+        self.references
+            .iter()
+            .flat_map(|references| references.references.iter())
+    }
+
+    fn write_xml(&self, s: &mut String) {
+        s.push_str("  <update");
+        if let Some(from) = &self.from {
+            write!(s, " from=\"{}\"", xml_escape(from)).unwrap();
+        }
+        if let Some(status) = &self.status {
+            write!(s, " status=\"{}\"", xml_escape(status)).unwrap();
+        }
+        write!(s, " type=\"{}\"", xml_escape(self.advisory_type.as_str())).unwrap();
+        if let Some(version) = &self.version {
+            write!(s, " version=\"{}\"", xml_escape(version)).unwrap();
+        }
+        s.push_str(">\n");
+
+        write_element(s, "    ", "id", &self.id);
+        write_element(s, "    ", "title", &self.title);
+
+        for (name, date) in [("issued", &self.issued), ("updated", &self.updated)] {
+            if let Some(date) = date {
+                writeln!(s, "    <{name} date=\"{}\"/>", xml_escape(&date.date)).unwrap();
+            }
+        }
+
+        for (name, value) in [
+            ("rights", &self.rights),
+            ("release", &self.release),
+            ("severity", &self.severity),
+            ("summary", &self.summary),
+            ("description", &self.description),
+            ("solution", &self.solution),
+        ] {
+            if let Some(value) = value {
+                write_element(s, "    ", name, value);
+            }
+        }
+
+        if let Some(references) = &self.references {
+            s.push_str("    <references>\n");
+            for reference in &references.references {
+                reference.write_xml(s);
+            }
+            s.push_str("    </references>\n");
+        }
+
+        if let Some(pkglist) = &self.pkglist {
+            s.push_str("    <pkglist>\n");
+            for collection in &pkglist.collections {
+                collection.write_xml(s);
+            }
+            s.push_str("    </pkglist>\n");
+        }
+
+        s.push_str("  </update>\n");
+    }
+}
+
+/// A date attached to an advisory.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AdvisoryDate {
+    /// The date, typically in `YYYY-MM-DD HH:MM:SS` format.
+    pub date: String,
+}
+
+/// The `<references>` element of an advisory.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct References {
+    #[serde(default, rename = "reference")]
+    pub references: Vec<Reference>,
+}
+
+/// A reference to an external resource describing an advisory.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Reference {
+    /// URL of the resource.
+    pub href: String,
+
+    /// Identifier of the resource in its tracker.
+    ///
+    /// e.g. a bug number or CVE identifier.
+    pub id: Option<String>,
+
+    /// Type of the resource.
+    ///
+    /// e.g. `bugzilla`, `cve`, or `self`.
+    #[serde(rename = "type")]
+    pub reference_type: Option<String>,
+
+    /// Title of the resource.
+    pub title: Option<String>,
+}
+
+impl Reference {
+    fn write_xml(&self, s: &mut String) {
+        write!(s, "      <reference href=\"{}\"", xml_escape(&self.href)).unwrap();
+        for (name, value) in [
+            ("id", &self.id),
+            ("type", &self.reference_type),
+            ("title", &self.title),
+        ] {
+            if let Some(value) = value {
+                write!(s, " {name}=\"{}\"", xml_escape(value)).unwrap();
+            }
+        }
+        s.push_str("/>\n");
+    }
+}
+
+/// The `<pkglist>` element of an advisory.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PackageList {
+    #[serde(default, rename = "collection")]
+    pub collections: Vec<Collection>,
+}
+
+/// A set of packages affected by an advisory, usually belonging to a single release.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Collection {
+    /// Short name of the collection.
+    ///
+    /// e.g. `F41`.
+    pub short: Option<String>,
+
+    /// Name of the collection.
+    pub name: Option<String>,
+
+    /// Packages in this collection.
+    #[serde(default, rename = "package")]
+    pub packages: Vec<AdvisoryPackage>,
+}
+
+impl Collection {
+    fn write_xml(&self, s: &mut String) {
+        s.push_str("      <collection");
+        if let Some(short) = &self.short {
+            write!(s, " short=\"{}\"", xml_escape(short)).unwrap();
+        }
+        s.push_str(">\n");
+        if let Some(name) = &self.name {
+            write_element(s, "        ", "name", name);
+        }
+        for package in &self.packages {
+            package.write_xml(s);
+        }
+        s.push_str("      </collection>\n");
+    }
+}
+
+/// A package affected by an advisory.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AdvisoryPackage {
+    /// The name of the package.
+    pub name: String,
+
+    /// The version of the package fixing the advisory.
+    pub version: String,
+
+    /// The release of the package fixing the advisory.
+    pub release: String,
+
+    /// The epoch of the package fixing the advisory.
+    pub epoch: Option<u64>,
+
+    /// The machine architecture of the package.
+    pub arch: String,
+
+    /// URL of the package.
+    pub src: Option<String>,
+
+    /// Filename of the package.
+    pub filename: Option<String>,
+
+    /// Content digest of the package file.
+    pub sum: Option<Checksum>,
+
+    /// Whether a reboot is suggested after installing the package.
+    ///
+    /// Typically `True` when present.
+    pub reboot_suggested: Option<String>,
+}
+
+impl AdvisoryPackage {
+    fn write_xml(&self, s: &mut String) {
+        write!(
+            s,
+            "        <package name=\"{}\" version=\"{}\" release=\"{}\"",
+            xml_escape(&self.name),
+            xml_escape(&self.version),
+            xml_escape(&self.release)
+        )
+        .unwrap();
+        if let Some(epoch) = self.epoch {
+            write!(s, " epoch=\"{}\"", epoch).unwrap();
+        }
+        write!(s, " arch=\"{}\"", xml_escape(&self.arch)).unwrap();
+        if let Some(src) = &self.src {
+            write!(s, " src=\"{}\"", xml_escape(src)).unwrap();
+        }
+        s.push_str(">\n");
+        if let Some(filename) = &self.filename {
+            write_element(s, "          ", "filename", filename);
+        }
+        if let Some(sum) = &self.sum {
+            writeln!(
+                s,
+                "          <sum type=\"{}\">{}</sum>",
+                xml_escape(&sum.name),
+                xml_escape(&sum.value)
+            )
+            .unwrap();
+        }
+        if let Some(reboot) = &self.reboot_suggested {
+            write_element(s, "          ", "reboot_suggested", reboot);
+        }
+        s.push_str("        </package>\n");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UPDATEINFO_XML: &str = include_str!("../testdata/updateinfo.xml");
+
+    #[test]
+    fn parse_roundtrip() -> Result<()> {
+        let info = UpdateInfo::from_xml(UPDATEINFO_XML)?;
+
+        for info in [UpdateInfo::from_xml(&info.to_xml())?, info] {
+            assert_eq!(info.updates.len(), 2);
+
+            let advisory = &info.updates[0];
+            assert_eq!(advisory.advisory_type, AdvisoryType::Security);
+            assert_eq!(advisory.id, "FEDORA-2024-0d1b4e0e3b");
+            assert_eq!(advisory.severity.as_deref(), Some("Important"));
+            assert_eq!(
+                advisory.description.as_deref(),
+                Some("Fix for CVE-2024-0001 & other issues.")
+            );
+            assert_eq!(
+                advisory.issued.as_ref().unwrap().date,
+                "2024-10-01 01:02:03"
+            );
+            assert_eq!(
+                advisory
+                    .references()
+                    .map(|r| r.reference_type.as_deref().unwrap())
+                    .collect::<Vec<_>>(),
+                vec!["bugzilla", "self"]
+            );
+
+            let packages = advisory.packages().collect::<Vec<_>>();
+            assert_eq!(packages.len(), 2);
+            assert_eq!(packages[0].name, "zstd");
+            assert_eq!(packages[0].epoch, Some(0));
+            assert_eq!(packages[0].sum.as_ref().unwrap().name, "sha256");
+            assert_eq!(packages[0].reboot_suggested.as_deref(), Some("True"));
+            assert!(packages[1].sum.is_none());
+
+            let advisory = &info.updates[1];
+            assert_eq!(advisory.advisory_type, AdvisoryType::Enhancement);
+            assert!(advisory.references.is_none());
+            assert_eq!(advisory.packages().count(), 1);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,656 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Generation of repository metadata files.
+
+[RepositoryMetadataBuilder] collects [Package] entries and produces the
+`primary.xml.gz`, `filelists.xml.gz`, `other.xml.gz`, and `repomd.xml` files
+constituting the `repodata` directory of a repository. If advisories are registered,
+`updateinfo.xml.gz` is produced as well. `modules.yaml.gz` holds the module metadata
+given to [RepositoryMetadataBuilder::set_modules_yaml()] verbatim.
+
+Compressed metadata files are named after the SHA-256 of their content, as
+`createrepo_c` does by default. This allows clients to cache them indefinitely.
+
+# Signing
+
+Clients configured with `repo_gpgcheck=1` require a detached, armored PGP signature
+of `repomd.xml` in `repomd.xml.asc`. Keys registered via
+[RepositoryMetadataBuilder::add_signing_key()] produce this signature when metadata
+is built. [sign_repomd()] signs existing `repomd.xml` content. Signatures are made by
+the same primitives used to sign Debian `Release` files.
+*/
+
+use {
+    crate::{
+        error::{Result, RpmRepositoryError},
+        io::ContentDigest,
+        metadata::{
+            primary::{Package, Primary},
+            repomd::{Location, RepoMd, RepoMdData},
+            updateinfo::{Advisory, UpdateInfo},
+            xml_escape,
+        },
+    },
+    async_compression::futures::write::GzipEncoder,
+    debian_packaging::{
+        repository::{
+            signing::{armor_signatures, sign_detached},
+            RepositoryWriter,
+        },
+        signing_key::signing_hash_algorithm,
+    },
+    futures::AsyncWriteExt,
+    pgp::{types::SecretKeyTrait, SignedSecretKey},
+    sha2::Digest,
+    std::{borrow::Cow, fmt::Write, path::Path, sync::Arc},
+};
+
+/// Produce the content of a `repomd.xml.asc` file.
+///
+/// Returns an armored detached signature of `repomd_xml` from each key in
+/// `signing_keys`, in order. Each key signs with the hash algorithm chosen by
+/// [signing_hash_algorithm()].
+pub fn sign_repomd<K, PW>(repomd_xml: &str, signing_keys: &[(&K, PW)]) -> Result<String>
+where
+    K: SecretKeyTrait,
+    PW: Fn() -> String,
+{
+    let signatures = signing_keys
+        .iter()
+        .map(|(key, key_pw)| {
+            sign_detached(
+                *key,
+                key_pw,
+                signing_hash_algorithm(*key),
+                repomd_xml.as_bytes(),
+            )
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(armor_signatures(&signatures)?)
+}
+
+type KeyPasswordFn = dyn Fn() -> String + Send + Sync;
+
+/// Holds keys registered via [RepositoryMetadataBuilder::add_signing_key()].
+#[derive(Clone, Default)]
+struct SigningKeys(Vec<(SignedSecretKey, Arc<KeyPasswordFn>)>);
+
+impl std::fmt::Debug for SigningKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SigningKeys({} keys)", self.0.len())
+    }
+}
+
+/// Builds the metadata files of an RPM repository.
+#[derive(Clone, Debug, Default)]
+pub struct RepositoryMetadataBuilder {
+    packages: Vec<Package>,
+    advisories: Vec<Advisory>,
+    modules_yaml: Option<String>,
+    revision: Option<String>,
+    timestamp: Option<u64>,
+    signing_keys: SigningKeys,
+}
+
+impl RepositoryMetadataBuilder {
+    /// Register a package with this repository.
+    ///
+    /// The package's `location` should be relative to the repository root and its
+    /// `checksum` is used as the package identifier in all metadata files.
+    pub fn add_package(&mut self, package: Package) {
+        self.packages.push(package);
+    }
+
+    /// Register an advisory with this repository.
+    ///
+    /// Advisories are written to `updateinfo.xml`.
+    pub fn add_advisory(&mut self, advisory: Advisory) {
+        self.advisories.push(advisory);
+    }
+
+    /// Set the content of the `modules.yaml` file of a modular repository.
+    ///
+    /// Content is published as is. Use the content fetched via
+    /// [crate::MetadataReader::modules_yaml()] to mirror a repository.
+    pub fn set_modules_yaml(&mut self, yaml: impl ToString) {
+        self.modules_yaml = Some(yaml.to_string());
+    }
+
+    /// Set the `<revision>` of the `repomd.xml` file.
+    ///
+    /// Defaults to the timestamp.
+    pub fn set_revision(&mut self, revision: impl ToString) {
+        self.revision = Some(revision.to_string());
+    }
+
+    /// Set the timestamp, in seconds since the UNIX epoch, recorded for metadata files.
+    ///
+    /// Defaults to the current time.
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        self.timestamp = Some(timestamp);
+    }
+
+    /// Register a key to sign `repomd.xml` with.
+    ///
+    /// If any keys are registered, [Self::build()] produces a `repomd.xml.asc` holding a
+    /// signature from each key.
+    pub fn add_signing_key(
+        &mut self,
+        key: SignedSecretKey,
+        key_pw: impl Fn() -> String + Send + Sync + 'static,
+    ) {
+        self.signing_keys.0.push((key, Arc::new(key_pw)));
+    }
+
+    /// Registered packages.
+    pub fn packages(&self) -> &[Package] {
+        &self.packages
+    }
+
+    /// Generate the uncompressed `primary.xml` content.
+    pub fn primary_xml(&self) -> String {
+        Primary {
+            count: self.packages.len(),
+            packages: self.packages.clone(),
+        }
+        .to_xml()
+    }
+
+    /// Registered advisories.
+    pub fn advisories(&self) -> &[Advisory] {
+        &self.advisories
+    }
+
+    /// Generate the uncompressed `updateinfo.xml` content.
+    pub fn updateinfo_xml(&self) -> String {
+        UpdateInfo {
+            updates: self.advisories.clone(),
+        }
+        .to_xml()
+    }
+
+    /// Generate the uncompressed `filelists.xml` content.
+    pub fn filelists_xml(&self) -> String {
+        let mut s = String::new();
+
+        s.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        writeln!(
+            s,
+            "<filelists xmlns=\"http://linux.duke.edu/metadata/filelists\" packages=\"{}\">",
+            self.packages.len()
+        )
+        .unwrap();
+
+        for package in &self.packages {
+            write_package_start(&mut s, package);
+
+            if let Some(format) = &package.format {
+                for file in &format.files {
+                    s.push_str("  ");
+                    file.write_xml(&mut s);
+                }
+            }
+
+            s.push_str("</package>\n");
+        }
+
+        s.push_str("</filelists>\n");
+
+        s
+    }
+
+    /// Generate the uncompressed `other.xml` content.
+    ///
+    /// Changelog entries aren't part of [Package] and are not emitted.
+    pub fn other_xml(&self) -> String {
+        let mut s = String::new();
+
+        s.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        writeln!(
+            s,
+            "<otherdata xmlns=\"http://linux.duke.edu/metadata/other\" packages=\"{}\">",
+            self.packages.len()
+        )
+        .unwrap();
+
+        for package in &self.packages {
+            write_package_start(&mut s, package);
+            s.push_str("</package>\n");
+        }
+
+        s.push_str("</otherdata>\n");
+
+        s
+    }
+
+    /// Generate all metadata files.
+    pub async fn build(&self) -> Result<RepositoryMetadata> {
+        let timestamp = self.timestamp.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+        let mut repomd = RepoMd {
+            revision: self
+                .revision
+                .clone()
+                .unwrap_or_else(|| timestamp.to_string()),
+            data: vec![],
+        };
+        let mut files = vec![];
+
+        let mut data_files = vec![
+            ("primary", "xml", self.primary_xml()),
+            ("filelists", "xml", self.filelists_xml()),
+            ("other", "xml", self.other_xml()),
+        ];
+        if !self.advisories.is_empty() {
+            data_files.push(("updateinfo", "xml", self.updateinfo_xml()));
+        }
+        if let Some(yaml) = &self.modules_yaml {
+            data_files.push(("modules", "yaml", yaml.clone()));
+        }
+
+        for (data_type, extension, content) in data_files {
+            let mut encoder = GzipEncoder::new(vec![]);
+            encoder.write_all(content.as_bytes()).await?;
+            encoder.close().await?;
+            let data = encoder.into_inner();
+
+            let checksum = ContentDigest::Sha256(sha2::Sha256::digest(&data).to_vec());
+            let open_checksum = ContentDigest::Sha256(sha2::Sha256::digest(&content).to_vec());
+
+            let path = format!(
+                "repodata/{}-{}.{}.gz",
+                checksum.digest_hex(),
+                data_type,
+                extension
+            );
+
+            repomd.data.push(RepoMdData {
+                data_type: data_type.to_string(),
+                checksum: (&checksum).into(),
+                location: Location { href: path.clone() },
+                size: Some(data.len() as u64),
+                timestamp: Some(timestamp),
+                open_checksum: Some((&open_checksum).into()),
+                open_size: Some(content.len() as u64),
+                header_checksum: None,
+                header_size: None,
+            });
+
+            files.push(MetadataFile { path, data });
+        }
+
+        let repomd_signature = if self.signing_keys.0.is_empty() {
+            None
+        } else {
+            Some(sign_repomd(
+                &repomd.to_xml(),
+                &self
+                    .signing_keys
+                    .0
+                    .iter()
+                    .map(|(key, key_pw)| (key, key_pw.as_ref()))
+                    .collect::<Vec<_>>(),
+            )?)
+        };
+
+        Ok(RepositoryMetadata {
+            repomd,
+            files,
+            repomd_signature,
+        })
+    }
+}
+
+/// Write the opening `<package>` and `<version>` elements of filelists and other data.
+fn write_package_start(s: &mut String, package: &Package) {
+    writeln!(
+        s,
+        "<package pkgid=\"{}\" name=\"{}\" arch=\"{}\">",
+        xml_escape(&package.checksum.value),
+        xml_escape(&package.name),
+        xml_escape(&package.arch)
+    )
+    .unwrap();
+    s.push_str("  ");
+    package.version.write_xml(s);
+}
+
+/// A generated metadata file.
+#[derive(Clone, Debug)]
+pub struct MetadataFile {
+    /// Path of this file relative to the repository root.
+    pub path: String,
+
+    /// Content of this file.
+    pub data: Vec<u8>,
+}
+
+/// Generated repository metadata.
+#[derive(Clone, Debug)]
+pub struct RepositoryMetadata {
+    /// The `repomd.xml` describing metadata files.
+    pub repomd: RepoMd,
+
+    /// Metadata files referenced by `repomd`.
+    pub files: Vec<MetadataFile>,
+
+    /// Content of `repomd.xml.asc`, if signing keys were registered.
+    ///
+    /// Signatures cover [Self::repomd_xml()] and are invalidated by changes to `repomd`.
+    pub repomd_signature: Option<String>,
+}
+
+impl RepositoryMetadata {
+    /// Generate the `repomd.xml` content.
+    pub fn repomd_xml(&self) -> String {
+        self.repomd.to_xml()
+    }
+
+    /// Obtain paths and content of all files to write, in the order to write them.
+    fn writes(&self) -> Vec<(&str, Cow<'_, [u8]>)> {
+        let mut writes = self
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), Cow::Borrowed(file.data.as_slice())))
+            .collect::<Vec<_>>();
+
+        writes.push((
+            "repodata/repomd.xml",
+            Cow::Owned(self.repomd_xml().into_bytes()),
+        ));
+
+        if let Some(signature) = &self.repomd_signature {
+            writes.push((
+                "repodata/repomd.xml.asc",
+                Cow::Borrowed(signature.as_bytes()),
+            ));
+        }
+
+        writes
+    }
+
+    /// Write all metadata files via a [RepositoryWriter].
+    ///
+    /// `repomd.xml` is written after the files it references so clients never see it
+    /// referencing missing files. `repomd.xml.asc` is written last.
+    pub async fn write_to(&self, writer: &dyn RepositoryWriter) -> Result<()> {
+        for (path, data) in self.writes() {
+            writer
+                .write_path(path.into(), Box::pin(futures::io::Cursor::new(data)))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Write all metadata files under a repository root directory.
+    ///
+    /// Files are written in the same order as [Self::write_to()].
+    pub fn write_to_directory(&self, root: impl AsRef<Path>) -> Result<()> {
+        let root = root.as_ref();
+
+        let write = |path: &str, data: &[u8]| -> Result<()> {
+            let dest = root.join(path);
+
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| RpmRepositoryError::IoPath(format!("{}", parent.display()), e))?;
+            }
+
+            std::fs::write(&dest, data)
+                .map_err(|e| RpmRepositoryError::IoPath(format!("{}", dest.display()), e))
+        };
+
+        for (path, data) in self.writes() {
+            write(path, &data)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            io::{read_decompressed, Compression},
+            metadata::primary::{
+                Checksum, Entries, FileEntry, PackageEntry, PackageFormat, PackageSize,
+                PackageTime, PackageVersion,
+            },
+        },
+        debian_packaging::{
+            repository::signing::parse_armored_signatures,
+            signing_key::{create_self_signed_key, signing_secret_key_params_builder},
+        },
+        futures::AsyncReadExt,
+    };
+
+    #[tokio::test]
+    async fn build_roundtrip() -> Result<()> {
+        let mut builder = RepositoryMetadataBuilder::default();
+        builder.set_timestamp(1700000000);
+        builder.add_package(Package {
+            package_type: "rpm".into(),
+            name: "zstd".into(),
+            arch: "x86_64".into(),
+            version: PackageVersion {
+                epoch: 0,
+                version: "1.5.6".into(),
+                release: "2.fc41".into(),
+            },
+            checksum: Checksum {
+                name: "sha256".into(),
+                value: "00".repeat(32),
+                pkg_id: Some("YES".into()),
+            },
+            summary: "Zstd compression & decompression".into(),
+            description: "<fast> compression".into(),
+            packager: None,
+            url: Some("https://github.com/facebook/zstd".into()),
+            time: PackageTime {
+                file: 1700000000,
+                build: 1700000000,
+            },
+            size: PackageSize {
+                package: 100,
+                installed: 200,
+                archive: 300,
+            },
+            location: Location {
+                href: "Packages/z/zstd-1.5.6-2.fc41.x86_64.rpm".into(),
+            },
+            format: Some(PackageFormat {
+                license: Some("BSD".into()),
+                vendor: None,
+                group: None,
+                build_host: None,
+                source_rpm: Some("zstd-1.5.6-2.fc41.src.rpm".into()),
+                header_range: None,
+                provides: Some(Entries {
+                    entries: vec![PackageEntry {
+                        name: "zstd".into(),
+                        flags: Some("EQ".into()),
+                        epoch: Some(0),
+                        version: Some("1.5.6".into()),
+                        release: Some("2.fc41".into()),
+                        pre: None,
+                    }],
+                }),
+                obsoletes: None,
+                requires: None,
+                conflicts: None,
+                suggests: None,
+                recommends: None,
+                supplements: None,
+                files: vec![FileEntry {
+                    file_type: None,
+                    value: "/usr/bin/zstd".into(),
+                }],
+            }),
+        });
+
+        let metadata = builder.build().await?;
+        assert_eq!(metadata.files.len(), 3);
+
+        let repomd = RepoMd::from_xml(&metadata.repomd_xml())?;
+        assert_eq!(repomd.revision, "1700000000");
+        assert_eq!(repomd.data.len(), 3);
+
+        let entry = repomd
+            .data
+            .iter()
+            .find(|entry| entry.data_type == "primary")
+            .unwrap();
+        let file = metadata
+            .files
+            .iter()
+            .find(|file| file.path == entry.location.href)
+            .unwrap();
+        assert_eq!(entry.size, Some(file.data.len() as u64));
+        assert_eq!(
+            ContentDigest::try_from(entry.checksum.clone())?,
+            ContentDigest::Sha256(sha2::Sha256::digest(&file.data).to_vec())
+        );
+
+        let mut xml = String::new();
+        read_decompressed(
+            futures::io::BufReader::new(file.data.as_slice()),
+            Compression::Gzip,
+        )
+        .read_to_string(&mut xml)
+        .await?;
+        assert_eq!(entry.open_size, Some(xml.len() as u64));
+
+        let primary = Primary::from_xml(&xml)?;
+        assert_eq!(primary.count, 1);
+        let package = &primary.packages[0];
+        assert_eq!(package.name, "zstd");
+        assert_eq!(package.summary, "Zstd compression & decompression");
+        assert_eq!(package.description, "<fast> compression");
+        assert_eq!(package.version.release, "2.fc41");
+        let format = package.format.as_ref().unwrap();
+        assert_eq!(format.provides.as_ref().unwrap().entries[0].name, "zstd");
+        assert_eq!(format.files[0].value, "/usr/bin/zstd");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn build_updateinfo() -> Result<()> {
+        let mut builder = RepositoryMetadataBuilder::default();
+        for advisory in UpdateInfo::from_xml(include_str!("../testdata/updateinfo.xml"))?.updates {
+            builder.add_advisory(advisory);
+        }
+
+        let metadata = builder.build().await?;
+        assert_eq!(metadata.files.len(), 4);
+
+        let entry = metadata
+            .repomd
+            .data
+            .iter()
+            .find(|entry| entry.data_type == "updateinfo")
+            .unwrap();
+        assert!(entry.location.href.ends_with("-updateinfo.xml.gz"));
+
+        let file = metadata
+            .files
+            .iter()
+            .find(|file| file.path == entry.location.href)
+            .unwrap();
+        let mut xml = String::new();
+        read_decompressed(
+            futures::io::BufReader::new(file.data.as_slice()),
+            Compression::Gzip,
+        )
+        .read_to_string(&mut xml)
+        .await?;
+
+        let info = UpdateInfo::from_xml(&xml)?;
+        assert_eq!(info.updates.len(), 2);
+        assert_eq!(info.updates[0].id, "FEDORA-2024-0d1b4e0e3b");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn build_modules() -> Result<()> {
+        let yaml = include_str!("../testdata/modules.yaml");
+
+        let mut builder = RepositoryMetadataBuilder::default();
+        builder.set_modules_yaml(yaml);
+
+        let metadata = builder.build().await?;
+        let entry = metadata
+            .repomd
+            .data
+            .iter()
+            .find(|entry| entry.data_type == "modules")
+            .unwrap();
+        assert!(entry.location.href.ends_with("-modules.yaml.gz"));
+
+        let file = metadata
+            .files
+            .iter()
+            .find(|file| file.path == entry.location.href)
+            .unwrap();
+        let mut content = String::new();
+        read_decompressed(
+            futures::io::BufReader::new(file.data.as_slice()),
+            Compression::Gzip,
+        )
+        .read_to_string(&mut content)
+        .await?;
+        assert_eq!(content, yaml);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn signed_repomd() -> Result<()> {
+        let keys = ["old@example.com", "new@example.com"]
+            .into_iter()
+            .map(|email| {
+                create_self_signed_key(
+                    signing_secret_key_params_builder(email).build().unwrap(),
+                    String::new,
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut builder = RepositoryMetadataBuilder::default();
+        assert!(builder.build().await?.repomd_signature.is_none());
+
+        for (key, _) in &keys {
+            builder.add_signing_key(key.clone(), String::new);
+        }
+
+        let metadata = builder.build().await?;
+        let signatures =
+            parse_armored_signatures(metadata.repomd_signature.as_ref().unwrap().as_bytes())?;
+        assert_eq!(signatures.len(), 2);
+
+        for ((_, public_key), signature) in keys.iter().zip(&signatures) {
+            signature.verify(public_key, metadata.repomd_xml().as_bytes())?;
+        }
+
+        // Clones keep the registered keys.
+        let metadata = builder.clone().build().await?;
+        assert_eq!(
+            parse_armored_signatures(metadata.repomd_signature.as_ref().unwrap().as_bytes())?.len(),
+            2
+        );
+
+        Ok(())
+    }
+}
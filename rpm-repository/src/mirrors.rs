@@ -0,0 +1,534 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Repository mirror resolution.
+
+Repositories like Fedora and EPEL are typically defined by a `metalink` or `mirrorlist`
+URL instead of a base URL. A mirror list is a text document listing base URLs, 1 per
+line. A metalink is an XML document listing URLs of the repository's `repomd.xml` on
+each mirror, ordered by preference, along with digests of current `repomd.xml` files.
+Verifying `repomd.xml` against these digests rejects stale mirrors.
+
+[MirrorRepositoryClient] is a [RepositoryRootReader] that fails over between mirrors.
+[MirrorRepositoryClient::from_repo_definition()] resolves mirrors like DNF does.
+*/
+
+use {
+    crate::{
+        error::{Result, RpmRepositoryError},
+        http::HttpRepositoryClient,
+        io::ContentDigest,
+        metadata::repomd::RepoMd,
+        repo_file::{RepoDefinition, RepoVariables},
+        DataResolver, MetadataReader, RepositoryRootReader,
+    },
+    futures::{AsyncRead, AsyncReadExt},
+    serde::{Deserialize, Serialize},
+    std::{future::Future, io::Read, pin::Pin, sync::Arc},
+    url::Url,
+};
+
+/// Suffix of URLs in metalinks relative to the repository base URL.
+const REPOMD_SUFFIX: &str = "repodata/repomd.xml";
+
+/// Parse a mirror list into base URLs.
+///
+/// Blank lines and `#` comments are ignored.
+pub fn parse_mirrorlist(s: &str) -> Vec<String> {
+    s.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// A metalink document.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Metalink {
+    pub files: MetalinkFiles,
+}
+
+impl Metalink {
+    /// Construct an instance by parsing XML from a reader.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        Ok(serde_xml_rs::from_reader(reader)?)
+    }
+
+    /// Construct an instance by parsing XML from a string.
+    pub fn from_xml(s: &str) -> Result<Self> {
+        Ok(serde_xml_rs::from_str(s)?)
+    }
+
+    /// Obtain the entry describing `repomd.xml`.
+    pub fn repomd(&self) -> Option<&MetalinkFile> {
+        self.files.files.iter().find(|f| f.name == "repomd.xml")
+    }
+
+    /// Obtain base URLs of HTTP mirrors of the repository, in order of preference.
+    pub fn base_urls(&self) -> Vec<String> {
+        let mut urls = self
+            .repomd()
+            .map(|f| f.resources.urls.iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        // Stable sort, retaining document order of mirrors with the same preference.
+        urls.sort_by_key(|url| std::cmp::Reverse(url.preference.unwrap_or(0)));
+
+        urls.into_iter()
+            .filter(|url| matches!(url.protocol.as_deref(), None | Some("http" | "https")))
+            .filter_map(|url| url.url.trim().strip_suffix(REPOMD_SUFFIX))
+            .map(|url| url.to_string())
+            .collect()
+    }
+
+    /// Obtain sizes and digests of acceptable `repomd.xml` files.
+    ///
+    /// This includes the current file and older alternates, which mirrors that haven't
+    /// synced the latest content yet serve.
+    pub fn repomd_digests(&self) -> Result<Vec<(u64, ContentDigest)>> {
+        let Some(file) = self.repomd() else {
+            return Ok(vec![]);
+        };
+
+        std::iter::once((file.size, &file.verification))
+            .chain(
+                file.alternates
+                    .iter()
+                    .flat_map(|a| a.alternates.iter())
+                    .map(|a| (a.size, &a.verification)),
+            )
+            .filter_map(|(size, verification)| Some((size?, verification.as_ref()?.digest()?)))
+            .map(|(size, digest)| Ok((size, digest?)))
+            .collect()
+    }
+}
+
+/// The `<files>` element of a metalink.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MetalinkFiles {
+    #[serde(default, rename = "file")]
+    pub files: Vec<MetalinkFile>,
+}
+
+/// A file described by a metalink.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MetalinkFile {
+    /// The name of the file.
+    pub name: String,
+
+    /// Time the file was last modified, in seconds since the UNIX epoch.
+    pub timestamp: Option<u64>,
+
+    /// Size of the file in bytes.
+    pub size: Option<u64>,
+
+    /// Digests of the file.
+    pub verification: Option<MetalinkVerification>,
+
+    /// Older versions of the file.
+    pub alternates: Option<MetalinkAlternates>,
+
+    /// URLs of the file.
+    #[serde(default)]
+    pub resources: MetalinkResources,
+}
+
+/// Digests of a file described by a metalink.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MetalinkVerification {
+    #[serde(default, rename = "hash")]
+    pub hashes: Vec<MetalinkHash>,
+}
+
+impl MetalinkVerification {
+    /// Obtain the strongest supported digest.
+    pub fn digest(&self) -> Option<Result<ContentDigest>> {
+        ["sha256", "sha1"].iter().find_map(|name| {
+            self.hashes
+                .iter()
+                .find(|h| h.name == *name)
+                .map(|h| ContentDigest::try_from(h.clone()))
+        })
+    }
+}
+
+/// A digest of a file described by a metalink.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MetalinkHash {
+    /// Digest type.
+    #[serde(rename = "type")]
+    pub name: String,
+
+    /// Hex encoded digest value.
+    #[serde(rename = "$value")]
+    pub value: String,
+}
+
+impl TryFrom<MetalinkHash> for ContentDigest {
+    type Error = RpmRepositoryError;
+
+    fn try_from(v: MetalinkHash) -> std::result::Result<Self, Self::Error> {
+        match v.name.as_str() {
+            "sha1" => ContentDigest::sha1_hex(&v.value),
+            "sha256" => ContentDigest::sha256_hex(&v.value),
+            name => Err(RpmRepositoryError::UnknownDigestFormat(name.to_string())),
+        }
+    }
+}
+
+/// Older versions of a file described by a metalink.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MetalinkAlternates {
+    #[serde(default, rename = "alternate")]
+    pub alternates: Vec<MetalinkAlternate>,
+}
+
+/// An older version of a file described by a metalink.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MetalinkAlternate {
+    /// Time the file was last modified, in seconds since the UNIX epoch.
+    pub timestamp: Option<u64>,
+
+    /// Size of the file in bytes.
+    pub size: Option<u64>,
+
+    /// Digests of the file.
+    pub verification: Option<MetalinkVerification>,
+}
+
+/// URLs of a file described by a metalink.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MetalinkResources {
+    #[serde(default, rename = "url")]
+    pub urls: Vec<MetalinkUrl>,
+}
+
+/// A URL of a file described by a metalink.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MetalinkUrl {
+    /// URL scheme. e.g. `https`.
+    pub protocol: Option<String>,
+
+    /// ISO 3166 country code of the mirror.
+    pub location: Option<String>,
+
+    /// Preference of this URL, from 1 to 100. Higher is preferred.
+    pub preference: Option<u32>,
+
+    /// The URL.
+    #[serde(rename = "$value")]
+    pub url: String,
+}
+
+/// Fetch the body of a URL as text.
+async fn fetch_text(client: &reqwest::Client, url: &str) -> Result<String> {
+    Ok(client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?)
+}
+
+/// Fetch and parse a mirror list.
+pub async fn fetch_mirrorlist(client: &reqwest::Client, url: &str) -> Result<Vec<String>> {
+    Ok(parse_mirrorlist(&fetch_text(client, url).await?))
+}
+
+/// Fetch and parse a metalink.
+pub async fn fetch_metalink(client: &reqwest::Client, url: &str) -> Result<Metalink> {
+    Metalink::from_xml(&fetch_text(client, url).await?)
+}
+
+type Mirror = Box<dyn RepositoryRootReader + Send>;
+
+/// Obtain a reader of a path from the first mirror able to serve it.
+///
+/// Mirrors are tried in order, starting at `start`.
+async fn get_path_failover(
+    mirrors: &[Mirror],
+    start: usize,
+    path: String,
+) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+    let mut last_error = None;
+
+    for mirror in mirrors[start..].iter().chain(mirrors[..start].iter()) {
+        match mirror.get_path(path.clone()).await {
+            Ok(reader) => return Ok(reader),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| RpmRepositoryError::Mirror("no mirrors defined".into())))
+}
+
+/// Fetch `repomd.xml` from a mirror, verifying it against expected content.
+async fn fetch_repomd(mirror: &Mirror, expected: &[(u64, ContentDigest)]) -> Result<RepoMd> {
+    let path = REPOMD_SUFFIX.to_string();
+    let mut data = vec![];
+    mirror
+        .get_path(path.clone())
+        .await?
+        .read_to_end(&mut data)
+        .await
+        .map_err(|e| RpmRepositoryError::IoPath(path, e))?;
+
+    let matches = |(size, digest): &(u64, ContentDigest)| {
+        let mut hasher = digest.new_hasher();
+        hasher.update(&data);
+
+        *size == data.len() as u64 && hasher.finalize().as_ref() == digest.digest_bytes()
+    };
+
+    if !expected.is_empty() && !expected.iter().any(matches) {
+        return Err(RpmRepositoryError::Mirror(format!(
+            "repomd.xml from {} doesn't match metalink",
+            mirror.url()?
+        )));
+    }
+
+    RepoMd::from_reader(std::io::Cursor::new(data))
+}
+
+/// A [RepositoryRootReader] failing over between mirrors of a repository.
+///
+/// Paths are fetched from the first mirror able to serve them. Failures after content
+/// starts streaming are not retried.
+pub struct MirrorRepositoryClient {
+    mirrors: Arc<Vec<Mirror>>,
+    repomd_digests: Vec<(u64, ContentDigest)>,
+}
+
+impl MirrorRepositoryClient {
+    /// Construct an instance from readers of mirrors, in order of preference.
+    pub fn new(mirrors: impl IntoIterator<Item = Mirror>) -> Self {
+        Self {
+            mirrors: Arc::new(mirrors.into_iter().collect()),
+            repomd_digests: vec![],
+        }
+    }
+
+    /// Construct an instance from base URLs of HTTP mirrors, in order of preference.
+    pub fn from_urls(urls: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Self> {
+        let client = reqwest::ClientBuilder::new()
+            .user_agent(crate::http::USER_AGENT)
+            .build()?;
+
+        Ok(Self::new(
+            urls.into_iter()
+                .map(|url| {
+                    Ok(Box::new(HttpRepositoryClient::new_client(
+                        client.clone(),
+                        url.as_ref(),
+                    )?) as Mirror)
+                })
+                .collect::<Result<Vec<_>>>()?,
+        ))
+    }
+
+    /// Construct an instance from a metalink.
+    ///
+    /// `repomd.xml` files not matching a digest in the metalink are rejected.
+    pub fn from_metalink(metalink: &Metalink) -> Result<Self> {
+        let mut res = Self::from_urls(metalink.base_urls())?;
+        res.repomd_digests = metalink.repomd_digests()?;
+
+        Ok(res)
+    }
+
+    /// Construct an instance for a repository defined in a `.repo` file.
+    ///
+    /// Like DNF, `baseurl` is preferred over `metalink`, which is preferred over
+    /// `mirrorlist`. Mirror lists holding a metalink are recognized.
+    pub async fn from_repo_definition(repo: &RepoDefinition, vars: &RepoVariables) -> Result<Self> {
+        let base_urls = repo.base_urls(vars);
+        if !base_urls.is_empty() {
+            return Self::from_urls(base_urls);
+        }
+
+        let client = reqwest::ClientBuilder::new()
+            .user_agent(crate::http::USER_AGENT)
+            .build()?;
+
+        if let Some(url) = repo.metalink(vars) {
+            Self::from_metalink(&fetch_metalink(&client, &url).await?)
+        } else if let Some(url) = repo.mirrorlist(vars) {
+            let text = fetch_text(&client, &url).await?;
+
+            if text.contains("<metalink") {
+                Self::from_metalink(&Metalink::from_xml(&text)?)
+            } else {
+                Self::from_urls(parse_mirrorlist(&text))
+            }
+        } else {
+            Err(RpmRepositoryError::RepoFile(format!(
+                "repository {} has no baseurl, metalink, or mirrorlist",
+                repo.id
+            )))
+        }
+    }
+}
+
+impl DataResolver for MirrorRepositoryClient {
+    #[allow(clippy::type_complexity)]
+    fn get_path(
+        &self,
+        path: String,
+    ) -> Pin<Box<dyn Future<Output = Result<Pin<Box<dyn AsyncRead + Send>>>> + Send + '_>> {
+        Box::pin(get_path_failover(&self.mirrors, 0, path))
+    }
+}
+
+impl RepositoryRootReader for MirrorRepositoryClient {
+    fn url(&self) -> Result<Url> {
+        self.mirrors
+            .first()
+            .ok_or_else(|| RpmRepositoryError::Mirror("no mirrors defined".into()))?
+            .url()
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn metadata_reader(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<Box<dyn MetadataReader>>> + Send + '_>> {
+        async fn run(slf: &MirrorRepositoryClient) -> Result<Box<dyn MetadataReader>> {
+            let mut last_error = None;
+
+            for (index, mirror) in slf.mirrors.iter().enumerate() {
+                match fetch_repomd(mirror, &slf.repomd_digests).await {
+                    Ok(repomd) => {
+                        return Ok(Box::new(MirrorMetadataClient {
+                            mirrors: slf.mirrors.clone(),
+                            index,
+                            relative_path: "repodata".to_string(),
+                            repomd,
+                        }));
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+
+            Err(last_error
+                .unwrap_or_else(|| RpmRepositoryError::Mirror("no mirrors defined".into())))
+        }
+
+        Box::pin(run(self))
+    }
+}
+
+/// Mirror failover client bound to a parsed `repomd.xml` file.
+///
+/// Paths are fetched from the mirror that served `repomd.xml` first.
+pub struct MirrorMetadataClient {
+    mirrors: Arc<Vec<Mirror>>,
+    index: usize,
+    relative_path: String,
+    repomd: RepoMd,
+}
+
+impl DataResolver for MirrorMetadataClient {
+    #[allow(clippy::type_complexity)]
+    fn get_path(
+        &self,
+        path: String,
+    ) -> Pin<Box<dyn Future<Output = Result<Pin<Box<dyn AsyncRead + Send>>>> + Send + '_>> {
+        Box::pin(get_path_failover(&self.mirrors, self.index, path))
+    }
+}
+
+impl MetadataReader for MirrorMetadataClient {
+    fn url(&self) -> Result<Url> {
+        Ok(self.mirrors[self.index].url()?.join(&self.relative_path)?)
+    }
+
+    fn root_relative_path(&self) -> &str {
+        &self.relative_path
+    }
+
+    fn repomd(&self) -> &RepoMd {
+        &self.repomd
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::filesystem::FilesystemRepositoryReader};
+
+    const METALINK_XML: &str = include_str!("testdata/metalink.xml");
+    const FEDORA_35_REPOMD_XML: &str = include_str!("testdata/fedora-35-repodata.xml");
+
+    #[test]
+    fn parse_metalink() -> Result<()> {
+        let metalink = Metalink::from_xml(METALINK_XML)?;
+
+        assert_eq!(
+            metalink.base_urls(),
+            vec![
+                "https://ftp.example.de/fedora/linux/updates/41/Everything/x86_64/",
+                "https://mirror.example.com/fedora/linux/updates/41/Everything/x86_64/",
+                "http://mirror2.example.com/pub/fedora/updates/41/Everything/x86_64/",
+            ]
+        );
+
+        let digests = metalink.repomd_digests()?;
+        assert_eq!(digests.len(), 2);
+        assert_eq!(digests[0].0, 6237);
+        assert_eq!(digests[1].0, 6212);
+
+        assert_eq!(
+            parse_mirrorlist("# repo = fedora\nhttp://a.example/\n\n  http://b.example/  \n"),
+            vec!["http://a.example/", "http://b.example/"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failover() -> Result<()> {
+        let missing = tempfile::tempdir()?;
+        let stale = tempfile::tempdir()?;
+        let current = tempfile::tempdir()?;
+
+        std::fs::create_dir(stale.path().join("repodata"))?;
+        std::fs::write(
+            stale.path().join(REPOMD_SUFFIX),
+            FEDORA_35_REPOMD_XML.replace("1635225124", "1635225125"),
+        )?;
+        std::fs::create_dir(current.path().join("repodata"))?;
+        std::fs::write(current.path().join(REPOMD_SUFFIX), FEDORA_35_REPOMD_XML)?;
+        std::fs::write(current.path().join("file"), b"content")?;
+
+        let mut client = MirrorRepositoryClient::new(
+            [missing.path(), stale.path(), current.path()]
+                .into_iter()
+                .map(|path| Box::new(FilesystemRepositoryReader::new(path)) as Mirror),
+        );
+
+        let mut data = vec![];
+        client
+            .get_path("file".into())
+            .await?
+            .read_to_end(&mut data)
+            .await?;
+        assert_eq!(data, b"content");
+
+        // Without digests, the first mirror having repomd.xml is used.
+        client.metadata_reader().await?;
+
+        client.repomd_digests = Metalink::from_xml(METALINK_XML)?.repomd_digests()?;
+        let metadata = client.metadata_reader().await?;
+        assert_eq!(
+            metadata.url()?,
+            Url::from_directory_path(current.path())
+                .unwrap()
+                .join("repodata")?
+        );
+
+        client.mirrors = Arc::new(vec![]);
+        assert!(client.metadata_reader().await.is_err());
+
+        Ok(())
+    }
+}
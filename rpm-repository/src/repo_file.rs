@@ -0,0 +1,362 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! `.repo` file parsing.
+
+DNF and YUM define repositories in INI files under `/etc/yum.repos.d`. Each section
+defines a repository, keyed by its ID. Option values can reference variables such as
+`$releasever` and `$basearch`, which [RepoVariables] substitutes.
+
+[RepoDefinition::http_client()] constructs a client for a defined repository, allowing
+real-world repository configs to be used directly. Repositories defined by a mirror
+list or metalink are accessed via [crate::mirrors::MirrorRepositoryClient].
+*/
+
+use {
+    crate::{
+        error::{Result, RpmRepositoryError},
+        http::HttpRepositoryClient,
+    },
+    std::collections::BTreeMap,
+};
+
+/// Resolve the base architecture of a machine architecture, like DNF's `$basearch`.
+///
+/// e.g. `i686` resolves to `i386` and `armv7hl` to `armhfp`. Unknown architectures
+/// resolve to themselves.
+pub fn basearch(arch: &str) -> &str {
+    match arch {
+        "amd64" | "ia32e" | "x86_64" => "x86_64",
+        "athlon" | "geode" | "i386" | "i486" | "i586" | "i686" => "i386",
+        "armv7hl" | "armv7hnl" | "armv8hl" => "armhfp",
+        _ if arch.starts_with("armv") => "arm",
+        _ => arch,
+    }
+}
+
+/// Variables substituted in `.repo` option values.
+///
+/// References take the form `$name` or `${name}`. References to undefined variables are
+/// left as-is.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RepoVariables {
+    values: BTreeMap<String, String>,
+}
+
+impl RepoVariables {
+    /// Construct an instance defining the standard variables.
+    ///
+    /// `$releasever` (and `$releasever_major` and `$releasever_minor`), `$arch`, and
+    /// `$basearch` are defined.
+    pub fn new(releasever: &str, arch: &str) -> Self {
+        let mut res = Self::default();
+        res.set_releasever(releasever);
+        res.set("arch", arch);
+        res.set("basearch", basearch(arch));
+
+        res
+    }
+
+    /// Define a variable.
+    pub fn set(&mut self, name: impl ToString, value: impl ToString) {
+        self.values.insert(name.to_string(), value.to_string());
+    }
+
+    /// Define `$releasever`, `$releasever_major`, and `$releasever_minor`.
+    pub fn set_releasever(&mut self, value: &str) {
+        let (major, minor) = value.split_once('.').unwrap_or((value, ""));
+
+        self.set("releasever", value);
+        self.set("releasever_major", major);
+        self.set("releasever_minor", minor);
+    }
+
+    /// Obtain the value of a variable.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|x| x.as_str())
+    }
+
+    /// Substitute variable references in a string.
+    pub fn substitute(&self, s: &str) -> String {
+        let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+        let mut res = String::with_capacity(s.len());
+        let mut remaining = s;
+
+        while let Some(pos) = remaining.find('$') {
+            res.push_str(&remaining[..pos]);
+            let reference = &remaining[pos..];
+
+            let (name, len) = if let Some(braced) = reference.strip_prefix("${") {
+                match braced.find('}') {
+                    Some(end) => (&braced[..end], end + 3),
+                    None => ("", 1),
+                }
+            } else {
+                let end = reference[1..]
+                    .find(|c| !is_name_char(c))
+                    .unwrap_or(reference.len() - 1);
+                (&reference[1..end + 1], end + 1)
+            };
+
+            match self.get(name) {
+                Some(value) => res.push_str(value),
+                None => res.push_str(&reference[..len]),
+            }
+
+            remaining = &reference[len..];
+        }
+
+        res.push_str(remaining);
+
+        res
+    }
+}
+
+/// Parse a boolean option value.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "yes" | "true" | "on" => Some(true),
+        "0" | "no" | "false" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// A repository defined by a section of a `.repo` file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RepoDefinition {
+    /// The repository ID. This is the name of the section.
+    pub id: String,
+
+    /// Options in this section.
+    ///
+    /// Values spanning multiple lines have lines joined by newlines. Variables are not
+    /// substituted.
+    pub options: BTreeMap<String, String>,
+}
+
+impl RepoDefinition {
+    /// Obtain the raw value of an option.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|x| x.as_str())
+    }
+
+    /// Obtain the value of an option holding a list of values.
+    ///
+    /// Values are separated by whitespace or commas.
+    pub fn option_list(&self, name: &str) -> Vec<&str> {
+        self.option(name)
+            .map(|value| {
+                value
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|x| !x.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The human readable name of the repository.
+    pub fn name(&self) -> Option<&str> {
+        self.option("name")
+    }
+
+    /// Whether the repository is enabled.
+    ///
+    /// Repositories are enabled unless `enabled` is false.
+    pub fn enabled(&self) -> bool {
+        self.option("enabled").and_then(parse_bool).unwrap_or(true)
+    }
+
+    /// Whether packages must have valid PGP signatures.
+    pub fn gpgcheck(&self) -> bool {
+        self.option("gpgcheck")
+            .and_then(parse_bool)
+            .unwrap_or(false)
+    }
+
+    /// URLs of mirrors of the repository, in order of preference.
+    pub fn base_urls(&self, vars: &RepoVariables) -> Vec<String> {
+        self.option_list("baseurl")
+            .into_iter()
+            .map(|x| vars.substitute(x))
+            .collect()
+    }
+
+    /// URL of a mirror list for the repository.
+    pub fn mirrorlist(&self, vars: &RepoVariables) -> Option<String> {
+        self.option("mirrorlist").map(|x| vars.substitute(x))
+    }
+
+    /// URL of a metalink document describing mirrors of the repository.
+    pub fn metalink(&self, vars: &RepoVariables) -> Option<String> {
+        self.option("metalink").map(|x| vars.substitute(x))
+    }
+
+    /// URLs of PGP keys signing packages in the repository.
+    pub fn gpgkeys(&self, vars: &RepoVariables) -> Vec<String> {
+        self.option_list("gpgkey")
+            .into_iter()
+            .map(|x| vars.substitute(x))
+            .collect()
+    }
+
+    /// Construct an HTTP client for the first base URL of the repository.
+    ///
+    /// Repositories only defining a mirror list or metalink are not supported. Use
+    /// [crate::mirrors::MirrorRepositoryClient::from_repo_definition()] for those.
+    pub fn http_client(&self, vars: &RepoVariables) -> Result<HttpRepositoryClient> {
+        let url = self.base_urls(vars).into_iter().next().ok_or_else(|| {
+            RpmRepositoryError::RepoFile(format!("repository {} has no baseurl", self.id))
+        })?;
+
+        HttpRepositoryClient::new(url.as_str())
+    }
+}
+
+/// A parsed `.repo` file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RepoFile {
+    /// Options in the `[main]` section.
+    ///
+    /// `dnf.conf` defines global options in this section.
+    pub main: BTreeMap<String, String>,
+
+    /// Repositories defined by this file, in order of appearance.
+    pub repos: Vec<RepoDefinition>,
+}
+
+impl RepoFile {
+    /// Construct an instance by parsing a string.
+    pub fn from_ini(s: &str) -> Result<Self> {
+        let mut res = Self::default();
+        // The current section and the last option in it, for continuation lines.
+        let mut section: Option<&mut BTreeMap<String, String>> = None;
+        let mut last_key: Option<String> = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let error = |message: &str| {
+                RpmRepositoryError::RepoFile(format!("line {}: {}", i + 1, message))
+            };
+
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with(char::is_whitespace) {
+                let value = section
+                    .as_mut()
+                    .zip(last_key.as_ref())
+                    .and_then(|(options, key)| options.get_mut(key))
+                    .ok_or_else(|| error("continuation line without an option"))?;
+
+                value.push('\n');
+                value.push_str(trimmed);
+            } else if let Some(id) = trimmed.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                let id = id.trim();
+                last_key = None;
+
+                section = Some(if id == "main" {
+                    &mut res.main
+                } else {
+                    res.repos.push(RepoDefinition {
+                        id: id.to_string(),
+                        options: BTreeMap::new(),
+                    });
+                    &mut res.repos.last_mut().expect("just pushed").options
+                });
+            } else if let Some((key, value)) = trimmed.split_once('=') {
+                let options = section
+                    .as_mut()
+                    .ok_or_else(|| error("option outside of a section"))?;
+
+                let key = key.trim().to_string();
+                options.insert(key.clone(), value.trim().to_string());
+                last_key = Some(key);
+            } else {
+                return Err(error("expected a section or option"));
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Find a repository by its ID.
+    pub fn repo(&self, id: &str) -> Option<&RepoDefinition> {
+        self.repos.iter().find(|repo| repo.id == id)
+    }
+
+    /// Iterate over enabled repositories.
+    pub fn enabled_repos(&self) -> impl Iterator<Item = &RepoDefinition> + '_ {
+        self.repos.iter().filter(|repo| repo.enabled())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FEDORA_REPO: &str = include_str!("testdata/fedora.repo");
+
+    #[test]
+    fn parse() -> Result<()> {
+        let file = RepoFile::from_ini(FEDORA_REPO)?;
+        assert_eq!(file.repos.len(), 3);
+        assert_eq!(
+            file.enabled_repos()
+                .map(|r| r.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["fedora"]
+        );
+
+        let vars = RepoVariables::new("41", "i686");
+
+        let fedora = file.repo("fedora").unwrap();
+        assert_eq!(fedora.name(), Some("Fedora $releasever - $basearch"));
+        assert!(fedora.gpgcheck());
+        assert!(fedora.base_urls(&vars).is_empty());
+        assert_eq!(
+            fedora.metalink(&vars).as_deref(),
+            Some("https://mirrors.fedoraproject.org/metalink?repo=fedora-41&arch=i386")
+        );
+        assert!(matches!(
+            fedora.http_client(&vars),
+            Err(RpmRepositoryError::RepoFile(_))
+        ));
+
+        let source = file.repo("fedora-source").unwrap();
+        assert_eq!(source.name(), Some("Fedora ${releasever} - Source"));
+        assert_eq!(
+            source.base_urls(&vars),
+            vec![
+                "https://dl.fedoraproject.org/pub/fedora/linux/releases/41/Everything/source/tree/",
+                "https://archives.fedoraproject.org/pub/archive/fedora/linux/releases/41/Everything/source/tree/",
+            ]
+        );
+        assert_eq!(
+            source.gpgkeys(&vars),
+            vec![
+                "file:///etc/pki/rpm-gpg/RPM-GPG-KEY-fedora-41-primary",
+                "file:///etc/pki/rpm-gpg/RPM-GPG-KEY-fedora-41-legacy",
+            ]
+        );
+
+        assert!(RepoFile::from_ini("baseurl=http://example.com/").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn substitute() {
+        let mut vars = RepoVariables::new("9.4", "armv7hl");
+        vars.set("contentdir", "centos");
+
+        assert_eq!(
+            vars.substitute("$contentdir/$releasever_major/${basearch}/$arch$unknown${x"),
+            "centos/9/armhfp/armv7hl$unknown${x"
+        );
+        assert_eq!(vars.substitute("$$releasever_minor$"), "$4$");
+    }
+}
@@ -0,0 +1,297 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! `.rpm` file reading.
+
+An `.rpm` file consists of a lead, a signature header, a main header, and a payload.
+The headers are parsed by the [crate::header] module. The payload is a compressed `cpio`
+archive holding the files installed by the package.
+
+[RpmReader] parses the headers and exposes the payload as a stream of entries. Entries
+are decompressed incrementally as they are consumed, so inspecting a single file doesn't
+require buffering the whole package.
+*/
+
+use {
+    crate::{
+        error::{Result, RpmRepositoryError},
+        header::{tag, RpmHeader},
+    },
+    std::io::Read,
+};
+
+type CpioReader<'a> = cpio::NewcReader<Box<dyn Read + 'a>>;
+
+/// Obtain a reader decompressing a payload.
+fn decompress_payload<'a>(compressor: &str, data: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+    match compressor {
+        "gzip" => Ok(Box::new(libflate::gzip::Decoder::new(data)?)),
+        "bzip2" => Ok(Box::new(bzip2::read::BzDecoder::new(data))),
+        "xz" => Ok(Box::new(xz2::read::XzDecoder::new(data))),
+        "lzma" => Ok(Box::new(xz2::read::XzDecoder::new_stream(
+            data,
+            xz2::stream::Stream::new_lzma_decoder(u64::MAX).map_err(std::io::Error::other)?,
+        ))),
+        "zstd" => Ok(Box::new(zstd::Decoder::new(data)?)),
+        _ => Err(RpmRepositoryError::RpmPayload(format!(
+            "unsupported compression: {}",
+            compressor
+        ))),
+    }
+}
+
+/// A reader of `.rpm` files.
+pub struct RpmReader<R: Read> {
+    header: RpmHeader,
+    reader: R,
+}
+
+impl<R: Read> RpmReader<R> {
+    /// Construct a new instance from a reader.
+    ///
+    /// The lead and headers are read and parsed. The payload isn't read.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let header = RpmHeader::from_reader(&mut reader)?;
+
+        Ok(Self { header, reader })
+    }
+
+    /// The parsed lead and headers.
+    pub fn header(&self) -> &RpmHeader {
+        &self.header
+    }
+
+    /// Consume self and obtain the lead and headers.
+    pub fn into_header(self) -> RpmHeader {
+        self.header
+    }
+
+    /// Obtain a reader of the decompressed `cpio` payload.
+    pub fn into_payload_reader<'a>(self) -> Result<Box<dyn Read + 'a>>
+    where
+        R: 'a,
+    {
+        let format = self
+            .header
+            .header
+            .string(tag::PAYLOADFORMAT)
+            .unwrap_or("cpio");
+        if format != "cpio" {
+            return Err(RpmRepositoryError::RpmPayload(format!(
+                "unsupported format: {}",
+                format
+            )));
+        }
+
+        // Packages predating the tag use gzip.
+        let compressor = self
+            .header
+            .header
+            .string(tag::PAYLOADCOMPRESSOR)
+            .unwrap_or("gzip");
+
+        decompress_payload(compressor, self.reader)
+    }
+
+    /// Obtain a reader of the files in the payload.
+    pub fn into_entries<'a>(self) -> Result<PayloadEntries<'a>>
+    where
+        R: 'a,
+    {
+        Ok(PayloadEntries {
+            state: EntriesState::Between(self.into_payload_reader()?),
+        })
+    }
+}
+
+enum EntriesState<'a> {
+    Between(Box<dyn Read + 'a>),
+    Entry(CpioReader<'a>),
+    Done,
+}
+
+/// Iterates over files in the payload of an `.rpm` file.
+pub struct PayloadEntries<'a> {
+    state: EntriesState<'a>,
+}
+
+impl<'a> PayloadEntries<'a> {
+    /// Obtain the next file in the payload.
+    ///
+    /// Content of the previous entry that wasn't read is skipped.
+    pub fn next_entry(&mut self) -> Option<Result<PayloadEntry<'_, 'a>>> {
+        let inner = match std::mem::replace(&mut self.state, EntriesState::Done) {
+            EntriesState::Between(inner) => inner,
+            EntriesState::Entry(reader) => match reader.finish() {
+                Ok(inner) => inner,
+                Err(e) => return Some(Err(e.into())),
+            },
+            EntriesState::Done => return None,
+        };
+
+        let reader = match cpio::NewcReader::new(inner) {
+            Ok(reader) => reader,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        if reader.entry().is_trailer() {
+            return None;
+        }
+
+        self.state = EntriesState::Entry(reader);
+
+        match &mut self.state {
+            EntriesState::Entry(reader) => Some(Ok(PayloadEntry { reader })),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// A file in the payload of an `.rpm` file.
+///
+/// Reading yields the file's content. For symlinks, this is the link target.
+pub struct PayloadEntry<'b, 'a> {
+    reader: &'b mut CpioReader<'a>,
+}
+
+impl<'b, 'a> PayloadEntry<'b, 'a> {
+    /// The name of the entry in the archive.
+    ///
+    /// e.g. `./usr/bin/zstd`.
+    pub fn name(&self) -> &str {
+        self.reader.entry().name()
+    }
+
+    /// The path the file is installed to.
+    ///
+    /// e.g. `/usr/bin/zstd`.
+    pub fn path(&self) -> String {
+        format!(
+            "/{}",
+            self.name().trim_start_matches('.').trim_start_matches('/')
+        )
+    }
+
+    /// The file's mode, including its type.
+    pub fn mode(&self) -> u32 {
+        self.reader.entry().mode()
+    }
+
+    /// The file's owner.
+    pub fn uid(&self) -> u32 {
+        self.reader.entry().uid()
+    }
+
+    /// The file's group.
+    pub fn gid(&self) -> u32 {
+        self.reader.entry().gid()
+    }
+
+    /// The file's modification time, in seconds since the UNIX epoch.
+    pub fn mtime(&self) -> u32 {
+        self.reader.entry().mtime()
+    }
+
+    /// The size of the file's content.
+    pub fn size(&self) -> u32 {
+        self.reader.entry().file_size()
+    }
+
+    /// Whether the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.mode() & 0o170000 == 0o040000
+    }
+
+    /// Whether the entry is a symlink.
+    pub fn is_symlink(&self) -> bool {
+        self.mode() & 0o170000 == 0o120000
+    }
+}
+
+impl<'b, 'a> Read for PayloadEntry<'b, 'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::header::{test_rpm_with_payload, test_strings},
+        std::io::Write,
+    };
+
+    #[test]
+    fn read_payload() -> Result<()> {
+        let mut cpio_data = vec![];
+        for (name, mode, content) in [
+            ("./usr/bin", 0o040755, b"".as_slice()),
+            ("./usr/bin/zstd", 0o100755, b"#!/bin/sh\n"),
+            ("./usr/bin/unzstd", 0o120777, b"zstd"),
+        ] {
+            let mut writer = cpio::newc::Builder::new(name)
+                .mode(mode)
+                .write(&mut cpio_data, content.len() as u32);
+            writer.write_all(content)?;
+            writer.finish()?;
+        }
+        cpio::newc::trailer(&mut cpio_data)?;
+
+        let mut encoder = libflate::gzip::Encoder::new(vec![])?;
+        encoder.write_all(&cpio_data)?;
+        let payload = encoder.finish().into_result()?;
+
+        let data = test_rpm_with_payload(
+            &[
+                (tag::NAME, 6, test_strings(&["zstd"]), 1),
+                (tag::PAYLOADFORMAT, 6, test_strings(&["cpio"]), 1),
+                (tag::PAYLOADCOMPRESSOR, 6, test_strings(&["gzip"]), 1),
+            ],
+            &payload,
+        );
+
+        let reader = RpmReader::new(std::io::Cursor::new(&data))?;
+        assert_eq!(reader.header().name(), Some("zstd"));
+
+        let mut entries = reader.into_entries()?;
+        let mut seen = vec![];
+        while let Some(entry) = entries.next_entry() {
+            let mut entry = entry?;
+
+            // Leave the content of some entries unread.
+            let mut content = String::new();
+            if !entry.is_symlink() {
+                entry.read_to_string(&mut content)?;
+            }
+
+            seen.push((entry.path(), entry.is_dir(), entry.is_symlink(), content));
+        }
+
+        assert_eq!(
+            seen,
+            vec![
+                ("/usr/bin".to_string(), true, false, "".to_string()),
+                (
+                    "/usr/bin/zstd".to_string(),
+                    false,
+                    false,
+                    "#!/bin/sh\n".to_string()
+                ),
+                ("/usr/bin/unzstd".to_string(), false, true, "".to_string()),
+            ]
+        );
+
+        let data = test_rpm_with_payload(
+            &[(tag::PAYLOADCOMPRESSOR, 6, test_strings(&["lzip"]), 1)],
+            &payload,
+        );
+        assert!(matches!(
+            RpmReader::new(std::io::Cursor::new(&data))?.into_entries(),
+            Err(RpmRepositoryError::RpmPayload(_))
+        ));
+
+        Ok(())
+    }
+}